@@ -0,0 +1,266 @@
+use glam::{Mat4, Quat, Vec3};
+use gltf::animation::{util::ReadOutputs, Interpolation};
+
+use crate::{GltfSkeleton, InstanceId, InstancePool, SkinnedMeshPool};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NodeTransform {
+    pub translation: Vec3,
+    pub rotation: Quat,
+    pub scale: Vec3,
+}
+
+impl NodeTransform {
+    pub fn matrix(&self) -> Mat4 {
+        Mat4::from_scale_rotation_translation(self.scale, self.rotation, self.translation)
+    }
+}
+
+enum ChannelValues {
+    Translation(Vec<Vec3>),
+    Rotation(Vec<Quat>),
+    Scale(Vec<Vec3>),
+}
+
+struct Channel {
+    node: usize,
+    times: Vec<f32>,
+    interpolation: Interpolation,
+    values: ChannelValues,
+}
+
+impl Channel {
+    // Key to the left of `time`, the next one and the blend factor between them.
+    fn key(&self, time: f32) -> (usize, usize, f32) {
+        let last = self.times.len() - 1;
+        let next = self.times.partition_point(|&t| t <= time);
+        if next == 0 {
+            return (0, 0, 0.);
+        }
+        if next > last {
+            return (last, last, 0.);
+        }
+        let (t0, t1) = (self.times[next - 1], self.times[next]);
+        let blend = match self.interpolation {
+            Interpolation::Step => 0.,
+            _ => (time - t0) / (t1 - t0).max(f32::EPSILON),
+        };
+        (next - 1, next, blend)
+    }
+
+    fn apply(&self, time: f32, node: &mut NodeTransform) {
+        if self.times.is_empty() {
+            return;
+        }
+        let (a, b, t) = self.key(time);
+        match &self.values {
+            ChannelValues::Translation(v) => node.translation = v[a].lerp(v[b], t),
+            ChannelValues::Rotation(v) => node.rotation = v[a].slerp(v[b], t),
+            ChannelValues::Scale(v) => node.scale = v[a].lerp(v[b], t),
+        }
+    }
+}
+
+/// Translation, rotation and scale keyframes of glTF nodes, morph targets are not supported.
+pub struct AnimationClip {
+    pub name: Option<String>,
+    /// Seconds, the time of the last keyframe.
+    pub duration: f32,
+    channels: Vec<Channel>,
+}
+
+impl AnimationClip {
+    pub(crate) fn from_gltf(
+        animation: &gltf::Animation<'_>,
+        buffers: &[gltf::buffer::Data],
+    ) -> Self {
+        let mut channels = vec![];
+        for channel in animation.channels() {
+            let reader = channel.reader(|buffer| Some(&buffers[buffer.index()]));
+            let (Some(inputs), Some(outputs)) = (reader.read_inputs(), reader.read_outputs())
+            else {
+                continue;
+            };
+            let interpolation = channel.sampler().interpolation();
+            // Cubic splines store in-tangent, value and out-tangent per key,
+            // sampling them linearly through the values is close enough.
+            let (skip, step) = match interpolation {
+                Interpolation::CubicSpline => (1, 3),
+                _ => (0, 1),
+            };
+            let values = match outputs {
+                ReadOutputs::Translations(v) => ChannelValues::Translation(
+                    v.skip(skip).step_by(step).map(Vec3::from_array).collect(),
+                ),
+                ReadOutputs::Rotations(v) => ChannelValues::Rotation(
+                    v.into_f32()
+                        .skip(skip)
+                        .step_by(step)
+                        .map(|q| Quat::from_array(q).normalize())
+                        .collect(),
+                ),
+                ReadOutputs::Scales(v) => {
+                    ChannelValues::Scale(v.skip(skip).step_by(step).map(Vec3::from_array).collect())
+                }
+                ReadOutputs::MorphTargetWeights(_) => continue,
+            };
+            channels.push(Channel {
+                node: channel.target().node().index(),
+                times: inputs.collect(),
+                interpolation,
+                values,
+            });
+        }
+
+        let duration = channels
+            .iter()
+            .filter_map(|channel| channel.times.last().copied())
+            .fold(0f32, f32::max);
+        Self {
+            name: animation.name().map(str::to_owned),
+            duration,
+            channels,
+        }
+    }
+
+    /// Overwrites the animated properties of `nodes` with their value at `time`,
+    /// clamped to the first and last keyframe.
+    pub fn sample(&self, time: f32, nodes: &mut [NodeTransform]) {
+        for channel in &self.channels {
+            if let Some(node) = nodes.get_mut(channel.node) {
+                channel.apply(time, node);
+            }
+        }
+    }
+
+    /// Nodes driven by a channel of this clip.
+    pub fn targets(&self) -> impl Iterator<Item = usize> + '_ {
+        self.channels.iter().map(|channel| channel.node)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AnimationId(pub u32);
+
+/// Instantiated glTF scene driven by the [`AnimationPlayer`].
+pub struct AnimatedScene {
+    pub skeleton: GltfSkeleton,
+    pub transform: Mat4,
+    /// Instances following a node, skinned ones follow their joints instead.
+    pub instances: Vec<(usize, InstanceId)>,
+    pub clip: Option<usize>,
+    /// Seconds into the clip.
+    pub time: f32,
+    pub speed: f32,
+    pub looping: bool,
+    pub playing: bool,
+    // Raised by `AnimationPlayer::play` so a stopped scene is posed once more.
+    dirty: bool,
+}
+
+impl AnimatedScene {
+    pub fn new(skeleton: GltfSkeleton, transform: Mat4) -> Self {
+        Self {
+            skeleton,
+            transform,
+            instances: vec![],
+            clip: None,
+            time: 0.,
+            speed: 1.,
+            looping: true,
+            playing: false,
+            dirty: true,
+        }
+    }
+
+    fn advance(&mut self, dt: f32) {
+        let Some(duration) = self
+            .clip
+            .and_then(|clip| self.skeleton.animations().get(clip))
+            .map(|clip| clip.duration)
+        else {
+            return;
+        };
+        self.time += dt * self.speed;
+        if self.looping {
+            self.time = self.time.rem_euclid(duration.max(f32::EPSILON));
+        } else if !(0. ..=duration).contains(&self.time) {
+            self.time = self.time.clamp(0., duration);
+            self.playing = false;
+        }
+    }
+}
+
+/// Plays glTF animations, moving the instances of animated nodes and posing skins.
+///
+/// Ticked by `App::update` before the example, so the skinning pass it records sees
+/// the current pose. Instances bound to nodes have to be dynamic.
+#[derive(Default)]
+pub struct AnimationPlayer {
+    scenes: Vec<AnimatedScene>,
+    last_time: Option<f64>,
+}
+
+impl AnimationPlayer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, scene: AnimatedScene) -> AnimationId {
+        self.scenes.push(scene);
+        AnimationId(self.scenes.len() as u32 - 1)
+    }
+
+    pub fn scene(&self, id: AnimationId) -> &AnimatedScene {
+        &self.scenes[id.0 as usize]
+    }
+
+    pub fn scene_mut(&mut self, id: AnimationId) -> &mut AnimatedScene {
+        &mut self.scenes[id.0 as usize]
+    }
+
+    /// Starts `clip` from the beginning, `None` returns to the rest pose.
+    pub fn play(&mut self, id: AnimationId, clip: Option<usize>) {
+        let scene = self.scene_mut(id);
+        scene.clip = clip;
+        scene.time = 0.;
+        scene.playing = clip.is_some();
+        scene.dirty = true;
+    }
+
+    pub fn set_playing(&mut self, id: AnimationId, playing: bool) {
+        self.scene_mut(id).playing = playing;
+    }
+
+    /// `true` while any scene is playing, the on-demand render mode keeps rendering then.
+    pub fn is_playing(&self) -> bool {
+        self.scenes.iter().any(|scene| scene.playing)
+    }
+
+    pub fn update(
+        &mut self,
+        total_time: f64,
+        instances: &mut InstancePool,
+        skinned: &mut SkinnedMeshPool,
+    ) {
+        let dt = total_time - self.last_time.unwrap_or(total_time);
+        self.last_time = Some(total_time);
+        for scene in &mut self.scenes {
+            if !scene.playing && !std::mem::take(&mut scene.dirty) {
+                continue;
+            }
+            if scene.playing {
+                scene.advance(dt as f32);
+            }
+            match scene.clip {
+                Some(clip) => scene.skeleton.sample(clip, scene.time),
+                None => scene.skeleton.reset(),
+            }
+            for &(node, instance) in &scene.instances {
+                let transform = scene.transform * scene.skeleton.global_transform(node);
+                instances.set_transform(instance, transform);
+            }
+            scene.skeleton.update(skinned);
+        }
+    }
+}
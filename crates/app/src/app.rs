@@ -1,12 +1,17 @@
-use std::{cell::RefCell, fmt::Display, sync::Arc, time::Duration};
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    fmt::Display,
+    path::PathBuf,
+    sync::{mpsc, Arc},
+    time::{Duration, Instant},
+};
 
-use color_eyre::{eyre::ContextCompat, Result};
-use egui_wgpu::renderer::ScreenDescriptor;
+use color_eyre::{eyre::WrapErr, Result};
 use glam::{Mat4, Vec2, Vec3};
 
 use pollster::FutureExt;
 use wgpu::FilterMode;
-use wgpu_profiler::{GpuProfiler, GpuTimerScopeResult};
 use winit::{dpi::PhysicalSize, window::Window};
 
 use components::{
@@ -16,28 +21,80 @@ use components::{
     },
     world::{Read, Write},
     Blitter, DrawIndexedIndirect, Gpu, ImageDimentions, RecordEvent, Recorder, ResizableBuffer,
-    Watcher, World, {CameraUniform, CameraUniformBinding},
+    SyncKind, Watcher, World, FRAME_SLOTS, {CameraUniform, CameraUniformBinding},
 };
 
+pub mod adapter;
+pub mod asset_browser;
+pub mod audio;
+pub mod crash;
+pub mod debug_draw;
+pub mod diagnostics;
+pub mod editor;
+pub mod frame_arena;
+pub mod frame_hash;
 pub mod gbuffer;
 pub mod global_ubo;
+mod hud;
+pub mod imposters;
+pub mod live_params;
+pub mod occlusion;
+pub mod output;
 pub mod pipeline;
+pub mod pre_pass;
+mod profiler;
+pub mod profiler_overlay;
+pub mod redraw;
+pub mod rng;
+pub mod sample_assets;
+pub mod scene_bindings;
 mod screenshot;
+pub mod settings;
 pub mod state;
+pub mod texture_lod;
+pub mod trace;
+mod ui;
 mod view_target;
 
 pub use view_target::ViewTarget;
 
 use self::{
+    asset_browser::AssetBrowser,
+    audio::{AudioAnalyzer, AudioBinding},
+    crash::CrashReporter,
+    debug_draw::DebugDraw,
+    frame_arena::FrameArena,
+    frame_hash::FrameHasher,
     gbuffer::GBuffer,
     global_ubo::GlobalsBindGroup,
+    hud::Hud,
+    imposters::{ImposterId, Imposters},
+    live_params::LiveParams,
+    occlusion::SoftwareOcclusion,
+    output::{Output, OutputSink, OutputStream},
     pipeline::PipelineArena,
+    pre_pass::PrePasses,
+    profiler::{GpuProfiler, GpuTimerScopeResult, OwningScope},
+    profiler_overlay::ProfilerOverlay,
+    redraw::Redraw,
+    rng::SceneRng,
+    scene_bindings::SceneBindings,
     screenshot::ScreenshotCtx,
     state::{AppState, StateAction},
+    texture_lod::TextureLod,
+    ui::Ui,
 };
 use crate::{
-    AreaLight, Example, Instance, InstancePool, LightPool, MaterialPool, TexturePool,
-    {MeshId, MeshPool, MeshRef},
+    animation::AnimationPlayer,
+    pass::{
+        picking::{PickHandle, Picking, PickingResource},
+        postprocess::DebugView,
+        Pass,
+    },
+    plugin::PluginHost,
+    AreaLight, Example, ImportCanceled, ImportProgress, Instance, InstanceId, InstancePool,
+    LightId, LightPool, MaterialId, MaterialPool, SceneGraph, SkinnedMeshPool, Streaming,
+    StreamingSettings, TexturePool, Timeline, {MeshId, MeshPool, MeshRef},
 };
 
 pub const DEFAULT_SAMPLER_DESC: wgpu::SamplerDescriptor<'static> = wgpu::SamplerDescriptor {
@@ -55,14 +112,18 @@ pub const DEFAULT_SAMPLER_DESC: wgpu::SamplerDescriptor<'static> = wgpu::Sampler
     border_color: None,
 };
 
+type CaptureCallback = Box<dyn FnOnce(Arc<wgpu::Buffer>, ImageDimentions) + Send>;
+
 pub struct App {
     pub gpu: Arc<Gpu>,
-    pub surface: wgpu::Surface,
+    pub surface: Option<wgpu::Surface>,
     pub surface_config: wgpu::SurfaceConfiguration,
     pub gbuffer: GBuffer,
     pub view_target: view_target::ViewTarget,
 
     global_uniform: global_ubo::Uniform,
+    // Fixed step the instance motions were last advanced at.
+    motion_step: u64,
 
     pub world: World,
 
@@ -70,41 +131,62 @@ pub struct App {
     draw_cmd_bind_group: wgpu::BindGroup,
 
     pub blitter: Blitter,
+    hud: Hud,
 
     recorder: Recorder,
     screenshot_ctx: ScreenshotCtx,
-    profiler: RefCell<wgpu_profiler::GpuProfiler>,
-
-    pub(crate) egui_context: egui::Context,
-    egui_renderer: egui_wgpu::Renderer,
-    pub(crate) egui_state: egui_winit::State,
+    capture_requests: Vec<CaptureCallback>,
+    ui_capture_requests: Vec<CaptureCallback>,
+    // F3 also saves the frame with the ui on top.
+    screenshot_ui: bool,
+    output: Option<OutputStream>,
+    frame_hasher: Option<FrameHasher>,
+    profiler: RefCell<GpuProfiler>,
+    crash: CrashReporter,
+    // Unfocused or minimized window, recording and profiling are paused.
+    background: bool,
+
+    pending_command_buffers: Vec<wgpu::CommandBuffer>,
+    frames_in_flight: usize,
+    submissions: VecDeque<wgpu::SubmissionIndex>,
+
+    pub(crate) ui: Option<Ui>,
+    plugins: PluginHost,
+    shader_changes: Option<mpsc::Receiver<PathBuf>>,
 }
 
+// Each frame in flight reads its own uniform slot while the next one is written.
+const _: () = assert!(FRAME_SLOTS > App::MAX_FRAMES_IN_FLIGHT);
+
 impl App {
     pub const SAMPLE_COUNT: u32 = 1;
-
-    // TODO: call resize right after
-    pub fn new(window: &Window, file_watcher: Watcher) -> Result<Self> {
-        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
-            backends: wgpu::Backends::VULKAN,
+    pub const MAX_FRAMES_IN_FLIGHT: usize = 3;
+    pub const BACKENDS: wgpu::Backends = wgpu::Backends::VULKAN;
+    /// Longest sync points of the last frame listed in the hud.
+    const HUD_SYNC_POINTS: usize = 3;
+
+    fn create_instance() -> wgpu::Instance {
+        wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: Self::BACKENDS,
             dx12_shader_compiler: wgpu::Dx12Compiler::Fxc,
-        });
-
-        let surface = unsafe { instance.create_surface(&window) }?;
+        })
+    }
 
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::HighPerformance,
-                force_fallback_adapter: false,
-                compatible_surface: Some(&surface),
-            })
-            .block_on()
-            .context("Failed to create Adapter")?;
+    /// Adapters [`App::new`] can pick from with [`adapter::AdapterSelection`].
+    pub fn adapters() -> Vec<wgpu::AdapterInfo> {
+        adapter::adapters(&Self::create_instance())
+    }
 
+    // TODO: call resize right after
+    pub fn new(window: &Window, file_watcher: Watcher) -> Result<Self> {
+        let (surface, adapter) = Self::create_adapter(window)?;
         let limits = adapter.limits();
-        let mut features = adapter.features();
-        features.remove(wgpu::Features::MAPPABLE_PRIMARY_BUFFERS);
+        let features = Self::device_features(&adapter);
 
+        let api_trace = trace::ApiTrace::from_env()?;
+        if let Some(api_trace) = &api_trace {
+            log::info!("Tracing wgpu calls into {}", api_trace.dir().display());
+        }
         let (device, queue) = adapter
             .request_device(
                 &wgpu::DeviceDescriptor {
@@ -112,7 +194,7 @@ impl App {
                     features,
                     limits,
                 },
-                None,
+                api_trace.as_ref().map(|trace| trace.dir()),
             )
             .block_on()?;
         let gpu = Arc::new(Gpu::new(adapter, device, queue));
@@ -129,6 +211,73 @@ impl App {
             view_formats: vec![],
         };
         surface.configure(gpu.device(), &surface_config);
+
+        let ui = Ui::new(&gpu, window, ViewTarget::FORMAT, Self::SAMPLE_COUNT);
+        let mut app = Self::from_parts(gpu, Some(surface), surface_config, Some(ui), file_watcher);
+        if let Some(api_trace) = api_trace {
+            app.world.insert(api_trace);
+        }
+        Ok(app)
+    }
+
+    /// The surface of `window` and the adapter [`Self::new`] builds the device on.
+    pub fn create_adapter(window: &Window) -> Result<(wgpu::Surface, wgpu::Adapter)> {
+        let instance = Self::create_instance();
+
+        let surface = unsafe { instance.create_surface(&window) }?;
+
+        let adapter = adapter::AdapterSelection::from_env()
+            .select(&instance, Some(&surface))
+            .wrap_err("Failed to create Adapter")?;
+        Ok((surface, adapter))
+    }
+
+    /// Everything the adapter supports is requested.
+    pub fn device_features(adapter: &wgpu::Adapter) -> wgpu::Features {
+        let mut features = adapter.features();
+        features.remove(wgpu::Features::MAPPABLE_PRIMARY_BUFFERS);
+        features
+    }
+
+    /// Creates the renderer on top of a device owned by the host application.
+    ///
+    /// There is no surface and no ui, frames are drawn with [`App::render_to_texture`]
+    /// into textures of `target_format` provided by the host.
+    pub fn from_existing(
+        device: wgpu::Device,
+        queue: wgpu::Queue,
+        adapter: wgpu::Adapter,
+        target_format: wgpu::TextureFormat,
+    ) -> Result<Self> {
+        let gpu = Arc::new(Gpu::new(adapter, device, queue));
+        let surface_config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format: target_format,
+            width: 1,
+            height: 1,
+            present_mode: wgpu::PresentMode::Fifo,
+            alpha_mode: wgpu::CompositeAlphaMode::Opaque,
+            view_formats: vec![],
+        };
+
+        let (tx, rx) = mpsc::channel();
+        let file_watcher = Watcher::with_callback(move |path| {
+            let _ = tx.send(path);
+        })?;
+
+        let mut app = Self::from_parts(gpu, None, surface_config, None, file_watcher);
+        app.shader_changes = Some(rx);
+        Ok(app)
+    }
+
+    fn from_parts(
+        gpu: Arc<Gpu>,
+        surface: Option<wgpu::Surface>,
+        surface_config: wgpu::SurfaceConfiguration,
+        ui: Option<Ui>,
+        file_watcher: Watcher,
+    ) -> Self {
+        let (width, height) = (surface_config.width, surface_config.height);
         let gbuffer = GBuffer::new(&gpu, surface_config.width, surface_config.height);
 
         let mut world = {
@@ -140,8 +289,29 @@ impl App {
             world.insert(MeshPool::new(gpu.clone()));
             world.insert(MaterialPool::new(gpu.clone()));
             world.insert(InstancePool::new(gpu.clone()));
+            world.insert(SkinnedMeshPool::new(gpu.clone()));
+            world.insert(Imposters::new(gpu.clone()));
+            world.insert(AssetBrowser::new(gpu.clone()));
             world.insert(LightPool::new(gpu.clone()));
+            let scene_bindings = SceneBindings::new(&world);
+            world.insert(scene_bindings);
+            world.insert(PrePasses::new());
+            world.insert(Picking::new(gpu.device()));
+            world.insert(DebugView::default());
+            world.insert(ProfilerOverlay::new());
+            world.insert(FrameArena::new(gpu.clone()));
+            world.insert(LiveParams::new());
+            world.insert(Timeline::new());
+            world.insert(AnimationPlayer::new());
+            world.insert(SceneGraph::new());
+            world.insert(Streaming::new(StreamingSettings::default()));
+            world.insert(SceneRng::from_env());
+            world.insert(Redraw::from_env());
+            world.insert(TextureLod::new());
+            world.insert(AudioAnalyzer::new());
+            world.insert(AudioBinding::new(gpu.device()));
             world.insert(GlobalsBindGroup::new(&gpu, &globals, &camera));
+            world.insert(DebugDraw::new(gpu.clone(), &camera));
             world.insert(globals);
             world.insert(camera);
             world.insert(CameraUniform::default());
@@ -154,15 +324,18 @@ impl App {
             world.insert(StorageWriteBindGroupLayout::<DrawIndexedIndirect>::new(
                 &gpu,
             ));
+            let occlusion = SoftwareOcclusion::new(
+                gpu.clone(),
+                &world.unwrap::<StorageReadBindGroupLayout<u32>>(),
+            );
+            world.insert(occlusion);
             world
         };
 
         let view_target = view_target::ViewTarget::new(&world, width, height);
 
-        let global_uniform = global_ubo::Uniform {
-            resolution: [surface_config.width as f32, surface_config.height as f32],
-            ..Default::default()
-        };
+        let mut global_uniform = global_ubo::Uniform::default();
+        global_uniform.resolution = [surface_config.width as f32, surface_config.height as f32];
 
         let draw_cmd_buffer = ResizableBuffer::new(
             gpu.device(),
@@ -177,67 +350,74 @@ impl App {
             4,
         ));
 
-        let egui_renderer = egui_wgpu::renderer::Renderer::new(
-            gpu.device(),
-            ViewTarget::FORMAT,
-            None,
-            Self::SAMPLE_COUNT,
-        );
-        let egui_context = egui::Context::default();
-        {
-            let mut arc_style = egui_context.style();
-            let style = Arc::make_mut(&mut arc_style);
-            style.visuals.window_shadow = egui::epaint::Shadow::NONE;
-            egui_context.set_style(style.clone());
-        }
-        let egui_state = egui_winit::State::new(window);
-
-        Ok(Self {
+        let app = Self {
             surface,
             surface_config,
             gbuffer,
             view_target,
 
             global_uniform,
+            motion_step: 0,
 
             draw_cmd_buffer,
             draw_cmd_bind_group,
 
             profiler,
+            crash: CrashReporter::new(),
             blitter: Blitter::new(&world),
+            hud: Hud::new(gpu.device()),
             screenshot_ctx: ScreenshotCtx::new(&gpu, width, height),
+            capture_requests: vec![],
+            ui_capture_requests: vec![],
+            screenshot_ui: false,
+            output: None,
+            frame_hasher: None,
+            background: false,
             recorder: Recorder::new(),
 
+            pending_command_buffers: vec![],
+            frames_in_flight: 2,
+            submissions: VecDeque::with_capacity(Self::MAX_FRAMES_IN_FLIGHT + 1),
+
             world,
             gpu,
 
-            egui_renderer,
-            egui_context,
-            egui_state,
-        })
+            ui,
+            plugins: PluginHost::new(),
+            shader_changes: None,
+        };
+        app.crash.context().renderer = app.get_info().to_string();
+        app
     }
 
+    /// Adds the light together with an emissive panel, which stays when the light is
+    /// disabled or removed.
     pub fn add_area_light(
         &mut self,
         color: Vec3,
         intensity: f32,
         wh: Vec2,
         transform: Mat4,
-    ) -> Result<()> {
-        self.world
+    ) -> Result<LightId> {
+        let id = self
+            .world
             .get_mut::<LightPool>()?
-            .add_area_light(&[AreaLight::from_transform(color, intensity, wh, transform)]);
+            .add_area_light(&[AreaLight::from_transform(color, intensity, wh, transform)])?[0];
         self.get_instance_pool_mut().add(&[Instance::new(
             transform * Mat4::from_scale((wh / 2.).extend(1.)),
             MeshPool::VERTICAL_PLANE_MESH,
             MaterialPool::LIGHT_MATERIAL,
-        )]);
-        Ok(())
+        )])?;
+        Ok(id)
     }
 
     pub fn setup_scene(&mut self, example: &mut impl Example) -> Result<()> {
         example.setup_scene(self)?;
+        self.refresh_scene_buffers()
+    }
 
+    /// Resizes the draw commands and rebuilds the TLAS after the instance pool changed.
+    fn refresh_scene_buffers(&mut self) -> Result<()> {
         let mut encoder = self.device().create_command_encoder(&Default::default());
         self.draw_cmd_buffer.set_len(
             self.gpu.device(),
@@ -248,57 +428,124 @@ impl App {
         self.draw_cmd_bind_group = self
             .draw_cmd_buffer
             .create_storage_write_bind_group(&mut self.world);
+        let instance_count = self.get_instance_pool().count();
+        self.world.get_mut::<Imposters>()?.reserve(instance_count);
 
         let mut mesh_pool = self.get_mesh_pool_mut();
         mesh_pool.generate_tlas(&self.get_instance_pool().instances_data);
 
-        mesh_pool.trace_bind_group = {
-            let instance_pool = self.get_instance_pool();
-            self.device().create_bind_group(&wgpu::BindGroupDescriptor {
-                label: Some("Trace BG"),
-                layout: &mesh_pool.trace_bind_group_layout,
-                entries: &[
-                    wgpu::BindGroupEntry {
-                        binding: 0,
-                        resource: mesh_pool.tlas_nodes.as_tight_binding(),
-                    },
-                    wgpu::BindGroupEntry {
-                        binding: 1,
-                        resource: instance_pool.instances.as_tight_binding(),
-                    },
-                    wgpu::BindGroupEntry {
-                        binding: 2,
-                        resource: mesh_pool.mesh_info.as_entire_binding(),
-                    },
-                    wgpu::BindGroupEntry {
-                        binding: 3,
-                        resource: mesh_pool.bvh_nodes.as_entire_binding(),
-                    },
-                    wgpu::BindGroupEntry {
-                        binding: 4,
-                        resource: mesh_pool.vertices.as_entire_binding(),
-                    },
-                    wgpu::BindGroupEntry {
-                        binding: 5,
-                        resource: mesh_pool.indices.as_entire_binding(),
-                    },
-                ],
-            })
-        };
+        mesh_pool.trace_bind_group =
+            Self::create_trace_bind_group(self.device(), &mesh_pool, &self.get_instance_pool());
+
+        self.crash.context().scene = format!(
+            "Meshes: {}\nInstances: {}\nMaterials: {}\n",
+            mesh_pool.count(),
+            self.get_instance_pool().count(),
+            self.get_material_pool().num_materials(),
+        );
 
         Ok(())
     }
 
+    fn create_trace_bind_group(
+        device: &wgpu::Device,
+        mesh_pool: &MeshPool,
+        instance_pool: &InstancePool,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Trace BG"),
+            layout: &mesh_pool.trace_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: mesh_pool.tlas_nodes.as_tight_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: instance_pool.instances.as_tight_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: mesh_pool.mesh_info.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: mesh_pool.bvh_nodes.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: mesh_pool.vertices.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 5,
+                    resource: mesh_pool.indices.as_entire_binding(),
+                },
+            ],
+        })
+    }
+
     pub fn render(
         &mut self,
         window: &Window,
         app_state: &AppState,
         draw: impl FnOnce(RenderContext),
     ) -> Result<(), wgpu::SurfaceError> {
-        let mut profiler = self.profiler.borrow_mut();
-        let target = self.surface.get_current_texture()?;
+        let target = self
+            .surface
+            .as_ref()
+            .expect("App without a surface renders with `render_to_texture`")
+            .get_current_texture()?;
         let target_view = target.texture.create_view(&Default::default());
 
+        let submission = self.render_to_view(
+            Some(window),
+            app_state,
+            &target_view,
+            self.surface_config.format,
+            draw,
+        );
+        target.present();
+        self.limit_frames_in_flight(submission);
+
+        Ok(())
+    }
+
+    /// Renders a frame into a texture owned by the host application, see [`App::from_existing`].
+    ///
+    /// The texture needs `RENDER_ATTACHMENT` usage, internal targets follow its size.
+    pub fn render_to_texture(
+        &mut self,
+        target: &wgpu::Texture,
+        app_state: &AppState,
+        draw: impl FnOnce(RenderContext),
+    ) {
+        if let Some(shader_changes) = self.shader_changes.take() {
+            shader_changes
+                .try_iter()
+                .for_each(|path| self.handle_events(path));
+            self.shader_changes = Some(shader_changes);
+        }
+        self.resize(target.width(), target.height());
+
+        let target_view = target.create_view(&Default::default());
+        let submission = self.render_to_view(None, app_state, &target_view, target.format(), draw);
+        self.limit_frames_in_flight(submission);
+    }
+
+    fn render_to_view(
+        &mut self,
+        window: Option<&Window>,
+        app_state: &AppState,
+        target_view: &wgpu::TextureView,
+        target_format: wgpu::TextureFormat,
+        draw: impl FnOnce(RenderContext),
+    ) -> wgpu::SubmissionIndex {
+        let cpu_start = Instant::now();
+        if let Some(hasher) = &mut self.frame_hasher {
+            hasher.poll();
+        }
+        let mut profiler = self.profiler.borrow_mut();
+
         let mut encoder = self
             .device()
             .create_command_encoder(&wgpu::CommandEncoderDescriptor {
@@ -307,6 +554,16 @@ impl App {
 
         profiler.begin_scope("Main Render Scope ", &mut encoder, self.device());
 
+        self.world.unwrap_mut::<SceneBindings>().update(&self.world);
+        self.world.unwrap_mut::<PrePasses>().record(
+            &self.world,
+            &mut ProfilerCommandEncoder {
+                encoder: &mut encoder,
+                device: self.gpu.device(),
+                profiler: &mut profiler,
+            },
+        );
+
         let render_context = RenderContext {
             window,
             app_state,
@@ -324,37 +581,196 @@ impl App {
             draw_cmd_buffer: &self.draw_cmd_buffer,
             draw_cmd_bind_group: &self.draw_cmd_bind_group,
 
-            egui_context: &self.egui_context,
-            egui_renderer: &mut self.egui_renderer,
-            egui_state: &mut self.egui_state,
+            // The ui changes every frame and would break the hashes.
+            ui: match self.frame_hasher {
+                Some(_) => None,
+                None => self.ui.as_mut(),
+            },
         };
 
         draw(render_context);
 
+        if !self.plugins.is_empty() {
+            self.plugins.render(&mut RenderContext {
+                window,
+                app_state,
+                encoder: ProfilerCommandEncoder {
+                    encoder: &mut encoder,
+                    device: self.gpu.device(),
+                    profiler: &mut profiler,
+                },
+                view_target: &self.view_target,
+                gbuffer: &self.gbuffer,
+                world: &self.world,
+                gpu: &self.gpu,
+                width: self.surface_config.width,
+                height: self.surface_config.height,
+                draw_cmd_buffer: &self.draw_cmd_buffer,
+                draw_cmd_bind_group: &self.draw_cmd_bind_group,
+
+                ui: None,
+            });
+        }
+
+        // The G-buffer is complete once the example and plugins rendered.
+        self.world.unwrap::<Picking>().record(
+            &self.world,
+            &mut ProfilerCommandEncoder {
+                encoder: &mut encoder,
+                device: self.gpu.device(),
+                profiler: &mut profiler,
+            },
+            PickingResource {
+                gbuffer: &self.gbuffer,
+            },
+        );
+        self.world.unwrap_mut::<DebugDraw>().draw(
+            &self.world.unwrap::<CameraUniformBinding>(),
+            &mut encoder,
+            self.view_target.main_view(),
+            &self.gbuffer.depth,
+        );
+
+        if self.recorder.is_active() && self.recorder.ffmpeg_installed() && !self.background {
+            let tx = self.recorder.sender.clone();
+            self.capture_requests.push(Box::new(move |frame, _| {
+                let _ = tx.send(RecordEvent::Record(frame));
+            }));
+        }
+        if let Some(callback) = self.output.as_ref().and_then(|o| o.request_frame()) {
+            self.capture_requests.push(Box::new(callback));
+        }
+        let mut captures: Vec<_> = self
+            .capture_requests
+            .drain(..)
+            .map(|callback| {
+                self.screenshot_ctx.capture_frame(
+                    &self.world,
+                    &self.blitter,
+                    &mut encoder,
+                    self.view_target.main_binding(),
+                    callback,
+                )
+            })
+            .collect();
+
+        // The stats change every frame and would break the hashes.
+        if self.hud.enabled() && self.frame_hasher.is_none() {
+            let (width, height) = (self.surface_config.width, self.surface_config.height);
+            let lines = [
+                format!("FPS {:.1}", 1. / app_state.dt.max(f64::EPSILON)),
+                format!("FRAME {:.2} MS", app_state.dt * 1e3),
+                format!("DRAWS {}", self.draw_cmd_buffer.len()),
+                format!("RES {width}X{height}"),
+            ];
+            let sync_points = self.gpu.sync_points();
+            let mut stalls = sync_points.last_frame().to_vec();
+            stalls.sort_by_key(|point| std::cmp::Reverse(point.duration));
+            let lines: Vec<_> = lines
+                .into_iter()
+                .chain(Some(format!(
+                    "SYNC {} {:.2} MS",
+                    stalls.len(),
+                    sync_points.last_frame_total().as_secs_f64() * 1e3
+                )))
+                .chain(stalls.iter().take(Self::HUD_SYNC_POINTS).map(|point| {
+                    format!(
+                        "  {} {} AT {:.2} FOR {:.2} MS",
+                        point.kind.name(),
+                        point.label,
+                        point.start.as_secs_f64() * 1e3,
+                        point.duration.as_secs_f64() * 1e3
+                    )
+                }))
+                .collect();
+            drop(sync_points);
+            self.hud.draw(
+                self.gpu.queue(),
+                &mut encoder,
+                self.view_target.main_view(),
+                (width, height),
+                &lines,
+            );
+        }
+        if let Some(ui) = self.ui.as_mut() {
+            ui.paint(&mut encoder, self.view_target.main_view());
+        }
+        captures.extend(self.ui_capture_requests.drain(..).map(|callback| {
+            self.screenshot_ctx.capture_frame(
+                &self.world,
+                &self.blitter,
+                &mut encoder,
+                self.view_target.main_binding(),
+                callback,
+            )
+        }));
         self.blitter.blit_to_texture_with_binding(
             &mut encoder,
             self.world.device(),
             self.view_target.main_binding(),
-            &target_view,
-            self.surface_config.format,
+            target_view,
+            target_format,
         );
 
+        let hashing = self.frame_hasher.as_ref().map(|hasher| {
+            hasher.hash_frame(
+                &self.world,
+                &mut encoder,
+                self.view_target.main_binding(),
+                (self.surface_config.width, self.surface_config.height),
+                app_state.frame_count,
+            )
+        });
+
         profiler.end_scope(&mut encoder);
         profiler.resolve_queries(&mut encoder);
 
-        self.gpu.queue().submit(Some(encoder.finish()));
-        target.present();
+        let command_buffers = self
+            .pending_command_buffers
+            .drain(..)
+            .chain(Some(encoder.finish()));
+        let submission = self.gpu.sync(SyncKind::Submit, "frame", || {
+            self.gpu.queue().submit(command_buffers)
+        });
+        captures.into_iter().for_each(|map| map());
+        if let Some(map) = hashing {
+            map();
+        }
+        // This frame saw the swapped instances, the next one keeps their history.
+        self.world.unwrap_mut::<InstancePool>().clear_changed();
 
         profiler.end_frame().ok();
+        self.world.unwrap_mut::<ProfilerOverlay>().push_cpu(
+            ProfilerOverlay::CPU_RENDER,
+            cpu_start.elapsed().as_secs_f32() * 1e3,
+        );
+        submission
+    }
 
-        if self.recorder.is_active() && self.recorder.ffmpeg_installed() {
-            let tx = self.recorder.sender.clone();
-            self.capture_frame(move |frame, _| {
-                let _ = tx.send(RecordEvent::Record(frame));
-            });
+    /// Waits for the oldest submission, the fence that keeps the per-frame uniform slot
+    /// written next from being read by a frame still on the gpu.
+    fn limit_frames_in_flight(&mut self, submission: wgpu::SubmissionIndex) {
+        self.submissions.push_back(submission);
+        while self.submissions.len() > self.frames_in_flight {
+            if let Some(oldest) = self.submissions.pop_front() {
+                self.gpu.sync(SyncKind::Poll, "frames in flight", || {
+                    self.gpu
+                        .device()
+                        .poll(wgpu::Maintain::WaitForSubmissionIndex(oldest));
+                });
+            }
         }
+        self.gpu.sync_points().end_frame();
+    }
 
-        Ok(())
+    /// Number of frames the CPU may record ahead of the GPU, in `1..=MAX_FRAMES_IN_FLIGHT`.
+    /// Lower values trade throughput for input latency.
+    pub fn set_frames_in_flight(&mut self, frames: usize) {
+        self.frames_in_flight = frames.clamp(1, Self::MAX_FRAMES_IN_FLIGHT);
+    }
+
+    pub fn frames_in_flight(&self) -> usize {
+        self.frames_in_flight
     }
 
     pub fn resize(&mut self, width: u32, height: u32) {
@@ -363,8 +779,9 @@ impl App {
         }
         self.surface_config.width = width;
         self.surface_config.height = height;
-        self.surface
-            .configure(self.gpu.device(), &self.surface_config);
+        if let Some(surface) = &self.surface {
+            surface.configure(self.gpu.device(), &self.surface_config);
+        }
         self.gbuffer.resize(&self.gpu, width, height);
         self.view_target = view_target::ViewTarget::new(&self.world, width, height);
         self.global_uniform.resolution = [width as f32, height as f32];
@@ -382,6 +799,28 @@ impl App {
         actions: Vec<StateAction>,
         update: impl FnOnce(UpdateContext),
     ) -> Result<()> {
+        let cpu_start = Instant::now();
+        self.world.get_mut::<FrameArena>()?.next_frame();
+        if self.motion_step != state.frame_count {
+            self.motion_step = state.frame_count;
+            self.world.get_mut::<InstancePool>()?.begin_step();
+        }
+        self.world.get_mut::<AnimationPlayer>()?.update(
+            state.total_time,
+            &mut self.world.get_mut::<InstancePool>()?,
+            &mut self.world.get_mut::<SkinnedMeshPool>()?,
+        );
+        self.world
+            .get_mut::<SceneGraph>()?
+            .update(&mut self.world.get_mut::<InstancePool>()?);
+
+        // Render didn't consume the previous update, don't let the work pile up.
+        if !self.pending_command_buffers.is_empty() {
+            self.gpu
+                .queue()
+                .submit(self.pending_command_buffers.drain(..));
+        }
+
         let mut profiler = self.profiler.borrow_mut();
         let mut encoder = self
             .device()
@@ -399,61 +838,245 @@ impl App {
             width: self.surface_config.width,
             height: self.surface_config.height,
         });
-        self.gpu.queue().submit(Some(encoder.finish()));
+        if !self.plugins.is_empty() {
+            self.plugins.update(&mut UpdateContext {
+                app_state: state,
+                encoder: ProfilerCommandEncoder {
+                    encoder: &mut encoder,
+                    device: self.gpu.device(),
+                    profiler: &mut profiler,
+                },
+                world: &self.world,
+                width: self.surface_config.width,
+                height: self.surface_config.height,
+            });
+        }
+        self.pending_command_buffers.push(encoder.finish());
+
+        self.world.get_mut::<InstancePool>()?.flush();
+        if self.world.get_mut::<InstancePool>()?.take_dynamic_moved() {
+            let instance_pool = self.get_instance_pool();
+            let mut mesh_pool = self.get_mesh_pool_mut();
+            if mesh_pool.update_dynamic_tlas(&instance_pool.instances_data) {
+                mesh_pool.trace_bind_group =
+                    Self::create_trace_bind_group(self.device(), &mesh_pool, &instance_pool);
+            }
+        }
 
         self.global_uniform.frame = state.frame_count as _;
         self.global_uniform.time = state.total_time as _;
         self.global_uniform.dt = state.dt as _;
+        self.global_uniform.alpha = state.alpha as _;
+        {
+            let mut live_params = self.world.get_mut::<LiveParams>()?;
+            live_params.poll();
+            let mut timeline = self.world.get_mut::<Timeline>()?;
+            timeline.tick(state.total_time);
+            if timeline.playing {
+                timeline.apply_camera(&mut state.camera);
+                timeline.apply_params(&mut live_params);
+            }
+            self.global_uniform.user = live_params.user_block();
+        }
         self.world
             .get_mut::<global_ubo::GlobalUniformBinding>()?
             .update(self.gpu.queue(), &self.global_uniform);
 
-        let mut camera_uniform = self.world.unwrap_mut::<CameraUniform>();
-        *camera_uniform = state.camera.get_uniform(Some(&camera_uniform));
+        let audio = self.world.get::<AudioAnalyzer>()?.latest();
         self.world
-            .get_mut::<CameraUniformBinding>()?
-            .update(self.gpu.queue(), &camera_uniform);
+            .get_mut::<AudioBinding>()?
+            .update(self.gpu.queue(), &audio);
+
+        let eye = state.camera.rig.final_transform.position;
+        let streamed = {
+            let mut instance_pool = self.world.get_mut::<InstancePool>()?;
+            self.world
+                .get_mut::<Streaming>()?
+                .update(&mut instance_pool, eye)?
+        };
+        if streamed {
+            self.refresh_scene_buffers()?;
+        }
 
-        if state.frame_count % 500 == 0 && std::env::var("GPU_PROFILING").is_ok() {
-            let mut last_profile = vec![];
-            while let Some(profiling_data) = profiler.process_finished_frame() {
-                last_profile = profiling_data;
+        let import = self.world.get_mut::<AssetBrowser>()?.next_import();
+        if let Some((path, progress)) = import {
+            let eye = state.camera.rig.final_transform.position;
+            let forward = state.camera.rig.final_transform.rotation * Vec3::NEG_Z;
+            match self.import_asset(&path, eye + forward * 5., &progress) {
+                Ok(()) => self.refresh_scene_buffers()?,
+                Err(err) if err.is::<ImportCanceled>() => {
+                    log::info!("Canceled import of {}", path.display())
+                }
+                Err(err) => log::error!("Failed to import {}: {err:#}", path.display()),
             }
-            scopes_to_console_recursive(&last_profile, 0);
+        }
+
+        let mut camera_uniform = self.world.unwrap_mut::<CameraUniform>();
+        *camera_uniform = state.camera.get_uniform(Some(&camera_uniform));
+        camera_uniform.mip_bias = self.world.get::<TextureLod>()?.mip_bias();
+        let slot = {
+            let mut camera_binding = self.world.get_mut::<CameraUniformBinding>()?;
+            camera_binding.update(self.gpu.queue(), &camera_uniform);
+            camera_binding.slot()
+        };
+        self.world.get_mut::<GlobalsBindGroup>()?.set_slot(slot);
+        self.world.get_mut::<SoftwareOcclusion>()?.update(
+            &self.get_instance_pool().instances_data,
+            &self.get_mesh_pool().mesh_info_cpu,
+            camera_uniform.projection * camera_uniform.view,
+        );
+
+        let mut crash = self.crash.context();
+        crash.frame = state.frame_count;
+        crash.camera_position = eye;
+        let mut overlay = self.world.get_mut::<ProfilerOverlay>()?;
+        while let Some(profiling_data) = profiler.process_finished_frame() {
+            overlay.push_gpu_frame(&profiling_data);
+            crash.last_profile = profiling_data;
+        }
+        overlay.push_cpu(ProfilerOverlay::CPU_FRAME, state.dt as f32 * 1e3);
+        drop(overlay);
+        if state.frame_count % 500 == 0
+            && !self.background
+            && std::env::var("GPU_PROFILING").is_ok()
+        {
+            scopes_to_console_recursive(&crash.last_profile, 0);
             println!();
         }
+        drop(crash);
 
         for action in actions {
             match action {
+                StateAction::ToggleHud => self.hud.set_enabled(!self.hud.enabled()),
                 StateAction::StartRecording => {
                     self.recorder.start(self.screenshot_ctx.image_dimentions)
                 }
                 StateAction::FinishRecording => self.recorder.finish(),
+                StateAction::ToggleProfiler => {
+                    let mut overlay = self.world.get_mut::<ProfilerOverlay>()?;
+                    overlay.enabled = !overlay.enabled;
+                }
+                StateAction::CycleDebugView => {
+                    let mut debug_view = self.world.get_mut::<DebugView>()?;
+                    *debug_view = debug_view.next();
+                }
                 StateAction::Screenshot => {
-                    let tx = self.recorder.sender.clone();
-                    self.capture_frame(move |frame, dims| {
-                        let _ = tx.send(RecordEvent::Screenshot((frame, dims)));
-                    });
+                    let seed = self.world.get::<SceneRng>()?.seed();
+                    let metadata = vec![("voidin:seed".to_string(), seed.to_string())];
+                    let screenshot = |metadata: Vec<_>, suffix: &'static str| -> CaptureCallback {
+                        let tx = self.recorder.sender.clone();
+                        Box::new(move |frame, dims| {
+                            let _ =
+                                tx.send(RecordEvent::Screenshot((frame, dims, metadata, suffix)));
+                        })
+                    };
+                    if self.screenshot_ui {
+                        let callback = screenshot(metadata.clone(), "-ui");
+                        self.ui_capture_requests.push(callback);
+                    }
+                    self.capture_requests.push(screenshot(metadata, ""));
                 }
             }
         }
+        self.world.get_mut::<ProfilerOverlay>()?.push_cpu(
+            ProfilerOverlay::CPU_UPDATE,
+            cpu_start.elapsed().as_secs_f32() * 1e3,
+        );
         Ok(())
     }
 
     pub fn handle_events(&mut self, path: std::path::PathBuf) {
+        if self.plugins.reload(&path, &self.world) {
+            return;
+        }
+        self.crash.context().shader_reloaded(&path);
         self.get_pipeline_arena_mut().reload_pipelines(&path);
     }
 
+    /// Loads a [`Plugin`](crate::plugin::Plugin) library and reloads it whenever it's rebuilt.
+    pub fn load_plugin(&mut self, path: impl AsRef<std::path::Path>) -> Result<()> {
+        let path = self.plugins.load(path, &self.world)?;
+        // Cargo replaces the library instead of writing into it, so watch the directory.
+        if let Some(dir) = path.parent() {
+            self.get_pipeline_arena_mut().watch_path(dir)?;
+        }
+        Ok(())
+    }
+
+    /// Built-in frame stats overlay, independent of egui. Toggled with F2.
+    pub fn set_hud(&mut self, enabled: bool) {
+        self.hud.set_enabled(enabled);
+    }
+
+    pub fn hud(&self) -> bool {
+        self.hud.enabled()
+    }
+
+    /// Captures the next rendered frame without the ui, the copy is submitted together with it.
     pub fn capture_frame(
-        &self,
+        &mut self,
         callback: impl FnOnce(Arc<wgpu::Buffer>, ImageDimentions) + Send + 'static,
     ) {
-        self.screenshot_ctx.capture_frame(
-            &self.world,
-            &self.blitter,
-            self.view_target.main_binding(),
-            callback,
-        );
+        self.capture_requests.push(Box::new(callback));
+    }
+
+    /// Captures the next frame as it's presented, with the ui on top.
+    pub fn capture_frame_with_ui(
+        &mut self,
+        callback: impl FnOnce(Arc<wgpu::Buffer>, ImageDimentions) + Send + 'static,
+    ) {
+        self.ui_capture_requests.push(Box::new(callback));
+    }
+
+    /// Screenshots also save the frame with the ui, as `screenshot-<time>-ui.png`
+    /// next to the clean one.
+    pub fn set_screenshot_ui(&mut self, enabled: bool) {
+        self.screenshot_ui = enabled;
+    }
+
+    pub fn screenshot_ui(&self) -> bool {
+        self.screenshot_ui
+    }
+
+    /// Streams every rendered frame to `output` in addition to the surface.
+    /// Frames are dropped while the previous one is still being written.
+    pub fn set_output(&mut self, output: &Output) -> Result<()> {
+        self.set_output_sink(output.create_sink()?);
+        Ok(())
+    }
+
+    pub fn set_output_sink(&mut self, sink: Option<Box<dyn OutputSink>>) {
+        self.output = sink.map(OutputStream::new);
+    }
+
+    /// Hashes every rendered frame, see [`FrameHasher`].
+    pub fn set_frame_hasher(&mut self, hasher: Option<FrameHasher>) {
+        self.frame_hasher = hasher;
+    }
+
+    /// Pauses the recorder and gpu timers while the window is unfocused or minimized,
+    /// the frame rate itself is throttled by [`Redraw`].
+    pub fn set_background(&mut self, background: bool) {
+        if self.background == background {
+            return;
+        }
+        self.background = background;
+        #[cfg(feature = "profiler")]
+        {
+            self.profiler.get_mut().enable_timer = !background;
+        }
+        if self.recorder.is_active() {
+            match background {
+                true => log::info!("Recording paused in background"),
+                false => log::info!("Recording resumed"),
+            }
+        }
+        self.world.unwrap_mut::<Redraw>().set_background(background);
+    }
+
+    /// Context written into the crash report, see [`CrashReporter::install`].
+    pub fn crash_reporter(&self) -> &CrashReporter {
+        &self.crash
     }
 
     pub fn get_pipeline_arena(&self) -> Read<PipelineArena> {
@@ -464,10 +1087,66 @@ impl App {
         self.world.unwrap_mut::<PipelineArena>()
     }
 
-    pub fn add_mesh(&mut self, mesh: MeshRef) -> MeshId {
+    pub fn add_mesh(&mut self, mesh: MeshRef) -> Result<MeshId> {
         self.world.unwrap_mut::<MeshPool>().add(mesh)
     }
 
+    pub fn add_meshes(&mut self, meshes: Vec<MeshRef>) -> Result<Vec<MeshId>> {
+        self.world.unwrap_mut::<MeshPool>().add_many(meshes)
+    }
+
+    /// Replaces the geometry of `id` and rebuilds the TLAS, see [`MeshPool::update_mesh`].
+    pub fn update_mesh(&mut self, id: MeshId, mesh: MeshRef) -> Result<()> {
+        self.world.unwrap_mut::<MeshPool>().update_mesh(id, mesh)?;
+        self.refresh_scene_buffers()
+    }
+
+    /// Imports a glTF scene, or a PLY or STL mesh, with its origin at `position`, see
+    /// [`AssetBrowser`].
+    pub fn import_asset(
+        &mut self,
+        path: &std::path::Path,
+        position: Vec3,
+        progress: &ImportProgress,
+    ) -> Result<()> {
+        let transform = Mat4::from_translation(position);
+        let extension = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(str::to_ascii_lowercase);
+        let instances = match extension.as_deref() {
+            Some(extension @ ("ply" | "stl")) => {
+                progress.expect(1);
+                let mesh = if extension == "ply" {
+                    crate::models::PlyModel::load(path)?
+                } else {
+                    crate::models::StlModel::load(path)?
+                };
+                progress.step(path.display().to_string())?;
+                let mesh = self.add_meshes(vec![mesh.as_ref()])?[0];
+                vec![Instance::new(transform, mesh, MaterialId::default())]
+            }
+            _ => {
+                let document = crate::GltfDocument::import_with_progress(self, path, progress)?;
+                document.get_scene_instances(transform)
+            }
+        };
+        self.get_instance_pool_mut().add(&instances)?;
+        Ok(())
+    }
+
+    /// Adds one of the built-in procedural scenes, see [`TestScene`].
+    pub fn load_test_scene(&mut self, scene: crate::TestScene) -> Result<()> {
+        scene.load(self)
+    }
+
+    /// Bakes an imposter of `mesh` shaded with `material`, see [`Imposters::bake`].
+    pub fn bake_imposter(&mut self, mesh: MeshId, material: MaterialId) -> Result<ImposterId> {
+        self.world
+            .get_mut::<Imposters>()?
+            .bake(&self.world, &self.blitter, mesh, material)
+    }
+
     pub fn get_material_pool(&self) -> Read<MaterialPool> {
         self.world.unwrap::<MaterialPool>()
     }
@@ -500,6 +1179,18 @@ impl App {
         self.world.unwrap_mut::<InstancePool>()
     }
 
+    /// Instance under a pixel of the last frames, in physical pixels from the top left.
+    /// Resolves a few frames later, see [`Picking`].
+    pub fn pick(&self, screen_pos: Vec2) -> PickHandle {
+        self.world.unwrap::<Picking>().pick(screen_pos)
+    }
+
+    pub fn pick_with(&self, screen_pos: Vec2, callback: impl FnOnce(Option<InstanceId>) + 'static) {
+        self.world
+            .unwrap::<Picking>()
+            .pick_with(screen_pos, callback)
+    }
+
     pub fn queue(&self) -> &wgpu::Queue {
         self.gpu.queue()
     }
@@ -580,7 +1271,7 @@ pub struct UpdateContext<'a> {
 }
 
 pub struct RenderContext<'a> {
-    pub window: &'a Window,
+    pub window: Option<&'a Window>,
     pub app_state: &'a AppState,
     pub encoder: ProfilerCommandEncoder<'a>,
     pub view_target: &'a ViewTarget,
@@ -592,62 +1283,44 @@ pub struct RenderContext<'a> {
     pub draw_cmd_buffer: &'a ResizableBuffer<DrawIndexedIndirect>,
     pub draw_cmd_bind_group: &'a wgpu::BindGroup,
 
-    egui_context: &'a egui::Context,
-    egui_renderer: &'a mut egui_wgpu::Renderer,
-    egui_state: &'a mut egui_winit::State,
+    #[cfg_attr(not(feature = "egui"), allow(dead_code))]
+    ui: Option<&'a mut Ui>,
 }
 
 impl<'a> RenderContext<'a> {
+    #[cfg(feature = "egui")]
     pub fn ui(&mut self, ui_builder: impl FnOnce(&egui::Context)) {
-        let screen_descriptor = ScreenDescriptor {
-            size_in_pixels: [self.width, self.height],
-            pixels_per_point: self.egui_state.pixels_per_point(),
+        let (Some(ui), Some(window)) = (self.ui.as_mut(), self.window) else {
+            return;
         };
-
-        let full_output = self
-            .egui_context
-            .run(self.egui_state.take_egui_input(self.window), |ctx| {
-                ui_builder(ctx)
-            });
-
-        let paint_jobs = self.egui_context.tessellate(full_output.shapes);
-        let textures_delta = full_output.textures_delta;
-
-        {
-            for (texture_id, image_delta) in &textures_delta.set {
-                self.egui_renderer.update_texture(
-                    self.gpu.device(),
-                    self.gpu.queue(),
-                    *texture_id,
-                    image_delta,
-                );
-            }
-            for texture_id in &textures_delta.free {
-                self.egui_renderer.free_texture(texture_id);
-            }
-            self.egui_renderer.update_buffers(
-                self.gpu.device(),
-                self.gpu.queue(),
-                &mut self.encoder,
-                &paint_jobs,
-                &screen_descriptor,
-            );
-
-            let mut render_pass = self.encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("UI Pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: self.view_target.main_view(),
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Load,
-                        store: true,
-                    },
-                })],
-                depth_stencil_attachment: None,
-            });
-            self.egui_renderer
-                .render(&mut render_pass, paint_jobs.as_slice(), &screen_descriptor);
-        }
+        let arena = self.world.unwrap::<PipelineArena>();
+        let mut profiler_overlay = self.world.unwrap_mut::<ProfilerOverlay>();
+        ui.draw(
+            self.gpu,
+            window,
+            &mut self.encoder,
+            self.width,
+            self.height,
+            |ctx| {
+                ui_builder(ctx);
+                profiler_overlay.ui(ctx);
+                // Stays up until the broken shaders compile again.
+                let mut errors = arena.shader_errors().peekable();
+                if errors.peek().is_none() {
+                    return;
+                }
+                egui::Window::new("Shader Errors")
+                    .anchor(egui::Align2::CENTER_TOP, [0., 8.])
+                    .collapsible(false)
+                    .resizable(false)
+                    .show(ctx, |ui| {
+                        for (path, error) in errors {
+                            ui.colored_label(egui::Color32::LIGHT_RED, path.display().to_string());
+                            ui.label(egui::RichText::new(error).monospace());
+                        }
+                    });
+            },
+        );
     }
 }
 
@@ -665,6 +1338,8 @@ pub struct ProfilerCommandEncoder<'a> {
 }
 
 impl<'a> ProfilerCommandEncoder<'a> {
+    /// Opens a named scope in the GPU profiler tree, every pass until the matching
+    /// [`Self::profile_end`] nests under it.
     pub fn profile_start(&mut self, label: &str) {
         #[cfg(debug_assertions)]
         self.encoder.push_debug_group(label);
@@ -677,11 +1352,17 @@ impl<'a> ProfilerCommandEncoder<'a> {
         self.encoder.pop_debug_group();
     }
 
+    /// Like [`Self::profile_start`], but the scope closes when the guard is dropped.
+    pub fn scope(&mut self, label: &str) -> ProfilerScope<'_, 'a> {
+        self.profile_start(label);
+        ProfilerScope { encoder: self }
+    }
+
     pub fn begin_compute_pass(
         &mut self,
         desc: &wgpu::ComputePassDescriptor,
-    ) -> wgpu_profiler::scope::OwningScope<wgpu::ComputePass> {
-        wgpu_profiler::scope::OwningScope::start(
+    ) -> OwningScope<wgpu::ComputePass> {
+        OwningScope::start(
             desc.label.unwrap_or("Compute Pass"),
             self.profiler,
             self.encoder.begin_compute_pass(desc),
@@ -692,8 +1373,8 @@ impl<'a> ProfilerCommandEncoder<'a> {
     pub fn begin_render_pass<'pass>(
         &'pass mut self,
         desc: &wgpu::RenderPassDescriptor<'pass, '_>,
-    ) -> wgpu_profiler::scope::OwningScope<wgpu::RenderPass<'pass>> {
-        wgpu_profiler::scope::OwningScope::start(
+    ) -> OwningScope<wgpu::RenderPass<'pass>> {
+        OwningScope::start(
             desc.label.unwrap_or("Render Pass"),
             self.profiler,
             self.encoder.begin_render_pass(desc),
@@ -702,6 +1383,31 @@ impl<'a> ProfilerCommandEncoder<'a> {
     }
 }
 
+/// Named profiler scope returned by [`ProfilerCommandEncoder::scope`], records through it.
+pub struct ProfilerScope<'s, 'a> {
+    encoder: &'s mut ProfilerCommandEncoder<'a>,
+}
+
+impl<'s, 'a> std::ops::Deref for ProfilerScope<'s, 'a> {
+    type Target = ProfilerCommandEncoder<'a>;
+
+    fn deref(&self) -> &Self::Target {
+        self.encoder
+    }
+}
+
+impl<'s, 'a> std::ops::DerefMut for ProfilerScope<'s, 'a> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.encoder
+    }
+}
+
+impl<'s, 'a> Drop for ProfilerScope<'s, 'a> {
+    fn drop(&mut self) {
+        self.encoder.profile_end();
+    }
+}
+
 impl<'a> std::ops::Deref for ProfilerCommandEncoder<'a> {
     type Target = wgpu::CommandEncoder;
 
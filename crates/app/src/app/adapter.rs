@@ -0,0 +1,106 @@
+use std::{convert::Infallible, str::FromStr};
+
+use color_eyre::{eyre::bail, Result};
+use pollster::FutureExt;
+
+use super::App;
+
+/// Environment variable selecting the [`AdapterSelection`] of [`crate::App::new`].
+pub const ADAPTER_ENV: &str = "VOIDIN_ADAPTER";
+/// Command line flag taking the same values as [`ADAPTER_ENV`], it wins over the variable.
+pub const ADAPTER_FLAG: &str = "--adapter";
+/// Command line flag making [`crate::run`] print the adapters and exit.
+pub const LIST_ADAPTERS_FLAG: &str = "--list-adapters";
+
+/// Which adapter the app renders with, `HighPerformance` guesses wrong on some hybrid laptops.
+///
+/// Parsed from `auto`, an index into [`adapters`] or a part of the adapter name, e.g.
+/// `llvmpipe` for the software rasterizer on CI.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum AdapterSelection {
+    /// Let wgpu pick a `HighPerformance` adapter.
+    #[default]
+    Auto,
+    Index(usize),
+    /// First adapter whose name contains it, ignoring case.
+    Name(String),
+}
+
+impl AdapterSelection {
+    pub fn from_env() -> Self {
+        let mut args = std::env::args().skip(1);
+        let flag = args
+            .by_ref()
+            .find(|arg| arg == ADAPTER_FLAG)
+            .and_then(|_| args.next());
+        match flag.or_else(|| std::env::var(ADAPTER_ENV).ok()) {
+            Some(spec) => spec.parse().unwrap_or_default(),
+            None => Self::Auto,
+        }
+    }
+
+    /// Adapter to create the device on, it has to present to `surface` if there is one.
+    pub fn select(
+        &self,
+        instance: &wgpu::Instance,
+        surface: Option<&wgpu::Surface>,
+    ) -> Result<wgpu::Adapter> {
+        let compatible = |adapter: &wgpu::Adapter| {
+            surface.map_or(true, |surface| adapter.is_surface_supported(surface))
+        };
+        let adapter = match self {
+            Self::Auto => instance
+                .request_adapter(&wgpu::RequestAdapterOptions {
+                    power_preference: wgpu::PowerPreference::HighPerformance,
+                    force_fallback_adapter: false,
+                    compatible_surface: surface,
+                })
+                .block_on(),
+            Self::Index(index) => instance
+                .enumerate_adapters(App::BACKENDS)
+                .nth(*index)
+                .filter(compatible),
+            Self::Name(name) => {
+                let name = name.to_lowercase();
+                instance
+                    .enumerate_adapters(App::BACKENDS)
+                    .filter(compatible)
+                    .find(|adapter| adapter.get_info().name.to_lowercase().contains(&name))
+            }
+        };
+        let Some(adapter) = adapter else {
+            let available: Vec<_> = adapters(instance)
+                .into_iter()
+                .enumerate()
+                .map(|(index, info)| format!("{index}: {}", info.name))
+                .collect();
+            bail!(
+                "No compatible adapter for {self:?}, available: [{}]",
+                available.join(", ")
+            );
+        };
+        Ok(adapter)
+    }
+}
+
+impl FromStr for AdapterSelection {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.trim() {
+            "" | "auto" => Self::Auto,
+            spec => match spec.parse() {
+                Ok(index) => Self::Index(index),
+                Err(_) => Self::Name(spec.to_string()),
+            },
+        })
+    }
+}
+
+/// Adapters of the backends the app runs on, in the order [`AdapterSelection::Index`] uses.
+pub fn adapters(instance: &wgpu::Instance) -> Vec<wgpu::AdapterInfo> {
+    instance
+        .enumerate_adapters(App::BACKENDS)
+        .map(|adapter| adapter.get_info())
+        .collect()
+}
@@ -0,0 +1,519 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    path::{Path, PathBuf},
+    sync::{mpsc, Arc},
+};
+
+use color_eyre::{
+    eyre::{bail, eyre},
+    Result,
+};
+use glam::{Mat4, Vec3};
+use wgpu::util::DeviceExt;
+
+use components::Gpu;
+
+use crate::ImportProgress;
+
+pub const ASSETS_FOLDER: &str = "assets";
+/// Cached thumbnails inside the assets folder, named after a hash of the path,
+/// size and modification time of their asset.
+pub const THUMBNAILS_FOLDER: &str = ".thumbnails";
+
+const IMPORTABLE_EXTENSIONS: [&str; 4] = ["gltf", "glb", "ply", "stl"];
+
+#[cfg_attr(not(feature = "egui"), allow(dead_code))]
+enum Thumbnail {
+    NotRequested,
+    Pending,
+    Failed,
+    Loaded(Vec<u8>),
+    #[cfg(feature = "egui")]
+    Uploaded(egui::TextureHandle),
+}
+
+#[cfg_attr(not(feature = "egui"), allow(dead_code))]
+struct Asset {
+    path: PathBuf,
+    thumbnail: Thumbnail,
+}
+
+type ThumbnailResult = (PathBuf, Option<Vec<u8>>);
+
+/// Lists the importable files under `assets/` with thumbnails for the egui panel.
+///
+/// Thumbnails are rendered offscreen on a worker thread with a small unlit pipeline of
+/// their own, only for the rows that get drawn, and cached on disk. Double-clicked
+/// assets are queued and imported in front of the camera by `App::update`, one per frame,
+/// and listed with their progress until then.
+#[cfg_attr(not(feature = "egui"), allow(dead_code))]
+pub struct AssetBrowser {
+    root: PathBuf,
+    assets: Option<Vec<Asset>>,
+    requests: mpsc::Sender<PathBuf>,
+    thumbnails: mpsc::Receiver<ThumbnailResult>,
+    imports: Vec<(PathBuf, ImportProgress)>,
+}
+
+impl AssetBrowser {
+    pub const THUMBNAIL_SIZE: u32 = 128;
+
+    pub fn new(gpu: Arc<Gpu>) -> Self {
+        let root = PathBuf::from(ASSETS_FOLDER);
+        let (requests, worker_requests) = mpsc::channel::<PathBuf>();
+        let (worker_thumbnails, thumbnails) = mpsc::channel();
+        let cache = root.join(THUMBNAILS_FOLDER);
+        std::thread::spawn(move || {
+            let renderer = ThumbnailRenderer::new(gpu);
+            for path in worker_requests {
+                let thumbnail = renderer
+                    .cached_or_render(&cache, &path)
+                    .map_err(|err| log::warn!("Thumbnail of {} failed: {err:#}", path.display()))
+                    .ok();
+                if worker_thumbnails.send((path, thumbnail)).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Self {
+            root,
+            assets: None,
+            requests,
+            thumbnails,
+            imports: vec![],
+        }
+    }
+
+    /// Rescans the assets folder on the next draw of the panel.
+    pub fn refresh(&mut self) {
+        self.assets = None;
+    }
+
+    /// Queues `path` for `App::update` to import, the returned handle follows or cancels it.
+    pub fn import(&mut self, path: impl Into<PathBuf>) -> ImportProgress {
+        let progress = ImportProgress::new();
+        self.imports.push((path.into(), progress.clone()));
+        progress
+    }
+
+    /// Pops the oldest queued import that wasn't canceled.
+    pub fn next_import(&mut self) -> Option<(PathBuf, ImportProgress)> {
+        self.imports.retain(|(_, progress)| !progress.is_canceled());
+        (!self.imports.is_empty()).then(|| self.imports.remove(0))
+    }
+
+    #[cfg_attr(not(feature = "egui"), allow(dead_code))]
+    fn scan(root: &Path) -> Vec<Asset> {
+        let mut assets = vec![];
+        let mut dirs = vec![root.to_path_buf()];
+        while let Some(dir) = dirs.pop() {
+            let Ok(entries) = std::fs::read_dir(&dir) else {
+                continue;
+            };
+            for path in entries.flatten().map(|entry| entry.path()) {
+                let hidden = path
+                    .file_name()
+                    .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+                if hidden {
+                    continue;
+                }
+                if path.is_dir() {
+                    dirs.push(path);
+                } else if path
+                    .extension()
+                    .is_some_and(|ext| IMPORTABLE_EXTENSIONS.iter().any(|known| ext == *known))
+                {
+                    assets.push(Asset {
+                        path,
+                        thumbnail: Thumbnail::NotRequested,
+                    });
+                }
+            }
+        }
+        assets.sort_by(|a, b| a.path.cmp(&b.path));
+        assets
+    }
+
+    #[cfg_attr(not(feature = "egui"), allow(dead_code))]
+    fn receive_thumbnails(&mut self) {
+        let Some(assets) = self.assets.as_mut() else {
+            return;
+        };
+        for (path, pixels) in self.thumbnails.try_iter() {
+            if let Some(asset) = assets.iter_mut().find(|asset| asset.path == path) {
+                asset.thumbnail = match pixels {
+                    Some(pixels) => Thumbnail::Loaded(pixels),
+                    None => Thumbnail::Failed,
+                };
+            }
+        }
+    }
+
+    #[cfg(feature = "egui")]
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label(format!("{}/", self.root.display()));
+            if ui.button("Refresh").clicked() {
+                self.refresh();
+            }
+        });
+        self.receive_thumbnails();
+        let assets = self.assets.get_or_insert_with(|| Self::scan(&self.root));
+        if assets.is_empty() {
+            ui.label("Nothing to import");
+            return;
+        }
+
+        let size = egui::vec2(64., 64.);
+        let row_height = size.y + ui.spacing().item_spacing.y;
+        let mut imported = None;
+        egui::ScrollArea::vertical().max_height(400.).show_rows(
+            ui,
+            row_height,
+            assets.len(),
+            |ui, rows| {
+                for asset in &mut assets[rows] {
+                    if let Thumbnail::NotRequested = asset.thumbnail {
+                        let _ = self.requests.send(asset.path.clone());
+                        asset.thumbnail = Thumbnail::Pending;
+                    }
+                    if let Thumbnail::Loaded(pixels) = &asset.thumbnail {
+                        let side = Self::THUMBNAIL_SIZE as usize;
+                        let image = egui::ColorImage::from_rgba_unmultiplied([side, side], pixels);
+                        let texture = ui.ctx().load_texture(
+                            asset.path.to_string_lossy(),
+                            image,
+                            egui::TextureOptions::LINEAR,
+                        );
+                        asset.thumbnail = Thumbnail::Uploaded(texture);
+                    }
+
+                    let response = ui
+                        .horizontal(|ui| {
+                            match &asset.thumbnail {
+                                Thumbnail::Uploaded(texture) => {
+                                    ui.image((texture.id(), size));
+                                }
+                                Thumbnail::Failed => {
+                                    ui.add_sized(size, egui::Label::new("?"));
+                                }
+                                _ => {
+                                    ui.add_sized(size, egui::Spinner::new());
+                                }
+                            }
+                            let name = asset.path.strip_prefix(&self.root).unwrap_or(&asset.path);
+                            ui.label(name.display().to_string());
+                        })
+                        .response
+                        .interact(egui::Sense::click());
+                    if response.double_clicked() {
+                        imported = Some(asset.path.clone());
+                    }
+                }
+            },
+        );
+        ui.label("Double-click to import in front of the camera");
+        if let Some(path) = imported {
+            self.import(path);
+        }
+
+        for (path, progress) in &self.imports {
+            ui.horizontal(|ui| {
+                let status = progress.status();
+                let name = path.strip_prefix(&self.root).unwrap_or(path);
+                ui.add(
+                    egui::ProgressBar::new(status.fraction())
+                        .desired_width(120.)
+                        .text(name.display().to_string()),
+                );
+                if !status.item.is_empty() {
+                    ui.label(&status.item);
+                }
+                if ui.button("Cancel").clicked() {
+                    progress.cancel();
+                }
+            });
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct ThumbnailVertex {
+    position: Vec3,
+    normal: Vec3,
+    color: Vec3,
+}
+
+struct ThumbnailRenderer {
+    gpu: Arc<Gpu>,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl ThumbnailRenderer {
+    const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+    const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+    fn new(gpu: Arc<Gpu>) -> Self {
+        let device = gpu.device();
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Thumbnail Shader"),
+            source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed(include_str!(
+                "thumbnail.wgsl"
+            ))),
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Thumbnail Pipeline"),
+            layout: None,
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<ThumbnailVertex>() as _,
+                    step_mode: wgpu::VertexStepMode::Vertex,
+                    attributes: &wgpu::vertex_attr_array![
+                        0 => Float32x3,
+                        1 => Float32x3,
+                        2 => Float32x3
+                    ],
+                }],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(Self::FORMAT.into())],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: Self::DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+        Self { gpu, pipeline }
+    }
+
+    fn cached_or_render(&self, cache: &Path, path: &Path) -> Result<Vec<u8>> {
+        let metadata = std::fs::metadata(path)?;
+        let mut hasher = DefaultHasher::new();
+        path.hash(&mut hasher);
+        metadata.len().hash(&mut hasher);
+        metadata.modified().ok().hash(&mut hasher);
+        let cached = cache.join(format!("{:016x}.png", hasher.finish()));
+
+        if let Ok(image) = image::open(&cached) {
+            let image = image.into_rgba8();
+            if image.dimensions() == (AssetBrowser::THUMBNAIL_SIZE, AssetBrowser::THUMBNAIL_SIZE) {
+                return Ok(image.into_raw());
+            }
+        }
+
+        let pixels = self.render(&load_triangles(path)?)?;
+        std::fs::create_dir_all(cache)?;
+        image::save_buffer(
+            &cached,
+            &pixels,
+            AssetBrowser::THUMBNAIL_SIZE,
+            AssetBrowser::THUMBNAIL_SIZE,
+            image::ColorType::Rgba8,
+        )?;
+        Ok(pixels)
+    }
+
+    fn render(&self, vertices: &[ThumbnailVertex]) -> Result<Vec<u8>> {
+        let device = self.gpu.device();
+        let (min, max) = vertices.iter().fold(
+            (Vec3::splat(f32::INFINITY), Vec3::splat(f32::NEG_INFINITY)),
+            |(min, max), v| (min.min(v.position), max.max(v.position)),
+        );
+        let center = (min + max) / 2.;
+        let radius = ((max - min).length() / 2.).max(1e-4);
+        let fov = 30f32.to_radians();
+        let distance = radius / (fov / 2.).sin();
+        let eye = center + Vec3::new(1., 0.6, 1.).normalize() * distance;
+        let world_to_clip =
+            Mat4::perspective_rh(fov, 1., distance - radius * 1.1, distance + radius * 1.1)
+                * Mat4::look_at_rh(eye, center, Vec3::Y);
+
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Thumbnail Vertices"),
+            contents: bytemuck::cast_slice(vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        let uniform = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Thumbnail Camera"),
+            contents: bytemuck::bytes_of(&world_to_clip),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Thumbnail Bind Group"),
+            layout: &self.pipeline.get_bind_group_layout(0),
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform.as_entire_binding(),
+            }],
+        });
+
+        let size = AssetBrowser::THUMBNAIL_SIZE;
+        let extent = wgpu::Extent3d {
+            width: size,
+            height: size,
+            depth_or_array_layers: 1,
+        };
+        let target = |label, format, usage| {
+            device.create_texture(&wgpu::TextureDescriptor {
+                label: Some(label),
+                size: extent,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage,
+                view_formats: &[],
+            })
+        };
+        let color = target(
+            "Thumbnail Color",
+            Self::FORMAT,
+            wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        );
+        let depth = target(
+            "Thumbnail Depth",
+            Self::DEPTH_FORMAT,
+            wgpu::TextureUsages::RENDER_ATTACHMENT,
+        );
+        // 512 bytes per row is already aligned for the copy, no padding to strip.
+        let bytes_per_row = size * 4;
+        let download = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Thumbnail Download"),
+            size: (bytes_per_row * size) as _,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Thumbnail Encoder"),
+        });
+        {
+            let color_view = color.create_view(&Default::default());
+            let depth_view = depth.create_view(&Default::default());
+            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Thumbnail Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &color_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color {
+                            r: 0.05,
+                            g: 0.05,
+                            b: 0.06,
+                            a: 1.,
+                        }),
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &depth_view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.),
+                        store: false,
+                    }),
+                    stencil_ops: None,
+                }),
+            });
+            rpass.set_pipeline(&self.pipeline);
+            rpass.set_bind_group(0, &bind_group, &[]);
+            rpass.set_vertex_buffer(0, vertex_buffer.slice(..));
+            rpass.draw(0..vertices.len() as u32, 0..1);
+        }
+        encoder.copy_texture_to_buffer(
+            color.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &download,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(bytes_per_row),
+                    rows_per_image: None,
+                },
+            },
+            extent,
+        );
+        self.gpu.queue().submit(Some(encoder.finish()));
+
+        let (tx, rx) = mpsc::channel();
+        download
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |res| {
+                let _ = tx.send(res);
+            });
+        device.poll(wgpu::Maintain::Wait);
+        rx.recv()??;
+        let pixels = download.slice(..).get_mapped_range().to_vec();
+        download.unmap();
+        Ok(pixels)
+    }
+}
+
+// Triangles of the default scene in world space, colored by the base color factor.
+fn load_triangles(path: &Path) -> Result<Vec<ThumbnailVertex>> {
+    let gltf::Gltf { document, blob } = gltf::Gltf::open(path)?;
+    let buffers = gltf::import_buffers(&document, path.parent(), blob)?;
+    let scene = document
+        .default_scene()
+        .or_else(|| document.scenes().next())
+        .ok_or_else(|| eyre!("No scenes"))?;
+
+    let mut vertices = vec![];
+    let mut nodes: Vec<_> = scene.nodes().map(|node| (node, Mat4::IDENTITY)).collect();
+    while let Some((node, parent)) = nodes.pop() {
+        let transform = parent * Mat4::from_cols_array_2d(&node.transform().matrix());
+        nodes.extend(node.children().map(|child| (child, transform)));
+        let Some(mesh) = node.mesh() else {
+            continue;
+        };
+        let normal_matrix = transform.inverse().transpose();
+        for primitive in mesh.primitives() {
+            if primitive.mode() != gltf::mesh::Mode::Triangles {
+                continue;
+            }
+            let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+            let Some(positions) = reader.read_positions() else {
+                continue;
+            };
+            let positions: Vec<_> = positions.map(Vec3::from).collect();
+            let normals: Vec<_> = match reader.read_normals() {
+                Some(normals) => normals.map(Vec3::from).collect(),
+                None => vec![Vec3::Y; positions.len()],
+            };
+            let indices: Vec<_> = match reader.read_indices() {
+                Some(indices) => indices.into_u32().collect(),
+                None => (0..positions.len() as u32).collect(),
+            };
+            let [r, g, b, _] = primitive
+                .material()
+                .pbr_metallic_roughness()
+                .base_color_factor();
+            let color = Vec3::new(r, g, b);
+            vertices.extend(indices.iter().filter_map(|&i| {
+                let i = i as usize;
+                Some(ThumbnailVertex {
+                    position: transform.transform_point3(*positions.get(i)?),
+                    normal: normal_matrix
+                        .transform_vector3(normals.get(i).copied().unwrap_or(Vec3::Y))
+                        .normalize_or_zero(),
+                    color,
+                })
+            }));
+        }
+    }
+    if vertices.is_empty() {
+        bail!("No triangles in the default scene");
+    }
+    Ok(vertices)
+}
@@ -0,0 +1,313 @@
+use std::{
+    f32::consts::PI,
+    io::{BufReader, Read},
+    process::{Child, Command, Stdio},
+    sync::{Arc, Mutex},
+};
+
+use bytemuck::{Pod, Zeroable};
+use color_eyre::{eyre::WrapErr, Result};
+use wgpu::util::DeviceExt;
+
+use components::{
+    bind_group_layout::{self, WrappedBindGroupLayout},
+    NonZeroSized,
+};
+
+/// Environment variable with a command printing mono `s16le` samples to stdout,
+/// e.g. `parec --format=s16le --channels=1 --rate=44100`.
+pub const AUDIO_ENV: &str = "VOIDIN_AUDIO";
+
+pub const AUDIO_BANDS: usize = 16;
+
+const FFT_SIZE: usize = 1024;
+const HOP_SIZE: usize = FFT_SIZE / 2;
+const MIN_FREQUENCY: f32 = 30.;
+// Bass bands summed for beat detection.
+const BEAT_BANDS: usize = 3;
+const BEAT_THRESHOLD: f32 = 1.4;
+const BEAT_COOLDOWN: f32 = 0.25;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, Pod, Zeroable)]
+pub struct AudioUniform {
+    /// Log spaced bands in `[0, 1]`, low frequencies first.
+    pub bands: [[f32; 4]; AUDIO_BANDS / 4],
+    /// RMS of the latest window.
+    pub level: f32,
+    /// Jumps to 1 on a beat and decays afterwards.
+    pub beat: f32,
+    pub beat_count: u32,
+    _padding: u32,
+}
+
+/// Analyzes audio on a worker thread, the result is uploaded to [`AudioBinding`] every update.
+pub struct AudioAnalyzer {
+    latest: Arc<Mutex<AudioUniform>>,
+    // Process of the last `listen_command`, killed when replaced or dropped.
+    capture: Mutex<Option<Child>>,
+}
+
+impl AudioAnalyzer {
+    pub fn new() -> Self {
+        Self {
+            latest: Arc::new(Mutex::new(AudioUniform::default())),
+            capture: Mutex::new(None),
+        }
+    }
+
+    pub fn listen_env(&self) -> Result<()> {
+        match std::env::var(AUDIO_ENV) {
+            Ok(command) => self.listen_command(&command, 44100),
+            Err(_) => Ok(()),
+        }
+    }
+
+    /// Runs a shell-like command and analyzes its stdout as mono `s16le` at `sample_rate`.
+    ///
+    /// The command of a previous call is killed first, the analyzer owns one at a time.
+    pub fn listen_command(&self, command: &str, sample_rate: u32) -> Result<()> {
+        let mut capture = self.capture.lock().unwrap();
+        Self::kill_capture(&mut capture);
+        let mut args = command.split_whitespace();
+        let program = args.next().unwrap_or_default();
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .wrap_err_with(|| format!("Failed to spawn audio capture {command:?}"))?;
+        let stdout = child.stdout.take().expect("audio capture stdout is piped");
+        log::info!("Capturing audio from {command:?}");
+        self.listen(stdout, sample_rate);
+        *capture = Some(child);
+        Ok(())
+    }
+
+    // Its stdout closes with it, which ends the analysis thread.
+    fn kill_capture(capture: &mut Option<Child>) {
+        if let Some(mut child) = capture.take() {
+            // Fails when the command already exited on its own.
+            let _ = child.kill();
+            if let Err(err) = child.wait() {
+                log::warn!("Failed to wait for the audio capture: {err}");
+            }
+        }
+    }
+
+    /// Analyzes mono `s16le` samples until the reader is exhausted.
+    pub fn listen(&self, reader: impl Read + Send + 'static, sample_rate: u32) {
+        let latest = self.latest.clone();
+        std::thread::spawn(move || {
+            let mut reader = BufReader::new(reader);
+            let mut analysis = Analysis::new(sample_rate as f32);
+            let mut bytes = [0u8; HOP_SIZE * 2];
+            while reader.read_exact(&mut bytes).is_ok() {
+                let hop = bytes
+                    .chunks_exact(2)
+                    .map(|s| i16::from_le_bytes([s[0], s[1]]) as f32 / i16::MAX as f32);
+                let uniform = analysis.push(hop);
+                *latest.lock().unwrap() = uniform;
+            }
+        });
+    }
+
+    pub fn latest(&self) -> AudioUniform {
+        *self.latest.lock().unwrap()
+    }
+}
+
+impl Drop for AudioAnalyzer {
+    fn drop(&mut self) {
+        if let Ok(capture) = self.capture.get_mut() {
+            Self::kill_capture(capture);
+        }
+    }
+}
+
+struct Analysis {
+    window: Vec<f32>,
+    samples: Vec<f32>,
+    spectrum: Vec<(f32, f32)>,
+    band_edges: Vec<usize>,
+    energy_history: Vec<f32>,
+    history_len: usize,
+    hop_duration: f32,
+    since_beat: f32,
+    uniform: AudioUniform,
+}
+
+impl Analysis {
+    fn new(sample_rate: f32) -> Self {
+        let window = (0..FFT_SIZE)
+            .map(|i| 0.5 - 0.5 * (2. * PI * i as f32 / (FFT_SIZE - 1) as f32).cos())
+            .collect();
+
+        let bin_width = sample_rate / FFT_SIZE as f32;
+        let max_frequency = sample_rate / 2.;
+        let band_edges = (0..=AUDIO_BANDS)
+            .map(|i| {
+                let t = i as f32 / AUDIO_BANDS as f32;
+                let frequency = MIN_FREQUENCY * (max_frequency / MIN_FREQUENCY).powf(t);
+                ((frequency / bin_width) as usize).clamp(1, FFT_SIZE / 2)
+            })
+            .collect();
+
+        let hop_duration = HOP_SIZE as f32 / sample_rate;
+        // About a second of bass energy to compare beats against.
+        let history_len = (1. / hop_duration) as usize;
+        Self {
+            window,
+            samples: vec![0.; FFT_SIZE],
+            spectrum: vec![(0., 0.); FFT_SIZE],
+            band_edges,
+            energy_history: Vec::with_capacity(history_len),
+            history_len,
+            hop_duration,
+            since_beat: 0.,
+            uniform: AudioUniform::default(),
+        }
+    }
+
+    fn push(&mut self, hop: impl Iterator<Item = f32>) -> AudioUniform {
+        self.samples.drain(..HOP_SIZE);
+        self.samples.extend(hop);
+
+        let uniform = &mut self.uniform;
+        uniform.level = (self.samples.iter().map(|s| s * s).sum::<f32>() / FFT_SIZE as f32).sqrt();
+
+        for ((bin, sample), window) in self
+            .spectrum
+            .iter_mut()
+            .zip(&self.samples)
+            .zip(&self.window)
+        {
+            *bin = (sample * window, 0.);
+        }
+        fft(&mut self.spectrum);
+
+        let mut bass_energy = 0.;
+        for (i, edges) in self.band_edges.windows(2).enumerate() {
+            let bins = &self.spectrum[edges[0]..edges[1].max(edges[0] + 1)];
+            let magnitude = bins
+                .iter()
+                .map(|(re, im)| (re * re + im * im).sqrt())
+                .fold(0., f32::max)
+                * 2.
+                / FFT_SIZE as f32;
+            if i < BEAT_BANDS {
+                bass_energy += magnitude * magnitude;
+            }
+            // Map -60..0 dB to 0..1, fast attack and slow release.
+            let value = ((20. * (magnitude + 1e-6).log10() + 60.) / 60.).clamp(0., 1.);
+            let band = &mut uniform.bands[i / 4][i % 4];
+            *band = value.max(*band * 0.85);
+        }
+
+        let average =
+            self.energy_history.iter().sum::<f32>() / self.energy_history.len().max(1) as f32;
+        if self.energy_history.len() >= self.history_len {
+            self.energy_history.remove(0);
+        }
+        self.energy_history.push(bass_energy);
+
+        self.since_beat += self.hop_duration;
+        uniform.beat *= 0.85;
+        if bass_energy > average * BEAT_THRESHOLD
+            && bass_energy > 1e-6
+            && self.since_beat > BEAT_COOLDOWN
+        {
+            self.since_beat = 0.;
+            uniform.beat = 1.;
+            uniform.beat_count = uniform.beat_count.wrapping_add(1);
+        }
+        *uniform
+    }
+}
+
+/// In-place iterative radix-2 FFT, `data.len()` must be a power of two.
+fn fft(data: &mut [(f32, f32)]) {
+    let n = data.len();
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            data.swap(i, j);
+        }
+    }
+
+    let mut len = 2;
+    while len <= n {
+        let angle = -2. * PI / len as f32;
+        for start in (0..n).step_by(len) {
+            for k in 0..len / 2 {
+                let (sin, cos) = (angle * k as f32).sin_cos();
+                let (re, im) = data[start + k + len / 2];
+                let t = (re * cos - im * sin, re * sin + im * cos);
+                let u = data[start + k];
+                data[start + k] = (u.0 + t.0, u.1 + t.1);
+                data[start + k + len / 2] = (u.0 - t.0, u.1 - t.1);
+            }
+        }
+        len <<= 1;
+    }
+}
+
+pub struct AudioBinding {
+    pub binding: wgpu::BindGroup,
+    pub layout: bind_group_layout::BindGroupLayout,
+    buffer: wgpu::Buffer,
+}
+
+impl AudioBinding {
+    pub const DESC: wgpu::BindGroupLayoutDescriptor<'static> = wgpu::BindGroupLayoutDescriptor {
+        label: Some("Audio Uniform Bind Group Layout"),
+        entries: &[wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStages::VERTEX_FRAGMENT.union(wgpu::ShaderStages::COMPUTE),
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: Some(AudioUniform::NSIZE),
+            },
+            count: None,
+        }],
+    };
+
+    pub fn new(device: &wgpu::Device) -> Self {
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Audio Uniform"),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            contents: bytemuck::bytes_of(&AudioUniform::default()),
+        });
+
+        let layout = device.create_bind_group_layout_wrap(&Self::DESC);
+        let binding = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Audio Uniform Bind Group"),
+            layout: &layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+        });
+        Self {
+            binding,
+            layout,
+            buffer,
+        }
+    }
+
+    pub fn update(&mut self, queue: &wgpu::Queue, uniform: &AudioUniform) {
+        queue.write_buffer(&self.buffer, 0, bytemuck::bytes_of(uniform))
+    }
+
+    pub fn buffer(&self) -> &wgpu::Buffer {
+        &self.buffer
+    }
+}
@@ -0,0 +1,137 @@
+use std::{
+    collections::VecDeque,
+    fmt::Write as _,
+    panic::PanicInfo,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard, PoisonError, TryLockError},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use glam::Vec3;
+
+use super::profiler::GpuTimerScopeResult;
+
+/// Environment variable overriding [`CRASH_REPORTS_FOLDER`].
+pub const CRASH_DIR_ENV: &str = "VOIDIN_CRASH_DIR";
+pub const CRASH_REPORTS_FOLDER: &str = "crash_reports";
+
+/// What the renderer was doing, kept up to date by `App` and written out on panic.
+#[derive(Default)]
+pub struct CrashContext {
+    pub renderer: String,
+    pub scene: String,
+    pub frame: u64,
+    pub camera_position: Vec3,
+    /// Gpu timings of the last finished frame, empty without the `profiler` feature.
+    pub last_profile: Vec<GpuTimerScopeResult>,
+    reloaded: VecDeque<(SystemTime, PathBuf)>,
+}
+
+impl CrashContext {
+    const MAX_RELOADED: usize = 16;
+
+    pub fn shader_reloaded(&mut self, path: &Path) {
+        if self.reloaded.len() == Self::MAX_RELOADED {
+            self.reloaded.pop_front();
+        }
+        self.reloaded
+            .push_back((SystemTime::now(), path.to_path_buf()));
+    }
+
+    fn report(&self, info: &PanicInfo) -> String {
+        let mut report = String::new();
+        let thread = std::thread::current();
+        let _ = writeln!(
+            report,
+            "voidin crashed in thread '{}': {info}\n",
+            thread.name().unwrap_or("<unnamed>")
+        );
+
+        let _ = writeln!(report, "[renderer]\n{}", self.renderer);
+        let _ = writeln!(report, "[frame]");
+        let _ = writeln!(report, "Frame: {}", self.frame);
+        let _ = writeln!(report, "Camera position: {}\n", self.camera_position);
+        let _ = writeln!(report, "[scene]\n{}", self.scene);
+
+        let _ = writeln!(report, "[reloaded shaders]");
+        let now = SystemTime::now();
+        for (time, path) in self.reloaded.iter().rev() {
+            let ago = now.duration_since(*time).unwrap_or_default();
+            let _ = writeln!(report, "{} ({ago:.1?} before the crash)", path.display());
+        }
+
+        let _ = writeln!(report, "\n[last gpu frame]");
+        write_scopes(&mut report, &self.last_profile, 0);
+
+        let _ = writeln!(
+            report,
+            "\n[backtrace]\n{}",
+            std::backtrace::Backtrace::force_capture()
+        );
+        report
+    }
+}
+
+/// Shared [`CrashContext`], [`CrashReporter::install`] writes it into a report
+/// under [`CRASH_REPORTS_FOLDER`] when any thread panics.
+#[derive(Clone, Default)]
+pub struct CrashReporter {
+    context: Arc<Mutex<CrashContext>>,
+}
+
+impl CrashReporter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn context(&self) -> MutexGuard<'_, CrashContext> {
+        self.context.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Chains the report in front of the current panic hook, install it after `color_eyre`.
+    pub fn install(&self) {
+        let context = self.context.clone();
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            // The panicking thread may hold the lock, a report is not worth a deadlock.
+            let report = match context.try_lock() {
+                Ok(context) => context.report(info),
+                Err(TryLockError::Poisoned(context)) => context.into_inner().report(info),
+                Err(TryLockError::WouldBlock) => CrashContext::default().report(info),
+            };
+            match write_report(&report) {
+                Ok(path) => eprintln!("Crash report written to {}", path.display()),
+                Err(err) => eprintln!("Failed to write crash report: {err}\n{report}"),
+            }
+            previous(info);
+        }));
+    }
+}
+
+fn write_report(report: &str) -> std::io::Result<PathBuf> {
+    let dir = std::env::var_os(CRASH_DIR_ENV)
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(CRASH_REPORTS_FOLDER));
+    std::fs::create_dir_all(&dir)?;
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let path = dir.join(format!("crash-{secs}.txt"));
+    std::fs::write(&path, report)?;
+    Ok(path)
+}
+
+fn write_scopes(report: &mut String, results: &[GpuTimerScopeResult], indentation: usize) {
+    for scope in results {
+        let time = Duration::from_micros(((scope.time.end - scope.time.start) * 1e6) as u64);
+        let _ = writeln!(
+            report,
+            "{:width$}{time:?} - {}",
+            "",
+            scope.label,
+            width = 4 * indentation
+        );
+        write_scopes(report, &scope.nested_scopes, indentation + 1);
+    }
+}
@@ -0,0 +1,231 @@
+use std::{f32::consts::TAU, mem::size_of, sync::Arc};
+
+use components::{CameraUniformBinding, Gpu, ResizableBuffer, ResizableBufferExt};
+use glam::{vec3, Mat4, Vec3};
+
+use super::{gbuffer::GBuffer, view_target::ViewTarget};
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct DebugVertex {
+    position: [f32; 3],
+    color: [f32; 3],
+}
+
+/// Immediate mode lines over the frame, for bounds, lights and culling volumes.
+///
+/// Shapes are buffered from anywhere the world is at hand and drawn once by the app after
+/// the example and plugins rendered, then dropped. They are tested against the G-buffer
+/// depth unless [`DebugDraw::set_depth_test`] turned it off for the shapes added after.
+pub struct DebugDraw {
+    gpu: Arc<Gpu>,
+    depth_test: bool,
+    // Depth tested lines, then the ones drawn on top.
+    vertices: [Vec<DebugVertex>; 2],
+    buffer: ResizableBuffer<DebugVertex>,
+    pipelines: [wgpu::RenderPipeline; 2],
+}
+
+impl DebugDraw {
+    const SPHERE_SEGMENTS: usize = 32;
+    /// Depth the far face of [`DebugDraw::frustum`] is drawn at, the infinite reversed
+    /// projections of the app have their far plane at 0.
+    pub const FRUSTUM_FAR_DEPTH: f32 = 1e-3;
+
+    pub fn new(gpu: Arc<Gpu>, camera: &CameraUniformBinding) -> Self {
+        let device = gpu.device();
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Debug Draw Shader"),
+            source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed(include_str!(
+                "debug_draw.wgsl"
+            ))),
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Debug Draw Pipeline Layout"),
+            bind_group_layouts: &[&camera.bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = |label, depth_compare| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(&layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: "vs_main",
+                    buffers: &[wgpu::VertexBufferLayout {
+                        array_stride: size_of::<DebugVertex>() as _,
+                        step_mode: wgpu::VertexStepMode::Vertex,
+                        attributes: &wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3],
+                    }],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: "fs_main",
+                    targets: &[Some(ViewTarget::FORMAT.into())],
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::LineList,
+                    ..Default::default()
+                },
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: GBuffer::DEPTH_FORMAT,
+                    depth_write_enabled: false,
+                    depth_compare,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+            })
+        };
+        let pipelines = [
+            pipeline("Debug Draw Pipeline", wgpu::CompareFunction::GreaterEqual),
+            pipeline("Debug Draw Overlay Pipeline", wgpu::CompareFunction::Always),
+        ];
+        let buffer = device.create_resizable_buffer(wgpu::BufferUsages::VERTEX);
+
+        Self {
+            gpu,
+            depth_test: true,
+            vertices: [vec![], vec![]],
+            buffer,
+            pipelines,
+        }
+    }
+
+    pub fn set_depth_test(&mut self, enabled: bool) {
+        self.depth_test = enabled;
+    }
+
+    pub fn depth_test(&self) -> bool {
+        self.depth_test
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vertices.iter().all(Vec::is_empty)
+    }
+
+    /// Drops the shapes added since the last frame.
+    pub fn clear(&mut self) {
+        self.vertices.iter_mut().for_each(Vec::clear);
+    }
+
+    pub fn line(&mut self, start: Vec3, end: Vec3, color: Vec3) {
+        let vertices = &mut self.vertices[!self.depth_test as usize];
+        for position in [start, end] {
+            vertices.push(DebugVertex {
+                position: position.to_array(),
+                color: color.to_array(),
+            });
+        }
+    }
+
+    pub fn aabb(&mut self, min: Vec3, max: Vec3, color: Vec3) {
+        let corners: [Vec3; 8] = std::array::from_fn(|i| {
+            vec3(
+                if i & 1 == 0 { min.x } else { max.x },
+                if i & 2 == 0 { min.y } else { max.y },
+                if i & 4 == 0 { min.z } else { max.z },
+            )
+        });
+        self.edges(&corners, color);
+    }
+
+    /// Three great circles around the axes.
+    pub fn sphere(&mut self, center: Vec3, radius: f32, color: Vec3) {
+        let point = |i: usize| {
+            let (sin, cos) = (i as f32 / Self::SPHERE_SEGMENTS as f32 * TAU).sin_cos();
+            (cos * radius, sin * radius)
+        };
+        for i in 0..Self::SPHERE_SEGMENTS {
+            let ((x0, y0), (x1, y1)) = (point(i), point(i + 1));
+            self.line(center + vec3(x0, y0, 0.), center + vec3(x1, y1, 0.), color);
+            self.line(center + vec3(0., x0, y0), center + vec3(0., x1, y1), color);
+            self.line(center + vec3(x0, 0., y0), center + vec3(x1, 0., y1), color);
+        }
+    }
+
+    /// Volume seen through `view_proj`, from the near plane to [`Self::FRUSTUM_FAR_DEPTH`].
+    pub fn frustum(&mut self, view_proj: Mat4, color: Vec3) {
+        let clip_to_world = view_proj.inverse();
+        let corners: [Vec3; 8] = std::array::from_fn(|i| {
+            clip_to_world.project_point3(vec3(
+                if i & 1 == 0 { -1. } else { 1. },
+                if i & 2 == 0 { -1. } else { 1. },
+                if i & 4 == 0 {
+                    1.
+                } else {
+                    Self::FRUSTUM_FAR_DEPTH
+                },
+            ))
+        });
+        self.edges(&corners, color);
+    }
+
+    /// X, y and z of `transform` in red, green and blue, `size` long before the transform.
+    pub fn axes(&mut self, transform: Mat4, size: f32) {
+        let origin = transform.transform_point3(Vec3::ZERO);
+        for axis in [Vec3::X, Vec3::Y, Vec3::Z] {
+            self.line(origin, transform.transform_point3(axis * size), axis);
+        }
+    }
+
+    // Edges of a box with the corner bits ordered x, y, z.
+    fn edges(&mut self, corners: &[Vec3; 8], color: Vec3) {
+        for i in 0..8 {
+            for bit in [1, 2, 4] {
+                if i & bit == 0 {
+                    self.line(corners[i], corners[i | bit], color);
+                }
+            }
+        }
+    }
+
+    pub(crate) fn draw(
+        &mut self,
+        camera: &CameraUniformBinding,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &wgpu::TextureView,
+        depth: &wgpu::TextureView,
+    ) {
+        if self.is_empty() {
+            return;
+        }
+        let tested = self.vertices[0].len() as u32;
+        let vertices: Vec<_> = self.vertices.iter_mut().flat_map(|v| v.drain(..)).collect();
+        self.buffer.clear();
+        self.buffer.push(&self.gpu, &vertices);
+
+        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Debug Draw Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: depth,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: true,
+                }),
+                stencil_ops: None,
+            }),
+        });
+        rpass.set_bind_group(0, camera.binding(), &[]);
+        rpass.set_vertex_buffer(0, self.buffer.full_slice());
+        for (pipeline, range) in self
+            .pipelines
+            .iter()
+            .zip([0..tested, tested..vertices.len() as u32])
+        {
+            if !range.is_empty() {
+                rpass.set_pipeline(pipeline);
+                rpass.draw(range, 0..1);
+            }
+        }
+    }
+}
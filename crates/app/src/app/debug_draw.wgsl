@@ -0,0 +1,41 @@
+// Lines of `app::debug_draw::DebugDraw`, drawn over the post processed frame.
+
+// Mirrors `Camera` of `shaders/shared.wgsl`.
+struct Camera {
+	position: vec4<f32>,
+	proj: mat4x4<f32>,
+	view: mat4x4<f32>,
+	clip_to_world: mat4x4<f32>,
+	prev_world_to_clip: mat4x4<f32>,
+	frustum_planes: array<vec4<f32>, 6>,
+	zfar: f32, znear: f32,
+	jitter: vec2<f32>,
+	prev_jitter: vec2<f32>,
+	mip_bias: f32,
+	padding: f32,
+}
+
+@group(0) @binding(0) var<uniform> camera: Camera;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) color: vec3<f32>,
+};
+
+@vertex
+fn vs_main(
+    @location(0) position: vec3<f32>,
+    @location(1) color: vec3<f32>,
+) -> VertexOutput {
+    var out: VertexOutput;
+    out.position = camera.proj * camera.view * vec4(position, 1.0);
+    // The frame is resolved already, the TAA jitter would only make the lines shimmer.
+    out.position += vec4(camera.jitter * out.position.w, 0.0, 0.0);
+    out.color = color;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4(in.color, 1.0);
+}
@@ -0,0 +1,94 @@
+use std::fmt::Display;
+
+/// Command line flag making [`crate::run`] print [`Diagnostics`] and exit before creating
+/// the device.
+pub const DIAGNOSTICS_FLAG: &str = "--diagnostics";
+
+/// What the adapter picked by [`crate::App`] offers and which of the optional renderer
+/// paths it turns on.
+pub struct Diagnostics {
+    info: wgpu::AdapterInfo,
+    limits: wgpu::Limits,
+    features: wgpu::Features,
+    surface: Option<wgpu::SurfaceCapabilities>,
+}
+
+impl Diagnostics {
+    pub fn requested() -> bool {
+        std::env::args().skip(1).any(|arg| arg == DIAGNOSTICS_FLAG)
+    }
+
+    pub fn new(adapter: &wgpu::Adapter, surface: Option<&wgpu::Surface>) -> Self {
+        Self {
+            info: adapter.get_info(),
+            limits: adapter.limits(),
+            features: crate::App::device_features(adapter),
+            surface: surface.map(|surface| surface.get_capabilities(adapter)),
+        }
+    }
+
+    /// Optional paths of the renderer and whether the device enables them.
+    pub fn renderer_features(&self) -> Vec<(&'static str, bool)> {
+        use wgpu::Features as F;
+        let has = |features: F| self.features.contains(features);
+        vec![
+            (
+                "Bindless textures",
+                has(F::TEXTURE_BINDING_ARRAY
+                    | F::SAMPLED_TEXTURE_AND_STORAGE_BUFFER_ARRAY_NON_UNIFORM_INDEXING),
+            ),
+            ("Push constants", has(F::PUSH_CONSTANTS)),
+            ("GPU timestamps", has(F::TIMESTAMP_QUERY)),
+            ("Multi draw indirect", has(F::MULTI_DRAW_INDIRECT)),
+            ("Primitive index", has(F::SHADER_PRIMITIVE_INDEX)),
+            ("BC textures", has(F::TEXTURE_COMPRESSION_BC)),
+            ("ASTC textures", has(F::TEXTURE_COMPRESSION_ASTC)),
+            // wgpu 0.17 exposes no ray queries, rays walk the BVH in compute shaders.
+            ("Ray query", false),
+        ]
+    }
+}
+
+impl Display for Diagnostics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let info = &self.info;
+        writeln!(
+            f,
+            "Adapter: {} ({:?}, {:?})",
+            info.name, info.device_type, info.backend
+        )?;
+        writeln!(f, "Driver: {} {}", info.driver, info.driver_info)?;
+        writeln!(
+            f,
+            "Vendor: {:#06x} Device: {:#06x}",
+            info.vendor, info.device
+        )?;
+
+        writeln!(f, "\nRenderer features:")?;
+        for (name, enabled) in self.renderer_features() {
+            let state = if enabled { "enabled" } else { "unavailable" };
+            writeln!(f, "  {name}: {state}")?;
+        }
+
+        let limits = &self.limits;
+        writeln!(f, "\nTexture sizes:")?;
+        writeln!(f, "  1D: {}", limits.max_texture_dimension_1d)?;
+        writeln!(f, "  2D: {}", limits.max_texture_dimension_2d)?;
+        writeln!(f, "  3D: {}", limits.max_texture_dimension_3d)?;
+        writeln!(f, "  Array layers: {}", limits.max_texture_array_layers)?;
+
+        writeln!(f, "\nSurface:")?;
+        match &self.surface {
+            Some(caps) => {
+                writeln!(f, "  Formats: {:?}", caps.formats)?;
+                writeln!(f, "  Present modes: {:?}", caps.present_modes)?;
+                writeln!(f, "  Alpha modes: {:?}", caps.alpha_modes)?;
+            }
+            None => writeln!(f, "  None, running headless")?,
+        }
+
+        writeln!(f, "\nFeatures: {:?}", self.features)?;
+        writeln!(f, "\nLimits: {limits:#?}")?;
+        Ok(())
+    }
+}
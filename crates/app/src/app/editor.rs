@@ -0,0 +1,241 @@
+use glam::Vec3;
+
+use components::InstanceId;
+#[cfg(feature = "egui")]
+use components::{world::World, Camera};
+#[cfg(feature = "egui")]
+use glam::{Mat4, Quat, Vec2};
+
+use crate::pass::picking::PickHandle;
+#[cfg(feature = "egui")]
+use crate::{pass::picking::Picking, InstancePool, MeshPool};
+
+/// What dragging a gizmo handle changes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GizmoMode {
+    #[default]
+    Translate,
+    Rotate,
+    Scale,
+}
+
+/// Picks instances by clicking them and moves the selected one with gizmo handles,
+/// writing the transform back into the [`crate::InstancePool`].
+///
+/// Clicks are resolved by the [`crate::pass::picking::Picking`] of the world.
+/// Only dynamic instances can be moved, static ones are baked into the TLAS.
+#[derive(Debug, Default)]
+pub struct Editor {
+    pub enabled: bool,
+    pub mode: GizmoMode,
+    selected: Option<InstanceId>,
+    pending: Option<PickHandle>,
+}
+
+#[cfg_attr(not(feature = "egui"), allow(dead_code))]
+impl Editor {
+    /// On screen length of the handles, in points.
+    const HANDLE_LENGTH: f32 = 96.;
+    const HANDLE_RADIUS: f32 = 7.;
+    const AXES: [(Vec3, [u8; 3]); 3] = [
+        (Vec3::X, [230, 70, 70]),
+        (Vec3::Y, [70, 200, 70]),
+        (Vec3::Z, [80, 120, 240]),
+    ];
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn selected(&self) -> Option<InstanceId> {
+        self.selected
+    }
+
+    pub fn select(&mut self, selected: Option<InstanceId>) {
+        self.selected = selected;
+    }
+}
+
+#[cfg(feature = "egui")]
+impl Editor {
+    /// Handles clicks and drags, draws the selection with its gizmo and the editor window.
+    pub fn ui(&mut self, ctx: &egui::Context, camera: &Camera, world: &World) {
+        if let Some(picked) = self.pending.as_ref().and_then(PickHandle::poll) {
+            self.selected = picked;
+            self.pending = None;
+        }
+        if !self.enabled {
+            return;
+        }
+
+        let screen = ctx.screen_rect();
+        let clicked = ctx.input(|input| {
+            input
+                .pointer
+                .primary_clicked()
+                .then(|| input.pointer.interact_pos())
+                .flatten()
+        });
+        let mut instances = world.unwrap_mut::<InstancePool>();
+        self.selected = self.selected.filter(|&id| instances.contains(id));
+
+        egui::Window::new("Editor").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.selectable_value(&mut self.mode, GizmoMode::Translate, "Translate");
+                ui.selectable_value(&mut self.mode, GizmoMode::Rotate, "Rotate");
+                ui.selectable_value(&mut self.mode, GizmoMode::Scale, "Scale");
+            });
+            let Some(id) = self.selected else {
+                ui.label("Click an instance to select it");
+                return;
+            };
+            let instance = instances.instances_data[id.0 as usize];
+            ui.label(format!(
+                "Instance {} mesh {} material {}",
+                id.0, instance.mesh.0, instance.material.0
+            ));
+            if !instance.is_dynamic() {
+                ui.label("Static, add it with `Instance::dynamic` to move it");
+                return;
+            }
+            let (scale, rotation, translation) = instance.transform.to_scale_rotation_translation();
+            let (mut scale, mut translation) = (scale.to_array(), translation.to_array());
+            let (y, x, z) = rotation.to_euler(glam::EulerRot::YXZ);
+            let mut euler = [x, y, z].map(f32::to_degrees);
+            let mut changed = false;
+            for (label, values, speed) in [
+                ("Translation", &mut translation, 0.05),
+                ("Rotation", &mut euler, 0.5),
+                ("Scale", &mut scale, 0.01),
+            ] {
+                ui.horizontal(|ui| {
+                    ui.label(label);
+                    for value in values.iter_mut() {
+                        changed |= ui.add(egui::DragValue::new(value).speed(speed)).changed();
+                    }
+                });
+            }
+            if changed {
+                let [x, y, z] = euler.map(f32::to_radians);
+                let rotation = Quat::from_euler(glam::EulerRot::YXZ, y, x, z);
+                let transform = Mat4::from_scale_rotation_translation(
+                    scale.into(),
+                    rotation,
+                    translation.into(),
+                );
+                instances.set_transform(id, transform);
+            }
+            if ui.button("Deselect").clicked() {
+                self.selected = None;
+            }
+        });
+
+        // Clicks on windows or on the handles below don't pick.
+        let over_ui = ctx.is_pointer_over_area();
+        if let (Some(pos), false) = (clicked, over_ui) {
+            let pos = (pos - screen.min) * ctx.pixels_per_point();
+            self.pending = Some(world.unwrap::<Picking>().pick(Vec2::new(pos.x, pos.y)));
+        }
+
+        let Some(id) = self.selected else {
+            return;
+        };
+        let instance = instances.instances_data[id.0 as usize];
+        let meshes = world.unwrap::<MeshPool>();
+        let Some(mesh) = meshes.mesh_info_cpu.get(instance.mesh.0 as usize) else {
+            return;
+        };
+        let (proj, view) = camera.build_projection_view_matrix();
+        let view_proj = proj * view;
+        // Screen position in points and the clip w, `None` behind the camera.
+        let project = |pos: Vec3| {
+            let clip = view_proj * pos.extend(1.);
+            (clip.w > 1e-4).then(|| {
+                let ndc = clip.truncate() / clip.w;
+                let pos = egui::pos2(0.5 + 0.5 * ndc.x, 0.5 - 0.5 * ndc.y);
+                (screen.min + pos.to_vec2() * screen.size(), clip.w)
+            })
+        };
+        let painter = ctx.layer_painter(egui::LayerId::new(
+            egui::Order::Foreground,
+            egui::Id::new("editor_gizmo"),
+        ));
+
+        let transform = instance.transform;
+        let (scale, rotation, translation) = transform.to_scale_rotation_translation();
+        let center = transform.transform_point3(mesh.center);
+        let Some((origin, w)) = project(center) else {
+            return;
+        };
+        let points_per_unit = 0.5 * screen.height() * proj.y_axis.y / w;
+        let radius = mesh.radius * scale.max_element() * points_per_unit;
+        painter.circle_stroke(origin, radius, egui::Stroke::new(2., egui::Color32::YELLOW));
+        if !instance.is_dynamic() {
+            return;
+        }
+
+        let length = Self::HANDLE_LENGTH / points_per_unit;
+        for (index, (axis, [r, g, b])) in Self::AXES.into_iter().enumerate() {
+            // Rotation and scale work in the instance's own axes.
+            let axis = match self.mode {
+                GizmoMode::Translate => axis,
+                GizmoMode::Rotate | GizmoMode::Scale => rotation * axis,
+            };
+            let Some((end, _)) = project(center + axis * length) else {
+                continue;
+            };
+            let color = egui::Color32::from_rgb(r, g, b);
+            painter.line_segment([origin, end], egui::Stroke::new(3., color));
+            match self.mode {
+                GizmoMode::Translate => painter.circle_filled(end, Self::HANDLE_RADIUS, color),
+                GizmoMode::Rotate => {
+                    painter.circle_stroke(end, Self::HANDLE_RADIUS, egui::Stroke::new(3., color))
+                }
+                GizmoMode::Scale => painter.rect_filled(
+                    egui::Rect::from_center_size(end, egui::Vec2::splat(2. * Self::HANDLE_RADIUS)),
+                    0.,
+                    color,
+                ),
+            }
+
+            let handle =
+                egui::Rect::from_center_size(end, egui::Vec2::splat(3. * Self::HANDLE_RADIUS));
+            let response = egui::Area::new(egui::Id::new(("editor_handle", index)))
+                .fixed_pos(handle.min)
+                .order(egui::Order::Foreground)
+                .show(ctx, |ui| {
+                    ui.allocate_response(handle.size(), egui::Sense::drag())
+                })
+                .inner;
+            let delta = response.drag_delta();
+            if delta == egui::Vec2::ZERO {
+                continue;
+            }
+            let on_screen = end - origin;
+            // Share of the handle length the pointer moved along it.
+            let along = delta.dot(on_screen) / on_screen.length_sq().max(1.);
+            let transform = match self.mode {
+                GizmoMode::Translate => Mat4::from_scale_rotation_translation(
+                    scale,
+                    rotation,
+                    translation + axis * along * length,
+                ),
+                GizmoMode::Rotate => {
+                    let across = egui::vec2(-on_screen.y, on_screen.x);
+                    let angle = delta.dot(across) / across.length().max(1.) * 0.02;
+                    // Spins around the bounding sphere center, not the pivot.
+                    let spin = Mat4::from_translation(center)
+                        * Mat4::from_axis_angle(axis, angle)
+                        * Mat4::from_translation(-center);
+                    spin * transform
+                }
+                GizmoMode::Scale => {
+                    let mut factor = Vec3::ONE;
+                    factor[index] = (1. + along).max(0.05);
+                    Mat4::from_scale_rotation_translation(scale * factor, rotation, translation)
+                }
+            };
+            instances.set_transform(id, transform);
+        }
+    }
+}
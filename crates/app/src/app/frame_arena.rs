@@ -0,0 +1,237 @@
+use std::sync::Arc;
+
+use super::App;
+use bytemuck::Pod;
+use color_eyre::{eyre::bail, Result};
+use components::{
+    bind_group_layout::{BindGroupLayout, WrappedBindGroupLayout},
+    Gpu,
+};
+
+/// Transient uniform and storage allocations that live for a single frame.
+///
+/// Allocations are sub-ranges of one ring buffer, split into a segment per
+/// frame in flight, and are bound through a shared bind group with a dynamic
+/// offset, so no buffers or bind groups are created per draw.
+///
+/// Every binding is a fixed window of [`FrameArena::UNIFORM_BINDING_SIZE`] or
+/// [`FrameArena::STORAGE_BINDING_SIZE`] bytes whatever was allocated, so shaders must not
+/// call `arrayLength` on arena storage, it returns the window. Pass
+/// [`FrameAllocation::len`] along instead.
+pub struct FrameArena {
+    gpu: Arc<Gpu>,
+    buffer: wgpu::Buffer,
+    segment_size: u64,
+    alignment: u64,
+    frame: u64,
+    offset: u64,
+
+    pub uniform_layout: BindGroupLayout,
+    pub storage_layout: BindGroupLayout,
+    uniform_bind_group: Arc<wgpu::BindGroup>,
+    storage_bind_group: Arc<wgpu::BindGroup>,
+}
+
+/// A slice of the frame arena, valid until the end of the frame it was made in.
+pub struct FrameAllocation {
+    pub offset: u32,
+    pub size: u64,
+    /// Elements written, 1 for uniforms.
+    pub len: u32,
+    bind_group: Arc<wgpu::BindGroup>,
+}
+
+impl FrameAllocation {
+    pub fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+
+    pub fn dynamic_offsets(&self) -> [u32; 1] {
+        [self.offset]
+    }
+}
+
+impl FrameArena {
+    pub const UNIFORM_BINDING_SIZE: u64 = 1024;
+    pub const STORAGE_BINDING_SIZE: u64 = 64 * 1024;
+
+    const INITIAL_SEGMENT_SIZE: u64 = 256 * 1024;
+
+    pub fn new(gpu: Arc<Gpu>) -> Self {
+        let uniform_layout =
+            gpu.device()
+                .create_bind_group_layout_wrap(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some("Frame Arena Uniform Bind Group Layout"),
+                    entries: &[wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::VERTEX_FRAGMENT
+                            | wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: true,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    }],
+                });
+        let storage_layout =
+            gpu.device()
+                .create_bind_group_layout_wrap(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some("Frame Arena Storage Bind Group Layout"),
+                    entries: &[wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::VERTEX_FRAGMENT
+                            | wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: true },
+                            has_dynamic_offset: true,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    }],
+                });
+
+        let limits = gpu.device().limits();
+        let alignment = (limits.min_uniform_buffer_offset_alignment as u64)
+            .max(limits.min_storage_buffer_offset_alignment as u64);
+
+        let segment_size = Self::INITIAL_SEGMENT_SIZE;
+        let (buffer, uniform_bind_group, storage_bind_group) =
+            Self::create_buffer(&gpu, &uniform_layout, &storage_layout, segment_size);
+
+        Self {
+            gpu,
+            buffer,
+            segment_size,
+            alignment,
+            frame: 0,
+            offset: 0,
+
+            uniform_layout,
+            storage_layout,
+            uniform_bind_group,
+            storage_bind_group,
+        }
+    }
+
+    fn create_buffer(
+        gpu: &Gpu,
+        uniform_layout: &wgpu::BindGroupLayout,
+        storage_layout: &wgpu::BindGroupLayout,
+        segment_size: u64,
+    ) -> (wgpu::Buffer, Arc<wgpu::BindGroup>, Arc<wgpu::BindGroup>) {
+        // Tail padding keeps the fixed size bindings of the last allocation in bounds.
+        let size = segment_size * App::MAX_FRAMES_IN_FLIGHT as u64 + Self::STORAGE_BINDING_SIZE;
+        let buffer = gpu.device().create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Frame Arena Buffer"),
+            size,
+            usage: wgpu::BufferUsages::UNIFORM
+                | wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = |label, layout, size| {
+            Arc::new(gpu.device().create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some(label),
+                layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                        buffer: &buffer,
+                        offset: 0,
+                        size: wgpu::BufferSize::new(size),
+                    }),
+                }],
+            }))
+        };
+        let uniform_bind_group = bind_group(
+            "Frame Arena Uniform Bind Group",
+            uniform_layout,
+            Self::UNIFORM_BINDING_SIZE,
+        );
+        let storage_bind_group = bind_group(
+            "Frame Arena Storage Bind Group",
+            storage_layout,
+            Self::STORAGE_BINDING_SIZE,
+        );
+        (buffer, uniform_bind_group, storage_bind_group)
+    }
+
+    /// Starts a new frame, the segment of the oldest frame in flight gets reused.
+    pub fn next_frame(&mut self) {
+        self.frame += 1;
+        self.offset = 0;
+    }
+
+    /// Fails for values larger than [`Self::UNIFORM_BINDING_SIZE`], they need a buffer
+    /// of their own.
+    pub fn alloc_uniform<T: Pod>(&mut self, value: &T) -> Result<FrameAllocation> {
+        let bytes = bytemuck::bytes_of(value);
+        if bytes.len() as u64 > Self::UNIFORM_BINDING_SIZE {
+            bail!(
+                "Frame uniform allocation of {} bytes exceeds {} bytes",
+                bytes.len(),
+                Self::UNIFORM_BINDING_SIZE
+            );
+        }
+        let offset = self.write(bytes);
+        Ok(FrameAllocation {
+            offset,
+            size: bytes.len() as u64,
+            len: 1,
+            bind_group: self.uniform_bind_group.clone(),
+        })
+    }
+
+    /// Fails for slices larger than [`Self::STORAGE_BINDING_SIZE`], they need a buffer
+    /// of their own.
+    pub fn alloc_storage<T: Pod>(&mut self, values: &[T]) -> Result<FrameAllocation> {
+        let bytes = bytemuck::cast_slice(values);
+        if bytes.len() as u64 > Self::STORAGE_BINDING_SIZE {
+            bail!(
+                "Frame storage allocation of {} bytes exceeds {} bytes",
+                bytes.len(),
+                Self::STORAGE_BINDING_SIZE
+            );
+        }
+        let offset = self.write(bytes);
+        Ok(FrameAllocation {
+            offset,
+            size: bytes.len() as u64,
+            len: values.len() as u32,
+            bind_group: self.storage_bind_group.clone(),
+        })
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> u32 {
+        let size = (bytes.len() as u64).next_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT);
+        let mut start = self.offset.next_multiple_of(self.alignment);
+        if start + size > self.segment_size {
+            // Already recorded allocations keep the old buffer alive through their bind groups.
+            self.segment_size = (self.segment_size * 2).max(size.next_power_of_two());
+            log::info!("Frame arena grew to {} bytes per frame", self.segment_size);
+            (
+                self.buffer,
+                self.uniform_bind_group,
+                self.storage_bind_group,
+            ) = Self::create_buffer(
+                &self.gpu,
+                &self.uniform_layout,
+                &self.storage_layout,
+                self.segment_size,
+            );
+            start = 0;
+        }
+        self.offset = start + size;
+
+        let offset = (self.frame % App::MAX_FRAMES_IN_FLIGHT as u64) * self.segment_size + start;
+        if bytes.len() as u64 == size {
+            self.gpu.queue().write_buffer(&self.buffer, offset, bytes);
+        } else {
+            let mut padded = bytes.to_vec();
+            padded.resize(size as usize, 0);
+            self.gpu.queue().write_buffer(&self.buffer, offset, &padded);
+        }
+        offset as u32
+    }
+}
@@ -0,0 +1,172 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+    sync::{mpsc, Arc},
+};
+
+use color_eyre::{eyre::WrapErr, Result};
+use wgpu::MapMode;
+
+use components::{
+    bind_group_layout::{SingleTextureBindGroupLayout, StorageWriteBindGroupLayout},
+    world::World,
+};
+
+use super::pipeline::{ComputeHandle, ComputePipelineDescriptor, PipelineArena};
+
+/// Environment variable enabling [`FrameHasher`] in [`crate::run`].
+///
+/// The value is a file receiving a `frame,hash` line per frame, empty only logs the hashes.
+pub const FRAME_HASH_ENV: &str = "VOIDIN_FRAME_HASH";
+
+/// Hashes every final frame on the gpu, so refactors meant to be bit-exact can be
+/// checked by comparing the hash logs of two runs.
+///
+/// Texel bits are reduced into 8 bytes which are read back after the frame is submitted.
+/// The ui is not drawn while hashing, and the scene has to be deterministic itself,
+/// e.g. with a fixed `VOIDIN_SEED` and no wall clock driven animation.
+pub struct FrameHasher {
+    pipeline: ComputeHandle,
+    result: wgpu::Buffer,
+    result_bind_group: wgpu::BindGroup,
+    sender: mpsc::Sender<(u64, u64)>,
+    receiver: mpsc::Receiver<(u64, u64)>,
+    record: Option<BufWriter<File>>,
+    last_hash: Option<(u64, u64)>,
+}
+
+impl FrameHasher {
+    const RESULT_SIZE: u64 = 2 * std::mem::size_of::<u32>() as u64;
+
+    pub fn new(world: &World, record: Option<&Path>) -> Result<Self> {
+        let record = record
+            .map(|path| {
+                File::create(path)
+                    .map(BufWriter::new)
+                    .wrap_err_with(|| format!("Failed to create {}", path.display()))
+            })
+            .transpose()?;
+
+        let result_layout = world.get::<StorageWriteBindGroupLayout<u32>>()?;
+        let texture_layout = world.get::<SingleTextureBindGroupLayout>()?;
+        let pipeline = world
+            .get_mut::<PipelineArena>()?
+            .process_compute_pipeline_from_path(
+                Path::new("shaders").join("frame_hash.wgsl"),
+                ComputePipelineDescriptor {
+                    label: Some("Frame Hash Pipeline".into()),
+                    layout: vec![result_layout.layout.clone(), texture_layout.layout.clone()],
+                    ..Default::default()
+                },
+            )?;
+
+        let result = world.device().create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Frame Hash"),
+            size: Self::RESULT_SIZE,
+            usage: wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::COPY_SRC
+                | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let result_bind_group = world
+            .device()
+            .create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Frame Hash Bind Group"),
+                layout: &result_layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: result.as_entire_binding(),
+                }],
+            });
+
+        let (sender, receiver) = mpsc::channel();
+        Ok(Self {
+            pipeline,
+            result,
+            result_bind_group,
+            sender,
+            receiver,
+            record,
+            last_hash: None,
+        })
+    }
+
+    pub fn from_env(world: &World) -> Result<Option<Self>> {
+        match std::env::var_os(FRAME_HASH_ENV) {
+            Some(path) if path.is_empty() => Self::new(world, None).map(Some),
+            Some(path) => Self::new(world, Some(Path::new(&path))).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Records hashing of `source` into `encoder`. The returned closure
+    /// has to be called after the encoder is submitted to map the result.
+    pub fn hash_frame(
+        &self,
+        world: &World,
+        encoder: &mut wgpu::CommandEncoder,
+        source: &wgpu::BindGroup,
+        (width, height): (u32, u32),
+        frame: u64,
+    ) -> impl FnOnce() {
+        encoder.clear_buffer(&self.result, 0, None);
+        {
+            let arena = world.unwrap::<PipelineArena>();
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Frame Hash Pass"),
+            });
+            pass.set_pipeline(arena.get_pipeline(self.pipeline));
+            pass.set_bind_group(0, &self.result_bind_group, &[]);
+            pass.set_bind_group(1, source, &[]);
+            pass.dispatch_workgroups((width + 7) / 8, (height + 7) / 8, 1);
+        }
+
+        let download = Arc::new(world.device().create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Frame Hash Download"),
+            size: Self::RESULT_SIZE,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        }));
+        encoder.copy_buffer_to_buffer(&self.result, 0, &download, 0, Self::RESULT_SIZE);
+
+        let sender = self.sender.clone();
+        move || {
+            let buffer = download.clone();
+            download.slice(..).map_async(MapMode::Read, move |res| {
+                if let Err(err) = res {
+                    log::error!("Failed to map frame hash: {err}");
+                    return;
+                }
+                let words: [u32; 2] =
+                    bytemuck::pod_read_unaligned(&buffer.slice(..).get_mapped_range());
+                let hash = (words[0] as u64) << 32 | words[1] as u64;
+                let _ = sender.send((frame, hash));
+            });
+        }
+    }
+
+    /// Logs and records the hashes of frames finished since the last call.
+    pub fn poll(&mut self) {
+        let mut received = false;
+        while let Ok((frame, hash)) = self.receiver.try_recv() {
+            log::info!("Frame {frame}: {hash:016x}");
+            if let Some(record) = &mut self.record {
+                let _ = writeln!(record, "{frame},{hash:016x}");
+            }
+            self.last_hash = Some((frame, hash));
+            received = true;
+        }
+        if let (true, Some(record)) = (received, &mut self.record) {
+            if let Err(err) = record.flush() {
+                log::error!("Failed to record frame hashes: {err}");
+                self.record = None;
+            }
+        }
+    }
+
+    /// Latest frame with a known hash.
+    pub fn last_hash(&self) -> Option<(u64, u64)> {
+        self.last_hash
+    }
+}
@@ -2,88 +2,183 @@ use crate::Gpu;
 
 use components::bind_group_layout::{self, WrappedBindGroupLayout};
 
+/// Packed normal, uv and material id plus depth, the layout lives in `shaders/utils/gbuffer.wgsl`.
+///
+/// `history_reject` is non zero where the instance changed its mesh or material this frame,
+/// `instance_ids` holds the index + 1 of the instance covering each pixel, zero for none.
+/// `overdraw` counts the fragments drawn per pixel, only filled while the
+/// [`crate::pass::postprocess::DebugView::Overdraw`] view is on.
 pub struct GBuffer {
-    pub normal_uv: wgpu::TextureView,
-    pub material: wgpu::TextureView,
+    pub packed: wgpu::TextureView,
+    pub history_reject: wgpu::TextureView,
+    pub instance_ids: wgpu::TextureView,
+    instance_ids_texture: wgpu::Texture,
+    pub overdraw: wgpu::TextureView,
     pub depth: wgpu::TextureView,
+    size: (u32, u32),
 
     pub bind_group: wgpu::BindGroup,
     pub bind_group_layout: bind_group_layout::BindGroupLayout,
 }
 
 impl GBuffer {
-    pub const NORMAL_UV_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rg32Uint;
-    pub const MATERIAL_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R8Uint;
+    pub const PACKED_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rg32Uint;
+    pub const HISTORY_REJECT_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R8Uint;
+    pub const INSTANCE_ID_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R32Uint;
+    pub const OVERDRAW_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R16Float;
     pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth24Plus;
     pub const fn color_target_state() -> &'static [Option<wgpu::ColorTargetState>] {
         &[
             Some(wgpu::ColorTargetState {
-                format: Self::NORMAL_UV_FORMAT,
+                format: Self::PACKED_FORMAT,
                 blend: None,
                 write_mask: wgpu::ColorWrites::ALL,
             }),
             Some(wgpu::ColorTargetState {
-                format: Self::MATERIAL_FORMAT,
+                format: Self::HISTORY_REJECT_FORMAT,
+                blend: None,
+                write_mask: wgpu::ColorWrites::ALL,
+            }),
+            Some(wgpu::ColorTargetState {
+                format: Self::INSTANCE_ID_FORMAT,
                 blend: None,
                 write_mask: wgpu::ColorWrites::ALL,
             }),
         ]
     }
 
-    pub fn color_target_attachment(&self) -> [Option<wgpu::RenderPassColorAttachment>; 2] {
-        [&self.normal_uv, &self.material].map(|view| {
+    /// Adds up one per fragment.
+    pub const fn overdraw_target_state() -> &'static [Option<wgpu::ColorTargetState>] {
+        const ADD: wgpu::BlendComponent = wgpu::BlendComponent {
+            src_factor: wgpu::BlendFactor::One,
+            dst_factor: wgpu::BlendFactor::One,
+            operation: wgpu::BlendOperation::Add,
+        };
+        &[Some(wgpu::ColorTargetState {
+            format: Self::OVERDRAW_FORMAT,
+            blend: Some(wgpu::BlendState {
+                color: ADD,
+                alpha: ADD,
+            }),
+            write_mask: wgpu::ColorWrites::ALL,
+        })]
+    }
+
+    pub fn color_target_attachment(&self) -> [Option<wgpu::RenderPassColorAttachment>; 3] {
+        [
             Some(wgpu::RenderPassColorAttachment {
-                view,
+                view: &self.packed,
                 resolve_target: None,
                 ops: wgpu::Operations {
                     load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
                     store: true,
                 },
-            })
-        })
+            }),
+            Some(self.history_reject_attachment()),
+            Some(self.instance_id_attachment()),
+        ]
     }
 
-    const LAYOUT_DESC: wgpu::BindGroupLayoutDescriptor<'static> = wgpu::BindGroupLayoutDescriptor {
-        label: Some("GBuffer Bind Group Layout"),
-        entries: &[
-            wgpu::BindGroupLayoutEntry {
-                binding: 0,
-                visibility: wgpu::ShaderStages::FRAGMENT.union(wgpu::ShaderStages::COMPUTE),
-                ty: wgpu::BindingType::Texture {
-                    sample_type: wgpu::TextureSampleType::Uint,
-                    view_dimension: wgpu::TextureViewDimension::D2,
-                    multisampled: false,
-                },
-                count: None,
+    pub fn history_reject_attachment(&self) -> wgpu::RenderPassColorAttachment {
+        wgpu::RenderPassColorAttachment {
+            view: &self.history_reject,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                store: true,
             },
-            wgpu::BindGroupLayoutEntry {
-                binding: 1,
-                visibility: wgpu::ShaderStages::FRAGMENT.union(wgpu::ShaderStages::COMPUTE),
-                ty: wgpu::BindingType::Texture {
-                    sample_type: wgpu::TextureSampleType::Uint,
-                    view_dimension: wgpu::TextureViewDimension::D2,
-                    multisampled: false,
-                },
-                count: None,
-            },
-            wgpu::BindGroupLayoutEntry {
-                binding: 2,
-                visibility: wgpu::ShaderStages::FRAGMENT.union(wgpu::ShaderStages::COMPUTE),
-                ty: wgpu::BindingType::Texture {
-                    sample_type: wgpu::TextureSampleType::Depth,
-                    view_dimension: wgpu::TextureViewDimension::D2,
-                    multisampled: false,
-                },
-                count: None,
+        }
+    }
+
+    pub fn instance_id_attachment(&self) -> wgpu::RenderPassColorAttachment {
+        wgpu::RenderPassColorAttachment {
+            view: &self.instance_ids,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                store: true,
             },
-            wgpu::BindGroupLayoutEntry {
-                binding: 3,
-                visibility: wgpu::ShaderStages::FRAGMENT.union(wgpu::ShaderStages::COMPUTE),
-                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                count: None,
+        }
+    }
+
+    pub fn overdraw_attachment(&self) -> [Option<wgpu::RenderPassColorAttachment>; 1] {
+        [Some(wgpu::RenderPassColorAttachment {
+            view: &self.overdraw,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                store: true,
             },
-        ],
-    };
+        })]
+    }
+
+    /// Source of the copies reading back picked instances.
+    pub fn instance_ids_texture(&self) -> &wgpu::Texture {
+        &self.instance_ids_texture
+    }
+
+    pub(crate) const LAYOUT_DESC: wgpu::BindGroupLayoutDescriptor<'static> =
+        wgpu::BindGroupLayoutDescriptor {
+            label: Some("GBuffer Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT.union(wgpu::ShaderStages::COMPUTE),
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Uint,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT.union(wgpu::ShaderStages::COMPUTE),
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Depth,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT.union(wgpu::ShaderStages::COMPUTE),
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT.union(wgpu::ShaderStages::COMPUTE),
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Uint,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: wgpu::ShaderStages::FRAGMENT.union(wgpu::ShaderStages::COMPUTE),
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Uint,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 5,
+                    visibility: wgpu::ShaderStages::FRAGMENT.union(wgpu::ShaderStages::COMPUTE),
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+            ],
+        };
 
     pub fn new(gpu: &Gpu, width: u32, height: u32) -> Self {
         let size = wgpu::Extent3d {
@@ -92,23 +187,41 @@ impl GBuffer {
             depth_or_array_layers: 1,
         };
         let mut desc = wgpu::TextureDescriptor {
-            label: Some("GBuffer: normal and uv"),
+            label: Some("GBuffer: packed"),
             size,
             mip_level_count: 1,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
-            format: Self::NORMAL_UV_FORMAT,
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            format: Self::PACKED_FORMAT,
+            // Storage for the visibility buffer mode, which resolves it in a compute pass.
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::STORAGE_BINDING,
             view_formats: &[],
         };
-        let normal_uv = create_view(gpu, &desc);
+        let packed = create_view(gpu, &desc);
 
-        desc.label = Some("GBuffer: material");
-        desc.format = Self::MATERIAL_FORMAT;
-        let material = create_view(gpu, &desc);
+        desc.label = Some("GBuffer: history reject");
+        desc.format = Self::HISTORY_REJECT_FORMAT;
+        desc.usage = wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING;
+        let history_reject = create_view(gpu, &desc);
+
+        desc.label = Some("GBuffer: instance ids");
+        desc.format = Self::INSTANCE_ID_FORMAT;
+        desc.usage = wgpu::TextureUsages::RENDER_ATTACHMENT
+            | wgpu::TextureUsages::TEXTURE_BINDING
+            | wgpu::TextureUsages::COPY_SRC;
+        let instance_ids_texture = gpu.device().create_texture(&desc);
+        let instance_ids = instance_ids_texture.create_view(&Default::default());
+
+        desc.label = Some("GBuffer: overdraw");
+        desc.format = Self::OVERDRAW_FORMAT;
+        desc.usage = wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING;
+        let overdraw = create_view(gpu, &desc);
 
         desc.label = Some("GBuffer: depth");
         desc.format = Self::DEPTH_FORMAT;
+        desc.usage = wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING;
         let depth_tex = gpu.device().create_texture(&desc);
         let depth = depth_tex.create_view(&Default::default());
 
@@ -126,14 +239,10 @@ impl GBuffer {
             entries: &[
                 wgpu::BindGroupEntry {
                     binding: 0,
-                    resource: wgpu::BindingResource::TextureView(&normal_uv),
+                    resource: wgpu::BindingResource::TextureView(&packed),
                 },
                 wgpu::BindGroupEntry {
                     binding: 1,
-                    resource: wgpu::BindingResource::TextureView(&material),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 2,
                     resource: wgpu::BindingResource::TextureView(&depth_tex.create_view(
                         &wgpu::TextureViewDescriptor {
                             aspect: wgpu::TextureAspect::DepthOnly,
@@ -142,22 +251,42 @@ impl GBuffer {
                     )),
                 },
                 wgpu::BindGroupEntry {
-                    binding: 3,
+                    binding: 2,
                     resource: wgpu::BindingResource::Sampler(&sampler),
                 },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(&history_reject),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: wgpu::BindingResource::TextureView(&instance_ids),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 5,
+                    resource: wgpu::BindingResource::TextureView(&overdraw),
+                },
             ],
         });
 
         Self {
-            normal_uv,
-            material,
+            packed,
+            history_reject,
+            instance_ids,
+            instance_ids_texture,
+            overdraw,
             depth,
+            size: (width, height),
 
             bind_group_layout,
             bind_group,
         }
     }
 
+    pub fn size(&self) -> (u32, u32) {
+        self.size
+    }
+
     pub fn resize(&mut self, gpu: &Gpu, width: u32, height: u32) {
         let mut other = Self::new(gpu, width, height);
         std::mem::swap(self, &mut other);
@@ -6,32 +6,57 @@ use wgpu::util::DeviceExt;
 
 use components::{
     bind_group_layout::{self, WrappedBindGroupLayout},
-    CameraUniform, CameraUniformBinding, Gpu, NonZeroSized,
+    CameraUniform, CameraUniformBinding, Gpu, NonZeroSized, FRAME_SLOTS,
 };
 
+/// Global and camera uniforms in one group, with a bind group per [`FRAME_SLOTS`].
+///
+/// Binding 1 is the main view, binding [`CameraUniformBinding::VIEWS_BINDING`] all views for
+/// passes that pick one with a `view_index` push constant.
 pub struct GlobalsBindGroup {
     pub layout: bind_group_layout::BindGroupLayout,
-    pub binding: wgpu::BindGroup,
+    bindings: Vec<wgpu::BindGroup>,
+    slot: usize,
 }
 
 impl GlobalsBindGroup {
     pub fn new(gpu: &Gpu, globals: &GlobalUniformBinding, camera: &CameraUniformBinding) -> Self {
         let layout = gpu.device().create_bind_group_layout_wrap(&Self::LAYOUT);
-        let binding = gpu.device().create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("Globals Bind Group"),
-            layout: &layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: globals.buffer().as_entire_binding(),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: camera.buffer().as_entire_binding(),
-                },
-            ],
-        });
-        Self { layout, binding }
+        let bindings = globals
+            .buffers()
+            .iter()
+            .zip(camera.buffers())
+            .map(|(globals, camera)| {
+                gpu.device().create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("Globals Bind Group"),
+                    layout: &layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: globals.as_entire_binding(),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: CameraUniformBinding::main_view_binding(camera),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: CameraUniformBinding::VIEWS_BINDING,
+                            resource: camera.as_entire_binding(),
+                        },
+                    ],
+                })
+            })
+            .collect();
+        Self {
+            layout,
+            bindings,
+            slot: 0,
+        }
+    }
+
+    /// Follows the uniforms, both have to be written to `slot` this frame.
+    pub fn set_slot(&mut self, slot: usize) {
+        self.slot = slot % FRAME_SLOTS;
     }
 
     const LAYOUT: wgpu::BindGroupLayoutDescriptor<'static> = wgpu::BindGroupLayoutDescriptor {
@@ -57,18 +82,30 @@ impl GlobalsBindGroup {
                 },
                 count: None,
             },
+            wgpu::BindGroupLayoutEntry {
+                binding: CameraUniformBinding::VIEWS_BINDING,
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT.union(wgpu::ShaderStages::COMPUTE),
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: Some(CameraUniformBinding::VIEWS_SIZE),
+                },
+                count: None,
+            },
         ],
     };
 
     pub fn binding(&self) -> &wgpu::BindGroup {
-        &self.binding
+        &self.bindings[self.slot]
     }
 }
 
+/// Global uniform with a copy per [`FRAME_SLOTS`], each frame writes and binds the next one.
 pub struct GlobalUniformBinding {
-    pub binding: wgpu::BindGroup,
+    bindings: Vec<wgpu::BindGroup>,
     pub layout: bind_group_layout::BindGroupLayout,
-    buffer: wgpu::Buffer,
+    buffers: Vec<wgpu::Buffer>,
+    slot: usize,
 }
 
 impl GlobalUniformBinding {
@@ -87,37 +124,65 @@ impl GlobalUniformBinding {
     };
 
     pub fn new(device: &wgpu::Device) -> Self {
-        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Global Uniform"),
-            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-            contents: bytemuck::bytes_of(&Uniform::default()),
-        });
+        let buffers: Vec<_> = (0..FRAME_SLOTS)
+            .map(|_| {
+                device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("Global Uniform"),
+                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                    contents: bytemuck::bytes_of(&Uniform::default()),
+                })
+            })
+            .collect();
 
         let layout = device.create_bind_group_layout_wrap(&Self::DESC);
-        let uniform = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("Global Uniform Bind Group"),
-            layout: &layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: buffer.as_entire_binding(),
-            }],
-        });
+        let bindings = buffers
+            .iter()
+            .map(|buffer| {
+                device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("Global Uniform Bind Group"),
+                    layout: &layout,
+                    entries: &[wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: buffer.as_entire_binding(),
+                    }],
+                })
+            })
+            .collect();
         Self {
-            binding: uniform,
-            buffer,
+            bindings,
+            buffers,
             layout,
+            slot: 0,
         }
     }
 
+    /// Writes the uniform into the next slot, which no frame in flight reads anymore.
     pub fn update(&mut self, queue: &wgpu::Queue, uniform: &Uniform) {
-        queue.write_buffer(&self.buffer, 0, bytemuck::bytes_of(uniform))
+        self.slot = (self.slot + 1) % FRAME_SLOTS;
+        queue.write_buffer(&self.buffers[self.slot], 0, bytemuck::bytes_of(uniform))
+    }
+
+    /// Bind group of the slot written last.
+    pub fn binding(&self) -> &wgpu::BindGroup {
+        &self.bindings[self.slot]
     }
 
     pub fn buffer(&self) -> &wgpu::Buffer {
-        &self.buffer
+        &self.buffers[self.slot]
+    }
+
+    pub fn buffers(&self) -> &[wgpu::Buffer] {
+        &self.buffers
+    }
+
+    pub fn slot(&self) -> usize {
+        self.slot
     }
 }
 
+/// Number of floats in the user block, see [`super::live_params::LiveParams`].
+pub const USER_PARAMS: usize = 16;
+
 #[repr(C)]
 #[derive(Debug, Clone, Copy, Pod, Zeroable)]
 pub struct Uniform {
@@ -126,6 +191,10 @@ pub struct Uniform {
     pub time: f32,
     pub dt: f32,
     pub custom: f32,
+    /// How far rendering is into the next fixed step, in `0..1`.
+    pub alpha: f32,
+    _padding: f32,
+    pub user: [[f32; 4]; USER_PARAMS / 4],
 }
 
 impl Default for Uniform {
@@ -136,6 +205,9 @@ impl Default for Uniform {
             frame: 0,
             dt: FIXED_TIME_STEP as _,
             custom: 0.,
+            alpha: 0.,
+            _padding: 0.,
+            user: [[0.; 4]; USER_PARAMS / 4],
         }
     }
 }
@@ -0,0 +1,197 @@
+use std::mem::size_of;
+
+use super::view_target::ViewTarget;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct HudGlyph {
+    // Top left corner and size in clip space.
+    origin: [f32; 2],
+    size: [f32; 2],
+    bits: u32,
+    shade: f32,
+}
+
+/// Text overlay with frame stats drawn by its own tiny pipeline, so it keeps working
+/// without the `egui` feature and doesn't depend on the hot reloaded shaders.
+///
+/// Toggled with F2. Only digits, latin letters and a few symbols have glyphs.
+pub struct Hud {
+    enabled: bool,
+    pipeline: wgpu::RenderPipeline,
+    glyphs: wgpu::Buffer,
+}
+
+impl Hud {
+    const MAX_GLYPHS: usize = 1024;
+    /// Screen pixels per font pixel.
+    const SCALE: f32 = 3.;
+    const MARGIN: f32 = 8.;
+
+    pub fn new(device: &wgpu::Device) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Hud Shader"),
+            source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed(include_str!("hud.wgsl"))),
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Hud Pipeline Layout"),
+            bind_group_layouts: &[],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Hud Pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: size_of::<HudGlyph>() as _,
+                    step_mode: wgpu::VertexStepMode::Instance,
+                    attributes: &wgpu::vertex_attr_array![
+                        0 => Float32x2,
+                        1 => Float32x2,
+                        2 => Uint32,
+                        3 => Float32
+                    ],
+                }],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(ViewTarget::FORMAT.into())],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+        let glyphs = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Hud Glyphs"),
+            size: (Self::MAX_GLYPHS * size_of::<HudGlyph>()) as _,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            enabled: false,
+            pipeline,
+            glyphs,
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    /// Writes `lines` into the top left corner of `target`, a view of the [`ViewTarget`].
+    pub fn draw(
+        &self,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &wgpu::TextureView,
+        (width, height): (u32, u32),
+        lines: &[String],
+    ) {
+        if !self.enabled {
+            return;
+        }
+
+        let pixel = [2. / width as f32, -2. / height as f32];
+        let size = [3. * Self::SCALE * pixel[0], 5. * Self::SCALE * pixel[1]];
+        let mut glyphs = vec![];
+        // A dark copy offset by a font pixel keeps the text readable on bright frames.
+        for (offset, shade) in [(Self::SCALE, 0.), (0., 1.)] {
+            for (row, line) in lines.iter().enumerate() {
+                for (col, c) in line.chars().enumerate() {
+                    let bits = glyph_bits(c);
+                    if bits == 0 {
+                        continue;
+                    }
+                    let x = Self::MARGIN + offset + col as f32 * 4. * Self::SCALE;
+                    let y = Self::MARGIN + offset + row as f32 * 7. * Self::SCALE;
+                    glyphs.push(HudGlyph {
+                        origin: [x * pixel[0] - 1., y * pixel[1] + 1.],
+                        size,
+                        bits,
+                        shade,
+                    });
+                }
+            }
+        }
+        glyphs.truncate(Self::MAX_GLYPHS);
+        if glyphs.is_empty() {
+            return;
+        }
+        queue.write_buffer(&self.glyphs, 0, bytemuck::cast_slice(&glyphs));
+
+        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Hud Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+        rpass.set_pipeline(&self.pipeline);
+        rpass.set_vertex_buffer(0, self.glyphs.slice(..));
+        rpass.draw(0..6, 0..glyphs.len() as u32);
+    }
+}
+
+// 3x5 bitmaps, rows from the top, `0` for characters without a glyph.
+fn glyph_bits(c: char) -> u32 {
+    match c.to_ascii_uppercase() {
+        '0' => 0b111_101_101_101_111,
+        '1' => 0b010_110_010_010_111,
+        '2' => 0b111_001_111_100_111,
+        '3' => 0b111_001_111_001_111,
+        '4' => 0b101_101_111_001_001,
+        '5' => 0b111_100_111_001_111,
+        '6' => 0b111_100_111_101_111,
+        '7' => 0b111_001_001_001_001,
+        '8' => 0b111_101_111_101_111,
+        '9' => 0b111_101_111_001_111,
+        'A' => 0b010_101_111_101_101,
+        'B' => 0b110_101_110_101_110,
+        'C' => 0b011_100_100_100_011,
+        'D' => 0b110_101_101_101_110,
+        'E' => 0b111_100_110_100_111,
+        'F' => 0b111_100_110_100_100,
+        'G' => 0b011_100_101_101_011,
+        'H' => 0b101_101_111_101_101,
+        'I' => 0b111_010_010_010_111,
+        'J' => 0b001_001_001_101_010,
+        'K' => 0b101_101_110_101_101,
+        'L' => 0b100_100_100_100_111,
+        'M' => 0b101_111_111_101_101,
+        'N' => 0b110_101_101_101_101,
+        'O' => 0b010_101_101_101_010,
+        'P' => 0b110_101_110_100_100,
+        'Q' => 0b010_101_101_110_011,
+        'R' => 0b110_101_110_101_101,
+        'S' => 0b011_100_010_001_110,
+        'T' => 0b111_010_010_010_010,
+        'U' => 0b101_101_101_101_111,
+        'V' => 0b101_101_101_101_010,
+        'W' => 0b101_101_111_111_101,
+        'X' => 0b101_101_010_101_101,
+        'Y' => 0b101_101_010_010_010,
+        'Z' => 0b111_001_010_100_111,
+        '.' => 0b000_000_000_000_010,
+        ':' => 0b000_010_000_010_000,
+        '-' => 0b000_000_111_000_000,
+        '/' => 0b001_001_010_100_100,
+        '(' => 0b010_100_100_100_010,
+        ')' => 0b010_001_001_001_010,
+        '%' => 0b101_001_010_100_101,
+        _ => 0,
+    }
+}
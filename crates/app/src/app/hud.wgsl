@@ -0,0 +1,40 @@
+// Glyphs of `app::hud::Hud`, 3x5 bitmaps with the top left pixel in bit 14.
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) @interpolate(flat) bits: u32,
+    @location(2) shade: f32,
+};
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) vertex_index: u32,
+    @location(0) origin: vec2<f32>,
+    @location(1) size: vec2<f32>,
+    @location(2) bits: u32,
+    @location(3) shade: f32,
+) -> VertexOutput {
+    var corners = array<vec2<f32>, 6>(
+        vec2(0., 0.), vec2(1., 0.), vec2(0., 1.),
+        vec2(0., 1.), vec2(1., 0.), vec2(1., 1.),
+    );
+    let corner = corners[vertex_index];
+
+    var out: VertexOutput;
+    out.position = vec4(origin + corner * size, 0.0, 1.0);
+    out.uv = corner * vec2(3., 5.);
+    out.bits = bits;
+    out.shade = shade;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let pixel = min(vec2<u32>(in.uv), vec2(2u, 4u));
+    let bit = (4u - pixel.y) * 3u + (2u - pixel.x);
+    if ((in.bits >> bit) & 1u) == 0u {
+        discard;
+    }
+    return vec4(vec3(in.shade), 1.0);
+}
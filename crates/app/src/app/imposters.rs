@@ -0,0 +1,737 @@
+use std::{path::Path, sync::Arc};
+
+use bytemuck::{Pod, Zeroable};
+use color_eyre::{
+    eyre::{eyre, WrapErr},
+    Result,
+};
+use glam::{vec3, Mat3, Mat4, UVec2, Vec2, Vec3, Vec4};
+use wgpu::util::{align_to, DeviceExt};
+
+use components::{
+    bind_group_layout::{BindGroupLayout, WrappedBindGroupLayout},
+    world::World,
+    Blitter, CameraUniform, CameraUniformBinding, Gpu, MaterialId, MeshId, NonZeroSized, MAX_VIEWS,
+};
+use pools::{ColorSpace, Material, MaterialPool, MeshPool, TextureId, TexturePool};
+
+use super::pipeline::{self, PipelineArena, RenderHandle, RenderPipelineDescriptor};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImposterId(pub u32);
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+struct ImposterInfo {
+    center: Vec3,
+    radius: f32,
+    albedo: TextureId,
+    normal: TextureId,
+    material: MaterialId,
+    frames: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+struct ImposterBake {
+    material: MaterialId,
+    _padding: [u32; 3],
+}
+
+/// Octahedral imposters replacing distant instances of a mesh with a single billboard.
+///
+/// [`Imposters::bake`] renders the mesh from `FRAMES` x `FRAMES` directions into atlases
+/// of albedo, normals, occlusion-roughness-metallic and emission, each direction a view
+/// of a camera views array. `emit_draws.wgsl` drops every instance farther than
+/// [`Imposters::distance`] whose mesh has an imposter and appends it to an indirect draw,
+/// which the visibility pass renders into the G-buffer with the atlas cell closest to the
+/// view direction. Shading then treats the atlases
+/// as a regular material, so imposters are lit like the meshes they replace.
+pub struct Imposters {
+    distance: f32,
+    infos: Vec<ImposterInfo>,
+    // Imposter of every mesh, `NONE` for meshes without one.
+    mesh_imposters: Vec<u32>,
+
+    settings: wgpu::Buffer,
+    infos_buffer: wgpu::Buffer,
+    mesh_imposters_buffer: wgpu::Buffer,
+    // `DrawIndirect` args followed by the instances drawn as imposters this frame.
+    draws: wgpu::Buffer,
+    capacity: u32,
+
+    pub emit_layout: BindGroupLayout,
+    pub emit_bind_group: wgpu::BindGroup,
+    pub draw_layout: BindGroupLayout,
+    pub draw_bind_group: wgpu::BindGroup,
+
+    bake_layout: BindGroupLayout,
+    bake_pipeline: Option<RenderHandle>,
+    gpu: Arc<Gpu>,
+}
+
+impl Imposters {
+    /// Atlas cells per side.
+    pub const FRAMES: u32 = 8;
+    /// Pixels per atlas cell side.
+    pub const FRAME_SIZE: u32 = 128;
+    const NONE: u32 = u32::MAX;
+    const ATLAS_FORMATS: [wgpu::TextureFormat; 4] = [
+        wgpu::TextureFormat::Rgba8UnormSrgb,
+        wgpu::TextureFormat::Rgba8Unorm,
+        wgpu::TextureFormat::Rgba8Unorm,
+        wgpu::TextureFormat::Rgba8UnormSrgb,
+    ];
+    const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+    const EMIT_LAYOUT: wgpu::BindGroupLayoutDescriptor<'static> = wgpu::BindGroupLayoutDescriptor {
+        label: Some("Imposters Emit Bind Group Layout"),
+        entries: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: false },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+        ],
+    };
+
+    // Read only, the draw list is the indirect buffer of the same pass.
+    const DRAW_LAYOUT: wgpu::BindGroupLayoutDescriptor<'static> = wgpu::BindGroupLayoutDescriptor {
+        label: Some("Imposters Draw Bind Group Layout"),
+        entries: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+        ],
+    };
+
+    pub fn new(gpu: Arc<Gpu>) -> Self {
+        let device = gpu.device();
+        let distance = 50.;
+        let settings = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Imposters Settings"),
+            contents: bytemuck::bytes_of(&Vec4::new(distance, 0., 0., 0.)),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let infos_buffer = Self::create_infos_buffer(device, &[]);
+        let mesh_imposters_buffer = Self::create_mesh_imposters_buffer(device, &[]);
+        let capacity = 1;
+        let draws = Self::create_draws_buffer(device, capacity);
+
+        let emit_layout = device.create_bind_group_layout_wrap(&Self::EMIT_LAYOUT);
+        let draw_layout = device.create_bind_group_layout_wrap(&Self::DRAW_LAYOUT);
+        let bake_layout = device.create_bind_group_layout_wrap(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Imposter Bake Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: CameraUniformBinding::VIEWS_BINDING,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: Some(CameraUniformBinding::VIEWS_SIZE),
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let emit_bind_group = Self::create_emit_bind_group(
+            device,
+            &emit_layout,
+            &settings,
+            &mesh_imposters_buffer,
+            &draws,
+        );
+        let draw_bind_group = Self::create_draw_bind_group(
+            device,
+            &draw_layout,
+            &mesh_imposters_buffer,
+            &infos_buffer,
+            &draws,
+        );
+
+        Self {
+            distance,
+            infos: vec![],
+            mesh_imposters: vec![],
+
+            settings,
+            infos_buffer,
+            mesh_imposters_buffer,
+            draws,
+            capacity,
+
+            emit_layout,
+            emit_bind_group,
+            draw_layout,
+            draw_bind_group,
+
+            bake_layout,
+            bake_pipeline: None,
+            gpu,
+        }
+    }
+
+    // Bindings can't be empty, so both lists hold at least one entry.
+    fn create_infos_buffer(device: &wgpu::Device, infos: &[ImposterInfo]) -> wgpu::Buffer {
+        let placeholder = [ImposterInfo::zeroed()];
+        let contents = match infos.is_empty() {
+            true => &placeholder,
+            false => infos,
+        };
+        device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Imposter Infos"),
+            contents: bytemuck::cast_slice(contents),
+            usage: wgpu::BufferUsages::STORAGE,
+        })
+    }
+
+    fn create_mesh_imposters_buffer(device: &wgpu::Device, mesh_imposters: &[u32]) -> wgpu::Buffer {
+        let placeholder = [Self::NONE];
+        let contents = match mesh_imposters.is_empty() {
+            true => &placeholder,
+            false => mesh_imposters,
+        };
+        device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Mesh Imposters"),
+            contents: bytemuck::cast_slice(contents),
+            usage: wgpu::BufferUsages::STORAGE,
+        })
+    }
+
+    fn create_draws_buffer(device: &wgpu::Device, capacity: u32) -> wgpu::Buffer {
+        let mut contents = vec![0u32; 4 + capacity as usize];
+        // Vertex count of the billboard quad.
+        contents[0] = 6;
+        device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Imposter Draws"),
+            contents: bytemuck::cast_slice(&contents),
+            usage: wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::INDIRECT
+                | wgpu::BufferUsages::COPY_DST,
+        })
+    }
+
+    fn create_emit_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        settings: &wgpu::Buffer,
+        mesh_imposters: &wgpu::Buffer,
+        draws: &wgpu::Buffer,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Imposters Emit Bind Group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: settings.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: mesh_imposters.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: draws.as_entire_binding(),
+                },
+            ],
+        })
+    }
+
+    fn create_draw_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        mesh_imposters: &wgpu::Buffer,
+        infos: &wgpu::Buffer,
+        draws: &wgpu::Buffer,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Imposters Draw Bind Group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: mesh_imposters.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: infos.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: draws.as_entire_binding(),
+                },
+            ],
+        })
+    }
+
+    fn update_bind_groups(&mut self) {
+        let device = self.gpu.device();
+        self.emit_bind_group = Self::create_emit_bind_group(
+            device,
+            &self.emit_layout,
+            &self.settings,
+            &self.mesh_imposters_buffer,
+            &self.draws,
+        );
+        self.draw_bind_group = Self::create_draw_bind_group(
+            device,
+            &self.draw_layout,
+            &self.mesh_imposters_buffer,
+            &self.infos_buffer,
+            &self.draws,
+        );
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.infos.is_empty()
+    }
+
+    pub fn count(&self) -> u32 {
+        self.infos.len() as _
+    }
+
+    /// Camera distance past which the imposter is drawn, in bounding radii of the instance,
+    /// so every mesh swaps at the same size on screen.
+    pub fn set_distance(&mut self, distance: f32) {
+        self.distance = distance.max(0.);
+        self.gpu.queue().write_buffer(
+            &self.settings,
+            0,
+            bytemuck::bytes_of(&Vec4::new(self.distance, 0., 0., 0.)),
+        );
+    }
+
+    pub fn distance(&self) -> f32 {
+        self.distance
+    }
+
+    pub fn imposter(&self, mesh: MeshId) -> Option<ImposterId> {
+        self.mesh_imposters
+            .get(mesh.0 as usize)
+            .filter(|id| **id != Self::NONE)
+            .map(|id| ImposterId(*id))
+    }
+
+    /// Grows the draw list to fit every instance, called when the instance pool changes.
+    pub fn reserve(&mut self, instance_count: u32) {
+        if instance_count <= self.capacity {
+            return;
+        }
+        self.capacity = instance_count.next_power_of_two();
+        self.draws = Self::create_draws_buffer(self.gpu.device(), self.capacity);
+        self.update_bind_groups();
+    }
+
+    pub(crate) fn draws(&self) -> &wgpu::Buffer {
+        &self.draws
+    }
+
+    /// Zeroes the instance count of the indirect draw before `emit_draws.wgsl` fills it.
+    pub(crate) fn reset(&self, encoder: &mut wgpu::CommandEncoder) {
+        encoder.clear_buffer(&self.draws, 4, wgpu::BufferSize::new(4));
+    }
+
+    /// Renders the imposter atlases of `mesh` with `material` and swaps every distant
+    /// instance of the mesh to it, whatever material the instance uses.
+    ///
+    /// Baking again replaces the previous imposter of the mesh. The atlases get mips down
+    /// to a texel per cell, so cells don't bleed into each other.
+    pub fn bake(
+        &mut self,
+        world: &World,
+        blitter: &Blitter,
+        mesh: MeshId,
+        material: MaterialId,
+    ) -> Result<ImposterId> {
+        let pipeline = self.bake_pipeline(world)?;
+        let info = world
+            .get::<MeshPool>()?
+            .mesh_info_cpu
+            .get(mesh.0 as usize)
+            .copied()
+            .ok_or_else(|| eyre!("Mesh {} is not in the MeshPool", mesh.0))?;
+        let center = info.center;
+        let radius = info.radius.max(1e-4);
+
+        let size = Self::FRAMES * Self::FRAME_SIZE;
+        let extent = wgpu::Extent3d {
+            width: size,
+            height: size,
+            depth_or_array_layers: 1,
+        };
+        let create_target = |label, format, usage, mip_level_count| {
+            self.gpu.device().create_texture(&wgpu::TextureDescriptor {
+                label: Some(label),
+                size: extent,
+                mip_level_count,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage,
+                view_formats: &[],
+            })
+        };
+        let labels = [
+            "Imposter Albedo",
+            "Imposter Normal",
+            "Imposter ORM",
+            "Imposter Emissive",
+        ];
+        let atlases: [_; 4] = std::array::from_fn(|i| {
+            create_target(
+                labels[i],
+                Self::ATLAS_FORMATS[i],
+                wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+                Self::FRAME_SIZE.ilog2() + 1,
+            )
+        });
+        let depth = create_target(
+            "Imposter Depth",
+            Self::DEPTH_FORMAT,
+            wgpu::TextureUsages::RENDER_ATTACHMENT,
+            1,
+        )
+        .create_view(&Default::default());
+
+        let device = self.gpu.device();
+        let uniform = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Imposter Bake Uniform"),
+            contents: bytemuck::bytes_of(&ImposterBake {
+                material,
+                _padding: [0; 3],
+            }),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        // The cells don't fit one views array, so each batch of `MAX_VIEWS` cells gets
+        // its own array in the buffer.
+        let cell_views: Vec<_> = (0..Self::FRAMES * Self::FRAMES)
+            .map(|frame| {
+                let cell = UVec2::new(frame % Self::FRAMES, frame / Self::FRAMES);
+                Self::cell_view(center, radius, cell)
+            })
+            .collect();
+        let stride = align_to(
+            CameraUniformBinding::VIEWS_SIZE.get(),
+            device.limits().min_uniform_buffer_offset_alignment as u64,
+        );
+        let batches = cell_views.chunks(MAX_VIEWS);
+        let mut contents = vec![0u8; stride as usize * batches.len()];
+        for (views, dst) in batches.zip(contents.chunks_mut(stride as usize)) {
+            let views: &[u8] = bytemuck::cast_slice(views);
+            dst[..views.len()].copy_from_slice(views);
+        }
+        let views = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Imposter Bake Views"),
+            contents: &contents,
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let bind_groups: Vec<_> = (0..contents.len() as u64 / stride)
+            .map(|batch| {
+                device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("Imposter Bake Bind Group"),
+                    layout: &self.bake_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: uniform.as_entire_binding(),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: CameraUniformBinding::VIEWS_BINDING,
+                            resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                                buffer: &views,
+                                offset: batch * stride,
+                                size: Some(CameraUniformBinding::VIEWS_SIZE),
+                            }),
+                        },
+                    ],
+                })
+            })
+            .collect();
+
+        let mut encoder =
+            self.gpu
+                .device()
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("Imposter Bake Encoder"),
+                });
+        {
+            let meshes = world.get::<MeshPool>()?;
+            let textures = world.get::<TexturePool>()?;
+            let materials = world.get::<MaterialPool>()?;
+            let arena = world.get::<PipelineArena>()?;
+
+            let views = atlases.each_ref().map(|atlas| {
+                atlas.create_view(&wgpu::TextureViewDescriptor {
+                    mip_level_count: Some(1),
+                    ..Default::default()
+                })
+            });
+            let color_attachments = views
+                .iter()
+                .map(|view| {
+                    Some(wgpu::RenderPassColorAttachment {
+                        view,
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                            store: true,
+                        },
+                    })
+                })
+                .collect::<Vec<_>>();
+            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Imposter Bake Pass"),
+                color_attachments: &color_attachments,
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &depth,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: false,
+                    }),
+                    stencil_ops: None,
+                }),
+            });
+            rpass.set_pipeline(arena.get_pipeline(pipeline));
+            rpass.set_bind_group(1, &textures.bind_group, &[]);
+            rpass.set_bind_group(2, &materials.bind_group, &[]);
+            rpass.set_vertex_buffer(0, meshes.vertices.full_slice());
+            rpass.set_vertex_buffer(1, meshes.normals.full_slice());
+            rpass.set_vertex_buffer(2, meshes.tangents.full_slice());
+            rpass.set_vertex_buffer(3, meshes.tex_coords.full_slice());
+            rpass.set_index_buffer(meshes.indices.full_slice(), wgpu::IndexFormat::Uint32);
+            // An instance per cell, the shader picks its view with `instance_index % MAX_VIEWS`.
+            for (batch, bind_group) in bind_groups.iter().enumerate() {
+                rpass.set_bind_group(0, bind_group, &[]);
+                let first = (batch * MAX_VIEWS) as u32;
+                let last = (first + MAX_VIEWS as u32).min(cell_views.len() as u32);
+                rpass.draw_indexed(
+                    info.base_index..info.base_index + info.index_count,
+                    info.vertex_offset,
+                    first..last,
+                );
+            }
+        }
+
+        let [albedo, normal, orm, emissive] = &atlases;
+        let mut textures = world.get_mut::<TexturePool>()?;
+        let albedo = textures.add_mipmapped(&mut encoder, blitter, albedo, ColorSpace::Srgb)?;
+        let normal = textures.add_mipmapped(&mut encoder, blitter, normal, ColorSpace::Linear)?;
+        let metallic_roughness =
+            textures.add_mipmapped(&mut encoder, blitter, orm, ColorSpace::Linear)?;
+        let emissive = textures.add_mipmapped(&mut encoder, blitter, emissive, ColorSpace::Srgb)?;
+        textures.update_bind_group()?;
+        drop(textures);
+        self.gpu.queue().submit(Some(encoder.finish()));
+        let material = world.get_mut::<MaterialPool>()?.add(Material {
+            albedo,
+            normal,
+            metallic_roughness,
+            emissive,
+            ..Default::default()
+        })?;
+
+        let info = ImposterInfo {
+            center,
+            radius,
+            albedo,
+            normal,
+            material,
+            frames: Self::FRAMES,
+        };
+        let id = match self.imposter(mesh) {
+            Some(id) => {
+                self.infos[id.0 as usize] = info;
+                id
+            }
+            None => {
+                self.infos.push(info);
+                ImposterId(self.infos.len() as u32 - 1)
+            }
+        };
+        let mesh_index = mesh.0 as usize;
+        if self.mesh_imposters.len() <= mesh_index {
+            self.mesh_imposters.resize(mesh_index + 1, Self::NONE);
+        }
+        self.mesh_imposters[mesh_index] = id.0;
+
+        let device = self.gpu.device();
+        self.infos_buffer = Self::create_infos_buffer(device, &self.infos);
+        self.mesh_imposters_buffer =
+            Self::create_mesh_imposters_buffer(device, &self.mesh_imposters);
+        self.update_bind_groups();
+
+        log::info!("Baked imposter {} for mesh {}", id.0, mesh.0);
+        Ok(id)
+    }
+
+    /// Orthographic view of the bounding sphere from the direction of `cell`, projected into
+    /// the cell of the atlas so nothing leaks into the neighbouring cells.
+    ///
+    /// Mirrors `imposter_frame_dir`, `imposter_basis` and `imposter_atlas_uv` of
+    /// `utils/imposter.wgsl`, which draw the atlas back.
+    fn cell_view(center: Vec3, radius: f32, cell: UVec2) -> CameraUniform {
+        let frames = Self::FRAMES as f32;
+        let v = (cell.as_vec2() + 0.5) / frames * 2. - 1.;
+        let mut dir = vec3(v.x, v.y, 1. - v.x.abs() - v.y.abs());
+        let t = (-dir.z).max(0.);
+        dir.x += if dir.x >= 0. { -t } else { t };
+        dir.y += if dir.y >= 0. { -t } else { t };
+        let dir = dir.normalize();
+        let up_hint = if dir.y.abs() > 0.999 {
+            Vec3::Z
+        } else {
+            Vec3::Y
+        };
+        let right = up_hint.cross(dir).normalize();
+        let up = dir.cross(right);
+
+        // World to the -1..1 box around the bounding sphere, looking down `-dir`.
+        let view = Mat4::from_scale(Vec3::splat(radius.recip()))
+            * Mat4::from_mat3(Mat3::from_cols(right, up, dir).transpose())
+            * Mat4::from_translation(-center);
+        let offset = (cell.as_vec2() * 2. + 1.) / frames;
+        let projection = Mat4::from_cols(
+            Vec4::new(frames.recip(), 0., 0., 0.),
+            Vec4::new(0., frames.recip(), 0., 0.),
+            Vec4::new(0., 0., -0.5, 0.),
+            Vec4::new(offset.x - 1., 1. - offset.y, 0.5, 1.),
+        );
+        CameraUniform::from_view(center + dir * radius, view, projection)
+    }
+
+    fn bake_pipeline(&mut self, world: &World) -> Result<RenderHandle> {
+        if let Some(pipeline) = self.bake_pipeline {
+            return Ok(pipeline);
+        }
+
+        let textures = world.get::<TexturePool>()?;
+        let materials = world.get::<MaterialPool>()?;
+        let desc = RenderPipelineDescriptor {
+            label: Some("Imposter Bake Pipeline".into()),
+            layout: vec![
+                self.bake_layout.clone(),
+                textures.bind_group_layout.clone(),
+                materials.bind_group_layout.clone(),
+            ],
+            vertex: pipeline::VertexState {
+                entry_point: "vs_main".into(),
+                buffers: vec![
+                    // Positions
+                    pipeline::VertexBufferLayout {
+                        array_stride: Vec3::SIZE as _,
+                        step_mode: wgpu::VertexStepMode::Vertex,
+                        attributes: wgpu::vertex_attr_array![0 => Float32x3].to_vec(),
+                    },
+                    // Normals
+                    pipeline::VertexBufferLayout {
+                        array_stride: Vec3::SIZE as _,
+                        step_mode: wgpu::VertexStepMode::Vertex,
+                        attributes: wgpu::vertex_attr_array![1 => Float32x3].to_vec(),
+                    },
+                    // Tangents
+                    pipeline::VertexBufferLayout {
+                        array_stride: Vec4::SIZE as _,
+                        step_mode: wgpu::VertexStepMode::Vertex,
+                        attributes: wgpu::vertex_attr_array![2 => Float32x4].to_vec(),
+                    },
+                    // UVs
+                    pipeline::VertexBufferLayout {
+                        array_stride: Vec2::SIZE as _,
+                        step_mode: wgpu::VertexStepMode::Vertex,
+                        attributes: wgpu::vertex_attr_array![3 => Float32x2].to_vec(),
+                    },
+                ],
+            },
+            fragment: Some(pipeline::FragmentState {
+                entry_point: "fs_main".into(),
+                targets: Self::ATLAS_FORMATS
+                    .map(|format| Some(format.into()))
+                    .to_vec(),
+            }),
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: Self::DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            ..Default::default()
+        };
+        drop((textures, materials));
+
+        let pipeline = world
+            .get_mut::<PipelineArena>()?
+            .process_render_pipeline_from_path(
+                Path::new("shaders").join("imposter_bake.wgsl"),
+                desc,
+            )
+            .wrap_err("while creating the imposter bake pipeline")?;
+        self.bake_pipeline = Some(pipeline);
+        Ok(pipeline)
+    }
+}
@@ -0,0 +1,310 @@
+use std::{
+    fs::File,
+    io::Read,
+    net::{SocketAddr, UdpSocket},
+    ops::RangeInclusive,
+    path::Path,
+    sync::mpsc,
+};
+
+use ahash::AHashMap;
+use color_eyre::{
+    eyre::{bail, WrapErr},
+    Result,
+};
+
+use super::global_ubo::USER_PARAMS;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ControlMessage {
+    Osc {
+        address: String,
+        value: f32,
+    },
+    MidiCc {
+        channel: u8,
+        controller: u8,
+        value: u8,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Source {
+    MidiCc { channel: u8, controller: u8 },
+}
+
+#[derive(Debug, Clone)]
+pub struct LiveParam {
+    pub name: String,
+    pub value: f32,
+    pub range: RangeInclusive<f32>,
+}
+
+/// Named parameters driven by OSC and MIDI controllers.
+///
+/// Parameter `i` lands in `globals.user[i / 4][i % 4]` in shaders,
+/// OSC messages to `/param/<name>` set it directly, MIDI CC needs [`LiveParams::map_midi_cc`].
+pub struct LiveParams {
+    params: Vec<LiveParam>,
+    osc_mappings: AHashMap<String, usize>,
+    midi_mappings: AHashMap<Source, usize>,
+    sender: mpsc::Sender<ControlMessage>,
+    receiver: mpsc::Receiver<ControlMessage>,
+}
+
+impl LiveParams {
+    pub fn new() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        Self {
+            params: vec![],
+            osc_mappings: AHashMap::new(),
+            midi_mappings: AHashMap::new(),
+            sender,
+            receiver,
+        }
+    }
+
+    /// Registers a parameter and returns its slot in the user block.
+    pub fn add(&mut self, name: &str, value: f32, range: RangeInclusive<f32>) -> Result<usize> {
+        if let Some(slot) = self.slot(name) {
+            return Ok(slot);
+        }
+        if self.params.len() == USER_PARAMS {
+            bail!("Only {USER_PARAMS} live parameters fit into the user block, can't add {name:?}");
+        }
+        let slot = self.params.len();
+        self.osc_mappings.insert(format!("/param/{name}"), slot);
+        self.params.push(LiveParam {
+            name: name.to_string(),
+            value: value.clamp(*range.start(), *range.end()),
+            range,
+        });
+        Ok(slot)
+    }
+
+    pub fn map_osc(&mut self, address: &str, name: &str) -> Result<()> {
+        let slot = self.expect_slot(name)?;
+        self.osc_mappings.insert(address.to_string(), slot);
+        Ok(())
+    }
+
+    /// Maps a control change, `channel` is zero based.
+    pub fn map_midi_cc(&mut self, channel: u8, controller: u8, name: &str) -> Result<()> {
+        let slot = self.expect_slot(name)?;
+        self.midi_mappings.insert(
+            Source::MidiCc {
+                channel,
+                controller,
+            },
+            slot,
+        );
+        Ok(())
+    }
+
+    /// Receives OSC packets on a UDP socket in the background.
+    pub fn listen_osc(&self, addr: SocketAddr) -> Result<()> {
+        let socket = UdpSocket::bind(addr)
+            .wrap_err_with(|| format!("Failed to bind OSC input to {addr}"))?;
+        log::info!("Listening for OSC on {}", socket.local_addr()?);
+        let sender = self.sender.clone();
+        std::thread::spawn(move || {
+            let mut packet = [0u8; 1536];
+            while let Ok(len) = socket.recv(&mut packet) {
+                let mut messages = vec![];
+                parse_osc_packet(&packet[..len], &mut messages);
+                if messages.into_iter().any(|msg| sender.send(msg).is_err()) {
+                    break;
+                }
+            }
+        });
+        Ok(())
+    }
+
+    /// Reads a raw MIDI byte stream in the background, e.g. `/dev/snd/midiC1D0`.
+    pub fn listen_midi(&self, device: impl AsRef<Path>) -> Result<()> {
+        let device = device.as_ref();
+        let mut file = File::open(device)
+            .wrap_err_with(|| format!("Failed to open MIDI device {}", device.display()))?;
+        let sender = self.sender.clone();
+        std::thread::spawn(move || {
+            let mut parser = MidiParser::default();
+            let mut bytes = [0u8; 64];
+            while let Ok(len @ 1..) = file.read(&mut bytes) {
+                let messages = bytes[..len].iter().filter_map(|&b| parser.push(b));
+                for msg in messages {
+                    if sender.send(msg).is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+        Ok(())
+    }
+
+    /// Sender for controllers living outside of this module.
+    pub fn sender(&self) -> mpsc::Sender<ControlMessage> {
+        self.sender.clone()
+    }
+
+    /// Applies every message received since the last call.
+    pub fn poll(&mut self) {
+        while let Ok(msg) = self.receiver.try_recv() {
+            self.apply(msg);
+        }
+    }
+
+    pub fn apply(&mut self, msg: ControlMessage) {
+        match msg {
+            ControlMessage::Osc { address, value } => {
+                if let Some(&slot) = self.osc_mappings.get(&address) {
+                    self.set_slot(slot, value);
+                }
+            }
+            ControlMessage::MidiCc {
+                channel,
+                controller,
+                value,
+            } => {
+                let source = Source::MidiCc {
+                    channel,
+                    controller,
+                };
+                if let Some(&slot) = self.midi_mappings.get(&source) {
+                    let param = &mut self.params[slot];
+                    let (start, end) = (*param.range.start(), *param.range.end());
+                    param.value = start + (end - start) * value as f32 / 127.;
+                }
+            }
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<f32> {
+        self.slot(name).map(|slot| self.params[slot].value)
+    }
+
+    pub fn set(&mut self, name: &str, value: f32) {
+        if let Some(slot) = self.slot(name) {
+            self.set_slot(slot, value);
+        }
+    }
+
+    pub fn params(&self) -> &[LiveParam] {
+        &self.params
+    }
+
+    pub fn user_block(&self) -> [[f32; 4]; USER_PARAMS / 4] {
+        let mut block = [[0.; 4]; USER_PARAMS / 4];
+        for (i, param) in self.params.iter().enumerate() {
+            block[i / 4][i % 4] = param.value;
+        }
+        block
+    }
+
+    fn set_slot(&mut self, slot: usize, value: f32) {
+        let param = &mut self.params[slot];
+        param.value = value.clamp(*param.range.start(), *param.range.end());
+    }
+
+    fn slot(&self, name: &str) -> Option<usize> {
+        self.params.iter().position(|p| p.name == name)
+    }
+
+    fn expect_slot(&self, name: &str) -> Result<usize> {
+        match self.slot(name) {
+            Some(slot) => Ok(slot),
+            None => bail!("Unknown live parameter {name:?}"),
+        }
+    }
+}
+
+fn read_osc_string(data: &[u8]) -> Option<(&str, &[u8])> {
+    let len = data.iter().position(|&b| b == 0)?;
+    let s = std::str::from_utf8(&data[..len]).ok()?;
+    let padded = (len + 4) & !3;
+    Some((s, data.get(padded..)?))
+}
+
+fn read_osc_u32(data: &[u8]) -> Option<(u32, &[u8])> {
+    let bytes = data.get(..4)?.try_into().ok()?;
+    Some((u32::from_be_bytes(bytes), &data[4..]))
+}
+
+/// Collects float-like first arguments of every message, bundles are flattened.
+fn parse_osc_packet(data: &[u8], messages: &mut Vec<ControlMessage>) -> Option<()> {
+    if let Some(mut elements) = data.strip_prefix(b"#bundle\0") {
+        // Skip the time tag, bundles are applied as soon as they arrive.
+        elements = elements.get(8..)?;
+        while !elements.is_empty() {
+            let (size, rest) = read_osc_u32(elements)?;
+            let element = rest.get(..size as usize)?;
+            parse_osc_packet(element, messages);
+            elements = &rest[size as usize..];
+        }
+        return Some(());
+    }
+
+    let (address, rest) = read_osc_string(data)?;
+    let (tags, args) = read_osc_string(rest)?;
+    let value = match tags.strip_prefix(',')?.as_bytes().first()? {
+        b'f' => f32::from_bits(read_osc_u32(args)?.0),
+        b'i' => read_osc_u32(args)?.0 as i32 as f32,
+        b'T' => 1.,
+        b'F' => 0.,
+        _ => return None,
+    };
+    messages.push(ControlMessage::Osc {
+        address: address.to_string(),
+        value,
+    });
+    Some(())
+}
+
+#[derive(Default)]
+struct MidiParser {
+    status: Option<u8>,
+    data: Vec<u8>,
+    in_sysex: bool,
+}
+
+impl MidiParser {
+    fn push(&mut self, byte: u8) -> Option<ControlMessage> {
+        match byte {
+            // Realtime messages may appear anywhere and don't affect running status.
+            0xF8..=0xFF => return None,
+            0xF0 => {
+                self.in_sysex = true;
+                self.status = None;
+                return None;
+            }
+            0xF1..=0xF7 => {
+                self.in_sysex = false;
+                self.status = None;
+                return None;
+            }
+            0x80..=0xEF => {
+                self.in_sysex = false;
+                self.status = Some(byte);
+                self.data.clear();
+                return None;
+            }
+            _ if self.in_sysex => return None,
+            _ => {}
+        }
+
+        let status = self.status?;
+        self.data.push(byte);
+        let expected = match status & 0xF0 {
+            0xC0 | 0xD0 => 1,
+            _ => 2,
+        };
+        if self.data.len() < expected {
+            return None;
+        }
+        let data = std::mem::take(&mut self.data);
+        (status & 0xF0 == 0xB0).then(|| ControlMessage::MidiCc {
+            channel: status & 0x0F,
+            controller: data[0],
+            value: data[1],
+        })
+    }
+}
@@ -0,0 +1,278 @@
+use std::sync::Arc;
+
+use glam::{Mat4, Vec3, Vec4Swizzles};
+
+use components::{
+    bind_group_layout::StorageReadBindGroupLayout, Gpu, Instance, InstanceId, MeshInfo,
+    ResizableBuffer, ResizableBufferExt,
+};
+use pools::MeshRef;
+
+/// Simplified geometry rasterized in place of an instance's full mesh.
+#[derive(Debug, Clone, Default)]
+pub struct OccluderMesh {
+    pub vertices: Vec<Vec3>,
+    pub indices: Vec<u32>,
+}
+
+impl From<MeshRef<'_>> for OccluderMesh {
+    fn from(mesh: MeshRef<'_>) -> Self {
+        Self {
+            vertices: mesh.vertices.to_vec(),
+            indices: mesh.indices,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OccluderId(pub u32);
+
+/// Cpu occlusion culling for platforms where gpu culling is slow or unavailable.
+///
+/// Occluder proxies are rasterized into a small reverse-z depth buffer, then every instance
+/// bounding box is tested against it. Each tile keeps the farthest depth it covers so most
+/// boxes are accepted or rejected without touching pixels, in the spirit of masked occlusion
+/// culling. The result is a bitmask read by `emit_draws.wgsl`.
+pub struct SoftwareOcclusion {
+    enabled: bool,
+    meshes: Vec<OccluderMesh>,
+    occluders: Vec<(InstanceId, OccluderId)>,
+
+    depth: Vec<f32>,
+    tile_depth: Vec<f32>,
+    occluded: Vec<u32>,
+    occluded_count: u32,
+
+    buffer: ResizableBuffer<u32>,
+    layout: StorageReadBindGroupLayout<u32>,
+    pub bind_group: wgpu::BindGroup,
+    gpu: Arc<Gpu>,
+}
+
+impl SoftwareOcclusion {
+    pub const WIDTH: usize = 256;
+    pub const HEIGHT: usize = 128;
+    const TILE: usize = 8;
+    const TILES_X: usize = Self::WIDTH / Self::TILE;
+    const TILES_Y: usize = Self::HEIGHT / Self::TILE;
+
+    pub fn new(gpu: Arc<Gpu>, layout: &StorageReadBindGroupLayout<u32>) -> Self {
+        let buffer = gpu
+            .device()
+            .create_resizable_buffer(wgpu::BufferUsages::STORAGE);
+        let bind_group = Self::create_bind_group(gpu.device(), layout, &buffer);
+        Self {
+            enabled: false,
+            meshes: vec![],
+            occluders: vec![],
+
+            depth: vec![0.; Self::WIDTH * Self::HEIGHT],
+            tile_depth: vec![0.; Self::TILES_X * Self::TILES_Y],
+            occluded: vec![],
+            occluded_count: 0,
+
+            buffer,
+            layout: layout.clone(),
+            bind_group,
+            gpu,
+        }
+    }
+
+    fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        buffer: &ResizableBuffer<u32>,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Software Occlusion Bind Group"),
+            layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+        })
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    pub fn add_mesh(&mut self, mesh: impl Into<OccluderMesh>) -> OccluderId {
+        self.meshes.push(mesh.into());
+        OccluderId(self.meshes.len() as u32 - 1)
+    }
+
+    /// Makes `instance` occlude others with the given proxy, replacing its previous one.
+    pub fn set_occluder(&mut self, instance: InstanceId, occluder: OccluderId) {
+        self.occluders.retain(|(id, _)| id.0 != instance.0);
+        self.occluders.push((instance, occluder));
+    }
+
+    pub fn remove_occluder(&mut self, instance: InstanceId) {
+        self.occluders.retain(|(id, _)| id.0 != instance.0);
+    }
+
+    /// Number of instances rejected by the last update.
+    pub fn occluded_count(&self) -> u32 {
+        self.occluded_count
+    }
+
+    pub fn update(&mut self, instances: &[Instance], meshes: &[MeshInfo], world_to_clip: Mat4) {
+        let words = (instances.len() + 31) / 32;
+        self.occluded.clear();
+        self.occluded.resize(words, 0);
+        self.occluded_count = 0;
+
+        if self.enabled {
+            self.rasterize_occluders(instances, world_to_clip);
+            for (i, instance) in instances.iter().enumerate() {
+                if !instance.is_active() {
+                    continue;
+                }
+                let mesh = meshes[instance.mesh.0 as usize];
+                if self.is_occluded(instance.transform, mesh.min, mesh.max, world_to_clip) {
+                    self.occluded[i / 32] |= 1 << (i % 32);
+                    self.occluded_count += 1;
+                }
+            }
+        }
+
+        if words == 0 {
+            return;
+        }
+        if self.buffer.len() != words {
+            self.buffer.clear();
+            if self.buffer.push(&self.gpu, &self.occluded) {
+                self.bind_group =
+                    Self::create_bind_group(self.gpu.device(), &self.layout, &self.buffer);
+            }
+        } else {
+            self.buffer.write_slice(&self.gpu, 0, &self.occluded);
+        }
+    }
+
+    fn rasterize_occluders(&mut self, instances: &[Instance], world_to_clip: Mat4) {
+        self.depth.fill(0.);
+        for &(instance, occluder) in &self.occluders {
+            let Some(instance) = instances.get(instance.0 as usize) else {
+                continue;
+            };
+            if !instance.is_active() {
+                continue;
+            }
+            let mesh = &self.meshes[occluder.0 as usize];
+            let model_to_clip = world_to_clip * instance.transform;
+            for tri in mesh.indices.chunks_exact(3) {
+                let [a, b, c] = [tri[0], tri[1], tri[2]]
+                    .map(|i| model_to_clip * mesh.vertices[i as usize].extend(1.));
+                // Dropping triangles crossing the near plane keeps the buffer conservative.
+                if a.w <= f32::EPSILON || b.w <= f32::EPSILON || c.w <= f32::EPSILON {
+                    continue;
+                }
+                let [a, b, c] = [a, b, c].map(|v| Self::to_screen(v.xyz() / v.w));
+                rasterize_triangle(&mut self.depth, a, b, c);
+            }
+        }
+
+        for (i, tile) in self.tile_depth.iter_mut().enumerate() {
+            let (tx, ty) = (i % Self::TILES_X, i / Self::TILES_X);
+            *tile = (0..Self::TILE)
+                .flat_map(|y| {
+                    let row = (ty * Self::TILE + y) * Self::WIDTH + tx * Self::TILE;
+                    self.depth[row..row + Self::TILE].iter().copied()
+                })
+                .fold(f32::INFINITY, f32::min);
+        }
+    }
+
+    fn is_occluded(&self, transform: Mat4, min: Vec3, max: Vec3, world_to_clip: Mat4) -> bool {
+        let model_to_clip = world_to_clip * transform;
+        let mut rect_min = Vec3::splat(f32::INFINITY);
+        let mut rect_max = Vec3::splat(f32::NEG_INFINITY);
+        for i in 0..8 {
+            let corner = Vec3::select(
+                glam::BVec3::new(i & 1 != 0, i & 2 != 0, i & 4 != 0),
+                max,
+                min,
+            );
+            let clip = model_to_clip * corner.extend(1.);
+            if clip.w <= f32::EPSILON {
+                return false;
+            }
+            let screen = Self::to_screen(clip.xyz() / clip.w);
+            rect_min = rect_min.min(screen);
+            rect_max = rect_max.max(screen);
+        }
+
+        // Reverse-z, the nearest point of the box has the largest depth.
+        let nearest = rect_max.z;
+        let x0 = rect_min.x.floor().max(0.) as usize;
+        let y0 = rect_min.y.floor().max(0.) as usize;
+        let x1 = (rect_max.x.ceil() as usize).min(Self::WIDTH);
+        let y1 = (rect_max.y.ceil() as usize).min(Self::HEIGHT);
+        if x0 >= x1 || y0 >= y1 {
+            // Off screen, frustum culling takes care of it.
+            return false;
+        }
+
+        for ty in y0 / Self::TILE..=(y1 - 1) / Self::TILE {
+            for tx in x0 / Self::TILE..=(x1 - 1) / Self::TILE {
+                if self.tile_depth[ty * Self::TILES_X + tx] > nearest {
+                    continue;
+                }
+                let (px0, px1) = (x0.max(tx * Self::TILE), x1.min((tx + 1) * Self::TILE));
+                let (py0, py1) = (y0.max(ty * Self::TILE), y1.min((ty + 1) * Self::TILE));
+                for y in py0..py1 {
+                    let row = &self.depth[y * Self::WIDTH..][px0..px1];
+                    if row.iter().any(|&depth| depth <= nearest) {
+                        return false;
+                    }
+                }
+            }
+        }
+        true
+    }
+
+    fn to_screen(ndc: Vec3) -> Vec3 {
+        Vec3::new(
+            (ndc.x * 0.5 + 0.5) * Self::WIDTH as f32,
+            (0.5 - ndc.y * 0.5) * Self::HEIGHT as f32,
+            ndc.z,
+        )
+    }
+}
+
+fn rasterize_triangle(depth: &mut [f32], a: Vec3, b: Vec3, c: Vec3) {
+    let (width, height) = (SoftwareOcclusion::WIDTH, SoftwareOcclusion::HEIGHT);
+    let edge = |p: Vec3, q: Vec3, x: f32, y: f32| (q.x - p.x) * (y - p.y) - (q.y - p.y) * (x - p.x);
+
+    let area = edge(a, b, c.x, c.y);
+    if area.abs() <= f32::EPSILON {
+        return;
+    }
+    let min = a.min(b).min(c);
+    let max = a.max(b).max(c);
+    let x0 = min.x.floor().max(0.) as usize;
+    let y0 = min.y.floor().max(0.) as usize;
+    let x1 = (max.x.ceil().max(0.) as usize).min(width);
+    let y1 = (max.y.ceil().max(0.) as usize).min(height);
+
+    for y in y0..y1 {
+        for x in x0..x1 {
+            let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+            let w0 = edge(b, c, px, py) / area;
+            let w1 = edge(c, a, px, py) / area;
+            let w2 = edge(a, b, px, py) / area;
+            if w0 < 0. || w1 < 0. || w2 < 0. {
+                continue;
+            }
+            let z = w0 * a.z + w1 * b.z + w2 * c.z;
+            let texel = &mut depth[y * width + x];
+            *texel = texel.max(z);
+        }
+    }
+}
@@ -0,0 +1,458 @@
+use std::{
+    fs::File,
+    io::{Read, Seek, SeekFrom, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    path::PathBuf,
+    process::{Child, Command, Stdio},
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use color_eyre::{
+    eyre::{bail, WrapErr},
+    Result,
+};
+use components::ImageDimentions;
+use image::{codecs::jpeg::JpegEncoder, ColorType};
+
+mod ndi;
+
+pub use ndi::NdiSink;
+
+/// Environment variable selecting the [`Output`] of [`crate::run`].
+pub const OUTPUT_ENV: &str = "VOIDIN_OUTPUT";
+
+/// Destination of rendered frames besides the window surface.
+pub trait OutputSink: Send {
+    fn name(&self) -> &str;
+
+    /// Receives tightly packed `Rgba8UnormSrgb` rows.
+    fn write_frame(&mut self, frame: &[u8], width: u32, height: u32) -> Result<()>;
+}
+
+/// Runtime selection of the frame output.
+///
+/// Parsed from `surface`, `shm:<path>`, `mjpeg:<addr>`, `h264:<addr>` or `ndi:<name>`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Output {
+    /// Only present to the window surface.
+    #[default]
+    Surface,
+    /// Overwrite a file in place, e.g. on `/dev/shm`, see [`SharedMemorySink`].
+    SharedMemory(PathBuf),
+    /// Serve a `multipart/x-mixed-replace` JPEG stream over HTTP, see [`MjpegSink`].
+    Mjpeg(SocketAddr),
+    /// Stream H.264 in MPEG-TS over TCP through ffmpeg, see [`H264Sink`].
+    H264(SocketAddr),
+    /// Publish an NDI source with the given name, see [`NdiSink`].
+    Ndi(String),
+}
+
+impl Output {
+    pub fn from_env() -> Result<Self> {
+        match std::env::var(OUTPUT_ENV) {
+            Ok(spec) => spec.parse(),
+            Err(_) => Ok(Self::Surface),
+        }
+    }
+
+    pub fn create_sink(&self) -> Result<Option<Box<dyn OutputSink>>> {
+        Ok(match self {
+            Output::Surface => None,
+            Output::SharedMemory(path) => Some(Box::new(SharedMemorySink::new(path.clone())?)),
+            Output::Mjpeg(addr) => Some(Box::new(MjpegSink::new(*addr)?)),
+            Output::H264(addr) => Some(Box::new(H264Sink::new(*addr))),
+            Output::Ndi(name) => Some(Box::new(NdiSink::new(name)?)),
+        })
+    }
+}
+
+impl FromStr for Output {
+    type Err = color_eyre::Report;
+
+    fn from_str(spec: &str) -> Result<Self> {
+        let (kind, arg) = spec.split_once(':').unwrap_or((spec, ""));
+        let addr = || {
+            arg.parse::<SocketAddr>()
+                .wrap_err_with(|| format!("Invalid output address: {arg:?}"))
+        };
+        Ok(match kind {
+            "surface" => Output::Surface,
+            "shm" if !arg.is_empty() => Output::SharedMemory(PathBuf::from(arg)),
+            "mjpeg" => Output::Mjpeg(addr()?),
+            "h264" => Output::H264(addr()?),
+            "ndi" if !arg.is_empty() => Output::Ndi(arg.to_string()),
+            _ => bail!("Unknown output: {spec:?}, expected surface, shm:<path>, mjpeg:<addr>, h264:<addr> or ndi:<name>"),
+        })
+    }
+}
+
+/// Feeds captured frames to a sink on its own thread.
+///
+/// At most one frame is in flight, frames rendered while the sink is busy are not captured.
+pub(crate) struct OutputStream {
+    sender: mpsc::Sender<(Arc<wgpu::Buffer>, ImageDimentions)>,
+    busy: Arc<AtomicBool>,
+}
+
+impl OutputStream {
+    pub fn new(mut sink: Box<dyn OutputSink>) -> Self {
+        let (sender, receiver) = mpsc::channel::<(Arc<wgpu::Buffer>, ImageDimentions)>();
+        let busy = Arc::new(AtomicBool::new(false));
+        let done = busy.clone();
+        std::thread::spawn(move || {
+            let mut pixels = vec![];
+            while let Ok((frame, dims)) = receiver.recv() {
+                let padded_bytes = dims.padded_bytes_per_row as usize;
+                let unpadded_bytes = dims.unpadded_bytes_per_row as usize;
+                pixels.clear();
+                {
+                    let frame = frame.slice(0..dims.linear_size()).get_mapped_range();
+                    frame
+                        .chunks(padded_bytes)
+                        .for_each(|chunk| pixels.extend_from_slice(&chunk[..unpadded_bytes]));
+                }
+                if let Err(err) = sink.write_frame(&pixels, dims.width, dims.height) {
+                    log::error!("{} output: {err:?}", sink.name());
+                }
+                done.store(false, Ordering::Release);
+            }
+        });
+        Self { sender, busy }
+    }
+
+    /// Returns a capture callback if the sink is ready for the next frame.
+    pub fn request_frame(
+        &self,
+    ) -> Option<impl FnOnce(Arc<wgpu::Buffer>, ImageDimentions) + Send + 'static> {
+        if self.busy.swap(true, Ordering::AcqRel) {
+            return None;
+        }
+        let sender = self.sender.clone();
+        let busy = self.busy.clone();
+        Some(move |frame, dims| {
+            if sender.send((frame, dims)).is_err() {
+                busy.store(false, Ordering::Release);
+            }
+        })
+    }
+}
+
+/// Writes frames into a file that other processes map, put it on `/dev/shm` to stay in memory.
+///
+/// Layout is a 24 byte little endian header `b"VOID"`, width: u32, height: u32,
+/// padding: u32, frame: u64 followed by `width * height` RGBA8 pixels.
+/// The frame counter is written last, readers poll it to detect new frames.
+pub struct SharedMemorySink {
+    path: PathBuf,
+    file: File,
+    frame: u64,
+}
+
+impl SharedMemorySink {
+    pub const HEADER_SIZE: u64 = 24;
+
+    pub fn new(path: PathBuf) -> Result<Self> {
+        let file = File::options()
+            .create(true)
+            .truncate(true)
+            .read(true)
+            .write(true)
+            .open(&path)
+            .wrap_err_with(|| format!("Failed to open shared memory output {}", path.display()))?;
+        Ok(Self {
+            path,
+            file,
+            frame: 0,
+        })
+    }
+}
+
+impl OutputSink for SharedMemorySink {
+    fn name(&self) -> &str {
+        "Shared Memory"
+    }
+
+    fn write_frame(&mut self, frame: &[u8], width: u32, height: u32) -> Result<()> {
+        let len = Self::HEADER_SIZE + frame.len() as u64;
+        if self.file.metadata()?.len() != len {
+            self.file.set_len(len).wrap_err_with(|| {
+                format!(
+                    "Failed to resize shared memory output {}",
+                    self.path.display()
+                )
+            })?;
+        }
+        self.frame += 1;
+
+        let mut header = [0u8; Self::HEADER_SIZE as usize];
+        header[0..4].copy_from_slice(b"VOID");
+        header[4..8].copy_from_slice(&width.to_le_bytes());
+        header[8..12].copy_from_slice(&height.to_le_bytes());
+        header[16..24].copy_from_slice(&self.frame.to_le_bytes());
+
+        self.file.seek(SeekFrom::Start(Self::HEADER_SIZE))?;
+        self.file.write_all(frame)?;
+        self.file.seek(SeekFrom::Start(0))?;
+        self.file.write_all(&header)?;
+        Ok(())
+    }
+}
+
+/// Motion JPEG over HTTP, any browser pointed at the address shows the preview.
+///
+/// Every client is written to by its own thread from a queue of [`MjpegSink::QUEUE`]
+/// frames, a slow one misses frames and a stalled one is dropped after
+/// [`MjpegSink::WRITE_TIMEOUT`], neither holds up the renderer.
+pub struct MjpegSink {
+    clients: Arc<Mutex<Vec<mpsc::SyncSender<Arc<Vec<u8>>>>>>,
+    quality: u8,
+}
+
+impl MjpegSink {
+    const BOUNDARY: &'static str = "voidinframe";
+    const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+    pub const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+    pub const QUEUE: usize = 2;
+
+    pub fn new(addr: SocketAddr) -> Result<Self> {
+        let listener = TcpListener::bind(addr)
+            .wrap_err_with(|| format!("Failed to bind MJPEG output to {addr}"))?;
+        log::info!("Serving MJPEG preview on http://{}", listener.local_addr()?);
+
+        let clients = Arc::new(Mutex::new(vec![]));
+        let accepted = clients.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let stream = match stream {
+                    Ok(stream) => stream,
+                    Err(err) => {
+                        log::warn!("MJPEG client rejected: {err}");
+                        continue;
+                    }
+                };
+                // A client that connects without sending a request holds its own thread
+                // until the timeout, never the ones behind it.
+                let accepted = accepted.clone();
+                std::thread::spawn(move || {
+                    let mut client = match Self::handshake(stream) {
+                        Ok(client) => client,
+                        Err(err) => {
+                            log::warn!("MJPEG client rejected: {err}");
+                            return;
+                        }
+                    };
+                    let (sender, frames) = mpsc::sync_channel(Self::QUEUE);
+                    accepted.lock().unwrap().push(sender);
+                    // Dropping `frames` on an error takes the client off the list.
+                    for jpeg in frames {
+                        if let Err(err) = Self::write_jpeg(&mut client, &jpeg) {
+                            log::info!("MJPEG client dropped: {err}");
+                            break;
+                        }
+                    }
+                });
+            }
+        });
+
+        Ok(Self {
+            clients,
+            quality: 80,
+        })
+    }
+
+    pub fn with_quality(mut self, quality: u8) -> Self {
+        self.quality = quality.clamp(1, 100);
+        self
+    }
+
+    fn handshake(mut stream: TcpStream) -> Result<TcpStream> {
+        // The request itself is irrelevant, every path serves the stream.
+        stream.set_read_timeout(Some(Self::REQUEST_TIMEOUT))?;
+        let mut request = [0u8; 1024];
+        let _ = stream.read(&mut request)?;
+        write!(
+            stream,
+            "HTTP/1.1 200 OK\r\n\
+             Cache-Control: no-cache\r\n\
+             Connection: close\r\n\
+             Content-Type: multipart/x-mixed-replace; boundary={}\r\n\r\n",
+            Self::BOUNDARY
+        )?;
+        stream.set_nodelay(true)?;
+        stream.set_write_timeout(Some(Self::WRITE_TIMEOUT))?;
+        Ok(stream)
+    }
+
+    fn write_jpeg(client: &mut TcpStream, jpeg: &[u8]) -> std::io::Result<()> {
+        write!(
+            client,
+            "--{}\r\nContent-Type: image/jpeg\r\nContent-Length: {}\r\n\r\n",
+            Self::BOUNDARY,
+            jpeg.len()
+        )?;
+        client.write_all(jpeg)?;
+        client.write_all(b"\r\n")
+    }
+}
+
+impl OutputSink for MjpegSink {
+    fn name(&self) -> &str {
+        "MJPEG"
+    }
+
+    fn write_frame(&mut self, frame: &[u8], width: u32, height: u32) -> Result<()> {
+        if self.clients.lock().unwrap().is_empty() {
+            return Ok(());
+        }
+
+        let mut jpeg = vec![];
+        JpegEncoder::new_with_quality(&mut jpeg, self.quality).encode(
+            frame,
+            width,
+            height,
+            ColorType::Rgba8,
+        )?;
+
+        let jpeg = Arc::new(jpeg);
+        self.clients
+            .lock()
+            .unwrap()
+            .retain(|client| match client.try_send(jpeg.clone()) {
+                Ok(()) | Err(mpsc::TrySendError::Full(_)) => true,
+                Err(mpsc::TrySendError::Disconnected(_)) => false,
+            });
+        Ok(())
+    }
+}
+
+impl Drop for MjpegSink {
+    // Ends the client threads, the accept thread keeps the list alive.
+    fn drop(&mut self) {
+        self.clients.lock().unwrap().clear();
+    }
+}
+
+/// H.264 in MPEG-TS served by ffmpeg, open it with `ffplay tcp://<addr>`.
+///
+/// ffmpeg waits for a viewer before it consumes frames. A writer thread feeds it, frames
+/// that find its queue full are dropped, so the renderer never waits on the encoder.
+pub struct H264Sink {
+    addr: SocketAddr,
+    encoder: Option<H264Encoder>,
+}
+
+struct H264Encoder {
+    process: Child,
+    frames: mpsc::SyncSender<Vec<u8>>,
+    width: u32,
+    height: u32,
+}
+
+impl H264Sink {
+    pub fn new(addr: SocketAddr) -> Self {
+        Self {
+            addr,
+            encoder: None,
+        }
+    }
+
+    /// Frames waiting for ffmpeg, the newer ones are dropped past it.
+    const QUEUE: usize = 2;
+
+    fn spawn_encoder(&self, width: u32, height: u32) -> Result<H264Encoder> {
+        #[rustfmt::skip]
+        let args = [
+            "-framerate", "60",
+            "-pix_fmt", "rgba",
+            "-f", "rawvideo",
+            "-i", "pipe:",
+            "-c:v", "libx264",
+            "-preset", "ultrafast",
+            "-tune", "zerolatency",
+            "-pix_fmt", "yuv420p",
+            "-f", "mpegts",
+        ];
+        let mut process = Command::new("ffmpeg")
+            .arg("-video_size")
+            .arg(format!("{width}x{height}"))
+            .args(args)
+            .arg(format!("tcp://{}?listen=1", self.addr))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .wrap_err("Failed to spawn ffmpeg for H.264 output")?;
+        let mut stdin = process.stdin.take().expect("ffmpeg stdin is piped");
+        let (frames, receiver) = mpsc::sync_channel::<Vec<u8>>(Self::QUEUE);
+        // Ends once the sink hangs up or ffmpeg exits, which drops the receiver.
+        std::thread::spawn(move || {
+            for frame in receiver {
+                if let Err(err) = stdin.write_all(&frame) {
+                    log::warn!("ffmpeg closed the H.264 stream: {err}");
+                    break;
+                }
+            }
+        });
+        Ok(H264Encoder {
+            process,
+            frames,
+            width,
+            height,
+        })
+    }
+
+    // ffmpeg may still wait for a viewer and never read its stdin to the end, so it is
+    // killed instead of waited for.
+    fn finish(&mut self) {
+        if let Some(H264Encoder {
+            mut process,
+            frames,
+            ..
+        }) = self.encoder.take()
+        {
+            drop(frames);
+            // Fails when ffmpeg already exited on its own.
+            let _ = process.kill();
+            if let Err(err) = process.wait() {
+                log::warn!("Failed to wait for ffmpeg: {err}");
+            }
+        }
+    }
+}
+
+impl OutputSink for H264Sink {
+    fn name(&self) -> &str {
+        "H.264"
+    }
+
+    fn write_frame(&mut self, frame: &[u8], width: u32, height: u32) -> Result<()> {
+        let resized = self.encoder.as_ref().map_or(true, |encoder| {
+            (encoder.width, encoder.height) != (width, height)
+        });
+        if resized {
+            self.finish();
+            self.encoder = Some(self.spawn_encoder(width, height)?);
+        }
+        let Some(encoder) = &self.encoder else {
+            return Ok(());
+        };
+        match encoder.frames.try_send(frame.to_vec()) {
+            Ok(()) | Err(mpsc::TrySendError::Full(_)) => Ok(()),
+            Err(mpsc::TrySendError::Disconnected(_)) => {
+                self.finish();
+                bail!("ffmpeg closed the H.264 stream");
+            }
+        }
+    }
+}
+
+impl Drop for H264Sink {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
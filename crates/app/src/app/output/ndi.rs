@@ -0,0 +1,147 @@
+use std::{
+    ffi::{c_char, c_float, c_int, c_void, CString},
+    path::PathBuf,
+};
+
+use color_eyre::{
+    eyre::{bail, WrapErr},
+    Result,
+};
+use libloading::Library;
+
+use super::OutputSink;
+
+#[repr(C)]
+struct SendCreate {
+    ndi_name: *const c_char,
+    groups: *const c_char,
+    clock_video: bool,
+    clock_audio: bool,
+}
+
+#[repr(C)]
+struct VideoFrameV2 {
+    xres: c_int,
+    yres: c_int,
+    four_cc: u32,
+    frame_rate_n: c_int,
+    frame_rate_d: c_int,
+    picture_aspect_ratio: c_float,
+    frame_format_type: c_int,
+    timecode: i64,
+    data: *const u8,
+    line_stride_in_bytes: c_int,
+    metadata: *const c_char,
+    timestamp: i64,
+}
+
+const FOURCC_RGBA: u32 = u32::from_le_bytes(*b"RGBA");
+const FRAME_FORMAT_PROGRESSIVE: c_int = 1;
+const TIMECODE_SYNTHESIZE: i64 = i64::MAX;
+
+type Initialize = unsafe extern "C" fn() -> bool;
+type SendCreateFn = unsafe extern "C" fn(*const SendCreate) -> *mut c_void;
+type SendVideo = unsafe extern "C" fn(*mut c_void, *const VideoFrameV2);
+type SendDestroy = unsafe extern "C" fn(*mut c_void);
+
+/// Publishes frames as an NDI source through the NDI runtime loaded at startup.
+///
+/// The runtime is looked up in `NDI_RUNTIME_DIR_V5` and then on the library path.
+/// NDI takes system memory, so frames go through the same readback as screenshots.
+/// Spout and Syphon need the native texture handles, which wgpu doesn't expose yet.
+pub struct NdiSink {
+    name: String,
+    sender: *mut c_void,
+    send_video: SendVideo,
+    send_destroy: SendDestroy,
+    // Keeps the function pointers above valid.
+    _runtime: Library,
+}
+
+// NDI senders may be used from any thread, but only one at a time.
+unsafe impl Send for NdiSink {}
+
+impl NdiSink {
+    #[cfg(target_os = "windows")]
+    const RUNTIME: &'static str = "Processing.NDI.Lib.x64.dll";
+    #[cfg(target_os = "macos")]
+    const RUNTIME: &'static str = "libndi.dylib";
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    const RUNTIME: &'static str = "libndi.so.5";
+
+    pub fn new(name: &str) -> Result<Self> {
+        let runtime = Self::load_runtime()?;
+        let c_name = CString::new(name).wrap_err("NDI source name contains a nul byte")?;
+        unsafe {
+            let initialize = *runtime.get::<Initialize>(b"NDIlib_initialize\0")?;
+            let send_create = *runtime.get::<SendCreateFn>(b"NDIlib_send_create\0")?;
+            let send_video = *runtime.get::<SendVideo>(b"NDIlib_send_send_video_v2\0")?;
+            let send_destroy = *runtime.get::<SendDestroy>(b"NDIlib_send_destroy\0")?;
+
+            if !initialize() {
+                bail!("NDI is not supported on this CPU");
+            }
+            let sender = send_create(&SendCreate {
+                ndi_name: c_name.as_ptr(),
+                groups: std::ptr::null(),
+                clock_video: false,
+                clock_audio: false,
+            });
+            if sender.is_null() {
+                bail!("Failed to create NDI source {name:?}");
+            }
+            log::info!("Publishing NDI source {name:?}");
+
+            Ok(Self {
+                name: format!("NDI {name}"),
+                sender,
+                send_video,
+                send_destroy,
+                _runtime: runtime,
+            })
+        }
+    }
+
+    fn load_runtime() -> Result<Library> {
+        let from_dir = std::env::var_os("NDI_RUNTIME_DIR_V5")
+            .map(|dir| PathBuf::from(dir).join(Self::RUNTIME))
+            .and_then(|path| unsafe { Library::new(path) }.ok());
+        match from_dir {
+            Some(runtime) => Ok(runtime),
+            None => unsafe { Library::new(Self::RUNTIME) }
+                .wrap_err_with(|| format!("Failed to load the NDI runtime {}", Self::RUNTIME)),
+        }
+    }
+}
+
+impl OutputSink for NdiSink {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn write_frame(&mut self, frame: &[u8], width: u32, height: u32) -> Result<()> {
+        let video = VideoFrameV2 {
+            xres: width as _,
+            yres: height as _,
+            four_cc: FOURCC_RGBA,
+            frame_rate_n: 60,
+            frame_rate_d: 1,
+            picture_aspect_ratio: width as f32 / height as f32,
+            frame_format_type: FRAME_FORMAT_PROGRESSIVE,
+            timecode: TIMECODE_SYNTHESIZE,
+            data: frame.as_ptr(),
+            line_stride_in_bytes: (width * 4) as _,
+            metadata: std::ptr::null(),
+            timestamp: 0,
+        };
+        // Synchronous send, NDI copies the frame before returning.
+        unsafe { (self.send_video)(self.sender, &video) };
+        Ok(())
+    }
+}
+
+impl Drop for NdiSink {
+    fn drop(&mut self) {
+        unsafe { (self.send_destroy)(self.sender) };
+    }
+}
@@ -20,7 +20,7 @@ use wgpu::{
 
 use crate::{app::App, Gpu, SHADER_FOLDER};
 
-use components::{bind_group_layout, ImportResolver, Watcher};
+use components::{bind_group_layout, shader_library, ImportResolver, Watcher};
 
 use super::{gbuffer::GBuffer, view_target};
 
@@ -34,6 +34,7 @@ pub struct PipelineArena {
     compute: ComputeArena,
     path_mapping: AHashMap<PathBuf, AHashSet<Either<RenderHandle, ComputeHandle>>>,
     import_mapping: AHashMap<PathBuf, AHashSet<PathBuf>>,
+    errors: AHashMap<PathBuf, String>,
     file_watcher: Watcher,
     gpu: Arc<Gpu>,
 }
@@ -143,6 +144,7 @@ impl PipelineArena {
             },
             path_mapping: AHashMap::new(),
             import_mapping: AHashMap::new(),
+            errors: AHashMap::new(),
             file_watcher,
             gpu,
         }
@@ -175,14 +177,19 @@ impl PipelineArena {
         let source = resolver
             .populate(&path)
             .with_context(|| eyre!("Failed to process file: {}", path.display()))?;
-        let module = self
-            .gpu
-            .device()
-            .create_shader_module(wgpu::ShaderModuleDescriptor {
-                label: path.to_str(),
-                source: wgpu::ShaderSource::Wgsl(source.contents.into()),
-            });
-        let handle = self.process_render_pipeline(&module, descriptor);
+        let name = descriptor.name().to_owned();
+        let gpu = self.gpu.clone();
+        let handle = gpu
+            .error_scope(|| {
+                let module = gpu
+                    .device()
+                    .create_shader_module(wgpu::ShaderModuleDescriptor {
+                        label: path.to_str(),
+                        source: wgpu::ShaderSource::Wgsl(source.contents.into()),
+                    });
+                self.process_render_pipeline(&module, descriptor)
+            })
+            .wrap_err_with(|| format!("while creating {name} from {}", path.display()))?;
         self.path_mapping
             .entry(path.clone())
             .or_insert_with_key(|path| {
@@ -222,14 +229,19 @@ impl PipelineArena {
         let source = resolver
             .populate(&path)
             .with_context(|| eyre!("Failed to process file: {}", path.display()))?;
-        let module = self
-            .gpu
-            .device()
-            .create_shader_module(wgpu::ShaderModuleDescriptor {
-                label: path.to_str(),
-                source: wgpu::ShaderSource::Wgsl(source.contents.into()),
-            });
-        let handle = self.process_compute_pipeline(&module, descriptor);
+        let name = descriptor.name().to_owned();
+        let gpu = self.gpu.clone();
+        let handle = gpu
+            .error_scope(|| {
+                let module = gpu
+                    .device()
+                    .create_shader_module(wgpu::ShaderModuleDescriptor {
+                        label: path.to_str(),
+                        source: wgpu::ShaderSource::Wgsl(source.contents.into()),
+                    });
+                self.process_compute_pipeline(&module, descriptor)
+            })
+            .wrap_err_with(|| format!("while creating {name} from {}", path.display()))?;
         self.path_mapping
             .entry(path.clone())
             .or_insert_with_key(|path| {
@@ -250,49 +262,59 @@ impl PipelineArena {
         Ok(handle)
     }
 
-    pub fn reload_pipelines(&mut self, path: &Path) {
-        let mut resolver = ImportResolver::new(&[SHADER_FOLDER]);
-
-        if self.path_mapping.contains_key(path) {
-            let source = match resolver.populate(path) {
-                Ok(source) => source,
-                Err(err) => {
-                    log::error!("Failed to process file {}: {err}", path.display());
-                    return;
-                }
-            };
+    /// Watches a non-shader path, changes arrive through the same channel as shader reloads.
+    pub fn watch_path(&mut self, path: impl AsRef<Path>) -> Result<()> {
+        self.file_watcher.watch_file(path)
+    }
 
-            // Remove unused includes
-            for (import, links) in self.import_mapping.iter_mut() {
-                if import != path && links.contains(path) && !source.imports.contains(import) {
-                    links.remove(path);
-                }
+    /// Points every file `shader` includes, directly or not, back at it so an edit to any of
+    /// them recompiles `shader`.
+    fn track_imports(&mut self, shader: &Path, imports: &AHashSet<PathBuf>) {
+        // Remove unused includes
+        for (import, links) in self.import_mapping.iter_mut() {
+            if import != shader && links.contains(shader) && !imports.contains(import) {
+                links.remove(shader);
             }
+        }
 
-            // Add new includes
-            for import in source.imports {
-                self.import_mapping
-                    .entry(import)
-                    .or_insert_with_key(|import| {
-                        let _ = self.file_watcher.watch_file(import).map_err(|err| {
-                            log::error!("Failed to watch file {}: {err}", import.display())
-                        });
-                        AHashSet::new()
-                    })
-                    .insert(path.to_path_buf());
+        // Add new includes, embedded library files never change
+        for import in imports {
+            if shader_library::is_embedded(import) {
+                continue;
             }
+            self.import_mapping
+                .entry(import.clone())
+                .or_insert_with_key(|import| {
+                    let _ = self.file_watcher.watch_file(import).map_err(|err| {
+                        log::error!("Failed to watch file {}: {err}", import.display())
+                    });
+                    AHashSet::new()
+                })
+                .insert(shader.to_path_buf());
         }
+    }
+
+    pub fn reload_pipelines(&mut self, path: &Path) {
+        let mut resolver = ImportResolver::new(&[SHADER_FOLDER]);
 
-        let device = self.gpu.device();
-        for path in &self.import_mapping[path] {
+        let Some(dependents) = self.import_mapping.get(path) else {
+            return;
+        };
+        let dependents: Vec<_> = dependents.iter().cloned().collect();
+        for path in &dependents {
             // Compile shader module
             let source = match resolver.populate(path) {
                 Ok(source) => source,
                 Err(err) => {
                     log::error!("Failed to process file {}: {err}", path.display());
+                    self.errors.insert(path.clone(), format!("{err:#}"));
                     continue;
                 }
             };
+            // The edit may have added or dropped includes anywhere down the tree.
+            self.track_imports(path, &source.imports);
+
+            let device = self.gpu.device();
             device.push_error_scope(wgpu::ErrorFilter::Validation);
             let module = self
                 .gpu
@@ -306,11 +328,13 @@ impl PipelineArena {
                 Some(err) => {
                     log::error!("Validation error on shader compilation.");
                     eprintln!("{err}");
+                    self.errors.insert(path.clone(), err.to_string());
                     continue;
                 }
             }
 
-            // Iterate over pipelines and update them
+            // Iterate over pipelines and update them, failed ones keep the last good pipeline
+            let mut error = None;
             for &handle in &self.path_mapping[path] {
                 self.gpu
                     .device()
@@ -327,7 +351,8 @@ impl PipelineArena {
 
                             Some(err) => {
                                 log::error!("Validation error on pipeline reloading.");
-                                eprintln!("{err}")
+                                eprintln!("{err}");
+                                error = Some(format!("{}: {err}", desc.name()));
                             }
                         }
                     }
@@ -341,15 +366,28 @@ impl PipelineArena {
                             }
                             Some(err) => {
                                 log::error!("Validation error on pipeline reloading.");
-                                eprintln!("{err}")
+                                eprintln!("{err}");
+                                error = Some(format!("{}: {err}", desc.name()));
                             }
                         }
                     }
                 }
             }
+            match error {
+                Some(error) => self.errors.insert(path.clone(), error),
+                None => self.errors.remove(path),
+            };
         }
     }
 
+    /// Shaders whose last reload failed with the error, they keep running their last good
+    /// pipelines until the next change compiles.
+    pub fn shader_errors(&self) -> impl Iterator<Item = (&Path, &str)> {
+        self.errors
+            .iter()
+            .map(|(path, error)| (path.as_path(), error.as_str()))
+    }
+
     pub fn device(&self) -> &wgpu::Device {
         self.gpu.device()
     }
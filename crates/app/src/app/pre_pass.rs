@@ -0,0 +1,94 @@
+use components::world::World;
+use slotmap::SlotMap;
+
+use super::ProfilerCommandEncoder;
+
+slotmap::new_key_type! {
+    pub struct PrePassId;
+}
+
+type RecordFn = Box<dyn FnMut(&World, &mut ProfilerCommandEncoder)>;
+
+struct PrePass {
+    label: String,
+    order: i32,
+    enabled: bool,
+    record: RecordFn,
+}
+
+/// User compute work recorded at the start of every frame, before the example renders.
+///
+/// Pre-passes go into the frame encoder after the pools uploaded the changes of the last
+/// update, so whatever they write into pool buffers is what the built-in passes read,
+/// with wgpu inserting the barriers in between. Every pre-pass gets a profiler scope
+/// under its label. They run once per rendered frame, in ascending `order` and in the
+/// order they were added on ties.
+///
+/// The resource stays borrowed while pre-passes record, they can't reach it through the
+/// world they are given.
+#[derive(Default)]
+pub struct PrePasses {
+    passes: SlotMap<PrePassId, PrePass>,
+    sorted: Vec<PrePassId>,
+}
+
+impl PrePasses {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(
+        &mut self,
+        label: impl Into<String>,
+        order: i32,
+        record: impl FnMut(&World, &mut ProfilerCommandEncoder) + 'static,
+    ) -> PrePassId {
+        let id = self.passes.insert(PrePass {
+            label: label.into(),
+            order,
+            enabled: true,
+            record: Box::new(record),
+        });
+        self.sorted.push(id);
+        // Stable, ties keep the order they were added in.
+        self.sorted.sort_by_key(|id| self.passes[*id].order);
+        id
+    }
+
+    /// Returns `false` if the pre-pass was already removed.
+    pub fn remove(&mut self, id: PrePassId) -> bool {
+        self.sorted.retain(|&other| other != id);
+        self.passes.remove(id).is_some()
+    }
+
+    /// Disabled pre-passes keep their place and are skipped.
+    pub fn set_enabled(&mut self, id: PrePassId, enabled: bool) {
+        if let Some(pass) = self.passes.get_mut(id) {
+            pass.enabled = enabled;
+        }
+    }
+
+    pub fn is_enabled(&self, id: PrePassId) -> bool {
+        self.passes.get(id).is_some_and(|pass| pass.enabled)
+    }
+
+    pub fn len(&self) -> usize {
+        self.passes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.passes.is_empty()
+    }
+
+    pub(crate) fn record(&mut self, world: &World, encoder: &mut ProfilerCommandEncoder) {
+        for id in &self.sorted {
+            let pass = &mut self.passes[*id];
+            if !pass.enabled {
+                continue;
+            }
+            encoder.profile_start(&pass.label);
+            (pass.record)(world, encoder);
+            encoder.profile_end();
+        }
+    }
+}
@@ -0,0 +1,77 @@
+#[cfg(feature = "profiler")]
+pub use wgpu_profiler::{scope::OwningScope, GpuProfiler, GpuTimerScopeResult};
+
+#[cfg(not(feature = "profiler"))]
+pub use noop::{GpuProfiler, GpuTimerScopeResult, OwningScope};
+
+/// Stand-ins used when the crate is built without the `profiler` feature.
+#[cfg(not(feature = "profiler"))]
+mod noop {
+    use std::{marker::PhantomData, ops::Range};
+
+    pub struct GpuProfiler;
+
+    impl GpuProfiler {
+        pub fn new(
+            _adapter: &wgpu::Adapter,
+            _device: &wgpu::Device,
+            _queue: &wgpu::Queue,
+            _max_num_pending_frames: usize,
+        ) -> Self {
+            Self
+        }
+
+        pub fn begin_scope<R>(&mut self, _label: &str, _recorder: &mut R, _device: &wgpu::Device) {}
+
+        pub fn end_scope<R>(&mut self, _recorder: &mut R) {}
+
+        pub fn resolve_queries(&mut self, _encoder: &mut wgpu::CommandEncoder) {}
+
+        pub fn end_frame(&mut self) -> Result<(), ()> {
+            Ok(())
+        }
+
+        pub fn process_finished_frame(&mut self) -> Option<Vec<GpuTimerScopeResult>> {
+            None
+        }
+    }
+
+    pub struct GpuTimerScopeResult {
+        pub label: String,
+        pub time: Range<f64>,
+        pub nested_scopes: Vec<GpuTimerScopeResult>,
+    }
+
+    pub struct OwningScope<'a, W> {
+        recorder: W,
+        _profiler: PhantomData<&'a mut GpuProfiler>,
+    }
+
+    impl<'a, W> OwningScope<'a, W> {
+        pub fn start(
+            _label: &str,
+            _profiler: &'a mut GpuProfiler,
+            recorder: W,
+            _device: &wgpu::Device,
+        ) -> Self {
+            Self {
+                recorder,
+                _profiler: PhantomData,
+            }
+        }
+    }
+
+    impl<'a, W> std::ops::Deref for OwningScope<'a, W> {
+        type Target = W;
+
+        fn deref(&self) -> &Self::Target {
+            &self.recorder
+        }
+    }
+
+    impl<'a, W> std::ops::DerefMut for OwningScope<'a, W> {
+        fn deref_mut(&mut self) -> &mut Self::Target {
+            &mut self.recorder
+        }
+    }
+}
@@ -0,0 +1,314 @@
+use std::collections::{BTreeMap, VecDeque};
+
+use super::profiler::GpuTimerScopeResult;
+
+/// Timing of one GPU profiler scope of the last finished frame, nested like the scopes
+/// were recorded.
+#[derive(Debug, Clone)]
+pub struct ScopeTiming {
+    pub label: String,
+    pub ms: f32,
+    pub nested: Vec<ScopeTiming>,
+}
+
+/// Min, max and average since the last reset and the recent samples of one timing,
+/// in milliseconds.
+#[derive(Debug, Clone)]
+pub struct TimingStats {
+    pub min: f32,
+    pub max: f32,
+    sum: f64,
+    count: u64,
+    history: VecDeque<f32>,
+}
+
+impl TimingStats {
+    fn new() -> Self {
+        Self {
+            min: f32::INFINITY,
+            max: 0.,
+            sum: 0.,
+            count: 0,
+            history: VecDeque::with_capacity(ProfilerOverlay::HISTORY),
+        }
+    }
+
+    fn push(&mut self, ms: f32) {
+        self.min = self.min.min(ms);
+        self.max = self.max.max(ms);
+        self.sum += ms as f64;
+        self.count += 1;
+        if self.history.len() == ProfilerOverlay::HISTORY {
+            self.history.pop_front();
+        }
+        self.history.push_back(ms);
+    }
+
+    fn reset(&mut self) {
+        *self = Self {
+            history: std::mem::take(&mut self.history),
+            ..Self::new()
+        };
+    }
+
+    pub fn avg(&self) -> f32 {
+        (self.sum / self.count.max(1) as f64) as f32
+    }
+
+    pub fn last(&self) -> Option<f32> {
+        self.history.back().copied()
+    }
+
+    /// Oldest first, at most [`ProfilerOverlay::HISTORY`] samples.
+    pub fn history(&self) -> &VecDeque<f32> {
+        &self.history
+    }
+}
+
+/// Live GPU scope tree and time graph in an egui window, fed by [`crate::App`] with the
+/// finished frames of the GPU profiler and the CPU times of its update and render.
+///
+/// Toggled with F6. GPU timings need the `profiler` feature and timestamp queries.
+#[derive(Debug)]
+pub struct ProfilerOverlay {
+    pub enabled: bool,
+    last_frame: Vec<ScopeTiming>,
+    // Keyed by `CPU/<phase>` or `GPU/<scope>/<nested scope>`.
+    stats: BTreeMap<String, TimingStats>,
+    graphed: Vec<String>,
+}
+
+impl Default for ProfilerOverlay {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ProfilerOverlay {
+    /// Frames kept for the graph.
+    pub const HISTORY: usize = 240;
+    pub const CPU_FRAME: &'static str = "CPU/Frame";
+    pub const CPU_UPDATE: &'static str = "CPU/Update";
+    pub const CPU_RENDER: &'static str = "CPU/Render";
+
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            last_frame: vec![],
+            stats: BTreeMap::new(),
+            graphed: vec![Self::CPU_FRAME.into(), "GPU/Main Render Scope".into()],
+        }
+    }
+
+    /// Records the scopes of a frame returned by `GpuProfiler::process_finished_frame`.
+    pub fn push_gpu_frame(&mut self, scopes: &[GpuTimerScopeResult]) {
+        fn convert(
+            scopes: &[GpuTimerScopeResult],
+            prefix: &str,
+            stats: &mut BTreeMap<String, TimingStats>,
+        ) -> Vec<ScopeTiming> {
+            scopes
+                .iter()
+                .map(|scope| {
+                    let label = scope.label.trim().to_string();
+                    let ms = ((scope.time.end - scope.time.start) * 1e3) as f32;
+                    let path = format!("{prefix}/{label}");
+                    stats
+                        .entry(path.clone())
+                        .or_insert_with(TimingStats::new)
+                        .push(ms);
+                    ScopeTiming {
+                        nested: convert(&scope.nested_scopes, &path, stats),
+                        label,
+                        ms,
+                    }
+                })
+                .collect()
+        }
+        self.last_frame = convert(scopes, "GPU", &mut self.stats);
+    }
+
+    /// Records a CPU time, `key` is one of the `CPU_*` constants or another `CPU/` path.
+    pub fn push_cpu(&mut self, key: &str, ms: f32) {
+        self.stats
+            .entry(key.to_string())
+            .or_insert_with(TimingStats::new)
+            .push(ms);
+    }
+
+    pub fn last_frame(&self) -> &[ScopeTiming] {
+        &self.last_frame
+    }
+
+    pub fn stats(&self, key: &str) -> Option<&TimingStats> {
+        self.stats.get(key)
+    }
+
+    /// Restarts min, max and average, the graph keeps its samples.
+    pub fn reset_stats(&mut self) {
+        self.stats.values_mut().for_each(TimingStats::reset);
+    }
+
+    /// Adds `key` to the graph or takes it out.
+    pub fn toggle_graphed(&mut self, key: &str) {
+        match self.graphed.iter().position(|graphed| graphed == key) {
+            Some(index) => {
+                self.graphed.remove(index);
+            }
+            None => self.graphed.push(key.to_string()),
+        }
+    }
+}
+
+#[cfg(feature = "egui")]
+impl ProfilerOverlay {
+    const GRAPH_HEIGHT: f32 = 120.;
+    const PALETTE: [egui::Color32; 6] = [
+        egui::Color32::from_rgb(240, 200, 80),
+        egui::Color32::from_rgb(90, 200, 240),
+        egui::Color32::from_rgb(230, 90, 90),
+        egui::Color32::from_rgb(120, 220, 120),
+        egui::Color32::from_rgb(200, 120, 240),
+        egui::Color32::from_rgb(240, 150, 90),
+    ];
+
+    pub fn ui(&mut self, ctx: &egui::Context) {
+        if !self.enabled {
+            return;
+        }
+        let mut enabled = self.enabled;
+        egui::Window::new("Profiler (F6)")
+            .open(&mut enabled)
+            .default_width(360.)
+            .show(ctx, |ui| {
+                self.graph_ui(ui);
+                ui.horizontal(|ui| {
+                    if ui.button("Reset Stats").clicked() {
+                        self.reset_stats();
+                    }
+                    ui.label("Click a row to graph it");
+                });
+                let mut toggled = None;
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    egui::Grid::new("profiler_scopes")
+                        .num_columns(5)
+                        .striped(true)
+                        .show(ui, |ui| {
+                            for header in ["Scope", "Last", "Min", "Avg", "Max"] {
+                                ui.strong(header);
+                            }
+                            ui.end_row();
+                            for key in [Self::CPU_FRAME, Self::CPU_UPDATE, Self::CPU_RENDER] {
+                                let label = key.trim_start_matches("CPU/");
+                                self.row_ui(ui, key, label, 0, &mut toggled);
+                            }
+                            if self.last_frame.is_empty() {
+                                ui.label("No GPU timings");
+                                ui.end_row();
+                            }
+                            self.tree_ui(ui, &self.last_frame, "GPU", 0, &mut toggled);
+                        });
+                });
+                if let Some(key) = toggled {
+                    self.toggle_graphed(&key);
+                }
+            });
+        self.enabled = enabled;
+    }
+
+    fn graph_ui(&self, ui: &mut egui::Ui) {
+        let series: Vec<_> = self
+            .graphed
+            .iter()
+            .filter_map(|key| Some((key, self.stats.get(key)?)))
+            .collect();
+        let top = series
+            .iter()
+            .flat_map(|(_, stats)| stats.history.iter().copied())
+            .fold(1f32, f32::max)
+            * 1.1;
+
+        let size = egui::vec2(ui.available_width(), Self::GRAPH_HEIGHT);
+        let (response, painter) = ui.allocate_painter(size, egui::Sense::hover());
+        let rect = response.rect;
+        painter.rect_filled(rect, 2., egui::Color32::from_black_alpha(160));
+        let step = rect.width() / (Self::HISTORY - 1) as f32;
+        for (index, (_, stats)) in series.iter().enumerate() {
+            // Newest sample on the right edge.
+            let points = stats
+                .history
+                .iter()
+                .rev()
+                .enumerate()
+                .map(|(x, &ms)| {
+                    egui::pos2(
+                        rect.right() - x as f32 * step,
+                        rect.bottom() - ms / top * rect.height(),
+                    )
+                })
+                .collect();
+            let color = Self::PALETTE[index % Self::PALETTE.len()];
+            painter.add(egui::Shape::line(points, egui::Stroke::new(1.5, color)));
+        }
+        painter.text(
+            rect.left_top() + egui::vec2(4., 2.),
+            egui::Align2::LEFT_TOP,
+            format!("{top:.2} ms"),
+            egui::FontId::monospace(11.),
+            egui::Color32::GRAY,
+        );
+
+        ui.horizontal_wrapped(|ui| {
+            for (index, (key, stats)) in series.iter().enumerate() {
+                let color = Self::PALETTE[index % Self::PALETTE.len()];
+                let text = format!("{key} {:.2}", stats.last().unwrap_or_default());
+                ui.colored_label(color, text);
+            }
+        });
+    }
+
+    fn tree_ui(
+        &self,
+        ui: &mut egui::Ui,
+        scopes: &[ScopeTiming],
+        prefix: &str,
+        depth: usize,
+        toggled: &mut Option<String>,
+    ) {
+        for scope in scopes {
+            let key = format!("{prefix}/{}", scope.label);
+            self.row_ui(ui, &key, &scope.label, depth, toggled);
+            self.tree_ui(ui, &scope.nested, &key, depth + 1, toggled);
+        }
+    }
+
+    fn row_ui(
+        &self,
+        ui: &mut egui::Ui,
+        key: &str,
+        label: &str,
+        depth: usize,
+        toggled: &mut Option<String>,
+    ) {
+        let Some(stats) = self.stats.get(key) else {
+            return;
+        };
+        let graphed = self.graphed.iter().any(|graphed| graphed == key);
+        let text = format!("{:indent$}{label}", "", indent = 2 * depth);
+        if ui
+            .selectable_label(graphed, egui::RichText::new(text).monospace())
+            .clicked()
+        {
+            *toggled = Some(key.to_string());
+        }
+        for ms in [
+            stats.last().unwrap_or_default(),
+            stats.min,
+            stats.avg(),
+            stats.max,
+        ] {
+            ui.monospace(format!("{ms:.3}"));
+        }
+        ui.end_row();
+    }
+}
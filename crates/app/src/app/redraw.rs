@@ -0,0 +1,196 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+/// Environment variable starting [`crate::run`] in [`RenderMode::OnDemand`].
+pub const ON_DEMAND_ENV: &str = "VOIDIN_ON_DEMAND";
+/// Environment variable capping the frame rate while the window is focused.
+pub const MAX_FPS_ENV: &str = "VOIDIN_MAX_FPS";
+/// Environment variable overriding [`Redraw::DEFAULT_BACKGROUND_FPS`].
+pub const BACKGROUND_FPS_ENV: &str = "VOIDIN_BACKGROUND_FPS";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum RenderMode {
+    /// Renders as fast as the surface presents.
+    #[default]
+    Continuous,
+    /// Renders only after something changed and sleeps otherwise, for editor-style use.
+    OnDemand,
+}
+
+/// Frame pacing of [`Redraw`], what the examples keep between runs.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct FrameSettings {
+    pub mode: RenderMode,
+    pub max_fps: Option<f32>,
+    pub background_fps: f32,
+}
+
+impl Default for FrameSettings {
+    fn default() -> Self {
+        Self {
+            mode: RenderMode::Continuous,
+            max_fps: None,
+            background_fps: Redraw::DEFAULT_BACKGROUND_FPS,
+        }
+    }
+}
+
+impl FrameSettings {
+    /// Overrides the fields whose environment variable is set.
+    pub fn with_env(mut self) -> Self {
+        if let Some(value) = std::env::var_os(ON_DEMAND_ENV) {
+            self.mode = match value != "0" {
+                true => RenderMode::OnDemand,
+                false => RenderMode::Continuous,
+            };
+        }
+        if let Some(fps) = fps_from_env(MAX_FPS_ENV) {
+            self.max_fps = Some(fps);
+        }
+        if let Some(fps) = fps_from_env(BACKGROUND_FPS_ENV) {
+            self.background_fps = fps;
+        }
+        self
+    }
+}
+
+/// Decides whether the event loop renders the next frame or sleeps in `ControlFlow::Wait`.
+///
+/// Input, camera movement, resizes and shader reloads are picked up by [`crate::run`].
+/// Anything else changing the image, like a plugin tweaking a pass, calls [`Redraw::request`],
+/// and animations keep the loop awake with [`Redraw::set_animating`].
+///
+/// Frames are also spaced by [`Redraw::frame_interval`], which drops to the background
+/// frame rate while the window is unfocused or minimized.
+pub struct Redraw {
+    mode: RenderMode,
+    frames_left: u32,
+    animating: bool,
+    max_fps: Option<f32>,
+    background_fps: f32,
+    background: bool,
+}
+
+impl Redraw {
+    /// Frames rendered after a change, enough for temporal passes to converge.
+    pub const SETTLE_FRAMES: u32 = 32;
+    pub const DEFAULT_BACKGROUND_FPS: f32 = 5.;
+
+    pub fn new(mode: RenderMode) -> Self {
+        Self {
+            mode,
+            frames_left: Self::SETTLE_FRAMES,
+            animating: false,
+            max_fps: None,
+            background_fps: Self::DEFAULT_BACKGROUND_FPS,
+            background: false,
+        }
+    }
+
+    pub fn from_env() -> Self {
+        let mut redraw = Self::new(RenderMode::Continuous);
+        redraw.set_frame_settings(FrameSettings::default().with_env());
+        redraw
+    }
+
+    pub fn frame_settings(&self) -> FrameSettings {
+        FrameSettings {
+            mode: self.mode,
+            max_fps: self.max_fps,
+            background_fps: self.background_fps,
+        }
+    }
+
+    pub fn set_frame_settings(&mut self, settings: FrameSettings) {
+        self.set_mode(settings.mode);
+        self.set_max_fps(settings.max_fps);
+        self.set_background_fps(settings.background_fps);
+    }
+
+    pub fn mode(&self) -> RenderMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: RenderMode) {
+        self.mode = mode;
+        self.request();
+    }
+
+    /// Renders the next [`Self::SETTLE_FRAMES`] frames.
+    pub fn request(&mut self) {
+        self.frames_left = Self::SETTLE_FRAMES;
+    }
+
+    /// Keeps rendering every frame while raised.
+    pub fn set_animating(&mut self, animating: bool) {
+        self.animating = animating;
+    }
+
+    pub fn is_animating(&self) -> bool {
+        self.animating
+    }
+
+    /// Caps the frame rate while focused, `None` renders as fast as the surface presents.
+    pub fn set_max_fps(&mut self, max_fps: Option<f32>) {
+        self.max_fps = max_fps.filter(|fps| *fps > 0.);
+    }
+
+    pub fn max_fps(&self) -> Option<f32> {
+        self.max_fps
+    }
+
+    pub fn set_background_fps(&mut self, fps: f32) {
+        self.background_fps = fps.max(0.1);
+    }
+
+    pub fn background_fps(&self) -> f32 {
+        self.background_fps
+    }
+
+    /// Raised while the window is unfocused or minimized.
+    pub fn set_background(&mut self, background: bool) {
+        if self.background && !background {
+            self.request();
+        }
+        self.background = background;
+    }
+
+    pub fn is_background(&self) -> bool {
+        self.background
+    }
+
+    /// Minimum time between two frames.
+    pub fn frame_interval(&self) -> Option<Duration> {
+        let fps = match self.background {
+            true => Some(self.background_fps),
+            false => self.max_fps,
+        };
+        fps.map(|fps| Duration::from_secs_f32(1. / fps))
+    }
+
+    /// Takes one frame, `false` means nothing changed and the loop can sleep.
+    pub fn next_frame(&mut self) -> bool {
+        if self.mode == RenderMode::Continuous || self.animating {
+            return true;
+        }
+        match self.frames_left {
+            0 => false,
+            _ => {
+                self.frames_left -= 1;
+                true
+            }
+        }
+    }
+}
+
+fn fps_from_env(var: &str) -> Option<f32> {
+    let value = std::env::var(var).ok()?;
+    match value.parse::<f32>() {
+        Ok(fps) if fps > 0. => Some(fps),
+        _ => {
+            log::warn!("Ignoring {var}: expected a positive frame rate, got {value:?}");
+            None
+        }
+    }
+}
@@ -0,0 +1,67 @@
+use rand::{rngs::SmallRng, RngCore, SeedableRng};
+
+/// Environment variable fixing the seed of [`SceneRng`].
+pub const SEED_ENV: &str = "VOIDIN_SEED";
+
+/// Seedable source for scene randomness, so a run can be reproduced from its seed.
+///
+/// The seed comes from `VOIDIN_SEED` or is picked at startup and logged,
+/// screenshots store it in their metadata.
+pub struct SceneRng {
+    seed: u64,
+    rng: SmallRng,
+}
+
+impl SceneRng {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            rng: SmallRng::seed_from_u64(seed),
+        }
+    }
+
+    pub fn from_env() -> Self {
+        let seed = match std::env::var(SEED_ENV).map(|seed| seed.parse::<u64>()) {
+            Ok(Ok(seed)) => seed,
+            Ok(Err(err)) => {
+                log::warn!("Ignoring {SEED_ENV}: {err}");
+                rand::random()
+            }
+            Err(_) => rand::random(),
+        };
+        log::info!("Scene seed: {seed}");
+        Self::new(seed)
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Restarts the sequence from `seed`.
+    pub fn reseed(&mut self, seed: u64) {
+        *self = Self::new(seed);
+    }
+
+    /// Independent generator for a subsystem, unaffected by how much the shared one was used.
+    pub fn fork(&self, stream: u64) -> SmallRng {
+        SmallRng::seed_from_u64(self.seed ^ stream.wrapping_mul(0x9E37_79B9_7F4A_7C15))
+    }
+}
+
+impl RngCore for SceneRng {
+    fn next_u32(&mut self) -> u32 {
+        self.rng.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.rng.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.rng.fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.rng.try_fill_bytes(dest)
+    }
+}
@@ -0,0 +1,251 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use color_eyre::{
+    eyre::{bail, Context},
+    Result,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::asset_browser::ASSETS_FOLDER;
+
+/// Environment variable overriding where downloaded samples go, by default
+/// [`SAMPLE_CACHE_FOLDER`] inside the assets folder.
+pub const SAMPLE_CACHE_ENV: &str = "VOIDIN_SAMPLE_CACHE";
+/// Environment variable keeping [`SampleAssets`] from downloading, missing files are an error.
+pub const OFFLINE_ENV: &str = "VOIDIN_OFFLINE";
+pub const SAMPLE_CACHE_FOLDER: &str = ".samples";
+
+const SAMPLE_MODELS_URL: &str =
+    "https://raw.githubusercontent.com/KhronosGroup/glTF-Sample-Models/master";
+/// A checkout of the sample models repository inside the assets folder wins over the cache.
+const SAMPLE_MODELS_CHECKOUT: &str = "glTF-Sample-Models";
+/// Sha256 of every cached file, recorded when it is first seen.
+const CHECKSUMS_FILE: &str = "checksums.json";
+
+/// Models of the Khronos glTF sample repository the examples use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SampleModel {
+    Sponza,
+    DamagedHelmet,
+    FlightHelmet,
+    CesiumMan,
+}
+
+impl SampleModel {
+    /// Path inside the sample models repository.
+    pub fn path(self) -> &'static str {
+        match self {
+            Self::Sponza => "2.0/Sponza/glTF/Sponza.gltf",
+            Self::DamagedHelmet => "2.0/DamagedHelmet/glTF-Binary/DamagedHelmet.glb",
+            Self::FlightHelmet => "2.0/FlightHelmet/glTF/FlightHelmet.gltf",
+            Self::CesiumMan => "2.0/CesiumMan/glTF-Binary/CesiumMan.glb",
+        }
+    }
+}
+
+/// Finds sample models on disk and downloads the missing ones on first use.
+///
+/// Files are checked against the checksums recorded when they were downloaded, so a
+/// truncated or swapped file is an error instead of a broken import. Downloading needs
+/// the `fetch` feature.
+pub struct SampleAssets {
+    checkout: PathBuf,
+    cache: PathBuf,
+    offline: bool,
+}
+
+impl SampleAssets {
+    pub fn new(cache: impl Into<PathBuf>) -> Self {
+        Self {
+            checkout: Path::new(ASSETS_FOLDER).join(SAMPLE_MODELS_CHECKOUT),
+            cache: cache.into(),
+            offline: false,
+        }
+    }
+
+    /// Cache and offline mode from [`SAMPLE_CACHE_ENV`] and [`OFFLINE_ENV`].
+    pub fn from_env() -> Self {
+        let cache = std::env::var_os(SAMPLE_CACHE_ENV)
+            .map(PathBuf::from)
+            .unwrap_or_else(|| Path::new(ASSETS_FOLDER).join(SAMPLE_CACHE_FOLDER));
+        let offline = std::env::var_os(OFFLINE_ENV).is_some_and(|value| value != "0");
+        Self::new(cache).offline(offline)
+    }
+
+    /// Only looks at what is already on disk.
+    pub fn offline(mut self, offline: bool) -> Self {
+        self.offline = offline;
+        self
+    }
+
+    pub fn cache(&self) -> &Path {
+        &self.cache
+    }
+
+    /// Local path of `model`, downloaded together with the buffers and images it
+    /// references when it isn't there yet.
+    pub fn fetch(&self, model: SampleModel) -> Result<PathBuf> {
+        let checkout = self.checkout.join(model.path());
+        if checkout.exists() {
+            return Ok(checkout);
+        }
+
+        let mut checksums = self.read_checksums()?;
+        let fetched = self.fetch_with_dependencies(model.path(), &mut checksums);
+        // Whatever made it to disk keeps its checksum, even when a later file failed.
+        self.write_checksums(&checksums)?;
+        fetched.wrap_err_with(|| format!("Failed to fetch the {model:?} sample"))
+    }
+
+    fn fetch_with_dependencies(
+        &self,
+        relative: &str,
+        checksums: &mut BTreeMap<String, String>,
+    ) -> Result<PathBuf> {
+        let path = self.fetch_file(relative, relative, checksums)?;
+        if path.extension().is_some_and(|ext| ext == "gltf") {
+            let document: serde_json::Value = serde_json::from_slice(&std::fs::read(&path)?)
+                .wrap_err_with(|| format!("Failed to parse {}", path.display()))?;
+            let dir = relative.rsplit_once('/').map_or("", |(dir, _)| dir);
+            for uri in external_uris(&document) {
+                let file = percent_decode(uri);
+                if file.split('/').any(|part| part == "..") {
+                    bail!("{relative} references {uri} outside of its directory");
+                }
+                self.fetch_file(&format!("{dir}/{file}"), &format!("{dir}/{uri}"), checksums)?;
+            }
+        }
+        Ok(path)
+    }
+
+    /// `remote` is `relative` as it appears in urls.
+    fn fetch_file(
+        &self,
+        relative: &str,
+        remote: &str,
+        checksums: &mut BTreeMap<String, String>,
+    ) -> Result<PathBuf> {
+        let path = self.cache.join(relative);
+        if path.exists() {
+            let checksum = sha256(&std::fs::read(&path)?);
+            match checksums.get(relative) {
+                Some(expected) if *expected != checksum => bail!(
+                    "{} doesn't match its checksum, delete it to download it again",
+                    path.display()
+                ),
+                Some(_) => {}
+                None => {
+                    checksums.insert(relative.to_owned(), checksum);
+                }
+            }
+            return Ok(path);
+        }
+
+        if self.offline {
+            bail!(
+                "{relative} isn't in {} and {OFFLINE_ENV} is set",
+                self.cache.display()
+            );
+        }
+        let bytes = download(&format!("{SAMPLE_MODELS_URL}/{remote}"))?;
+        let checksum = sha256(&bytes);
+        if checksums
+            .get(relative)
+            .is_some_and(|expected| *expected != checksum)
+        {
+            bail!("Downloaded {relative} doesn't match the checksum recorded for it");
+        }
+
+        // Written next to the target first, so an interrupted run leaves no half file.
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let partial = path.with_extension("part");
+        std::fs::write(&partial, &bytes)?;
+        std::fs::rename(&partial, &path)?;
+        checksums.insert(relative.to_owned(), checksum);
+        Ok(path)
+    }
+
+    fn read_checksums(&self) -> Result<BTreeMap<String, String>> {
+        let path = self.cache.join(CHECKSUMS_FILE);
+        if !path.exists() {
+            return Ok(BTreeMap::new());
+        }
+        serde_json::from_slice(&std::fs::read(&path)?)
+            .wrap_err_with(|| format!("Failed to parse {}", path.display()))
+    }
+
+    fn write_checksums(&self, checksums: &BTreeMap<String, String>) -> Result<()> {
+        if checksums.is_empty() {
+            return Ok(());
+        }
+        std::fs::create_dir_all(&self.cache)?;
+        let json = serde_json::to_string_pretty(checksums)?;
+        std::fs::write(self.cache.join(CHECKSUMS_FILE), json)?;
+        Ok(())
+    }
+}
+
+/// Relative file uris of the buffers and images of a glTF document.
+fn external_uris(document: &serde_json::Value) -> Vec<&str> {
+    ["buffers", "images"]
+        .iter()
+        .filter_map(|key| document[key].as_array())
+        .flatten()
+        .filter_map(|entry| entry["uri"].as_str())
+        .filter(|uri| !uri.starts_with("data:") && !uri.contains("://"))
+        .collect()
+}
+
+fn percent_decode(uri: &str) -> String {
+    let bytes = uri.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| uri.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn sha256(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+#[cfg(feature = "fetch")]
+fn download(url: &str) -> Result<Vec<u8>> {
+    use std::io::Read;
+
+    log::info!("Downloading {url}");
+    let response = ureq::get(url)
+        .call()
+        .wrap_err_with(|| format!("Failed to download {url}"))?;
+    let mut bytes = vec![];
+    response.into_reader().read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+#[cfg(not(feature = "fetch"))]
+fn download(url: &str) -> Result<Vec<u8>> {
+    bail!("Can't download {url} without the `fetch` feature")
+}
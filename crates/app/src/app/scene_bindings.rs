@@ -0,0 +1,176 @@
+use std::num::NonZeroU32;
+
+use bvh::{BvhNode, TlasNode};
+use components::{
+    bind_group_layout::{BindGroupLayout, WrappedBindGroupLayout},
+    world::World,
+    NonZeroSized,
+};
+
+use crate::{
+    Instance, InstancePool, Material, MaterialPool, MeshInfo, MeshPool, TexturePool, MAX_TEXTURES,
+};
+
+/// Buffers and bind groups the bind group was made from, it's stale once any changed.
+#[derive(PartialEq, Eq)]
+struct SceneKey {
+    buffers: [wgpu::Id<wgpu::Buffer>; 7],
+    textures: wgpu::Id<wgpu::BindGroup>,
+    tlas_len: usize,
+    instance_count: usize,
+}
+
+impl SceneKey {
+    fn new(world: &World) -> Self {
+        let meshes = world.unwrap::<MeshPool>();
+        let instances = world.unwrap::<InstancePool>();
+        Self {
+            buffers: [
+                meshes.tlas_nodes.global_id(),
+                instances.instances.global_id(),
+                meshes.mesh_info.global_id(),
+                meshes.bvh_nodes.global_id(),
+                meshes.vertices.global_id(),
+                meshes.indices.global_id(),
+                world.unwrap::<MaterialPool>().buffer().global_id(),
+            ],
+            textures: world.unwrap::<TexturePool>().bind_group.global_id(),
+            tlas_len: meshes.tlas_nodes.len(),
+            instance_count: instances.instances.len(),
+        }
+    }
+}
+
+/// The whole scene in one bind group for traced passes, kept in sync with the pools by the app.
+///
+/// Bindings 0 to 5 match the trace group of `MeshPool`: TLAS nodes, instances, mesh infos,
+/// BLAS nodes, vertices and indices. Then come the materials, the bindless textures and
+/// their sampler at 6, 7 and 8. The layout never changes, the bind group is remade at the
+/// start of a frame whenever a pool reallocated one of its buffers.
+pub struct SceneBindings {
+    pub layout: BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    key: SceneKey,
+}
+
+impl SceneBindings {
+    pub fn new(world: &World) -> Self {
+        let layout =
+            world
+                .device()
+                .create_bind_group_layout_wrap(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some("Scene Bind Group Layout"),
+                    entries: &[
+                        storage_entry(0, TlasNode::NSIZE),
+                        storage_entry(1, Instance::NSIZE),
+                        storage_entry(2, MeshInfo::NSIZE),
+                        storage_entry(3, BvhNode::NSIZE),
+                        storage_entry(4, f32::NSIZE),
+                        storage_entry(5, u32::NSIZE),
+                        storage_entry(6, Material::NSIZE),
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 7,
+                            visibility: wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::COMPUTE,
+                            ty: wgpu::BindingType::Texture {
+                                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                                view_dimension: wgpu::TextureViewDimension::D2,
+                                multisampled: false,
+                            },
+                            count: NonZeroU32::new(MAX_TEXTURES),
+                        },
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 8,
+                            visibility: wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::COMPUTE,
+                            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                            count: None,
+                        },
+                    ],
+                });
+        let bind_group = Self::create_bind_group(&layout, world);
+        Self {
+            layout,
+            bind_group,
+            key: SceneKey::new(world),
+        }
+    }
+
+    pub fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+
+    /// Remakes the bind group if a pool buffer was reallocated, returns `true` if it did.
+    pub fn update(&mut self, world: &World) -> bool {
+        let key = SceneKey::new(world);
+        if key == self.key {
+            return false;
+        }
+        self.bind_group = Self::create_bind_group(&self.layout, world);
+        self.key = key;
+        true
+    }
+
+    fn create_bind_group(layout: &wgpu::BindGroupLayout, world: &World) -> wgpu::BindGroup {
+        let meshes = world.unwrap::<MeshPool>();
+        let instances = world.unwrap::<InstancePool>();
+        let materials = world.unwrap::<MaterialPool>();
+        let textures = world.unwrap::<TexturePool>();
+        let views: Vec<_> = textures.views.iter().collect();
+        world
+            .device()
+            .create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Scene Bind Group"),
+                layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: meshes.tlas_nodes.as_tight_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: instances.instances.as_tight_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: meshes.mesh_info.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: meshes.bvh_nodes.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 4,
+                        resource: meshes.vertices.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 5,
+                        resource: meshes.indices.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 6,
+                        resource: materials.buffer().as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 7,
+                        resource: wgpu::BindingResource::TextureViewArray(&views),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 8,
+                        resource: wgpu::BindingResource::Sampler(textures.sampler()),
+                    },
+                ],
+            })
+    }
+}
+
+fn storage_entry(binding: u32, min_binding_size: wgpu::BufferSize) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::COMPUTE,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only: true },
+            has_dynamic_offset: false,
+            min_binding_size: Some(min_binding_size),
+        },
+        count: None,
+    }
+}
@@ -53,13 +53,16 @@ impl ScreenshotCtx {
         self.image_dimentions = new_dims;
     }
 
+    /// Records the copy of `src_texture` into `encoder`. The returned closure
+    /// has to be called after the encoder is submitted to map the result.
     pub fn capture_frame(
         &self,
         world: &World,
         blitter: &Blitter,
+        encoder: &mut wgpu::CommandEncoder,
         src_texture: &wgpu::BindGroup,
         callback: impl FnOnce(Arc<wgpu::Buffer>, ImageDimentions) + Send + 'static,
-    ) {
+    ) -> impl FnOnce() {
         let dims = self.image_dimentions;
 
         let download = Arc::new(world.device().create_buffer(&wgpu::BufferDescriptor {
@@ -70,13 +73,8 @@ impl ScreenshotCtx {
         }));
 
         let view = self.texture.create_view(&Default::default());
-        let mut encoder = world
-            .device()
-            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                label: Some("Screenshot"),
-            });
         blitter.blit_to_texture_with_binding(
-            &mut encoder,
+            encoder,
             world.device(),
             src_texture,
             &view,
@@ -96,17 +94,17 @@ impl ScreenshotCtx {
             self.texture.size(),
         );
 
-        world.queue().submit(Some(encoder.finish()));
-
-        let buff = download.clone();
-        let image_slice = download.slice(0..dims.linear_size());
-        image_slice.map_async(MapMode::Read, move |res| {
-            if let Err(err) = res {
-                log::error!("Oh no, failed to map buffer: {err}");
-                return;
-            }
+        move || {
+            let buff = download.clone();
+            let image_slice = download.slice(0..dims.linear_size());
+            image_slice.map_async(MapMode::Read, move |res| {
+                if let Err(err) = res {
+                    log::error!("Oh no, failed to map buffer: {err}");
+                    return;
+                }
 
-            callback(buff, dims);
-        });
+                callback(buff, dims);
+            });
+        }
     }
 }
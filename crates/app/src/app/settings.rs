@@ -0,0 +1,122 @@
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter},
+    path::PathBuf,
+};
+
+use color_eyre::{eyre::WrapErr, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use winit::{
+    dpi::{PhysicalPosition, PhysicalSize},
+    window::{Window, WindowBuilder},
+};
+
+use super::{frame_hash::FRAME_HASH_ENV, redraw::FrameSettings};
+use crate::timeline::CameraKey;
+
+/// Environment variable overriding [`SETTINGS_FILE`], left empty nothing is loaded or saved.
+pub const SETTINGS_ENV: &str = "VOIDIN_SETTINGS";
+pub const SETTINGS_FILE: &str = "voidin_settings.json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WindowState {
+    /// Physical pixels of the inner size.
+    pub size: [u32; 2],
+    /// Outer position, missing where the platform doesn't tell it.
+    pub position: Option<[i32; 2]>,
+    pub maximized: bool,
+}
+
+impl WindowState {
+    /// `None` while minimized, there is no size worth keeping.
+    pub fn from_window(window: &Window) -> Option<Self> {
+        let PhysicalSize { width, height } = window.inner_size();
+        if width == 0 || height == 0 {
+            return None;
+        }
+        Some(Self {
+            size: [width, height],
+            position: window.outer_position().ok().map(|pos| [pos.x, pos.y]),
+            maximized: window.is_maximized(),
+        })
+    }
+
+    pub fn apply(&self, builder: WindowBuilder) -> WindowBuilder {
+        let [width, height] = self.size;
+        let builder = builder
+            .with_inner_size(PhysicalSize::new(width, height))
+            .with_maximized(self.maximized);
+        match self.position {
+            Some([x, y]) => builder.with_position(PhysicalPosition::new(x, y)),
+            None => builder,
+        }
+    }
+}
+
+/// What [`crate::run`] restores for an example on startup, saved on exit.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ExampleSettings {
+    pub window: Option<WindowState>,
+    pub camera: CameraKey,
+    pub frame: FrameSettings,
+}
+
+/// One JSON file holding [`ExampleSettings`] of every example, keyed by
+/// [`crate::Example::name`]. Entries of other examples are kept as they are, so one that
+/// fails to parse after a format change only resets that example.
+pub struct SettingsStore {
+    path: Option<PathBuf>,
+}
+
+impl SettingsStore {
+    pub fn new(path: Option<PathBuf>) -> Self {
+        Self { path }
+    }
+
+    /// Off while hashing frames, runs being compared have to start from the same state.
+    pub fn from_env() -> Self {
+        if std::env::var_os(FRAME_HASH_ENV).is_some() {
+            return Self::new(None);
+        }
+        let path = match std::env::var_os(SETTINGS_ENV) {
+            Some(path) if path.is_empty() => None,
+            Some(path) => Some(PathBuf::from(path)),
+            None => Some(PathBuf::from(SETTINGS_FILE)),
+        };
+        Self::new(path)
+    }
+
+    /// `None` until the example was saved once.
+    pub fn load(&self, name: &str) -> Result<Option<ExampleSettings>> {
+        let Some(entry) = self.read()?.remove(name) else {
+            return Ok(None);
+        };
+        let settings = serde_json::from_value(entry)
+            .wrap_err_with(|| format!("Failed to parse the saved settings of {name}"))?;
+        Ok(Some(settings))
+    }
+
+    pub fn save(&self, name: &str, settings: &ExampleSettings) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        // A broken file is replaced rather than blocking every later save.
+        let mut entries = self.read().unwrap_or_default();
+        entries.insert(name.to_owned(), serde_json::to_value(settings)?);
+        let file = File::create(path)
+            .wrap_err_with(|| format!("Failed to create settings {}", path.display()))?;
+        serde_json::to_writer_pretty(BufWriter::new(file), &entries)?;
+        Ok(())
+    }
+
+    fn read(&self) -> Result<Map<String, Value>> {
+        let Some(path) = self.path.as_ref().filter(|path| path.exists()) else {
+            return Ok(Map::new());
+        };
+        let file = File::open(path)
+            .wrap_err_with(|| format!("Failed to open settings {}", path.display()))?;
+        serde_json::from_reader(BufReader::new(file))
+            .wrap_err_with(|| format!("Failed to parse settings {}", path.display()))
+    }
+}
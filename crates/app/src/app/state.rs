@@ -7,9 +7,12 @@ use components::{
 };
 
 pub enum StateAction {
+    ToggleHud,
     Screenshot,
     StartRecording,
     FinishRecording,
+    CycleDebugView,
+    ToggleProfiler,
 }
 
 pub struct AppState {
@@ -19,6 +22,10 @@ pub struct AppState {
     pub input: Input,
     pub keyboard_map: KeyboardMap,
     pub dt: f64,
+    /// Time left in the fixed step accumulator, as a fraction of `FIXED_TIME_STEP`.
+    /// Moving instances are drawn this far from their previous step to the last one,
+    /// runners without an accumulator keep it at 1.
+    pub alpha: f64,
     recording: bool,
 }
 
@@ -32,6 +39,7 @@ impl AppState {
             keyboard_map: keyboard_map.unwrap_or_default(),
             recording: false,
             dt: 0.,
+            alpha: 1.,
         }
     }
 
@@ -65,6 +73,9 @@ impl AppState {
         self.camera.position = self.camera.rig.final_transform.position;
         self.camera.rotation = self.camera.rig.final_transform.rotation;
 
+        if self.keyboard().was_just_pressed(VirtualKeyCode::F2) {
+            actions.push(StateAction::ToggleHud);
+        };
         if self.keyboard().was_just_pressed(VirtualKeyCode::F3) {
             actions.push(StateAction::Screenshot);
         };
@@ -76,6 +87,12 @@ impl AppState {
             }
             self.recording = !self.recording;
         };
+        if self.keyboard().was_just_pressed(VirtualKeyCode::F5) {
+            actions.push(StateAction::CycleDebugView);
+        };
+        if self.keyboard().was_just_pressed(VirtualKeyCode::F6) {
+            actions.push(StateAction::ToggleProfiler);
+        };
         actions
     }
 
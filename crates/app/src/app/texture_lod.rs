@@ -0,0 +1,40 @@
+/// Mip bias of material texture lookups, written into `CameraUniform::mip_bias` every update.
+///
+/// Rendering below the output resolution selects mips for the internal resolution,
+/// which a temporal upscaler then blurs further. The render scale biases the lookups
+/// back to the mips the output resolution would pick, the manual bias comes on top.
+pub struct TextureLod {
+    render_scale: f32,
+    bias: f32,
+}
+
+impl TextureLod {
+    pub fn new() -> Self {
+        Self {
+            render_scale: 1.,
+            bias: 0.,
+        }
+    }
+
+    /// Internal resolution divided by the output one, set by passes rendering at a reduced size.
+    pub fn set_render_scale(&mut self, scale: f32) {
+        self.render_scale = scale.clamp(0.01, 1.);
+    }
+
+    pub fn render_scale(&self) -> f32 {
+        self.render_scale
+    }
+
+    /// Added to the derived bias, negative values sharpen and positive ones blur.
+    pub fn set_bias(&mut self, bias: f32) {
+        self.bias = bias;
+    }
+
+    pub fn bias(&self) -> f32 {
+        self.bias
+    }
+
+    pub fn mip_bias(&self) -> f32 {
+        self.render_scale.log2() + self.bias
+    }
+}
@@ -0,0 +1,30 @@
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) color: vec3<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) normal: vec3<f32>,
+    @location(1) color: vec3<f32>,
+}
+
+@group(0) @binding(0) var<uniform> world_to_clip: mat4x4<f32>;
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = world_to_clip * vec4(in.position, 1.0);
+    out.normal = in.normal;
+    out.color = in.color;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // Key light from the camera side, textures are skipped.
+    let light = normalize(vec3(0.6, 1.0, 0.8));
+    let diffuse = abs(dot(normalize(in.normal), light));
+    return vec4(in.color * (0.25 + 0.75 * diffuse), 1.0);
+}
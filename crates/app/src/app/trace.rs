@@ -0,0 +1,73 @@
+use std::{
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use color_eyre::{eyre::WrapErr, Result};
+
+/// Environment variable enabling [`ApiTrace`], the value is the folder receiving the traces.
+pub const TRACE_ENV: &str = "VOIDIN_TRACE";
+/// Environment variable limiting an [`ApiTrace`] to the first N frames.
+pub const TRACE_FRAMES_ENV: &str = "VOIDIN_TRACE_FRAMES";
+
+/// Records every wgpu call of the device into a folder, so a gpu bug can be reported with
+/// the trace instead of the scene and its assets.
+///
+/// The device is traced from its creation, wgpu can't attach a trace later on. To keep
+/// the trace short the app exits after [`TRACE_FRAMES_ENV`] frames, which also flushes it.
+/// Needs the `trace` feature, otherwise wgpu logs an error and records nothing.
+///
+/// Traces are replayed with the `play` binary of the wgpu player matching the wgpu
+/// version in `Cargo.toml`: `cargo run -p player --features winit -- <trace folder>`.
+pub struct ApiTrace {
+    dir: PathBuf,
+    frames_left: Option<u32>,
+}
+
+impl ApiTrace {
+    pub fn from_env() -> Result<Option<Self>> {
+        let Some(root) = std::env::var_os(TRACE_ENV) else {
+            return Ok(None);
+        };
+        let frames_left = match std::env::var(TRACE_FRAMES_ENV) {
+            Ok(frames) => Some(
+                frames
+                    .parse()
+                    .wrap_err_with(|| format!("Invalid {TRACE_FRAMES_ENV}: {frames:?}"))?,
+            ),
+            Err(_) => None,
+        };
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        Self::new(
+            Path::new(&root).join(format!("trace-{timestamp}")),
+            frames_left,
+        )
+        .map(Some)
+    }
+
+    pub fn new(dir: impl Into<PathBuf>, frames_left: Option<u32>) -> Result<Self> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)
+            .wrap_err_with(|| format!("Failed to create {}", dir.display()))?;
+        Ok(Self { dir, frames_left })
+    }
+
+    /// Folder handed to `Adapter::request_device`.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Counts a rendered frame, returns `true` once the requested frames are captured.
+    pub fn frame_finished(&mut self) -> bool {
+        match &mut self.frames_left {
+            Some(frames) => {
+                *frames = frames.saturating_sub(1);
+                *frames == 0
+            }
+            None => false,
+        }
+    }
+}
@@ -0,0 +1,122 @@
+use winit::{event::WindowEvent, window::Window};
+
+use components::Gpu;
+
+#[cfg(feature = "egui")]
+pub struct Ui {
+    context: egui::Context,
+    renderer: egui_wgpu::Renderer,
+    state: egui_winit::State,
+    // Built by `draw`, painted after the captures of the clean frame.
+    pending: Option<(
+        Vec<egui::ClippedPrimitive>,
+        egui_wgpu::renderer::ScreenDescriptor,
+    )>,
+}
+
+#[cfg(feature = "egui")]
+impl Ui {
+    pub fn new(gpu: &Gpu, window: &Window, format: wgpu::TextureFormat, sample_count: u32) -> Self {
+        let renderer = egui_wgpu::renderer::Renderer::new(gpu.device(), format, None, sample_count);
+        let context = egui::Context::default();
+        {
+            let mut arc_style = context.style();
+            let style = std::sync::Arc::make_mut(&mut arc_style);
+            style.visuals.window_shadow = egui::epaint::Shadow::NONE;
+            context.set_style(style.clone());
+        }
+        let state = egui_winit::State::new(window);
+        Self {
+            context,
+            renderer,
+            state,
+            pending: None,
+        }
+    }
+
+    /// Returns `true` if the event was consumed by the ui.
+    pub fn on_event(&mut self, event: &WindowEvent) -> bool {
+        self.state.on_event(&self.context, event).consumed
+    }
+
+    /// Runs the ui and uploads its buffers, [`Ui::paint`] draws it at the end of the frame.
+    pub fn draw(
+        &mut self,
+        gpu: &Gpu,
+        window: &Window,
+        encoder: &mut crate::ProfilerCommandEncoder,
+        width: u32,
+        height: u32,
+        ui_builder: impl FnOnce(&egui::Context),
+    ) {
+        let screen_descriptor = egui_wgpu::renderer::ScreenDescriptor {
+            size_in_pixels: [width, height],
+            pixels_per_point: self.state.pixels_per_point(),
+        };
+
+        let full_output = self
+            .context
+            .run(self.state.take_egui_input(window), |ctx| ui_builder(ctx));
+
+        let paint_jobs = self.context.tessellate(full_output.shapes);
+        let textures_delta = full_output.textures_delta;
+
+        for (texture_id, image_delta) in &textures_delta.set {
+            self.renderer
+                .update_texture(gpu.device(), gpu.queue(), *texture_id, image_delta);
+        }
+        for texture_id in &textures_delta.free {
+            self.renderer.free_texture(texture_id);
+        }
+        self.renderer.update_buffers(
+            gpu.device(),
+            gpu.queue(),
+            encoder,
+            &paint_jobs,
+            &screen_descriptor,
+        );
+        self.pending = Some((paint_jobs, screen_descriptor));
+    }
+
+    pub fn paint(&mut self, encoder: &mut wgpu::CommandEncoder, target: &wgpu::TextureView) {
+        let Some((paint_jobs, screen_descriptor)) = self.pending.take() else {
+            return;
+        };
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("UI Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+        self.renderer
+            .render(&mut render_pass, paint_jobs.as_slice(), &screen_descriptor);
+    }
+}
+
+/// Stand-in used when the crate is built without the `egui` feature.
+#[cfg(not(feature = "egui"))]
+pub struct Ui;
+
+#[cfg(not(feature = "egui"))]
+impl Ui {
+    pub fn new(
+        _gpu: &Gpu,
+        _window: &Window,
+        _format: wgpu::TextureFormat,
+        _sample_count: u32,
+    ) -> Self {
+        Self
+    }
+
+    pub fn on_event(&mut self, _event: &WindowEvent) -> bool {
+        false
+    }
+
+    pub fn paint(&mut self, _encoder: &mut wgpu::CommandEncoder, _target: &wgpu::TextureView) {}
+}
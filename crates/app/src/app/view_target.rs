@@ -51,7 +51,8 @@ impl ViewTarget {
             format: Self::FORMAT,
             usage: wgpu::TextureUsages::TEXTURE_BINDING
                 | wgpu::TextureUsages::RENDER_ATTACHMENT
-                | wgpu::TextureUsages::COPY_DST,
+                | wgpu::TextureUsages::COPY_DST
+                | wgpu::TextureUsages::COPY_SRC,
             view_formats: &[Self::FORMAT, Self::FORMAT.add_srgb_suffix()],
         };
         let a = world.device().create_texture(&desc);
@@ -4,7 +4,7 @@ use color_eyre::Result;
 use components::FpsCounter;
 use std::time::Instant;
 
-use glam::vec3;
+use glam::{vec3, Vec3};
 use log::warn;
 use wgpu::SurfaceError;
 use winit::{
@@ -14,26 +14,68 @@ use winit::{
 };
 
 pub use crate::app::App;
+pub mod animation;
 mod app;
 pub mod models;
 pub mod pass;
+pub mod plugin;
 pub mod prelude;
+pub mod scene;
+pub mod scene_file;
+pub mod streaming;
+pub mod timeline;
 
-pub use crate::models::GltfDocument;
+pub use crate::animation::{
+    AnimatedScene, AnimationClip, AnimationId, AnimationPlayer, NodeTransform,
+};
+pub use crate::models::{
+    GltfDocument, GltfSkeleton, IesProfile, ImportCanceled, ImportProgress, ImportResult,
+    ImportStatus, PlyModel, StlModel, TestScene,
+};
+pub use crate::scene::{NodeId, SceneGraph};
+pub use crate::scene_file::{Scene, SpawnedScene};
+pub use crate::streaming::{Streaming, StreamingSettings};
+pub use crate::timeline::Timeline;
 pub use app::DEFAULT_SAMPLER_DESC;
 pub use app::{
+    adapter::{AdapterSelection, ADAPTER_ENV, ADAPTER_FLAG, LIST_ADAPTERS_FLAG},
+    asset_browser::{AssetBrowser, ASSETS_FOLDER, THUMBNAILS_FOLDER},
+    audio::{AudioAnalyzer, AudioBinding, AudioUniform},
+    crash::{CrashContext, CrashReporter, CRASH_DIR_ENV, CRASH_REPORTS_FOLDER},
+    debug_draw::DebugDraw,
+    diagnostics::{Diagnostics, DIAGNOSTICS_FLAG},
+    editor::{Editor, GizmoMode},
+    frame_arena::{FrameAllocation, FrameArena},
+    frame_hash::{FrameHasher, FRAME_HASH_ENV},
     gbuffer::GBuffer,
     global_ubo::{GlobalUniformBinding, GlobalsBindGroup, Uniform},
+    imposters::{ImposterId, Imposters},
+    live_params::{ControlMessage, LiveParam, LiveParams},
+    occlusion::{OccluderId, OccluderMesh, SoftwareOcclusion},
+    output::{self, Output, OutputSink},
     pipeline,
+    pre_pass::{PrePassId, PrePasses},
+    profiler_overlay::{ProfilerOverlay, ScopeTiming, TimingStats},
+    redraw::{FrameSettings, Redraw, RenderMode, BACKGROUND_FPS_ENV, MAX_FPS_ENV, ON_DEMAND_ENV},
+    rng::SceneRng,
+    sample_assets::{
+        SampleAssets, SampleModel, OFFLINE_ENV, SAMPLE_CACHE_ENV, SAMPLE_CACHE_FOLDER,
+    },
+    scene_bindings::SceneBindings,
+    settings::{ExampleSettings, SettingsStore, WindowState, SETTINGS_ENV, SETTINGS_FILE},
     state::AppState,
-    ProfilerCommandEncoder, RenderContext, UpdateContext, ViewTarget,
+    texture_lod::TextureLod,
+    trace::{ApiTrace, TRACE_ENV, TRACE_FRAMES_ENV},
+    ProfilerCommandEncoder, ProfilerScope, RenderContext, UpdateContext, ViewTarget,
 };
 pub use components::{
     bind_group_layout::{self, WrappedBindGroupLayout},
+    shader_library,
     shared::*,
-    Camera, Gpu, LerpExt, NonZeroSized, ResizableBuffer, ResizableBufferExt, Watcher,
-    {CameraUniform, CameraUniformBinding}, {KeyMap, KeyboardMap},
+    Camera, Components, Entity, Gpu, LerpExt, NonZeroSized, ResizableBuffer, ResizableBufferExt,
+    Watcher, {CameraUniform, CameraUniformBinding, MAX_VIEWS}, {KeyMap, KeyboardMap},
 };
+#[cfg(feature = "egui")]
 pub use egui;
 pub use pools::*;
 pub use winit::{dpi::LogicalSize, window::WindowBuilder};
@@ -49,10 +91,22 @@ pub trait Example: 'static + Sized {
         "Example"
     }
 
+    /// Shader of the `ShadingPass`, examples ship their own look by overriding it.
+    fn shading_shader() -> &'static str {
+        "shaders/shading.wgsl"
+    }
+
+    /// Shader of the `PostProcess` pass.
+    fn postprocess_shader() -> &'static str {
+        "shaders/postprocess.wgsl"
+    }
+
     fn init(gpu: &mut App) -> Result<Self>;
     fn setup_scene(&mut self, _app: &mut App) -> Result<()> {
         Ok(())
     }
+    /// Runs at the fixed update rate, its encoder is submitted ahead of the pool uploads.
+    /// Compute work writing pool buffers belongs in [`PrePasses`] instead.
     fn update(&mut self, _ctx: UpdateContext) {}
     fn resize(&mut self, _gpu: &Gpu, _width: u32, _height: u32) {}
     fn render(&mut self, ctx: RenderContext);
@@ -67,8 +121,11 @@ pub fn run_default<E: Example>() -> color_eyre::Result<()> {
     run::<E>(window, camera)
 }
 
+/// Runs the example, starting from the window, camera and frame settings it was last
+/// closed with, see [`SettingsStore`]. Given [`DIAGNOSTICS_FLAG`] it prints [`Diagnostics`]
+/// of the adapter instead.
 pub fn run<E: Example>(
-    window_builder: WindowBuilder,
+    mut window_builder: WindowBuilder,
     mut camera: Camera,
 ) -> color_eyre::Result<()> {
     color_eyre::install()?;
@@ -81,9 +138,41 @@ pub fn run<E: Example>(
         .filter_module("naga", log::LevelFilter::Error)
         .init();
 
+    if std::env::args()
+        .skip(1)
+        .any(|arg| arg == LIST_ADAPTERS_FLAG)
+    {
+        for (index, info) in App::adapters().iter().enumerate() {
+            println!(
+                "{index}: {} ({:?}, {:?})",
+                info.name, info.device_type, info.backend
+            );
+        }
+        return Ok(());
+    }
+
+    let settings_store = SettingsStore::from_env();
+    let saved = settings_store.load(E::name()).unwrap_or_else(|err| {
+        warn!("Ignoring the saved settings: {err:#}");
+        None
+    });
+    if let Some(saved) = &saved {
+        if let Some(window) = &saved.window {
+            window_builder = window.apply(window_builder);
+        }
+        let key = saved.camera;
+        camera = Camera::new(Vec3::from(key.position), key.yaw, key.pitch);
+    }
+
     let event_loop = winit::event_loop::EventLoopBuilder::with_user_event().build();
     let window = window_builder.with_title(E::name()).build(&event_loop)?;
 
+    if Diagnostics::requested() {
+        let (surface, adapter) = App::create_adapter(&window)?;
+        println!("{}", Diagnostics::new(&adapter, Some(&surface)));
+        return Ok(());
+    }
+
     let PhysicalSize { width, height } = window.inner_size();
     camera.aspect = width as f32 / height as f32;
 
@@ -104,6 +193,16 @@ pub fn run<E: Example>(
     let watcher = Watcher::new(event_loop.create_proxy())?;
 
     let mut app = App::new(&window, watcher)?;
+    if let Some(saved) = &saved {
+        app.world
+            .unwrap_mut::<Redraw>()
+            .set_frame_settings(saved.frame.with_env());
+    }
+    app.crash_reporter().install();
+    app.set_output(&Output::from_env()?)?;
+    let frame_hasher = FrameHasher::from_env(&app.world)?;
+    app.set_frame_hasher(frame_hasher);
+    app.world.get::<AudioAnalyzer>()?.listen_env()?;
     let info = app.get_info();
     println!("{info}");
 
@@ -116,13 +215,41 @@ pub fn run<E: Example>(
     let mut current_instant = Instant::now();
     let mut accumulated_time = 0.;
     let mut fps_counter = FpsCounter::new();
+    let mut rendering = true;
+    let mut last_frame = Instant::now();
+    let mut wake_at = None;
+    let (mut focused, mut minimized) = (true, false);
 
     event_loop.run(move |event, _, control_flow| {
-        *control_flow = ControlFlow::Wait;
+        *control_flow = match wake_at {
+            Some(instant) => ControlFlow::WaitUntil(instant),
+            None => ControlFlow::Wait,
+        };
 
         match event {
             Event::MainEventsCleared => {
+                let interval = app.world.unwrap::<Redraw>().frame_interval();
+                wake_at = interval.map(|interval| last_frame + interval);
+                if let Some(instant) = wake_at.filter(|instant| Instant::now() < *instant) {
+                    rendering = false;
+                    *control_flow = ControlFlow::WaitUntil(instant);
+                    return;
+                }
+
+                let playing = app.world.unwrap::<Timeline>().playing
+                    || app.world.unwrap::<AnimationPlayer>().is_playing();
+                rendering = app.world.unwrap_mut::<Redraw>().next_frame() || playing;
+                if !rendering {
+                    // Don't catch up on the time spent asleep.
+                    current_instant = Instant::now();
+                    accumulated_time = 0.;
+                    wake_at = None;
+                    return;
+                }
+
                 let new_instant = Instant::now();
+                last_frame = new_instant;
+                wake_at = interval.map(|interval| last_frame + interval);
                 let frame_time = new_instant
                     .duration_since(current_instant)
                     .as_secs_f64()
@@ -130,6 +257,7 @@ pub fn run<E: Example>(
                 current_instant = new_instant;
 
                 let mut actions = vec![];
+                let camera = (app_state.camera.position, app_state.camera.rotation);
                 accumulated_time += frame_time;
                 while accumulated_time >= FIXED_TIME_STEP {
                     app_state.input.tick();
@@ -137,19 +265,37 @@ pub fn run<E: Example>(
 
                     accumulated_time -= FIXED_TIME_STEP;
                 }
+                app_state.alpha = accumulated_time / FIXED_TIME_STEP;
+                if camera != (app_state.camera.position, app_state.camera.rotation)
+                    || !actions.is_empty()
+                {
+                    app.world.unwrap_mut::<Redraw>().request();
+                }
                 app.update(&mut app_state, actions, |ctx| example.update(ctx))
                     .unwrap();
                 app_state.input.mouse_state.refresh();
             }
-            Event::RedrawEventsCleared => window.request_redraw(),
+            Event::RedrawEventsCleared if rendering => window.request_redraw(),
             Event::RedrawRequested(_) => {
                 app_state.dt = fps_counter.record();
-                if let Err(err) = app.render(&window, &app_state, |ctx| example.render(ctx)) {
+                let rendered = app.render(&window, &app_state, |ctx| example.render(ctx));
+                if rendered.is_ok()
+                    && app
+                        .world
+                        .get_mut::<ApiTrace>()
+                        .is_ok_and(|mut trace| trace.frame_finished())
+                {
+                    log::info!("Finished the wgpu trace");
+                    *control_flow = ControlFlow::Exit;
+                }
+                if let Err(err) = rendered {
                     eprintln!("get_current_texture error: {:?}", err);
                     match err {
                         SurfaceError::Lost | SurfaceError::Outdated => {
                             warn!("render: Outdated Surface");
-                            app.surface.configure(app.device(), &app.surface_config);
+                            if let Some(surface) = &app.surface {
+                                surface.configure(app.device(), &app.surface_config);
+                            }
                             window.request_redraw();
                         }
                         SurfaceError::OutOfMemory => *control_flow = ControlFlow::Exit,
@@ -166,7 +312,10 @@ pub fn run<E: Example>(
                     },
                 ..
             } => {
-                if width != 0 && height != 0 {
+                minimized = width == 0 || height == 0;
+                app.set_background(!focused || minimized);
+                if !minimized {
+                    app.world.unwrap_mut::<Redraw>().request();
                     app_state.camera.aspect = width as f32 / height as f32;
                     example.resize(&app.gpu, width, height);
                     app.resize(width, height);
@@ -187,16 +336,32 @@ pub fn run<E: Example>(
             } => *control_flow = ControlFlow::Exit,
             Event::DeviceEvent { event, .. } => app_state.input.on_device_event(&event),
             Event::WindowEvent { event, .. } => {
-                if app.egui_state.on_event(&app.egui_context, &event).consumed {
+                if let WindowEvent::Focused(is_focused) = event {
+                    focused = is_focused;
+                    app.set_background(!focused || minimized);
+                }
+                app.world.unwrap_mut::<Redraw>().request();
+                if app.ui.as_mut().is_some_and(|ui| ui.on_event(&event)) {
                     return;
                 }
 
                 app_state.input.on_window_event(&window, &event);
             }
             Event::UserEvent(path) => {
+                app.world.unwrap_mut::<Redraw>().request();
                 app.handle_events(path);
             }
             Event::LoopDestroyed => {
+                let settings = ExampleSettings {
+                    // Minimized, the window is reopened where it was before.
+                    window: WindowState::from_window(&window)
+                        .or(saved.as_ref().and_then(|saved| saved.window)),
+                    camera: timeline::CameraKey::from_camera(&app_state.camera),
+                    frame: app.world.unwrap::<Redraw>().frame_settings(),
+                };
+                if let Err(err) = settings_store.save(E::name(), &settings) {
+                    warn!("Failed to save the settings: {err:#}");
+                }
                 println!("// End from the loop. Bye bye~⏎ ");
             }
             _ => {}
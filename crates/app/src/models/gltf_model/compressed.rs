@@ -0,0 +1,156 @@
+use std::io::Read;
+
+use color_eyre::{
+    eyre::{bail, eyre},
+    Result,
+};
+use ktx2::{Format, SupercompressionScheme};
+
+use super::TextureData;
+
+const KTX2_MAGIC: [u8; 12] = [
+    0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+];
+
+pub fn is_ktx2(bytes: &[u8]) -> bool {
+    bytes.starts_with(&KTX2_MAGIC)
+}
+
+/// Loads a 2D KTX2 texture with its mips, BCn and ASTC blocks stay compressed on the gpu.
+///
+/// UASTC Basis Universal textures are transcoded to BC7 or ASTC, whichever `features`
+/// allow, and to RGBA8 without either. That needs the `basisu` feature, ETC1S isn't
+/// supported. The sRGB-ness of the file is replaced by `srgb`, like for other images.
+pub fn load_ktx2(bytes: &[u8], srgb: bool, features: wgpu::Features) -> Result<TextureData> {
+    let reader = ktx2::Reader::new(bytes).map_err(|err| eyre!("Invalid KTX2: {err:?}"))?;
+    let header = reader.header();
+    if header.pixel_depth > 1 || header.layer_count > 1 || header.face_count > 1 {
+        bail!("Only 2D KTX2 textures are supported");
+    }
+    let levels = reader
+        .levels()
+        .map(|level| match header.supercompression_scheme {
+            None => Ok(level.to_vec()),
+            Some(SupercompressionScheme::Zstandard) => {
+                let mut source = level;
+                let mut decompressed = vec![];
+                ruzstd::StreamingDecoder::new(&mut source)
+                    .map_err(|err| eyre!("Invalid zstd stream: {err:?}"))?
+                    .read_to_end(&mut decompressed)?;
+                Ok(decompressed)
+            }
+            Some(SupercompressionScheme::BasisLZ) => {
+                bail!("ETC1S Basis Universal textures are not supported, use UASTC")
+            }
+            Some(scheme) => bail!("Unsupported KTX2 supercompression {scheme:?}"),
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let (width, height) = (header.pixel_width, header.pixel_height.max(1));
+    let (format, pixels) = match header.format {
+        Some(format) => (texture_format(format)?, levels.concat()),
+        None => transcode_uastc(&levels, width, height, features)?,
+    };
+    let format = if srgb {
+        format.add_srgb_suffix()
+    } else {
+        format.remove_srgb_suffix()
+    };
+    let missing = format.required_features() - features;
+    if !missing.is_empty() {
+        bail!("{format:?} textures need {missing:?}, which the adapter lacks");
+    }
+    Ok(TextureData {
+        width,
+        height,
+        format,
+        levels: levels.len() as u32,
+        pixels,
+    })
+}
+
+// The sRGB variants map to the linear ones, `load_ktx2` picks the color space.
+fn texture_format(format: Format) -> Result<wgpu::TextureFormat> {
+    use wgpu::TextureFormat as T;
+    Ok(match format {
+        Format::BC1_RGBA_UNORM_BLOCK | Format::BC1_RGBA_SRGB_BLOCK => T::Bc1RgbaUnorm,
+        Format::BC2_UNORM_BLOCK | Format::BC2_SRGB_BLOCK => T::Bc2RgbaUnorm,
+        Format::BC3_UNORM_BLOCK | Format::BC3_SRGB_BLOCK => T::Bc3RgbaUnorm,
+        Format::BC4_UNORM_BLOCK => T::Bc4RUnorm,
+        Format::BC4_SNORM_BLOCK => T::Bc4RSnorm,
+        Format::BC5_UNORM_BLOCK => T::Bc5RgUnorm,
+        Format::BC5_SNORM_BLOCK => T::Bc5RgSnorm,
+        Format::BC6H_UFLOAT_BLOCK => T::Bc6hRgbUfloat,
+        Format::BC6H_SFLOAT_BLOCK => T::Bc6hRgbFloat,
+        Format::BC7_UNORM_BLOCK | Format::BC7_SRGB_BLOCK => T::Bc7RgbaUnorm,
+        Format::ASTC_4x4_UNORM_BLOCK | Format::ASTC_4x4_SRGB_BLOCK => T::Astc {
+            block: wgpu::AstcBlock::B4x4,
+            channel: wgpu::AstcChannel::Unorm,
+        },
+        Format::R8G8B8A8_UNORM | Format::R8G8B8A8_SRGB => T::Rgba8Unorm,
+        Format::R16G16B16A16_SFLOAT => T::Rgba16Float,
+        format => bail!("Unsupported KTX2 format {format:?}"),
+    })
+}
+
+#[cfg(feature = "basisu")]
+fn transcode_uastc(
+    levels: &[Vec<u8>],
+    width: u32,
+    height: u32,
+    features: wgpu::Features,
+) -> Result<(wgpu::TextureFormat, Vec<u8>)> {
+    use basis_universal::{
+        DecodeFlags, LowLevelUastcTranscoder, SliceParametersUastc, TranscoderBlockFormat,
+    };
+
+    static INIT: std::sync::Once = std::sync::Once::new();
+    INIT.call_once(basis_universal::transcoder_init);
+
+    let (block_format, format) = if features.contains(wgpu::Features::TEXTURE_COMPRESSION_BC) {
+        (
+            TranscoderBlockFormat::BC7,
+            wgpu::TextureFormat::Bc7RgbaUnorm,
+        )
+    } else if features.contains(wgpu::Features::TEXTURE_COMPRESSION_ASTC) {
+        let format = wgpu::TextureFormat::Astc {
+            block: wgpu::AstcBlock::B4x4,
+            channel: wgpu::AstcChannel::Unorm,
+        };
+        (TranscoderBlockFormat::ASTC_4x4, format)
+    } else {
+        (
+            TranscoderBlockFormat::RGBA32,
+            wgpu::TextureFormat::Rgba8Unorm,
+        )
+    };
+
+    let transcoder = LowLevelUastcTranscoder::new();
+    let mut pixels = vec![];
+    for (level, data) in levels.iter().enumerate() {
+        let width = (width >> level).max(1);
+        let height = (height >> level).max(1);
+        let params = SliceParametersUastc {
+            num_blocks_x: width.div_ceil(4),
+            num_blocks_y: height.div_ceil(4),
+            has_alpha: false,
+            original_width: width,
+            original_height: height,
+        };
+        let transcoded = transcoder
+            .transcode_slice(data, params, DecodeFlags::HIGH_QUALITY, block_format)
+            .map_err(|err| eyre!("Failed to transcode UASTC level {level}: {err:?}"))?;
+        pixels.extend(transcoded);
+    }
+    Ok((format, pixels))
+}
+
+#[cfg(not(feature = "basisu"))]
+fn transcode_uastc(
+    _levels: &[Vec<u8>],
+    _width: u32,
+    _height: u32,
+    _features: wgpu::Features,
+) -> Result<(wgpu::TextureFormat, Vec<u8>)> {
+    bail!("Basis Universal textures need the `basisu` feature")
+}
@@ -1,4 +1,7 @@
-use std::mem::size_of;
+use std::{
+    hash::{Hash, Hasher},
+    mem::size_of,
+};
 
 use color_eyre::{eyre::eyre, eyre::ContextCompat, Result};
 use gltf::{
@@ -148,7 +151,121 @@ pub fn wrappping_to_address_mode(mode: gltf::texture::WrappingMode) -> wgpu::Add
     }
 }
 
-type RgbaImage = image::ImageBuffer<image::Rgba<u8>, Vec<u8>>;
+pub(crate) type RgbaImage = image::ImageBuffer<image::Rgba<u8>, Vec<u8>>;
+
+/// Pixels laid out for a texture of `format`.
+pub struct TextureData {
+    pub width: u32,
+    pub height: u32,
+    pub format: wgpu::TextureFormat,
+    /// Mip levels in `pixels`, one level gets the rest generated on upload.
+    pub levels: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl TextureData {
+    pub fn rgba8(image: RgbaImage, format: wgpu::TextureFormat) -> Self {
+        let (width, height) = image.dimensions();
+        Self {
+            width,
+            height,
+            format,
+            levels: 1,
+            pixels: image.into_raw(),
+        }
+    }
+
+    /// Hash of the size, format and pixels, equal for textures that look the same.
+    pub fn content_hash(&self) -> u64 {
+        let mut hasher = ahash::AHasher::default();
+        (self.width, self.height, self.format).hash(&mut hasher);
+        self.pixels.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Bytes per row of blocks and rows of blocks of mip `level`.
+    pub fn level_layout(&self, level: u32) -> (u32, u32) {
+        let (block_width, block_height) = self.format.block_dimensions();
+        let width = (self.width >> level).max(1);
+        let height = (self.height >> level).max(1);
+        let block_size = self.format.block_size(None).unwrap_or(4);
+        (
+            width.div_ceil(block_width) * block_size,
+            height.div_ceil(block_height),
+        )
+    }
+}
+
+/// Like [`convert_to_rgba`], but 16-bit and float images become `Rgba16Float` to keep
+/// their precision. There is no sRGB variant of it, so 16-bit color is linearized here,
+/// float images are linear already.
+pub fn convert_texture(image: &gltf::image::Data, srgb: bool) -> Result<TextureData> {
+    let (width, height) = (image.width, image.height);
+    let buf = image.pixels.as_slice();
+    let linear: Option<ImageBuffer<image::Rgba<f32>, Vec<f32>>> = match image.format {
+        Format::R8 | Format::R8G8 | Format::R8G8B8 | Format::R8G8B8A8 => {
+            let (image, format) = convert_to_rgba(image, srgb)?;
+            return Ok(TextureData::rgba8(image, format));
+        }
+        Format::R16 => {
+            ImageBuffer::<image::Luma<u16>, _>::from_raw(width, height, bytemuck::cast_slice(buf))
+                .map(|image| image.convert())
+        }
+        Format::R16G16 => {
+            ImageBuffer::<image::LumaA<u16>, _>::from_raw(width, height, bytemuck::cast_slice(buf))
+                .map(|image| image.convert())
+        }
+        Format::R16G16B16 => {
+            ImageBuffer::<image::Rgb<u16>, _>::from_raw(width, height, bytemuck::cast_slice(buf))
+                .map(|image| image.convert())
+        }
+        Format::R16G16B16A16 => {
+            ImageBuffer::<image::Rgba<u16>, _>::from_raw(width, height, bytemuck::cast_slice(buf))
+                .map(|image| image.convert())
+        }
+        Format::R32G32B32FLOAT => {
+            ImageBuffer::<image::Rgb<f32>, _>::from_raw(width, height, bytemuck::cast_slice(buf))
+                .map(|image| image.convert())
+        }
+        Format::R32G32B32A32FLOAT => {
+            ImageBuffer::<image::Rgba<f32>, _>::from_raw(width, height, bytemuck::cast_slice(buf))
+                .map(|image| image.convert())
+        }
+    };
+    let linearize = srgb
+        && !matches!(
+            image.format,
+            Format::R32G32B32FLOAT | Format::R32G32B32A32FLOAT
+        );
+    let linear = linear.context(eyre!(
+        "Failed to convert {:?} image with size ({width}, {height}) to RGBA16F",
+        image.format
+    ))?;
+    let pixels: Vec<half::f16> = linear
+        .pixels()
+        .flat_map(|pixel| {
+            let [r, g, b, a] = pixel.0;
+            let color = |c: f32| if linearize { srgb_to_linear(c) } else { c };
+            [color(r), color(g), color(b), a]
+        })
+        .map(half::f16::from_f32)
+        .collect();
+    Ok(TextureData {
+        width,
+        height,
+        format: wgpu::TextureFormat::Rgba16Float,
+        levels: 1,
+        pixels: bytemuck::cast_slice(&pixels).to_vec(),
+    })
+}
+
+fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
 
 pub fn convert_to_rgba(
     image: &gltf::image::Data,
@@ -0,0 +1,142 @@
+use std::path::Path;
+
+use ahash::AHashMap;
+use color_eyre::{eyre::eyre, Result};
+use gltf::image::{Data, Format, Source};
+use image::{DynamicImage, ImageFormat};
+use rayon::prelude::*;
+
+use super::compressed::is_ktx2;
+
+/// Images of a document by their index.
+pub struct Images {
+    /// KTX2 images hold a black pixel here, they are only decoded on upload.
+    pub decoded: Vec<Data>,
+    pub ktx2: AHashMap<usize, Vec<u8>>,
+}
+
+enum Image {
+    Decoded(Data),
+    Ktx2(Vec<u8>),
+}
+
+/// Decodes the images of `document` like `gltf::import_images`, which only knows PNG and
+/// JPEG, but also takes TGA, TIFF and EXR files. 16-bit and float images keep their
+/// precision.
+pub fn import_images(
+    document: &gltf::Document,
+    base: Option<&Path>,
+    buffers: &[gltf::buffer::Data],
+) -> Result<Images> {
+    let images: Vec<_> = document.images().collect();
+    let images = images
+        .par_iter()
+        .map(|image| {
+            import_image(image.source(), base, buffers)
+                .map_err(|err| eyre!("Failed to load image {}: {err:#}", image.index()))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut decoded = Vec::with_capacity(images.len());
+    let mut ktx2 = AHashMap::new();
+    for (index, image) in images.into_iter().enumerate() {
+        match image {
+            Image::Decoded(data) => decoded.push(data),
+            Image::Ktx2(bytes) => {
+                ktx2.insert(index, bytes);
+                decoded.push(Data {
+                    pixels: vec![0, 0, 0, 255],
+                    format: Format::R8G8B8A8,
+                    width: 1,
+                    height: 1,
+                });
+            }
+        }
+    }
+    Ok(Images { decoded, ktx2 })
+}
+
+fn import_image(
+    source: Source<'_>,
+    base: Option<&Path>,
+    buffers: &[gltf::buffer::Data],
+) -> Result<Image> {
+    let (bytes, format) = match source {
+        Source::View { view, mime_type } => {
+            let buffer = &buffers[view.buffer().index()];
+            let bytes = buffer[view.offset()..][..view.length()].to_vec();
+            (bytes, format_of_mime(mime_type))
+        }
+        // Embedded base64 images are PNG or JPEG in practice, gltf handles those.
+        Source::Uri { uri, .. } if uri.starts_with("data:") => {
+            return Ok(Image::Decoded(Data::from_source(source, base, buffers)?));
+        }
+        Source::Uri { uri, mime_type } => {
+            let path = base.unwrap_or(Path::new(".")).join(uri);
+            let format = mime_type
+                .and_then(format_of_mime)
+                .or_else(|| ImageFormat::from_path(&path).ok());
+            (std::fs::read(&path)?, format)
+        }
+    };
+    if is_ktx2(&bytes) {
+        return Ok(Image::Ktx2(bytes));
+    }
+    // TGA has no magic bytes, everything else is recognized even with a wrong extension.
+    let format = image::guess_format(&bytes)
+        .ok()
+        .or(format)
+        .ok_or_else(|| eyre!("Unknown image format"))?;
+    let image = image::load_from_memory_with_format(&bytes, format)?;
+    Ok(Image::Decoded(into_data(image)))
+}
+
+/// Decodes an image file outside of a glTF document, e.g. a texture of an OBJ material.
+pub fn load_image(path: &Path) -> Result<Data> {
+    let bytes = std::fs::read(path)?;
+    let format = image::guess_format(&bytes)
+        .or_else(|_| ImageFormat::from_path(path))
+        .map_err(|_| eyre!("Unknown image format of {}", path.display()))?;
+    let image = image::load_from_memory_with_format(&bytes, format)?;
+    Ok(into_data(image))
+}
+
+fn into_data(image: DynamicImage) -> Data {
+    let (width, height) = (image.width(), image.height());
+    let (format, pixels) = match image {
+        DynamicImage::ImageLuma8(image) => (Format::R8, image.into_raw()),
+        DynamicImage::ImageLumaA8(image) => (Format::R8G8, image.into_raw()),
+        DynamicImage::ImageRgb8(image) => (Format::R8G8B8, image.into_raw()),
+        DynamicImage::ImageRgba8(image) => (Format::R8G8B8A8, image.into_raw()),
+        DynamicImage::ImageLuma16(image) => (Format::R16, bytes(image.as_raw())),
+        DynamicImage::ImageLumaA16(image) => (Format::R16G16, bytes(image.as_raw())),
+        DynamicImage::ImageRgb16(image) => (Format::R16G16B16, bytes(image.as_raw())),
+        DynamicImage::ImageRgba16(image) => (Format::R16G16B16A16, bytes(image.as_raw())),
+        DynamicImage::ImageRgb32F(image) => (Format::R32G32B32FLOAT, bytes(image.as_raw())),
+        DynamicImage::ImageRgba32F(image) => (Format::R32G32B32A32FLOAT, bytes(image.as_raw())),
+        image => (Format::R8G8B8A8, image.into_rgba8().into_raw()),
+    };
+    Data {
+        pixels,
+        format,
+        width,
+        height,
+    }
+}
+
+// Extension images are named after the mime types of their formats, e.g. `image/tiff`.
+fn format_of_mime(mime_type: &str) -> Option<ImageFormat> {
+    match mime_type {
+        "image/jpeg" => Some(ImageFormat::Jpeg),
+        "image/x-exr" | "image/aces" => Some(ImageFormat::OpenExr),
+        _ => ImageFormat::from_extension(
+            mime_type
+                .strip_prefix("image/x-")
+                .or(mime_type.strip_prefix("image/"))?,
+        ),
+    }
+}
+
+fn bytes<T: bytemuck::Pod>(raw: &[T]) -> Vec<u8> {
+    bytemuck::cast_slice(raw).to_vec()
+}
@@ -1,158 +1,244 @@
-use std::{path::Path, vec};
+use std::{collections::BTreeSet, path::Path, vec};
 
-use ahash::AHashMap;
+use ahash::{AHashMap, AHashSet};
 use color_eyre::{
-    eyre::{eyre, Context},
+    eyre::{bail, eyre, Context},
     Result,
 };
 
+mod compressed;
 mod conversions;
+mod images;
+mod packing;
+mod skin;
 pub use conversions::*;
-use glam::{Mat4, Vec3, Vec4};
+use glam::{Mat4, Vec2, Vec3, Vec4};
+pub(crate) use images::load_image;
+pub(crate) use packing::pack_roughness_metallic;
+use packing::PackCache;
+use rayon::prelude::*;
+pub use skin::{GltfSkeleton, GltfSkin};
+use skin::{GltfSkinning, SkinnedPrimitive};
 
 use crate::{
+    animation::{AnimatedScene, AnimationClip, AnimationId, AnimationPlayer},
     app::App,
-    Instance, {Material, MaterialId}, {MeshId, MeshRef}, {TextureId, BLACK_TEXTURE, WHITE_TEXTURE},
+    models::ImportProgress,
+    scene::{NodeId, SceneGraph},
+    ColorSpace, Instance, InstancePool, SkinnedMeshPool, {Material, MaterialId},
+    {Mesh, MeshId, MeshPool, MeshRef}, {TextureId, BLACK_TEXTURE, WHITE_TEXTURE},
 };
-use components::{FormatConversions, UnwrapRepeat};
+use components::{Blitter, FormatConversions, UnwrapRepeat};
+
+/// Pool ids the parts of a document got on import, keyed by their indices in the document.
+#[derive(Debug, Clone, Default)]
+pub struct ImportResult {
+    /// By mesh and primitive index.
+    pub meshes: AHashMap<(usize, usize), MeshId>,
+    /// By material index.
+    pub materials: Vec<MaterialId>,
+    /// By image index and whether the image holds colors.
+    pub textures: AHashMap<(usize, bool), TextureId>,
+    /// Materials found in the pool already, shared instead of added again.
+    pub reused_materials: usize,
+    /// Textures with the same pixels as ones in the pool already.
+    pub reused_textures: usize,
+}
+
+impl ImportResult {
+    pub fn mesh(&self, mesh: usize, primitive: usize) -> Option<MeshId> {
+        self.meshes.get(&(mesh, primitive)).copied()
+    }
+
+    pub fn material(&self, material: usize) -> Option<MaterialId> {
+        self.materials.get(material).copied()
+    }
+}
 
 pub struct GltfDocument {
     pub document: gltf::Document,
 
-    meshes: AHashMap<(usize, usize), MeshId>,
-    materials: Vec<MaterialId>,
+    ids: ImportResult,
+    skinning: GltfSkinning,
 }
 
 impl GltfDocument {
     pub fn import(app: &mut App, path: impl AsRef<Path>) -> Result<Self> {
+        Self::import_with_progress(app, path, &ImportProgress::new())
+    }
+
+    /// Reports to and can be canceled through `progress` until the first gpu upload.
+    pub fn import_with_progress(
+        app: &mut App,
+        path: impl AsRef<Path>,
+        progress: &ImportProgress,
+    ) -> Result<Self> {
         let name = path.as_ref().file_name();
         log::info!("Started processing model: {name:?}",);
-        let (document, buffers, images) = gltf::import(&path)
+        progress.expect(1);
+        let gltf::Gltf { document, blob } = gltf::Gltf::open(&path)
             .with_context(|| eyre!("Failed to open file: {}", path.as_ref().display()))?;
-        let materials = Self::make_materials(app, &document, &images)?;
-        let meshes = Self::make_meshes(app, &document, &buffers)?;
-
-        app.get_texture_pool_mut().update_bind_group();
+        let base = path.as_ref().parent();
+        let buffers = gltf::import_buffers(&document, base, blob)?;
+        let images = images::import_images(&document, base, &buffers)?;
+        progress.step(path.as_ref().display().to_string())?;
+
+        let pack_cache = PackCache::new(path.as_ref());
+        let features = app.device().features();
+        let textures =
+            DecodedTextures::decode(&document, &images, &pack_cache, features, progress)?;
+        let primitives = PrimitiveData::read_all(&document, &buffers, progress)?;
+        progress.check()?;
+
+        let mut ids = ImportResult::default();
+        Self::make_materials(app, &document, textures, &mut ids)?;
+        let skinned = Self::make_meshes(app, primitives, &mut ids)?;
+        let skinning = GltfSkinning::read(&document, &buffers, skinned);
+
+        app.get_texture_pool_mut().update_bind_group()?;
+        let textures = app.get_texture_pool();
+        for mismatch in app.get_material_pool().validate_color_spaces(&textures) {
+            log::warn!("{mismatch}");
+        }
+        drop(textures);
+        log::info!(
+            "Shared {} materials and {} textures with earlier imports",
+            ids.reused_materials,
+            ids.reused_textures
+        );
 
         Ok(Self {
             document,
-            meshes,
-            materials,
+            ids,
+            skinning,
         })
     }
 
+    pub fn ids(&self) -> &ImportResult {
+        &self.ids
+    }
+
+    // Identical textures and materials already in the pools, e.g. from another document
+    // sharing a texture set, are reused instead of added again.
     fn make_materials(
         app: &App,
         document: &gltf::Document,
-        images: &[gltf::image::Data],
-    ) -> Result<Vec<MaterialId>> {
-        let mut image_map = AHashMap::new();
+        textures: DecodedTextures,
+        ids: &mut ImportResult,
+    ) -> Result<()> {
         let mut encoder = app.device().create_command_encoder(&Default::default());
-        let mut materials = vec![];
+        let mut reused_textures = 0;
+        let mut add_or_reuse = |name: &str, data: TextureData, color_space| {
+            let (id, reused) = add_texture(app, name, data, color_space, &mut encoder)?;
+            reused_textures += reused as usize;
+            Result::<_>::Ok(id)
+        };
+        for (key @ (index, srgb), data) in textures.images {
+            let name = document
+                .images()
+                .nth(index)
+                .and_then(|image| image.name())
+                .unwrap_or("");
+            let color_space = if srgb {
+                ColorSpace::Srgb
+            } else {
+                ColorSpace::Linear
+            };
+            ids.textures
+                .insert(key, add_or_reuse(name, data, color_space)?);
+        }
+        let compressed = textures.compressed;
+        let mut packed_map = AHashMap::new();
+        for (key, image) in textures.packed {
+            let data = TextureData::rgba8(image, wgpu::TextureFormat::Rgba8Unorm);
+            packed_map.insert(key, add_or_reuse("ORM", data, ColorSpace::Linear)?);
+        }
+        ids.reused_textures = reused_textures;
+
         for material in document.materials() {
             let name = material.name().unwrap_or("");
             let pbr = material.pbr_metallic_roughness();
             let mut color: Vec4 = pbr.base_color_factor().into();
             color.w = material.alpha_cutoff().unwrap_or(0.5);
 
-            let mut process = |img, srgb| {
-                process_texture_cached(app, &mut image_map, images, img, srgb, &mut encoder)
-            };
-
+            let texture = |t: gltf::Texture, srgb| ids.textures[&(texture_source(&t), srgb)];
             let albedo = pbr
                 .base_color_texture()
-                .map(|t| process(t.texture().source(), true))
-                .transpose()?
-                .unwrap_or(WHITE_TEXTURE);
-
+                .map_or(WHITE_TEXTURE, |t| texture(t.texture(), true));
             let normal = material
                 .normal_texture()
-                .map(|t| process(t.texture().source(), false))
-                .transpose()?
-                .unwrap_or(WHITE_TEXTURE);
-
+                .map_or(WHITE_TEXTURE, |t| texture(t.texture(), false));
             let emissive = material
                 .emissive_texture()
-                .map(|t| process(t.texture().source(), true))
-                .transpose()?
-                .unwrap_or(BLACK_TEXTURE);
+                .map_or(BLACK_TEXTURE, |t| texture(t.texture(), true));
 
-            let metallic_roughness = pbr
-                .metallic_roughness_texture()
-                .map(|t| process(t.texture().source(), false))
-                .transpose()?
-                .unwrap_or(BLACK_TEXTURE);
+            // Occlusion goes into the unused red channel of metallic-roughness.
+            let metallic_roughness = match orm_sources(&material, &compressed) {
+                (None, None) => BLACK_TEXTURE,
+                (Some(ao), Some(mr)) if ao == mr => ids.textures[&(mr, false)],
+                key => packed_map[&key],
+            };
 
+            let (clearcoat, clearcoat_roughness) = clearcoat(&material);
             let material = Material {
                 base_color: color,
                 albedo,
                 normal,
                 metallic_roughness,
                 emissive,
+                emissive_strength: material.emissive_strength().unwrap_or(1.),
+                ior: material.ior().unwrap_or(1.5),
+                transmission: material
+                    .transmission()
+                    .map_or(0., |transmission| transmission.transmission_factor()),
+                clearcoat,
+                clearcoat_roughness,
+                ..Default::default()
+            };
+            let mut materials = app.get_material_pool_mut();
+            let id = match materials.find(&material) {
+                Some(id) => {
+                    ids.reused_materials += 1;
+                    id
+                }
+                None => materials.add(material)?,
             };
-            let id = app.get_material_pool_mut().add(material);
             log::info!("Inserted material {name} with id: {:?}", id);
-            materials.push(id);
+            ids.materials.push(id);
         }
 
         app.queue().submit(Some(encoder.finish()));
 
-        Ok(materials)
+        Ok(())
     }
 
     fn make_meshes(
         app: &mut App,
-        document: &gltf::Document,
-        buffers: &[gltf::buffer::Data],
-    ) -> Result<AHashMap<(usize, usize), MeshId>> {
-        let mut meshes = AHashMap::new();
-        for mesh in document.meshes() {
-            let gltf_mesh_id = mesh.index();
-            for primitive in mesh.primitives() {
-                let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
-                let get_data = |semantic: &gltf::Semantic| -> Option<&[u8]> {
-                    primitive
-                        .get(semantic)
-                        .and_then(|sem| data_of_accessor(buffers, &sem))
-                };
-                let Some(vertices) = get_data(&gltf::Semantic::Positions) else {
-                    continue;
-                };
-                let Some(normals) = get_data(&gltf::Semantic::Normals) else {
-                    continue;
-                };
-                let vertices: &[Vec3] = bytemuck::cast_slice(vertices);
-                let tangents: Vec<[f32; 4]> = reader
-                    .read_tangents()
-                    .into_iter()
-                    .flatten()
-                    .chain(std::iter::repeat([0., 1., 0., 1.]))
-                    .take(vertices.len())
-                    .collect();
-                let tex_coords: Vec<[f32; 2]> = reader
-                    .read_tex_coords(0)
-                    .map(|uv| uv.into_f32())
-                    .unwrap_repeat()
-                    .take(vertices.len())
-                    .collect();
-                let indices: Vec<_> = match reader.read_indices() {
-                    Some(indices) => indices.into_u32().collect(),
-                    None => (0..vertices.len() as u32).collect(),
-                };
-                let mesh = MeshRef {
-                    vertices,
-                    normals: bytemuck::cast_slice(normals),
-                    tangents: bytemuck::cast_slice(&tangents),
-                    tex_coords: bytemuck::cast_slice(&tex_coords),
-                    indices,
-                };
-                let mesh = app.add_mesh(mesh);
-                meshes.insert((gltf_mesh_id, primitive.index()), mesh);
+        mut primitives: Vec<PrimitiveData>,
+        ids: &mut ImportResult,
+    ) -> Result<AHashMap<(usize, usize), SkinnedPrimitive>> {
+        let mut skinned = AHashMap::new();
+        let mut keys = vec![];
+        let mut refs = vec![];
+        for primitive in &mut primitives {
+            if let Some(skinned_primitive) = primitive.skinned.take() {
+                skinned.insert(primitive.key, skinned_primitive);
             }
+            keys.push(primitive.key);
+            refs.push(MeshRef {
+                vertices: primitive.vertices,
+                normals: primitive.normals,
+                tangents: &primitive.tangents,
+                tex_coords: &primitive.tex_coords,
+                indices: std::mem::take(&mut primitive.indices),
+            });
         }
+        let mesh_ids = app.add_meshes(refs)?;
+        ids.meshes = keys.into_iter().zip(mesh_ids).collect();
 
-        Ok(meshes)
+        Ok(skinned)
     }
+
     pub fn get_node(&self, name: &str) -> Option<gltf::Node> {
         self.document.nodes().find(|node| node.name() == Some(name))
     }
@@ -164,7 +250,7 @@ impl GltfDocument {
         instances: &mut Vec<Instance>,
     ) {
         for node in nodes {
-            gather_instances_recursive(instances, &node, &transform, &self.meshes, &self.materials);
+            gather_instances_recursive(instances, &node, &transform, &self.ids);
         }
     }
 
@@ -175,29 +261,186 @@ impl GltfDocument {
         }
         instances
     }
+
+    pub fn animations(&self) -> &[AnimationClip] {
+        &self.skinning.animations
+    }
+
+    /// Same as [`Self::get_scene_instances`], except that skinned meshes get their own
+    /// copies in the [`SkinnedMeshPool`], posed through the returned skeleton.
+    pub fn instantiate_skinned(
+        &self,
+        app: &App,
+        transform: Mat4,
+    ) -> Result<(Vec<Instance>, GltfSkeleton)> {
+        let (instances, skeleton) = self.instantiate(app, transform)?;
+        let instances = instances
+            .into_iter()
+            .map(|(_, instance)| instance)
+            .collect();
+        Ok((instances, skeleton))
+    }
+
+    /// Adds the scene to the [`InstancePool`] and hands it to the [`AnimationPlayer`],
+    /// start a clip with [`AnimationPlayer::play`].
+    pub fn instantiate_animated(&self, app: &App, transform: Mat4) -> Result<AnimationId> {
+        let (instances, skeleton) = self.instantiate(app, transform)?;
+        let (nodes, instances): (Vec<_>, Vec<_>) = instances.into_iter().unzip();
+        let ids = app.world.get_mut::<InstancePool>()?.add(&instances)?;
+
+        let mut scene = AnimatedScene::new(skeleton, transform);
+        scene.instances = nodes
+            .into_iter()
+            .zip(ids)
+            .filter_map(|(node, id)| Some((node?, id)))
+            .filter(|&(node, _)| self.skinning.animated[node])
+            .collect();
+        Ok(app.world.get_mut::<AnimationPlayer>()?.add(scene))
+    }
+
+    /// Mirrors the node hierarchy of the scenes in the [`SceneGraph`] under a new root
+    /// placed at `transform`, each node keeping its name and local transform and owning
+    /// the instances of its primitives. Skinned primitives are left out, see
+    /// [`Self::instantiate_animated`].
+    ///
+    /// `dynamic` lets every instance follow its node when the graph moves it, otherwise
+    /// only the ones of animated nodes can.
+    pub fn instantiate_graph(&self, app: &App, transform: Mat4, dynamic: bool) -> Result<NodeId> {
+        let mut graph = app.world.get_mut::<SceneGraph>()?;
+        let root = graph.spawn(None, None, transform)?;
+        let mut instances = vec![];
+        let mut stack: Vec<_> = self
+            .document
+            .scenes()
+            .flat_map(|scene| scene.nodes())
+            .map(|node| (node, root, transform))
+            .collect();
+        while let Some((node, parent, parent_transform)) = stack.pop() {
+            let local = Mat4::from_cols_array_2d(&node.transform().matrix());
+            let id = graph.spawn(Some(parent), node.name(), local)?;
+            let node_transform = parent_transform * local;
+            stack.extend(node.children().map(|child| (child, id, node_transform)));
+
+            let Some(mesh) = node.mesh() else {
+                continue;
+            };
+            if node.skin().is_some() {
+                continue;
+            }
+            for primitive in mesh.primitives() {
+                let Some(mesh) = self.ids.mesh(mesh.index(), primitive.index()) else {
+                    continue;
+                };
+                let material_id = primitive
+                    .material()
+                    .index()
+                    .and_then(|index| self.ids.material(index))
+                    .unwrap_or_default();
+                let mut instance = Instance::new(node_transform, mesh, material_id);
+                if dynamic || self.skinning.animated[node.index()] {
+                    instance = instance.dynamic();
+                }
+                instances.push((id, instance));
+            }
+        }
+        let (nodes, instances): (Vec<_>, Vec<_>) = instances.into_iter().unzip();
+        let ids = app.world.get_mut::<InstancePool>()?.add(&instances)?;
+        for (node, id) in nodes.into_iter().zip(ids) {
+            graph.bind(node, id)?;
+        }
+        Ok(root)
+    }
+
+    // Instances with the node they follow, `None` for skinned ones.
+    // Instances of animated nodes are dynamic.
+    fn instantiate(
+        &self,
+        app: &App,
+        transform: Mat4,
+    ) -> Result<(Vec<(Option<usize>, Instance)>, GltfSkeleton)> {
+        let mut skeleton = self.skinning.skeleton();
+        let mut skins = AHashMap::new();
+        let mut instances = vec![];
+        let mut skinned_pool = app.world.get_mut::<SkinnedMeshPool>()?;
+        let mut mesh_pool = app.world.get_mut::<MeshPool>()?;
+        let mut stack: Vec<_> = self
+            .document
+            .scenes()
+            .flat_map(|scene| scene.nodes())
+            .map(|node| (node, transform))
+            .collect();
+        while let Some((node, parent_transform)) = stack.pop() {
+            let node_transform =
+                parent_transform * Mat4::from_cols_array_2d(&node.transform().matrix());
+            stack.extend(node.children().map(|child| (child, node_transform)));
+
+            let Some(mesh) = node.mesh() else {
+                continue;
+            };
+            for primitive in mesh.primitives() {
+                let key = (mesh.index(), primitive.index());
+                let material_id = primitive
+                    .material()
+                    .index()
+                    .and_then(|index| self.ids.material(index))
+                    .unwrap_or_default();
+                let skinned = node
+                    .skin()
+                    .and_then(|skin| Some((skin, self.skinning.primitives.get(&key)?)));
+                let Some((skin, skinned)) = skinned else {
+                    if let Some(mesh) = self.ids.mesh(key.0, key.1) {
+                        let mut instance = Instance::new(node_transform, mesh, material_id);
+                        if self.skinning.animated[node.index()] {
+                            instance = instance.dynamic();
+                        }
+                        instances.push((Some(node.index()), instance));
+                    }
+                    continue;
+                };
+
+                let gltf_skin = &self.skinning.skins[skin.index()];
+                let skin_id = *skins.entry(skin.index()).or_insert_with(|| {
+                    let id = skinned_pool.add_skin(gltf_skin.joints.len() as u32);
+                    skeleton.add_skin(id, gltf_skin.clone());
+                    id
+                });
+                let mesh = skinned_pool.add_mesh(
+                    &mut mesh_pool,
+                    skin_id,
+                    skinned.mesh.as_ref(),
+                    &skinned.joints,
+                    &skinned.weights,
+                )?;
+                // Joint matrices already carry the node hierarchy, the transform of
+                // the skinned node itself is ignored as the spec requires.
+                instances.push((None, Instance::new(transform, mesh, material_id)));
+            }
+        }
+        skeleton.update(&mut skinned_pool);
+        Ok((instances, skeleton))
+    }
 }
 
 fn gather_instances_recursive(
     instances: &mut Vec<Instance>,
     node: &gltf::Node<'_>,
     transform: &glam::Mat4,
-    meshes: &AHashMap<(usize, usize), MeshId>,
-    materials: &[MaterialId],
+    ids: &ImportResult,
 ) {
     let node_transform = glam::Mat4::from_cols_array_2d(&node.transform().matrix());
     let transform = *transform * node_transform;
 
     for child in node.children() {
-        gather_instances_recursive(instances, &child, &transform, meshes, materials);
+        gather_instances_recursive(instances, &child, &transform, ids);
     }
 
     if let Some(mesh) = node.mesh() {
         for primitive in mesh.primitives() {
-            if let Some(&mesh) = meshes.get(&(mesh.index(), primitive.index())) {
+            if let Some(mesh) = ids.mesh(mesh.index(), primitive.index()) {
                 let material_id = primitive
                     .material()
                     .index()
-                    .and_then(|index| materials.get(index).copied())
+                    .and_then(|index| ids.material(index))
                     .unwrap_or_default();
 
                 instances.push(Instance::new(transform, mesh, material_id));
@@ -219,49 +462,270 @@ pub fn data_of_accessor<'a>(
     Some(accessor_data)
 }
 
-type TexKey = (usize, bool);
+/// Decoded and packed images of all materials, ready for upload.
+struct DecodedTextures {
+    images: Vec<(TexKey, TextureData)>,
+    packed: Vec<((Option<usize>, Option<usize>), RgbaImage)>,
+    /// KTX2 images, see [`orm_sources`].
+    compressed: AHashSet<usize>,
+}
 
-fn process_texture_cached(
-    app: &App,
-    image_map: &mut AHashMap<TexKey, TextureId>,
-    images: &[gltf::image::Data],
-    image: gltf::image::Image<'_>,
-    srgb: bool,
-    encoder: &mut wgpu::CommandEncoder,
-) -> Result<TextureId> {
-    let key: TexKey = (image.index(), srgb);
+impl DecodedTextures {
+    // Decoding and packing dominate the import, so they run in parallel and only the
+    // uploads go through the shared encoder.
+    fn decode(
+        document: &gltf::Document,
+        images: &images::Images,
+        pack_cache: &PackCache,
+        features: wgpu::Features,
+        progress: &ImportProgress,
+    ) -> Result<Self> {
+        let compressed: AHashSet<_> = images.ktx2.keys().copied().collect();
+        let mut texture_keys = BTreeSet::new();
+        let mut packed_keys = BTreeSet::new();
+        for material in document.materials() {
+            let pbr = material.pbr_metallic_roughness();
+            let source = |t: gltf::Texture, srgb| (texture_source(&t), srgb);
+            texture_keys.extend(pbr.base_color_texture().map(|t| source(t.texture(), true)));
+            texture_keys.extend(
+                material
+                    .normal_texture()
+                    .map(|t| source(t.texture(), false)),
+            );
+            texture_keys.extend(
+                material
+                    .emissive_texture()
+                    .map(|t| source(t.texture(), true)),
+            );
+            match orm_sources(&material, &compressed) {
+                (None, None) => {}
+                (Some(ao), Some(mr)) if ao == mr => {
+                    texture_keys.insert((mr, false));
+                }
+                key => {
+                    packed_keys.insert(key);
+                }
+            }
+        }
+        progress.expect((texture_keys.len() + packed_keys.len()) as u32);
+
+        let names: Vec<_> = document
+            .images()
+            .map(|image| image.name().unwrap_or("image").to_owned())
+            .collect();
+        let decoded = texture_keys
+            .into_par_iter()
+            .map(|key @ (index, srgb)| {
+                let image = images
+                    .decoded
+                    .get(index)
+                    .ok_or_else(|| eyre!("Invalid image index: {index}"))?;
+                let decoded = match images.ktx2.get(&index) {
+                    Some(bytes) => compressed::load_ktx2(bytes, srgb, features)
+                        .with_context(|| eyre!("Failed to load KTX2 image {}", names[index]))?,
+                    None => convert_texture(image, srgb)?,
+                };
+                progress.step(&names[index])?;
+                Ok((key, decoded))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let packed = packed_keys
+            .into_par_iter()
+            .map(|key| {
+                let packed = pack_cache.get_or_pack(&images.decoded, key.0, key.1)?;
+                progress.step("ORM")?;
+                Ok((key, packed))
+            })
+            .collect::<Result<Vec<_>>>()?;
 
-    let entry = match image_map.entry(key) {
-        std::collections::hash_map::Entry::Occupied(handle) => return Ok(*handle.get()),
-        std::collections::hash_map::Entry::Vacant(v) => v,
+        Ok(Self {
+            images: decoded,
+            packed,
+            compressed,
+        })
+    }
+}
+
+/// Cpu side data of one primitive, read in parallel before the batched upload.
+struct PrimitiveData<'a> {
+    key: (usize, usize),
+    vertices: &'a [Vec3],
+    normals: &'a [Vec3],
+    tangents: Vec<Vec4>,
+    tex_coords: Vec<Vec2>,
+    indices: Vec<u32>,
+    skinned: Option<SkinnedPrimitive>,
+}
+
+impl<'a> PrimitiveData<'a> {
+    fn read_all(
+        document: &'a gltf::Document,
+        buffers: &'a [gltf::buffer::Data],
+        progress: &ImportProgress,
+    ) -> Result<Vec<Self>> {
+        let primitives: Vec<_> = document
+            .meshes()
+            .flat_map(|mesh| {
+                mesh.primitives()
+                    .map(move |primitive| (mesh.clone(), primitive))
+            })
+            .collect();
+        progress.expect(primitives.len() as u32);
+        let primitives = primitives
+            .par_iter()
+            .map(|(mesh, primitive)| {
+                let data = Self::read(mesh.index(), primitive, buffers);
+                let name = mesh.name().unwrap_or("mesh");
+                progress.step(format!("{name} #{}", primitive.index()))?;
+                Ok(data)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(primitives.into_iter().flatten().collect())
+    }
+
+    /// Returns `None` for primitives without positions or normals.
+    fn read(
+        mesh: usize,
+        primitive: &gltf::Primitive<'a>,
+        buffers: &'a [gltf::buffer::Data],
+    ) -> Option<Self> {
+        let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+        let get_data = |semantic: &gltf::Semantic| -> Option<&'a [u8]> {
+            primitive
+                .get(semantic)
+                .and_then(|sem| data_of_accessor(buffers, &sem))
+        };
+        let vertices: &[Vec3] = bytemuck::cast_slice(get_data(&gltf::Semantic::Positions)?);
+        let normals: &[Vec3] = bytemuck::cast_slice(get_data(&gltf::Semantic::Normals)?);
+        let tangents: Vec<Vec4> = reader
+            .read_tangents()
+            .into_iter()
+            .flatten()
+            .chain(std::iter::repeat([0., 1., 0., 1.]))
+            .take(vertices.len())
+            .map(Vec4::from)
+            .collect();
+        let tex_coords: Vec<Vec2> = reader
+            .read_tex_coords(0)
+            .map(|uv| uv.into_f32())
+            .unwrap_repeat()
+            .take(vertices.len())
+            .map(Vec2::from)
+            .collect();
+        let indices: Vec<_> = match reader.read_indices() {
+            Some(indices) => indices.into_u32().collect(),
+            None => (0..vertices.len() as u32).collect(),
+        };
+        let skinned = primitive.get(&gltf::Semantic::Joints(0)).and_then(|_| {
+            let owned = Mesh {
+                vertices: vertices.to_vec(),
+                normals: normals.to_vec(),
+                tangents: tangents.clone(),
+                tex_coords: tex_coords.clone(),
+                indices: indices.clone(),
+            };
+            SkinnedPrimitive::read(primitive, buffers, owned)
+        });
+
+        Some(Self {
+            key: (mesh, primitive.index()),
+            vertices,
+            normals,
+            tangents,
+            tex_coords,
+            indices,
+            skinned,
+        })
+    }
+}
+
+type TexKey = (usize, bool);
+
+// The gltf crate has no typed `KHR_materials_clearcoat` yet, its textures are ignored.
+fn clearcoat(material: &gltf::Material) -> (f32, f32) {
+    let Some(extension) = material.extension_value("KHR_materials_clearcoat") else {
+        return (0., 0.);
     };
+    let factor = |name: &str| extension.get(name).and_then(|value| value.as_f64());
+    (
+        factor("clearcoatFactor").unwrap_or(0.) as f32,
+        factor("clearcoatRoughnessFactor").unwrap_or(0.) as f32,
+    )
+}
 
-    let handle = process_texture(app, images, image, srgb, encoder)?;
+/// Image of a texture, the KTX2 one of `KHR_texture_basisu` when there is one.
+fn texture_source(texture: &gltf::Texture) -> usize {
+    texture
+        .extension_value("KHR_texture_basisu")
+        .and_then(|basisu| basisu.get("source")?.as_u64())
+        .map_or(texture.source().index(), |source| source as usize)
+}
 
-    entry.insert(handle);
+/// Images of the occlusion and metallic-roughness textures, packed into one ORM texture.
+///
+/// Compressed images can't be packed on the cpu. A compressed metallic-roughness texture
+/// is used as it is, with occlusion in its red channel like ORM exports have it, and a
+/// compressed occlusion texture on its own is dropped.
+fn orm_sources(
+    material: &gltf::Material,
+    compressed: &AHashSet<usize>,
+) -> (Option<usize>, Option<usize>) {
+    let occlusion = material
+        .occlusion_texture()
+        .map(|t| texture_source(&t.texture()));
+    let metallic_roughness = material
+        .pbr_metallic_roughness()
+        .metallic_roughness_texture()
+        .map(|t| texture_source(&t.texture()));
+    match (occlusion, metallic_roughness) {
+        (_, Some(mr)) if compressed.contains(&mr) => (Some(mr), Some(mr)),
+        (Some(ao), mr) if compressed.contains(&ao) => (None, mr),
+        sources => sources,
+    }
+}
 
-    Ok(handle)
+/// Uploads `data` unless the pool holds a texture with the same pixels, in which case
+/// that one is shared. Returns whether it was.
+pub(crate) fn add_texture(
+    app: &App,
+    name: &str,
+    data: TextureData,
+    color_space: ColorSpace,
+    encoder: &mut wgpu::CommandEncoder,
+) -> Result<(TextureId, bool)> {
+    let hash = data.content_hash();
+    let reused = app.get_texture_pool_mut().reuse(hash, color_space);
+    match reused {
+        Some(id) => Ok((id, true)),
+        None => upload_texture(app, name, data, color_space, hash, encoder).map(|id| (id, false)),
+    }
 }
 
-fn process_texture(
+fn upload_texture(
     app: &App,
-    images: &[gltf::image::Data],
-    image: gltf::image::Image<'_>,
-    srgb: bool,
+    name: &str,
+    data: TextureData,
+    color_space: ColorSpace,
+    hash: u64,
     encoder: &mut wgpu::CommandEncoder,
 ) -> Result<TextureId> {
-    let name = image.name().unwrap_or("");
-    let image = images
-        .get(image.index())
-        .ok_or_else(|| eyre!("Invalid image index: {}", image.index()))?;
-    let (width, height) = (image.width, image.height);
-    let (image, format) = convert_to_rgba(image, srgb)?;
+    let format = data.format;
     let size = wgpu::Extent3d {
-        width,
-        height,
+        width: data.width,
+        height: data.height,
         depth_or_array_layers: 1,
     };
-    let mip_level_count = size.max_mips(wgpu::TextureDimension::D2);
+    // Compressed formats can't be rendered to, they come with their mips or go without.
+    let generate_mips =
+        data.levels == 1 && Blitter::can_generate_mipmaps(format, app.device().features());
+    let (mip_level_count, usage) = if generate_mips {
+        (
+            size.max_mips(wgpu::TextureDimension::D2),
+            wgpu::TextureUsages::RENDER_ATTACHMENT,
+        )
+    } else {
+        (data.levels, wgpu::TextureUsages::empty())
+    };
 
     let desc = wgpu::TextureDescriptor {
         label: None,
@@ -270,33 +734,46 @@ fn process_texture(
         sample_count: 1,
         dimension: wgpu::TextureDimension::D2,
         format,
-        usage: wgpu::TextureUsages::TEXTURE_BINDING
-            | wgpu::TextureUsages::COPY_DST
-            | wgpu::TextureUsages::RENDER_ATTACHMENT,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST | usage,
 
         view_formats: &[format, format.swap_srgb_suffix()],
     };
     let texture = app.device().create_texture(&desc);
-    app.queue().write_texture(
-        wgpu::ImageCopyTextureBase {
-            texture: &texture,
-            mip_level: 0,
-            origin: wgpu::Origin3d::ZERO,
-            aspect: wgpu::TextureAspect::All,
-        },
-        image.as_raw(),
-        wgpu::ImageDataLayout {
-            offset: 0,
-            bytes_per_row: Some(width * 4),
-            rows_per_image: None,
-        },
-        size,
-    );
+    let mut offset = 0;
+    for level in 0..data.levels {
+        let (bytes_per_row, rows) = data.level_layout(level);
+        let len = (bytes_per_row * rows) as usize;
+        let Some(pixels) = data.pixels.get(offset..offset + len) else {
+            bail!("Texture {name} is missing data of mip {level}");
+        };
+        offset += len;
+        app.queue().write_texture(
+            wgpu::ImageCopyTextureBase {
+                texture: &texture,
+                mip_level: level,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            pixels,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(bytes_per_row),
+                rows_per_image: None,
+            },
+            size.mip_level_size(level, wgpu::TextureDimension::D2)
+                .physical_size(format),
+        );
+    }
     let texture_view = texture.create_view(&Default::default());
 
-    app.blitter.generate_mipmaps(encoder, &app.world, &texture);
+    if generate_mips {
+        app.blitter
+            .generate_mipmaps(encoder, app.device(), &texture);
+    }
 
-    let texture_id = app.get_texture_pool_mut().add(texture_view);
+    let texture_id = app
+        .get_texture_pool_mut()
+        .add_with_hash(texture_view, color_space, hash)?;
     log::info!("Inserted texture {name} with id: {}", texture_id.id());
     Ok(texture_id)
 }
@@ -0,0 +1,162 @@
+use std::{
+    hash::{Hash, Hasher},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use color_eyre::{eyre::eyre, Result};
+use image::imageops::{self, FilterType};
+
+use super::{convert_to_rgba, RgbaImage};
+
+/// Packs occlusion into R next to glTF's roughness in G and metallic in B.
+///
+/// Missing inputs fall back like the `TexturePool` defaults do: occlusion to white,
+/// metallic and roughness to black. Occlusion is resized to the metallic-roughness
+/// resolution when they differ.
+pub fn pack_orm(
+    occlusion: Option<&gltf::image::Data>,
+    metallic_roughness: Option<&gltf::image::Data>,
+) -> Result<RgbaImage> {
+    let occlusion = occlusion.map(|i| convert_to_rgba(i, false)).transpose()?;
+    let metallic_roughness = metallic_roughness
+        .map(|i| convert_to_rgba(i, false))
+        .transpose()?;
+    let (width, height) = match (&metallic_roughness, &occlusion) {
+        (Some((image, _)), _) | (None, Some((image, _))) => image.dimensions(),
+        (None, None) => return Err(eyre!("Nothing to pack")),
+    };
+    let occlusion = occlusion.map(|(image, _)| {
+        if image.dimensions() == (width, height) {
+            image
+        } else {
+            imageops::resize(&image, width, height, FilterType::Triangle)
+        }
+    });
+
+    Ok(RgbaImage::from_fn(width, height, |x, y| {
+        let ao = occlusion
+            .as_ref()
+            .map_or(255, |image| image.get_pixel(x, y)[0]);
+        let [_, roughness, metallic, _] = metallic_roughness
+            .as_ref()
+            .map_or([0; 4], |(image, _)| image.get_pixel(x, y).0);
+        image::Rgba([ao, roughness, metallic, 255])
+    }))
+}
+
+/// Packs separate grayscale roughness and metallic maps, as OBJ materials have them,
+/// into G and B of an ORM texture with white occlusion.
+pub fn pack_roughness_metallic(
+    roughness: Option<&gltf::image::Data>,
+    metallic: Option<&gltf::image::Data>,
+) -> Result<RgbaImage> {
+    let roughness = roughness.map(|i| convert_to_rgba(i, false)).transpose()?;
+    let metallic = metallic.map(|i| convert_to_rgba(i, false)).transpose()?;
+    let (width, height) = match (&roughness, &metallic) {
+        (Some((image, _)), _) | (None, Some((image, _))) => image.dimensions(),
+        (None, None) => return Err(eyre!("Nothing to pack")),
+    };
+    let metallic = metallic.map(|(image, _)| {
+        if image.dimensions() == (width, height) {
+            image
+        } else {
+            imageops::resize(&image, width, height, FilterType::Triangle)
+        }
+    });
+    Ok(RgbaImage::from_fn(width, height, |x, y| {
+        let roughness = roughness
+            .as_ref()
+            .map_or(0, |(image, _)| image.get_pixel(x, y)[0]);
+        let metallic = metallic
+            .as_ref()
+            .map_or(0, |image| image.get_pixel(x, y)[0]);
+        image::Rgba([255, roughness, metallic, 255])
+    }))
+}
+
+/// Packed textures of one glTF file, invalidated when the file changes.
+pub struct PackCache {
+    dir: PathBuf,
+    stamp: u64,
+}
+
+impl PackCache {
+    pub fn new(model_path: &Path) -> Self {
+        let modified = std::fs::metadata(model_path)
+            .and_then(|meta| meta.modified())
+            .ok()
+            .and_then(|time| time.duration_since(SystemTime::UNIX_EPOCH).ok())
+            .unwrap_or_default();
+        let mut hasher = Fnv1a::default();
+        model_path.hash(&mut hasher);
+        modified.hash(&mut hasher);
+        let dir = model_path
+            .parent()
+            .unwrap_or(Path::new("."))
+            .join(".packed_textures");
+        Self {
+            dir,
+            stamp: hasher.finish(),
+        }
+    }
+
+    fn path(&self, occlusion: Option<usize>, metallic_roughness: Option<usize>) -> PathBuf {
+        let mut hasher = Fnv1a(self.stamp);
+        (occlusion, metallic_roughness).hash(&mut hasher);
+        self.dir.join(format!("{:016x}.png", hasher.finish()))
+    }
+
+    pub fn get_or_pack(
+        &self,
+        images: &[gltf::image::Data],
+        occlusion: Option<usize>,
+        metallic_roughness: Option<usize>,
+    ) -> Result<RgbaImage> {
+        let path = self.path(occlusion, metallic_roughness);
+        if let Ok(image) = image::open(&path) {
+            return Ok(image.into_rgba8());
+        }
+
+        let data = |index: Option<usize>| {
+            index
+                .map(|i| {
+                    images
+                        .get(i)
+                        .ok_or_else(|| eyre!("Invalid image index: {i}"))
+                })
+                .transpose()
+        };
+        let packed = pack_orm(data(occlusion)?, data(metallic_roughness)?)?;
+        // The cache is an optimization, a read-only asset folder only costs the repacking.
+        if let Err(err) = std::fs::create_dir_all(&self.dir)
+            .map_err(image::ImageError::IoError)
+            .and_then(|_| packed.save_with_format(&path, image::ImageFormat::Png))
+        {
+            log::warn!("Failed to cache packed texture {}: {err}", path.display());
+        }
+        Ok(packed)
+    }
+}
+
+// Stable across runs unlike the std and ahash hashers, cache names must not change.
+struct Fnv1a(u64);
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+}
+
+impl Hasher for Fnv1a {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+}
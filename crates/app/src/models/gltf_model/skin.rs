@@ -0,0 +1,209 @@
+use std::sync::Arc;
+
+use ahash::AHashMap;
+use glam::{Mat4, Quat, UVec4, Vec3, Vec4};
+
+use crate::{
+    animation::{AnimationClip, NodeTransform},
+    Mesh, SkinId, SkinnedMeshPool,
+};
+
+/// Primitive with joint influences, kept on the cpu until the scene is instantiated
+/// since every instance gets its own posed copy.
+pub(super) struct SkinnedPrimitive {
+    pub mesh: Mesh,
+    pub joints: Vec<UVec4>,
+    pub weights: Vec<Vec4>,
+}
+
+impl SkinnedPrimitive {
+    /// Returns `None` for primitives without joint influences.
+    pub fn read(
+        primitive: &gltf::Primitive<'_>,
+        buffers: &[gltf::buffer::Data],
+        mesh: Mesh,
+    ) -> Option<Self> {
+        let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+        let joints = reader
+            .read_joints(0)?
+            .into_u16()
+            .map(|j| UVec4::from_array(j.map(u32::from)))
+            .collect();
+        let weights = reader
+            .read_weights(0)?
+            .into_f32()
+            .map(Vec4::from_array)
+            .collect();
+        Some(Self {
+            mesh,
+            joints,
+            weights,
+        })
+    }
+}
+
+pub struct GltfSkin {
+    /// Node index of every joint.
+    pub joints: Vec<usize>,
+    pub inverse_bind_matrices: Vec<Mat4>,
+}
+
+/// Skins, animations and the node hierarchy of a [`super::GltfDocument`].
+pub(super) struct GltfSkinning {
+    pub primitives: AHashMap<(usize, usize), SkinnedPrimitive>,
+    pub skins: Vec<Arc<GltfSkin>>,
+    pub animations: Arc<[AnimationClip]>,
+    /// Nodes moved by an animation, either directly or through an ancestor.
+    pub animated: Vec<bool>,
+    parents: Vec<Option<usize>>,
+    rest: Vec<NodeTransform>,
+}
+
+impl GltfSkinning {
+    pub fn read(
+        document: &gltf::Document,
+        buffers: &[gltf::buffer::Data],
+        primitives: AHashMap<(usize, usize), SkinnedPrimitive>,
+    ) -> Self {
+        let skins = document
+            .skins()
+            .map(|skin| {
+                let joints: Vec<_> = skin.joints().map(|node| node.index()).collect();
+                let reader = skin.reader(|buffer| Some(&buffers[buffer.index()]));
+                let inverse_bind_matrices = reader
+                    .read_inverse_bind_matrices()
+                    .map(|m| m.map(|m| Mat4::from_cols_array_2d(&m)).collect())
+                    .unwrap_or_else(|| vec![Mat4::IDENTITY; joints.len()]);
+                Arc::new(GltfSkin {
+                    joints,
+                    inverse_bind_matrices,
+                })
+            })
+            .collect();
+        let animations: Arc<[_]> = document
+            .animations()
+            .map(|animation| AnimationClip::from_gltf(&animation, buffers))
+            .collect();
+
+        let mut parents = vec![None; document.nodes().len()];
+        for node in document.nodes() {
+            for child in node.children() {
+                parents[child.index()] = Some(node.index());
+            }
+        }
+        let rest = document
+            .nodes()
+            .map(|node| {
+                let (translation, rotation, scale) = node.transform().decomposed();
+                NodeTransform {
+                    translation: Vec3::from_array(translation),
+                    rotation: Quat::from_array(rotation),
+                    scale: Vec3::from_array(scale),
+                }
+            })
+            .collect();
+
+        let mut targeted = vec![false; parents.len()];
+        for node in animations.iter().flat_map(|clip| clip.targets()) {
+            targeted[node] = true;
+        }
+        let animated = (0..parents.len())
+            .map(|mut node| loop {
+                if targeted[node] {
+                    break true;
+                }
+                match parents[node] {
+                    Some(parent) => node = parent,
+                    None => break false,
+                }
+            })
+            .collect();
+
+        Self {
+            primitives,
+            skins,
+            animations,
+            animated,
+            parents,
+            rest,
+        }
+    }
+
+    pub fn skeleton(&self) -> GltfSkeleton {
+        GltfSkeleton {
+            nodes: self.rest.clone(),
+            rest: self.rest.clone(),
+            parents: self.parents.clone(),
+            skins: vec![],
+            animations: self.animations.clone(),
+        }
+    }
+}
+
+/// Pose of one instantiated glTF scene, drives the joint matrices of its skins.
+pub struct GltfSkeleton {
+    nodes: Vec<NodeTransform>,
+    rest: Vec<NodeTransform>,
+    parents: Vec<Option<usize>>,
+    skins: Vec<(SkinId, Arc<GltfSkin>)>,
+    animations: Arc<[AnimationClip]>,
+}
+
+impl GltfSkeleton {
+    pub(super) fn add_skin(&mut self, id: SkinId, skin: Arc<GltfSkin>) {
+        self.skins.push((id, skin));
+    }
+
+    pub fn animations(&self) -> &[AnimationClip] {
+        &self.animations
+    }
+
+    pub fn find_animation(&self, name: &str) -> Option<usize> {
+        self.animations
+            .iter()
+            .position(|animation| animation.name.as_deref() == Some(name))
+    }
+
+    /// Poses the nodes at `time` seconds into `animation`, looping past its end.
+    pub fn play(&mut self, animation: usize, time: f32) {
+        let Some(duration) = self.animations.get(animation).map(|clip| clip.duration) else {
+            return self.reset();
+        };
+        self.sample(animation, time.rem_euclid(duration.max(f32::EPSILON)));
+    }
+
+    /// Poses the nodes at `time` seconds into `animation`, holding the last keyframe.
+    pub fn sample(&mut self, animation: usize, time: f32) {
+        self.nodes.copy_from_slice(&self.rest);
+        if let Some(animation) = self.animations.get(animation) {
+            animation.sample(time, &mut self.nodes);
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.nodes.copy_from_slice(&self.rest);
+    }
+
+    /// Transform of `node` relative to the scene root.
+    pub fn global_transform(&self, mut node: usize) -> Mat4 {
+        let mut transform = self.nodes[node].matrix();
+        while let Some(parent) = self.parents[node] {
+            transform = self.nodes[parent].matrix() * transform;
+            node = parent;
+        }
+        transform
+    }
+
+    /// Uploads the current pose to the joint matrices of every skin.
+    pub fn update(&self, pool: &mut SkinnedMeshPool) {
+        for (id, skin) in &self.skins {
+            let matrices: Vec<_> = skin
+                .joints
+                .iter()
+                .zip(&skin.inverse_bind_matrices)
+                .map(|(&joint, inverse_bind)| self.global_transform(joint) * *inverse_bind)
+                .collect();
+            pool.set_joint_matrices(*id, &matrices);
+        }
+    }
+}
@@ -0,0 +1,190 @@
+use std::path::Path;
+
+use color_eyre::{
+    eyre::{bail, eyre, ContextCompat, WrapErr},
+    Result,
+};
+use half::f16;
+use wgpu::util::DeviceExt;
+
+use crate::{app::App, ColorSpace, TextureId};
+
+/// Photometric profile of a luminaire from an IES LM-63 file.
+///
+/// Only type C photometry is read, which is what architectural luminaires ship with. The
+/// candela table is baked into a [`Self::TEXTURE_SIZE`] texture normalized to its peak,
+/// with the horizontal angle along u and the vertical one from the nadir along v, see
+/// `shaders/utils/ies.wgsl`. Lights reference it through `Light::with_ies`, their color
+/// still sets the brightness.
+#[derive(Debug, Clone)]
+pub struct IesProfile {
+    /// Degrees from the nadir, ascending.
+    pub vertical_angles: Vec<f32>,
+    /// Degrees around the axis, ascending. The last one tells the symmetry of the table.
+    pub horizontal_angles: Vec<f32>,
+    /// Candelas for every horizontal angle, each a row over the vertical angles.
+    pub candela: Vec<f32>,
+}
+
+impl IesProfile {
+    /// Matches `IES_SIZE` of `shaders/utils/ies.wgsl`.
+    pub const TEXTURE_SIZE: [u32; 2] = [128, 64];
+
+    /// Adds the profile to the `TexturePool`, for `Light::with_ies` and `SpotLight::with_ies`.
+    pub fn import(app: &mut App, path: impl AsRef<Path>) -> Result<TextureId> {
+        let profile = Self::load(path)?;
+        let [width, height] = Self::TEXTURE_SIZE;
+        let pixels: Vec<_> = profile.bake().into_iter().map(f16::from_f32).collect();
+        let texture = app.device().create_texture_with_data(
+            app.queue(),
+            &wgpu::TextureDescriptor {
+                label: Some("IES Profile"),
+                size: wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::R16Float,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            },
+            bytemuck::cast_slice(&pixels),
+        );
+        let mut textures = app.get_texture_pool_mut();
+        let id = textures.add(texture.create_view(&Default::default()), ColorSpace::Linear)?;
+        textures.update_bind_group()?;
+        Ok(id)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .wrap_err_with(|| format!("Failed to open file: {}", path.display()))?;
+        Self::parse(&text).wrap_err_with(|| format!("Failed to parse IES: {}", path.display()))
+    }
+
+    pub fn parse(text: &str) -> Result<Self> {
+        // Keywords and free text come first, the numbers start after the `TILT=` line.
+        let mut lines = text.lines();
+        let tilt = lines
+            .find_map(|line| line.trim().strip_prefix("TILT="))
+            .context("No TILT line")?;
+        let mut numbers = lines
+            .flat_map(|line| line.split(|c: char| c.is_whitespace() || c == ','))
+            .filter(|token| !token.is_empty())
+            .map(|token| {
+                token
+                    .parse::<f32>()
+                    .map_err(|_| eyre!("Not a number: {token}"))
+            });
+        let mut next = || numbers.next().context("The file ends early")?;
+
+        match tilt.trim() {
+            "NONE" => {}
+            // Lamp tilt changes the output with the mounting angle, the table is used as is.
+            "INCLUDE" => {
+                let _geometry = next()?;
+                let pairs = next()? as usize;
+                for _ in 0..2 * pairs {
+                    next()?;
+                }
+            }
+            file => bail!("Tilt data in a separate file is not supported: {file}"),
+        }
+
+        let _lamps = next()?;
+        let _lumens_per_lamp = next()?;
+        let multiplier = next()?;
+        let vertical_count = next()? as usize;
+        let horizontal_count = next()? as usize;
+        let photometric_type = next()?;
+        let _units = next()?;
+        let _dimensions = [next()?, next()?, next()?];
+        let ballast_factor = next()?;
+        let _future_use = next()?;
+        let _input_watts = next()?;
+        if photometric_type != 1. {
+            bail!("Only type C photometry is supported, got type {photometric_type}");
+        }
+        if vertical_count < 2 || horizontal_count == 0 {
+            bail!("A table of {vertical_count} by {horizontal_count} angles is too small");
+        }
+
+        let vertical_angles = (0..vertical_count)
+            .map(|_| next())
+            .collect::<Result<Vec<_>>>()?;
+        let horizontal_angles = (0..horizontal_count)
+            .map(|_| next())
+            .collect::<Result<Vec<_>>>()?;
+        let candela = (0..vertical_count * horizontal_count)
+            .map(|_| Ok(next()? * multiplier * ballast_factor))
+            .collect::<Result<Vec<_>>>()?;
+        for angles in [&vertical_angles, &horizontal_angles] {
+            if angles.windows(2).any(|pair| pair[0] >= pair[1]) {
+                bail!("Angles have to be ascending");
+            }
+        }
+
+        Ok(Self {
+            vertical_angles,
+            horizontal_angles,
+            candela,
+        })
+    }
+
+    /// Candelas towards `vertical` degrees from the nadir and `horizontal` degrees around,
+    /// zero outside of the measured cone.
+    pub fn sample(&self, vertical: f32, horizontal: f32) -> f32 {
+        let horizontal = horizontal.rem_euclid(360.);
+        // The last horizontal angle says which part of the full circle was measured.
+        let horizontal = match self.horizontal_angles.last() {
+            Some(&last) if last <= 0. => 0.,
+            Some(&last) if last <= 90. => 90. - (horizontal % 180. - 90.).abs(),
+            Some(&last) if last <= 180. => 180. - (horizontal - 180.).abs(),
+            _ => horizontal,
+        };
+
+        let vertical_count = self.vertical_angles.len();
+        let Some((v0, v1, tv)) = bracket(&self.vertical_angles, vertical) else {
+            return 0.;
+        };
+        let (h0, h1, th) = bracket(&self.horizontal_angles, horizontal).unwrap_or((0, 0, 0.));
+        let at = |h: usize, v: usize| self.candela[h * vertical_count + v];
+        let row = |h| at(h, v0) + (at(h, v1) - at(h, v0)) * tv;
+        row(h0) + (row(h1) - row(h0)) * th
+    }
+
+    /// The profile over [`Self::TEXTURE_SIZE`], scaled so the peak is one.
+    pub fn bake(&self) -> Vec<f32> {
+        let [width, height] = Self::TEXTURE_SIZE;
+        let mut texels: Vec<_> = (0..height)
+            .flat_map(|y| {
+                let vertical = (y as f32 + 0.5) / height as f32 * 180.;
+                (0..width).map(move |x| (vertical, (x as f32 + 0.5) / width as f32 * 360.))
+            })
+            .map(|(vertical, horizontal)| self.sample(vertical, horizontal))
+            .collect();
+        let peak = texels.iter().copied().fold(0., f32::max);
+        if peak > 0. {
+            texels.iter_mut().for_each(|texel| *texel /= peak);
+        }
+        texels
+    }
+}
+
+/// Indices around `x` in ascending `angles` and how far it is between them.
+fn bracket(angles: &[f32], x: f32) -> Option<(usize, usize, f32)> {
+    let (&first, &last) = (angles.first()?, angles.last()?);
+    if x < first || x > last {
+        return None;
+    }
+    let i = angles.partition_point(|&angle| angle <= x);
+    if i == angles.len() {
+        return Some((i - 1, i - 1, 0.));
+    }
+    let (a, b) = (angles[i - 1], angles[i]);
+    Some((i - 1, i, (x - a) / (b - a)))
+}
@@ -1,58 +1,183 @@
 mod gltf_model;
+mod ies;
+mod ply;
+mod progress;
+mod stl;
+mod tangents;
+mod test_scene;
 
 use color_eyre::{
     eyre::{eyre, Context},
     Result,
 };
-use glam::{Vec3, Vec4};
+use glam::{Vec2, Vec3, Vec4};
 use std::path::Path;
 
 pub use gltf_model::*;
+pub use ies::IesProfile;
+pub use ply::PlyModel;
+pub use progress::{ImportCanceled, ImportProgress, ImportStatus};
+pub use stl::StlModel;
+pub use tangents::{generate_normals, generate_tangents};
+pub use test_scene::TestScene;
 
 use crate::{
     app::App,
-    {Material, MaterialId}, {MeshId, MeshRef},
+    ColorSpace, {Material, MaterialId}, {Mesh, MeshId}, {TextureId, BLACK_TEXTURE, WHITE_TEXTURE},
 };
 
+/// Fills in what a file left out: area weighted normals, zero uvs and MikkTSpace tangents,
+/// or +Y tangents without uvs.
+fn complete_mesh(
+    vertices: Vec<Vec3>,
+    normals: Option<Vec<Vec3>>,
+    tex_coords: Option<Vec<Vec2>>,
+    indices: Vec<u32>,
+) -> Mesh {
+    let tex_coords = tex_coords.map(|mut tex_coords| {
+        tex_coords.resize(vertices.len(), Vec2::ZERO);
+        tex_coords
+    });
+    let normals = normals.unwrap_or_else(|| tangents::generate_normals(&vertices, &indices));
+    let tangents = match &tex_coords {
+        Some(tex_coords) => tangents::generate_tangents(&vertices, &normals, tex_coords, &indices),
+        None => vec![Vec4::new(0., 1., 0., 1.); vertices.len()],
+    };
+    let tex_coords = tex_coords.unwrap_or_else(|| vec![Vec2::ZERO; vertices.len()]);
+    Mesh {
+        vertices,
+        normals,
+        tangents,
+        tex_coords,
+        indices,
+    }
+}
+
 pub struct ObjModel;
 
 impl ObjModel {
     pub fn import(app: &mut App, path: impl AsRef<Path>) -> Result<Vec<(MeshId, MaterialId)>> {
+        Self::import_with_progress(app, path, &ImportProgress::new())
+    }
+
+    /// Can only be canceled while the file is parsed, meshes are uploaded right after.
+    pub fn import_with_progress(
+        app: &mut App,
+        path: impl AsRef<Path>,
+        progress: &ImportProgress,
+    ) -> Result<Vec<(MeshId, MaterialId)>> {
         let name = path.as_ref().file_name();
         log::info!("Started processing model: {name:?}",);
+        progress.expect(1);
         let (model_meshes, model_materials) =
             tobj::load_obj(path.as_ref(), &tobj::GPU_LOAD_OPTIONS)
                 .with_context(|| eyre!("Failed to open file: {}", path.as_ref().display()))?;
+        progress.step(path.as_ref().display().to_string())?;
 
+        let dir = path.as_ref().parent().unwrap_or(Path::new("."));
+        let mut encoder = app.device().create_command_encoder(&Default::default());
         let mut materials = vec![];
         if let Ok(model_materials) = model_materials {
             for material in model_materials {
-                let base_color = Vec3::from_array(material.diffuse.unwrap_or([1., 1., 1.]));
-                let material_id = app.get_material_pool_mut().add(Material {
-                    base_color: base_color.extend(0.5),
-                    ..Default::default()
-                });
-                materials.push(material_id);
+                let material = Self::make_material(app, dir, &material, &mut encoder)?;
+                materials.push(app.get_material_pool_mut().add(material)?);
             }
         }
+        app.queue().submit(Some(encoder.finish()));
 
-        let mut meshes = vec![];
-        for mesh in model_meshes.iter().map(|m| &m.mesh) {
-            let mesh_id = app.add_mesh(MeshRef {
-                vertices: bytemuck::cast_slice(&mesh.positions),
-                normals: bytemuck::cast_slice(&mesh.normals),
-                tangents: &vec![Vec4::ZERO; mesh.positions.len()],
-                tex_coords: bytemuck::cast_slice(&mesh.texcoords),
-                indices: mesh.indices.to_vec(),
-            });
-            let material_id = match mesh.material_id {
-                Some(id) => materials[id],
-                None => MaterialId::default(),
-            };
-            meshes.push((mesh_id, material_id));
-        }
+        let meshes: Vec<_> = model_meshes
+            .iter()
+            .map(|model| {
+                let mesh = &model.mesh;
+                let normals = (!mesh.normals.is_empty())
+                    .then(|| bytemuck::cast_slice(&mesh.normals).to_vec());
+                // OBJ puts the uv origin at the bottom left.
+                let tex_coords = (!mesh.texcoords.is_empty()).then(|| {
+                    mesh.texcoords
+                        .chunks_exact(2)
+                        .map(|uv| Vec2::new(uv[0], 1. - uv[1]))
+                        .collect()
+                });
+                complete_mesh(
+                    bytemuck::cast_slice(&mesh.positions).to_vec(),
+                    normals,
+                    tex_coords,
+                    mesh.indices.clone(),
+                )
+            })
+            .collect();
+        let mesh_ids = app.add_meshes(meshes.iter().map(Mesh::as_ref).collect())?;
+        let meshes = mesh_ids
+            .into_iter()
+            .zip(&model_meshes)
+            .map(|(mesh_id, model)| {
+                let material_id = match model.mesh.material_id {
+                    Some(id) => materials[id],
+                    None => MaterialId::default(),
+                };
+                (mesh_id, material_id)
+            })
+            .collect();
 
-        app.get_texture_pool_mut().update_bind_group();
+        app.get_texture_pool_mut().update_bind_group()?;
         Ok(meshes)
     }
+
+    // Missing texture files only cost a warning, MTL files often point at absolute paths
+    // of the machine they were exported on.
+    fn make_material(
+        app: &App,
+        dir: &Path,
+        material: &tobj::Material,
+        encoder: &mut wgpu::CommandEncoder,
+    ) -> Result<Material> {
+        let file = |option: Option<&String>| {
+            // Texture options like `-bm 0.5` come before the file name.
+            let file = dir.join(option?.split_whitespace().last()?);
+            if !file.exists() {
+                log::warn!("Missing texture of {}: {}", material.name, file.display());
+                return None;
+            }
+            Some(file)
+        };
+        let mut texture = |option: Option<&String>, color_space| -> Result<Option<TextureId>> {
+            let Some(file) = file(option) else {
+                return Ok(None);
+            };
+            let data = convert_texture(&load_image(&file)?, color_space == ColorSpace::Srgb)?;
+            let name = file.to_string_lossy();
+            Ok(Some(add_texture(app, &name, data, color_space, encoder)?.0))
+        };
+
+        let params = &material.unknown_param;
+        let albedo = texture(material.diffuse_texture.as_ref(), ColorSpace::Srgb)?;
+        let normal = material.normal_texture.as_ref().or(params.get("norm"));
+        let normal = texture(normal, ColorSpace::Linear)?;
+        let emissive = texture(params.get("map_Ke"), ColorSpace::Srgb)?;
+
+        let roughness = file(params.get("map_Pr"))
+            .map(|file| load_image(&file))
+            .transpose()?;
+        let metallic = file(params.get("map_Pm"))
+            .map(|file| load_image(&file))
+            .transpose()?;
+        let metallic_roughness = match (&roughness, &metallic) {
+            (None, None) => BLACK_TEXTURE,
+            (roughness, metallic) => {
+                let packed = pack_roughness_metallic(roughness.as_ref(), metallic.as_ref())?;
+                let data = TextureData::rgba8(packed, wgpu::TextureFormat::Rgba8Unorm);
+                add_texture(app, "ORM", data, ColorSpace::Linear, encoder)?.0
+            }
+        };
+
+        let base_color = Vec3::from_array(material.diffuse.unwrap_or([1., 1., 1.]));
+        Ok(Material {
+            base_color: base_color.extend(0.5),
+            albedo: albedo.unwrap_or(WHITE_TEXTURE),
+            normal: normal.unwrap_or(WHITE_TEXTURE),
+            metallic_roughness,
+            emissive: emissive.unwrap_or(BLACK_TEXTURE),
+            ..Default::default()
+        })
+    }
 }
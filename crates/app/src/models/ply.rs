@@ -0,0 +1,279 @@
+use std::path::Path;
+
+use color_eyre::{
+    eyre::{bail, eyre, Context},
+    Result,
+};
+use glam::{Vec2, Vec3};
+
+use super::complete_mesh;
+use crate::{app::App, Mesh, MeshId};
+
+/// Importer of Stanford PLY files, as written by scanners and photogrammetry tools.
+///
+/// Reads the ascii and both binary formats. Positions, normals, uvs and the faces are
+/// used, polygons are fanned into triangles and everything else, like vertex colors, is
+/// skipped.
+pub struct PlyModel;
+
+impl PlyModel {
+    pub fn import(app: &mut App, path: impl AsRef<Path>) -> Result<MeshId> {
+        let mesh = Self::load(path)?;
+        Ok(app.add_meshes(vec![mesh.as_ref()])?[0])
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Mesh> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path)
+            .with_context(|| eyre!("Failed to open file: {}", path.display()))?;
+        Self::parse(&bytes).with_context(|| eyre!("Failed to parse PLY: {}", path.display()))
+    }
+
+    pub fn parse(bytes: &[u8]) -> Result<Mesh> {
+        let header_end = bytes
+            .windows(b"end_header".len())
+            .position(|window| window == b"end_header")
+            .ok_or_else(|| eyre!("Missing end_header"))?;
+        let body_start = bytes[header_end..]
+            .iter()
+            .position(|&byte| byte == b'\n')
+            .map_or(bytes.len(), |newline| header_end + newline + 1);
+        let header = std::str::from_utf8(&bytes[..header_end])?;
+        let (format, elements) = parse_header(header)?;
+
+        let body = &bytes[body_start..];
+        let mut values: Box<dyn Values> = match format {
+            Format::Ascii => Box::new(AsciiValues(
+                std::str::from_utf8(body)?.split_ascii_whitespace(),
+            )),
+            Format::BinaryLittleEndian => Box::new(BinaryValues {
+                bytes: body,
+                big_endian: false,
+            }),
+            Format::BinaryBigEndian => Box::new(BinaryValues {
+                bytes: body,
+                big_endian: true,
+            }),
+        };
+
+        let mut vertices = vec![];
+        let mut normals = vec![];
+        let mut tex_coords = vec![];
+        let mut indices = vec![];
+        for element in &elements {
+            let mut row = vec![0f64; element.properties.len()];
+            for _ in 0..element.count {
+                let mut face = vec![];
+                for (property, value) in element.properties.iter().zip(&mut row) {
+                    match property.kind {
+                        Kind::Scalar(ty) => *value = values.read(ty)?,
+                        Kind::List(count, ty) => {
+                            let count = values.read(count)? as usize;
+                            let is_face = element.name == "face"
+                                && matches!(&*property.name, "vertex_indices" | "vertex_index");
+                            for _ in 0..count {
+                                let index = values.read(ty)?;
+                                if is_face {
+                                    face.push(index as u32);
+                                }
+                            }
+                        }
+                    }
+                }
+
+                if element.name == "vertex" {
+                    let get = |names: &[&str]| {
+                        element
+                            .properties
+                            .iter()
+                            .position(|property| names.contains(&&*property.name))
+                            .map(|i| row[i] as f32)
+                    };
+                    let (Some(x), Some(y), Some(z)) = (get(&["x"]), get(&["y"]), get(&["z"]))
+                    else {
+                        bail!("Vertices without positions");
+                    };
+                    vertices.push(Vec3::new(x, y, z));
+                    if let (Some(x), Some(y), Some(z)) = (get(&["nx"]), get(&["ny"]), get(&["nz"]))
+                    {
+                        normals.push(Vec3::new(x, y, z));
+                    }
+                    let u = get(&["u", "s", "texture_u", "texture_s"]);
+                    let v = get(&["v", "t", "texture_v", "texture_t"]);
+                    if let (Some(u), Some(v)) = (u, v) {
+                        // Same bottom left uv origin as OBJ.
+                        tex_coords.push(Vec2::new(u, 1. - v));
+                    }
+                }
+                for i in 2..face.len() {
+                    indices.extend_from_slice(&[face[0], face[i - 1], face[i]]);
+                }
+            }
+        }
+
+        if indices.is_empty() {
+            bail!("No faces, point clouds are not supported");
+        }
+        if let Some(&index) = indices.iter().find(|&&i| i as usize >= vertices.len()) {
+            bail!("Face index {index} out of {} vertices", vertices.len());
+        }
+        let normals = (normals.len() == vertices.len()).then_some(normals);
+        let tex_coords = (tex_coords.len() == vertices.len()).then_some(tex_coords);
+        Ok(complete_mesh(vertices, normals, tex_coords, indices))
+    }
+}
+
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Clone, Copy)]
+enum Scalar {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl Scalar {
+    fn parse(name: &str) -> Result<Self> {
+        Ok(match name {
+            "char" | "int8" => Self::I8,
+            "uchar" | "uint8" => Self::U8,
+            "short" | "int16" => Self::I16,
+            "ushort" | "uint16" => Self::U16,
+            "int" | "int32" => Self::I32,
+            "uint" | "uint32" => Self::U32,
+            "float" | "float32" => Self::F32,
+            "double" | "float64" => Self::F64,
+            _ => bail!("Unknown property type {name}"),
+        })
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Self::I8 | Self::U8 => 1,
+            Self::I16 | Self::U16 => 2,
+            Self::I32 | Self::U32 | Self::F32 => 4,
+            Self::F64 => 8,
+        }
+    }
+}
+
+enum Kind {
+    Scalar(Scalar),
+    /// Type of the length, then of the items.
+    List(Scalar, Scalar),
+}
+
+struct Property {
+    name: String,
+    kind: Kind,
+}
+
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+fn parse_header(header: &str) -> Result<(Format, Vec<Element>)> {
+    let mut lines = header.lines().map(str::trim);
+    if lines.next() != Some("ply") {
+        bail!("Missing ply magic");
+    }
+    let mut format = None;
+    let mut elements: Vec<Element> = vec![];
+    for line in lines {
+        let words: Vec<_> = line.split_ascii_whitespace().collect();
+        match words.as_slice() {
+            ["format", name, _version] => {
+                format = Some(match *name {
+                    "ascii" => Format::Ascii,
+                    "binary_little_endian" => Format::BinaryLittleEndian,
+                    "binary_big_endian" => Format::BinaryBigEndian,
+                    _ => bail!("Unknown format {name}"),
+                });
+            }
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count.parse()?,
+                properties: vec![],
+            }),
+            ["property", "list", count, ty, name] => {
+                let Some(element) = elements.last_mut() else {
+                    bail!("Property before any element");
+                };
+                element.properties.push(Property {
+                    name: name.to_string(),
+                    kind: Kind::List(Scalar::parse(count)?, Scalar::parse(ty)?),
+                });
+            }
+            ["property", ty, name] => {
+                let Some(element) = elements.last_mut() else {
+                    bail!("Property before any element");
+                };
+                element.properties.push(Property {
+                    name: name.to_string(),
+                    kind: Kind::Scalar(Scalar::parse(ty)?),
+                });
+            }
+            ["comment", ..] | ["obj_info", ..] | [] => {}
+            _ => bail!("Unexpected header line {line:?}"),
+        }
+    }
+    let format = format.ok_or_else(|| eyre!("Missing format"))?;
+    Ok((format, elements))
+}
+
+trait Values {
+    fn read(&mut self, ty: Scalar) -> Result<f64>;
+}
+
+struct AsciiValues<'a>(std::str::SplitAsciiWhitespace<'a>);
+
+impl Values for AsciiValues<'_> {
+    fn read(&mut self, _ty: Scalar) -> Result<f64> {
+        let word = self
+            .0
+            .next()
+            .ok_or_else(|| eyre!("Unexpected end of file"))?;
+        Ok(word.parse()?)
+    }
+}
+
+struct BinaryValues<'a> {
+    bytes: &'a [u8],
+    big_endian: bool,
+}
+
+impl Values for BinaryValues<'_> {
+    fn read(&mut self, ty: Scalar) -> Result<f64> {
+        if self.bytes.len() < ty.size() {
+            bail!("Unexpected end of file");
+        }
+        let (value, rest) = self.bytes.split_at(ty.size());
+        self.bytes = rest;
+        let mut buf = [0u8; 8];
+        buf[..value.len()].copy_from_slice(value);
+        if self.big_endian {
+            buf[..value.len()].reverse();
+        }
+        Ok(match ty {
+            Scalar::I8 => buf[0] as i8 as f64,
+            Scalar::U8 => buf[0] as f64,
+            Scalar::I16 => i16::from_le_bytes([buf[0], buf[1]]) as f64,
+            Scalar::U16 => u16::from_le_bytes([buf[0], buf[1]]) as f64,
+            Scalar::I32 => i32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]) as f64,
+            Scalar::U32 => u32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]) as f64,
+            Scalar::F32 => f32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]) as f64,
+            Scalar::F64 => f64::from_le_bytes(buf),
+        })
+    }
+}
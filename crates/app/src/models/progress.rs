@@ -0,0 +1,121 @@
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use color_eyre::Result;
+
+/// Snapshot of an import handed to [`ImportProgress`] callbacks.
+#[derive(Debug, Clone, Default)]
+pub struct ImportStatus {
+    pub done: u32,
+    pub total: u32,
+    /// Name of the last finished item, e.g. a texture or a primitive.
+    pub item: String,
+}
+
+impl ImportStatus {
+    /// Share of the work done in `[0, 1]`.
+    pub fn fraction(&self) -> f32 {
+        if self.total == 0 {
+            return 0.;
+        }
+        (self.done as f32 / self.total as f32).min(1.)
+    }
+}
+
+/// Returned by an import canceled with [`ImportProgress::cancel`].
+#[derive(Debug, Clone, Copy)]
+pub struct ImportCanceled;
+
+impl fmt::Display for ImportCanceled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Import canceled")
+    }
+}
+
+impl std::error::Error for ImportCanceled {}
+
+type Callback = Box<dyn Fn(&ImportStatus) + Send + Sync>;
+
+#[derive(Default)]
+struct Shared {
+    status: Mutex<ImportStatus>,
+    canceled: AtomicBool,
+    callback: Option<Callback>,
+}
+
+/// Reports how far an import got and lets another thread cancel it, clones share the state.
+///
+/// Importers do all cpu work before touching the gpu pools and only stop in that phase,
+/// so a canceled import leaves nothing behind.
+#[derive(Clone, Default)]
+pub struct ImportProgress(Arc<Shared>);
+
+impl ImportProgress {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Calls `callback` on every step, from the worker threads of the importer.
+    pub fn with_callback(callback: impl Fn(&ImportStatus) + Send + Sync + 'static) -> Self {
+        Self(Arc::new(Shared {
+            callback: Some(Box::new(callback)),
+            ..Default::default()
+        }))
+    }
+
+    pub fn status(&self) -> ImportStatus {
+        self.0.status.lock().unwrap().clone()
+    }
+
+    pub fn fraction(&self) -> f32 {
+        self.status().fraction()
+    }
+
+    pub fn cancel(&self) {
+        self.0.canceled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_canceled(&self) -> bool {
+        self.0.canceled.load(Ordering::Relaxed)
+    }
+
+    /// Fails with [`ImportCanceled`] once the import is canceled.
+    pub fn check(&self) -> Result<()> {
+        if self.is_canceled() {
+            return Err(ImportCanceled.into());
+        }
+        Ok(())
+    }
+
+    /// Adds `steps` to the expected total.
+    pub(crate) fn expect(&self, steps: u32) {
+        self.update(|status| status.total += steps);
+    }
+
+    /// Marks `item` done, fails if the import got canceled.
+    pub(crate) fn step(&self, item: impl Into<String>) -> Result<()> {
+        self.check()?;
+        let item = item.into();
+        self.update(|status| {
+            status.done += 1;
+            status.item = item;
+        });
+        Ok(())
+    }
+
+    fn update(&self, f: impl FnOnce(&mut ImportStatus)) {
+        let status = {
+            let mut status = self.0.status.lock().unwrap();
+            f(&mut status);
+            status.clone()
+        };
+        if let Some(callback) = &self.0.callback {
+            callback(&status);
+        }
+    }
+}
@@ -0,0 +1,114 @@
+use std::path::Path;
+
+use color_eyre::{
+    eyre::{bail, eyre, Context},
+    Result,
+};
+use glam::Vec3;
+
+use super::complete_mesh;
+use crate::{app::App, Mesh, MeshId};
+
+/// Importer of STL files, the usual export of CAD tools and slicers.
+///
+/// Reads binary and ascii files. STL has neither uvs nor shared vertices, so every facet
+/// gets its own three vertices with the facet normal and the mesh is flat shaded. CAD
+/// exports are often Z up and in millimeters, the instance transform has to fix that.
+pub struct StlModel;
+
+impl StlModel {
+    pub fn import(app: &mut App, path: impl AsRef<Path>) -> Result<MeshId> {
+        let mesh = Self::load(path)?;
+        Ok(app.add_meshes(vec![mesh.as_ref()])?[0])
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Mesh> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path)
+            .with_context(|| eyre!("Failed to open file: {}", path.display()))?;
+        Self::parse(&bytes).with_context(|| eyre!("Failed to parse STL: {}", path.display()))
+    }
+
+    pub fn parse(bytes: &[u8]) -> Result<Mesh> {
+        // Binary files may start with `solid` too, their size gives them away.
+        let binary_len = bytes
+            .get(80..84)
+            .map(|count| 84 + 50 * u32::from_le_bytes(count.try_into().unwrap()) as usize);
+        let facets = if binary_len == Some(bytes.len()) {
+            parse_binary(bytes)
+        } else if bytes.trim_ascii_start().starts_with(b"solid") {
+            parse_ascii(std::str::from_utf8(bytes)?)?
+        } else {
+            bail!("Neither a binary nor an ascii STL");
+        };
+        if facets.is_empty() {
+            bail!("No facets");
+        }
+
+        let mut vertices = Vec::with_capacity(facets.len() * 3);
+        let mut normals = Vec::with_capacity(facets.len() * 3);
+        for (normal, corners) in facets {
+            // Plenty of exporters write zero normals and leave them to the winding.
+            let [a, b, c] = corners;
+            let normal = normal
+                .try_normalize()
+                .or_else(|| (b - a).cross(c - a).try_normalize())
+                .unwrap_or(Vec3::Y);
+            vertices.extend_from_slice(&corners);
+            normals.extend_from_slice(&[normal; 3]);
+        }
+        let indices = (0..vertices.len() as u32).collect();
+        Ok(complete_mesh(vertices, Some(normals), None, indices))
+    }
+}
+
+type Facet = (Vec3, [Vec3; 3]);
+
+fn parse_binary(bytes: &[u8]) -> Vec<Facet> {
+    let vec3 = |bytes: &[u8]| {
+        let [x, y, z] = [0, 4, 8].map(|i| f32::from_le_bytes(bytes[i..i + 4].try_into().unwrap()));
+        Vec3::new(x, y, z)
+    };
+    bytes[84..]
+        .chunks_exact(50)
+        .map(|facet| {
+            let normal = vec3(&facet[0..12]);
+            let corners = [12, 24, 36].map(|i| vec3(&facet[i..i + 12]));
+            (normal, corners)
+        })
+        .collect()
+}
+
+fn parse_ascii(text: &str) -> Result<Vec<Facet>> {
+    let mut words = text.split_ascii_whitespace();
+
+    let mut facets = vec![];
+    let mut normal = Vec3::ZERO;
+    let mut corners = vec![];
+    while let Some(word) = words.next() {
+        match word {
+            "normal" => normal = vec3(&mut words)?,
+            "vertex" => corners.push(vec3(&mut words)?),
+            "endfacet" => {
+                let [a, b, c] = corners[..] else {
+                    bail!("Facet with {} vertices", corners.len());
+                };
+                facets.push((normal, [a, b, c]));
+                normal = Vec3::ZERO;
+                corners.clear();
+            }
+            _ => {}
+        }
+    }
+    Ok(facets)
+}
+
+fn vec3(words: &mut std::str::SplitAsciiWhitespace) -> Result<Vec3> {
+    let mut next = || -> Result<f32> {
+        let word = words
+            .next()
+            .ok_or_else(|| eyre!("Unexpected end of file"))?;
+        Ok(word.parse()?)
+    };
+    Ok(Vec3::new(next()?, next()?, next()?))
+}
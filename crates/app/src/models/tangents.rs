@@ -0,0 +1,82 @@
+use glam::{Vec2, Vec3, Vec4};
+
+struct Geometry<'a> {
+    vertices: &'a [Vec3],
+    normals: &'a [Vec3],
+    tex_coords: &'a [Vec2],
+    indices: &'a [u32],
+    tangents: Vec<Vec4>,
+}
+
+impl Geometry<'_> {
+    fn index(&self, face: usize, vert: usize) -> usize {
+        self.indices[face * 3 + vert] as usize
+    }
+}
+
+impl mikktspace::Geometry for Geometry<'_> {
+    fn num_faces(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    fn num_vertices_of_face(&self, _face: usize) -> usize {
+        3
+    }
+
+    fn position(&self, face: usize, vert: usize) -> [f32; 3] {
+        self.vertices[self.index(face, vert)].to_array()
+    }
+
+    fn normal(&self, face: usize, vert: usize) -> [f32; 3] {
+        self.normals[self.index(face, vert)].to_array()
+    }
+
+    fn tex_coord(&self, face: usize, vert: usize) -> [f32; 2] {
+        self.tex_coords[self.index(face, vert)].to_array()
+    }
+
+    fn set_tangent_encoded(&mut self, tangent: [f32; 4], face: usize, vert: usize) {
+        let index = self.index(face, vert);
+        self.tangents[index] = Vec4::from_array(tangent);
+    }
+}
+
+/// MikkTSpace tangents of an indexed triangle list, with the handedness in `w`.
+///
+/// Vertices shared by faces that disagree on the tangent keep the last one, which only
+/// matters along uv seams that weren't split on export. Falls back to +Y tangents when
+/// the uvs are degenerate.
+pub fn generate_tangents(
+    vertices: &[Vec3],
+    normals: &[Vec3],
+    tex_coords: &[Vec2],
+    indices: &[u32],
+) -> Vec<Vec4> {
+    let mut geometry = Geometry {
+        vertices,
+        normals,
+        tex_coords,
+        indices,
+        tangents: vec![Vec4::new(0., 1., 0., 1.); vertices.len()],
+    };
+    if !mikktspace::generate_tangents(&mut geometry) {
+        log::warn!("Failed to generate tangents for a mesh with degenerate uvs");
+    }
+    geometry.tangents
+}
+
+/// Area weighted vertex normals, for meshes that come without any.
+pub fn generate_normals(vertices: &[Vec3], indices: &[u32]) -> Vec<Vec3> {
+    let mut normals = vec![Vec3::ZERO; vertices.len()];
+    for triangle in indices.chunks_exact(3) {
+        let [a, b, c] = [0, 1, 2].map(|i| triangle[i] as usize);
+        let normal = (vertices[b] - vertices[a]).cross(vertices[c] - vertices[a]);
+        for i in [a, b, c] {
+            normals[i] += normal;
+        }
+    }
+    normals
+        .into_iter()
+        .map(|normal| normal.try_normalize().unwrap_or(Vec3::Y))
+        .collect()
+}
@@ -0,0 +1,271 @@
+use std::f32::consts::PI;
+
+use color_eyre::Result;
+use glam::{vec3, Mat4, Quat, Vec3};
+use image::{Rgba, RgbaImage};
+
+use super::{add_texture, TextureData};
+use crate::{
+    app::App, ColorSpace, DirectionalLight, Instance, Light, LightPool, Material, MaterialId,
+    MaterialPool, MeshPool, TextureId,
+};
+
+/// Procedural scenes to look at shading and GI without downloading sample models, see
+/// [`App::load_test_scene`]. All of them stand on the origin with y up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TestScene {
+    /// Box with a red left and a green right wall and two blocks inside, 10 units on
+    /// every side and open towards +z, lit through a panel in the ceiling.
+    CornellBox,
+    /// White spheres of rising roughness inside a white emissive sphere, with working
+    /// energy conservation they fade into the background.
+    Furnace,
+    /// 7x7 spheres, roughness rises along x and metallic along y.
+    MaterialGrid,
+    /// 32x32 small colored point lights over a floor, a stress test of light culling.
+    LightArray,
+}
+
+impl TestScene {
+    pub const ALL: [Self; 4] = [
+        Self::CornellBox,
+        Self::Furnace,
+        Self::MaterialGrid,
+        Self::LightArray,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::CornellBox => "Cornell Box",
+            Self::Furnace => "Furnace",
+            Self::MaterialGrid => "Material Grid",
+            Self::LightArray => "Light Array",
+        }
+    }
+
+    /// Adds the meshes, materials, lights and instances of the scene to `app`.
+    pub fn load(self, app: &mut App) -> Result<()> {
+        let instances = match self {
+            Self::CornellBox => cornell_box(app)?,
+            Self::Furnace => furnace(app)?,
+            Self::MaterialGrid => material_grid(app)?,
+            Self::LightArray => light_array(app)?,
+        };
+        app.get_instance_pool_mut().add(&instances)?;
+        Ok(())
+    }
+}
+
+/// Solid color materials, made of 1x1 albedo and ORM textures.
+struct Materials<'a> {
+    app: &'a App,
+    encoder: wgpu::CommandEncoder,
+}
+
+impl<'a> Materials<'a> {
+    fn new(app: &'a App) -> Self {
+        let encoder = app.device().create_command_encoder(&Default::default());
+        Self { app, encoder }
+    }
+
+    /// `color` is linear.
+    fn add(&mut self, color: Vec3, roughness: f32, metallic: f32) -> Result<MaterialId> {
+        let srgb = color.clamp(Vec3::ZERO, Vec3::ONE).to_array().map(|c| {
+            let c = if c <= 0.0031308 {
+                c * 12.92
+            } else {
+                1.055 * c.powf(1. / 2.4) - 0.055
+            };
+            (c * 255.).round() as u8
+        });
+        let albedo = self.texture(
+            "Test Albedo",
+            [srgb[0], srgb[1], srgb[2], 255],
+            ColorSpace::Srgb,
+        )?;
+        let [roughness, metallic] = [roughness, metallic].map(|v| (v * 255.).round() as u8);
+        let metallic_roughness = self.texture(
+            "Test ORM",
+            [255, roughness, metallic, 255],
+            ColorSpace::Linear,
+        )?;
+        self.app.get_material_pool_mut().add(Material {
+            albedo,
+            metallic_roughness,
+            ..Default::default()
+        })
+    }
+
+    fn texture(
+        &mut self,
+        name: &str,
+        pixel: [u8; 4],
+        color_space: ColorSpace,
+    ) -> Result<TextureId> {
+        let format = match color_space {
+            ColorSpace::Srgb => wgpu::TextureFormat::Rgba8UnormSrgb,
+            ColorSpace::Linear => wgpu::TextureFormat::Rgba8Unorm,
+        };
+        let data = TextureData::rgba8(RgbaImage::from_pixel(1, 1, Rgba(pixel)), format);
+        Ok(add_texture(self.app, name, data, color_space, &mut self.encoder)?.0)
+    }
+
+    fn finish(self) -> Result<()> {
+        self.app.queue().submit(Some(self.encoder.finish()));
+        self.app.get_texture_pool_mut().update_bind_group()
+    }
+}
+
+fn cornell_box(app: &mut App) -> Result<Vec<Instance>> {
+    let block = app.add_mesh(crate::make_box_mesh(1., 1., 1.).as_ref())?;
+
+    let mut materials = Materials::new(app);
+    let white = materials.add(vec3(0.725, 0.71, 0.68), 0.8, 0.)?;
+    let red = materials.add(vec3(0.63, 0.065, 0.05), 0.8, 0.)?;
+    let green = materials.add(vec3(0.14, 0.45, 0.091), 0.8, 0.)?;
+    materials.finish()?;
+
+    let plane = MeshPool::HORISONTAL_PLANE_MESH;
+    let wall = |translation: Vec3, rotation: Quat| {
+        Mat4::from_scale_rotation_translation(Vec3::splat(10.), rotation, translation)
+    };
+    let block_transform = |translation: Vec3, angle: f32, height: f32| {
+        Mat4::from_scale_rotation_translation(
+            vec3(1.5, height / 2., 1.5),
+            Quat::from_rotation_y(angle),
+            translation + Vec3::Y * height / 2.,
+        )
+    };
+    let instances = vec![
+        Instance::new(wall(Vec3::ZERO, Quat::IDENTITY), plane, white),
+        Instance::new(
+            wall(vec3(0., 10., 0.), Quat::from_rotation_x(PI)),
+            plane,
+            white,
+        ),
+        Instance::new(
+            wall(vec3(0., 5., -5.), Quat::from_rotation_x(PI / 2.)),
+            plane,
+            white,
+        ),
+        Instance::new(
+            wall(vec3(-5., 5., 0.), Quat::from_rotation_z(-PI / 2.)),
+            plane,
+            red,
+        ),
+        Instance::new(
+            wall(vec3(5., 5., 0.), Quat::from_rotation_z(PI / 2.)),
+            plane,
+            green,
+        ),
+        Instance::new(block_transform(vec3(-1.8, 0., -1.5), 0.3, 6.), block, white),
+        Instance::new(block_transform(vec3(1.8, 0., 1.2), -0.3, 3.), block, white),
+        // Just below the ceiling, so it isn't hidden by it.
+        Instance::new(
+            Mat4::from_scale_rotation_translation(
+                Vec3::splat(2.5),
+                Quat::from_rotation_x(PI),
+                vec3(0., 9.99, 0.),
+            ),
+            plane,
+            MaterialPool::LIGHT_MATERIAL,
+        ),
+    ];
+
+    app.world
+        .get_mut::<LightPool>()?
+        .add_point_light(&[Light::new(vec3(0., 9., 0.), 25., Vec3::ONE)])?;
+    Ok(instances)
+}
+
+fn furnace(app: &mut App) -> Result<Vec<Instance>> {
+    let mut materials = Materials::new(app);
+    let mut instances = vec![];
+    for i in 0..5 {
+        let roughness = i as f32 / 4.;
+        let material = materials.add(Vec3::ONE, roughness, 0.)?;
+        instances.push(Instance::new(
+            Mat4::from_translation(vec3(-6. + 3. * i as f32, 1., 0.)),
+            MeshPool::SPHERE_10_MESH,
+            material,
+        ));
+    }
+    materials.finish()?;
+
+    // Turned inside out, the faces look inwards.
+    instances.push(Instance::new(
+        Mat4::from_scale(Vec3::splat(-40.)),
+        MeshPool::SPHERE_10_MESH,
+        MaterialPool::LIGHT_MATERIAL,
+    ));
+    Ok(instances)
+}
+
+fn material_grid(app: &mut App) -> Result<Vec<Instance>> {
+    const SIDE: usize = 7;
+
+    let mut materials = Materials::new(app);
+    let mut instances = vec![];
+    for y in 0..SIDE {
+        for x in 0..SIDE {
+            let roughness = x as f32 / (SIDE - 1) as f32;
+            let metallic = y as f32 / (SIDE - 1) as f32;
+            let material = materials.add(vec3(0.9, 0.6, 0.2), roughness, metallic)?;
+            let offset = (SIDE - 1) as f32 / 2.;
+            instances.push(Instance::new(
+                Mat4::from_translation(vec3(x as f32 - offset, y as f32 + 1., 0.) * 2.5),
+                MeshPool::SPHERE_10_MESH,
+                material,
+            ));
+        }
+    }
+    materials.finish()?;
+
+    let mut lights = app.world.get_mut::<LightPool>()?;
+    lights.set_sun(DirectionalLight::new(vec3(0.3, 0.6, 1.), 2., Vec3::ONE));
+    lights.add_point_light(&[
+        Light::new(vec3(-8., 12., 8.), 30., Vec3::ONE),
+        Light::new(vec3(8., 4., 8.), 30., Vec3::ONE),
+    ])?;
+    Ok(instances)
+}
+
+fn light_array(app: &mut App) -> Result<Vec<Instance>> {
+    const SIDE: usize = 32;
+    const SPACING: f32 = 1.8;
+
+    let mut materials = Materials::new(app);
+    let floor = materials.add(Vec3::splat(0.5), 0.6, 0.)?;
+    materials.finish()?;
+
+    let extent = SIDE as f32 * SPACING;
+    let mut instances = vec![Instance::new(
+        Mat4::from_scale(Vec3::splat(extent + SPACING)),
+        MeshPool::HORISONTAL_PLANE_MESH,
+        floor,
+    )];
+    let mut lights = Vec::with_capacity(SIDE * SIDE);
+    for z in 0..SIDE {
+        for x in 0..SIDE {
+            let position = vec3(
+                (x as f32 + 0.5) * SPACING - extent / 2.,
+                1.,
+                (z as f32 + 0.5) * SPACING - extent / 2.,
+            );
+            lights.push(Light::new(position, 3., hue((x * SIDE + z) as f32 * 0.618)));
+            instances.push(Instance::new(
+                Mat4::from_translation(position) * Mat4::from_scale(Vec3::splat(0.1)),
+                MeshPool::SPHERE_10_MESH,
+                MaterialPool::LIGHT_MATERIAL,
+            ));
+        }
+    }
+    app.world.get_mut::<LightPool>()?.add_point_light(&lights)?;
+    Ok(instances)
+}
+
+/// Saturated color of the hue `h`, which wraps around at 1.
+fn hue(h: f32) -> Vec3 {
+    let t = (Vec3::splat(h) + vec3(0., 2. / 3., 1. / 3.)).fract() * 6. - 3.;
+    (t.abs() - 1.).clamp(Vec3::ZERO, Vec3::ONE)
+}
@@ -60,7 +60,7 @@ impl Pass for ComputeUpdate {
         });
 
         cpass.set_pipeline(arena.get_pipeline(self.pipeline));
-        cpass.set_bind_group(0, &global_ubo.binding, &[]);
+        cpass.set_bind_group(0, global_ubo.binding(), &[]);
         cpass.set_bind_group(1, resources.idx_bind_group, &[]);
         cpass.set_bind_group(2, &instances.bind_group, &[]);
         let num_dispatches = align_to(resources.dispatch_size, 64) / 64;
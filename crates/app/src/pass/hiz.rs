@@ -0,0 +1,269 @@
+use std::path::Path;
+
+use color_eyre::Result;
+use components::{
+    bind_group_layout::{BindGroupLayout, WrappedBindGroupLayout},
+    world::World,
+};
+use wgpu::util::align_to;
+
+use crate::{
+    pipeline::{ComputeHandle, ComputePipelineDescriptor, PipelineArena},
+    GBuffer, ProfilerCommandEncoder,
+};
+
+use super::Pass;
+
+/// Which depth of the cell a pyramid texel keeps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DepthReduction {
+    /// Ray marching skips cells whose closest surface is behind the ray.
+    Closest,
+    /// Occlusion culling rejects boxes behind the farthest surface of the cells they cover.
+    Farthest,
+}
+
+/// Depth pyramid built from the G-buffer depth, closest depth by default.
+///
+/// Passes that march rays in screen space bind [`HiZ::bind_group`] and
+/// `#import "utils/hiz.wgsl"`, which walks the pyramid instead of stepping every pixel.
+pub struct HiZ {
+    copy_pipeline: ComputeHandle,
+    downsample_pipeline: ComputeHandle,
+
+    write_layout: BindGroupLayout,
+    pyramid: Pyramid,
+
+    pub bind_group_layout: BindGroupLayout,
+    pub bind_group: wgpu::BindGroup,
+}
+
+struct Pyramid {
+    size: (u32, u32),
+    view: wgpu::TextureView,
+    // Per mip: storage binding of the mip and sampled binding of the one above it.
+    write_bind_groups: Vec<wgpu::BindGroup>,
+    read_bind_groups: Vec<wgpu::BindGroup>,
+}
+
+impl Pyramid {
+    fn new(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        write_layout: &wgpu::BindGroupLayout,
+        read_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        let mip_level_count = u32::BITS - width.max(height).max(1).leading_zeros();
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Hi-Z Pyramid"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: HiZ::FORMAT,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::STORAGE_BINDING,
+            view_formats: &[],
+        });
+        let mip_views = (0..mip_level_count)
+            .map(|mip| {
+                texture.create_view(&wgpu::TextureViewDescriptor {
+                    base_mip_level: mip,
+                    mip_level_count: Some(1),
+                    ..Default::default()
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let single_view_bind_group = |layout, view| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Hi-Z Mip BG"),
+                layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(view),
+                }],
+            })
+        };
+        let write_bind_groups = mip_views
+            .iter()
+            .map(|view| single_view_bind_group(write_layout, view))
+            .collect();
+        let read_bind_groups = mip_views
+            .iter()
+            .map(|view| single_view_bind_group(read_layout, view))
+            .collect();
+
+        Self {
+            size: (width, height),
+            view: texture.create_view(&Default::default()),
+            write_bind_groups,
+            read_bind_groups,
+        }
+    }
+
+    fn mip_size(&self, mip: usize) -> (u32, u32) {
+        ((self.size.0 >> mip).max(1), (self.size.1 >> mip).max(1))
+    }
+}
+
+impl HiZ {
+    pub const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R32Float;
+
+    const READ_LAYOUT_DESC: wgpu::BindGroupLayoutDescriptor<'static> =
+        wgpu::BindGroupLayoutDescriptor {
+            label: Some("Hi-Z Read BGL"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::COMPUTE.union(wgpu::ShaderStages::FRAGMENT),
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            }],
+        };
+
+    pub fn new(world: &World, gbuffer: &GBuffer, width: u32, height: u32) -> Result<Self> {
+        Self::with_reduction(
+            world,
+            &gbuffer.bind_group_layout,
+            width,
+            height,
+            DepthReduction::Closest,
+        )
+    }
+
+    pub fn with_reduction(
+        world: &World,
+        gbuffer_layout: &BindGroupLayout,
+        width: u32,
+        height: u32,
+        reduction: DepthReduction,
+    ) -> Result<Self> {
+        let device = world.device();
+        let write_layout = device.create_bind_group_layout_wrap(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Hi-Z Write BGL"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::StorageTexture {
+                    access: wgpu::StorageTextureAccess::WriteOnly,
+                    format: Self::FORMAT,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                },
+                count: None,
+            }],
+        });
+        let read_layout = device.create_bind_group_layout_wrap(&Self::READ_LAYOUT_DESC);
+
+        let path = Path::new("shaders").join("hiz.wgsl");
+        let copy_desc = ComputePipelineDescriptor {
+            label: Some("Hi-Z Copy Depth Pipeline".into()),
+            layout: vec![write_layout.clone(), gbuffer_layout.clone()],
+            push_constant_ranges: vec![],
+            entry_point: "copy_depth".into(),
+        };
+        let downsample_desc = ComputePipelineDescriptor {
+            label: Some("Hi-Z Downsample Pipeline".into()),
+            layout: vec![write_layout.clone(), read_layout.clone()],
+            push_constant_ranges: vec![],
+            entry_point: match reduction {
+                DepthReduction::Closest => "downsample",
+                DepthReduction::Farthest => "downsample_farthest",
+            }
+            .into(),
+        };
+        let mut arena = world.get_mut::<PipelineArena>()?;
+        let copy_pipeline = arena.process_compute_pipeline_from_path(&path, copy_desc)?;
+        let downsample_pipeline =
+            arena.process_compute_pipeline_from_path(&path, downsample_desc)?;
+
+        let pyramid = Pyramid::new(device, width, height, &write_layout, &read_layout);
+        let bind_group = Self::create_bind_group(device, &read_layout, &pyramid);
+
+        Ok(Self {
+            copy_pipeline,
+            downsample_pipeline,
+
+            bind_group_layout: read_layout,
+            bind_group,
+
+            write_layout,
+            pyramid,
+        })
+    }
+
+    fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        pyramid: &Pyramid,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Hi-Z BG"),
+            layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&pyramid.view),
+            }],
+        })
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        self.pyramid = Pyramid::new(
+            device,
+            width,
+            height,
+            &self.write_layout,
+            &self.bind_group_layout,
+        );
+        self.bind_group = Self::create_bind_group(device, &self.bind_group_layout, &self.pyramid);
+    }
+
+    pub fn mip_level_count(&self) -> u32 {
+        self.pyramid.write_bind_groups.len() as u32
+    }
+}
+
+pub struct HiZResource<'a> {
+    pub gbuffer: &'a GBuffer,
+}
+
+impl Pass for HiZ {
+    type Resources<'a> = HiZResource<'a>;
+
+    fn record(
+        &self,
+        world: &World,
+        encoder: &mut ProfilerCommandEncoder,
+        resources: Self::Resources<'_>,
+    ) {
+        let arena = world.unwrap::<PipelineArena>();
+
+        encoder.profile_start("Hi-Z");
+        let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Hi-Z Pass"),
+        });
+
+        let (width, height) = self.pyramid.mip_size(0);
+        cpass.set_pipeline(arena.get_pipeline(self.copy_pipeline));
+        cpass.set_bind_group(0, &self.pyramid.write_bind_groups[0], &[]);
+        cpass.set_bind_group(1, &resources.gbuffer.bind_group, &[]);
+        cpass.dispatch_workgroups(align_to(width, 8) / 8, align_to(height, 8) / 8, 1);
+
+        cpass.set_pipeline(arena.get_pipeline(self.downsample_pipeline));
+        for mip in 1..self.pyramid.write_bind_groups.len() {
+            let (width, height) = self.pyramid.mip_size(mip);
+            cpass.set_bind_group(0, &self.pyramid.write_bind_groups[mip], &[]);
+            cpass.set_bind_group(1, &self.pyramid.read_bind_groups[mip - 1], &[]);
+            cpass.dispatch_workgroups(align_to(width, 8) / 8, align_to(height, 8) / 8, 1);
+        }
+        drop(cpass);
+        encoder.profile_end();
+    }
+}
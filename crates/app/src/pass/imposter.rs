@@ -0,0 +1,119 @@
+use std::path::Path;
+
+use color_eyre::Result;
+use components::world::World;
+
+use crate::{
+    pipeline::{self, PipelineArena, RenderHandle, RenderPipelineDescriptor},
+    CameraUniformBinding, GBuffer, Imposters, InstancePool, ProfilerCommandEncoder, TexturePool,
+};
+
+use super::Pass;
+
+/// Draws the instances `EmitDraws` swapped to [`Imposters`] as billboards into the G-buffer.
+pub struct ImposterBillboards {
+    pipeline: RenderHandle,
+}
+
+impl ImposterBillboards {
+    pub fn new(world: &World) -> Result<Self> {
+        let camera = world.get::<CameraUniformBinding>()?;
+        let textures = world.get::<TexturePool>()?;
+        let instances = world.get::<InstancePool>()?;
+        let imposters = world.get::<Imposters>()?;
+        let desc = RenderPipelineDescriptor {
+            label: Some("Imposter Billboards Pipeline".into()),
+            layout: vec![
+                camera.bind_group_layout.clone(),
+                textures.bind_group_layout.clone(),
+                instances.bind_group_layout.clone(),
+                imposters.draw_layout.clone(),
+            ],
+            vertex: pipeline::VertexState {
+                entry_point: "vs_main".into(),
+                buffers: vec![],
+            },
+            fragment: Some(pipeline::FragmentState {
+                entry_point: "fs_main".into(),
+                targets: GBuffer::color_target_state().into(),
+            }),
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: GBuffer::DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Greater,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            ..Default::default()
+        };
+        let pipeline = world
+            .get_mut::<PipelineArena>()?
+            .process_render_pipeline_from_path(Path::new("shaders").join("imposter.wgsl"), desc)?;
+        Ok(Self { pipeline })
+    }
+}
+
+pub struct ImposterBillboardsResource<'a> {
+    pub gbuffer: &'a GBuffer,
+}
+
+impl Pass for ImposterBillboards {
+    type Resources<'a> = ImposterBillboardsResource<'a>;
+
+    fn record(
+        &self,
+        world: &World,
+        encoder: &mut ProfilerCommandEncoder,
+        resources: Self::Resources<'_>,
+    ) {
+        let imposters = world.unwrap::<Imposters>();
+        if imposters.is_empty() {
+            return;
+        }
+        let camera = world.unwrap::<CameraUniformBinding>();
+        let textures = world.unwrap::<TexturePool>();
+        let instances = world.unwrap::<InstancePool>();
+        let arena = world.unwrap::<PipelineArena>();
+        let gbuffer = resources.gbuffer;
+
+        // Drawn on top of the geometry pass.
+        let load = wgpu::Operations {
+            load: wgpu::LoadOp::Load,
+            store: true,
+        };
+        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Imposter Billboards Pass"),
+            color_attachments: &[
+                Some(wgpu::RenderPassColorAttachment {
+                    view: &gbuffer.packed,
+                    resolve_target: None,
+                    ops: load,
+                }),
+                Some(wgpu::RenderPassColorAttachment {
+                    view: &gbuffer.history_reject,
+                    resolve_target: None,
+                    ops: load,
+                }),
+                Some(wgpu::RenderPassColorAttachment {
+                    view: &gbuffer.instance_ids,
+                    resolve_target: None,
+                    ops: load,
+                }),
+            ],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &gbuffer.depth,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: true,
+                }),
+                stencil_ops: None,
+            }),
+        });
+        rpass.set_pipeline(arena.get_pipeline(self.pipeline));
+        rpass.set_bind_group(0, camera.binding(), &[]);
+        rpass.set_bind_group(1, &textures.bind_group, &[]);
+        rpass.set_bind_group(2, &instances.bind_group, &[]);
+        rpass.set_bind_group(3, &imposters.draw_bind_group, &[]);
+        rpass.draw_indirect(imposters.draws(), 0);
+    }
+}
@@ -0,0 +1,69 @@
+use std::path::Path;
+
+use color_eyre::Result;
+use wgpu::util::align_to;
+
+use crate::{
+    pipeline::{ComputeHandle, ComputePipelineDescriptor, PipelineArena},
+    GlobalUniformBinding, InstancePool, ProfilerCommandEncoder,
+};
+use components::world::World;
+
+use super::Pass;
+
+/// Blends the transforms of instances moved by fixed steps with the render frame alpha,
+/// so motion stays smooth at any frame rate. Record it before the passes reading
+/// instances, the transforms stay blended until the next frame.
+pub struct InstanceInterpolation {
+    pipeline: ComputeHandle,
+}
+
+impl InstanceInterpolation {
+    pub fn new(world: &World) -> Result<Self> {
+        let path = Path::new("shaders").join("interpolate.wgsl");
+        let global_ubo = world.get::<GlobalUniformBinding>()?;
+        let instances = world.get::<InstancePool>()?;
+        let desc = ComputePipelineDescriptor {
+            label: Some("Instance Interpolation Pipeline".into()),
+            layout: vec![
+                global_ubo.layout.clone(),
+                instances.bind_group_layout.clone(),
+                instances.motion_bind_group_layout.clone(),
+            ],
+            push_constant_ranges: vec![],
+            entry_point: "interpolate".into(),
+        };
+        let pipeline = world
+            .get_mut::<PipelineArena>()?
+            .process_compute_pipeline_from_path(path, desc)?;
+        Ok(Self { pipeline })
+    }
+}
+
+impl Pass for InstanceInterpolation {
+    type Resources<'a> = ();
+
+    fn record(
+        &self,
+        world: &World,
+        encoder: &mut ProfilerCommandEncoder,
+        _resources: Self::Resources<'_>,
+    ) {
+        let instances = world.unwrap::<InstancePool>();
+        let count = instances.motion_count();
+        if count == 0 {
+            return;
+        }
+        let arena = world.unwrap::<PipelineArena>();
+        let global_ubo = world.unwrap::<GlobalUniformBinding>();
+        let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Instance Interpolation Pass"),
+        });
+
+        cpass.set_pipeline(arena.get_pipeline(self.pipeline));
+        cpass.set_bind_group(0, global_ubo.binding(), &[]);
+        cpass.set_bind_group(1, &instances.bind_group, &[]);
+        cpass.set_bind_group(2, &instances.motion_bind_group, &[]);
+        cpass.dispatch_workgroups(align_to(count, 64) / 64, 1, 1);
+    }
+}
@@ -1,9 +1,21 @@
 use components::world::World;
 
 pub mod compute_update;
+pub mod hiz;
+pub mod imposter;
+pub mod interpolate;
+pub mod pathtrace;
+pub mod picking;
 pub mod postprocess;
+pub mod restir;
 pub mod shading;
+pub mod shading_rate;
+pub mod skinning;
+pub mod sky;
+pub mod ssgi;
+pub mod stats;
 pub mod taa;
+pub mod tlas_build;
 pub mod visibility;
 
 pub trait Pass {
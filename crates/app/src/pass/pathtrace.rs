@@ -0,0 +1,316 @@
+use std::{
+    cell::Cell,
+    path::Path,
+    sync::atomic::{AtomicU8, Ordering},
+};
+
+use bytemuck::{Pod, Zeroable};
+use color_eyre::Result;
+use components::{
+    bind_group_layout::{BindGroupLayout, WrappedBindGroupLayout},
+    world::World,
+    Camera, NonZeroSized,
+};
+use glam::{Mat4, Vec4};
+use pools::MeshPool;
+
+use crate::{
+    pipeline::{self, PipelineArena, RenderHandle, RenderPipelineDescriptor},
+    LightPool, MaterialPool, ProfilerCommandEncoder, TexturePool, ViewTarget,
+};
+
+use super::Pass;
+
+/// Tunables of [`PathTrace`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PathTraceSettings {
+    /// Bounces after the primary hit, russian roulette ends most paths earlier.
+    pub max_bounces: u32,
+    /// The image stops changing once this many samples are in.
+    pub max_samples: u32,
+}
+
+impl Default for PathTraceSettings {
+    fn default() -> Self {
+        Self {
+            max_bounces: 4,
+            max_samples: 4096,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct PathTraceUniform {
+    clip_to_world: Mat4,
+    position: Vec4,
+    samples: u32,
+    max_samples: u32,
+    max_bounces: u32,
+    junk: u32,
+}
+
+struct Targets {
+    accumulation: [wgpu::TextureView; 2],
+    // Per history index: reading it while the other one is written.
+    bind_groups: [wgpu::BindGroup; 2],
+}
+
+impl Targets {
+    fn new(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        layout: &wgpu::BindGroupLayout,
+        uniform: &wgpu::Buffer,
+    ) -> Self {
+        let accumulation: [wgpu::TextureView; 2] = std::array::from_fn(|i| {
+            device
+                .create_texture(&wgpu::TextureDescriptor {
+                    label: Some(["Path Trace Accumulation 0", "Path Trace Accumulation 1"][i]),
+                    size: wgpu::Extent3d {
+                        width,
+                        height,
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format: PathTrace::FORMAT,
+                    usage: wgpu::TextureUsages::TEXTURE_BINDING
+                        | wgpu::TextureUsages::RENDER_ATTACHMENT,
+                    view_formats: &[],
+                })
+                .create_view(&Default::default())
+        });
+        let bind_groups = std::array::from_fn(|i| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Path Trace BG"),
+                layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&accumulation[i]),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: uniform.as_entire_binding(),
+                    },
+                ],
+            })
+        });
+        Self {
+            accumulation,
+            bind_groups,
+        }
+    }
+}
+
+/// Progressive path tracer over the TLAS and mesh BVHs, the ground truth to hold the
+/// raster passes against.
+///
+/// Every frame adds one sample per pixel to a running average, which starts over when
+/// the camera moves or on [`PathTrace::reset`]. Paths pick up emissive surfaces when
+/// they hit them and sample one point light and the sun at every bounce, with the
+/// falloff of the shading pass. Every area light gets shadow rays to points of a
+/// low-discrepancy sequence over its panel, more of them the larger it looks, so its soft
+/// shadows converge with the samples. Like all tracing here back faces are culled.
+pub struct PathTrace {
+    pipeline: RenderHandle,
+    layout: BindGroupLayout,
+
+    settings: PathTraceSettings,
+    uniform: wgpu::Buffer,
+
+    samples: Cell<u32>,
+    last_world_to_clip: Cell<Mat4>,
+    active_history: AtomicU8,
+    targets: Targets,
+}
+
+impl PathTrace {
+    pub const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba32Float;
+
+    pub fn new(world: &World, width: u32, height: u32) -> Result<Self> {
+        let device = world.device();
+        let textures = world.get::<TexturePool>()?;
+        let materials = world.get::<MaterialPool>()?;
+        let lights = world.get::<LightPool>()?;
+        let meshes = world.get::<MeshPool>()?;
+
+        let layout = device.create_bind_group_layout_wrap(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Path Trace BGL"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: Some(PathTraceUniform::NSIZE),
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let desc = RenderPipelineDescriptor {
+            label: Some("Path Trace Pipeline".into()),
+            layout: vec![
+                layout.clone(),
+                textures.bind_group_layout.clone(),
+                materials.bind_group_layout.clone(),
+                lights.point_bind_group_layout.clone(),
+                meshes.trace_bind_group_layout.clone(),
+                meshes.attributes_layout.clone(),
+                lights.sun_bind_group_layout.clone(),
+                lights.area_bind_group_layout.clone(),
+            ],
+            fragment: Some(pipeline::FragmentState {
+                entry_point: "fs_main".into(),
+                targets: vec![Some(Self::FORMAT.into()), Some(ViewTarget::FORMAT.into())],
+            }),
+            depth_stencil: None,
+            ..Default::default()
+        };
+        let pipeline = world
+            .get_mut::<PipelineArena>()?
+            .process_render_pipeline_from_path(Path::new("shaders").join("pathtrace.wgsl"), desc)?;
+
+        let uniform = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Path Trace Uniform"),
+            size: PathTraceUniform::SIZE as _,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let targets = Targets::new(device, width, height, &layout, &uniform);
+
+        Ok(Self {
+            pipeline,
+            layout,
+
+            settings: PathTraceSettings::default(),
+            uniform,
+
+            samples: Cell::new(0),
+            last_world_to_clip: Cell::new(Mat4::ZERO),
+            active_history: AtomicU8::new(0),
+            targets,
+        })
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        self.targets = Targets::new(device, width, height, &self.layout, &self.uniform);
+        self.reset();
+    }
+
+    /// Throws the accumulated samples away, needed after the scene changes.
+    pub fn reset(&self) {
+        self.samples.set(0);
+    }
+
+    /// Samples per pixel in the current image.
+    pub fn samples(&self) -> u32 {
+        self.samples.get()
+    }
+
+    pub fn settings(&self) -> PathTraceSettings {
+        self.settings
+    }
+
+    pub fn set_settings(&mut self, settings: PathTraceSettings) {
+        if settings != self.settings {
+            self.settings = settings;
+            self.reset();
+        }
+    }
+}
+
+pub struct PathTraceResource<'a> {
+    pub view_target: &'a ViewTarget,
+    pub camera: &'a Camera,
+}
+
+impl Pass for PathTrace {
+    type Resources<'a> = PathTraceResource<'a>;
+
+    fn record(
+        &self,
+        world: &World,
+        encoder: &mut ProfilerCommandEncoder,
+        resources: Self::Resources<'_>,
+    ) {
+        // Without the jitter, which would restart the accumulation every frame.
+        let (projection, view) = resources.camera.build_projection_view_matrix();
+        let world_to_clip = projection * view;
+        if !world_to_clip.abs_diff_eq(self.last_world_to_clip.get(), 1e-5) {
+            self.last_world_to_clip.set(world_to_clip);
+            self.reset();
+        }
+
+        let samples = self.samples.get();
+        let uniform = PathTraceUniform {
+            clip_to_world: world_to_clip.inverse(),
+            position: resources.camera.position.extend(1.),
+            samples,
+            max_samples: self.settings.max_samples,
+            max_bounces: self.settings.max_bounces,
+            junk: 0,
+        };
+        world
+            .queue()
+            .write_buffer(&self.uniform, 0, bytemuck::bytes_of(&uniform));
+        self.samples
+            .set((samples + 1).min(self.settings.max_samples));
+
+        let history = self.active_history.fetch_xor(1, Ordering::Relaxed) as usize;
+        let textures = world.unwrap::<TexturePool>();
+        let materials = world.unwrap::<MaterialPool>();
+        let lights = world.unwrap::<LightPool>();
+        let meshes = world.unwrap::<MeshPool>();
+        let arena = world.unwrap::<PipelineArena>();
+
+        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Path Trace Pass"),
+            color_attachments: &[
+                Some(wgpu::RenderPassColorAttachment {
+                    view: &self.targets.accumulation[history ^ 1],
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: true,
+                    },
+                }),
+                Some(wgpu::RenderPassColorAttachment {
+                    view: resources.view_target.main_view(),
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: true,
+                    },
+                }),
+            ],
+            depth_stencil_attachment: None,
+        });
+        rpass.set_pipeline(arena.get_pipeline(self.pipeline));
+        rpass.set_bind_group(0, &self.targets.bind_groups[history], &[]);
+        rpass.set_bind_group(1, &textures.bind_group, &[]);
+        rpass.set_bind_group(2, &materials.bind_group, &[]);
+        rpass.set_bind_group(3, &lights.point_bind_group, &[]);
+        rpass.set_bind_group(4, &meshes.trace_bind_group, &[]);
+        rpass.set_bind_group(5, &meshes.attributes_bind_group, &[]);
+        rpass.set_bind_group(6, &lights.sun_bind_group, &[]);
+        rpass.set_bind_group(7, &lights.area_bind_group, &[]);
+        rpass.draw(0..3, 0..1);
+    }
+}
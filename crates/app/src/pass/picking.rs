@@ -0,0 +1,194 @@
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+    sync::{
+        atomic::{AtomicU8, Ordering},
+        Arc,
+    },
+};
+
+use components::{world::World, InstanceId};
+use glam::Vec2;
+
+use crate::{GBuffer, InstancePool, ProfilerCommandEncoder};
+
+use super::Pass;
+
+const IDLE: u8 = 0;
+const COPIED: u8 = 1;
+const MAPPING: u8 = 2;
+const MAPPED: u8 = 3;
+
+// Texels are copied at offsets aligned for buffer copies.
+const SLOT_SIZE: u64 = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT as u64;
+
+type PickCallback = Box<dyn FnOnce(Option<InstanceId>)>;
+
+/// Result of a [`Picking::pick`], filled in once the readback landed a few frames later.
+#[derive(Debug, Clone, Default)]
+pub struct PickHandle(Rc<Cell<Option<Option<InstanceId>>>>);
+
+impl PickHandle {
+    /// `None` while the readback is in flight, `Some(None)` when the pick hit nothing.
+    pub fn poll(&self) -> Option<Option<InstanceId>> {
+        self.0.get()
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.0.get().is_some()
+    }
+}
+
+/// Reads back the instances under pixels of [`GBuffer::instance_ids`].
+///
+/// Held by the world and recorded by the app once the example rendered the frame, picks
+/// resolve in the order they were asked for. Up to [`Picking::MAX_PICKS`] pixels share a
+/// readback, the rest wait for the next one.
+pub struct Picking {
+    staging: wgpu::Buffer,
+    readback: Arc<AtomicU8>,
+    pending: RefCell<Vec<(Vec2, PickCallback)>>,
+    // Callbacks of the texels in the staging buffer, in slot order.
+    in_flight: RefCell<Vec<PickCallback>>,
+}
+
+impl Picking {
+    pub const MAX_PICKS: usize = 8;
+
+    pub fn new(device: &wgpu::Device) -> Self {
+        let staging = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Picking Staging"),
+            size: SLOT_SIZE * Self::MAX_PICKS as u64,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        Self {
+            staging,
+            readback: Arc::new(AtomicU8::new(IDLE)),
+            pending: RefCell::new(vec![]),
+            in_flight: RefCell::new(vec![]),
+        }
+    }
+
+    /// Picks the instance under a pixel of the G-buffer, in physical pixels from the top
+    /// left corner.
+    pub fn pick(&self, screen_pos: Vec2) -> PickHandle {
+        let handle = PickHandle::default();
+        let result = handle.0.clone();
+        self.pick_with(screen_pos, move |picked| result.set(Some(picked)));
+        handle
+    }
+
+    /// Same as [`Picking::pick`], calling back with the result instead.
+    pub fn pick_with(&self, screen_pos: Vec2, callback: impl FnOnce(Option<InstanceId>) + 'static) {
+        self.pending
+            .borrow_mut()
+            .push((screen_pos, Box::new(callback)));
+    }
+
+    // Drives the readback one step, returns `true` once the staging buffer is free again.
+    fn poll_readback(&self, instances: &InstancePool) -> bool {
+        match self.readback.load(Ordering::Acquire) {
+            COPIED => {
+                // The copies were submitted with the previous frame, safe to map now.
+                self.readback.store(MAPPING, Ordering::Release);
+                let readback = self.readback.clone();
+                self.staging
+                    .slice(..)
+                    .map_async(wgpu::MapMode::Read, move |res| match res {
+                        Ok(()) => readback.store(MAPPED, Ordering::Release),
+                        Err(err) => {
+                            log::error!("Failed to map picked instances: {err}");
+                            readback.store(COPIED, Ordering::Release);
+                        }
+                    });
+                false
+            }
+            MAPPING => false,
+            MAPPED => {
+                let ids: Vec<u32> = {
+                    let mapped = self.staging.slice(..).get_mapped_range();
+                    let stride = SLOT_SIZE as usize / std::mem::size_of::<u32>();
+                    bytemuck::cast_slice::<_, u32>(&mapped)
+                        .iter()
+                        .step_by(stride)
+                        .copied()
+                        .collect()
+                };
+                self.staging.unmap();
+                self.readback.store(IDLE, Ordering::Release);
+                for (callback, id) in self.in_flight.take().into_iter().zip(ids) {
+                    // Slots freed since the frame was drawn pick nothing.
+                    let picked = id.checked_sub(1).and_then(|index| instances.id(index));
+                    callback(picked);
+                }
+                true
+            }
+            _ => true,
+        }
+    }
+}
+
+pub struct PickingResource<'a> {
+    pub gbuffer: &'a GBuffer,
+}
+
+impl Pass for Picking {
+    type Resources<'a> = PickingResource<'a>;
+
+    fn record(
+        &self,
+        world: &World,
+        encoder: &mut ProfilerCommandEncoder,
+        resources: Self::Resources<'_>,
+    ) {
+        if !self.poll_readback(&world.unwrap::<InstancePool>()) {
+            return;
+        }
+
+        let (width, height) = resources.gbuffer.size();
+        let picks: Vec<_> = {
+            let mut pending = self.pending.borrow_mut();
+            let count = pending.len().min(Self::MAX_PICKS);
+            pending.drain(..count).collect()
+        };
+        // Callbacks may pick again, nothing stays borrowed while they run.
+        let mut in_flight = vec![];
+        for (pos, callback) in picks {
+            if pos.x < 0. || pos.y < 0. || pos.x >= width as f32 || pos.y >= height as f32 {
+                callback(None);
+                continue;
+            }
+            encoder.copy_texture_to_buffer(
+                wgpu::ImageCopyTexture {
+                    texture: resources.gbuffer.instance_ids_texture(),
+                    mip_level: 0,
+                    origin: wgpu::Origin3d {
+                        x: pos.x as u32,
+                        y: pos.y as u32,
+                        z: 0,
+                    },
+                    aspect: wgpu::TextureAspect::All,
+                },
+                wgpu::ImageCopyBuffer {
+                    buffer: &self.staging,
+                    layout: wgpu::ImageDataLayout {
+                        offset: SLOT_SIZE * in_flight.len() as u64,
+                        bytes_per_row: None,
+                        rows_per_image: None,
+                    },
+                },
+                wgpu::Extent3d {
+                    width: 1,
+                    height: 1,
+                    depth_or_array_layers: 1,
+                },
+            );
+            in_flight.push(callback);
+        }
+        if !in_flight.is_empty() {
+            *self.in_flight.borrow_mut() = in_flight;
+            self.readback.store(COPIED, Ordering::Release);
+        }
+    }
+}
@@ -1,17 +1,290 @@
 use crate::{
     pipeline::{PipelineArena, RenderHandle, RenderPipelineDescriptor},
-    GlobalUniformBinding, ProfilerCommandEncoder, ViewTarget, WrappedBindGroupLayout,
-    DEFAULT_SAMPLER_DESC,
+    GBuffer, GlobalUniformBinding, GlobalsBindGroup, MaterialPool, ProfilerCommandEncoder,
+    TexturePool, ViewTarget, WrappedBindGroupLayout, DEFAULT_SAMPLER_DESC,
 };
+use bytemuck::{Pod, Zeroable};
 use color_eyre::Result;
-use components::{bind_group_layout::SingleTextureBindGroupLayout, world::World};
-use std::path::Path;
+use components::{bind_group_layout::SingleTextureBindGroupLayout, world::World, NonZeroSized};
+use glam::{vec2, vec3, Mat3, UVec2, Vec2, Vec3};
+use half::f16;
+use std::{
+    cell::Cell,
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+};
+use wgpu::util::DeviceExt;
 
 use super::Pass;
 
+/// CIE 1931 chromaticity of the D65 white point.
+const D65_WHITE: Vec2 = vec2(0.31271, 0.32902);
+
+const LINEAR_TO_LMS: Mat3 = Mat3::from_cols(
+    vec3(3.90405e-1, 7.08416e-2, 2.31082e-2),
+    vec3(5.49941e-1, 9.63172e-1, 1.28021e-1),
+    vec3(8.92632e-3, 1.35775e-3, 9.36245e-1),
+);
+
+const LMS_TO_LINEAR: Mat3 = Mat3::from_cols(
+    vec3(2.85847e+0, -2.10182e-1, -4.18120e-2),
+    vec3(-1.62879e+0, 1.15820e+0, -1.18169e-1),
+    vec3(-2.48910e-2, 3.24281e-4, 1.06867e+0),
+);
+
+const LINEAR_TO_XYZ: Mat3 = Mat3::from_cols(
+    vec3(0.4124, 0.2126, 0.0193),
+    vec3(0.3576, 0.7152, 0.1192),
+    vec3(0.1805, 0.0722, 0.9505),
+);
+
+/// White balance as a shift of the assumed scene illuminant away from D65.
+///
+/// Both values are in `[-1, 1]`. Positive temperature warms the image up,
+/// positive tint pushes it towards magenta.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct WhiteBalance {
+    pub temperature: f32,
+    pub tint: f32,
+}
+
+impl WhiteBalance {
+    /// Returns the white balance which maps `color` (linear rgb) to gray.
+    pub fn neutralize(color: Vec3) -> Self {
+        let xyz = LINEAR_TO_XYZ * color.max(Vec3::ZERO);
+        let sum = xyz.x + xyz.y + xyz.z;
+        if sum <= 1e-6 {
+            return Self::default();
+        }
+        let (x, y) = (xyz.x / sum, xyz.y / sum);
+
+        let temperature = if x > D65_WHITE.x {
+            (D65_WHITE.x - x) / 0.1
+        } else {
+            (D65_WHITE.x - x) / 0.05
+        };
+        let tint = (y - standard_illuminant_y(x)) / 0.05;
+
+        Self {
+            temperature: temperature.clamp(-1., 1.),
+            tint: tint.clamp(-1., 1.),
+        }
+    }
+
+    /// Chromaticity of the illuminant which gets mapped to white.
+    pub fn white_point(&self) -> Vec2 {
+        let t1 = self.temperature;
+        let x = D65_WHITE.x - t1 * if t1 < 0. { 0.1 } else { 0.05 };
+        let y = standard_illuminant_y(x) + self.tint * 0.05;
+        vec2(x, y)
+    }
+
+    /// Von Kries adaptation from the white point to D65 in linear rgb.
+    pub fn matrix(&self) -> Mat3 {
+        let balance = xy_to_lms(D65_WHITE) / xy_to_lms(self.white_point());
+        LMS_TO_LINEAR * Mat3::from_diagonal(balance) * LINEAR_TO_LMS
+    }
+}
+
+fn standard_illuminant_y(x: f32) -> f32 {
+    2.87 * x - 3. * x * x - 0.275_095_07
+}
+
+fn xy_to_lms(xy: Vec2) -> Vec3 {
+    let xyz = vec3(xy.x / xy.y, 1., (1. - xy.x - xy.y) / xy.y);
+    Mat3::from_cols(
+        vec3(0.7328, -0.7036, 0.0030),
+        vec3(0.4296, 1.6975, 0.0136),
+        vec3(-0.1624, 0.0061, 0.9834),
+    ) * xyz
+}
+
+/// G-buffer channel drawn in place of the post processed image.
+///
+/// A world resource, picked from the ui or cycled with F5. Shown raw, without tone mapping
+/// or white balance. Motion vectors only hold the camera motion, as the ones TAA gets.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DebugView {
+    #[default]
+    Off,
+    Albedo,
+    Normals,
+    /// Roughness in red, metallic in green.
+    RoughnessMetallic,
+    Depth,
+    MotionVectors,
+    /// Random color per instance.
+    InstanceIds,
+    /// Fragments drawn per pixel by the geometry pass, drawn again while this is on.
+    Overdraw,
+}
+
+impl DebugView {
+    pub const ALL: [Self; 8] = [
+        Self::Off,
+        Self::Albedo,
+        Self::Normals,
+        Self::RoughnessMetallic,
+        Self::Depth,
+        Self::MotionVectors,
+        Self::InstanceIds,
+        Self::Overdraw,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Off => "Off",
+            Self::Albedo => "Albedo",
+            Self::Normals => "Normals",
+            Self::RoughnessMetallic => "Roughness / Metallic",
+            Self::Depth => "Depth",
+            Self::MotionVectors => "Motion Vectors",
+            Self::InstanceIds => "Instance Ids",
+            Self::Overdraw => "Overdraw",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        Self::ALL[(self as usize + 1) % Self::ALL.len()]
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+struct DebugViewUniform {
+    view: u32,
+    junk: [u32; 3],
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+struct PostProcessUniform {
+    white_balance: [[f32; 4]; 3],
+}
+
+impl From<WhiteBalance> for PostProcessUniform {
+    fn from(white_balance: WhiteBalance) -> Self {
+        let m = white_balance.matrix();
+        Self {
+            white_balance: [
+                m.x_axis.extend(0.).to_array(),
+                m.y_axis.extend(0.).to_array(),
+                m.z_axis.extend(0.).to_array(),
+            ],
+        }
+    }
+}
+
+/// Reads back a single pixel of the post process source to pick a neutral color.
+///
+/// The copy is recorded together with the pass and mapped on the next
+/// `record` once the previous frame has been submitted.
+struct NeutralPicker {
+    readback: Arc<wgpu::Buffer>,
+    request: Cell<Option<UVec2>>,
+    copied: Cell<bool>,
+    busy: Arc<AtomicBool>,
+    picked: Arc<Mutex<Option<Vec3>>>,
+}
+
+impl NeutralPicker {
+    const TEXEL_SIZE: u64 = 8;
+
+    fn new(device: &wgpu::Device) -> Self {
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Neutral Picker Readback Buffer"),
+            size: Self::TEXEL_SIZE,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        Self {
+            readback: Arc::new(readback),
+            request: Cell::new(None),
+            copied: Cell::new(false),
+            busy: Arc::new(AtomicBool::new(false)),
+            picked: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    fn map_copied(&self) {
+        if !self.copied.take() {
+            return;
+        }
+        let buffer = self.readback.clone();
+        let busy = self.busy.clone();
+        let picked = self.picked.clone();
+        self.readback
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |res| {
+                if let Err(err) = res {
+                    log::error!("Failed to map neutral picker buffer: {err}");
+                } else {
+                    let texel: [f16; 4] =
+                        bytemuck::pod_read_unaligned(&buffer.slice(..).get_mapped_range());
+                    let color = vec3(texel[0].to_f32(), texel[1].to_f32(), texel[2].to_f32());
+                    *picked.lock().unwrap() = Some(color);
+                    buffer.unmap();
+                }
+                busy.store(false, Ordering::Release);
+            });
+    }
+
+    fn copy_requested(&self, encoder: &mut wgpu::CommandEncoder, texture: &wgpu::Texture) {
+        let Some(pixel) = self.request.get() else {
+            return;
+        };
+        if self.busy.swap(true, Ordering::Acquire) {
+            return;
+        }
+        self.request.set(None);
+
+        let pixel = pixel.min(UVec2::new(texture.width(), texture.height()) - 1);
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d {
+                    x: pixel.x,
+                    y: pixel.y,
+                    z: 0,
+                },
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: &self.readback,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: None,
+                    rows_per_image: None,
+                },
+            },
+            wgpu::Extent3d {
+                width: 1,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+        );
+        self.copied.set(true);
+    }
+}
+
 pub struct PostProcess {
     pipeline: RenderHandle,
     sampler: wgpu::BindGroup,
+
+    white_balance: WhiteBalance,
+    uniform: wgpu::Buffer,
+    uniform_bind_group: wgpu::BindGroup,
+    neutral_picker: NeutralPicker,
+
+    // Takes the place of `pipeline` while a `DebugView` is on.
+    debug_pipeline: RenderHandle,
+    debug_uniform: wgpu::Buffer,
+    debug_bind_group: wgpu::BindGroup,
+    debug_view: Cell<DebugView>,
 }
 
 impl PostProcess {
@@ -45,23 +318,162 @@ impl PostProcess {
                 }],
             });
 
+        let white_balance = WhiteBalance::default();
+        let uniform = world
+            .device()
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Post Process Uniform"),
+                contents: bytemuck::bytes_of(&PostProcessUniform::from(white_balance)),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            });
+        let uniform_bind_group_layout =
+            world
+                .device()
+                .create_bind_group_layout_wrap(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some("Post Process Uniform Bind Group Layout"),
+                    entries: &[wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: Some(PostProcessUniform::NSIZE),
+                        },
+                        count: None,
+                    }],
+                });
+        let uniform_bind_group = world
+            .device()
+            .create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Post Process Uniform Bind Group"),
+                layout: &uniform_bind_group_layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform.as_entire_binding(),
+                }],
+            });
+
         let desc = RenderPipelineDescriptor {
             label: Some("Post Process Pipeline".into()),
             layout: vec![
                 global_ubo.layout.clone(),
                 texture_bind_group_layout.layout.clone(),
                 sampler_bind_group_layout,
+                uniform_bind_group_layout,
             ],
             depth_stencil: None,
             ..Default::default()
         };
         let pipeline = pipeline_arena.process_render_pipeline_from_path(path, desc)?;
-        Ok(Self { pipeline, sampler })
+
+        let debug_uniform = world
+            .device()
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Debug View Uniform"),
+                contents: bytemuck::bytes_of(&DebugViewUniform {
+                    view: DebugView::Off as u32,
+                    junk: [0; 3],
+                }),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            });
+        let debug_bind_group_layout =
+            world
+                .device()
+                .create_bind_group_layout_wrap(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some("Debug View Bind Group Layout"),
+                    entries: &[wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: Some(DebugViewUniform::NSIZE),
+                        },
+                        count: None,
+                    }],
+                });
+        let debug_bind_group = world
+            .device()
+            .create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Debug View Bind Group"),
+                layout: &debug_bind_group_layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: debug_uniform.as_entire_binding(),
+                }],
+            });
+        let gbuffer_layout = world
+            .device()
+            .create_bind_group_layout_wrap(&GBuffer::LAYOUT_DESC);
+        let debug_desc = RenderPipelineDescriptor {
+            label: Some("Debug View Pipeline".into()),
+            layout: vec![
+                world.get::<GlobalsBindGroup>()?.layout.clone(),
+                gbuffer_layout,
+                world.get::<TexturePool>()?.bind_group_layout.clone(),
+                world.get::<MaterialPool>()?.bind_group_layout.clone(),
+                debug_bind_group_layout,
+            ],
+            depth_stencil: None,
+            ..Default::default()
+        };
+        let debug_pipeline = pipeline_arena.process_render_pipeline_from_path(
+            Path::new("shaders").join("debug_view.wgsl"),
+            debug_desc,
+        )?;
+
+        Ok(Self {
+            pipeline,
+            sampler,
+
+            white_balance,
+            uniform,
+            uniform_bind_group,
+            neutral_picker: NeutralPicker::new(world.device()),
+
+            debug_pipeline,
+            debug_uniform,
+            debug_bind_group,
+            debug_view: Cell::new(DebugView::Off),
+        })
+    }
+
+    pub fn white_balance(&self) -> WhiteBalance {
+        self.white_balance
+    }
+
+    pub fn set_white_balance(&mut self, queue: &wgpu::Queue, white_balance: WhiteBalance) {
+        self.white_balance = white_balance;
+        queue.write_buffer(
+            &self.uniform,
+            0,
+            bytemuck::bytes_of(&PostProcessUniform::from(white_balance)),
+        );
+    }
+
+    /// Requests a readback of `pixel` on the next `record`. Once the color
+    /// arrives `poll_neutral_pick` balances it to gray.
+    pub fn pick_neutral(&self, pixel: UVec2) {
+        self.neutral_picker.request.set(Some(pixel));
+    }
+
+    /// Applies the last picked neutral color, returns `true` if white balance changed.
+    pub fn poll_neutral_pick(&mut self, queue: &wgpu::Queue) -> bool {
+        let picked = self.neutral_picker.picked.lock().unwrap().take();
+        match picked {
+            Some(color) => {
+                self.set_white_balance(queue, WhiteBalance::neutralize(color));
+                true
+            }
+            None => false,
+        }
     }
 }
 
 pub struct PostProcessResource<'a> {
     pub view_target: &'a ViewTarget,
+    /// Read by the debug views.
+    pub gbuffer: &'a GBuffer,
 }
 
 impl Pass for PostProcess {
@@ -74,9 +486,25 @@ impl Pass for PostProcess {
         resource: Self::Resources<'_>,
     ) {
         let global_ubo = world.unwrap::<GlobalUniformBinding>();
-        let post_process_target = resource.view_target.post_process_write();
         let arena = world.unwrap::<PipelineArena>();
 
+        let debug_view = *world.unwrap::<DebugView>();
+        if self.debug_view.get() != debug_view {
+            self.debug_view.set(debug_view);
+            let uniform = DebugViewUniform {
+                view: debug_view as u32,
+                junk: [0; 3],
+            };
+            world
+                .queue()
+                .write_buffer(&self.debug_uniform, 0, bytemuck::bytes_of(&uniform));
+        }
+
+        self.neutral_picker.map_copied();
+        self.neutral_picker
+            .copy_requested(encoder, resource.view_target.main_texture());
+
+        let post_process_target = resource.view_target.post_process_write();
         let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
             label: Some("Post Process Pass"),
             color_attachments: &[Some(post_process_target.get_color_attachment(
@@ -89,9 +517,21 @@ impl Pass for PostProcess {
             ))],
             depth_stencil_attachment: None,
         });
-        pass.set_bind_group(0, &global_ubo.binding, &[]);
+        if debug_view != DebugView::Off {
+            let globals = world.unwrap::<GlobalsBindGroup>();
+            pass.set_bind_group(0, globals.binding(), &[]);
+            pass.set_bind_group(1, &resource.gbuffer.bind_group, &[]);
+            pass.set_bind_group(2, &world.unwrap::<TexturePool>().bind_group, &[]);
+            pass.set_bind_group(3, &world.unwrap::<MaterialPool>().bind_group, &[]);
+            pass.set_bind_group(4, &self.debug_bind_group, &[]);
+            pass.set_pipeline(arena.get_pipeline(self.debug_pipeline));
+            pass.draw(0..3, 0..1);
+            return;
+        }
+        pass.set_bind_group(0, global_ubo.binding(), &[]);
         pass.set_bind_group(1, post_process_target.source_binding, &[]);
         pass.set_bind_group(2, &self.sampler, &[]);
+        pass.set_bind_group(3, &self.uniform_bind_group, &[]);
         pass.set_pipeline(arena.get_pipeline(self.pipeline));
         pass.draw(0..3, 0..1);
     }
@@ -0,0 +1,355 @@
+use std::path::Path;
+
+use bytemuck::{Pod, Zeroable};
+use color_eyre::Result;
+use components::{
+    bind_group_layout::{BindGroupLayout, SingleTextureBindGroupLayout, WrappedBindGroupLayout},
+    world::World,
+    NonZeroSized,
+};
+use wgpu::util::{align_to, DeviceExt};
+
+use crate::{
+    pipeline::{
+        self, ComputeHandle, ComputePipelineDescriptor, PipelineArena, RenderHandle,
+        RenderPipelineDescriptor,
+    },
+    GBuffer, GlobalsBindGroup, LightPool, MaterialPool, ProfilerCommandEncoder, TexturePool,
+    ViewTarget,
+};
+
+use super::Pass;
+
+/// Tunables of [`RestirDi`], `spatial_radius` is in pixels.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Pod, Zeroable)]
+pub struct RestirSettings {
+    /// Lights tried per pixel per frame.
+    pub candidates: u32,
+    /// Neighbours merged in the spatial pass.
+    pub spatial_samples: u32,
+    pub spatial_radius: f32,
+    /// Cap on the reused history in frames, 0 turns temporal reuse off.
+    pub max_history: f32,
+}
+
+impl Default for RestirSettings {
+    fn default() -> Self {
+        Self {
+            candidates: 32,
+            spatial_samples: 5,
+            spatial_radius: 30.,
+            max_history: 20.,
+        }
+    }
+}
+
+struct Targets {
+    bind_group: wgpu::BindGroup,
+    composite_bind_group: wgpu::BindGroup,
+}
+
+impl Targets {
+    /// Size of `Reservoir` in `shaders/restir_di.wgsl`.
+    const RESERVOIR_SIZE: u64 = 32;
+
+    fn new(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        layout: &wgpu::BindGroupLayout,
+        composite_layout: &wgpu::BindGroupLayout,
+        uniform: &wgpu::Buffer,
+    ) -> Self {
+        let [reservoirs, temporal] =
+            ["ReSTIR Reservoirs", "ReSTIR Temporal Reservoirs"].map(|label| {
+                device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some(label),
+                    size: width as u64 * height as u64 * Self::RESERVOIR_SIZE,
+                    usage: wgpu::BufferUsages::STORAGE,
+                    mapped_at_creation: false,
+                })
+            });
+        let lighting = device
+            .create_texture(&wgpu::TextureDescriptor {
+                label: Some("ReSTIR Lighting"),
+                size: wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: RestirDi::FORMAT,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::STORAGE_BINDING,
+                view_formats: &[],
+            })
+            .create_view(&Default::default());
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("ReSTIR BG"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: reservoirs.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: temporal.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(&lighting),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: uniform.as_entire_binding(),
+                },
+            ],
+        });
+        let composite_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("ReSTIR Composite BG"),
+            layout: composite_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&lighting),
+            }],
+        });
+
+        Self {
+            bind_group,
+            composite_bind_group,
+        }
+    }
+}
+
+/// Direct lighting from point and area lights with reservoir resampling.
+///
+/// Every pixel picks a light out of a handful of random candidates, reuses the picks of
+/// the previous frame and of its neighbours, and shades only the one it ends up with. Cost
+/// stays flat with the light count, the noise is what grows. The lighting is added on
+/// top of [`super::shading::ShadingPass::without_local_lights`].
+pub struct RestirDi {
+    initial_pipeline: ComputeHandle,
+    spatial_pipeline: ComputeHandle,
+    composite_pipeline: RenderHandle,
+
+    layout: BindGroupLayout,
+    composite_layout: BindGroupLayout,
+
+    settings: RestirSettings,
+    uniform: wgpu::Buffer,
+
+    targets: Targets,
+}
+
+impl RestirDi {
+    pub const FORMAT: wgpu::TextureFormat = ViewTarget::FORMAT;
+
+    pub fn new(world: &World, gbuffer: &GBuffer, width: u32, height: u32) -> Result<Self> {
+        let device = world.device();
+        let globals = world.get::<GlobalsBindGroup>()?;
+        let textures = world.get::<TexturePool>()?;
+        let materials = world.get::<MaterialPool>()?;
+        let lights = world.get::<LightPool>()?;
+        let composite_layout = world.get::<SingleTextureBindGroupLayout>()?.layout.clone();
+
+        let storage_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only: false },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let layout = device.create_bind_group_layout_wrap(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("ReSTIR BGL"),
+            entries: &[
+                storage_entry(0),
+                storage_entry(1),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::StorageTexture {
+                        access: wgpu::StorageTextureAccess::WriteOnly,
+                        format: Self::FORMAT,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: Some(RestirSettings::NSIZE),
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let path = Path::new("shaders").join("restir_di.wgsl");
+        let compute_layout = vec![
+            globals.layout.clone(),
+            gbuffer.bind_group_layout.clone(),
+            textures.bind_group_layout.clone(),
+            materials.bind_group_layout.clone(),
+            lights.point_bind_group_layout.clone(),
+            lights.area_bind_group_layout.clone(),
+            layout.clone(),
+        ];
+        let initial_desc = ComputePipelineDescriptor {
+            label: Some("ReSTIR Initial Pipeline".into()),
+            layout: compute_layout.clone(),
+            push_constant_ranges: vec![],
+            entry_point: "initial".into(),
+        };
+        let spatial_desc = ComputePipelineDescriptor {
+            label: Some("ReSTIR Spatial Pipeline".into()),
+            layout: compute_layout,
+            push_constant_ranges: vec![],
+            entry_point: "spatial".into(),
+        };
+        let composite_desc = RenderPipelineDescriptor {
+            label: Some("ReSTIR Composite Pipeline".into()),
+            layout: vec![composite_layout.clone()],
+            fragment: Some(pipeline::FragmentState {
+                entry_point: "fs_main".into(),
+                targets: vec![Some(wgpu::ColorTargetState {
+                    format: ViewTarget::FORMAT,
+                    blend: Some(wgpu::BlendState {
+                        color: wgpu::BlendComponent {
+                            src_factor: wgpu::BlendFactor::One,
+                            dst_factor: wgpu::BlendFactor::One,
+                            operation: wgpu::BlendOperation::Add,
+                        },
+                        alpha: wgpu::BlendComponent {
+                            src_factor: wgpu::BlendFactor::Zero,
+                            dst_factor: wgpu::BlendFactor::One,
+                            operation: wgpu::BlendOperation::Add,
+                        },
+                    }),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            depth_stencil: None,
+            ..Default::default()
+        };
+        let mut arena = world.get_mut::<PipelineArena>()?;
+        let initial_pipeline = arena.process_compute_pipeline_from_path(&path, initial_desc)?;
+        let spatial_pipeline = arena.process_compute_pipeline_from_path(&path, spatial_desc)?;
+        let composite_pipeline = arena.process_render_pipeline_from_path(
+            Path::new("shaders").join("restir_composite.wgsl"),
+            composite_desc,
+        )?;
+
+        let settings = RestirSettings::default();
+        let uniform = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("ReSTIR Uniform"),
+            contents: bytemuck::bytes_of(&settings),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let targets = Targets::new(device, width, height, &layout, &composite_layout, &uniform);
+
+        Ok(Self {
+            initial_pipeline,
+            spatial_pipeline,
+            composite_pipeline,
+
+            layout,
+            composite_layout,
+
+            settings,
+            uniform,
+
+            targets,
+        })
+    }
+
+    /// Drops the reservoirs, the first frame after starts without history.
+    pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        self.targets = Targets::new(
+            device,
+            width,
+            height,
+            &self.layout,
+            &self.composite_layout,
+            &self.uniform,
+        );
+    }
+
+    pub fn settings(&self) -> RestirSettings {
+        self.settings
+    }
+
+    pub fn set_settings(&mut self, queue: &wgpu::Queue, settings: RestirSettings) {
+        self.settings = settings;
+        queue.write_buffer(&self.uniform, 0, bytemuck::bytes_of(&settings));
+    }
+}
+
+pub struct RestirResource<'a> {
+    pub gbuffer: &'a GBuffer,
+    pub view_target: &'a ViewTarget,
+}
+
+impl Pass for RestirDi {
+    type Resources<'a> = RestirResource<'a>;
+
+    fn record(
+        &self,
+        world: &World,
+        encoder: &mut ProfilerCommandEncoder,
+        resources: Self::Resources<'_>,
+    ) {
+        let globals = world.unwrap::<GlobalsBindGroup>();
+        let textures = world.unwrap::<TexturePool>();
+        let materials = world.unwrap::<MaterialPool>();
+        let lights = world.unwrap::<LightPool>();
+        let arena = world.unwrap::<PipelineArena>();
+
+        encoder.profile_start("ReSTIR DI");
+        let (width, height) = resources.gbuffer.size();
+        let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("ReSTIR Pass"),
+        });
+        cpass.set_bind_group(0, globals.binding(), &[]);
+        cpass.set_bind_group(1, &resources.gbuffer.bind_group, &[]);
+        cpass.set_bind_group(2, &textures.bind_group, &[]);
+        cpass.set_bind_group(3, &materials.bind_group, &[]);
+        cpass.set_bind_group(4, &lights.point_bind_group, &[]);
+        cpass.set_bind_group(5, &lights.area_bind_group, &[]);
+        cpass.set_bind_group(6, &self.targets.bind_group, &[]);
+        // Spatial reuse reads the temporal reservoirs of other pixels, so it waits for
+        // the whole initial dispatch.
+        for pipeline in [self.initial_pipeline, self.spatial_pipeline] {
+            cpass.set_pipeline(arena.get_pipeline(pipeline));
+            cpass.dispatch_workgroups(align_to(width, 8) / 8, align_to(height, 8) / 8, 1);
+        }
+        drop(cpass);
+
+        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("ReSTIR Composite Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: resources.view_target.main_view(),
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+        rpass.set_pipeline(arena.get_pipeline(self.composite_pipeline));
+        rpass.set_bind_group(0, &self.targets.composite_bind_group, &[]);
+        rpass.draw(0..3, 0..1);
+        drop(rpass);
+        encoder.profile_end();
+    }
+}
@@ -2,27 +2,64 @@ use std::path::Path;
 
 use color_eyre::Result;
 use pools::MeshPool;
+use wgpu::util::align_to;
 
 use crate::{
-    pipeline::{PipelineArena, RenderHandle, RenderPipelineDescriptor},
+    pipeline::{
+        self, ComputeHandle, ComputePipelineDescriptor, PipelineArena, RenderHandle,
+        RenderPipelineDescriptor,
+    },
     GBuffer, GlobalsBindGroup, ProfilerCommandEncoder, ViewTarget,
     {LightPool, MaterialPool, TexturePool},
 };
 use components::world::World;
 
-use super::Pass;
+use super::{shading_rate::ShadingRate, Pass};
 
+/// Deferred shading of the gbuffer.
+///
+/// Point lights are first culled into the froxel grid of [`LightPool`], so every pixel
+/// only walks the lights that can reach its cluster. With a [`ShadingRate`] in the
+/// resources, the pixels it masked out are left for it to reconstruct.
 pub struct ShadingPass {
     pipeline: RenderHandle,
+    // Same, testing the stencil mask of `ShadingRate`.
+    coarse_pipeline: RenderHandle,
+    // None when local lights are left out.
+    cull_pipeline: Option<ComputeHandle>,
 }
 
 impl ShadingPass {
     pub fn new(shader: impl AsRef<Path>, world: &World, gbuffer: &GBuffer) -> Result<Self> {
+        Self::with_local_lights(shader, true, world, gbuffer)
+    }
+
+    /// Shades with the `fs_ambient` entry point, which skips point and area lights for
+    /// [`super::restir::RestirDi`] to add.
+    pub fn without_local_lights(
+        shader: impl AsRef<Path>,
+        world: &World,
+        gbuffer: &GBuffer,
+    ) -> Result<Self> {
+        Self::with_local_lights(shader, false, world, gbuffer)
+    }
+
+    fn with_local_lights(
+        shader: impl AsRef<Path>,
+        local_lights: bool,
+        world: &World,
+        gbuffer: &GBuffer,
+    ) -> Result<Self> {
         let globals = world.get::<GlobalsBindGroup>()?;
         let materials = world.get::<MaterialPool>()?;
         let textures = world.get::<TexturePool>()?;
         let lights = world.get::<LightPool>()?;
         let meshes = world.get::<MeshPool>()?;
+        let entry_point = if local_lights {
+            "fs_main"
+        } else {
+            "fs_ambient"
+        };
         let desc = RenderPipelineDescriptor {
             label: Some("Shading Pipeline".into()),
             layout: vec![
@@ -33,20 +70,52 @@ impl ShadingPass {
                 lights.point_bind_group_layout.clone(),
                 lights.area_bind_group_layout.clone(),
                 meshes.trace_bind_group_layout.clone(),
+                lights.sun_bind_group_layout.clone(),
             ],
+            fragment: Some(pipeline::FragmentState {
+                entry_point: entry_point.into(),
+                ..Default::default()
+            }),
             depth_stencil: None,
             ..Default::default()
         };
-        let pipeline = world
-            .get_mut::<PipelineArena>()?
-            .process_render_pipeline_from_path(shader, desc)?;
-        Ok(Self { pipeline })
+        let coarse_desc = RenderPipelineDescriptor {
+            label: Some("Coarse Shading Pipeline".into()),
+            depth_stencil: Some(ShadingRate::stencil_test()),
+            ..desc.clone()
+        };
+        let mut arena = world.get_mut::<PipelineArena>()?;
+        let pipeline = arena.process_render_pipeline_from_path(&shader, desc)?;
+        let coarse_pipeline = arena.process_render_pipeline_from_path(&shader, coarse_desc)?;
+        let cull_desc = ComputePipelineDescriptor {
+            label: Some("Light Cull Pipeline".into()),
+            layout: vec![
+                globals.layout.clone(),
+                lights.cluster_bind_group_layout.clone(),
+            ],
+            push_constant_ranges: vec![],
+            entry_point: "cull".into(),
+        };
+        let cull_pipeline = local_lights
+            .then(|| {
+                arena.process_compute_pipeline_from_path(
+                    Path::new("shaders").join("light_cull.wgsl"),
+                    cull_desc,
+                )
+            })
+            .transpose()?;
+        Ok(Self {
+            pipeline,
+            coarse_pipeline,
+            cull_pipeline,
+        })
     }
 }
 
 pub struct ShadingResource<'a> {
     pub gbuffer: &'a GBuffer,
     pub view_target: &'a ViewTarget,
+    pub shading_rate: Option<&'a ShadingRate>,
 }
 
 impl Pass for ShadingPass {
@@ -65,6 +134,17 @@ impl Pass for ShadingPass {
         let lights = world.unwrap::<LightPool>();
         let meshes = world.unwrap::<MeshPool>();
 
+        if let Some(cull_pipeline) = self.cull_pipeline {
+            let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Light Cull Pass"),
+            });
+            cpass.set_pipeline(arena.get_pipeline(cull_pipeline));
+            cpass.set_bind_group(0, globals.binding(), &[]);
+            cpass.set_bind_group(1, &lights.cluster_bind_group, &[]);
+            cpass.dispatch_workgroups(align_to(LightPool::cluster_count(), 64) / 64, 1, 1);
+        }
+
+        let shading_rate = resources.shading_rate.filter(|rate| rate.enabled());
         let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
             label: Some("Shading Pass"),
             color_attachments: &[Some(wgpu::RenderPassColorAttachment {
@@ -75,17 +155,22 @@ impl Pass for ShadingPass {
                     store: true,
                 },
             })],
-            depth_stencil_attachment: None,
+            depth_stencil_attachment: shading_rate.map(ShadingRate::stencil_attachment),
         });
 
-        rpass.set_pipeline(arena.get_pipeline(self.pipeline));
-        rpass.set_bind_group(0, &globals.binding, &[]);
+        let pipeline = match shading_rate {
+            Some(_) => self.coarse_pipeline,
+            None => self.pipeline,
+        };
+        rpass.set_pipeline(arena.get_pipeline(pipeline));
+        rpass.set_bind_group(0, globals.binding(), &[]);
         rpass.set_bind_group(1, &resources.gbuffer.bind_group, &[]);
         rpass.set_bind_group(2, &textures.bind_group, &[]);
         rpass.set_bind_group(3, &materials.bind_group, &[]);
         rpass.set_bind_group(4, &lights.point_bind_group, &[]);
         rpass.set_bind_group(5, &lights.area_bind_group, &[]);
         rpass.set_bind_group(6, &meshes.trace_bind_group, &[]);
+        rpass.set_bind_group(7, &lights.sun_bind_group, &[]);
 
         rpass.draw(0..3, 0..1);
     }
@@ -0,0 +1,449 @@
+use std::path::Path;
+
+use bytemuck::{Pod, Zeroable};
+use color_eyre::Result;
+use components::{
+    bind_group_layout::{BindGroupLayout, SingleTextureBindGroupLayout, WrappedBindGroupLayout},
+    world::World,
+    NonZeroSized,
+};
+use wgpu::util::{align_to, DeviceExt};
+
+use crate::{
+    pipeline::{
+        self, ComputeHandle, ComputePipelineDescriptor, PipelineArena, RenderHandle,
+        RenderPipelineDescriptor,
+    },
+    GBuffer, ProfilerCommandEncoder, ViewTarget,
+};
+
+use super::Pass;
+
+/// Tunables of [`ShadingRate`], how much a tile may vary and still be shaded coarsely.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Pod, Zeroable)]
+pub struct ShadingRateSettings {
+    /// Standard deviation of the luminance relative to its mean.
+    pub luma_threshold: f32,
+    /// One minus the length of the mean normal, zero for a flat tile.
+    pub normal_threshold: f32,
+}
+
+impl Default for ShadingRateSettings {
+    fn default() -> Self {
+        Self {
+            luma_threshold: 0.05,
+            normal_threshold: 0.01,
+        }
+    }
+}
+
+struct Targets {
+    color: wgpu::Texture,
+    stencil: wgpu::TextureView,
+    bind_group: wgpu::BindGroup,
+    // The reconstructed frame, read back by the next classification.
+    color_binding: wgpu::BindGroup,
+    output_bind_group: wgpu::BindGroup,
+    tiles: (u32, u32),
+}
+
+impl Targets {
+    fn new(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        layout: &wgpu::BindGroupLayout,
+        color_layout: &wgpu::BindGroupLayout,
+        output_layout: &wgpu::BindGroupLayout,
+        uniform: &wgpu::Buffer,
+    ) -> Self {
+        let tiles = (
+            align_to(width, ShadingRate::TILE_SIZE) / ShadingRate::TILE_SIZE,
+            align_to(height, ShadingRate::TILE_SIZE) / ShadingRate::TILE_SIZE,
+        );
+        let rates = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Shading Rates"),
+            size: (tiles.0 * tiles.1) as u64 * u32::SIZE as u64,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+        let mut desc = wgpu::TextureDescriptor {
+            label: Some("Shading Rate Color"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: ViewTarget::FORMAT,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::STORAGE_BINDING
+                | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        };
+        let color = device.create_texture(&desc);
+        let color_view = color.create_view(&Default::default());
+        desc.label = Some("Shading Rate Stencil");
+        desc.format = ShadingRate::STENCIL_FORMAT;
+        desc.usage = wgpu::TextureUsages::RENDER_ATTACHMENT;
+        let stencil = device
+            .create_texture(&desc)
+            .create_view(&Default::default());
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Shading Rate BG"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: rates.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: uniform.as_entire_binding(),
+                },
+            ],
+        });
+        let color_binding = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Shading Rate Color BG"),
+            layout: color_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&color_view),
+            }],
+        });
+        let output_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Shading Rate Output BG"),
+            layout: output_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&color_view),
+            }],
+        });
+
+        Self {
+            color,
+            stencil,
+            bind_group,
+            color_binding,
+            output_bind_group,
+            tiles,
+        }
+    }
+}
+
+/// Variable rate shading without hardware support.
+///
+/// Every [`Self::TILE_SIZE`] tile whose luminance in the last frame and normals in the
+/// gbuffer barely vary is shaded once per 2x2 quad. [`Pass::record`] classifies the tiles
+/// and writes the pixels to skip into a stencil mask, which the shading pass tests against
+/// when given this pass in [`super::shading::ShadingResource`]. [`Self::reconstruct`] then
+/// fills the skipped pixels in from the shaded ones around, depth and normal aware, so
+/// everything after the shading pass, TAA included, sees a full resolution frame.
+pub struct ShadingRate {
+    classify_pipeline: ComputeHandle,
+    mask_pipeline: RenderHandle,
+    reconstruct_pipeline: ComputeHandle,
+
+    layout: BindGroupLayout,
+    color_layout: BindGroupLayout,
+    output_layout: BindGroupLayout,
+
+    settings: ShadingRateSettings,
+    uniform: wgpu::Buffer,
+    enabled: bool,
+
+    targets: Targets,
+}
+
+impl ShadingRate {
+    /// Matches `TILE_SIZE` of `shaders/shading_rate.wgsl`.
+    pub const TILE_SIZE: u32 = 8;
+    pub const STENCIL_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Stencil8;
+    const STENCIL_SKIPPED: u32 = 1;
+
+    pub fn new(world: &World, gbuffer: &GBuffer, width: u32, height: u32) -> Result<Self> {
+        let device = world.device();
+        let color_layout = world.get::<SingleTextureBindGroupLayout>()?.layout.clone();
+
+        let layout = device.create_bind_group_layout_wrap(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Shading Rate BGL"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE.union(wgpu::ShaderStages::FRAGMENT),
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: Some(u32::NSIZE),
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: Some(ShadingRateSettings::NSIZE),
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let output_layout =
+            device.create_bind_group_layout_wrap(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Shading Rate Output BGL"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::StorageTexture {
+                        access: wgpu::StorageTextureAccess::WriteOnly,
+                        format: ViewTarget::FORMAT,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                    },
+                    count: None,
+                }],
+            });
+
+        let path = Path::new("shaders").join("shading_rate.wgsl");
+        let classify_desc = ComputePipelineDescriptor {
+            label: Some("Shading Rate Classify Pipeline".into()),
+            layout: vec![
+                gbuffer.bind_group_layout.clone(),
+                layout.clone(),
+                color_layout.clone(),
+            ],
+            push_constant_ranges: vec![],
+            entry_point: "classify".into(),
+        };
+        let mask_desc = RenderPipelineDescriptor {
+            label: Some("Shading Rate Mask Pipeline".into()),
+            layout: vec![gbuffer.bind_group_layout.clone(), layout.clone()],
+            fragment: Some(pipeline::FragmentState {
+                entry_point: "fs_mask".into(),
+                targets: vec![],
+            }),
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: Self::STENCIL_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::Always,
+                stencil: wgpu::StencilState {
+                    front: wgpu::StencilFaceState {
+                        compare: wgpu::CompareFunction::Always,
+                        pass_op: wgpu::StencilOperation::Replace,
+                        ..Default::default()
+                    },
+                    back: wgpu::StencilFaceState {
+                        compare: wgpu::CompareFunction::Always,
+                        pass_op: wgpu::StencilOperation::Replace,
+                        ..Default::default()
+                    },
+                    read_mask: !0,
+                    write_mask: !0,
+                },
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            ..Default::default()
+        };
+        let reconstruct_desc = ComputePipelineDescriptor {
+            label: Some("Shading Rate Reconstruct Pipeline".into()),
+            layout: vec![
+                gbuffer.bind_group_layout.clone(),
+                layout.clone(),
+                color_layout.clone(),
+                output_layout.clone(),
+            ],
+            push_constant_ranges: vec![],
+            entry_point: "reconstruct".into(),
+        };
+        let mut arena = world.get_mut::<PipelineArena>()?;
+        let classify_pipeline = arena.process_compute_pipeline_from_path(&path, classify_desc)?;
+        let mask_pipeline = arena.process_render_pipeline_from_path(&path, mask_desc)?;
+        let reconstruct_pipeline =
+            arena.process_compute_pipeline_from_path(&path, reconstruct_desc)?;
+
+        let settings = ShadingRateSettings::default();
+        let uniform = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Shading Rate Uniform"),
+            contents: bytemuck::bytes_of(&settings),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let targets = Targets::new(
+            device,
+            width,
+            height,
+            &layout,
+            &color_layout,
+            &output_layout,
+            &uniform,
+        );
+
+        Ok(Self {
+            classify_pipeline,
+            mask_pipeline,
+            reconstruct_pipeline,
+
+            layout,
+            color_layout,
+            output_layout,
+
+            settings,
+            uniform,
+            enabled: true,
+
+            targets,
+        })
+    }
+
+    /// Stencil state for passes that leave the skipped pixels alone, with the default
+    /// stencil reference of zero.
+    pub fn stencil_test() -> wgpu::DepthStencilState {
+        let face = wgpu::StencilFaceState {
+            compare: wgpu::CompareFunction::Equal,
+            ..Default::default()
+        };
+        wgpu::DepthStencilState {
+            format: Self::STENCIL_FORMAT,
+            depth_write_enabled: false,
+            depth_compare: wgpu::CompareFunction::Always,
+            stencil: wgpu::StencilState {
+                front: face,
+                back: face,
+                read_mask: !0,
+                write_mask: 0,
+            },
+            bias: wgpu::DepthBiasState::default(),
+        }
+    }
+
+    /// Attachment of the mask for pipelines with [`Self::stencil_test`].
+    pub fn stencil_attachment(&self) -> wgpu::RenderPassDepthStencilAttachment {
+        wgpu::RenderPassDepthStencilAttachment {
+            view: &self.targets.stencil,
+            depth_ops: None,
+            stencil_ops: Some(wgpu::Operations {
+                load: wgpu::LoadOp::Load,
+                store: false,
+            }),
+        }
+    }
+
+    /// The first frame after starts from black history and shades coarsely everywhere.
+    pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        self.targets = Targets::new(
+            device,
+            width,
+            height,
+            &self.layout,
+            &self.color_layout,
+            &self.output_layout,
+            &self.uniform,
+        );
+    }
+
+    pub fn settings(&self) -> ShadingRateSettings {
+        self.settings
+    }
+
+    pub fn set_settings(&mut self, queue: &wgpu::Queue, settings: ShadingRateSettings) {
+        self.settings = settings;
+        queue.write_buffer(&self.uniform, 0, bytemuck::bytes_of(&settings));
+    }
+
+    /// Disabled, nothing is recorded and the shading pass shades every pixel.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Fills in the pixels the shading pass skipped, right after it.
+    pub fn reconstruct(
+        &self,
+        world: &World,
+        encoder: &mut ProfilerCommandEncoder,
+        resources: ShadingRateResource<'_>,
+    ) {
+        if !self.enabled {
+            return;
+        }
+        let arena = world.unwrap::<PipelineArena>();
+        let (width, height) = resources.gbuffer.size();
+
+        let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Shading Rate Reconstruct Pass"),
+        });
+        cpass.set_pipeline(arena.get_pipeline(self.reconstruct_pipeline));
+        cpass.set_bind_group(0, &resources.gbuffer.bind_group, &[]);
+        cpass.set_bind_group(1, &self.targets.bind_group, &[]);
+        cpass.set_bind_group(2, resources.view_target.main_binding(), &[]);
+        cpass.set_bind_group(3, &self.targets.output_bind_group, &[]);
+        cpass.dispatch_workgroups(align_to(width, 8) / 8, align_to(height, 8) / 8, 1);
+        drop(cpass);
+
+        encoder.copy_texture_to_texture(
+            self.targets.color.as_image_copy(),
+            resources.view_target.main_texture().as_image_copy(),
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+}
+
+pub struct ShadingRateResource<'a> {
+    pub gbuffer: &'a GBuffer,
+    pub view_target: &'a ViewTarget,
+}
+
+impl Pass for ShadingRate {
+    type Resources<'a> = ShadingRateResource<'a>;
+
+    fn record(
+        &self,
+        world: &World,
+        encoder: &mut ProfilerCommandEncoder,
+        resources: Self::Resources<'_>,
+    ) {
+        if !self.enabled {
+            return;
+        }
+        let arena = world.unwrap::<PipelineArena>();
+
+        let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Shading Rate Classify Pass"),
+        });
+        cpass.set_pipeline(arena.get_pipeline(self.classify_pipeline));
+        cpass.set_bind_group(0, &resources.gbuffer.bind_group, &[]);
+        cpass.set_bind_group(1, &self.targets.bind_group, &[]);
+        cpass.set_bind_group(2, &self.targets.color_binding, &[]);
+        cpass.dispatch_workgroups(self.targets.tiles.0, self.targets.tiles.1, 1);
+        drop(cpass);
+
+        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Shading Rate Mask Pass"),
+            color_attachments: &[],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &self.targets.stencil,
+                depth_ops: None,
+                stencil_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(0),
+                    store: true,
+                }),
+            }),
+        });
+        rpass.set_pipeline(arena.get_pipeline(self.mask_pipeline));
+        rpass.set_bind_group(0, &resources.gbuffer.bind_group, &[]);
+        rpass.set_bind_group(1, &self.targets.bind_group, &[]);
+        rpass.set_stencil_reference(Self::STENCIL_SKIPPED);
+        rpass.draw(0..3, 0..1);
+    }
+}
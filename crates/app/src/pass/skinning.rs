@@ -0,0 +1,160 @@
+use std::{
+    cell::{Ref, RefCell},
+    path::Path,
+};
+
+use color_eyre::Result;
+use components::{
+    bind_group_layout::{BindGroupLayout, WrappedBindGroupLayout},
+    world::World,
+};
+use wgpu::util::align_to;
+
+use crate::{
+    pipeline::{ComputeHandle, ComputePipelineDescriptor, PipelineArena},
+    MeshPool, ProfilerCommandEncoder, SkinnedMeshPool,
+};
+
+use super::Pass;
+
+type TargetKey = [wgpu::Id<wgpu::Buffer>; 5];
+
+/// Poses the meshes of the [`SkinnedMeshPool`] into their copies in the [`MeshPool`].
+///
+/// Record it before the visibility pass so the frame is drawn with the current pose,
+/// a [`crate::PrePasses`] entry does that for every frame.
+/// The culling bounds of the posed copies are refit to the pose on the gpu, while
+/// mesh BVHs keep the rest pose, traced effects see skinned meshes undeformed.
+pub struct Skinning {
+    pipeline: ComputeHandle,
+    bounds_pipeline: ComputeHandle,
+    target_layout: BindGroupLayout,
+    // Keyed by the mesh pool attribute buffers, which are recreated when they grow.
+    target_bind_group: RefCell<Option<(TargetKey, wgpu::BindGroup)>>,
+}
+
+impl Skinning {
+    pub fn new(world: &World) -> Result<Self> {
+        let skinned = world.get::<SkinnedMeshPool>()?;
+        let entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only: false },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let target_layout =
+            world
+                .device()
+                .create_bind_group_layout_wrap(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some("Skinning Target BGL"),
+                    entries: &[entry(0), entry(1), entry(2), entry(3), entry(4)],
+                });
+
+        let path = Path::new("shaders").join("skinning.wgsl");
+        let desc = ComputePipelineDescriptor {
+            label: Some("Skinning Pipeline".into()),
+            layout: vec![skinned.bind_group_layout.clone(), target_layout.clone()],
+            ..Default::default()
+        };
+        let bounds_desc = ComputePipelineDescriptor {
+            label: Some("Skinned Bounds Pipeline".into()),
+            entry_point: "cs_bounds".into(),
+            ..desc.clone()
+        };
+        let mut arena = world.get_mut::<PipelineArena>()?;
+        let pipeline = arena.process_compute_pipeline_from_path(&path, desc)?;
+        let bounds_pipeline = arena.process_compute_pipeline_from_path(&path, bounds_desc)?;
+
+        Ok(Self {
+            pipeline,
+            bounds_pipeline,
+            target_layout,
+            target_bind_group: RefCell::new(None),
+        })
+    }
+
+    fn target_bind_group(
+        &self,
+        device: &wgpu::Device,
+        meshes: &MeshPool,
+        skinned: &SkinnedMeshPool,
+    ) -> Ref<'_, wgpu::BindGroup> {
+        let key = [
+            meshes.vertices.global_id(),
+            meshes.normals.global_id(),
+            meshes.tangents.global_id(),
+            meshes.mesh_info.global_id(),
+            skinned.bounds.global_id(),
+        ];
+        let mut cached = self.target_bind_group.borrow_mut();
+        if !cached.as_ref().is_some_and(|(id, _)| *id == key) {
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Skinning Target Bind Group"),
+                layout: &self.target_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: meshes.vertices.as_tight_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: meshes.normals.as_tight_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: meshes.tangents.as_tight_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: meshes.mesh_info.as_tight_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 4,
+                        resource: skinned.bounds.as_tight_binding(),
+                    },
+                ],
+            });
+            *cached = Some((key, bind_group));
+        }
+        drop(cached);
+        Ref::map(self.target_bind_group.borrow(), |cached| {
+            &cached.as_ref().unwrap().1
+        })
+    }
+}
+
+impl Pass for Skinning {
+    type Resources<'a> = ();
+
+    fn record(
+        &self,
+        world: &World,
+        encoder: &mut ProfilerCommandEncoder,
+        _resources: Self::Resources<'_>,
+    ) {
+        let skinned = world.unwrap::<SkinnedMeshPool>();
+        let vertex_count = skinned.vertex_count();
+        if vertex_count == 0 {
+            return;
+        }
+        let meshes = world.unwrap::<MeshPool>();
+        let arena = world.unwrap::<PipelineArena>();
+        let target_bind_group = self.target_bind_group(world.device(), &meshes, &skinned);
+
+        encoder.clear_buffer(&skinned.bounds, 0, None);
+        let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Skinning Pass"),
+        });
+        cpass.set_pipeline(arena.get_pipeline(self.pipeline));
+        cpass.set_bind_group(0, &skinned.bind_group, &[]);
+        cpass.set_bind_group(1, &target_bind_group, &[]);
+        cpass.dispatch_workgroups(align_to(vertex_count, 64) / 64, 1, 1);
+
+        cpass.set_pipeline(arena.get_pipeline(self.bounds_pipeline));
+        cpass.dispatch_workgroups(align_to(skinned.mesh_count(), 64) / 64, 1, 1);
+    }
+}
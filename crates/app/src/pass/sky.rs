@@ -0,0 +1,329 @@
+use std::path::Path;
+
+use bytemuck::{Pod, Zeroable};
+use color_eyre::{eyre::bail, Result};
+use components::{
+    bind_group_layout::{BindGroupLayout, WrappedBindGroupLayout},
+    world::World,
+    NonZeroSized,
+};
+use wgpu::util::DeviceExt;
+
+use crate::{
+    pipeline::{self, PipelineArena, RenderHandle, RenderPipelineDescriptor},
+    CameraUniformBinding, GBuffer, LightPool, ProfilerCommandEncoder, ViewTarget,
+    DEFAULT_SAMPLER_DESC,
+};
+
+use super::Pass;
+
+/// Runtime tunables of [`Sky`].
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Pod, Zeroable)]
+pub struct SkySettings {
+    /// Haziness of the procedural sky, 2 is a clear day and 10 a hazy one.
+    pub turbidity: f32,
+    /// Scales the sky radiance, the Preetham model is in kcd/m² and cubemaps are used as is.
+    pub exposure: f32,
+}
+
+impl Default for SkySettings {
+    fn default() -> Self {
+        Self {
+            turbidity: 3.,
+            exposure: 0.05,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+struct SkyUniform {
+    settings: SkySettings,
+    cubemap: u32,
+    _padding: u32,
+}
+
+/// Fills the background of the [`ViewTarget`] with a Preetham sky lit by the sun of the
+/// [`LightPool`], or with a cubemap once one is loaded.
+///
+/// Depth tested against the G-buffer, so only pixels left at the far plane are touched.
+/// Record it after shading.
+pub struct Sky {
+    pipeline: RenderHandle,
+    settings: SkySettings,
+    has_cubemap: bool,
+    uniform: wgpu::Buffer,
+    sampler: wgpu::Sampler,
+    cubemap: wgpu::TextureView,
+    bind_group_layout: BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+}
+
+impl Sky {
+    pub fn new(world: &World) -> Result<Self> {
+        let device = world.device();
+        let camera = world.get::<CameraUniformBinding>()?;
+        let lights = world.get::<LightPool>()?;
+        let bind_group_layout =
+            device.create_bind_group_layout_wrap(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Sky BGL"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: Some(SkyUniform::NSIZE),
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                            view_dimension: wgpu::TextureViewDimension::Cube,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                        count: None,
+                    },
+                ],
+            });
+
+        let settings = SkySettings::default();
+        let uniform = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Sky Settings"),
+            contents: bytemuck::bytes_of(&SkyUniform {
+                settings,
+                cubemap: 0,
+                _padding: 0,
+            }),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Sky Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            ..DEFAULT_SAMPLER_DESC
+        });
+        // Never sampled, stands in until a cubemap is loaded.
+        let cubemap = Self::create_cubemap(device, world.queue(), 1, &[&[0; 4]; 6]);
+        let bind_group =
+            Self::create_bind_group(device, &bind_group_layout, &uniform, &cubemap, &sampler);
+
+        let desc = RenderPipelineDescriptor {
+            label: Some("Sky Pipeline".into()),
+            layout: vec![
+                camera.bind_group_layout.clone(),
+                bind_group_layout.clone(),
+                lights.sun_bind_group_layout.clone(),
+            ],
+            fragment: Some(pipeline::FragmentState {
+                entry_point: "fs_main".into(),
+                targets: vec![Some(ViewTarget::FORMAT.into())],
+            }),
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: GBuffer::DEPTH_FORMAT,
+                depth_write_enabled: false,
+                // Reversed depth, the background stays at the cleared zero.
+                depth_compare: wgpu::CompareFunction::Equal,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            ..Default::default()
+        };
+        let pipeline = world
+            .get_mut::<PipelineArena>()?
+            .process_render_pipeline_from_path(Path::new("shaders").join("sky.wgsl"), desc)?;
+
+        Ok(Self {
+            pipeline,
+            settings,
+            has_cubemap: false,
+            uniform,
+            sampler,
+            cubemap,
+            bind_group_layout,
+            bind_group,
+        })
+    }
+
+    fn create_cubemap(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        size: u32,
+        faces: &[&[u8]; 6],
+    ) -> wgpu::TextureView {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Sky Cubemap"),
+            size: wgpu::Extent3d {
+                width: size,
+                height: size,
+                depth_or_array_layers: 6,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        for (layer, pixels) in faces.iter().enumerate() {
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    texture: &texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d {
+                        x: 0,
+                        y: 0,
+                        z: layer as u32,
+                    },
+                    aspect: wgpu::TextureAspect::All,
+                },
+                pixels,
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(4 * size),
+                    rows_per_image: None,
+                },
+                wgpu::Extent3d {
+                    width: size,
+                    height: size,
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+        texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::Cube),
+            ..Default::default()
+        })
+    }
+
+    fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        uniform: &wgpu::Buffer,
+        cubemap: &wgpu::TextureView,
+        sampler: &wgpu::Sampler,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Sky Bind Group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(cubemap),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+            ],
+        })
+    }
+
+    /// Replaces the procedural sky with six square images in +X, -X, +Y, -Y, +Z, -Z order.
+    pub fn load_cubemap(&mut self, world: &World, faces: [impl AsRef<Path>; 6]) -> Result<()> {
+        let mut size = None;
+        let mut pixels = vec![];
+        for face in &faces {
+            let image = image::open(face.as_ref())?.into_rgba8();
+            let (width, height) = image.dimensions();
+            if width != height || size.is_some_and(|size| size != width) {
+                bail!(
+                    "Cubemap faces have to be squares of the same size, {} is {width}x{height}",
+                    face.as_ref().display()
+                );
+            }
+            size = Some(width);
+            pixels.push(image.into_raw());
+        }
+        let faces: [&[u8]; 6] = std::array::from_fn(|i| pixels[i].as_slice());
+
+        self.cubemap =
+            Self::create_cubemap(world.device(), world.queue(), size.unwrap_or(1), &faces);
+        self.bind_group = Self::create_bind_group(
+            world.device(),
+            &self.bind_group_layout,
+            &self.uniform,
+            &self.cubemap,
+            &self.sampler,
+        );
+        self.has_cubemap = true;
+        self.upload(world.queue());
+        Ok(())
+    }
+
+    pub fn settings(&self) -> SkySettings {
+        self.settings
+    }
+
+    pub fn set_settings(&mut self, queue: &wgpu::Queue, settings: SkySettings) {
+        self.settings = settings;
+        self.upload(queue);
+    }
+
+    fn upload(&self, queue: &wgpu::Queue) {
+        let uniform = SkyUniform {
+            settings: self.settings,
+            cubemap: self.has_cubemap as u32,
+            _padding: 0,
+        };
+        queue.write_buffer(&self.uniform, 0, bytemuck::bytes_of(&uniform));
+    }
+}
+
+pub struct SkyResource<'a> {
+    pub gbuffer: &'a GBuffer,
+    pub view_target: &'a ViewTarget,
+}
+
+impl Pass for Sky {
+    type Resources<'a> = SkyResource<'a>;
+
+    fn record(
+        &self,
+        world: &World,
+        encoder: &mut ProfilerCommandEncoder,
+        resources: Self::Resources<'_>,
+    ) {
+        let arena = world.unwrap::<PipelineArena>();
+        let camera = world.unwrap::<CameraUniformBinding>();
+        let lights = world.unwrap::<LightPool>();
+
+        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Sky Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: resources.view_target.main_view(),
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &resources.gbuffer.depth,
+                depth_ops: None,
+                stencil_ops: None,
+            }),
+        });
+        rpass.set_pipeline(arena.get_pipeline(self.pipeline));
+        rpass.set_bind_group(0, camera.binding(), &[]);
+        rpass.set_bind_group(1, &self.bind_group, &[]);
+        rpass.set_bind_group(2, &lights.sun_bind_group, &[]);
+        rpass.draw(0..3, 0..1);
+    }
+}
@@ -0,0 +1,424 @@
+use std::{
+    path::Path,
+    sync::atomic::{AtomicU8, Ordering},
+};
+
+use bytemuck::{Pod, Zeroable};
+use color_eyre::Result;
+use components::{
+    bind_group_layout::{BindGroupLayout, WrappedBindGroupLayout},
+    world::World,
+    NonZeroSized,
+};
+use wgpu::util::{align_to, DeviceExt};
+
+use crate::{
+    pipeline::{
+        self, ComputeHandle, ComputePipelineDescriptor, PipelineArena, RenderHandle,
+        RenderPipelineDescriptor,
+    },
+    GBuffer, GlobalsBindGroup, MaterialPool, ProfilerCommandEncoder, TexturePool, ViewTarget,
+    DEFAULT_SAMPLER_DESC,
+};
+
+use super::{hiz::HiZ, Pass};
+
+/// Tunables of [`Ssgi`], `radius` and `thickness` are in world units.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Pod, Zeroable)]
+pub struct SsgiSettings {
+    pub intensity: f32,
+    /// How far rays march before giving up.
+    pub radius: f32,
+    /// Assumed depth of surfaces seen in the depth buffer.
+    pub thickness: f32,
+    /// Rays per pixel per frame.
+    pub ray_count: u32,
+}
+
+impl Default for SsgiSettings {
+    fn default() -> Self {
+        Self {
+            intensity: 1.,
+            radius: 5.,
+            thickness: 0.5,
+            ray_count: 2,
+        }
+    }
+}
+
+struct Targets {
+    radiance: wgpu::Texture,
+    // Per history index: trace reading it and writing the other one,
+    // composite reading the one the trace wrote.
+    trace_bind_groups: [wgpu::BindGroup; 2],
+    composite_bind_groups: [wgpu::BindGroup; 2],
+}
+
+impl Targets {
+    fn new(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        trace_layout: &wgpu::BindGroupLayout,
+        composite_layout: &wgpu::BindGroupLayout,
+        sampler: &wgpu::Sampler,
+        uniform: &wgpu::Buffer,
+    ) -> Self {
+        let mut desc = wgpu::TextureDescriptor {
+            label: Some("SSGI Radiance"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: Ssgi::FORMAT,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        };
+        let radiance = device.create_texture(&desc);
+        let radiance_view = radiance.create_view(&Default::default());
+
+        desc.usage = wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::STORAGE_BINDING;
+        let history: [wgpu::TextureView; 2] = std::array::from_fn(|i| {
+            desc.label = Some(["SSGI History 0", "SSGI History 1"][i]);
+            device
+                .create_texture(&desc)
+                .create_view(&Default::default())
+        });
+
+        let trace_bind_groups = std::array::from_fn(|i| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("SSGI Trace BG"),
+                layout: trace_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&radiance_view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::TextureView(&history[i]),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: wgpu::BindingResource::TextureView(&history[i ^ 1]),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: wgpu::BindingResource::Sampler(sampler),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 4,
+                        resource: uniform.as_entire_binding(),
+                    },
+                ],
+            })
+        });
+        let composite_bind_groups = std::array::from_fn(|i| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("SSGI Composite BG"),
+                layout: composite_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&history[i ^ 1]),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: uniform.as_entire_binding(),
+                    },
+                ],
+            })
+        });
+
+        Self {
+            radiance,
+            trace_bind_groups,
+            composite_bind_groups,
+        }
+    }
+}
+
+/// Screen space diffuse global illumination.
+///
+/// Traces cosine distributed rays through the [`HiZ`] pyramid, lights the hits with
+/// the previous frame and accumulates the result over time. The irradiance is then
+/// added on top of the shaded image, modulated by albedo, in place of the flat ambient.
+pub struct Ssgi {
+    trace_pipeline: ComputeHandle,
+    composite_pipeline: RenderHandle,
+
+    trace_layout: BindGroupLayout,
+    composite_layout: BindGroupLayout,
+    sampler: wgpu::Sampler,
+
+    settings: SsgiSettings,
+    uniform: wgpu::Buffer,
+    enabled: bool,
+
+    active_history: AtomicU8,
+    targets: Targets,
+}
+
+impl Ssgi {
+    pub const FORMAT: wgpu::TextureFormat = ViewTarget::FORMAT;
+
+    pub fn new(
+        world: &World,
+        gbuffer: &GBuffer,
+        hiz: &HiZ,
+        width: u32,
+        height: u32,
+    ) -> Result<Self> {
+        let device = world.device();
+        let globals = world.get::<GlobalsBindGroup>()?;
+        let textures = world.get::<TexturePool>()?;
+        let materials = world.get::<MaterialPool>()?;
+
+        let texture_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE.union(wgpu::ShaderStages::FRAGMENT),
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let uniform_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE.union(wgpu::ShaderStages::FRAGMENT),
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: Some(SsgiSettings::NSIZE),
+            },
+            count: None,
+        };
+        let trace_layout = device.create_bind_group_layout_wrap(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("SSGI Trace BGL"),
+            entries: &[
+                texture_entry(0),
+                texture_entry(1),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::StorageTexture {
+                        access: wgpu::StorageTextureAccess::WriteOnly,
+                        format: Self::FORMAT,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                uniform_entry(4),
+            ],
+        });
+        let composite_layout =
+            device.create_bind_group_layout_wrap(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("SSGI Composite BGL"),
+                entries: &[texture_entry(0), uniform_entry(1)],
+            });
+
+        let trace_desc = ComputePipelineDescriptor {
+            label: Some("SSGI Trace Pipeline".into()),
+            layout: vec![
+                globals.layout.clone(),
+                gbuffer.bind_group_layout.clone(),
+                hiz.bind_group_layout.clone(),
+                trace_layout.clone(),
+            ],
+            ..Default::default()
+        };
+        let composite_desc = RenderPipelineDescriptor {
+            label: Some("SSGI Composite Pipeline".into()),
+            layout: vec![
+                globals.layout.clone(),
+                gbuffer.bind_group_layout.clone(),
+                textures.bind_group_layout.clone(),
+                materials.bind_group_layout.clone(),
+                composite_layout.clone(),
+            ],
+            fragment: Some(pipeline::FragmentState {
+                entry_point: "fs_main".into(),
+                targets: vec![Some(wgpu::ColorTargetState {
+                    format: ViewTarget::FORMAT,
+                    blend: Some(wgpu::BlendState {
+                        color: wgpu::BlendComponent {
+                            src_factor: wgpu::BlendFactor::One,
+                            dst_factor: wgpu::BlendFactor::One,
+                            operation: wgpu::BlendOperation::Add,
+                        },
+                        alpha: wgpu::BlendComponent {
+                            src_factor: wgpu::BlendFactor::Zero,
+                            dst_factor: wgpu::BlendFactor::One,
+                            operation: wgpu::BlendOperation::Add,
+                        },
+                    }),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            depth_stencil: None,
+            ..Default::default()
+        };
+        let mut arena = world.get_mut::<PipelineArena>()?;
+        let trace_pipeline = arena.process_compute_pipeline_from_path(
+            Path::new("shaders").join("ssgi.wgsl"),
+            trace_desc,
+        )?;
+        let composite_pipeline = arena.process_render_pipeline_from_path(
+            Path::new("shaders").join("ssgi_composite.wgsl"),
+            composite_desc,
+        )?;
+
+        let settings = SsgiSettings::default();
+        let uniform = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("SSGI Uniform"),
+            contents: bytemuck::bytes_of(&settings),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("SSGI Sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..DEFAULT_SAMPLER_DESC
+        });
+        let targets = Targets::new(
+            device,
+            width,
+            height,
+            &trace_layout,
+            &composite_layout,
+            &sampler,
+            &uniform,
+        );
+
+        Ok(Self {
+            trace_pipeline,
+            composite_pipeline,
+
+            trace_layout,
+            composite_layout,
+            sampler,
+
+            settings,
+            uniform,
+            enabled: true,
+
+            active_history: AtomicU8::new(0),
+            targets,
+        })
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        self.targets = Targets::new(
+            device,
+            width,
+            height,
+            &self.trace_layout,
+            &self.composite_layout,
+            &self.sampler,
+            &self.uniform,
+        );
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn settings(&self) -> SsgiSettings {
+        self.settings
+    }
+
+    pub fn set_settings(&mut self, queue: &wgpu::Queue, settings: SsgiSettings) {
+        self.settings = settings;
+        queue.write_buffer(&self.uniform, 0, bytemuck::bytes_of(&settings));
+    }
+}
+
+pub struct SsgiResource<'a> {
+    pub gbuffer: &'a GBuffer,
+    pub hiz: &'a HiZ,
+    pub view_target: &'a ViewTarget,
+    pub width_height: (u32, u32),
+}
+
+impl Pass for Ssgi {
+    type Resources<'a> = SsgiResource<'a>;
+
+    fn record(
+        &self,
+        world: &World,
+        encoder: &mut ProfilerCommandEncoder,
+        resources: Self::Resources<'_>,
+    ) {
+        if !self.enabled {
+            return;
+        }
+        let history = self.active_history.fetch_xor(1, Ordering::Relaxed) as usize;
+
+        let globals = world.unwrap::<GlobalsBindGroup>();
+        let textures = world.unwrap::<TexturePool>();
+        let materials = world.unwrap::<MaterialPool>();
+        let arena = world.unwrap::<PipelineArena>();
+
+        encoder.profile_start("SSGI");
+        let (width, height) = resources.width_height;
+        let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("SSGI Trace Pass"),
+        });
+        cpass.set_pipeline(arena.get_pipeline(self.trace_pipeline));
+        cpass.set_bind_group(0, globals.binding(), &[]);
+        cpass.set_bind_group(1, &resources.gbuffer.bind_group, &[]);
+        cpass.set_bind_group(2, &resources.hiz.bind_group, &[]);
+        cpass.set_bind_group(3, &self.targets.trace_bind_groups[history], &[]);
+        cpass.dispatch_workgroups(align_to(width, 8) / 8, align_to(height, 8) / 8, 1);
+        drop(cpass);
+
+        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("SSGI Composite Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: resources.view_target.main_view(),
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+        rpass.set_pipeline(arena.get_pipeline(self.composite_pipeline));
+        rpass.set_bind_group(0, globals.binding(), &[]);
+        rpass.set_bind_group(1, &resources.gbuffer.bind_group, &[]);
+        rpass.set_bind_group(2, &textures.bind_group, &[]);
+        rpass.set_bind_group(3, &materials.bind_group, &[]);
+        rpass.set_bind_group(4, &self.targets.composite_bind_groups[history], &[]);
+        rpass.draw(0..3, 0..1);
+        drop(rpass);
+
+        // Lit hits of the next frame read this one, bounces included.
+        encoder.copy_texture_to_texture(
+            resources.view_target.main_texture().as_image_copy(),
+            self.targets.radiance.as_image_copy(),
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+        encoder.profile_end();
+    }
+}
@@ -0,0 +1,336 @@
+use std::{
+    cell::{Ref, RefCell},
+    path::Path,
+    sync::{
+        atomic::{AtomicU8, Ordering},
+        Arc,
+    },
+};
+
+use color_eyre::Result;
+use components::{
+    bind_group_layout::{BindGroupLayout, StorageWriteBindGroupLayout, WrappedBindGroupLayout},
+    world::World,
+    DrawIndexedIndirect, ResizableBuffer,
+};
+use wgpu::util::align_to;
+
+use crate::{
+    pipeline::{ComputeHandle, ComputePipelineDescriptor, PipelineArena},
+    GBuffer, InstancePool, ProfilerCommandEncoder,
+};
+
+use super::Pass;
+
+/// Counters gathered by [`SceneStats`], indexed by material or instance id.
+#[derive(Debug, Clone, Default)]
+pub struct SceneStatsReport {
+    pub material_pixels: Vec<u32>,
+    pub material_triangles: Vec<u32>,
+    /// Only filled with the visibility buffer on, the G-buffer keeps just the material.
+    pub instance_pixels: Vec<u32>,
+    pub instance_triangles: Vec<u32>,
+}
+
+const IDLE: u8 = 0;
+const COPIED: u8 = 1;
+const MAPPING: u8 = 2;
+const MAPPED: u8 = 3;
+
+struct Counters {
+    buffer: wgpu::Buffer,
+    staging: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    instance_count: u32,
+}
+
+impl Counters {
+    fn new(device: &wgpu::Device, layout: &wgpu::BindGroupLayout, instance_count: u32) -> Self {
+        let size = SceneStats::counter_count(instance_count) * 4;
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Scene Stats Counters"),
+            size,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let staging = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Scene Stats Staging"),
+            size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Scene Stats Counters Bind Group"),
+            layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+        });
+        Self {
+            buffer,
+            staging,
+            bind_group,
+            instance_count,
+        }
+    }
+}
+
+/// Counts rasterized pixels and submitted triangles per material and instance.
+///
+/// Counters are read back asynchronously, the report lags a few frames behind.
+pub struct SceneStats {
+    pixels_pipeline: ComputeHandle,
+    triangles_pipeline: ComputeHandle,
+    instance_pixels_pipeline: ComputeHandle,
+
+    counters_layout: BindGroupLayout,
+    ids_layout: BindGroupLayout,
+
+    enabled: bool,
+    readback: Arc<AtomicU8>,
+    counters: RefCell<Counters>,
+    // Keyed by the visibility buffer ids view, which is recreated on resize.
+    ids_bind_group: RefCell<Option<(wgpu::Id<wgpu::TextureView>, wgpu::BindGroup)>>,
+    report: RefCell<SceneStatsReport>,
+}
+
+impl SceneStats {
+    pub const MATERIAL_SLOTS: usize = 256;
+
+    pub fn new(world: &World, gbuffer: &GBuffer) -> Result<Self> {
+        let device = world.device();
+        let instances = world.get::<InstancePool>()?;
+        let draw_cmd_layout = world.get::<StorageWriteBindGroupLayout<DrawIndexedIndirect>>()?;
+
+        let counters_layout =
+            device.create_bind_group_layout_wrap(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Scene Stats Counters BGL"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+            });
+        let ids_layout = device.create_bind_group_layout_wrap(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Scene Stats Ids BGL"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Uint,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            }],
+        });
+
+        let pixels_desc = ComputePipelineDescriptor {
+            label: Some("Scene Stats Pixels Pipeline".into()),
+            layout: vec![counters_layout.clone(), gbuffer.bind_group_layout.clone()],
+            entry_point: "count_pixels".into(),
+            ..Default::default()
+        };
+        let triangles_layout = vec![
+            counters_layout.clone(),
+            gbuffer.bind_group_layout.clone(),
+            instances.bind_group_layout.clone(),
+            draw_cmd_layout.layout.clone(),
+        ];
+        let triangles_desc = ComputePipelineDescriptor {
+            label: Some("Scene Stats Triangles Pipeline".into()),
+            layout: triangles_layout.clone(),
+            entry_point: "count_triangles".into(),
+            ..Default::default()
+        };
+        let instance_pixels_desc = ComputePipelineDescriptor {
+            label: Some("Scene Stats Instance Pixels Pipeline".into()),
+            layout: [triangles_layout, vec![ids_layout.clone()]].concat(),
+            entry_point: "count_instance_pixels".into(),
+            ..Default::default()
+        };
+
+        let path = Path::new("shaders").join("scene_stats.wgsl");
+        let mut arena = world.get_mut::<PipelineArena>()?;
+        let pixels_pipeline = arena.process_compute_pipeline_from_path(&path, pixels_desc)?;
+        let triangles_pipeline = arena.process_compute_pipeline_from_path(&path, triangles_desc)?;
+        let instance_pixels_pipeline =
+            arena.process_compute_pipeline_from_path(&path, instance_pixels_desc)?;
+
+        let counters = Counters::new(device, &counters_layout, instances.count());
+
+        Ok(Self {
+            pixels_pipeline,
+            triangles_pipeline,
+            instance_pixels_pipeline,
+            counters_layout,
+            ids_layout,
+            enabled: false,
+            readback: Arc::new(AtomicU8::new(IDLE)),
+            counters: RefCell::new(counters),
+            ids_bind_group: RefCell::new(None),
+            report: RefCell::new(SceneStatsReport::default()),
+        })
+    }
+
+    fn counter_count(instance_count: u32) -> u64 {
+        (2 * Self::MATERIAL_SLOTS) as u64 + 2 * instance_count as u64
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Latest counters that made it back from the gpu.
+    pub fn report(&self) -> Ref<'_, SceneStatsReport> {
+        self.report.borrow()
+    }
+
+    // Drives the readback one step, returns `true` once the staging buffer is free again.
+    fn poll_readback(&self) -> bool {
+        let counters = self.counters.borrow();
+        match self.readback.load(Ordering::Acquire) {
+            COPIED => {
+                // The copy was submitted with the previous frame, safe to map now.
+                self.readback.store(MAPPING, Ordering::Release);
+                let readback = self.readback.clone();
+                counters
+                    .staging
+                    .slice(..)
+                    .map_async(wgpu::MapMode::Read, move |res| match res {
+                        Ok(()) => readback.store(MAPPED, Ordering::Release),
+                        Err(err) => {
+                            log::error!("Failed to map scene stats: {err}");
+                            readback.store(IDLE, Ordering::Release);
+                        }
+                    });
+                false
+            }
+            MAPPING => false,
+            MAPPED => {
+                {
+                    let mapped = counters.staging.slice(..).get_mapped_range();
+                    let values: &[u32] = bytemuck::cast_slice(&mapped);
+                    let (materials, instances) = values.split_at(2 * Self::MATERIAL_SLOTS);
+                    let (material_pixels, material_triangles) =
+                        materials.split_at(Self::MATERIAL_SLOTS);
+                    let (instance_pixels, instance_triangles) =
+                        instances.split_at(counters.instance_count as usize);
+                    let mut report = self.report.borrow_mut();
+                    report.material_pixels = material_pixels.to_vec();
+                    report.material_triangles = material_triangles.to_vec();
+                    report.instance_pixels = instance_pixels.to_vec();
+                    report.instance_triangles = instance_triangles.to_vec();
+                }
+                counters.staging.unmap();
+                self.readback.store(IDLE, Ordering::Release);
+                true
+            }
+            _ => true,
+        }
+    }
+
+    fn ids_bind_group(
+        &self,
+        device: &wgpu::Device,
+        ids: &wgpu::TextureView,
+    ) -> Ref<'_, wgpu::BindGroup> {
+        let key = ids.global_id();
+        let mut cached = self.ids_bind_group.borrow_mut();
+        if !cached.as_ref().is_some_and(|(id, _)| *id == key) {
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Scene Stats Ids Bind Group"),
+                layout: &self.ids_layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(ids),
+                }],
+            });
+            *cached = Some((key, bind_group));
+        }
+        drop(cached);
+        Ref::map(self.ids_bind_group.borrow(), |cached| {
+            &cached.as_ref().unwrap().1
+        })
+    }
+}
+
+pub struct SceneStatsResource<'a> {
+    pub gbuffer: &'a GBuffer,
+    pub draw_cmd_buffer: &'a ResizableBuffer<DrawIndexedIndirect>,
+    pub draw_cmd_bind_group: &'a wgpu::BindGroup,
+    /// Visibility buffer ids, see [`super::visibility::Visibility::vis_buffer_ids`].
+    pub ids: Option<&'a wgpu::TextureView>,
+}
+
+impl Pass for SceneStats {
+    type Resources<'a> = SceneStatsResource<'a>;
+
+    fn record(
+        &self,
+        world: &World,
+        encoder: &mut ProfilerCommandEncoder,
+        resources: Self::Resources<'_>,
+    ) {
+        if !self.enabled || !self.poll_readback() {
+            return;
+        }
+
+        let instances = world.unwrap::<InstancePool>();
+        let arena = world.unwrap::<PipelineArena>();
+        if self.counters.borrow().instance_count != instances.count() {
+            *self.counters.borrow_mut() =
+                Counters::new(world.device(), &self.counters_layout, instances.count());
+        }
+        let counters = self.counters.borrow();
+        let ids_bind_group = resources
+            .ids
+            .map(|ids| self.ids_bind_group(world.device(), ids));
+
+        encoder.profile_start("Scene Stats");
+        encoder.clear_buffer(&counters.buffer, 0, None);
+        {
+            let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Scene Stats Pass"),
+            });
+            cpass.set_bind_group(0, &counters.bind_group, &[]);
+            cpass.set_bind_group(1, &resources.gbuffer.bind_group, &[]);
+
+            let (width, height) = resources.gbuffer.size();
+            let (groups_x, groups_y) = (align_to(width, 8) / 8, align_to(height, 8) / 8);
+            cpass.set_pipeline(arena.get_pipeline(self.pixels_pipeline));
+            cpass.dispatch_workgroups(groups_x, groups_y, 1);
+
+            cpass.set_bind_group(2, &instances.bind_group, &[]);
+            cpass.set_bind_group(3, resources.draw_cmd_bind_group, &[]);
+            cpass.set_pipeline(arena.get_pipeline(self.triangles_pipeline));
+            let draw_count = resources.draw_cmd_buffer.len() as u32;
+            cpass.dispatch_workgroups(align_to(draw_count, 64) / 64, 1, 1);
+
+            if let Some(ids_bind_group) = ids_bind_group.as_deref() {
+                cpass.set_bind_group(4, ids_bind_group, &[]);
+                cpass.set_pipeline(arena.get_pipeline(self.instance_pixels_pipeline));
+                cpass.dispatch_workgroups(groups_x, groups_y, 1);
+            }
+        }
+        encoder.copy_buffer_to_buffer(
+            &counters.buffer,
+            0,
+            &counters.staging,
+            0,
+            counters.staging.size(),
+        );
+        encoder.profile_end();
+        self.readback.store(COPIED, Ordering::Release);
+    }
+}
@@ -7,17 +7,43 @@ use crate::{
     pipeline::{ComputeHandle, ComputePipelineDescriptor, PipelineArena},
     CameraUniformBinding, GBuffer, ProfilerCommandEncoder, ViewTarget, DEFAULT_SAMPLER_DESC,
 };
+use bytemuck::{Pod, Zeroable};
 use color_eyre::Result;
 use components::{
     bind_group_layout::{BindGroupLayout, SingleTextureBindGroupLayout, WrappedBindGroupLayout},
     world::World,
+    NonZeroSized,
 };
 use glam::{vec2, Vec2};
 use rand::{rngs::SmallRng, seq::SliceRandom, SeedableRng};
-use wgpu::util::align_to;
+use wgpu::util::{align_to, DeviceExt};
 
 use super::Pass;
 
+/// Runtime tunables of [`Taa`].
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Pod, Zeroable)]
+pub struct TaaSettings {
+    /// Weight of the current frame once history is trusted, lower is smoother but ghosts more.
+    pub blend: f32,
+    /// Size of the neighbourhood box history is clipped to, in standard deviations.
+    pub clamp_gamma: f32,
+    pub sharpness: f32,
+    /// How much history is favoured when luma barely changes, in `[0, 1]`.
+    pub anti_flicker: f32,
+}
+
+impl Default for TaaSettings {
+    fn default() -> Self {
+        Self {
+            blend: 1. / 12.,
+            clamp_gamma: 1.5,
+            sharpness: 0.2,
+            anti_flicker: 1.,
+        }
+    }
+}
+
 struct CombinedTexture {
     texture: wgpu::Texture,
     view: wgpu::TextureView,
@@ -106,6 +132,10 @@ pub struct Taa {
     taa_pipeline: ComputeHandle,
     sampler: wgpu::BindGroup,
 
+    settings: TaaSettings,
+    settings_buffer: wgpu::Buffer,
+    settings_bind_group: wgpu::BindGroup,
+
     jitter_samples: Vec<Vec2>,
 }
 
@@ -155,6 +185,35 @@ impl Taa {
                 }],
             });
 
+        let settings = TaaSettings::default();
+        let settings_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Taa Settings Buffer"),
+            contents: bytemuck::bytes_of(&settings),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let settings_layout =
+            device.create_bind_group_layout_wrap(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Taa Settings BGL"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: Some(TaaSettings::NSIZE),
+                    },
+                    count: None,
+                }],
+            });
+        let settings_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Taa Settings BG"),
+            layout: &settings_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: settings_buffer.as_entire_binding(),
+            }],
+        });
+
         let history_textures = std::array::from_fn(|i| {
             CombinedTexture::new(
                 device,
@@ -202,6 +261,7 @@ impl Taa {
                 read_texture_layout.clone(),
                 // Output Texture
                 write_texture_layout.clone(),
+                settings_layout,
             ],
             ..Default::default()
         };
@@ -249,6 +309,10 @@ impl Taa {
             taa_pipeline,
             sampler,
 
+            settings,
+            settings_buffer,
+            settings_bind_group,
+
             jitter_samples,
         })
     }
@@ -277,6 +341,15 @@ impl Taa {
         );
     }
 
+    pub fn settings(&self) -> TaaSettings {
+        self.settings
+    }
+
+    pub fn set_settings(&mut self, queue: &wgpu::Queue, settings: TaaSettings) {
+        self.settings = settings;
+        queue.write_buffer(&self.settings_buffer, 0, bytemuck::bytes_of(&settings));
+    }
+
     pub fn output_texture(&self) -> &wgpu::TextureView {
         &self.history[self.active_texture.load(Ordering::Relaxed) as usize].view
     }
@@ -329,7 +402,7 @@ impl Pass for Taa {
         });
 
         cpass.set_pipeline(arena.get_pipeline(self.reprojection_pipeline));
-        cpass.set_bind_group(0, &camera.binding, &[]);
+        cpass.set_bind_group(0, camera.binding(), &[]);
         cpass.set_bind_group(1, &resource.gbuffer.bind_group, &[]);
         cpass.set_bind_group(2, &self.motion_texture.storage_bind_group, &[]);
         cpass.dispatch_workgroups(x, y, 1);
@@ -345,6 +418,7 @@ impl Pass for Taa {
         cpass.set_bind_group(2, &self.history[input_history].sample_bind_group, &[]);
         cpass.set_bind_group(3, &self.motion_texture.sample_bind_group, &[]);
         cpass.set_bind_group(4, &self.history[output_history].storage_bind_group, &[]);
+        cpass.set_bind_group(5, &self.settings_bind_group, &[]);
         cpass.dispatch_workgroups(x, y, 1);
         drop(cpass);
 
@@ -0,0 +1,290 @@
+use std::{cell::RefCell, path::Path};
+
+use bvh::TlasNode;
+use color_eyre::{eyre::bail, Result};
+use components::{
+    bind_group_layout::{BindGroupLayout, WrappedBindGroupLayout},
+    world::World,
+    NonZeroSized,
+};
+use wgpu::util::{align_to, DeviceExt};
+
+use crate::{
+    pipeline::{ComputeHandle, ComputePipelineDescriptor, PipelineArena},
+    Instance, InstancePool, MeshInfo, MeshPool, ProfilerCommandEncoder,
+};
+
+use super::Pass;
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct BuildParams {
+    count: u32,
+    padded: u32,
+    k: u32,
+    j: u32,
+}
+
+/// Scratch buffers of one instance count, remade when it changes.
+struct Scratch {
+    count: u32,
+    padded: u32,
+    /// The params of the kernels, then one entry per sort stage.
+    stages: u32,
+    stride: u32,
+    params: wgpu::Buffer,
+    aabbs: wgpu::Buffer,
+    keys: wgpu::Buffer,
+    scene: wgpu::Buffer,
+}
+
+impl Scratch {
+    fn new(device: &wgpu::Device, count: u32) -> Self {
+        let padded = count.next_power_of_two();
+        let stride = align_to(
+            std::mem::size_of::<BuildParams>() as u32,
+            device.limits().min_uniform_buffer_offset_alignment,
+        );
+
+        let mut entries = vec![BuildParams {
+            count,
+            padded,
+            k: 0,
+            j: 0,
+        }];
+        let mut k = 2;
+        while k <= padded {
+            let mut j = k / 2;
+            while j > 0 {
+                entries.push(BuildParams {
+                    count,
+                    padded,
+                    k,
+                    j,
+                });
+                j /= 2;
+            }
+            k *= 2;
+        }
+        let mut bytes = vec![0u8; entries.len() * stride as usize];
+        for (entry, chunk) in entries.iter().zip(bytes.chunks_exact_mut(stride as usize)) {
+            chunk[..std::mem::size_of::<BuildParams>()].copy_from_slice(bytemuck::bytes_of(entry));
+        }
+        let params = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Tlas Build Params"),
+            contents: &bytes,
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+
+        let storage = |label, size| {
+            device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(label),
+                size,
+                usage: wgpu::BufferUsages::STORAGE,
+                mapped_at_creation: false,
+            })
+        };
+        Self {
+            count,
+            padded,
+            stages: entries.len() as u32 - 1,
+            stride,
+            params,
+            aabbs: storage("Tlas Build Aabbs", count as u64 * 32),
+            keys: storage("Tlas Build Keys", padded as u64 * 8),
+            scene: storage("Tlas Build Scene Bounds", 32),
+        }
+    }
+}
+
+/// Where [`TlasBuild`] writes the nodes, it has to hold
+/// [`TlasBuild::node_count`] of them.
+pub struct TlasBuildResources<'a> {
+    pub nodes: &'a wgpu::Buffer,
+}
+
+/// Builds the TLAS of the `InstancePool` on the gpu: morton codes of the instance
+/// centers, a bitonic sort and a linear BVH over the sorted codes (Karras 2012).
+///
+/// The nodes have the layout of `bvh::Tlas`, so `traverse_tlas` walks either. The trees
+/// are looser than the binned cpu ones, but rebuilding every frame costs no cpu time and
+/// no readback.
+pub struct TlasBuild {
+    layout: BindGroupLayout,
+    instance_bounds: ComputeHandle,
+    scene_bounds: ComputeHandle,
+    morton: ComputeHandle,
+    bitonic: ComputeHandle,
+    leaves: ComputeHandle,
+    internal: ComputeHandle,
+    scratch: RefCell<Option<Scratch>>,
+}
+
+impl TlasBuild {
+    /// Child indices are packed in 16 bits, which caps the node count.
+    pub const MAX_INSTANCES: u32 = 1 << 15;
+
+    /// Nodes written for `instances`, internal ones first and then the leaves.
+    pub fn node_count(instances: u32) -> Result<usize> {
+        if instances > Self::MAX_INSTANCES {
+            bail!(
+                "The gpu TLAS takes up to {} instances, got {instances}",
+                Self::MAX_INSTANCES
+            );
+        }
+        Ok((2 * instances as usize).saturating_sub(1))
+    }
+
+    pub fn new(world: &World) -> Result<Self> {
+        let path = Path::new("shaders").join("tlas_build.wgsl");
+        let storage = |binding, read_only, min_binding_size| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: Some(min_binding_size),
+            },
+            count: None,
+        };
+        let layout =
+            world
+                .device()
+                .create_bind_group_layout_wrap(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some("Tlas Build Bind Group Layout"),
+                    entries: &[
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 0,
+                            visibility: wgpu::ShaderStages::COMPUTE,
+                            ty: wgpu::BindingType::Buffer {
+                                ty: wgpu::BufferBindingType::Uniform,
+                                has_dynamic_offset: true,
+                                min_binding_size: Some(BuildParams::NSIZE),
+                            },
+                            count: None,
+                        },
+                        storage(1, true, Instance::NSIZE),
+                        storage(2, true, MeshInfo::NSIZE),
+                        storage(3, false, <[f32; 8]>::NSIZE),
+                        storage(4, false, <[u32; 2]>::NSIZE),
+                        storage(5, false, <[f32; 8]>::NSIZE),
+                        storage(6, false, TlasNode::NSIZE),
+                    ],
+                });
+
+        let mut arena = world.get_mut::<PipelineArena>()?;
+        let mut pipeline = |entry_point: &str| {
+            let desc = ComputePipelineDescriptor {
+                label: Some(format!("Tlas Build {entry_point} Pipeline").into()),
+                layout: vec![layout.clone()],
+                push_constant_ranges: vec![],
+                entry_point: entry_point.to_string().into(),
+            };
+            arena.process_compute_pipeline_from_path(&path, desc)
+        };
+        Ok(Self {
+            instance_bounds: pipeline("instance_bounds")?,
+            scene_bounds: pipeline("scene_bounds")?,
+            morton: pipeline("morton")?,
+            bitonic: pipeline("bitonic")?,
+            leaves: pipeline("leaves")?,
+            internal: pipeline("internal")?,
+            layout,
+            scratch: RefCell::new(None),
+        })
+    }
+}
+
+impl Pass for TlasBuild {
+    type Resources<'a> = TlasBuildResources<'a>;
+
+    fn record(
+        &self,
+        world: &World,
+        encoder: &mut ProfilerCommandEncoder,
+        resources: Self::Resources<'_>,
+    ) {
+        let instances = world.unwrap::<InstancePool>();
+        let count = instances.count();
+        if count == 0 || count > Self::MAX_INSTANCES {
+            return;
+        }
+        let device = world.device();
+        let mut scratch = self.scratch.borrow_mut();
+        if scratch
+            .as_ref()
+            .map_or(true, |scratch| scratch.count != count)
+        {
+            *scratch = Some(Scratch::new(device, count));
+        }
+        let scratch = scratch.as_ref().unwrap();
+
+        let meshes = world.unwrap::<MeshPool>();
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Tlas Build Bind Group"),
+            layout: &self.layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                        buffer: &scratch.params,
+                        offset: 0,
+                        size: Some(BuildParams::NSIZE),
+                    }),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: instances.instances.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: meshes.mesh_info.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: scratch.aabbs.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: scratch.keys.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 5,
+                    resource: scratch.scene.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 6,
+                    resource: resources.nodes.as_entire_binding(),
+                },
+            ],
+        });
+
+        let arena = world.unwrap::<PipelineArena>();
+        let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Tlas Build Pass"),
+        });
+        let groups = |threads: u32| align_to(threads, 64) / 64;
+
+        cpass.set_bind_group(0, &bind_group, &[0]);
+        cpass.set_pipeline(arena.get_pipeline(self.instance_bounds));
+        cpass.dispatch_workgroups(groups(count), 1, 1);
+        cpass.set_pipeline(arena.get_pipeline(self.scene_bounds));
+        cpass.dispatch_workgroups(1, 1, 1);
+        cpass.set_pipeline(arena.get_pipeline(self.morton));
+        cpass.dispatch_workgroups(groups(scratch.padded), 1, 1);
+
+        cpass.set_pipeline(arena.get_pipeline(self.bitonic));
+        for stage in 1..=scratch.stages {
+            cpass.set_bind_group(0, &bind_group, &[stage * scratch.stride]);
+            cpass.dispatch_workgroups(groups(scratch.padded), 1, 1);
+        }
+
+        cpass.set_bind_group(0, &bind_group, &[0]);
+        cpass.set_pipeline(arena.get_pipeline(self.leaves));
+        cpass.dispatch_workgroups(groups(count), 1, 1);
+        if count > 1 {
+            cpass.set_pipeline(arena.get_pipeline(self.internal));
+            cpass.dispatch_workgroups(groups(count - 1), 1, 1);
+        }
+    }
+}
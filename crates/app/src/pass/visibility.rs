@@ -1,13 +1,28 @@
-use std::path::Path;
+use std::{
+    cell::{Cell, Ref, RefCell},
+    path::Path,
+};
 
+use bytemuck::{Pod, Zeroable};
 use color_eyre::Result;
-use components::bind_group_layout::StorageWriteBindGroupLayout;
+use components::bind_group_layout::{
+    BindGroupLayout, StorageReadBindGroupLayout, StorageWriteBindGroupLayout,
+    WrappedBindGroupLayout,
+};
 use components::world::World;
-use components::{DrawIndexedIndirect, NonZeroSized, ResizableBuffer};
+use components::{DrawIndexedIndirect, NonZeroSized, ResizableBuffer, FRAME_SLOTS};
 use glam::{Vec2, Vec3, Vec4};
-use wgpu::{util::align_to, IndexFormat};
+use wgpu::{
+    util::{align_to, DeviceExt},
+    IndexFormat,
+};
 
-use super::Pass;
+use super::{
+    hiz::{DepthReduction, HiZ, HiZResource},
+    imposter::{ImposterBillboards, ImposterBillboardsResource},
+    postprocess::DebugView,
+    Pass,
+};
 
 use crate::ProfilerCommandEncoder;
 use crate::{
@@ -15,21 +30,185 @@ use crate::{
         self, ComputeHandle, ComputePipelineDescriptor, PipelineArena, RenderHandle,
         RenderPipelineDescriptor,
     },
-    CameraUniformBinding, GBuffer, InstancePool, MaterialPool, MeshPool, TexturePool,
+    CameraUniformBinding, GBuffer, Imposters, InstancePool, MaterialPool, MeshPool,
+    SoftwareOcclusion, TexturePool,
 };
 
+/// Screen space error budget of the coarser mesh levels made by `LodChain`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LodSettings {
+    /// Largest error in pixels a coarser level may show, zero always draws the full meshes.
+    pub pixel_error: f32,
+}
+
+impl Default for LodSettings {
+    fn default() -> Self {
+        Self { pixel_error: 1. }
+    }
+}
+
 pub struct Visibility {
     geometry: Geometry,
     emit_draws: EmitDraws,
+    lod_settings: LodSettings,
+    meshlet_cull: Option<MeshletCull>,
+    meshlet_culling: bool,
+    sort_draws: SortDraws,
+    draw_sorting: bool,
+    imposters: ImposterBillboards,
+    hiz_culling: bool,
+    // Farthest-depth pyramid of the last frame, keyed by the G-buffer depth it was built from.
+    // `None` until it holds a frame, which is when `EmitDraws` starts testing against it.
+    occlusion_pyramid: RefCell<(Option<wgpu::Id<wgpu::TextureView>>, HiZ)>,
 }
 
 impl Visibility {
     pub fn new(world: &World) -> Result<Self> {
+        let gbuffer_layout = world
+            .device()
+            .create_bind_group_layout_wrap(&GBuffer::LAYOUT_DESC);
+        // Sized to the G-buffer on first use.
+        let pyramid = HiZ::with_reduction(world, &gbuffer_layout, 1, 1, DepthReduction::Farthest)?;
+        let meshlet_cull = world
+            .device()
+            .features()
+            .contains(wgpu::Features::MULTI_DRAW_INDIRECT)
+            .then(|| MeshletCull::new(world))
+            .transpose()?;
         Ok(Self {
             geometry: Geometry::new(world)?,
-            emit_draws: EmitDraws::new(world)?,
+            emit_draws: EmitDraws::new(world, &pyramid.bind_group_layout)?,
+            lod_settings: LodSettings::default(),
+            meshlet_cull,
+            meshlet_culling: false,
+            sort_draws: SortDraws::new(world)?,
+            draw_sorting: false,
+            imposters: ImposterBillboards::new(world)?,
+            hiz_culling: false,
+            occlusion_pyramid: RefCell::new((None, pyramid)),
         })
     }
+
+    /// Culls instances hidden behind the previous frame's depth, tested with a
+    /// farthest-depth pyramid built after the geometry pass.
+    ///
+    /// Cuts overdraw in dense scenes. The test lags a frame behind, so geometry
+    /// revealed by a fast moving camera or object can pop in one frame late.
+    pub fn set_hiz_culling(&mut self, enabled: bool) {
+        self.hiz_culling = enabled;
+        if !enabled {
+            self.occlusion_pyramid.borrow_mut().0.take();
+        }
+    }
+
+    pub fn hiz_culling(&self) -> bool {
+        self.hiz_culling
+    }
+
+    /// Culls the meshlets of every instance left after instance culling against the
+    /// frustum and by their normal cone, and draws only the visible ones. Cuts vertex work
+    /// for dense meshes, at the cost of a draw per meshlet.
+    ///
+    /// Needs `MULTI_DRAW_INDIRECT`, stays off when the adapter lacks it. Render bundles and
+    /// the visibility buffer keep drawing whole instances, the triangle ids of the latter
+    /// count from the start of the draw.
+    pub fn set_meshlet_culling(&mut self, enabled: bool) {
+        if enabled && self.meshlet_cull.is_none() {
+            log::warn!("Meshlet culling needs MULTI_DRAW_INDIRECT, which the adapter lacks");
+            return;
+        }
+        self.meshlet_culling = enabled;
+    }
+
+    pub fn meshlet_culling(&self) -> bool {
+        self.meshlet_culling
+    }
+
+    /// Picks a coarser level for instances far enough that it shows no more error than
+    /// the budget. The visibility buffer always draws the full meshes, its triangle ids
+    /// index them.
+    pub fn set_lod_settings(&mut self, settings: LodSettings) {
+        self.lod_settings = settings;
+    }
+
+    pub fn lod_settings(&self) -> LodSettings {
+        self.lod_settings
+    }
+
+    /// Draws instances grouped by material, then by mesh, so neighbouring draws read the
+    /// same material and textures. The order is rebuilt on the cpu when an instance
+    /// changes its material or mesh, and the draws are copied in it after culling.
+    ///
+    /// Meshlet culling appends its draws in whatever order they survive, it ignores this.
+    pub fn set_draw_sorting(&mut self, enabled: bool) {
+        self.draw_sorting = enabled;
+    }
+
+    pub fn draw_sorting(&self) -> bool {
+        self.draw_sorting
+    }
+
+    /// Replays geometry draws from a cached render bundle instead of encoding them every frame.
+    ///
+    /// Enabled by default when the adapter lacks `MULTI_DRAW_INDIRECT` and every
+    /// instance needs its own indirect draw.
+    pub fn set_render_bundles(&mut self, enabled: bool) {
+        self.geometry.use_render_bundle = enabled;
+        if !enabled {
+            self.geometry.bundle.take();
+            self.geometry.overdraw_bundle.take();
+        }
+    }
+
+    pub fn render_bundles(&self) -> bool {
+        self.geometry.use_render_bundle
+    }
+
+    /// Lays down depth with an alpha-tested depth-only pass first, so the G-buffer pass
+    /// shades each pixel once. Pays off with heavy materials and alpha-tested foliage.
+    pub fn set_depth_prepass(&mut self, enabled: bool) {
+        self.geometry.depth_prepass = enabled;
+        if !enabled {
+            self.geometry.prepass_bundle.take();
+        }
+    }
+
+    pub fn depth_prepass(&self) -> bool {
+        self.geometry.depth_prepass
+    }
+
+    /// Rasterizes only instance and triangle ids, then resolves the G-buffer in a compute
+    /// pass that fetches and interpolates vertex attributes from the `MeshPool`.
+    /// Cuts raster bandwidth for very dense meshes, the depth pre-pass is skipped while on.
+    ///
+    /// Needs `SHADER_PRIMITIVE_INDEX`, stays off when the adapter lacks it.
+    pub fn set_vis_buffer(&mut self, enabled: bool) {
+        if enabled && self.geometry.vis_buffer.is_none() {
+            log::warn!("Visibility buffer needs SHADER_PRIMITIVE_INDEX, which the adapter lacks");
+            return;
+        }
+        self.geometry.use_vis_buffer = enabled;
+        if let (false, Some(vis_buffer)) = (enabled, &self.geometry.vis_buffer) {
+            vis_buffer.bundle.take();
+            vis_buffer.targets.take();
+        }
+    }
+
+    pub fn vis_buffer(&self) -> bool {
+        self.geometry.use_vis_buffer
+    }
+
+    /// Instance and triangle ids rasterized last frame, `None` with the visibility buffer off.
+    pub fn vis_buffer_ids(&self) -> Option<Ref<'_, wgpu::TextureView>> {
+        let vis_buffer = self.geometry.vis_buffer.as_ref()?;
+        if !self.geometry.use_vis_buffer {
+            return None;
+        }
+        Ref::filter_map(vis_buffer.targets.borrow(), |targets| {
+            targets.as_ref().map(|(_, targets)| &targets.ids)
+        })
+        .ok()
+    }
 }
 
 pub struct VisibilityResource<'a> {
@@ -48,28 +227,134 @@ impl Pass for Visibility {
         resources: Self::Resources<'_>,
     ) {
         encoder.profile_start("Visibility");
+        let mut occlusion_pyramid = self.occlusion_pyramid.borrow_mut();
+        let (built_from, pyramid) = &mut *occlusion_pyramid;
+        let depth_id = resources.gbuffer.depth.global_id();
+        world.unwrap::<Imposters>().reset(encoder);
         self.emit_draws.record(
             world,
             encoder,
             EmitDrawsResource {
                 draw_cmd_buffer: resources.draw_cmd_buffer,
                 draw_cmd_bind_group: resources.draw_cmd_bind_group,
+                hiz: pyramid,
+                hiz_culling: self.hiz_culling && *built_from == Some(depth_id),
+                lod: LodUniform {
+                    pixel_error: match self.geometry.use_vis_buffer {
+                        true => 0.,
+                        false => self.lod_settings.pixel_error,
+                    },
+                    viewport_height: resources.gbuffer.size().1 as f32,
+                    junk: [0.; 2],
+                },
             },
         );
-        self.geometry.record(
+        let meshlet_cull = self.meshlet_cull.as_ref().filter(|_| {
+            self.meshlet_culling
+                && !self.geometry.use_render_bundle
+                && !self.geometry.use_vis_buffer
+        });
+        if let Some(meshlet_cull) = meshlet_cull {
+            meshlet_cull.record(
+                world,
+                encoder,
+                MeshletCullResource {
+                    instance_draws: resources.draw_cmd_buffer,
+                },
+            );
+            let draws = meshlet_cull.draws.borrow();
+            self.geometry.record(
+                world,
+                encoder,
+                GeometryResource {
+                    gbuffer: resources.gbuffer,
+                    draw_cmd_buffer: &draws.buffer,
+                    draw_count: meshlet_cull.use_count.then_some(&draws.count),
+                },
+            );
+        } else if self.draw_sorting {
+            self.sort_draws.record(
+                world,
+                encoder,
+                SortDrawsResource {
+                    draws: resources.draw_cmd_buffer,
+                },
+            );
+            let sorted = self.sort_draws.sorted.borrow();
+            self.geometry.record(
+                world,
+                encoder,
+                GeometryResource {
+                    gbuffer: resources.gbuffer,
+                    draw_cmd_buffer: &sorted.buffer,
+                    draw_count: None,
+                },
+            );
+        } else {
+            self.geometry.record(
+                world,
+                encoder,
+                GeometryResource {
+                    gbuffer: resources.gbuffer,
+                    draw_cmd_buffer: resources.draw_cmd_buffer,
+                    draw_count: None,
+                },
+            );
+        }
+        self.imposters.record(
             world,
             encoder,
-            GeometryResource {
+            ImposterBillboardsResource {
                 gbuffer: resources.gbuffer,
-                draw_cmd_buffer: resources.draw_cmd_buffer,
             },
         );
+        if self.hiz_culling {
+            if *built_from != Some(depth_id) {
+                let (width, height) = resources.gbuffer.size();
+                pyramid.resize(world.device(), width, height);
+            }
+            pyramid.record(
+                world,
+                encoder,
+                HiZResource {
+                    gbuffer: resources.gbuffer,
+                },
+            );
+            *built_from = Some(depth_id);
+        }
         encoder.profile_end();
     }
 }
 
 struct Geometry {
     pipeline: RenderHandle,
+    prepass_pipeline: RenderHandle,
+    // Same as `pipeline`, but only shades the fragments left by the pre-pass.
+    after_prepass_pipeline: RenderHandle,
+    // Draws into `GBuffer::overdraw` while the debug view shows it.
+    overdraw_pipeline: RenderHandle,
+    depth_prepass: bool,
+    vis_buffer: Option<VisBuffer>,
+    use_vis_buffer: bool,
+    use_render_bundle: bool,
+    bundle: BundleCache,
+    prepass_bundle: BundleCache,
+    overdraw_bundle: BundleCache,
+}
+
+/// Recorded bundles by key. The camera bind group changes with the uniform slot every
+/// frame, so there is one bundle per [`FRAME_SLOTS`], the oldest is dropped first.
+type BundleCache = RefCell<Vec<(GeometryBundleKey, wgpu::RenderBundle)>>;
+
+/// Identity of everything baked into the geometry render bundle.
+/// Pools recreate their buffers and bind groups when they grow and
+/// hot reload replaces the pipeline, all of which changes the key.
+#[derive(PartialEq, Eq)]
+struct GeometryBundleKey {
+    pipeline: wgpu::Id<wgpu::RenderPipeline>,
+    bind_groups: [wgpu::Id<wgpu::BindGroup>; 5],
+    buffers: [wgpu::Id<wgpu::Buffer>; 6],
+    draw_count: usize,
 }
 
 impl Geometry {
@@ -78,6 +363,7 @@ impl Geometry {
         let textures = world.get::<TexturePool>()?;
         let materials = world.get::<MaterialPool>()?;
         let instances = world.get::<InstancePool>()?;
+        let meshes = world.get::<MeshPool>()?;
         let camera = world.get::<CameraUniformBinding>()?;
         let render_desc = RenderPipelineDescriptor {
             label: Some("Visibilty Pipeline".into()),
@@ -86,6 +372,7 @@ impl Geometry {
                 textures.bind_group_layout.clone(),
                 instances.bind_group_layout.clone(),
                 materials.bind_group_layout.clone(),
+                meshes.mesh_info_layout.clone(),
             ],
             vertex: pipeline::VertexState {
                 entry_point: "vs_main".into(),
@@ -133,10 +420,156 @@ impl Geometry {
             }),
             ..Default::default()
         };
-        let pipeline = world
-            .get_mut::<PipelineArena>()?
-            .process_render_pipeline_from_path(path, render_desc)?;
-        Ok(Self { pipeline })
+
+        let prepass_desc = RenderPipelineDescriptor {
+            label: Some("Depth Pre-Pass Pipeline".into()),
+            fragment: Some(pipeline::FragmentState {
+                entry_point: "fs_depth".into(),
+                targets: vec![],
+            }),
+            ..render_desc.clone()
+        };
+        let after_prepass_desc = RenderPipelineDescriptor {
+            label: Some("Visibilty After Pre-Pass Pipeline".into()),
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: GBuffer::DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::GreaterEqual,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            ..render_desc.clone()
+        };
+
+        let overdraw_desc = RenderPipelineDescriptor {
+            label: Some("Overdraw Pipeline".into()),
+            fragment: Some(pipeline::FragmentState {
+                entry_point: "fs_overdraw".into(),
+                targets: GBuffer::overdraw_target_state().into(),
+            }),
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: GBuffer::DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::Always,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            ..render_desc.clone()
+        };
+
+        let mut arena = world.get_mut::<PipelineArena>()?;
+        let pipeline = arena.process_render_pipeline_from_path(&path, render_desc.clone())?;
+        let prepass_pipeline = arena.process_render_pipeline_from_path(&path, prepass_desc)?;
+        let after_prepass_pipeline =
+            arena.process_render_pipeline_from_path(&path, after_prepass_desc)?;
+        let overdraw_pipeline = arena.process_render_pipeline_from_path(&path, overdraw_desc)?;
+        drop(arena);
+
+        let features = world.device().features();
+        let vis_buffer = features
+            .contains(wgpu::Features::SHADER_PRIMITIVE_INDEX)
+            .then(|| VisBuffer::new(world, render_desc))
+            .transpose()?;
+        let use_render_bundle = !features.contains(wgpu::Features::MULTI_DRAW_INDIRECT);
+        Ok(Self {
+            pipeline,
+            prepass_pipeline,
+            after_prepass_pipeline,
+            overdraw_pipeline,
+            depth_prepass: false,
+            vis_buffer,
+            use_vis_buffer: false,
+            use_render_bundle,
+            bundle: RefCell::new(vec![]),
+            prepass_bundle: RefCell::new(vec![]),
+            overdraw_bundle: RefCell::new(vec![]),
+        })
+    }
+
+    fn bundle_key(
+        &self,
+        world: &World,
+        pipeline: RenderHandle,
+        draw_cmd_buffer: &ResizableBuffer<DrawIndexedIndirect>,
+    ) -> GeometryBundleKey {
+        let meshes = world.unwrap::<MeshPool>();
+        let arena = world.unwrap::<PipelineArena>();
+        GeometryBundleKey {
+            pipeline: arena.get_pipeline(pipeline).global_id(),
+            bind_groups: [
+                world.unwrap::<CameraUniformBinding>().binding().global_id(),
+                world.unwrap::<TexturePool>().bind_group.global_id(),
+                world.unwrap::<InstancePool>().bind_group.global_id(),
+                world.unwrap::<MaterialPool>().bind_group.global_id(),
+                meshes.mesh_info_bind_group.global_id(),
+            ],
+            buffers: [
+                meshes.vertices.global_id(),
+                meshes.normals.global_id(),
+                meshes.tangents.global_id(),
+                meshes.tex_coords.global_id(),
+                meshes.indices.global_id(),
+                draw_cmd_buffer.global_id(),
+            ],
+            draw_count: draw_cmd_buffer.len(),
+        }
+    }
+
+    fn record_bundle(
+        &self,
+        world: &World,
+        pipeline: RenderHandle,
+        color_targets: &[Option<wgpu::ColorTargetState>],
+        draw_cmd_buffer: &ResizableBuffer<DrawIndexedIndirect>,
+    ) -> wgpu::RenderBundle {
+        let meshes = world.unwrap::<MeshPool>();
+        let textures = world.unwrap::<TexturePool>();
+        let materials = world.unwrap::<MaterialPool>();
+        let instances = world.unwrap::<InstancePool>();
+        let arena = world.unwrap::<PipelineArena>();
+        let camera = world.unwrap::<CameraUniformBinding>();
+
+        let color_formats = color_targets
+            .iter()
+            .map(|target| target.as_ref().map(|t| t.format))
+            .collect::<Vec<_>>();
+        let mut bundle =
+            world
+                .device()
+                .create_render_bundle_encoder(&wgpu::RenderBundleEncoderDescriptor {
+                    label: Some("Visibility Bundle Encoder"),
+                    color_formats: &color_formats,
+                    depth_stencil: Some(wgpu::RenderBundleDepthStencil {
+                        format: GBuffer::DEPTH_FORMAT,
+                        depth_read_only: false,
+                        stencil_read_only: true,
+                    }),
+                    sample_count: 1,
+                    multiview: None,
+                });
+
+        bundle.set_pipeline(arena.get_pipeline(pipeline));
+        bundle.set_bind_group(0, camera.binding(), &[]);
+        bundle.set_bind_group(1, &textures.bind_group, &[]);
+        bundle.set_bind_group(2, &instances.bind_group, &[]);
+        bundle.set_bind_group(3, &materials.bind_group, &[]);
+        bundle.set_bind_group(4, &meshes.mesh_info_bind_group, &[]);
+
+        bundle.set_vertex_buffer(0, meshes.vertices.full_slice());
+        bundle.set_vertex_buffer(1, meshes.normals.full_slice());
+        bundle.set_vertex_buffer(2, meshes.tangents.full_slice());
+        bundle.set_vertex_buffer(3, meshes.tex_coords.full_slice());
+        bundle.set_index_buffer(meshes.indices.full_slice(), IndexFormat::Uint32);
+        for i in 0..draw_cmd_buffer.len() {
+            bundle.draw_indexed_indirect(
+                draw_cmd_buffer,
+                (i * std::mem::size_of::<DrawIndexedIndirect>()) as _,
+            );
+        }
+
+        bundle.finish(&wgpu::RenderBundleDescriptor {
+            label: Some("Visibility Bundle"),
+        })
     }
 }
 
@@ -144,16 +577,46 @@ struct GeometryResource<'a> {
     pub gbuffer: &'a GBuffer,
 
     pub draw_cmd_buffer: &'a ResizableBuffer<DrawIndexedIndirect>,
+    /// Number of draws written to `draw_cmd_buffer` on the gpu, all of them are drawn without it.
+    pub draw_count: Option<&'a wgpu::Buffer>,
 }
 
-impl Pass for Geometry {
-    type Resources<'a> = GeometryResource<'a>;
-    fn record(
+impl Geometry {
+    #[allow(clippy::too_many_arguments)]
+    fn record_pass(
         &self,
         world: &World,
         encoder: &mut ProfilerCommandEncoder,
-        resources: Self::Resources<'_>,
+        label: &str,
+        color_targets: &[Option<wgpu::ColorTargetState>],
+        color_attachments: &[Option<wgpu::RenderPassColorAttachment>],
+        depth: &wgpu::TextureView,
+        depth_load: wgpu::LoadOp<f32>,
+        pipeline: RenderHandle,
+        bundle_cache: &BundleCache,
+        draw_cmd_buffer: &ResizableBuffer<DrawIndexedIndirect>,
+        draw_count: Option<&wgpu::Buffer>,
     ) {
+        let mut bundles = bundle_cache.borrow_mut();
+        let bundle = if self.use_render_bundle {
+            let key = self.bundle_key(world, pipeline, draw_cmd_buffer);
+            let index = match bundles.iter().position(|(cached, _)| *cached == key) {
+                Some(index) => index,
+                None => {
+                    if bundles.len() >= FRAME_SLOTS {
+                        bundles.remove(0);
+                    }
+                    let bundle =
+                        self.record_bundle(world, pipeline, color_targets, draw_cmd_buffer);
+                    bundles.push((key, bundle));
+                    bundles.len() - 1
+                }
+            };
+            Some(&bundles[index].1)
+        } else {
+            None
+        };
+
         let meshes = world.unwrap::<MeshPool>();
         let textures = world.unwrap::<TexturePool>();
         let materials = world.unwrap::<MaterialPool>();
@@ -162,47 +625,400 @@ impl Pass for Geometry {
         let camera = world.unwrap::<CameraUniformBinding>();
 
         let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-            label: Some("Visibility Pass"),
-            color_attachments: &resources.gbuffer.color_target_attachment(),
+            label: Some(label),
+            color_attachments,
             depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                view: &resources.gbuffer.depth,
+                view: depth,
                 depth_ops: Some(wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(0.0),
+                    load: depth_load,
                     store: true,
                 }),
                 stencil_ops: None,
             }),
         });
 
-        rpass.set_pipeline(arena.get_pipeline(self.pipeline));
-        rpass.set_bind_group(0, &camera.binding, &[]);
+        if let Some(bundle) = bundle {
+            rpass.execute_bundles(Some(bundle));
+            return;
+        }
+
+        rpass.set_pipeline(arena.get_pipeline(pipeline));
+        rpass.set_bind_group(0, camera.binding(), &[]);
         rpass.set_bind_group(1, &textures.bind_group, &[]);
         rpass.set_bind_group(2, &instances.bind_group, &[]);
         rpass.set_bind_group(3, &materials.bind_group, &[]);
+        rpass.set_bind_group(4, &meshes.mesh_info_bind_group, &[]);
 
         rpass.set_vertex_buffer(0, meshes.vertices.full_slice());
         rpass.set_vertex_buffer(1, meshes.normals.full_slice());
         rpass.set_vertex_buffer(2, meshes.tangents.full_slice());
         rpass.set_vertex_buffer(3, meshes.tex_coords.full_slice());
         rpass.set_index_buffer(meshes.indices.full_slice(), IndexFormat::Uint32);
-        rpass.multi_draw_indexed_indirect(
+        match draw_count {
+            Some(count) => rpass.multi_draw_indexed_indirect_count(
+                draw_cmd_buffer,
+                0,
+                count,
+                0,
+                draw_cmd_buffer.len() as _,
+            ),
+            None => {
+                rpass.multi_draw_indexed_indirect(draw_cmd_buffer, 0, draw_cmd_buffer.len() as _)
+            }
+        }
+    }
+}
+
+impl Pass for Geometry {
+    type Resources<'a> = GeometryResource<'a>;
+    fn record(
+        &self,
+        world: &World,
+        encoder: &mut ProfilerCommandEncoder,
+        resources: Self::Resources<'_>,
+    ) {
+        // Ahead of the passes below, which clear the depth it is attached with.
+        if *world.unwrap::<DebugView>() == DebugView::Overdraw {
+            self.record_pass(
+                world,
+                encoder,
+                "Overdraw Pass",
+                GBuffer::overdraw_target_state(),
+                &resources.gbuffer.overdraw_attachment(),
+                &resources.gbuffer.depth,
+                wgpu::LoadOp::Clear(0.0),
+                self.overdraw_pipeline,
+                &self.overdraw_bundle,
+                resources.draw_cmd_buffer,
+                resources.draw_count,
+            );
+        }
+
+        if let Some(vis_buffer) = self.vis_buffer.as_ref().filter(|_| self.use_vis_buffer) {
+            let targets = vis_buffer.targets(world, resources.gbuffer);
+            let (_, targets) = targets.as_ref().unwrap();
+            self.record_pass(
+                world,
+                encoder,
+                "Visibility Buffer Pass",
+                VisBuffer::color_target_state(),
+                &targets.color_target_attachment(resources.gbuffer),
+                &resources.gbuffer.depth,
+                wgpu::LoadOp::Clear(0.0),
+                vis_buffer.pipeline,
+                &vis_buffer.bundle,
+                resources.draw_cmd_buffer,
+                resources.draw_count,
+            );
+            vis_buffer.resolve(world, encoder, targets, resources.gbuffer);
+            return;
+        }
+
+        let mut depth_load = wgpu::LoadOp::Clear(0.0);
+        let mut pipeline = self.pipeline;
+        if self.depth_prepass {
+            self.record_pass(
+                world,
+                encoder,
+                "Depth Pre-Pass",
+                &[],
+                &[],
+                &resources.gbuffer.depth,
+                depth_load,
+                self.prepass_pipeline,
+                &self.prepass_bundle,
+                resources.draw_cmd_buffer,
+                resources.draw_count,
+            );
+            depth_load = wgpu::LoadOp::Load;
+            pipeline = self.after_prepass_pipeline;
+        }
+
+        self.record_pass(
+            world,
+            encoder,
+            "Visibility Pass",
+            GBuffer::color_target_state(),
+            &resources.gbuffer.color_target_attachment(),
+            &resources.gbuffer.depth,
+            depth_load,
+            pipeline,
+            &self.bundle,
             resources.draw_cmd_buffer,
-            0,
-            resources.draw_cmd_buffer.len() as _,
+            resources.draw_count,
         );
     }
 }
 
+/// Instance and triangle ids rasterized in place of the G-buffer, see [`Visibility::set_vis_buffer`].
+struct VisBuffer {
+    pipeline: RenderHandle,
+    resolve_pipeline: ComputeHandle,
+    targets_layout: BindGroupLayout,
+    bundle: BundleCache,
+    // Keyed by the G-buffer it resolves into, which is recreated on resize.
+    targets: RefCell<Option<(wgpu::Id<wgpu::TextureView>, VisBufferTargets)>>,
+}
+
+struct VisBufferTargets {
+    ids: wgpu::TextureView,
+    bind_group: wgpu::BindGroup,
+}
+
+impl VisBufferTargets {
+    fn new(device: &wgpu::Device, layout: &wgpu::BindGroupLayout, gbuffer: &GBuffer) -> Self {
+        let (width, height) = gbuffer.size();
+        let ids = device
+            .create_texture(&wgpu::TextureDescriptor {
+                label: Some("Visibility Buffer: ids"),
+                size: wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: VisBuffer::IDS_FORMAT,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                    | wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            })
+            .create_view(&Default::default());
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Visibility Buffer: bind group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&ids),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&gbuffer.packed),
+                },
+            ],
+        });
+        Self { ids, bind_group }
+    }
+
+    fn color_target_attachment<'a>(
+        &'a self,
+        gbuffer: &'a GBuffer,
+    ) -> [Option<wgpu::RenderPassColorAttachment<'a>>; 3] {
+        [
+            Some(wgpu::RenderPassColorAttachment {
+                view: &self.ids,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: true,
+                },
+            }),
+            Some(gbuffer.history_reject_attachment()),
+            Some(gbuffer.instance_id_attachment()),
+        ]
+    }
+}
+
+impl VisBuffer {
+    const IDS_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rg32Uint;
+    const fn color_target_state() -> &'static [Option<wgpu::ColorTargetState>] {
+        &[
+            Some(wgpu::ColorTargetState {
+                format: Self::IDS_FORMAT,
+                blend: None,
+                write_mask: wgpu::ColorWrites::ALL,
+            }),
+            Some(wgpu::ColorTargetState {
+                format: GBuffer::HISTORY_REJECT_FORMAT,
+                blend: None,
+                write_mask: wgpu::ColorWrites::ALL,
+            }),
+            Some(wgpu::ColorTargetState {
+                format: GBuffer::INSTANCE_ID_FORMAT,
+                blend: None,
+                write_mask: wgpu::ColorWrites::ALL,
+            }),
+        ]
+    }
+
+    fn new(world: &World, geometry_desc: RenderPipelineDescriptor) -> Result<Self> {
+        let camera = world.get::<CameraUniformBinding>()?;
+        let textures = world.get::<TexturePool>()?;
+        let materials = world.get::<MaterialPool>()?;
+        let instances = world.get::<InstancePool>()?;
+        let meshes = world.get::<MeshPool>()?;
+
+        let targets_layout =
+            world
+                .device()
+                .create_bind_group_layout_wrap(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some("Visibility Buffer BGL"),
+                    entries: &[
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 0,
+                            visibility: wgpu::ShaderStages::COMPUTE,
+                            ty: wgpu::BindingType::Texture {
+                                sample_type: wgpu::TextureSampleType::Uint,
+                                view_dimension: wgpu::TextureViewDimension::D2,
+                                multisampled: false,
+                            },
+                            count: None,
+                        },
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 1,
+                            visibility: wgpu::ShaderStages::COMPUTE,
+                            ty: wgpu::BindingType::StorageTexture {
+                                access: wgpu::StorageTextureAccess::WriteOnly,
+                                format: GBuffer::PACKED_FORMAT,
+                                view_dimension: wgpu::TextureViewDimension::D2,
+                            },
+                            count: None,
+                        },
+                    ],
+                });
+
+        let raster_desc = RenderPipelineDescriptor {
+            label: Some("Visibility Buffer Pipeline".into()),
+            fragment: Some(pipeline::FragmentState {
+                entry_point: "fs_main".into(),
+                targets: Self::color_target_state().into(),
+            }),
+            ..geometry_desc
+        };
+        let resolve_desc = ComputePipelineDescriptor {
+            label: Some("Visibility Buffer Resolve Pipeline".into()),
+            layout: vec![
+                camera.bind_group_layout.clone(),
+                targets_layout.clone(),
+                instances.bind_group_layout.clone(),
+                meshes.attributes_layout.clone(),
+                materials.bind_group_layout.clone(),
+                textures.bind_group_layout.clone(),
+            ],
+            push_constant_ranges: vec![],
+            entry_point: "cs_main".into(),
+        };
+
+        let mut arena = world.get_mut::<PipelineArena>()?;
+        let pipeline = arena.process_render_pipeline_from_path(
+            Path::new("shaders").join("vis_buffer.wgsl"),
+            raster_desc,
+        )?;
+        let resolve_pipeline = arena.process_compute_pipeline_from_path(
+            Path::new("shaders").join("vis_buffer_resolve.wgsl"),
+            resolve_desc,
+        )?;
+        Ok(Self {
+            pipeline,
+            resolve_pipeline,
+            targets_layout,
+            bundle: RefCell::new(vec![]),
+            targets: RefCell::new(None),
+        })
+    }
+
+    fn targets(
+        &self,
+        world: &World,
+        gbuffer: &GBuffer,
+    ) -> Ref<'_, Option<(wgpu::Id<wgpu::TextureView>, VisBufferTargets)>> {
+        let key = gbuffer.packed.global_id();
+        let mut targets = self.targets.borrow_mut();
+        if !targets.as_ref().is_some_and(|(cached, _)| *cached == key) {
+            *targets = Some((
+                key,
+                VisBufferTargets::new(world.device(), &self.targets_layout, gbuffer),
+            ));
+        }
+        drop(targets);
+        self.targets.borrow()
+    }
+
+    fn resolve(
+        &self,
+        world: &World,
+        encoder: &mut ProfilerCommandEncoder,
+        targets: &VisBufferTargets,
+        gbuffer: &GBuffer,
+    ) {
+        let camera = world.unwrap::<CameraUniformBinding>();
+        let textures = world.unwrap::<TexturePool>();
+        let materials = world.unwrap::<MaterialPool>();
+        let instances = world.unwrap::<InstancePool>();
+        let meshes = world.unwrap::<MeshPool>();
+        let arena = world.unwrap::<PipelineArena>();
+
+        let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Visibility Buffer Resolve"),
+        });
+        cpass.set_pipeline(arena.get_pipeline(self.resolve_pipeline));
+        cpass.set_bind_group(0, camera.binding(), &[]);
+        cpass.set_bind_group(1, &targets.bind_group, &[]);
+        cpass.set_bind_group(2, &instances.bind_group, &[]);
+        cpass.set_bind_group(3, &meshes.attributes_bind_group, &[]);
+        cpass.set_bind_group(4, &materials.bind_group, &[]);
+        cpass.set_bind_group(5, &textures.bind_group, &[]);
+        let (width, height) = gbuffer.size();
+        cpass.dispatch_workgroups(align_to(width, 8) / 8, align_to(height, 8) / 8, 1);
+    }
+}
+
+// Mirrors `LodSettings` of `shaders/emit_draws.wgsl`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Pod, Zeroable)]
+struct LodUniform {
+    pixel_error: f32,
+    viewport_height: f32,
+    junk: [f32; 2],
+}
+
 struct EmitDraws {
     pipeline: ComputeHandle,
+    hiz_pipeline: ComputeHandle,
+    lod_uniform: wgpu::Buffer,
+    lod_bind_group: wgpu::BindGroup,
+    // Last written to `lod_uniform`.
+    lod: Cell<LodUniform>,
 }
 
 impl EmitDraws {
-    pub fn new(world: &World) -> Result<Self> {
+    pub fn new(world: &World, hiz_layout: &BindGroupLayout) -> Result<Self> {
         let camera = world.get::<CameraUniformBinding>()?;
         let meshes = world.get::<MeshPool>()?;
         let instances = world.get::<InstancePool>()?;
         let draw_cmd_layout = world.get::<StorageWriteBindGroupLayout<DrawIndexedIndirect>>()?;
+        let occluded_layout = world.get::<StorageReadBindGroupLayout<u32>>()?;
+        let imposters = world.get::<Imposters>()?;
+        let device = world.device();
+        let lod_layout = device.create_bind_group_layout_wrap(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("LOD Settings BGL"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: Some(LodUniform::NSIZE),
+                },
+                count: None,
+            }],
+        });
+        let lod = LodUniform::zeroed();
+        let lod_uniform = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("LOD Settings Uniform"),
+            contents: bytemuck::bytes_of(&lod),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let lod_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("LOD Settings BG"),
+            layout: &lod_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: lod_uniform.as_entire_binding(),
+            }],
+        });
         let path = Path::new("shaders").join("emit_draws.wgsl");
         let comp_desc = ComputePipelineDescriptor {
             label: Some("Emit Draws Pipeline".into()),
@@ -211,20 +1027,38 @@ impl EmitDraws {
                 meshes.mesh_info_layout.clone(),
                 instances.bind_group_layout.clone(),
                 draw_cmd_layout.layout.clone(),
+                occluded_layout.layout.clone(),
+                hiz_layout.clone(),
+                imposters.emit_layout.clone(),
+                lod_layout,
             ],
             push_constant_ranges: vec![],
             entry_point: "emit_draws".into(),
         };
-        let pipeline = world
-            .get_mut::<PipelineArena>()?
-            .process_compute_pipeline_from_path(path, comp_desc)?;
-        Ok(Self { pipeline })
+        let hiz_desc = ComputePipelineDescriptor {
+            label: Some("Emit Draws Hi-Z Pipeline".into()),
+            entry_point: "emit_draws_hiz".into(),
+            ..comp_desc.clone()
+        };
+        let mut arena = world.get_mut::<PipelineArena>()?;
+        let pipeline = arena.process_compute_pipeline_from_path(&path, comp_desc)?;
+        let hiz_pipeline = arena.process_compute_pipeline_from_path(&path, hiz_desc)?;
+        Ok(Self {
+            pipeline,
+            hiz_pipeline,
+            lod_uniform,
+            lod_bind_group,
+            lod: Cell::new(lod),
+        })
     }
 }
 
 struct EmitDrawsResource<'a> {
     pub draw_cmd_bind_group: &'a wgpu::BindGroup,
     pub draw_cmd_buffer: &'a ResizableBuffer<DrawIndexedIndirect>,
+    pub hiz: &'a HiZ,
+    pub hiz_culling: bool,
+    pub lod: LodUniform,
 }
 
 impl Pass for EmitDraws {
@@ -240,16 +1074,356 @@ impl Pass for EmitDraws {
         let meshes = world.unwrap::<MeshPool>();
         let arena = world.unwrap::<PipelineArena>();
         let instances = world.unwrap::<InstancePool>();
+        let occlusion = world.unwrap::<SoftwareOcclusion>();
+        let imposters = world.unwrap::<Imposters>();
+        if self.lod.get() != resources.lod {
+            self.lod.set(resources.lod);
+            world
+                .queue()
+                .write_buffer(&self.lod_uniform, 0, bytemuck::bytes_of(&resources.lod));
+        }
         let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
             label: Some("Emit Draws Pass"),
         });
 
-        cpass.set_pipeline(arena.get_pipeline(self.pipeline));
-        cpass.set_bind_group(0, &camera.binding, &[]);
+        let pipeline = match resources.hiz_culling {
+            true => self.hiz_pipeline,
+            false => self.pipeline,
+        };
+        cpass.set_pipeline(arena.get_pipeline(pipeline));
+        cpass.set_bind_group(0, camera.binding(), &[]);
         cpass.set_bind_group(1, &meshes.mesh_info_bind_group, &[]);
         cpass.set_bind_group(2, &instances.bind_group, &[]);
         cpass.set_bind_group(3, resources.draw_cmd_bind_group, &[]);
+        cpass.set_bind_group(4, &occlusion.bind_group, &[]);
+        cpass.set_bind_group(5, &resources.hiz.bind_group, &[]);
+        cpass.set_bind_group(6, &imposters.emit_bind_group, &[]);
+        cpass.set_bind_group(7, &self.lod_bind_group, &[]);
         let num_dispatches = align_to(resources.draw_cmd_buffer.len() as _, 64) / 64;
         cpass.dispatch_workgroups(num_dispatches, 1, 1);
     }
 }
+
+/// Second culling level, see [`Visibility::set_meshlet_culling`].
+struct MeshletCull {
+    pipeline: ComputeHandle,
+    draws_layout: BindGroupLayout,
+    // Without `MULTI_DRAW_INDIRECT_COUNT` the draws are cleared and all of them drawn.
+    use_count: bool,
+    draws: RefCell<MeshletDraws>,
+}
+
+struct MeshletDraws {
+    // Room for every meshlet of every instance.
+    buffer: ResizableBuffer<DrawIndexedIndirect>,
+    count: wgpu::Buffer,
+    // Keyed by the instance draws and `buffer`, both are replaced when they grow.
+    bind_group: Option<([wgpu::Id<wgpu::Buffer>; 2], wgpu::BindGroup)>,
+}
+
+impl MeshletCull {
+    fn new(world: &World) -> Result<Self> {
+        let camera = world.get::<CameraUniformBinding>()?;
+        let meshes = world.get::<MeshPool>()?;
+        let instances = world.get::<InstancePool>()?;
+        let device = world.device();
+        let storage_entry = |binding, read_only, min_binding_size| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: Some(min_binding_size),
+            },
+            count: None,
+        };
+        let draws_layout = device.create_bind_group_layout_wrap(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Meshlet Draws BGL"),
+            entries: &[
+                storage_entry(0, true, DrawIndexedIndirect::NSIZE),
+                storage_entry(1, false, DrawIndexedIndirect::NSIZE),
+                storage_entry(2, false, u32::NSIZE),
+            ],
+        });
+        let desc = ComputePipelineDescriptor {
+            label: Some("Meshlet Cull Pipeline".into()),
+            layout: vec![
+                camera.bind_group_layout.clone(),
+                meshes.mesh_info_layout.clone(),
+                instances.bind_group_layout.clone(),
+                draws_layout.clone(),
+            ],
+            push_constant_ranges: vec![],
+            entry_point: "cull".into(),
+        };
+        let pipeline = world
+            .get_mut::<PipelineArena>()?
+            .process_compute_pipeline_from_path(
+                Path::new("shaders").join("meshlet_cull.wgsl"),
+                desc,
+            )?;
+
+        let draws = MeshletDraws {
+            buffer: ResizableBuffer::new(
+                device,
+                wgpu::BufferUsages::INDIRECT | wgpu::BufferUsages::STORAGE,
+            ),
+            count: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Meshlet Draw Count"),
+                size: u32::SIZE as u64,
+                usage: wgpu::BufferUsages::INDIRECT
+                    | wgpu::BufferUsages::STORAGE
+                    | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }),
+            bind_group: None,
+        };
+        Ok(Self {
+            pipeline,
+            draws_layout,
+            use_count: device
+                .features()
+                .contains(wgpu::Features::MULTI_DRAW_INDIRECT_COUNT),
+            draws: RefCell::new(draws),
+        })
+    }
+}
+
+struct MeshletCullResource<'a> {
+    pub instance_draws: &'a ResizableBuffer<DrawIndexedIndirect>,
+}
+
+impl Pass for MeshletCull {
+    type Resources<'a> = MeshletCullResource<'a>;
+
+    fn record(
+        &self,
+        world: &World,
+        encoder: &mut ProfilerCommandEncoder,
+        resources: Self::Resources<'_>,
+    ) {
+        let camera = world.unwrap::<CameraUniformBinding>();
+        let meshes = world.unwrap::<MeshPool>();
+        let instances = world.unwrap::<InstancePool>();
+        let arena = world.unwrap::<PipelineArena>();
+        let device = world.device();
+
+        let capacity = instances
+            .instances_data
+            .iter()
+            .map(|instance| {
+                let mesh = &meshes.mesh_info_cpu[instance.mesh.0 as usize];
+                mesh.meshlet_count.max(1) as usize
+            })
+            .sum();
+        let mut draws = self.draws.borrow_mut();
+        let draws = &mut *draws;
+        draws.buffer.set_len(device, encoder, capacity);
+        let key = [
+            resources.instance_draws.global_id(),
+            draws.buffer.global_id(),
+        ];
+        if !draws
+            .bind_group
+            .as_ref()
+            .is_some_and(|(cached, _)| *cached == key)
+        {
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Meshlet Draws BG"),
+                layout: &self.draws_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: resources.instance_draws.as_tight_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: draws.buffer.as_tight_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: draws.count.as_entire_binding(),
+                    },
+                ],
+            });
+            draws.bind_group = Some((key, bind_group));
+        }
+        encoder.clear_buffer(&draws.count, 0, None);
+        if !self.use_count {
+            encoder.clear_buffer(&draws.buffer, 0, None);
+        }
+
+        let instance_count = resources.instance_draws.len() as u32;
+        if instance_count == 0 {
+            return;
+        }
+        let max_groups = device.limits().max_compute_workgroups_per_dimension;
+        let (_, bind_group) = draws.bind_group.as_ref().unwrap();
+        let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Meshlet Cull Pass"),
+        });
+        cpass.set_pipeline(arena.get_pipeline(self.pipeline));
+        cpass.set_bind_group(0, camera.binding(), &[]);
+        cpass.set_bind_group(1, &meshes.mesh_info_bind_group, &[]);
+        cpass.set_bind_group(2, &instances.bind_group, &[]);
+        cpass.set_bind_group(3, bind_group, &[]);
+        cpass.dispatch_workgroups(
+            instance_count.min(max_groups),
+            instance_count.div_ceil(max_groups),
+            1,
+        );
+    }
+}
+
+/// Material order of the draws, see [`Visibility::set_draw_sorting`].
+struct SortDraws {
+    pipeline: ComputeHandle,
+    layout: BindGroupLayout,
+    sorted: RefCell<SortedDraws>,
+}
+
+struct SortedDraws {
+    buffer: ResizableBuffer<DrawIndexedIndirect>,
+    // Instance indices by material, then by mesh.
+    order: ResizableBuffer<u32>,
+    // Material and mesh of every instance when `order` was built.
+    keys: Vec<(u32, u32)>,
+    // Keyed by the draws, `order` and `buffer`, all are replaced when they grow.
+    bind_group: Option<([wgpu::Id<wgpu::Buffer>; 3], wgpu::BindGroup)>,
+}
+
+impl SortDraws {
+    fn new(world: &World) -> Result<Self> {
+        let device = world.device();
+        let storage_entry = |binding, read_only, min_binding_size| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: Some(min_binding_size),
+            },
+            count: None,
+        };
+        let layout = device.create_bind_group_layout_wrap(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Sort Draws BGL"),
+            entries: &[
+                storage_entry(0, true, DrawIndexedIndirect::NSIZE),
+                storage_entry(1, true, u32::NSIZE),
+                storage_entry(2, false, DrawIndexedIndirect::NSIZE),
+            ],
+        });
+        let desc = ComputePipelineDescriptor {
+            label: Some("Sort Draws Pipeline".into()),
+            layout: vec![layout.clone()],
+            push_constant_ranges: vec![],
+            entry_point: "sort".into(),
+        };
+        let pipeline = world
+            .get_mut::<PipelineArena>()?
+            .process_compute_pipeline_from_path(
+                Path::new("shaders").join("sort_draws.wgsl"),
+                desc,
+            )?;
+
+        let sorted = SortedDraws {
+            buffer: ResizableBuffer::new(
+                device,
+                wgpu::BufferUsages::INDIRECT | wgpu::BufferUsages::STORAGE,
+            ),
+            order: ResizableBuffer::new(
+                device,
+                wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            ),
+            keys: vec![],
+            bind_group: None,
+        };
+        Ok(Self {
+            pipeline,
+            layout,
+            sorted: RefCell::new(sorted),
+        })
+    }
+}
+
+struct SortDrawsResource<'a> {
+    pub draws: &'a ResizableBuffer<DrawIndexedIndirect>,
+}
+
+impl Pass for SortDraws {
+    type Resources<'a> = SortDrawsResource<'a>;
+
+    fn record(
+        &self,
+        world: &World,
+        encoder: &mut ProfilerCommandEncoder,
+        resources: Self::Resources<'_>,
+    ) {
+        let instances = world.unwrap::<InstancePool>();
+        let arena = world.unwrap::<PipelineArena>();
+        let device = world.device();
+
+        let mut sorted = self.sorted.borrow_mut();
+        let sorted = &mut *sorted;
+        let keys = instances
+            .instances_data
+            .iter()
+            .take(resources.draws.len())
+            .map(|instance| (instance.material.0, instance.mesh.0));
+        if !sorted.keys.iter().copied().eq(keys.clone()) {
+            sorted.keys = keys.collect();
+            let mut order: Vec<u32> = (0..sorted.keys.len() as u32).collect();
+            order.sort_by_key(|&index| sorted.keys[index as usize]);
+            // Emptied first, so growing copies nothing over the new order.
+            sorted.order.clear();
+            sorted.order.set_len(device, encoder, order.len());
+            world
+                .queue()
+                .write_buffer(&sorted.order, 0, bytemuck::cast_slice(&order));
+        }
+        sorted.buffer.set_len(device, encoder, sorted.order.len());
+        if sorted.buffer.is_empty() {
+            return;
+        }
+
+        let key = [
+            resources.draws.global_id(),
+            sorted.order.global_id(),
+            sorted.buffer.global_id(),
+        ];
+        if !sorted
+            .bind_group
+            .as_ref()
+            .is_some_and(|(cached, _)| *cached == key)
+        {
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Sort Draws BG"),
+                layout: &self.layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: resources.draws.as_tight_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: sorted.order.as_tight_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: sorted.buffer.as_tight_binding(),
+                    },
+                ],
+            });
+            sorted.bind_group = Some((key, bind_group));
+        }
+
+        let (_, bind_group) = sorted.bind_group.as_ref().unwrap();
+        let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Sort Draws Pass"),
+        });
+        cpass.set_pipeline(arena.get_pipeline(self.pipeline));
+        cpass.set_bind_group(0, bind_group, &[]);
+        let num_dispatches = align_to(sorted.buffer.len() as _, 64) / 64;
+        cpass.dispatch_workgroups(num_dispatches, 1, 1);
+    }
+}
@@ -0,0 +1,168 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use color_eyre::{eyre::WrapErr, Result};
+use components::world::World;
+use libloading::Library;
+
+use crate::{RenderContext, UpdateContext};
+
+/// Symbol exported by [`declare_plugin!`].
+pub const PLUGIN_ENTRY: &[u8] = b"_voidin_create_plugin\0";
+
+/// Code living in a `cdylib` that is reloaded whenever it gets rebuilt.
+///
+/// The library has to be built by the same compiler against the same `app` crate,
+/// trait objects and `World` resources cross the boundary as plain Rust types.
+/// Everything the plugin owns is dropped on reload, keep long-lived state in the `World`.
+pub trait Plugin: 'static {
+    fn init(&mut self, _world: &World) -> Result<()> {
+        Ok(())
+    }
+    /// Runs after the example update with the same encoder.
+    fn update(&mut self, _ctx: &mut UpdateContext) {}
+    /// Runs after the example render, before the frame is blitted to the target.
+    /// The context has no ui, egui is drawn once per frame by the example.
+    fn render(&mut self, _ctx: &mut RenderContext) {}
+    /// Called before the library is unloaded.
+    fn unload(&mut self, _world: &World) {}
+}
+
+/// Exports the constructor of a [`Plugin`] from a `cdylib`.
+///
+/// ```ignore
+/// voidin::declare_plugin!(MyPlugin::default);
+/// ```
+#[macro_export]
+macro_rules! declare_plugin {
+    ($constructor:path) => {
+        #[no_mangle]
+        pub fn _voidin_create_plugin() -> Box<dyn $crate::plugin::Plugin> {
+            Box::new($constructor())
+        }
+    };
+}
+
+struct LoadedPlugin {
+    path: PathBuf,
+    // Field order matters: the instance has to be dropped before its library.
+    instance: Box<dyn Plugin>,
+    _library: Library,
+    loaded_copy: PathBuf,
+}
+
+impl Drop for LoadedPlugin {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.loaded_copy);
+    }
+}
+
+#[derive(Default)]
+pub struct PluginHost {
+    plugins: Vec<LoadedPlugin>,
+}
+
+impl PluginHost {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.plugins.is_empty()
+    }
+
+    /// Returns the canonical path of the library that is watched for rebuilds.
+    pub fn load(&mut self, path: impl AsRef<Path>, world: &World) -> Result<PathBuf> {
+        let path = path
+            .as_ref()
+            .canonicalize()
+            .wrap_err_with(|| format!("Failed to find plugin {}", path.as_ref().display()))?;
+        let plugin = Self::load_library(&path, world)?;
+        self.plugins.push(plugin);
+        Ok(path)
+    }
+
+    /// Reloads the plugin built to `path`, the old one is initialized again if the new one fails.
+    /// Returns `false` if `path` isn't a loaded plugin.
+    pub fn reload(&mut self, path: &Path, world: &World) -> bool {
+        let Some(plugin) = self
+            .plugins
+            .iter_mut()
+            .find(|p| path.canonicalize().is_ok_and(|path| path == p.path))
+        else {
+            return false;
+        };
+
+        plugin.instance.unload(world);
+        match Self::load_library(&plugin.path, world) {
+            Ok(new) => {
+                *plugin = new;
+                log::info!("Reloaded plugin {}", plugin.path.display());
+            }
+            Err(err) => {
+                log::error!("Plugin reload failed: {err:?}");
+                if let Err(err) = plugin.instance.init(world) {
+                    log::error!("Failed to restore the previous plugin: {err:?}");
+                }
+            }
+        }
+        true
+    }
+
+    pub fn update(&mut self, ctx: &mut UpdateContext) {
+        for plugin in &mut self.plugins {
+            plugin.instance.update(ctx);
+        }
+    }
+
+    pub fn render(&mut self, ctx: &mut RenderContext) {
+        for plugin in &mut self.plugins {
+            plugin.instance.render(ctx);
+        }
+    }
+
+    pub fn unload_all(&mut self, world: &World) {
+        for mut plugin in self.plugins.drain(..) {
+            plugin.instance.unload(world);
+        }
+    }
+
+    fn load_library(path: &Path, world: &World) -> Result<LoadedPlugin> {
+        // Loading a copy lets cargo overwrite the original and defeats the loader cache.
+        static GENERATION: AtomicUsize = AtomicUsize::new(0);
+        let generation = GENERATION.fetch_add(1, Ordering::Relaxed);
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+        let loaded_copy = std::env::temp_dir().join(format!(
+            "voidin-{}-{generation}-{file_name}",
+            std::process::id()
+        ));
+        std::fs::copy(path, &loaded_copy)
+            .wrap_err_with(|| format!("Failed to copy plugin {}", path.display()))?;
+        Self::instantiate(path, loaded_copy.clone(), world).inspect_err(|_| {
+            let _ = std::fs::remove_file(&loaded_copy);
+        })
+    }
+
+    fn instantiate(path: &Path, loaded_copy: PathBuf, world: &World) -> Result<LoadedPlugin> {
+        let library = unsafe { Library::new(&loaded_copy) }
+            .wrap_err_with(|| format!("Failed to load plugin {}", path.display()))?;
+        let mut instance = unsafe {
+            let create = library
+                .get::<fn() -> Box<dyn Plugin>>(PLUGIN_ENTRY)
+                .wrap_err_with(|| format!("{} is missing declare_plugin!", path.display()))?;
+            create()
+        };
+        instance
+            .init(world)
+            .wrap_err_with(|| format!("Failed to init plugin {}", path.display()))?;
+
+        Ok(LoadedPlugin {
+            path: path.to_path_buf(),
+            instance,
+            _library: library,
+            loaded_copy,
+        })
+    }
+}
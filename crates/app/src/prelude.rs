@@ -1,11 +1,14 @@
+#[cfg(feature = "egui")]
+pub use crate::egui;
 pub use crate::{
-    egui, models,
+    models,
     pass::{self, Pass},
     pipeline::{self, ComputeHandle, PipelineArena, RenderHandle, VertexState},
-    run, run_default, Camera, CameraUniform, CameraUniformBinding, Example, GltfDocument, Gpu,
-    Instance, InstanceId, InstancePool, LerpExt, LogicalSize, MaterialId, NonZeroSized,
-    ResizableBuffer, ResizableBufferExt, UpdateContext, WindowBuilder, WrappedBindGroupLayout,
-    {App, RenderContext}, {Light, LightPool},
+    run, run_default, Camera, CameraUniform, CameraUniformBinding, DebugDraw, Editor, Example,
+    FrameArena, GltfDocument, Gpu, Instance, InstanceId, InstancePool, LerpExt, LiveParams,
+    LogicalSize, MaterialId, NonZeroSized, PrePasses, ResizableBuffer, ResizableBufferExt,
+    SampleAssets, SampleModel, SceneBindings, SceneGraph, SceneRng, TestScene, Timeline,
+    UpdateContext, WindowBuilder, WrappedBindGroupLayout, {App, RenderContext}, {Light, LightPool},
 };
 pub use glam::*;
 pub use pools::*;
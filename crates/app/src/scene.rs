@@ -0,0 +1,254 @@
+use color_eyre::{eyre::bail, Result};
+use glam::Mat4;
+
+use crate::{InstanceId, InstancePool};
+
+/// Handle to a [`SceneGraph`] node, goes stale once the node is removed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NodeId(pub u32, u32);
+
+impl NodeId {
+    pub fn generation(&self) -> u32 {
+        self.1
+    }
+}
+
+struct Node {
+    name: Option<String>,
+    local: Mat4,
+    world: Mat4,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+    instances: Vec<InstanceId>,
+    // Raised when `local` or the parent changed, `world` is recomputed on update.
+    dirty: bool,
+}
+
+/// Named nodes with parent relative transforms, moving the instances bound to them.
+///
+/// Ticked by `App::update` after the [`crate::AnimationPlayer`], only subtrees below a
+/// changed node are walked again. Instances have to be dynamic to follow their node,
+/// static ones keep the transform they were added with.
+#[derive(Default)]
+pub struct SceneGraph {
+    nodes: Vec<Option<Node>>,
+    generations: Vec<u32>,
+    free: Vec<u32>,
+    roots: Vec<NodeId>,
+    dirty: bool,
+}
+
+impl SceneGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a node under `parent`, or a root without one.
+    pub fn spawn(
+        &mut self,
+        parent: Option<NodeId>,
+        name: Option<&str>,
+        local: Mat4,
+    ) -> Result<NodeId> {
+        if let Some(parent) = parent {
+            self.node(parent)?;
+        }
+        let node = Node {
+            name: name.map(str::to_owned),
+            local,
+            world: local,
+            parent,
+            children: vec![],
+            instances: vec![],
+            dirty: true,
+        };
+        let id = match self.free.pop() {
+            Some(index) => {
+                self.nodes[index as usize] = Some(node);
+                NodeId(index, self.generations[index as usize])
+            }
+            None => {
+                self.nodes.push(Some(node));
+                self.generations.push(0);
+                NodeId(self.nodes.len() as u32 - 1, 0)
+            }
+        };
+        match parent {
+            Some(parent) => self.node_mut(parent)?.children.push(id),
+            None => self.roots.push(id),
+        }
+        self.dirty = true;
+        Ok(id)
+    }
+
+    /// Removes the node with its whole subtree and the instances bound to them.
+    pub fn remove(&mut self, id: NodeId, instances: &mut InstancePool) -> Result<()> {
+        self.unlink(id)?;
+        let mut stack = vec![id];
+        while let Some(id) = stack.pop() {
+            let index = id.0 as usize;
+            let node = self.nodes[index].take().unwrap();
+            self.generations[index] = self.generations[index].wrapping_add(1);
+            self.free.push(id.0);
+            stack.extend(node.children);
+            for instance in node.instances {
+                if instances.contains(instance) {
+                    instances.remove(instance)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Moves the node with its subtree under `parent`, keeping its local transform.
+    pub fn attach(&mut self, id: NodeId, parent: NodeId) -> Result<()> {
+        let mut ancestor = Some(parent);
+        while let Some(node) = ancestor {
+            if node == id {
+                bail!("Node {} can't be attached below itself", id.0);
+            }
+            ancestor = self.node(node)?.parent;
+        }
+        self.unlink(id)?;
+        self.node_mut(parent)?.children.push(id);
+        self.link(id, Some(parent))
+    }
+
+    /// Makes the node a root, keeping its local transform.
+    pub fn detach(&mut self, id: NodeId) -> Result<()> {
+        self.unlink(id)?;
+        self.roots.push(id);
+        self.link(id, None)
+    }
+
+    /// Binds an instance to the node, it follows the node's world transform from now on.
+    pub fn bind(&mut self, id: NodeId, instance: InstanceId) -> Result<()> {
+        let node = self.node_mut(id)?;
+        node.instances.push(instance);
+        node.dirty = true;
+        self.dirty = true;
+        Ok(())
+    }
+
+    pub fn set_local(&mut self, id: NodeId, local: Mat4) -> Result<()> {
+        let node = self.node_mut(id)?;
+        node.local = local;
+        node.dirty = true;
+        self.dirty = true;
+        Ok(())
+    }
+
+    pub fn local(&self, id: NodeId) -> Option<Mat4> {
+        self.get(id).map(|node| node.local)
+    }
+
+    /// As of the last [`SceneGraph::update`].
+    pub fn world_transform(&self, id: NodeId) -> Option<Mat4> {
+        self.get(id).map(|node| node.world)
+    }
+
+    pub fn name(&self, id: NodeId) -> Option<&str> {
+        self.get(id)?.name.as_deref()
+    }
+
+    pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self.get(id)?.parent
+    }
+
+    pub fn children(&self, id: NodeId) -> &[NodeId] {
+        self.get(id).map_or(&[], |node| &node.children)
+    }
+
+    pub fn instances(&self, id: NodeId) -> &[InstanceId] {
+        self.get(id).map_or(&[], |node| &node.instances)
+    }
+
+    pub fn roots(&self) -> &[NodeId] {
+        &self.roots
+    }
+
+    /// First node called `name`.
+    pub fn find(&self, name: &str) -> Option<NodeId> {
+        self.nodes
+            .iter()
+            .enumerate()
+            .find(|(_, node)| {
+                node.as_ref()
+                    .is_some_and(|node| node.name.as_deref() == Some(name))
+            })
+            .map(|(index, _)| NodeId(index as u32, self.generations[index]))
+    }
+
+    pub fn contains(&self, id: NodeId) -> bool {
+        self.get(id).is_some()
+    }
+
+    /// Recomputes the world transforms below changed nodes and moves their instances.
+    pub fn update(&mut self, instances: &mut InstancePool) {
+        if !std::mem::take(&mut self.dirty) {
+            return;
+        }
+        let mut stack: Vec<_> = self
+            .roots
+            .iter()
+            .map(|&root| (root, Mat4::IDENTITY, false))
+            .collect();
+        while let Some((id, parent_world, parent_moved)) = stack.pop() {
+            let node = self.nodes[id.0 as usize].as_mut().unwrap();
+            let moved = std::mem::take(&mut node.dirty) || parent_moved;
+            if moved {
+                node.world = parent_world * node.local;
+                for &instance in &node.instances {
+                    // Static instances added where their node is are left alone.
+                    let current = instances
+                        .contains(instance)
+                        .then(|| instances.instances_data[instance.0 as usize].transform);
+                    if current.is_some_and(|current| current != node.world) {
+                        instances.set_transform(instance, node.world);
+                    }
+                }
+            }
+            stack.extend(
+                node.children
+                    .iter()
+                    .map(|&child| (child, node.world, moved)),
+            );
+        }
+    }
+
+    fn get(&self, id: NodeId) -> Option<&Node> {
+        if self.generations.get(id.0 as usize) != Some(&id.1) {
+            return None;
+        }
+        self.nodes[id.0 as usize].as_ref()
+    }
+
+    fn node(&self, id: NodeId) -> Result<&Node> {
+        match self.get(id) {
+            Some(node) => Ok(node),
+            None => bail!("Node {} of generation {} is stale", id.0, id.1),
+        }
+    }
+
+    fn node_mut(&mut self, id: NodeId) -> Result<&mut Node> {
+        self.node(id)?;
+        Ok(self.nodes[id.0 as usize].as_mut().unwrap())
+    }
+
+    // Takes the node out of its parent's children or the roots.
+    fn unlink(&mut self, id: NodeId) -> Result<()> {
+        match self.node(id)?.parent {
+            Some(parent) => self.node_mut(parent)?.children.retain(|&child| child != id),
+            None => self.roots.retain(|&root| root != id),
+        }
+        Ok(())
+    }
+
+    fn link(&mut self, id: NodeId, parent: Option<NodeId>) -> Result<()> {
+        let node = self.node_mut(id)?;
+        node.parent = parent;
+        node.dirty = true;
+        self.dirty = true;
+        Ok(())
+    }
+}
@@ -0,0 +1,389 @@
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter},
+    path::{Path, PathBuf},
+};
+
+use ahash::AHashMap;
+use color_eyre::{eyre::WrapErr, Result};
+use glam::{EulerRot, Mat4, Quat, Vec3};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    timeline::CameraKey, AnimationId, AnimationPlayer, AnyLight, App, Camera, DirectionalLight,
+    GltfDocument, InstanceId, InstancePool, Light, LightKind, LightPool, Material, MaterialId,
+    MaterialPool, SampleAssets, SampleModel, SpotLight,
+};
+
+/// Models, lights and camera of a scene, kept in a JSON file so a setup can live in data
+/// instead of an example's `setup_scene`. Missing fields take their defaults.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Scene {
+    pub camera: Option<CameraKey>,
+    pub models: Vec<SceneModel>,
+    pub point_lights: Vec<ScenePointLight>,
+    pub spot_lights: Vec<SceneSpotLight>,
+    pub area_lights: Vec<SceneAreaLight>,
+    pub sun: Option<SceneSun>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ModelSource {
+    Path(PathBuf),
+    /// Fetched through [`SampleAssets::from_env`].
+    Sample(SampleModel),
+}
+
+/// A glTF document and the places it is instantiated at.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SceneModel {
+    pub source: ModelSource,
+    pub instances: Vec<SceneInstance>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SceneInstance {
+    pub transform: SceneTransform,
+    /// Replaces parts of every material of the model.
+    pub material: Option<MaterialOverride>,
+    /// Plays the clip of this index in a loop, the model is instantiated animated then
+    /// and its materials are kept.
+    pub animation: Option<usize>,
+}
+
+/// Transform spelled out for editing by hand.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SceneTransform {
+    pub translation: [f32; 3],
+    /// Degrees around x, y and z, applied around y first, then x, then z.
+    pub rotation: [f32; 3],
+    pub scale: [f32; 3],
+}
+
+impl Default for SceneTransform {
+    fn default() -> Self {
+        Self {
+            translation: [0.; 3],
+            rotation: [0.; 3],
+            scale: [1.; 3],
+        }
+    }
+}
+
+impl SceneTransform {
+    pub fn from_matrix(matrix: Mat4) -> Self {
+        let (scale, rotation, translation) = matrix.to_scale_rotation_translation();
+        let (y, x, z) = rotation.to_euler(EulerRot::YXZ);
+        Self {
+            translation: translation.to_array(),
+            rotation: [x, y, z].map(f32::to_degrees),
+            scale: scale.to_array(),
+        }
+    }
+
+    pub fn matrix(&self) -> Mat4 {
+        let [x, y, z] = self.rotation.map(f32::to_radians);
+        Mat4::from_scale_rotation_translation(
+            Vec3::from(self.scale),
+            Quat::from_euler(EulerRot::YXZ, y, x, z),
+            Vec3::from(self.translation),
+        )
+    }
+}
+
+/// Material properties replacing the ones of the model, textures are kept.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MaterialOverride {
+    /// Linear color, alpha is the alpha cutoff.
+    pub base_color: Option<[f32; 4]>,
+    pub emissive_strength: Option<f32>,
+    pub ior: Option<f32>,
+    pub transmission: Option<f32>,
+    pub clearcoat: Option<f32>,
+    pub clearcoat_roughness: Option<f32>,
+}
+
+impl MaterialOverride {
+    pub fn apply(&self, material: &Material) -> Material {
+        let mut material = *material;
+        if let Some(base_color) = self.base_color {
+            material.base_color = base_color.into();
+        }
+        material.emissive_strength = self.emissive_strength.unwrap_or(material.emissive_strength);
+        material.ior = self.ior.unwrap_or(material.ior);
+        material.transmission = self.transmission.unwrap_or(material.transmission);
+        material.clearcoat = self.clearcoat.unwrap_or(material.clearcoat);
+        material.clearcoat_roughness = self
+            .clearcoat_roughness
+            .unwrap_or(material.clearcoat_roughness);
+        material
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ScenePointLight {
+    pub position: [f32; 3],
+    pub radius: f32,
+    pub color: [f32; 3],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SceneSpotLight {
+    pub position: [f32; 3],
+    pub direction: [f32; 3],
+    pub radius: f32,
+    pub color: [f32; 3],
+    /// Degrees from the axis.
+    pub inner_angle: f32,
+    pub outer_angle: f32,
+}
+
+/// Rectangle of `size` facing down its local z.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SceneAreaLight {
+    pub color: [f32; 3],
+    pub intensity: f32,
+    pub size: [f32; 2],
+    pub transform: SceneTransform,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SceneSun {
+    /// Points from the scene towards the sun.
+    pub direction: [f32; 3],
+    pub intensity: f32,
+    pub color: [f32; 3],
+}
+
+/// What [`Scene::instantiate`] added to the pools, for [`Scene::capture`] to read the
+/// placements back.
+#[derive(Debug, Clone, Default)]
+pub struct SpawnedScene {
+    // Per model, per placement, in the order of the scene.
+    placements: Vec<Vec<SpawnedPlacement>>,
+}
+
+#[derive(Debug, Clone)]
+enum SpawnedPlacement {
+    /// Instances with the transform they were added at.
+    Static(Vec<(InstanceId, Mat4)>),
+    Animated(AnimationId),
+}
+
+impl SpawnedScene {
+    /// Instances of a placement of a model, empty for animated ones.
+    pub fn instances(&self, model: usize, placement: usize) -> Vec<InstanceId> {
+        match self
+            .placements
+            .get(model)
+            .and_then(|model| model.get(placement))
+        {
+            Some(SpawnedPlacement::Static(instances)) => {
+                instances.iter().map(|&(id, _)| id).collect()
+            }
+            _ => vec![],
+        }
+    }
+}
+
+impl Scene {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path)
+            .wrap_err_with(|| format!("Failed to open scene {}", path.display()))?;
+        serde_json::from_reader(BufReader::new(file))
+            .wrap_err_with(|| format!("Failed to parse scene {}", path.display()))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let file = File::create(path)
+            .wrap_err_with(|| format!("Failed to create scene {}", path.display()))?;
+        serde_json::to_writer_pretty(BufWriter::new(file), self)?;
+        Ok(())
+    }
+
+    /// Imports the models and adds their instances and the lights to the pools, from an
+    /// example's `setup_scene`.
+    pub fn instantiate(&self, app: &mut App) -> Result<SpawnedScene> {
+        let samples = SampleAssets::from_env();
+        let mut spawned = SpawnedScene::default();
+        for model in &self.models {
+            let path = match &model.source {
+                ModelSource::Path(path) => path.clone(),
+                ModelSource::Sample(sample) => samples.fetch(*sample)?,
+            };
+            let document = GltfDocument::import(app, &path)?;
+            let mut overridden: AHashMap<u32, MaterialId> = AHashMap::new();
+            let mut placements = vec![];
+            for placement in &model.instances {
+                let transform = placement.transform.matrix();
+                if let Some(clip) = placement.animation {
+                    let id = document.instantiate_animated(app, transform)?;
+                    app.world.get_mut::<AnimationPlayer>()?.play(id, Some(clip));
+                    placements.push(SpawnedPlacement::Animated(id));
+                    continue;
+                }
+                let mut instances = document.get_scene_instances(transform);
+                if let Some(material) = &placement.material {
+                    overridden.clear();
+                    let mut materials = app.world.get_mut::<MaterialPool>()?;
+                    for instance in &mut instances {
+                        instance.material = match overridden.get(&instance.material.0) {
+                            Some(&id) => id,
+                            None => {
+                                let original = materials.get(instance.material).copied();
+                                let changed = material.apply(&original.unwrap_or_default());
+                                let id = match materials.find(&changed) {
+                                    Some(id) => id,
+                                    None => materials.add(changed)?,
+                                };
+                                overridden.insert(instance.material.0, id);
+                                id
+                            }
+                        };
+                    }
+                }
+                let ids = app.world.get_mut::<InstancePool>()?.add(&instances)?;
+                placements.push(SpawnedPlacement::Static(
+                    ids.into_iter()
+                        .zip(instances.iter().map(|instance| instance.transform))
+                        .collect(),
+                ));
+            }
+            spawned.placements.push(placements);
+        }
+
+        let mut lights = app.world.get_mut::<LightPool>()?;
+        let points: Vec<_> = self
+            .point_lights
+            .iter()
+            .map(|light| Light::new(light.position.into(), light.radius, light.color.into()))
+            .collect();
+        if !points.is_empty() {
+            lights.add_point_light(&points)?;
+        }
+        let spots: Vec<_> = self
+            .spot_lights
+            .iter()
+            .map(|light| {
+                SpotLight::new(
+                    light.position.into(),
+                    light.direction.into(),
+                    light.radius,
+                    light.color.into(),
+                    light.inner_angle.to_radians(),
+                    light.outer_angle.to_radians(),
+                )
+            })
+            .collect();
+        if !spots.is_empty() {
+            lights.add_spot_light(&spots)?;
+        }
+        if let Some(sun) = self.sun {
+            lights.set_sun(DirectionalLight::new(
+                sun.direction.into(),
+                sun.intensity,
+                sun.color.into(),
+            ));
+        }
+        drop(lights);
+        for light in &self.area_lights {
+            app.add_area_light(
+                light.color.into(),
+                light.intensity,
+                light.size.into(),
+                light.transform.matrix(),
+            )?;
+        }
+
+        if let Some(camera) = &self.camera {
+            camera.apply(&mut app.world.get_mut::<Camera>()?);
+        }
+        Ok(spawned)
+    }
+
+    /// Reads the placements of the models and the point lights, spot lights and sun back
+    /// from the pools, to save a scene edited in the app.
+    ///
+    /// A placement follows the first of its instances still in the pool and is dropped
+    /// once all of them are removed, animated ones follow their [`AnimatedScene`].
+    /// Disabled lights are left out, area lights and the camera are kept as they are.
+    ///
+    /// [`AnimatedScene`]: crate::AnimatedScene
+    pub fn capture(&mut self, app: &App, spawned: &SpawnedScene) -> Result<()> {
+        let instances = app.world.get::<InstancePool>()?;
+        let player = app.world.get::<AnimationPlayer>()?;
+        for (model, placements) in self.models.iter_mut().zip(&spawned.placements) {
+            let mut placements = placements.iter();
+            model.instances.retain_mut(|placement| {
+                let transform = match placements.next() {
+                    Some(SpawnedPlacement::Static(added)) => {
+                        let Some((id, spawned_at)) =
+                            added.iter().find(|(id, _)| instances.contains(*id))
+                        else {
+                            return false;
+                        };
+                        let current = instances.instances_data[id.0 as usize].transform;
+                        current * spawned_at.inverse() * placement.transform.matrix()
+                    }
+                    Some(&SpawnedPlacement::Animated(id)) => player.scene(id).transform,
+                    None => return true,
+                };
+                placement.transform = SceneTransform::from_matrix(transform);
+                true
+            });
+        }
+
+        let lights = app.world.get::<LightPool>()?;
+        let lights = &*lights;
+        let live = |kind: LightKind| {
+            lights
+                .ids(kind)
+                .into_iter()
+                .filter(move |&id| lights.is_light_enabled(id))
+                .filter_map(move |id| lights.light(id))
+        };
+        self.point_lights = live(LightKind::Point)
+            .filter_map(|light| match light {
+                AnyLight::Point(light) => Some(ScenePointLight {
+                    position: light.position.to_array(),
+                    radius: light.radius,
+                    color: light.color.to_array(),
+                }),
+                _ => None,
+            })
+            .collect();
+        self.spot_lights = live(LightKind::Spot)
+            .filter_map(|light| match light {
+                AnyLight::Spot(light) => Some(SceneSpotLight {
+                    position: light.position.to_array(),
+                    direction: light.direction.to_array(),
+                    radius: light.radius,
+                    color: light.color.to_array(),
+                    inner_angle: light.cos_inner.clamp(-1., 1.).acos().to_degrees(),
+                    outer_angle: light.cos_outer.clamp(-1., 1.).acos().to_degrees(),
+                }),
+                _ => None,
+            })
+            .collect();
+        let sun = lights.sun();
+        self.sun = (sun != DirectionalLight::default()).then(|| SceneSun {
+            direction: sun.direction.to_array(),
+            intensity: sun.intensity,
+            color: sun.color.to_array(),
+        });
+        Ok(())
+    }
+
+    /// Takes the current camera of the app, to save the view the scene was left at.
+    pub fn capture_camera(&mut self, app: &App) -> Result<()> {
+        self.camera = Some(CameraKey::from_camera(&app.world.get::<Camera>()?));
+        Ok(())
+    }
+}
@@ -0,0 +1,178 @@
+use std::ops::Range;
+
+use ahash::AHashMap;
+use color_eyre::Result;
+use components::Instance;
+use glam::{IVec3, Vec3};
+use pools::InstancePool;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StreamingSettings {
+    /// Edge length of the cubic chunks instances are sorted into.
+    pub chunk_size: f32,
+    /// Chunks closer than this to the camera get loaded into the pool.
+    pub load_distance: f32,
+    /// Loaded chunks further than this are released, should exceed `load_distance`.
+    pub unload_distance: f32,
+    /// Upper bound on instance pool slots the streamer may occupy.
+    pub slot_budget: u32,
+}
+
+impl Default for StreamingSettings {
+    fn default() -> Self {
+        Self {
+            chunk_size: 32.,
+            load_distance: 96.,
+            unload_distance: 128.,
+            slot_budget: 1 << 16,
+        }
+    }
+}
+
+struct Chunk {
+    instances: Vec<Instance>,
+    slots: Option<Range<u32>>,
+}
+
+/// Keeps instances on the cpu in spatial chunks and only the ones near the camera in the
+/// `InstancePool`.
+///
+/// Released chunks leave their slots in the pool with `Instance::INACTIVE` raised, so culling
+/// and tracing skip them until another chunk reuses the range.
+pub struct Streaming {
+    settings: StreamingSettings,
+    chunks: AHashMap<IVec3, Chunk>,
+    free_slots: Vec<Range<u32>>,
+    slot_count: u32,
+}
+
+impl Streaming {
+    pub fn new(settings: StreamingSettings) -> Self {
+        Self {
+            settings,
+            chunks: AHashMap::new(),
+            free_slots: vec![],
+            slot_count: 0,
+        }
+    }
+
+    pub fn settings(&self) -> StreamingSettings {
+        self.settings
+    }
+
+    /// Only distances and budget apply to already added instances, chunk size needs a `clear`.
+    pub fn set_settings(&mut self, settings: StreamingSettings) {
+        self.settings = settings;
+    }
+
+    /// Sorts streamed instances into chunks by their translation, nothing is loaded until `update`.
+    pub fn add(&mut self, instances: &[Instance]) {
+        for instance in instances {
+            let position = instance.transform.w_axis.truncate();
+            let key = (position / self.settings.chunk_size).floor().as_ivec3();
+            self.chunks
+                .entry(key)
+                .or_insert_with(|| Chunk {
+                    instances: vec![],
+                    slots: None,
+                })
+                .instances
+                .push(*instance);
+        }
+    }
+
+    /// Forgets every chunk, the slots stay in the pool until it is cleared.
+    pub fn clear(&mut self, pool: &mut InstancePool) {
+        for chunk in self.chunks.values_mut() {
+            if let Some(slots) = chunk.slots.take() {
+                slots
+                    .filter_map(|slot| pool.id(slot))
+                    .for_each(|id| pool.set_active(id, false));
+            }
+        }
+        self.chunks.clear();
+        self.free_slots.clear();
+        self.slot_count = 0;
+    }
+
+    pub fn chunk_count(&self) -> usize {
+        self.chunks.len()
+    }
+
+    pub fn loaded_chunk_count(&self) -> usize {
+        self.chunks.values().filter(|c| c.slots.is_some()).count()
+    }
+
+    pub fn slot_count(&self) -> u32 {
+        self.slot_count
+    }
+
+    /// Loads chunks near `eye` and releases far ones.
+    ///
+    /// Returns `true` if the pool contents changed and the scene buffers need a refresh.
+    pub fn update(&mut self, pool: &mut InstancePool, eye: Vec3) -> Result<bool> {
+        let mut changed = false;
+        let mut to_load = vec![];
+        for (key, chunk) in self.chunks.iter_mut() {
+            let distance = self.settings.distance_to(*key, eye);
+            match chunk.slots.take() {
+                Some(slots) if distance > self.settings.unload_distance => {
+                    slots
+                        .clone()
+                        .filter_map(|slot| pool.id(slot))
+                        .for_each(|id| pool.set_active(id, false));
+                    self.free_slots.push(slots);
+                    changed = true;
+                }
+                Some(slots) => chunk.slots = Some(slots),
+                None if distance <= self.settings.load_distance => to_load.push((distance, *key)),
+                None => {}
+            }
+        }
+
+        to_load.sort_by(|a, b| a.0.total_cmp(&b.0));
+        for (_, key) in to_load {
+            let chunk = self.chunks.get_mut(&key).unwrap();
+            let len = chunk.instances.len() as u32;
+            let slots = if let Some(slots) = Self::take_free(&mut self.free_slots, len) {
+                // Slots removed from the pool behind our back may be reused by `insert`.
+                let Some(first) = pool.id(slots.start) else {
+                    log::warn!("Streamed slot {} was removed from the pool", slots.start);
+                    continue;
+                };
+                pool.write(first, &chunk.instances);
+                slots
+            } else if self.slot_count + len <= self.settings.slot_budget {
+                let ids = pool.add(&chunk.instances)?;
+                self.slot_count += len;
+                ids[0].0..ids[0].0 + len
+            } else {
+                log::debug!("Streaming budget exhausted, chunk {key} stays unloaded");
+                continue;
+            };
+            chunk.slots = Some(slots);
+            changed = true;
+        }
+
+        Ok(changed)
+    }
+
+    // First fit, the unused tail goes back to the free list.
+    fn take_free(free_slots: &mut Vec<Range<u32>>, len: u32) -> Option<Range<u32>> {
+        let idx = free_slots.iter().position(|r| r.len() as u32 >= len)?;
+        let range = free_slots.swap_remove(idx);
+        let (taken, rest) = (range.start..range.start + len, range.start + len..range.end);
+        if !rest.is_empty() {
+            free_slots.push(rest);
+        }
+        Some(taken)
+    }
+}
+
+impl StreamingSettings {
+    fn distance_to(&self, key: IVec3, eye: Vec3) -> f32 {
+        let min = key.as_vec3() * self.chunk_size;
+        let max = min + self.chunk_size;
+        eye.clamp(min, max).distance(eye)
+    }
+}
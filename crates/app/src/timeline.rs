@@ -0,0 +1,438 @@
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter},
+    path::{Path, PathBuf},
+};
+
+use color_eyre::{eyre::WrapErr, Result};
+use components::Camera;
+use dolly::prelude::{Position, YawPitch};
+use glam::Vec3;
+use serde::{Deserialize, Serialize};
+
+use crate::LiveParams;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Interpolation {
+    Step,
+    #[default]
+    Linear,
+    Smooth,
+}
+
+/// Values a [`Track`] can blend between keyframes.
+pub trait Keyable: Clone {
+    fn blend(&self, next: &Self, t: f32) -> Self;
+}
+
+impl Keyable for f32 {
+    fn blend(&self, next: &Self, t: f32) -> Self {
+        self + (next - self) * t
+    }
+}
+
+impl Keyable for bool {
+    fn blend(&self, _next: &Self, _t: f32) -> Self {
+        *self
+    }
+}
+
+impl Keyable for String {
+    fn blend(&self, _next: &Self, _t: f32) -> Self {
+        self.clone()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CameraKey {
+    pub position: [f32; 3],
+    /// Degrees, same as the camera rig.
+    pub yaw: f32,
+    pub pitch: f32,
+}
+
+impl CameraKey {
+    pub fn from_camera(camera: &Camera) -> Self {
+        let yaw_pitch = camera.rig.driver::<YawPitch>();
+        Self {
+            position: camera.rig.driver::<Position>().position.to_array(),
+            yaw: yaw_pitch.yaw_degrees,
+            pitch: yaw_pitch.pitch_degrees,
+        }
+    }
+
+    pub fn apply(&self, camera: &mut Camera) {
+        camera.rig.driver_mut::<Position>().position = Vec3::from(self.position);
+        let yaw_pitch = camera.rig.driver_mut::<YawPitch>();
+        yaw_pitch.yaw_degrees = self.yaw;
+        yaw_pitch.pitch_degrees = self.pitch;
+    }
+}
+
+impl Keyable for CameraKey {
+    fn blend(&self, next: &Self, t: f32) -> Self {
+        let position = Vec3::from(self.position).lerp(Vec3::from(next.position), t);
+        Self {
+            position: position.to_array(),
+            yaw: self.yaw.blend(&next.yaw, t),
+            pitch: self.pitch.blend(&next.pitch, t),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Keyframe<T> {
+    /// In the [`TimeUnit`] of the timeline.
+    pub time: f64,
+    pub value: T,
+    /// How to get from this keyframe to the next one.
+    #[serde(default)]
+    pub interpolation: Interpolation,
+}
+
+/// Keyframes sorted by time.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Track<T> {
+    pub name: String,
+    pub keys: Vec<Keyframe<T>>,
+}
+
+impl<T: Keyable> Track<T> {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            keys: vec![],
+        }
+    }
+
+    /// Inserts a keyframe, replacing the one at the same time.
+    pub fn insert(&mut self, time: f64, value: T, interpolation: Interpolation) {
+        let key = Keyframe {
+            time,
+            value,
+            interpolation,
+        };
+        match self.keys.binary_search_by(|k| k.time.total_cmp(&time)) {
+            Ok(i) => self.keys[i] = key,
+            Err(i) => self.keys.insert(i, key),
+        }
+    }
+
+    pub fn remove(&mut self, index: usize) -> Keyframe<T> {
+        self.keys.remove(index)
+    }
+
+    /// Value at `time`, holds the first and the last keyframe outside of the track.
+    pub fn sample(&self, time: f64) -> Option<T> {
+        let next = self.keys.partition_point(|k| k.time <= time);
+        let (prev, next) = match (next.checked_sub(1), self.keys.get(next)) {
+            (None, next) => return next.map(|k| k.value.clone()),
+            (Some(prev), None) => return Some(self.keys[prev].value.clone()),
+            (Some(prev), Some(next)) => (&self.keys[prev], next),
+        };
+        let t = ((time - prev.time) / (next.time - prev.time)) as f32;
+        let t = match prev.interpolation {
+            Interpolation::Step => 0.,
+            Interpolation::Linear => t,
+            Interpolation::Smooth => t * t * (3. - 2. * t),
+        };
+        Some(prev.value.blend(&next.value, t))
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TimeUnit {
+    #[default]
+    Seconds,
+    Beats,
+}
+
+/// Keyframed camera, parameters, pass toggles and scene switches on a seconds or beats clock.
+///
+/// Parameter tracks drive [`LiveParams`] of the same name, toggles and scenes are
+/// queried by the example with [`Timeline::toggle`] and [`Timeline::scene`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Timeline {
+    pub bpm: f32,
+    pub unit: TimeUnit,
+    /// In `unit`.
+    pub length: f64,
+    pub looping: bool,
+    pub camera: Track<CameraKey>,
+    pub params: Vec<Track<f32>>,
+    pub toggles: Vec<Track<bool>>,
+    pub scenes: Track<String>,
+
+    #[serde(skip)]
+    pub playing: bool,
+    #[serde(skip)]
+    seconds: f64,
+    #[serde(skip)]
+    last_tick: Option<f64>,
+    #[serde(skip)]
+    path: PathBuf,
+}
+
+impl Timeline {
+    pub fn new() -> Self {
+        Self {
+            bpm: 120.,
+            unit: TimeUnit::Seconds,
+            length: 60.,
+            looping: true,
+            camera: Track::new("Camera"),
+            params: vec![],
+            toggles: vec![],
+            scenes: Track::new("Scene"),
+            playing: false,
+            seconds: 0.,
+            last_tick: None,
+            path: PathBuf::from("timeline.json"),
+        }
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path)
+            .wrap_err_with(|| format!("Failed to open timeline {}", path.display()))?;
+        let mut timeline: Self = serde_json::from_reader(BufReader::new(file))
+            .wrap_err_with(|| format!("Failed to parse timeline {}", path.display()))?;
+        timeline.path = path.to_path_buf();
+        Ok(timeline)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let file = File::create(path)
+            .wrap_err_with(|| format!("Failed to create timeline {}", path.display()))?;
+        serde_json::to_writer_pretty(BufWriter::new(file), self)?;
+        Ok(())
+    }
+
+    /// Current time in `unit`.
+    pub fn position(&self) -> f64 {
+        match self.unit {
+            TimeUnit::Seconds => self.seconds,
+            TimeUnit::Beats => self.seconds * self.bpm as f64 / 60.,
+        }
+    }
+
+    pub fn seek(&mut self, position: f64) {
+        let position = position.clamp(0., self.length);
+        self.seconds = match self.unit {
+            TimeUnit::Seconds => position,
+            TimeUnit::Beats => position * 60. / self.bpm as f64,
+        };
+    }
+
+    pub fn seconds(&self) -> f64 {
+        self.seconds
+    }
+
+    pub fn beat(&self) -> f64 {
+        self.seconds * self.bpm as f64 / 60.
+    }
+
+    /// Advances the clock by the time passed since the previous tick while playing.
+    pub fn tick(&mut self, total_time: f64) {
+        let dt = total_time - self.last_tick.unwrap_or(total_time);
+        self.last_tick = Some(total_time);
+        if !self.playing {
+            return;
+        }
+        self.seconds += dt;
+        let position = self.position();
+        if position > self.length {
+            if self.looping {
+                self.seek(position % self.length.max(f64::EPSILON));
+            } else {
+                self.seek(self.length);
+                self.playing = false;
+            }
+        }
+    }
+
+    pub fn param_track_mut(&mut self, name: &str) -> &mut Track<f32> {
+        track_mut(&mut self.params, name)
+    }
+
+    pub fn toggle_track_mut(&mut self, name: &str) -> &mut Track<bool> {
+        track_mut(&mut self.toggles, name)
+    }
+
+    pub fn param(&self, name: &str) -> Option<f32> {
+        let track = self.params.iter().find(|t| t.name == name)?;
+        track.sample(self.position())
+    }
+
+    pub fn toggle(&self, name: &str) -> Option<bool> {
+        let track = self.toggles.iter().find(|t| t.name == name)?;
+        track.sample(self.position())
+    }
+
+    pub fn scene(&self) -> Option<String> {
+        self.scenes.sample(self.position())
+    }
+
+    pub fn apply_camera(&self, camera: &mut Camera) {
+        if let Some(key) = self.camera.sample(self.position()) {
+            key.apply(camera);
+        }
+    }
+
+    pub fn apply_params(&self, params: &mut LiveParams) {
+        let position = self.position();
+        for track in &self.params {
+            if let Some(value) = track.sample(position) {
+                params.set(&track.name, value);
+            }
+        }
+    }
+
+    #[cfg(feature = "egui")]
+    pub fn ui(&mut self, ui: &mut egui::Ui, camera: &Camera, params: &LiveParams) {
+        ui.horizontal(|ui| {
+            if ui
+                .button(if self.playing { "Pause" } else { "Play" })
+                .clicked()
+            {
+                self.playing = !self.playing;
+            }
+            if ui.button("Stop").clicked() {
+                self.playing = false;
+                self.seek(0.);
+            }
+            ui.checkbox(&mut self.looping, "Loop");
+            egui::ComboBox::from_id_source("Timeline Unit")
+                .selected_text(format!("{:?}", self.unit))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut self.unit, TimeUnit::Seconds, "Seconds");
+                    ui.selectable_value(&mut self.unit, TimeUnit::Beats, "Beats");
+                });
+            ui.add(
+                egui::DragValue::new(&mut self.bpm)
+                    .clamp_range(1.0..=999.0)
+                    .suffix(" bpm"),
+            );
+            ui.add(
+                egui::DragValue::new(&mut self.length)
+                    .clamp_range(1.0..=3600.0)
+                    .prefix("Length "),
+            );
+        });
+
+        let mut position = self.position();
+        if ui
+            .add(
+                egui::Slider::new(&mut position, 0.0..=self.length)
+                    .text(format!("Beat {:.0}", self.beat().floor())),
+            )
+            .changed()
+        {
+            self.seek(position);
+        }
+
+        ui.horizontal(|ui| {
+            if ui.button("Key Camera").clicked() {
+                let key = CameraKey::from_camera(camera);
+                self.camera.insert(position, key, Interpolation::Smooth);
+            }
+            if ui.button("Key Params").clicked() {
+                for param in params.params() {
+                    self.param_track_mut(&param.name).insert(
+                        position,
+                        param.value,
+                        Interpolation::Linear,
+                    );
+                }
+            }
+        });
+
+        let length = self.length;
+        let mut seek = None;
+        let mut row = |ui: &mut egui::Ui, name: &str, times: Vec<f64>| -> Option<usize> {
+            ui.horizontal(|ui| {
+                ui.add_sized([80., 14.], egui::Label::new(name).truncate(true));
+                let size = egui::vec2(ui.available_width(), 14.);
+                let (rect, response) = ui.allocate_exact_size(size, egui::Sense::click());
+                let painter = ui.painter_at(rect);
+                painter.rect_filled(rect, 2., ui.visuals().extreme_bg_color);
+                let to_x = |time: f64| rect.left() + (time / length) as f32 * rect.width();
+                for &time in &times {
+                    let center = egui::pos2(to_x(time), rect.center().y);
+                    painter.circle_filled(center, 4., ui.visuals().selection.bg_fill);
+                }
+                painter.vline(
+                    to_x(position),
+                    rect.y_range(),
+                    ui.visuals().widgets.active.fg_stroke,
+                );
+
+                let pointer = response.interact_pointer_pos()?;
+                let pointer_time = (pointer.x - rect.left()) as f64 / rect.width() as f64 * length;
+                if response.clicked() {
+                    seek = Some(pointer_time);
+                }
+                // Right click removes the closest keyframe under the cursor.
+                if response.secondary_clicked() {
+                    return times
+                        .iter()
+                        .position(|&time| (to_x(time) - pointer.x).abs() < 5.);
+                }
+                None
+            })
+            .inner
+        };
+
+        fn times<T>(track: &Track<T>) -> Vec<f64> {
+            track.keys.iter().map(|k| k.time).collect()
+        }
+        if let Some(i) = row(ui, "Camera", times(&self.camera)) {
+            self.camera.remove(i);
+        }
+        for track in &mut self.params {
+            if let Some(i) = row(ui, &track.name, times(track)) {
+                track.remove(i);
+            }
+        }
+        for track in &mut self.toggles {
+            if let Some(i) = row(ui, &track.name, times(track)) {
+                track.remove(i);
+            }
+        }
+        if let Some(i) = row(ui, "Scene", times(&self.scenes)) {
+            self.scenes.remove(i);
+        }
+        if let Some(time) = seek {
+            self.seek(time);
+        }
+
+        ui.horizontal(|ui| {
+            let mut path = self.path.display().to_string();
+            if ui.text_edit_singleline(&mut path).changed() {
+                self.path = PathBuf::from(path);
+            }
+            if ui.button("Save").clicked() {
+                if let Err(err) = self.save(&self.path) {
+                    log::error!("{err:?}");
+                }
+            }
+            if ui.button("Load").clicked() {
+                match Self::load(&self.path) {
+                    Ok(timeline) => *self = timeline,
+                    Err(err) => log::error!("{err:?}"),
+                }
+            }
+        });
+    }
+}
+
+fn track_mut<'a, T: Keyable>(tracks: &'a mut Vec<Track<T>>, name: &str) -> &'a mut Track<T> {
+    match tracks.iter().position(|t| t.name == name) {
+        Some(i) => &mut tracks[i],
+        None => {
+            tracks.push(Track::new(name));
+            tracks.last_mut().unwrap()
+        }
+    }
+}
@@ -1,7 +1,9 @@
 mod blas;
 mod intersection;
+mod reference;
 mod tlas;
 
 pub use blas::{Bvh, BvhBuilder, BvhNode};
 pub use intersection::{Dist, Ray};
+pub use reference::{FrameDiff, ReferenceTracer, SceneRef, Shading, TraceHit};
 pub use tlas::{Tlas, TlasNode};
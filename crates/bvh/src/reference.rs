@@ -0,0 +1,355 @@
+use components::{Instance, MeshInfo};
+use glam::{vec2, vec4, Mat4, Vec3, Vec4, Vec4Swizzles};
+
+use crate::{intersection::MAX_DIST, BvhNode, Ray, TlasNode};
+
+/// Scene in the layout of the gpu trace buffers.
+///
+/// Traversal mirrors `<voidin/bvh.wgsl>` step by step, including backface culling and
+/// the child ordering, so differences against a gpu frame point at the shader.
+#[derive(Clone, Copy)]
+pub struct SceneRef<'a> {
+    pub tlas_nodes: &'a [TlasNode],
+    pub instances: &'a [Instance],
+    pub meshes: &'a [MeshInfo],
+    pub bvh_nodes: &'a [BvhNode],
+    pub vertices: &'a [Vec3],
+    pub indices: &'a [u32],
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct TraceHit {
+    /// Triangle in object space of the instance.
+    pub triangle: [Vec3; 3],
+    pub dist: f32,
+    pub instance: u32,
+}
+
+impl TraceHit {
+    /// Same as `triangle_normal` in wgsl, in object space.
+    pub fn normal(&self) -> Vec3 {
+        let [v0, v1, v2] = self.triangle;
+        (v1 - v0)
+            .normalize()
+            .cross((v1 - v2).normalize())
+            .normalize()
+    }
+}
+
+#[derive(Clone, Copy)]
+struct TraceRay {
+    eye: Vec3,
+    dir: Vec3,
+    inv_dir: Vec3,
+}
+
+impl TraceRay {
+    fn new(eye: Vec3, dir: Vec3) -> Self {
+        Self {
+            eye,
+            dir,
+            inv_dir: 1. / dir,
+        }
+    }
+}
+
+fn intersect_aabb(ray: &TraceRay, bmin: Vec3, bmax: Vec3, t: f32) -> f32 {
+    let tx1 = (bmin - ray.eye) * ray.inv_dir;
+    let tx2 = (bmax - ray.eye) * ray.inv_dir;
+    let tmax = tx1.max(tx2).min_element();
+    let tmin = tx1.min(tx2).max_element();
+    if tmax >= tmin && tmin < t && tmax > 0. {
+        tmin
+    } else {
+        MAX_DIST
+    }
+}
+
+fn intersect_trig(ray: &TraceRay, [v0, v1, v2]: [Vec3; 3], hit: &mut f32) -> bool {
+    let edge1 = v1 - v0;
+    let edge2 = v2 - v0;
+    let uvec = ray.dir.cross(edge2);
+    let det = edge1.dot(uvec);
+    if det < 1e-10 {
+        return false;
+    }
+    let inv_det = 1. / det;
+    let orig = ray.eye - v0;
+    let u = inv_det * orig.dot(uvec);
+    if !(0. ..=1.).contains(&u) {
+        return false;
+    }
+    let vvec = orig.cross(edge1);
+    let v = inv_det * ray.dir.dot(vvec);
+    if v < 0. || u + v > 1. {
+        return false;
+    }
+    let t = inv_det * edge2.dot(vvec);
+    if t > 0. && t < *hit {
+        *hit = t;
+        true
+    } else {
+        false
+    }
+}
+
+impl SceneRef<'_> {
+    pub fn trace(&self, ray: Ray) -> Option<TraceHit> {
+        let ray = TraceRay::new(ray.orig, ray.dir);
+        let mut stack = vec![0usize];
+        let mut res = None;
+        while let Some(index) = stack.pop() {
+            let node = self.tlas_nodes[index];
+            if node.is_leaf() {
+                let index = node.instance_idx;
+                match self.instances.get(index as usize) {
+                    Some(instance) if instance.is_active() => {
+                        self.intersect_instance(&ray, instance, index, &mut res)
+                    }
+                    _ => {}
+                }
+                continue;
+            }
+
+            let dist = res.map_or(MAX_DIST, |hit: TraceHit| hit.dist);
+            let mut min_index = (node.left_right & 0xffff) as usize;
+            let mut max_index = (node.left_right >> 16) as usize;
+            let (min_child, max_child) = (self.tlas_nodes[min_index], self.tlas_nodes[max_index]);
+            let mut min_dist = intersect_aabb(&ray, min_child.min, min_child.max, dist);
+            let mut max_dist = intersect_aabb(&ray, max_child.min, max_child.max, dist);
+            if min_dist > max_dist {
+                std::mem::swap(&mut min_index, &mut max_index);
+                std::mem::swap(&mut min_dist, &mut max_dist);
+            }
+            if min_dist >= dist {
+                continue;
+            }
+            if max_dist < dist {
+                stack.push(max_index);
+            }
+            stack.push(min_index);
+        }
+        res
+    }
+
+    fn intersect_instance(
+        &self,
+        ray: &TraceRay,
+        instance: &Instance,
+        index: u32,
+        res: &mut Option<TraceHit>,
+    ) {
+        let inv_transform = instance.inv_transform();
+        let ray = TraceRay::new(
+            inv_transform.transform_point3(ray.eye),
+            inv_transform.transform_vector3(ray.dir),
+        );
+        let mesh = self.meshes[instance.mesh.0 as usize];
+        let fetch_vertex = |idx: u32| {
+            let i = mesh.vertex_offset as u32 + self.indices[(mesh.base_index + idx) as usize];
+            self.vertices[i as usize]
+        };
+
+        let mut stack = vec![mesh.bvh_index as usize];
+        let mut hit = res.map_or(MAX_DIST, |hit| hit.dist);
+        while let Some(node_index) = stack.pop() {
+            let node = self.bvh_nodes[node_index];
+            if node.is_leaf() {
+                for i in 0..node.count {
+                    let idx = node.left_first + i;
+                    let triangle = [0, 1, 2].map(|v| fetch_vertex(3 * idx + v));
+                    if intersect_trig(&ray, triangle, &mut hit) {
+                        *res = Some(TraceHit {
+                            triangle,
+                            dist: hit,
+                            instance: index,
+                        });
+                    }
+                }
+                continue;
+            }
+
+            let mut min_index = mesh.bvh_index as usize + node.left_node_index();
+            let mut max_index = mesh.bvh_index as usize + node.right_node_index();
+            let (min_child, max_child) = (self.bvh_nodes[min_index], self.bvh_nodes[max_index]);
+            let mut min_dist = intersect_aabb(&ray, min_child.min, min_child.max, hit);
+            let mut max_dist = intersect_aabb(&ray, max_child.min, max_child.max, hit);
+            if min_dist > max_dist {
+                std::mem::swap(&mut min_index, &mut max_index);
+                std::mem::swap(&mut min_dist, &mut max_dist);
+            }
+            if min_dist >= hit {
+                continue;
+            }
+            if max_dist <= hit {
+                stack.push(max_index);
+            }
+            stack.push(min_index);
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Shading {
+    /// Object space normal as `normal * 0.5 + 0.5`, what `bvh_trace.wgsl` shows.
+    Normal,
+    /// Diffuse path tracing under a uniform white sky.
+    PathTraced {
+        samples: u32,
+        bounces: u32,
+        albedo: f32,
+    },
+}
+
+/// Renders small frames on the cpu to cross-check the gpu tracer.
+///
+/// Every pixel stores its color in `xyz` and the primary hit distance in `w`,
+/// `MAX_DIST` on a miss.
+pub struct ReferenceTracer {
+    pub width: u32,
+    pub height: u32,
+    pub shading: Shading,
+    pub background: Vec3,
+}
+
+impl ReferenceTracer {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            shading: Shading::Normal,
+            background: Vec3::splat(0.13),
+        }
+    }
+
+    pub fn with_shading(mut self, shading: Shading) -> Self {
+        self.shading = shading;
+        self
+    }
+
+    /// Primary ray through the center of pixel `x`, `y` counted from the top left.
+    pub fn camera_ray(&self, clip_to_world: Mat4, x: u32, y: u32) -> Ray {
+        let uv = vec2(
+            (x as f32 + 0.5) / self.width as f32,
+            1. - (y as f32 + 0.5) / self.height as f32,
+        ) * 2.
+            - 1.;
+        let view_pos = clip_to_world * vec4(uv.x, uv.y, 1., 1.);
+        let view_dir = clip_to_world * vec4(uv.x, uv.y, 0., 1.);
+        Ray::new(view_pos.xyz() / view_pos.w, view_dir.xyz().normalize())
+    }
+
+    pub fn render(&self, scene: &SceneRef, clip_to_world: Mat4) -> Vec<Vec4> {
+        (0..self.height)
+            .flat_map(|y| (0..self.width).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                let ray = self.camera_ray(clip_to_world, x, y);
+                let Some(hit) = scene.trace(ray) else {
+                    return self.background.extend(MAX_DIST);
+                };
+                let color = match self.shading {
+                    Shading::Normal => hit.normal() * 0.5 + 0.5,
+                    Shading::PathTraced {
+                        samples,
+                        bounces,
+                        albedo,
+                    } => {
+                        let mut seed = y * self.width + x;
+                        let radiance = (0..samples)
+                            .map(|_| self.path(scene, ray, hit, bounces, albedo, &mut seed))
+                            .sum::<f32>();
+                        Vec3::splat(radiance / samples.max(1) as f32)
+                    }
+                };
+                color.extend(hit.dist)
+            })
+            .collect()
+    }
+
+    fn path(
+        &self,
+        scene: &SceneRef,
+        mut ray: Ray,
+        mut hit: TraceHit,
+        bounces: u32,
+        albedo: f32,
+        seed: &mut u32,
+    ) -> f32 {
+        let mut throughput = 1.;
+        for _ in 0..bounces {
+            let instance = scene.instances[hit.instance as usize];
+            let normal = instance
+                .inv_transform()
+                .transpose()
+                .transform_vector3(hit.normal())
+                .normalize();
+            let normal = if normal.dot(ray.dir) > 0. {
+                -normal
+            } else {
+                normal
+            };
+            let pos = ray.orig + ray.dir * hit.dist;
+
+            throughput *= albedo;
+            ray = Ray::new(pos + normal * 1e-3, sample_cosine(normal, seed));
+            match scene.trace(ray) {
+                Some(next) => hit = next,
+                None => return throughput,
+            }
+        }
+        0.
+    }
+}
+
+fn sample_cosine(normal: Vec3, seed: &mut u32) -> Vec3 {
+    let (u, v) = (random(seed), random(seed));
+    let (tangent, bitangent) = normal.any_orthonormal_pair();
+    let phi = std::f32::consts::TAU * u;
+    let r = v.sqrt();
+    (tangent * phi.cos() * r + bitangent * phi.sin() * r + normal * (1. - v).sqrt()).normalize()
+}
+
+fn random(seed: &mut u32) -> f32 {
+    let state = seed.wrapping_mul(747796405).wrapping_add(2891336453);
+    let word = ((state >> ((state >> 28) + 4)) ^ state).wrapping_mul(277803737);
+    *seed = (word >> 22) ^ word;
+    (*seed >> 8) as f32 / (1 << 24) as f32
+}
+
+/// Differences between a reference frame and one from the gpu, in the layout of
+/// `ReferenceTracer::render`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FrameDiff {
+    /// Pixels hit in one frame and missed in the other.
+    pub hit_mismatches: usize,
+    pub max_color_error: f32,
+    pub rms_color_error: f32,
+    /// Largest relative error of the primary hit distance.
+    pub max_dist_error: f32,
+}
+
+impl FrameDiff {
+    pub fn new(reference: &[Vec4], other: &[Vec4]) -> Self {
+        assert_eq!(reference.len(), other.len(), "Frames differ in size");
+        let mut diff = Self::default();
+        let mut squared_sum = 0.;
+        let mut compared = 0;
+        for (a, b) in reference.iter().zip(other) {
+            let (a_hit, b_hit) = (a.w < MAX_DIST, b.w < MAX_DIST);
+            if a_hit != b_hit {
+                diff.hit_mismatches += 1;
+                continue;
+            }
+            let error = (a.xyz() - b.xyz()).abs().max_element();
+            diff.max_color_error = diff.max_color_error.max(error);
+            squared_sum += (a.xyz() - b.xyz()).length_squared() / 3.;
+            compared += 1;
+            if a_hit {
+                let dist_error = (a.w - b.w).abs() / a.w.max(f32::EPSILON);
+                diff.max_dist_error = diff.max_dist_error.max(dist_error);
+            }
+        }
+        diff.rms_color_error = (squared_sum / compared.max(1) as f32).sqrt();
+        diff
+    }
+}
@@ -21,51 +21,104 @@ impl TlasNode {
 
 pub struct Tlas {
     pub nodes: Vec<TlasNode>,
+    // Subtree over static instances, reused by `rebuild_dynamic`.
+    static_nodes: Vec<TlasNode>,
 }
 
 impl Tlas {
     pub fn empty() -> Self {
-        Self { nodes: vec![] }
+        Self {
+            nodes: vec![],
+            static_nodes: vec![],
+        }
     }
 
     pub fn build(&mut self, instances: &[Instance], meshes: &[MeshInfo]) {
-        self.nodes = vec![TlasNode::default(); 2 * instances.len() + 1];
+        self.static_nodes =
+            Self::build_segment(instances, meshes, |instance| !instance.is_dynamic());
+        self.rebuild_dynamic(instances, meshes);
+    }
+
+    /// Rebuilds only the subtree over dynamic instances and links it with the static one.
+    pub fn rebuild_dynamic(&mut self, instances: &[Instance], meshes: &[MeshInfo]) {
+        let dynamic = Self::build_segment(instances, meshes, |instance| {
+            instance.is_active() && instance.is_dynamic()
+        });
+        self.nodes = Self::link(&self.static_nodes, &dynamic);
+    }
 
+    /// Updates the bounds of every node for moved instances, keeping the tree as is.
+    ///
+    /// Much cheaper than a rebuild, but the tree degrades as instances drift away from
+    /// where they were built, so rebuild once in a while and whenever instances are added,
+    /// removed or toggled. The static subtree kept for [`Tlas::rebuild_dynamic`] is untouched.
+    pub fn refit(&mut self, instances: &[Instance], meshes: &[MeshInfo]) {
+        // A lone node is the placeholder of an empty tree.
+        if self.nodes.len() > 1 {
+            Self::refit_node(&mut self.nodes, 0, instances, meshes);
+        }
+    }
+
+    fn refit_node(
+        nodes: &mut [TlasNode],
+        index: usize,
+        instances: &[Instance],
+        meshes: &[MeshInfo],
+    ) -> [Vec3; 2] {
+        let node = nodes[index];
+        let [min, max] = if node.is_leaf() {
+            let instance = &instances[node.instance_idx as usize];
+            instance_bounds(instance, &meshes[instance.mesh.0 as usize])
+        } else {
+            let left = (node.left_right & 0xffff) as usize;
+            let right = (node.left_right >> 16) as usize;
+            let [left_min, left_max] = Self::refit_node(nodes, left, instances, meshes);
+            let [right_min, right_max] = Self::refit_node(nodes, right, instances, meshes);
+            [left_min.min(right_min), left_max.max(right_max)]
+        };
+        nodes[index].min = min;
+        nodes[index].max = max;
+        [min, max]
+    }
+
+    fn build_segment(
+        instances: &[Instance],
+        meshes: &[MeshInfo],
+        filter: impl Fn(&Instance) -> bool,
+    ) -> Vec<TlasNode> {
+        // First node reserved for root
+        let mut nodes = vec![TlasNode::default()];
         for (i, instance) in instances.iter().enumerate() {
-            let mesh = meshes[instance.mesh.0 as usize];
-            let bound = [mesh.min, mesh.max];
-            let [min, max] = (0..8)
-                .map(|i| [i & 1, i & 2, i & 4].map(|i| i == 0).map(usize::from))
-                .fold([mesh.min, mesh.max], |[min, max], [i, j, k]| {
-                    let bound = instance
-                        .transform
-                        .transform_point3(vec3(bound[i].x, bound[j].y, bound[k].z));
-                    [min.min(bound), max.max(bound)]
-                });
-            let node = TlasNode {
+            if !filter(instance) {
+                continue;
+            }
+            let [min, max] = instance_bounds(instance, &meshes[instance.mesh.0 as usize]);
+            nodes.push(TlasNode {
                 min,
                 left_right: 0,
                 max,
                 instance_idx: i as u32,
-            };
+            });
+        }
 
-            // First node reserved for root
-            self.nodes[i + 1] = node;
+        let mut instance_count = nodes.len() - 1;
+        if instance_count == 0 {
+            return vec![];
         }
+        nodes.resize(2 * instance_count + 1, TlasNode::default());
 
-        let mut instance_count = instances.len();
         let mut nodes_used = 1 + instance_count;
         let mut node_indices: Vec<_> = (1..).take(instance_count).collect();
         let mut a = 0;
-        let mut b = self.find_best_match(&node_indices, instance_count, a);
+        let mut b = Self::find_best_match(&nodes, &node_indices, instance_count, a);
         while instance_count > 0 {
-            let c = self.find_best_match(&node_indices, instance_count, b);
+            let c = Self::find_best_match(&nodes, &node_indices, instance_count, b);
             if a == c {
                 let idx_a = node_indices[a];
                 let idx_b = node_indices[b];
-                let node_a = &self.nodes[idx_a];
-                let node_b = &self.nodes[idx_b];
-                self.nodes[nodes_used] = TlasNode {
+                let node_a = &nodes[idx_a];
+                let node_b = &nodes[idx_b];
+                nodes[nodes_used] = TlasNode {
                     min: node_a.min.min(node_b.min),
                     max: node_a.max.max(node_b.max),
                     left_right: idx_a as u32 + ((idx_b as u32) << 16),
@@ -75,24 +128,69 @@ impl Tlas {
                 nodes_used += 1;
                 node_indices[b] = node_indices[instance_count - 1];
                 instance_count -= 1;
-                b = self.find_best_match(&node_indices, instance_count, a);
+                b = Self::find_best_match(&nodes, &node_indices, instance_count, a);
             } else {
                 a = b;
                 b = c;
             }
         }
-        self.nodes[0] = self.nodes[node_indices[a]];
+        nodes[0] = nodes[node_indices[a]];
+        nodes
     }
 
-    fn find_best_match(&self, indices: &[usize], num_unused: usize, target: usize) -> usize {
+    // Places both segments under a shared root, shifting their child indices.
+    fn link(static_nodes: &[TlasNode], dynamic_nodes: &[TlasNode]) -> Vec<TlasNode> {
+        if static_nodes.is_empty() && dynamic_nodes.is_empty() {
+            // Lone leaf so the buffer is never empty, tracing skips the inactive instance.
+            return vec![TlasNode::default()];
+        }
+        if dynamic_nodes.is_empty() {
+            return static_nodes.to_vec();
+        }
+        if static_nodes.is_empty() {
+            return dynamic_nodes.to_vec();
+        }
+
+        let shift = |offset: u32| {
+            move |node: &TlasNode| {
+                let mut node = *node;
+                if !node.is_leaf() {
+                    let left = (node.left_right & 0xffff) + offset;
+                    let right = (node.left_right >> 16) + offset;
+                    node.left_right = left + (right << 16);
+                }
+                node
+            }
+        };
+        let dynamic_offset = 1 + static_nodes.len() as u32;
+        let (left, right) = (static_nodes[0], dynamic_nodes[0]);
+        let root = TlasNode {
+            min: left.min.min(right.min),
+            max: left.max.max(right.max),
+            left_right: 1 + (dynamic_offset << 16),
+            instance_idx: u32::MAX,
+        };
+
+        std::iter::once(root)
+            .chain(static_nodes.iter().map(shift(1)))
+            .chain(dynamic_nodes.iter().map(shift(dynamic_offset)))
+            .collect()
+    }
+
+    fn find_best_match(
+        nodes: &[TlasNode],
+        indices: &[usize],
+        num_unused: usize,
+        target: usize,
+    ) -> usize {
         let mut smallest = 1e30;
         let mut best_idx = target;
         for i in 0..num_unused {
             if target == i {
                 continue;
             }
-            let target_node = self.nodes[indices[target]];
-            let best_node = self.nodes[indices[i]];
+            let target_node = nodes[indices[target]];
+            let best_node = nodes[indices[i]];
             let bmin = target_node.min.min(best_node.min);
             let bmax = target_node.max.max(best_node.max);
             let surface_area = Aabb::new(bmin, bmax).area();
@@ -104,3 +202,16 @@ impl Tlas {
         best_idx
     }
 }
+
+// World space bounds of the transformed corners of the mesh bounds.
+fn instance_bounds(instance: &Instance, mesh: &MeshInfo) -> [Vec3; 2] {
+    let bound = [mesh.min, mesh.max];
+    (0..8)
+        .map(|i| [i & 1, i & 2, i & 4].map(|i| i == 0).map(usize::from))
+        .fold([mesh.min, mesh.max], |[min, max], [i, j, k]| {
+            let bound = instance
+                .transform
+                .transform_point3(vec3(bound[i].x, bound[j].y, bound[k].z));
+            [min.min(bound), max.max(bound)]
+        })
+}
@@ -0,0 +1,90 @@
+// Reference microfacet BRDF: GGX distribution, height-correlated Smith visibility,
+// Schlick fresnel and a Lambert diffuse lobe.
+#import <voidin/math.wgsl>
+#import <voidin/random.wgsl>
+
+struct BrdfInput {
+    base_color: vec3<f32>,
+    roughness: f32,
+    metallic: f32,
+}
+
+fn brdf_alpha(roughness: f32) -> f32 {
+    let r = max(roughness, 0.02);
+    return r * r;
+}
+
+fn brdf_f0(input: BrdfInput) -> vec3<f32> {
+    return mix(vec3(0.04), input.base_color, input.metallic);
+}
+
+fn d_ggx(n_dot_h: f32, alpha: f32) -> f32 {
+    let a2 = alpha * alpha;
+    let d = n_dot_h * n_dot_h * (a2 - 1.) + 1.;
+    return a2 / (PI * d * d);
+}
+
+fn v_smith_ggx_correlated(n_dot_v: f32, n_dot_l: f32, alpha: f32) -> f32 {
+    let a2 = alpha * alpha;
+    let ggx_l = n_dot_v * sqrt(n_dot_l * n_dot_l * (1. - a2) + a2);
+    let ggx_v = n_dot_l * sqrt(n_dot_v * n_dot_v * (1. - a2) + a2);
+    return 0.5 / max(ggx_v + ggx_l, EPS);
+}
+
+fn f_schlick(f0: vec3<f32>, v_dot_h: f32) -> vec3<f32> {
+    return f0 + (1. - f0) * pow(1. - v_dot_h, 5.);
+}
+
+fn brdf_specular(input: BrdfInput, n_dot_v: f32, n_dot_l: f32, n_dot_h: f32, v_dot_h: f32) -> vec3<f32> {
+    let alpha = brdf_alpha(input.roughness);
+    let d = d_ggx(n_dot_h, alpha);
+    let v = v_smith_ggx_correlated(n_dot_v, n_dot_l, alpha);
+    return d * v * f_schlick(brdf_f0(input), v_dot_h);
+}
+
+fn brdf_diffuse(input: BrdfInput, v_dot_h: f32) -> vec3<f32> {
+    let kd = (1. - f_schlick(brdf_f0(input), v_dot_h)) * (1. - input.metallic);
+    return kd * input.base_color / PI;
+}
+
+// Half vector around +z distributed by D(h) * n_dot_h.
+fn sample_ggx(u: vec2<f32>, alpha: f32) -> vec3<f32> {
+    let phi = TAU * u.x;
+    let cos_theta = sqrt((1. - u.y) / (1. + (alpha * alpha - 1.) * u.y));
+    let sin_theta = sqrt(1. - cos_theta * cos_theta);
+    return vec3(sin_theta * cos(phi), sin_theta * sin(phi), cos_theta);
+}
+
+fn sample_cosine(u: vec2<f32>) -> vec3<f32> {
+    let phi = TAU * u.x;
+    let r = sqrt(u.y);
+    return vec3(r * cos(phi), r * sin(phi), sqrt(1. - u.y));
+}
+
+// Integral of brdf * cos over the hemisphere for a view at `n_dot_v` in the +z frame,
+// the radiance reflected under a uniform white environment.
+fn directional_albedo(input: BrdfInput, n_dot_v: f32, sample_count: u32, jitter: vec2<f32>) -> vec3<f32> {
+    let v = vec3(sqrt(1. - n_dot_v * n_dot_v), 0., n_dot_v);
+    let alpha = brdf_alpha(input.roughness);
+    var specular = vec3(0.);
+    var diffuse = vec3(0.);
+    for (var i = 0u; i < sample_count; i += 1u) {
+        let u = fract(hammersley(i, sample_count) + jitter);
+
+        // Specular lobe, pdf(l) = D * n_dot_h / (4 * v_dot_h).
+        let h = sample_ggx(u, alpha);
+        let v_dot_h = dot(v, h);
+        let l = reflect(-v, h);
+        if l.z > 0. && v_dot_h > 0. {
+            let n_dot_h = h.z;
+            let pdf = d_ggx(n_dot_h, alpha) * n_dot_h / (4. * v_dot_h);
+            specular += brdf_specular(input, n_dot_v, l.z, n_dot_h, v_dot_h) * l.z / pdf;
+        }
+
+        // Diffuse lobe, pdf(l) = n_dot_l / PI.
+        let ld = sample_cosine(u);
+        let hd = normalize(v + ld);
+        diffuse += brdf_diffuse(input, dot(v, hd)) * PI;
+    }
+    return (specular + diffuse) / f32(sample_count);
+}
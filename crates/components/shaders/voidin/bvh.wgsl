@@ -1,5 +1,9 @@
-#import "./stack.wgsl"
-#import "./intersections.wgsl"
+// Walks the TLAS and mesh BVHs of the engine. The including shader declares the
+// `Instance` and `MeshInfo` structs, `INSTANCE_INACTIVE` and the `tlas_nodes`,
+// `instances`, `meshes`, `bvh_nodes`, `vertices` and `indices` buffers, like
+// `shared.wgsl` and the trace bind group do.
+#import <voidin/stack.wgsl>
+#import <voidin/intersections.wgsl>
 
 struct TlasNode {
 	min: vec3<f32>,
@@ -21,10 +25,13 @@ struct TraceResult {
 	v2: vec3<f32>,
 	hit: bool,
 	dist: f32,
+	// Index into `instances` and triangle of its mesh, the vertices are in object space.
+	instance: u32,
+	triangle: u32,
 }
 
 fn trace_result_new() -> TraceResult {
-    return TraceResult(vec3(0.), vec3(0.), vec3(0.), false, MAX_DIST);
+    return TraceResult(vec3(0.), vec3(0.), vec3(0.), false, MAX_DIST, 0u, 0u);
 }
 
 fn fetch_vertex(idx: u32, mesh: MeshInfo) -> vec3<f32> {
@@ -46,7 +53,7 @@ fn traverse_bvh(ray: Ray, mesh: MeshInfo, res: ptr<function, TraceResult>) {
                 let v1 = fetch_vertex(3u * idx + 1u, mesh);
                 let v2 = fetch_vertex(3u * idx + 2u, mesh);
                 if intersect_trig(ray, v0, v1, v2, &hit) {
-                    *res = TraceResult(v0, v1, v2, true, hit);
+                    *res = TraceResult(v0, v1, v2, true, hit, (*res).instance, idx);
                 }
             }
         } else {
@@ -94,7 +101,14 @@ fn traverse_tlas(ray: Ray) -> TraceResult {
     while stack.head > 0u {
         let node = tlas_nodes[stack_pop(&stack)];
         if node.left_right == 0u { // is leaf
-            instance_intersect(ray, instances[node.instance_idx], &res);
+            let instance = instances[node.instance_idx];
+            if (instance.flags & INSTANCE_INACTIVE) == 0u {
+                let dist = res.dist;
+                instance_intersect(ray, instance, &res);
+                if res.dist < dist {
+                    res.instance = node.instance_idx;
+                }
+            }
 		} else {
             var min_index = node.left_right & 0xffffu;
             var max_index = node.left_right >> 16u;
@@ -11,3 +11,15 @@ fn ycbcr_to_rgb(col: vec3<f32>) -> vec3<f32> {
     let m = mat3x3(1.0, 0.0, 1.5748, 1.0, -0.1873, -.4681, 1.0, 1.8556, 0.0);
     return col * m;
 }
+
+fn rgb_to_ycocg(col: vec3<f32>) -> vec3<f32> {
+    let y = dot(col, vec3(0.25, 0.5, 0.25));
+    let co = dot(col, vec3(0.5, 0.0, -0.5));
+    let cg = dot(col, vec3(-0.25, 0.5, -0.25));
+    return vec3(y, co, cg);
+}
+
+fn ycocg_to_rgb(col: vec3<f32>) -> vec3<f32> {
+    let tmp = col.x - col.z;
+    return vec3(tmp + col.y, col.x + col.z, tmp - col.y);
+}
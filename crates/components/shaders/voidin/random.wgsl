@@ -28,3 +28,14 @@ fn hash33(x: vec3<f32>) -> vec3<f32> {
     p3 += dot(p3, p3.yxz + 31.323);
     return fract((p3.xxy + p3.yxx) * p3.zyx);
 }
+
+fn hammersley(i: u32, count: u32) -> vec2<f32> {
+    return vec2(f32(i) / f32(count), f32(reverseBits(i)) * 2.3283064365386963e-10);
+}
+
+// PCG hash of Jarzynski and Olano, "Hash Functions for GPU Rendering" (2020).
+fn pcg(v: u32) -> u32 {
+    let state = v * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
@@ -1,4 +1,5 @@
-const STACK_LEN: u32 = 24u;
+// Deep enough for the LBVH of `TlasBuild`, which splits on morton bits.
+const STACK_LEN: u32 = 32u;
 struct Stack {
     arr: array<u32, STACK_LEN>,
 	head: u32,
@@ -0,0 +1,30 @@
+#import <voidin/color.wgsl>
+
+fn tonemap_curve(v: f32) -> f32 {
+    let c = v + v * v + 0.5 * v * v * v;
+    return c / (1.0 + c);
+}
+
+fn tonemap_curve_vec(col: vec3<f32>) -> vec3<f32> {
+    return vec3(tonemap_curve(col.r), tonemap_curve(col.g), tonemap_curve(col.b));
+}
+
+fn neutral_tonemap(col: vec3<f32>) -> vec3<f32> {
+    let ycbcr = rgb_to_ycbcr(col);
+
+    let chroma = length(ycbcr.yz) * 2.4;
+    let bt = tonemap_curve(chroma);
+
+    var desat = max((bt - 0.7) * 0.8, 0.0);
+    desat *= desat;
+
+    let desat_col = mix(col, ycbcr.xxx, desat);
+
+    let tm_luma = tonemap_curve(ycbcr.x);
+    let tm0 = col * max(0.0, tm_luma / max(1e-5, calculate_luma(col)));
+    let final_mult = 0.97;
+    let tm1 = tonemap_curve_vec(desat_col);
+
+    let res = mix(tm0, tm1, vec3(bt * bt));
+    return res * final_mult;
+}
@@ -0,0 +1,2 @@
+// Bumped when a library function changes its signature or meaning.
+const VOIDIN_SHADER_VERSION: u32 = 2u;
@@ -4,12 +4,13 @@ use ahash::AHashMap;
 
 use crate::world::World;
 
-use super::bind_group_layout::SingleTextureBindGroupLayout;
+use super::bind_group_layout::{BindGroupLayout, SingleTextureBindGroupLayout};
 
 pub struct Blitter {
     pipelines: RefCell<AHashMap<(wgpu::TextureFormat, bool), wgpu::RenderPipeline>>,
     shader: wgpu::ShaderModule,
     pipeline_layout: wgpu::PipelineLayout,
+    texture_layout: BindGroupLayout,
     sampler: wgpu::BindGroup,
 }
 
@@ -65,6 +66,7 @@ impl Blitter {
             pipelines: RefCell::new(AHashMap::new()),
             shader,
             pipeline_layout,
+            texture_layout: texture_bind_group_layout.layout.clone(),
             sampler,
         }
     }
@@ -136,10 +138,27 @@ impl Blitter {
         )
     }
 
+    /// Whether [`Blitter::generate_mipmaps`] can downsample textures of `format`, which has
+    /// to be filterable and renderable. Block compressed formats come with their mips.
+    pub fn can_generate_mipmaps(format: wgpu::TextureFormat, features: wgpu::Features) -> bool {
+        let supported = format.guaranteed_format_features(features);
+        format.block_dimensions() == (1, 1)
+            && supported
+                .allowed_usages
+                .contains(wgpu::TextureUsages::RENDER_ATTACHMENT)
+            && supported
+                .flags
+                .contains(wgpu::TextureFormatFeatureFlags::FILTERABLE)
+    }
+
+    /// Fills mips `1..` of `texture` by halving the level above with a bilinear blit.
+    ///
+    /// The texture needs `RENDER_ATTACHMENT` and `TEXTURE_BINDING` usage and a format
+    /// passing [`Blitter::can_generate_mipmaps`]. sRGB textures are filtered in linear space.
     pub fn generate_mipmaps(
         &self,
         encoder: &mut wgpu::CommandEncoder,
-        world: &World,
+        device: &wgpu::Device,
         texture: &wgpu::Texture,
     ) {
         let mip_count = texture.mip_level_count();
@@ -155,23 +174,20 @@ impl Blitter {
             .collect();
 
         for (src_view, dst_view) in views.iter().zip(views.iter().skip(1)) {
-            let texture_bind_group_layout = world.unwrap::<SingleTextureBindGroupLayout>();
-            let texture_bind_group = world
-                .device()
-                .create_bind_group(&wgpu::BindGroupDescriptor {
-                    label: None,
-                    layout: &texture_bind_group_layout,
-                    entries: &[wgpu::BindGroupEntry {
-                        binding: 0,
-                        resource: wgpu::BindingResource::TextureView(src_view),
-                    }],
-                });
+            let texture_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: None,
+                layout: &self.texture_layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(src_view),
+                }],
+            });
             let mut pipelines = self.pipelines.borrow_mut();
             let pipeline = pipelines
                 .entry((texture.format(), true))
                 .or_insert_with(|| {
                     Self::create_pipeline(
-                        world.device(),
+                        device,
                         &self.shader,
                         texture.format(),
                         &self.pipeline_layout,
@@ -180,7 +196,7 @@ impl Blitter {
                 });
 
             let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Blit Pass"),
+                label: Some("Mipmap Pass"),
                 color_attachments: &[Some(wgpu::RenderPassColorAttachment {
                     view: dst_view,
                     resolve_target: None,
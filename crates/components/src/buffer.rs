@@ -1,9 +1,12 @@
 use crate::{
     bind_group_layout::{StorageReadBindGroupLayout, StorageWriteBindGroupLayout},
-    Gpu,
+    Gpu, SyncKind,
 };
 
-use std::{marker::PhantomData, ops::RangeBounds};
+use std::{
+    marker::PhantomData,
+    ops::{Range, RangeBounds},
+};
 
 use bytemuck::Pod;
 use pretty_type_name::pretty_type_name;
@@ -200,14 +203,25 @@ impl<T: bytemuck::Pod + NonZeroSized> ResizableBuffer<T> {
     }
 
     pub fn read(&self, gpu: &Gpu) -> Vec<T> {
+        self.read_range(gpu, 0..self.len())
+    }
+
+    /// Blocks until the elements in `range` are copied back from the gpu.
+    pub fn read_range(&self, gpu: &Gpu, range: Range<usize>) -> Vec<T> {
+        assert!(range.end <= self.len());
+        let offset = (range.start * T::SIZE) as BufferAddress;
+        let size = (range.len() * T::SIZE) as BufferAddress;
+        if size == 0 {
+            return vec![];
+        }
         let staging = gpu.device().create_buffer(&wgpu::BufferDescriptor {
             label: None,
-            size: self.size_bytes(),
+            size,
             usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
             mapped_at_creation: false,
         });
         let mut encoder = gpu.device().create_command_encoder(&Default::default());
-        encoder.copy_buffer_to_buffer(&self.buffer, 0, &staging, 0, self.size_bytes());
+        encoder.copy_buffer_to_buffer(&self.buffer, offset, &staging, 0, size);
         let submit = gpu.queue().submit(Some(encoder.finish()));
         let slice = staging.slice(..);
         slice.map_async(wgpu::MapMode::Read, |err| {
@@ -215,8 +229,10 @@ impl<T: bytemuck::Pod + NonZeroSized> ResizableBuffer<T> {
                 log::error!("Failed to map buffer: {err}");
             }
         });
-        gpu.device()
-            .poll(wgpu::Maintain::WaitForSubmissionIndex(submit));
+        gpu.sync(SyncKind::BufferMap, "ResizableBuffer::read", || {
+            gpu.device()
+                .poll(wgpu::Maintain::WaitForSubmissionIndex(submit));
+        });
         let mapped = slice.get_mapped_range();
         bytemuck::cast_slice(&mapped).to_vec()
     }
@@ -293,6 +309,22 @@ impl<T: bytemuck::Pod + NonZeroSized> ResizableBuffer<T> {
         self.len = 0;
     }
 
+    /// Forgets the values past `len`, the capacity is kept.
+    pub fn truncate(&mut self, len: usize) {
+        self.len = self.len.min(len);
+    }
+
+    /// Whether `additional` more values stay within the buffer size limits of the device,
+    /// [`ResizableBuffer::push`] past them fails validation.
+    pub fn fits(&self, device: &Device, additional: usize) -> bool {
+        let limits = device.limits();
+        let mut max_size = limits.max_buffer_size;
+        if self.usages().contains(BufferUsages::STORAGE) {
+            max_size = max_size.min(limits.max_storage_buffer_binding_size as u64);
+        }
+        ((self.len + additional) * T::SIZE) as u64 <= max_size
+    }
+
     pub fn size_bytes(&self) -> BufferAddress {
         (T::SIZE * self.len) as BufferAddress
     }
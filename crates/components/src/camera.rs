@@ -2,14 +2,20 @@ use dolly::{
     prelude::{Position, Smooth, YawPitch},
     rig::CameraRig,
 };
-use glam::{vec4, Mat4, Quat, Vec2, Vec3, Vec4};
+use std::num::NonZeroU64;
+
+use color_eyre::{eyre::bail, Result};
+use glam::{Mat4, Quat, Vec2, Vec3, Vec4};
 use wgpu::util::DeviceExt;
 
 use crate::{
     bind_group_layout::{self, WrappedBindGroupLayout},
-    NonZeroSized,
+    NonZeroSized, FRAME_SLOTS,
 };
 
+/// Number of views in [`CameraUniformBinding`], matches `MAX_VIEWS` of `shared.wgsl`.
+pub const MAX_VIEWS: usize = 8;
+
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct CameraUniform {
@@ -18,12 +24,15 @@ pub struct CameraUniform {
     pub view: Mat4,
     pub clip_to_world: Mat4,
     pub prev_world_to_clip: Mat4,
-    frustum: [f32; 4],
+    /// World space left, right, bottom, top, near and far planes, facing inwards.
+    frustum_planes: [Vec4; 6],
     zfar: f32,
     znear: f32,
     pub jitter: [f32; 2],
     prev_jitter: [f32; 2],
-    _padding: [f32; 2],
+    /// Added to the mip level of material texture lookups.
+    pub mip_bias: f32,
+    _padding: f32,
 }
 
 impl Default for CameraUniform {
@@ -34,66 +43,185 @@ impl Default for CameraUniform {
             view: Mat4::IDENTITY,
             clip_to_world: Mat4::IDENTITY,
             prev_world_to_clip: Mat4::IDENTITY,
-            frustum: [0.; 4],
+            frustum_planes: [Vec4::ZERO; 6],
             zfar: f32::INFINITY,
             znear: Camera::ZNEAR,
             jitter: [0.; 2],
             prev_jitter: [0.; 2],
-            _padding: [0.; 2],
+            mip_bias: 0.,
+            _padding: 0.,
+        }
+    }
+}
+
+impl CameraUniform {
+    /// Uniform of another viewpoint, like a shadow cascade or a probe face.
+    pub fn from_view(position: Vec3, view: Mat4, projection: Mat4) -> Self {
+        let proj_view = projection * view;
+        Self {
+            view_position: Vec4::from((position, 1.)).to_array(),
+            projection,
+            view,
+            clip_to_world: proj_view.inverse(),
+            prev_world_to_clip: proj_view,
+            frustum_planes: frustum_planes(proj_view),
+            ..Default::default()
         }
     }
 }
 
+/// Array of [`MAX_VIEWS`] camera uniforms with a copy per [`FRAME_SLOTS`], each frame writes
+/// and binds the next one.
+///
+/// View [`Self::MAIN_VIEW`] is the main camera, the others hold alternate viewpoints like
+/// shadow cascades, probes or stereo eyes. Binding 0 is the main view alone, binding
+/// [`Self::VIEWS_BINDING`] the whole array. Shaders that render from any view index it with
+/// the push constant of [`Self::VIEW_INDEX_PUSH_CONSTANT`], so one pipeline serves every view.
 pub struct CameraUniformBinding {
-    buffer: wgpu::Buffer,
-    pub binding: wgpu::BindGroup,
+    buffers: Vec<wgpu::Buffer>,
+    bindings: Vec<wgpu::BindGroup>,
+    views: [CameraUniform; MAX_VIEWS],
+    slot: usize,
     pub bind_group_layout: bind_group_layout::BindGroupLayout,
 }
 
 impl CameraUniformBinding {
+    pub const MAIN_VIEW: u32 = 0;
+    /// Binding of the views array, the same in every layout exposing it, like
+    /// `GlobalsBindGroup`.
+    pub const VIEWS_BINDING: u32 = 2;
+
+    /// `var<push_constant> view_index: u32;`, set with these stages on render passes.
+    pub const VIEW_INDEX_PUSH_CONSTANT: wgpu::PushConstantRange = wgpu::PushConstantRange {
+        stages: wgpu::ShaderStages::VERTEX_FRAGMENT.union(wgpu::ShaderStages::COMPUTE),
+        range: 0..4,
+    };
+
     pub const DESC: wgpu::BindGroupLayoutDescriptor<'static> = wgpu::BindGroupLayoutDescriptor {
         label: Some("Camera Bind Group Layout"),
-        entries: &[wgpu::BindGroupLayoutEntry {
-            binding: 0,
-            visibility: wgpu::ShaderStages::VERTEX_FRAGMENT.union(wgpu::ShaderStages::COMPUTE),
-            ty: wgpu::BindingType::Buffer {
-                ty: wgpu::BufferBindingType::Uniform,
-                has_dynamic_offset: false,
-                min_binding_size: Some(CameraUniform::NSIZE),
+        entries: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT.union(wgpu::ShaderStages::COMPUTE),
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: Some(CameraUniform::NSIZE),
+                },
+                count: None,
             },
-            count: None,
-        }],
+            wgpu::BindGroupLayoutEntry {
+                binding: Self::VIEWS_BINDING,
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT.union(wgpu::ShaderStages::COMPUTE),
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: Some(Self::VIEWS_SIZE),
+                },
+                count: None,
+            },
+        ],
     };
 
+    pub const VIEWS_SIZE: NonZeroU64 =
+        unsafe { NonZeroU64::new_unchecked((CameraUniform::SIZE * MAX_VIEWS) as _) };
+
     pub fn new(device: &wgpu::Device) -> Self {
-        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Camera Buffer"),
-            contents: bytemuck::bytes_of(&CameraUniform::default()),
-            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-        });
+        let views = [CameraUniform::default(); MAX_VIEWS];
+        let buffers: Vec<_> = (0..FRAME_SLOTS)
+            .map(|_| {
+                device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("Camera Buffer"),
+                    contents: bytemuck::cast_slice(&views),
+                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                })
+            })
+            .collect();
         let bind_group_layout = device.create_bind_group_layout_wrap(&Self::DESC);
-        let binding = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("Camera Bind Group"),
-            layout: &bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: buffer.as_entire_binding(),
-            }],
-        });
+        let bindings = buffers
+            .iter()
+            .map(|buffer| {
+                device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("Camera Bind Group"),
+                    layout: &bind_group_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: Self::main_view_binding(buffer),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: Self::VIEWS_BINDING,
+                            resource: buffer.as_entire_binding(),
+                        },
+                    ],
+                })
+            })
+            .collect();
 
         Self {
-            buffer,
-            binding,
+            buffers,
+            bindings,
+            views,
+            slot: 0,
             bind_group_layout,
         }
     }
 
+    /// The main view at the start of one of [`Self::buffers`].
+    pub fn main_view_binding(buffer: &wgpu::Buffer) -> wgpu::BindingResource {
+        wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+            buffer,
+            offset: 0,
+            size: Some(CameraUniform::NSIZE),
+        })
+    }
+
+    /// Writes all views into the next slot, which no frame in flight reads anymore.
+    ///
+    /// `camera_uniform` becomes the main view, the others keep what [`Self::set_view`] gave
+    /// them last.
     pub fn update(&mut self, queue: &wgpu::Queue, camera_uniform: &CameraUniform) {
-        queue.write_buffer(&self.buffer, 0, bytemuck::bytes_of(camera_uniform));
+        self.views[Self::MAIN_VIEW as usize] = *camera_uniform;
+        self.slot = (self.slot + 1) % FRAME_SLOTS;
+        queue.write_buffer(
+            &self.buffers[self.slot],
+            0,
+            bytemuck::cast_slice(&self.views),
+        );
+    }
+
+    /// Sets an alternate view, uploaded with the main one on the next [`Self::update`].
+    pub fn set_view(&mut self, view: u32, camera_uniform: CameraUniform) -> Result<()> {
+        if view == Self::MAIN_VIEW {
+            bail!("The main view follows the camera and can't be set");
+        }
+        let Some(slot) = self.views.get_mut(view as usize) else {
+            bail!("View {view} is out of range, there are {MAX_VIEWS} views");
+        };
+        *slot = camera_uniform;
+        Ok(())
+    }
+
+    pub fn view(&self, view: u32) -> Option<&CameraUniform> {
+        self.views.get(view as usize)
+    }
+
+    /// Bind group of the slot written last.
+    pub fn binding(&self) -> &wgpu::BindGroup {
+        &self.bindings[self.slot]
     }
 
     pub fn buffer(&self) -> &wgpu::Buffer {
-        &self.buffer
+        &self.buffers[self.slot]
+    }
+
+    /// Buffers of all slots, indexed by [`CameraUniformBinding::slot`].
+    pub fn buffers(&self) -> &[wgpu::Buffer] {
+        &self.buffers
+    }
+
+    pub fn slot(&self) -> usize {
+        self.slot
     }
 }
 
@@ -139,14 +267,6 @@ impl Camera {
         projection.z_axis[1] += self.jitter.y;
         let proj_view = projection * view;
 
-        // https://github.com/zeux/niagara/blob/3fafe000ba8fe6e309b41e915b81242b4ca3db28/src/niagara.cpp#L836-L852
-        let perspective_t = projection.transpose();
-        // x + w < 0
-        let frustum_x = (perspective_t.col(3) + perspective_t.col(0)).normalize();
-        // y + w < 0
-        let frustum_y = (perspective_t.col(3) + perspective_t.col(1)).normalize();
-        let frustum = vec4(frustum_x.x, frustum_x.z, frustum_y.y, frustum_y.z);
-
         let (prev_world_to_clip, prev_jitter) = if let Some(prev) = previous {
             ((prev.projection * prev.view), prev.jitter)
         } else {
@@ -159,12 +279,13 @@ impl Camera {
             view,
             clip_to_world: proj_view.inverse(),
             prev_world_to_clip,
-            frustum: frustum.to_array(),
+            frustum_planes: frustum_planes(proj_view),
             zfar: f32::INFINITY,
             znear: Camera::ZNEAR,
             jitter: self.jitter.to_array(),
             prev_jitter,
-            _padding: [0.; 2],
+            mip_bias: 0.,
+            _padding: 0.,
         }
     }
 
@@ -172,3 +293,18 @@ impl Camera {
         self.aspect
     }
 }
+
+// Gribb-Hartmann extraction from the rows of `world_to_clip`. With the reverse infinite
+// projection the near plane is `z <= w` and the far one `z >= 0` never rejects anything.
+fn frustum_planes(world_to_clip: Mat4) -> [Vec4; 6] {
+    let rows = world_to_clip.transpose();
+    let (x, y, z, w) = (rows.x_axis, rows.y_axis, rows.z_axis, rows.w_axis);
+    [w + x, w - x, w + y, w - y, w - z, z].map(|plane| {
+        let len = plane.truncate().length();
+        if len > 0. {
+            plane / len
+        } else {
+            plane
+        }
+    })
+}
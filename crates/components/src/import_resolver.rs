@@ -8,18 +8,33 @@ use std::{
     rc::Rc,
 };
 
+use crate::shader_library;
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 struct ImportClause {
     path: PathBuf,
+    /// `<path>` skips the directory of the including file and ends at the shader library.
+    library: bool,
 }
 
 impl ImportClause {
-    pub const PREFIX: &'static str = "#import ";
+    /// `#include` is accepted as an alias of `#import`, both splice the whole file once.
+    pub const PREFIXES: [&'static str; 2] = ["#import ", "#include "];
+
+    fn prefix(line: &str) -> Option<&'static str> {
+        let line = line.trim();
+        Self::PREFIXES
+            .into_iter()
+            .find(|prefix| line.starts_with(prefix))
+    }
 }
 
 impl<P: Into<PathBuf>> From<P> for ImportClause {
     fn from(path: P) -> Self {
-        Self { path: path.into() }
+        Self {
+            path: path.into(),
+            library: false,
+        }
     }
 }
 
@@ -29,20 +44,25 @@ impl std::str::FromStr for ImportClause {
     fn from_str(clause_str: &str) -> Result<Self, Self::Err> {
         let s = clause_str.trim();
 
-        if !s.starts_with(Self::PREFIX) {
+        let Some(prefix) = Self::prefix(s) else {
             return Err(eyre!(
-                "import clause must start with {:?}, got {s:?}",
-                Self::PREFIX
+                "import clause must start with one of {:?}, got {s:?}",
+                Self::PREFIXES
             ));
-        }
+        };
 
-        let s = s.trim_start_matches(Self::PREFIX).trim();
+        let s = s.trim_start_matches(prefix).trim();
 
-        let splits = s
+        let quoted = s
             .find('"')
-            .and_then(|i0| s.rfind('"').map(|i1| (i0 + 1, i1)));
-
-        if let Some((i0, i1)) = splits {
+            .and_then(|i0| s.rfind('"').map(|i1| (i0 + 1, i1, false)));
+        let bracketed = || {
+            s.strip_prefix('<')
+                .and_then(|rest| rest.find('>'))
+                .map(|i1| (1, i1 + 1, true))
+        };
+
+        if let Some((i0, i1, library)) = quoted.or_else(bracketed) {
             let s = &s[i0..i1];
 
             if s.is_empty() {
@@ -52,7 +72,7 @@ impl std::str::FromStr for ImportClause {
             return s
                 .parse()
                 .with_context(|| "couldn't parse {s:?} as PathBuf")
-                .map(|path| Self { path });
+                .map(|path| Self { path, library });
         }
 
         bail!("misformatted import clause: {clause_str:?}")
@@ -61,7 +81,12 @@ impl std::str::FromStr for ImportClause {
 
 impl std::fmt::Display for ImportClause {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_fmt(format_args!("#import <{}>", self.path.to_string_lossy()))
+        let path = self.path.to_string_lossy();
+        if self.library {
+            f.write_fmt(format_args!("#import <{path}>"))
+        } else {
+            f.write_fmt(format_args!("#import \"{path}\""))
+        }
     }
 }
 
@@ -112,18 +137,22 @@ impl ImportResolver {
                 return Ok(Default::default());
             }
 
-            let contents = std::fs::read_to_string(&path)?;
+            let contents = match shader_library::embedded(&path) {
+                Some(contents) => contents.to_owned(),
+                None => std::fs::read_to_string(&path)?,
+            };
 
             let mut imports = AHashSet::new();
 
             let children: Result<Vec<_>, _> = contents
                 .lines()
                 .map(|line| {
-                    if line.trim().starts_with(ImportClause::PREFIX) {
+                    if ImportClause::prefix(line).is_some() {
                         let clause = line.parse::<ImportClause>()?;
                         let cwd = path.join("..").clean();
-                        let clause_path =
-                            this.resolve_clause_path(cwd, &clause.path).ok_or_else(|| {
+                        let clause_path = this
+                            .resolve_clause_path(cwd, &clause.path, clause.library)
+                            .ok_or_else(|| {
                                 eyre!("couldn't resolve import clause path at {:?}", clause.path)
                             })?;
                         imports.insert(clause_path.clone());
@@ -172,10 +201,12 @@ impl ImportResolver {
         .map(|reslv| (*reslv).clone())
     }
 
+    /// Library includes may be overridden by a file of the same path in the search path.
     fn resolve_clause_path(
         &self,
         cwd: impl AsRef<Path>,
         path: impl AsRef<Path>,
+        library: bool,
     ) -> Option<PathBuf> {
         let path = path.as_ref().clean();
 
@@ -183,7 +214,7 @@ impl ImportResolver {
             return path.into();
         }
 
-        {
+        if !library {
             let path = cwd.as_ref().join(&path).clean();
             if path.exists() {
                 return path.into();
@@ -197,6 +228,9 @@ impl ImportResolver {
             }
         }
 
+        if library {
+            return shader_library::resolve(&path);
+        }
         None
     }
 }
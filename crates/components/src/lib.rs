@@ -6,6 +6,8 @@ use std::{
     num::NonZeroU64,
     ops::Range,
     path::Path,
+    sync::{Mutex, MutexGuard},
+    time::Instant,
 };
 
 pub mod bind_group_layout;
@@ -15,8 +17,14 @@ mod camera;
 mod fps_counter;
 mod import_resolver;
 mod input;
+#[cfg(feature = "recorder")]
 mod recorder;
+#[cfg(not(feature = "recorder"))]
+#[path = "recorder_noop.rs"]
+mod recorder;
+pub mod shader_library;
 pub mod shared;
+mod sync_points;
 mod watcher;
 pub mod world;
 
@@ -25,18 +33,25 @@ pub use shared::*;
 pub use bind_group_layout::{BindGroupLayout, WrappedBindGroupLayout};
 pub use blitter::Blitter;
 pub use buffer::{ResizableBuffer, ResizableBufferExt};
-pub use camera::{Camera, CameraUniform, CameraUniformBinding};
+pub use camera::{Camera, CameraUniform, CameraUniformBinding, MAX_VIEWS};
 pub use fps_counter::FpsCounter;
 pub use import_resolver::{ImportResolver, ResolvedFile};
 pub use input::{Input, KeyMap, KeyboardMap, KeyboardState};
 pub use recorder::{RecordEvent, Recorder};
+pub use sync_points::{SyncKind, SyncPoint, SyncPoints};
 pub use watcher::Watcher;
-pub use world::World;
+pub use world::{Components, Entity, World};
 
+use color_eyre::{eyre::eyre, Result};
 use either::Either;
 use glam::Vec3;
+use pollster::FutureExt;
 use wgpu::util::{align_to, DeviceExt};
 
+/// Copies of the per-frame uniforms. One more than frames may be in flight, so the copy
+/// written while recording a frame is never read by the gpu at the same time.
+pub const FRAME_SLOTS: usize = 4;
+
 pub const SCREENSHOTS_FOLDER: &str = "screenshots";
 pub const VIDEO_FOLDER: &str = "recordings";
 
@@ -45,6 +60,7 @@ pub struct Gpu {
     adapter: wgpu::Adapter,
     device: wgpu::Device,
     queue: wgpu::Queue,
+    sync_points: Mutex<SyncPoints>,
 }
 
 impl Gpu {
@@ -53,6 +69,7 @@ impl Gpu {
             adapter,
             device,
             queue,
+            sync_points: Mutex::new(SyncPoints::new()),
         }
     }
 
@@ -67,6 +84,32 @@ impl Gpu {
     pub fn adapter(&self) -> &wgpu::Adapter {
         &self.adapter
     }
+
+    /// Runs `f`, which waits on the gpu, and records it as a sync point of the frame.
+    pub fn sync<T>(&self, kind: SyncKind, label: &'static str, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let res = f();
+        self.sync_points().record(kind, label, start);
+        res
+    }
+
+    pub fn sync_points(&self) -> MutexGuard<SyncPoints> {
+        self.sync_points.lock().unwrap()
+    }
+
+    /// Runs `f` inside validation and out-of-memory error scopes, returning
+    /// captured errors instead of hitting the uncaptured error handler.
+    pub fn error_scope<T>(&self, f: impl FnOnce() -> T) -> Result<T> {
+        self.device.push_error_scope(wgpu::ErrorFilter::OutOfMemory);
+        self.device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let res = f();
+        let validation = self.device.pop_error_scope().block_on();
+        let out_of_memory = self.device.pop_error_scope().block_on();
+        match validation.or(out_of_memory) {
+            None => Ok(res),
+            Some(err) => Err(eyre!("{err}")),
+        }
+    }
 }
 
 pub trait NonZeroSized: Sized {
@@ -18,7 +18,16 @@ pub enum RecordEvent {
     Start(ImageDimentions),
     Record(Arc<wgpu::Buffer>),
     Finish,
-    Screenshot((Arc<wgpu::Buffer>, ImageDimentions)),
+    /// Frame with PNG text metadata as keyword, value pairs
+    /// and a suffix appended to the file name.
+    Screenshot(
+        (
+            Arc<wgpu::Buffer>,
+            ImageDimentions,
+            Vec<(String, String)>,
+            &'static str,
+        ),
+    ),
 }
 
 pub struct Recorder {
@@ -177,10 +186,10 @@ fn record_thread(rx: Receiver<RecordEvent>) {
                 recorder = None;
                 eprintln!("Recording finished");
             }
-            RecordEvent::Screenshot((frame, image_dimentions)) => {
+            RecordEvent::Screenshot((frame, image_dimentions, metadata, suffix)) => {
                 let frame_slice = frame.slice(0..image_dimentions.linear_size());
                 let frame = frame_slice.get_mapped_range();
-                match save_screenshot(&frame, image_dimentions) {
+                match save_screenshot(&frame, image_dimentions, &metadata, suffix) {
                     Ok(_) => {}
                     Err(err) => {
                         eprintln!("{err}")
@@ -191,12 +200,17 @@ fn record_thread(rx: Receiver<RecordEvent>) {
     }
 }
 
-pub fn save_screenshot(frame: &[u8], image_dimentions: ImageDimentions) -> Result<()> {
+pub fn save_screenshot(
+    frame: &[u8],
+    image_dimentions: ImageDimentions,
+    metadata: &[(String, String)],
+    suffix: &str,
+) -> Result<()> {
     let now = Instant::now();
     let screenshots_folder = Path::new(SCREENSHOTS_FOLDER);
     create_folder(screenshots_folder)?;
     let path = screenshots_folder.join(format!(
-        "screenshot-{}.png",
+        "screenshot-{}{suffix}.png",
         chrono::Local::now().format("%d-%m-%Y-%H-%M-%S")
     ));
     let file = File::create(path)?;
@@ -205,6 +219,9 @@ pub fn save_screenshot(frame: &[u8], image_dimentions: ImageDimentions) -> Resul
         png::Encoder::new(w, image_dimentions.width as _, image_dimentions.height as _);
     encoder.set_color(png::ColorType::Rgba);
     encoder.set_depth(png::BitDepth::Eight);
+    for (keyword, text) in metadata {
+        encoder.add_text_chunk(keyword.clone(), text.clone())?;
+    }
     let padded_bytes = image_dimentions.padded_bytes_per_row as _;
     let unpadded_bytes = image_dimentions.unpadded_bytes_per_row as _;
     let mut writer = encoder
@@ -0,0 +1,63 @@
+//! Stand-in used when the crate is built without the `recorder` feature:
+//! screenshots and recordings are dropped with a warning.
+use std::sync::Arc;
+
+use crate::ImageDimentions;
+
+pub enum RecordEvent {
+    Start(ImageDimentions),
+    Record(Arc<wgpu::Buffer>),
+    Finish,
+    /// Frame with PNG text metadata as keyword, value pairs
+    /// and a suffix appended to the file name.
+    Screenshot(
+        (
+            Arc<wgpu::Buffer>,
+            ImageDimentions,
+            Vec<(String, String)>,
+            &'static str,
+        ),
+    ),
+}
+
+#[derive(Clone)]
+pub struct RecordSender;
+
+impl RecordSender {
+    pub fn send(&self, event: RecordEvent) -> Result<(), RecordEvent> {
+        if let RecordEvent::Screenshot(_) = event {
+            log::warn!("Screenshots are not available without the `recorder` feature");
+        }
+        Ok(())
+    }
+}
+
+pub struct Recorder {
+    pub sender: RecordSender,
+    pub ffmpeg_version: String,
+}
+
+impl Recorder {
+    pub fn new() -> Self {
+        Self {
+            sender: RecordSender,
+            ffmpeg_version: String::new(),
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        false
+    }
+
+    pub fn ffmpeg_installed(&self) -> bool {
+        false
+    }
+
+    pub fn start(&mut self, _dims: ImageDimentions) {
+        log::warn!("Recording is not available without the `recorder` feature");
+    }
+
+    pub fn finish(&mut self) {}
+
+    pub fn send(&self, _event: RecordEvent) {}
+}
@@ -0,0 +1,64 @@
+//! WGSL shipped with the crate: camera reconstruction, BRDF, tonemapping, packing,
+//! random numbers and BVH traversal. Shaders include it as `#include <voidin/brdf.wgsl>`.
+
+use std::path::{Path, PathBuf};
+
+/// Matches `VOIDIN_SHADER_VERSION` of `<voidin/version.wgsl>`.
+pub const VERSION: u32 = 2;
+
+/// Made up root of the embedded copies, nothing exists under it on disk.
+const EMBEDDED_ROOT: &str = "<embedded>";
+
+macro_rules! library {
+    ($($name:literal),* $(,)?) => {
+        &[$((
+            concat!("voidin/", $name),
+            include_str!(concat!("../shaders/voidin/", $name)),
+        )),*]
+    };
+}
+
+static FILES: &[(&str, &str)] = library![
+    "brdf.wgsl",
+    "bvh.wgsl",
+    "camera.wgsl",
+    "color.wgsl",
+    "intersections.wgsl",
+    "math.wgsl",
+    "packing.wgsl",
+    "random.wgsl",
+    "stack.wgsl",
+    "tonemap.wgsl",
+    "version.wgsl",
+];
+
+/// Finds a library file, like `voidin/brdf.wgsl`.
+///
+/// The sources next to this crate win when they are around, so edits to them hot reload.
+/// Everywhere else the copies embedded in the binary are used.
+pub fn resolve(path: &Path) -> Option<PathBuf> {
+    let source = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("shaders")
+        .join(path);
+    if source.exists() {
+        return Some(source);
+    }
+    FILES
+        .iter()
+        .any(|(name, _)| Path::new(name) == path)
+        .then(|| Path::new(EMBEDDED_ROOT).join(path))
+}
+
+/// Contents of an embedded path returned by [`resolve`].
+pub fn embedded(path: &Path) -> Option<&'static str> {
+    let path = path.strip_prefix(EMBEDDED_ROOT).ok()?;
+    FILES
+        .iter()
+        .find(|(name, _)| Path::new(name) == path)
+        .map(|(_, contents)| *contents)
+}
+
+/// Embedded files have nothing to watch.
+pub fn is_embedded(path: &Path) -> bool {
+    path.starts_with(EMBEDDED_ROOT)
+}
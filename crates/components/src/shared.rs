@@ -35,7 +35,24 @@ pub struct MeshInfo {
     pub base_index: u32,
     pub vertex_offset: i32,
     pub bvh_index: u32,
-    pub junk: [u32; 2],
+    /// Offset into `MeshPool::triangle_materials`, `NO_TRIANGLE_MATERIALS` when the
+    /// instance material covers the whole mesh.
+    pub triangle_materials: u32,
+    /// First of the mesh's `MeshPool::meshlets`.
+    pub meshlet_index: u32,
+    /// Bounding sphere, usually tighter than the one around `min` and `max`.
+    pub center: Vec3,
+    pub radius: f32,
+    /// Zero when the mesh is drawn whole, e.g. after its bounds were overridden.
+    pub meshlet_count: u32,
+    /// First coarser level in `MeshPool::lods`, the mesh itself is the finest one.
+    pub lod_index: u32,
+    pub lod_count: u32,
+    pub junk: u32,
+}
+
+impl MeshInfo {
+    pub const NO_TRIANGLE_MATERIALS: u32 = u32::MAX;
 }
 
 #[repr(C)]
@@ -54,14 +71,24 @@ impl Default for MaterialId {
     }
 }
 
+/// Slot of an instance in the `InstancePool`, plus the generation of the slot so handles
+/// to removed instances are told apart from the ones reusing the slot.
 #[repr(C)]
-#[derive(Debug, Default, Clone, Copy, Zeroable, Pod)]
-pub struct InstanceId(pub u32);
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Zeroable, Pod)]
+pub struct InstanceId(pub u32, u32);
 
 impl InstanceId {
+    pub const fn new(index: u32, generation: u32) -> Self {
+        Self(index, generation)
+    }
+
     pub fn id(&self) -> u32 {
         self.0
     }
+
+    pub fn generation(&self) -> u32 {
+        self.1
+    }
 }
 
 #[repr(C)]
@@ -71,7 +98,8 @@ pub struct Instance {
     inv_transform: glam::Mat4,
     pub mesh: MeshId,
     pub material: MaterialId,
-    junk: [u32; 2],
+    pub flags: u32,
+    junk: u32,
 }
 
 impl Default for Instance {
@@ -81,22 +109,54 @@ impl Default for Instance {
             inv_transform: Mat4::IDENTITY,
             mesh: MeshId::default(),
             material: MaterialId::default(),
-            junk: [0; 2],
+            flags: 0,
+            junk: 0,
         }
     }
 }
 
 impl Instance {
+    /// Raised for a frame after the mesh or material is swapped, TAA drops the history there.
+    pub const CHANGED: u32 = 1;
+    /// Moved from the cpu after creation, the rest of the scene is treated as static.
+    pub const DYNAMIC: u32 = 2;
+    /// Skipped by culling and tracing, raised on pool slots released by streaming.
+    pub const INACTIVE: u32 = 4;
+
     pub fn new(transform: glam::Mat4, mesh: MeshId, material: MaterialId) -> Self {
         Self {
             transform,
             inv_transform: transform.inverse(),
             mesh,
             material,
-            junk: [0; 2],
+            flags: 0,
+            junk: 0,
         }
     }
 
+    /// Marks the instance as dynamic, only these can be moved with `InstancePool::set_transform`.
+    pub fn dynamic(mut self) -> Self {
+        self.flags |= Self::DYNAMIC;
+        self
+    }
+
+    pub fn is_dynamic(&self) -> bool {
+        self.flags & Self::DYNAMIC != 0
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.flags & Self::INACTIVE == 0
+    }
+
+    pub fn inv_transform(&self) -> glam::Mat4 {
+        self.inv_transform
+    }
+
+    pub fn set_transform(&mut self, transform: glam::Mat4) {
+        self.transform = transform;
+        self.inv_transform = transform.inverse();
+    }
+
     pub fn transform(&mut self, transform: glam::Mat4) {
         self.transform = transform * self.transform;
     }
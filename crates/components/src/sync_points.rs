@@ -0,0 +1,74 @@
+use std::time::{Duration, Instant};
+
+/// What the cpu waited for at a [`SyncPoint`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncKind {
+    /// `Device::poll` blocking until a submission finished.
+    Poll,
+    /// A buffer mapped for a blocking cpu read back.
+    BufferMap,
+    /// `Queue::submit`, which blocks once the driver queue is full.
+    Submit,
+}
+
+impl SyncKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Poll => "POLL",
+            Self::BufferMap => "MAP",
+            Self::Submit => "SUBMIT",
+        }
+    }
+}
+
+/// A place where the cpu waited on the gpu, `start` is relative to the frame start.
+#[derive(Debug, Clone)]
+pub struct SyncPoint {
+    pub kind: SyncKind,
+    pub label: &'static str,
+    pub start: Duration,
+    pub duration: Duration,
+}
+
+/// Sync points of the frame in progress and the last finished one, recorded through
+/// [`Gpu::sync`](crate::Gpu::sync).
+#[derive(Debug)]
+pub struct SyncPoints {
+    frame_start: Instant,
+    current: Vec<SyncPoint>,
+    last_frame: Vec<SyncPoint>,
+}
+
+impl SyncPoints {
+    pub fn new() -> Self {
+        Self {
+            frame_start: Instant::now(),
+            current: vec![],
+            last_frame: vec![],
+        }
+    }
+
+    pub fn record(&mut self, kind: SyncKind, label: &'static str, start: Instant) {
+        self.current.push(SyncPoint {
+            kind,
+            label,
+            start: start.saturating_duration_since(self.frame_start),
+            duration: start.elapsed(),
+        });
+    }
+
+    /// Moves the points of the frame in progress to [`SyncPoints::last_frame`].
+    pub fn end_frame(&mut self) {
+        self.last_frame = std::mem::take(&mut self.current);
+        self.frame_start = Instant::now();
+    }
+
+    pub fn last_frame(&self) -> &[SyncPoint] {
+        &self.last_frame
+    }
+
+    /// Time the last frame spent waiting on the gpu.
+    pub fn last_frame_total(&self) -> Duration {
+        self.last_frame.iter().map(|point| point.duration).sum()
+    }
+}
@@ -3,7 +3,6 @@ use notify_debouncer_mini::{DebounceEventResult, DebouncedEventKind};
 use winit::event_loop::EventLoopProxy;
 
 use std::{
-    ffi::OsStr,
     path::{Path, PathBuf},
     time::Duration,
 };
@@ -14,9 +13,16 @@ pub struct Watcher {
 
 impl Watcher {
     pub fn new(proxy: EventLoopProxy<PathBuf>) -> Result<Self> {
+        Self::with_callback(move |path| {
+            proxy.send_event(path).expect("Event Loop has been dropped")
+        })
+    }
+
+    /// Calls `on_change` with the path of every modified watched file.
+    pub fn with_callback(on_change: impl Fn(PathBuf) + Send + 'static) -> Result<Self> {
         let watcher = notify_debouncer_mini::new_debouncer(
             Duration::from_millis(100),
-            watch_callback(proxy),
+            watch_callback(on_change),
         )?;
 
         Ok(Self { watcher })
@@ -35,7 +41,7 @@ impl Watcher {
     }
 }
 
-fn watch_callback(proxy: EventLoopProxy<PathBuf>) -> impl FnMut(DebounceEventResult) {
+fn watch_callback(on_change: impl Fn(PathBuf)) -> impl FnMut(DebounceEventResult) {
     move |event| match event {
         Ok(events) => {
             if let Some(path) = events
@@ -44,13 +50,7 @@ fn watch_callback(proxy: EventLoopProxy<PathBuf>) -> impl FnMut(DebounceEventRes
                 .map(|event| event.path)
                 .next()
             {
-                assert_eq!(
-                    path.extension(),
-                    Some(OsStr::new("wgsl")),
-                    "TODO: Support glsl shaders."
-                );
-
-                proxy.send_event(path).expect("Event Loop has been dropped");
+                on_change(path);
             }
         }
         Err(errors) => eprintln!("File watcher error: {errors}"),
@@ -78,8 +78,142 @@ impl<R: Resource> AsRef<R> for Write<'_, R> {
     }
 }
 
+/// Handle to an entity of the [`World`], goes stale once it is despawned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Entity(pub u32, u32);
+
+impl Entity {
+    pub fn generation(&self) -> u32 {
+        self.1
+    }
+}
+
+#[derive(Default)]
+struct Entities {
+    // Current generation of every slot, bumped on despawn.
+    generations: Vec<u32>,
+    free: Vec<u32>,
+}
+
+/// Components of one type by entity, stored as a sparse set so iterating them is a walk
+/// over a dense array. Held by the [`World`] as a resource.
+pub struct Components<T> {
+    // Dense index + 1 by entity index, zero for entities without the component.
+    sparse: Vec<u32>,
+    entities: Vec<Entity>,
+    data: Vec<T>,
+}
+
+impl<T> Default for Components<T> {
+    fn default() -> Self {
+        Self {
+            sparse: vec![],
+            entities: vec![],
+            data: vec![],
+        }
+    }
+}
+
+impl<T> Components<T> {
+    /// Returns the component held by the slot of the entity before, which a stale
+    /// entity of the same slot loses.
+    pub fn insert(&mut self, entity: Entity, value: T) -> Option<T> {
+        let index = entity.0 as usize;
+        if self.sparse.len() <= index {
+            self.sparse.resize(index + 1, 0);
+        }
+        match self.sparse[index].checked_sub(1) {
+            Some(dense) => {
+                let dense = dense as usize;
+                self.entities[dense] = entity;
+                Some(std::mem::replace(&mut self.data[dense], value))
+            }
+            None => {
+                self.entities.push(entity);
+                self.data.push(value);
+                self.sparse[index] = self.data.len() as u32;
+                None
+            }
+        }
+    }
+
+    pub fn remove(&mut self, entity: Entity) -> Option<T> {
+        let dense = self.dense(entity)?;
+        self.sparse[entity.0 as usize] = 0;
+        let value = self.data.swap_remove(dense);
+        self.entities.swap_remove(dense);
+        if let Some(moved) = self.entities.get(dense) {
+            self.sparse[moved.0 as usize] = dense as u32 + 1;
+        }
+        Some(value)
+    }
+
+    pub fn get(&self, entity: Entity) -> Option<&T> {
+        self.dense(entity).map(|dense| &self.data[dense])
+    }
+
+    pub fn get_mut(&mut self, entity: Entity) -> Option<&mut T> {
+        self.dense(entity).map(|dense| &mut self.data[dense])
+    }
+
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.dense(entity).is_some()
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Entity, &T)> {
+        self.entities.iter().copied().zip(&self.data)
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (Entity, &mut T)> {
+        self.entities.iter().copied().zip(&mut self.data)
+    }
+
+    /// Entities having both components, walks the smaller of the two sets.
+    pub fn join<'a, U>(
+        &'a self,
+        other: &'a Components<U>,
+    ) -> impl Iterator<Item = (Entity, &'a T, &'a U)> {
+        let smaller = match self.len() <= other.len() {
+            true => &self.entities,
+            false => &other.entities,
+        };
+        smaller
+            .iter()
+            .filter_map(move |&entity| Some((entity, self.get(entity)?, other.get(entity)?)))
+    }
+
+    /// Like [`Components::join`], with these components mutable.
+    pub fn join_mut<'a, U>(
+        &'a mut self,
+        other: &'a Components<U>,
+    ) -> impl Iterator<Item = (Entity, &'a mut T, &'a U)> {
+        self.entities
+            .iter()
+            .copied()
+            .zip(&mut self.data)
+            .filter_map(|(entity, value)| Some((entity, value, other.get(entity)?)))
+    }
+
+    fn dense(&self, entity: Entity) -> Option<usize> {
+        let dense = *self.sparse.get(entity.0 as usize)? as usize;
+        let found = dense.checked_sub(1)?;
+        (self.entities[found] == entity).then_some(found)
+    }
+}
+
 pub struct World {
     pub(crate) resources: AHashMap<TypeId, RefCell<Box<dyn Resource>>>,
+    entities: RefCell<Entities>,
+    // Drop the components of a despawned entity, one per registered component type.
+    despawners: Vec<fn(&World, Entity)>,
     pub gpu: Arc<Gpu>,
 }
 
@@ -87,6 +221,8 @@ impl World {
     pub fn new(gpu: Arc<Gpu>) -> Self {
         Self {
             resources: AHashMap::new(),
+            entities: RefCell::default(),
+            despawners: vec![],
             gpu,
         }
     }
@@ -152,6 +288,70 @@ impl World {
         self.resources.contains_key(&TypeId::of::<R>())
     }
 
+    /// Creates the storage of a component type, after which components of it can be
+    /// added through [`World::components_mut`] without a mutable world.
+    pub fn register<T: 'static>(&mut self) {
+        if self.contains::<Components<T>>() {
+            return;
+        }
+        self.insert(Components::<T>::default());
+        self.despawners.push(|world, entity| {
+            if let Ok(mut components) = world.get_mut::<Components<T>>() {
+                components.remove(entity);
+            }
+        });
+    }
+
+    pub fn spawn(&self) -> Entity {
+        let mut entities = self.entities.borrow_mut();
+        match entities.free.pop() {
+            Some(index) => Entity(index, entities.generations[index as usize]),
+            None => {
+                entities.generations.push(0);
+                Entity(entities.generations.len() as u32 - 1, 0)
+            }
+        }
+    }
+
+    /// Drops every component of the entity and frees its slot for [`World::spawn`].
+    pub fn despawn(&self, entity: Entity) -> Result<()> {
+        if !self.is_alive(entity) {
+            return Err(eyre!(
+                "Entity {} of generation {} is stale",
+                entity.0,
+                entity.1
+            ));
+        }
+        for despawn in &self.despawners {
+            despawn(self, entity);
+        }
+        let mut entities = self.entities.borrow_mut();
+        let generation = &mut entities.generations[entity.0 as usize];
+        *generation = generation.wrapping_add(1);
+        entities.free.push(entity.0);
+        Ok(())
+    }
+
+    pub fn is_alive(&self, entity: Entity) -> bool {
+        self.entities.borrow().generations.get(entity.0 as usize) == Some(&entity.1)
+    }
+
+    /// Adds a component to the entity, registering its type on first use.
+    pub fn insert_component<T: 'static>(&mut self, entity: Entity, value: T) -> Result<()> {
+        self.register::<T>();
+        self.components_mut::<T>()?.insert(entity, value);
+        Ok(())
+    }
+
+    /// Components of a registered type, [`Components::join`] queries several of them.
+    pub fn components<T: 'static>(&self) -> Result<Read<Components<T>>> {
+        self.get::<Components<T>>()
+    }
+
+    pub fn components_mut<T: 'static>(&self) -> Result<Write<Components<T>>> {
+        self.get_mut::<Components<T>>()
+    }
+
     pub fn device(&self) -> &wgpu::Device {
         self.gpu.device()
     }
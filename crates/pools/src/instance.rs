@@ -1,8 +1,17 @@
-use std::sync::Arc;
+use std::{collections::BTreeMap, sync::Arc};
+
+use bytemuck::{Pod, Zeroable};
+use glam::{Mat4, Quat, Vec3A};
+
+use color_eyre::{
+    eyre::{bail, WrapErr},
+    Result,
+};
 
 use components::{
     bind_group_layout::{self, WrappedBindGroupLayout},
-    Gpu, Instance, InstanceId, NonZeroSized, ResizableBuffer, ResizableBufferExt,
+    Gpu, Instance, InstanceId, MaterialId, MeshId, NonZeroSized, ResizableBuffer,
+    ResizableBufferExt,
 };
 
 pub struct InstancePool {
@@ -11,9 +20,92 @@ pub struct InstancePool {
 
     pub bind_group: wgpu::BindGroup,
     pub bind_group_layout: bind_group_layout::BindGroupLayout,
+    // Instances with `Instance::CHANGED` raised, cleared by `clear_changed`.
+    changed: Vec<InstanceId>,
+    dynamic: Vec<InstanceId>,
+    // Raised by `set_transform`, the dynamic TLAS segment is rebuilt when taken.
+    dynamic_moved: bool,
+    // Current generation of every slot, bumped on removal.
+    generations: Vec<u32>,
+    // Removed slots, reused by `insert`.
+    free: Vec<u32>,
+    // Slots changed since the last `flush`, keyed by index so runs are written together.
+    dirty: BTreeMap<u32, Dirty>,
+    // Instances moved during the last two fixed steps, by index.
+    motions: BTreeMap<u32, InstanceMotion>,
+    motions_dirty: bool,
+    motion_buffer: ResizableBuffer<InstanceMotion>,
+    /// Motions of the moving instances, blended by the instance interpolation pass.
+    pub motion_bind_group: wgpu::BindGroup,
+    pub motion_bind_group_layout: bind_group_layout::BindGroupLayout,
     gpu: Arc<Gpu>,
 }
 
+/// Transform of a moving instance at the start and the end of the current fixed step,
+/// decomposed so the gpu can blend rotations. Mirrored in `interpolate.wgsl`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+struct InstanceMotion {
+    prev_rotation: Quat,
+    rotation: Quat,
+    prev_translation: Vec3A,
+    translation: Vec3A,
+    prev_scale: Vec3A,
+    scale: Vec3A,
+    index: u32,
+    junk: [u32; 3],
+}
+
+impl InstanceMotion {
+    fn new(index: u32, previous: Mat4, current: Mat4) -> Self {
+        let (prev_scale, prev_rotation, prev_translation) =
+            previous.to_scale_rotation_translation();
+        let mut motion = Self {
+            prev_rotation,
+            rotation: prev_rotation,
+            prev_translation: prev_translation.into(),
+            translation: prev_translation.into(),
+            prev_scale: prev_scale.into(),
+            scale: prev_scale.into(),
+            index,
+            junk: [0; 3],
+        };
+        motion.set_current(current);
+        motion
+    }
+
+    fn set_current(&mut self, current: Mat4) {
+        let (scale, rotation, translation) = current.to_scale_rotation_translation();
+        // Blend along the shorter arc.
+        self.rotation = if self.prev_rotation.dot(rotation) < 0. {
+            -rotation
+        } else {
+            rotation
+        };
+        self.translation = translation.into();
+        self.scale = scale.into();
+    }
+
+    fn is_at_rest(&self) -> bool {
+        self.prev_rotation == self.rotation
+            && self.prev_translation == self.translation
+            && self.prev_scale == self.scale
+    }
+
+    fn step(&mut self) {
+        self.prev_rotation = self.rotation;
+        self.prev_translation = self.translation;
+        self.prev_scale = self.scale;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Dirty {
+    /// Mesh, material and flags, the transform may be animated on the gpu.
+    Tail,
+    Whole,
+}
+
 impl InstancePool {
     const LAYOUT: wgpu::BindGroupLayoutDescriptor<'static> = wgpu::BindGroupLayoutDescriptor {
         label: Some("Draw Instances Bind Group Layout"),
@@ -29,6 +121,21 @@ impl InstancePool {
         }],
     };
 
+    const MOTION_LAYOUT: wgpu::BindGroupLayoutDescriptor<'static> =
+        wgpu::BindGroupLayoutDescriptor {
+            label: Some("Instance Motions Bind Group Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: Some(InstanceMotion::NSIZE),
+                },
+                count: None,
+            }],
+        };
+
     pub fn new(gpu: Arc<Gpu>) -> Self {
         let instances_data = Vec::with_capacity(32);
         let instances = gpu.device().create_resizable_buffer(
@@ -38,15 +145,50 @@ impl InstancePool {
         let bind_group_layout = gpu.device().create_bind_group_layout_wrap(&Self::LAYOUT);
         let bind_group = Self::create_bind_group(gpu.device(), &bind_group_layout, &instances);
 
+        let motion_buffer = gpu
+            .device()
+            .create_resizable_buffer(wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST);
+        let motion_bind_group_layout = gpu
+            .device()
+            .create_bind_group_layout_wrap(&Self::MOTION_LAYOUT);
+        let motion_bind_group =
+            Self::create_motion_bind_group(gpu.device(), &motion_bind_group_layout, &motion_buffer);
+
         Self {
             instances_data,
             instances,
             bind_group,
             bind_group_layout,
+            changed: vec![],
+            dynamic: vec![],
+            dynamic_moved: false,
+            generations: vec![],
+            free: vec![],
+            dirty: BTreeMap::new(),
+            motions: BTreeMap::new(),
+            motions_dirty: false,
+            motion_buffer,
+            motion_bind_group,
+            motion_bind_group_layout,
             gpu,
         }
     }
 
+    fn create_motion_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        motions: &ResizableBuffer<InstanceMotion>,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Instance Motions Bind Group"),
+            layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: motions.as_tight_binding(),
+            }],
+        })
+    }
+
     pub fn create_bind_group(
         device: &wgpu::Device,
         layout: &wgpu::BindGroupLayout,
@@ -64,18 +206,276 @@ impl InstancePool {
         bind_group
     }
 
-    pub fn add(&mut self, instances: &[Instance]) -> Vec<InstanceId> {
-        let initial_len = self.instances.len();
+    /// Fails without changing the pool when the buffer can't grow.
+    pub fn add(&mut self, instances: &[Instance]) -> Result<Vec<InstanceId>> {
+        let initial_len = self.instances_data.len();
+        let new_len = initial_len + instances.len();
+        if !self.instances.fits(self.gpu.device(), instances.len()) {
+            bail!("InstancePool can't grow to {new_len} entries past the buffer size limits");
+        }
+        let grown = self.gpu.error_scope(|| {
+            self.instances.push(&self.gpu, instances);
+            self.bind_group = Self::create_bind_group(
+                self.gpu.device(),
+                &self.bind_group_layout,
+                &self.instances,
+            );
+        });
+        if let Err(err) = grown {
+            // Ids are handed out from `instances_data`, the gpu length has to follow it.
+            self.instances.truncate(initial_len);
+            return Err(err)
+                .wrap_err_with(|| format!("while growing InstancePool to {new_len} entries"));
+        }
         self.instances_data.extend_from_slice(instances);
-        self.instances.push(&self.gpu, instances);
-        let bind_group =
-            Self::create_bind_group(self.gpu.device(), &self.bind_group_layout, &self.instances);
-        self.bind_group = bind_group;
+        self.generations.resize(self.instances_data.len(), 0);
 
-        (initial_len..)
+        let ids: Vec<_> = (initial_len..)
             .take(instances.len())
-            .map(|x| InstanceId(x as u32))
-            .collect()
+            .map(|x| InstanceId::new(x as u32, 0))
+            .collect();
+        self.dynamic.extend(
+            ids.iter()
+                .zip(instances)
+                .filter(|(_, instance)| instance.is_dynamic())
+                .map(|(id, _)| *id),
+        );
+        Ok(ids)
+    }
+
+    /// Adds a single instance into a removed slot if there is one.
+    ///
+    /// Unlike [`InstancePool::add`] the ids of consecutive calls are not contiguous.
+    pub fn insert(&mut self, instance: Instance) -> Result<InstanceId> {
+        let Some(index) = self.free.pop() else {
+            return Ok(self.add(&[instance])?[0]);
+        };
+        self.instances_data[index as usize] = instance;
+        self.mark_dirty(index, Dirty::Whole);
+        let id = InstanceId::new(index, self.generations[index as usize]);
+        if instance.is_dynamic() {
+            self.dynamic.push(id);
+            self.dynamic_moved = true;
+        }
+        Ok(id)
+    }
+
+    /// Deactivates the instance and frees its slot for [`InstancePool::insert`].
+    ///
+    /// Handles to it go stale, the pool ignores them from now on.
+    pub fn remove(&mut self, id: InstanceId) -> Result<()> {
+        if !self.contains(id) {
+            bail!(
+                "Instance {} of generation {} is stale",
+                id.0,
+                id.generation()
+            );
+        }
+        let index = id.0 as usize;
+        self.generations[index] = self.generations[index].wrapping_add(1);
+        self.instances_data[index].flags |= Instance::INACTIVE;
+        if self.instances_data[index].is_dynamic() {
+            self.dynamic.retain(|dynamic| dynamic.0 != id.0);
+            self.dynamic_moved = true;
+            self.motions_dirty |= self.motions.remove(&id.0).is_some();
+        }
+        self.free.push(id.0);
+        self.mark_dirty(id.0, Dirty::Tail);
+        Ok(())
+    }
+
+    /// Returns `false` for ids of removed instances.
+    pub fn contains(&self, id: InstanceId) -> bool {
+        self.generations.get(id.0 as usize) == Some(&id.generation())
+    }
+
+    /// Current id of the instance in slot `index`, `None` for removed slots and past the end.
+    pub fn id(&self, index: u32) -> Option<InstanceId> {
+        let generation = *self.generations.get(index as usize)?;
+        (!self.free.contains(&index)).then(|| InstanceId::new(index, generation))
+    }
+
+    // Stale ids are logged and skipped, like moves of static instances.
+    fn live(&self, id: InstanceId) -> bool {
+        let live = self.contains(id);
+        if !live {
+            log::warn!(
+                "Attempted to use stale instance {} of generation {}",
+                id.0,
+                id.generation()
+            );
+        }
+        live
+    }
+
+    /// Moves a dynamic instance, static ones keep the transform they were added with.
+    pub fn set_transform(&mut self, id: InstanceId, transform: glam::Mat4) {
+        if !self.live(id) {
+            return;
+        }
+        let instance = &mut self.instances_data[id.0 as usize];
+        if !instance.is_dynamic() {
+            log::warn!("Attempted to move static instance {}", id.0);
+            return;
+        }
+        let previous = instance.transform;
+        instance.set_transform(transform);
+        self.motions
+            .entry(id.0)
+            .and_modify(|motion| motion.set_current(transform))
+            .or_insert_with(|| InstanceMotion::new(id.0, previous, transform));
+        self.motions_dirty = true;
+        self.mark_dirty(id.0, Dirty::Whole);
+        self.dynamic_moved = true;
+    }
+
+    /// Starts a fixed step, the transforms set so far become the ones moves blend from.
+    ///
+    /// Instances that weren't moved during the last step stop being interpolated and get
+    /// their exact transform back.
+    pub fn begin_step(&mut self) {
+        if self.motions.is_empty() {
+            return;
+        }
+        let mut at_rest = vec![];
+        self.motions.retain(|&index, motion| {
+            if motion.is_at_rest() {
+                at_rest.push(index);
+                return false;
+            }
+            motion.step();
+            true
+        });
+        for index in at_rest {
+            self.mark_dirty(index, Dirty::Whole);
+        }
+        self.motions_dirty = true;
+    }
+
+    /// Instances blended by the instance interpolation pass.
+    pub fn motion_count(&self) -> u32 {
+        self.motions.len() as u32
+    }
+
+    pub fn dynamic_instances(&self) -> &[InstanceId] {
+        &self.dynamic
+    }
+
+    /// Returns `true` once after any dynamic instance was moved.
+    pub fn take_dynamic_moved(&mut self) -> bool {
+        std::mem::take(&mut self.dynamic_moved)
+    }
+
+    pub fn set_mesh(&mut self, id: InstanceId, mesh: MeshId) {
+        if !self.live(id) {
+            return;
+        }
+        self.instances_data[id.0 as usize].mesh = mesh;
+        self.mark_changed(id);
+    }
+
+    pub fn set_material(&mut self, id: InstanceId, material: MaterialId) {
+        if !self.live(id) {
+            return;
+        }
+        self.instances_data[id.0 as usize].material = material;
+        self.mark_changed(id);
+    }
+
+    /// Overwrites a run of instances starting at `first`.
+    pub fn write(&mut self, first: InstanceId, instances: &[Instance]) {
+        let first = first.0 as usize;
+        self.instances_data[first..first + instances.len()].copy_from_slice(instances);
+        self.instances.write_slice(&self.gpu, first, instances);
+    }
+
+    pub fn set_active(&mut self, id: InstanceId, active: bool) {
+        if !self.live(id) {
+            return;
+        }
+        let instance = &mut self.instances_data[id.0 as usize];
+        if active {
+            instance.flags &= !Instance::INACTIVE;
+        } else {
+            instance.flags |= Instance::INACTIVE;
+        }
+        self.mark_dirty(id.0, Dirty::Tail);
+    }
+
+    fn mark_changed(&mut self, id: InstanceId) {
+        let instance = &mut self.instances_data[id.0 as usize];
+        if instance.flags & Instance::CHANGED == 0 {
+            instance.flags |= Instance::CHANGED;
+            self.changed.push(id);
+        }
+        self.mark_dirty(id.0, Dirty::Tail);
+    }
+
+    /// Lowers `Instance::CHANGED` on everything swapped since the last call.
+    ///
+    /// Called after submitting a frame, so every change is seen by exactly one frame.
+    pub fn clear_changed(&mut self) {
+        for id in std::mem::take(&mut self.changed) {
+            self.instances_data[id.0 as usize].flags &= !Instance::CHANGED;
+            self.mark_dirty(id.0, Dirty::Tail);
+        }
+    }
+
+    fn mark_dirty(&mut self, index: u32, dirty: Dirty) {
+        let entry = self.dirty.entry(index).or_insert(dirty);
+        *entry = (*entry).max(dirty);
+    }
+
+    /// Uploads the instances changed since the last call, once per frame before rendering.
+    ///
+    /// Runs of whole instances go out in one write, the rest only writes the tail.
+    pub fn flush(&mut self) {
+        let mut run: Option<(u32, u32)> = None;
+        for (&index, &dirty) in std::mem::take(&mut self.dirty).iter() {
+            if dirty == Dirty::Tail {
+                self.write_mesh_material_flags(index);
+                continue;
+            }
+            match &mut run {
+                Some((_, end)) if *end == index => *end += 1,
+                _ => {
+                    if let Some(run) = run.replace((index, index + 1)) {
+                        self.write_run(run);
+                    }
+                }
+            }
+        }
+        if let Some(run) = run {
+            self.write_run(run);
+        }
+
+        if std::mem::take(&mut self.motions_dirty) {
+            let motions: Vec<_> = self.motions.values().copied().collect();
+            self.motion_buffer.clear();
+            if !motions.is_empty() {
+                self.motion_buffer.push(&self.gpu, &motions);
+            }
+            self.motion_bind_group = Self::create_motion_bind_group(
+                self.gpu.device(),
+                &self.motion_bind_group_layout,
+                &self.motion_buffer,
+            );
+        }
+    }
+
+    fn write_run(&mut self, (start, end): (u32, u32)) {
+        let (start, end) = (start as usize, end as usize);
+        self.instances
+            .write_slice(&self.gpu, start, &self.instances_data[start..end]);
+    }
+
+    // Transforms are animated on the gpu, so only the tail of the instance is written.
+    fn write_mesh_material_flags(&mut self, index: u32) {
+        let instance = &self.instances_data[index as usize];
+        let data = [instance.mesh.0, instance.material.0, instance.flags];
+        let offset = index as usize * Instance::SIZE + std::mem::offset_of!(Instance, mesh);
+        self.instances
+            .write_bytes(&self.gpu, offset as _, bytemuck::cast_slice(&data));
     }
 
     pub fn count(&self) -> u32 {
@@ -85,5 +485,13 @@ impl InstancePool {
     pub fn clear(&mut self) {
         self.instances_data.clear();
         self.instances.clear();
+        self.changed.clear();
+        self.dynamic.clear();
+        self.dynamic_moved = false;
+        self.generations.clear();
+        self.free.clear();
+        self.dirty.clear();
+        self.motions.clear();
+        self.motions_dirty = true;
     }
 }
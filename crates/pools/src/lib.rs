@@ -2,10 +2,12 @@ mod instance;
 mod light;
 mod material;
 mod mesh;
+mod skinned;
 mod texture;
 
 pub use instance::*;
 pub use light::*;
 pub use material::*;
 pub use mesh::*;
+pub use skinned::*;
 pub use texture::*;
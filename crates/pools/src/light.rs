@@ -1,5 +1,10 @@
 use std::sync::Arc;
 
+use color_eyre::{
+    eyre::{bail, WrapErr},
+    Result,
+};
+
 use components::{
     bind_group_layout::{self, WrappedBindGroupLayout},
     Gpu, NonZeroSized, ResizableBuffer,
@@ -7,6 +12,9 @@ use components::{
 
 use bytemuck::{Pod, Zeroable};
 use glam::{vec3, Mat4, Vec2, Vec3, Vec3Swizzles, Vec4};
+use wgpu::util::DeviceExt;
+
+use crate::{TextureId, WHITE_TEXTURE};
 
 #[repr(C)]
 #[derive(Debug, Copy, Clone, Default, Pod, Zeroable)]
@@ -14,6 +22,9 @@ pub struct AreaLight {
     pub color: Vec3,
     pub intensity: f32,
     pub points: [Vec4; 4],
+    // Set by `LightPool`.
+    flags: u32,
+    junk: [u32; 3],
 }
 
 impl AreaLight {
@@ -22,6 +33,8 @@ impl AreaLight {
             color,
             intensity,
             points: points.map(|v| v.extend(0.)),
+            flags: 0,
+            junk: [0; 3],
         }
     }
 
@@ -44,11 +57,7 @@ impl AreaLight {
             trans - dx + dy,
         ];
 
-        Self {
-            color,
-            intensity,
-            points: points.map(|v| v.extend(0.)),
-        }
+        Self::new(color, intensity, points)
     }
 }
 
@@ -58,7 +67,11 @@ pub struct Light {
     pub position: glam::Vec3,
     pub radius: f32,
     pub color: glam::Vec3,
-    _padding: u32,
+    // Set by `LightPool`.
+    flags: u32,
+    /// IES profile around -Y, [`WHITE_TEXTURE`] lights evenly.
+    pub ies: TextureId,
+    junk: [u32; 3],
 }
 
 impl Light {
@@ -67,24 +80,324 @@ impl Light {
             position,
             radius,
             color,
-            _padding: 0,
+            flags: 0,
+            ies: WHITE_TEXTURE,
+            junk: [0; 3],
+        }
+    }
+
+    /// Shapes the light with a profile of `app::IesProfile::import`.
+    pub fn with_ies(self, ies: TextureId) -> Self {
+        Self { ies, ..self }
+    }
+}
+
+/// Point light limited to a cone around `direction`, fading out between the two angles.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default, Pod, Zeroable)]
+pub struct SpotLight {
+    pub position: Vec3,
+    pub radius: f32,
+    /// Points from the light along the axis of the cone.
+    pub direction: Vec3,
+    /// Cosine of the angle where the falloff starts.
+    pub cos_inner: f32,
+    pub color: Vec3,
+    /// Cosine of the angle where the light ends.
+    pub cos_outer: f32,
+    // Set by `LightPool`.
+    flags: u32,
+    /// IES profile around `direction`, [`WHITE_TEXTURE`] lights evenly.
+    pub ies: TextureId,
+    junk: [u32; 2],
+}
+
+impl SpotLight {
+    /// Angles are in radians from the axis, the outer one is kept past the inner one.
+    pub fn new(
+        position: Vec3,
+        direction: Vec3,
+        radius: f32,
+        color: Vec3,
+        inner_angle: f32,
+        outer_angle: f32,
+    ) -> Self {
+        Self {
+            position,
+            radius,
+            direction: direction.normalize_or_zero(),
+            cos_inner: inner_angle.cos(),
+            color,
+            cos_outer: outer_angle.max(inner_angle + 1e-3).cos(),
+            flags: 0,
+            ies: WHITE_TEXTURE,
+            junk: [0; 2],
+        }
+    }
+
+    /// Shapes the light with a profile of `app::IesProfile::import`.
+    pub fn with_ies(self, ies: TextureId) -> Self {
+        Self { ies, ..self }
+    }
+}
+
+/// Raised on lights that shaders skip, `LIGHT_DISABLED` in `shared.wgsl`.
+const LIGHT_DISABLED: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LightKind {
+    Point,
+    Area,
+    Spot,
+    Directional,
+}
+
+/// Handle of a light in the [`LightPool`], stale once the light is removed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LightId {
+    kind: LightKind,
+    index: u32,
+    generation: u32,
+}
+
+impl LightId {
+    pub fn kind(&self) -> LightKind {
+        self.kind
+    }
+
+    /// Index into the buffer of lights of its kind.
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+}
+
+/// Any kind of light, for [`LightPool::update_light`].
+#[derive(Debug, Clone, Copy)]
+pub enum AnyLight {
+    Point(Light),
+    Area(AreaLight),
+    Spot(SpotLight),
+    Directional(DirectionalLight),
+}
+
+impl From<Light> for AnyLight {
+    fn from(light: Light) -> Self {
+        Self::Point(light)
+    }
+}
+
+impl From<AreaLight> for AnyLight {
+    fn from(light: AreaLight) -> Self {
+        Self::Area(light)
+    }
+}
+
+impl From<SpotLight> for AnyLight {
+    fn from(light: SpotLight) -> Self {
+        Self::Spot(light)
+    }
+}
+
+impl From<DirectionalLight> for AnyLight {
+    fn from(light: DirectionalLight) -> Self {
+        Self::Directional(light)
+    }
+}
+
+impl AnyLight {
+    fn flags(&mut self) -> &mut u32 {
+        match self {
+            Self::Point(light) => light.flags(),
+            Self::Area(light) => light.flags(),
+            Self::Spot(light) => light.flags(),
+            Self::Directional(light) => light.flags(),
         }
     }
 }
 
+trait LightFlags: Pod + NonZeroSized {
+    fn flags(&mut self) -> &mut u32;
+}
+
+impl LightFlags for Light {
+    fn flags(&mut self) -> &mut u32 {
+        &mut self.flags
+    }
+}
+
+impl LightFlags for AreaLight {
+    fn flags(&mut self) -> &mut u32 {
+        &mut self.flags
+    }
+}
+
+impl LightFlags for SpotLight {
+    fn flags(&mut self) -> &mut u32 {
+        &mut self.flags
+    }
+}
+
+impl LightFlags for DirectionalLight {
+    fn flags(&mut self) -> &mut u32 {
+        &mut self.flags
+    }
+}
+
+/// Cpu mirror of the lights of one kind and the slots of removed ones.
+struct Slots<T> {
+    kind: LightKind,
+    data: Vec<T>,
+    // Current generation of every slot, bumped on removal.
+    generations: Vec<u32>,
+    free: Vec<u32>,
+}
+
+impl<T: LightFlags> Slots<T> {
+    fn new(kind: LightKind) -> Self {
+        Self {
+            kind,
+            data: vec![],
+            generations: vec![],
+            free: vec![],
+        }
+    }
+
+    fn get(&self, id: LightId) -> Option<&T> {
+        let live =
+            id.kind == self.kind && self.generations.get(id.index as usize) == Some(&id.generation);
+        live.then(|| &self.data[id.index as usize])
+    }
+
+    fn id(&self, index: u32) -> LightId {
+        LightId {
+            kind: self.kind,
+            index,
+            generation: self.generations[index as usize],
+        }
+    }
+
+    fn ids(&self) -> Vec<LightId> {
+        (0..self.data.len() as u32)
+            .filter(|index| !self.free.contains(index))
+            .map(|index| self.id(index))
+            .collect()
+    }
+
+    /// Puts lights into removed slots, as many as there are.
+    fn reuse(&mut self, gpu: &Gpu, buffer: &mut ResizableBuffer<T>, lights: &[T]) -> Vec<LightId> {
+        lights
+            .iter()
+            .map_while(|&light| {
+                let index = self.free.pop()?;
+                self.write(gpu, buffer, index, light);
+                Some(self.id(index))
+            })
+            .collect()
+    }
+
+    /// Mirrors lights pushed to the end of the buffer.
+    fn append(&mut self, lights: &[T]) -> Vec<LightId> {
+        let first = self.data.len() as u32;
+        self.data.extend_from_slice(lights);
+        self.generations.resize(self.data.len(), 0);
+        (first..self.data.len() as u32)
+            .map(|index| self.id(index))
+            .collect()
+    }
+
+    fn write(&mut self, gpu: &Gpu, buffer: &mut ResizableBuffer<T>, index: u32, light: T) {
+        self.data[index as usize] = light;
+        buffer.write(gpu, index as usize, light);
+    }
+
+    fn enabled(mut light: T) -> T {
+        *light.flags() &= !LIGHT_DISABLED;
+        light
+    }
+
+    /// Frees the slot for reuse, which makes `id` stale.
+    fn remove(&mut self, id: LightId) {
+        self.free.push(id.index);
+        let generation = &mut self.generations[id.index as usize];
+        *generation = generation.wrapping_add(1);
+    }
+}
+
+/// Light infinitely far away, the sun of the scene or one of [`LightPool::add_directional_light`].
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, Pod, Zeroable)]
+pub struct DirectionalLight {
+    /// Points from the scene towards the light.
+    pub direction: Vec3,
+    pub intensity: f32,
+    pub color: Vec3,
+    // Set by `LightPool`, the sun ignores it.
+    flags: u32,
+}
+
+impl DirectionalLight {
+    pub fn new(direction: Vec3, intensity: f32, color: Vec3) -> Self {
+        Self {
+            direction: direction.normalize_or_zero(),
+            intensity,
+            color,
+            flags: 0,
+        }
+    }
+}
+
+impl Default for DirectionalLight {
+    /// Afternoon sun that doesn't light anything, scenes opt in through the intensity.
+    fn default() -> Self {
+        Self::new(vec3(0.4, 0.6, 0.3), 0., Vec3::ONE)
+    }
+}
+
+/// Point, area, spot and directional lights and the sun of the scene.
+///
+/// Point lights are also binned into a froxel grid of light lists, filled every frame by
+/// `ShadingPass` and read through `point_bind_group`, see `shaders/utils/clusters.wgsl`.
+/// Spot and directional lights share `sun_bind_group` with the sun, at bindings 1 and 2.
 pub struct LightPool {
     pub(crate) point_lights: ResizableBuffer<Light>,
+    point_slots: Slots<Light>,
     pub point_bind_group_layout: bind_group_layout::BindGroupLayout,
     pub point_bind_group: wgpu::BindGroup,
 
+    cluster_counts: wgpu::Buffer,
+    cluster_lights: wgpu::Buffer,
+    /// Point lights plus the writable light lists, for the culling pass.
+    pub cluster_bind_group_layout: bind_group_layout::BindGroupLayout,
+    pub cluster_bind_group: wgpu::BindGroup,
+
     pub(crate) area_lights: ResizableBuffer<AreaLight>,
+    area_slots: Slots<AreaLight>,
     pub area_bind_group_layout: bind_group_layout::BindGroupLayout,
     pub area_bind_group: wgpu::BindGroup,
 
+    pub(crate) spot_lights: ResizableBuffer<SpotLight>,
+    spot_slots: Slots<SpotLight>,
+    pub(crate) directional_lights: ResizableBuffer<DirectionalLight>,
+    directional_slots: Slots<DirectionalLight>,
+
+    sun: DirectionalLight,
+    sun_buffer: wgpu::Buffer,
+    pub sun_bind_group_layout: bind_group_layout::BindGroupLayout,
+    pub sun_bind_group: wgpu::BindGroup,
+
     gpu: Arc<Gpu>,
 }
 
 impl LightPool {
+    /// Tiles across, tiles down and depth slices of the froxel grid.
+    pub const CLUSTER_GRID: [u32; 3] = [16, 9, 24];
+    /// Lights past this many in one cluster are dropped.
+    pub const MAX_LIGHTS_PER_CLUSTER: u32 = 128;
+
+    pub fn cluster_count() -> u32 {
+        Self::CLUSTER_GRID.iter().product()
+    }
+
     pub fn new(gpu: Arc<Gpu>) -> Self {
         let point_lights = ResizableBuffer::new(
             gpu.device(),
@@ -94,24 +407,67 @@ impl LightPool {
             gpu.device(),
             wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::VERTEX,
         );
+        let spot_lights = ResizableBuffer::new(gpu.device(), wgpu::BufferUsages::STORAGE);
+        let directional_lights = ResizableBuffer::new(gpu.device(), wgpu::BufferUsages::STORAGE);
+
+        let cluster_counts = gpu.device().create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Light Cluster Counts"),
+            size: Self::cluster_count() as u64 * std::mem::size_of::<u32>() as u64,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+        let cluster_lights = gpu.device().create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Light Cluster Lists"),
+            size: (Self::cluster_count() * Self::MAX_LIGHTS_PER_CLUSTER) as u64
+                * std::mem::size_of::<u32>() as u64,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
 
+        let storage_entry =
+            |binding, read_only, visibility, min_binding_size| wgpu::BindGroupLayoutEntry {
+                binding,
+                visibility,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only },
+                    has_dynamic_offset: false,
+                    min_binding_size,
+                },
+                count: None,
+            };
+        let visibility = wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::COMPUTE;
         let point_bind_group_layout =
             gpu.device()
                 .create_bind_group_layout_wrap(&wgpu::BindGroupLayoutDescriptor {
                     label: Some("Point Light Bind Group Layout"),
-                    entries: &[wgpu::BindGroupLayoutEntry {
-                        binding: 0,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Buffer {
-                            ty: wgpu::BufferBindingType::Storage { read_only: true },
-                            has_dynamic_offset: false,
-                            min_binding_size: Some(Light::NSIZE),
-                        },
-                        count: None,
-                    }],
+                    entries: &[
+                        storage_entry(0, true, visibility, Some(Light::NSIZE)),
+                        storage_entry(1, true, visibility, None),
+                        storage_entry(2, true, visibility, None),
+                    ],
+                });
+        let cluster_bind_group_layout =
+            gpu.device()
+                .create_bind_group_layout_wrap(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some("Light Cluster Bind Group Layout"),
+                    entries: &[
+                        storage_entry(0, true, wgpu::ShaderStages::COMPUTE, Some(Light::NSIZE)),
+                        storage_entry(1, false, wgpu::ShaderStages::COMPUTE, None),
+                        storage_entry(2, false, wgpu::ShaderStages::COMPUTE, None),
+                    ],
                 });
-        let point_bind_group =
-            Self::create_point_bind_group(&gpu, &point_bind_group_layout, &point_lights);
+        let point_bind_group = Self::create_point_bind_group(
+            &gpu,
+            &point_bind_group_layout,
+            &point_lights,
+            [&cluster_counts, &cluster_lights],
+        );
+        let cluster_bind_group = Self::create_point_bind_group(
+            &gpu,
+            &cluster_bind_group_layout,
+            &point_lights,
+            [&cluster_counts, &cluster_lights],
+        );
 
         let area_bind_group_layout =
             gpu.device()
@@ -119,7 +475,7 @@ impl LightPool {
                     label: Some("Area Light Bind Group Layout"),
                     entries: &[wgpu::BindGroupLayoutEntry {
                         binding: 0,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        visibility: wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::COMPUTE,
                         ty: wgpu::BindingType::Buffer {
                             ty: wgpu::BufferBindingType::Storage { read_only: true },
                             has_dynamic_offset: false,
@@ -131,14 +487,66 @@ impl LightPool {
         let area_bind_group =
             Self::create_area_bind_group(&gpu, &area_bind_group_layout, &area_lights);
 
+        let sun = DirectionalLight::default();
+        let sun_buffer = gpu
+            .device()
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Sun Light Buffer"),
+                contents: bytemuck::bytes_of(&sun),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            });
+        let sun_bind_group_layout =
+            gpu.device()
+                .create_bind_group_layout_wrap(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some("Sun Light Bind Group Layout"),
+                    entries: &[
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 0,
+                            visibility,
+                            ty: wgpu::BindingType::Buffer {
+                                ty: wgpu::BufferBindingType::Uniform,
+                                has_dynamic_offset: false,
+                                min_binding_size: Some(DirectionalLight::NSIZE),
+                            },
+                            count: None,
+                        },
+                        storage_entry(1, true, visibility, Some(SpotLight::NSIZE)),
+                        storage_entry(2, true, visibility, Some(DirectionalLight::NSIZE)),
+                    ],
+                });
+        let sun_bind_group = Self::create_sun_bind_group(
+            &gpu,
+            &sun_bind_group_layout,
+            &sun_buffer,
+            &spot_lights,
+            &directional_lights,
+        );
+
         Self {
             point_lights,
+            point_slots: Slots::new(LightKind::Point),
             point_bind_group_layout,
             point_bind_group,
 
+            cluster_counts,
+            cluster_lights,
+            cluster_bind_group_layout,
+            cluster_bind_group,
+
             area_lights,
+            area_slots: Slots::new(LightKind::Area),
             area_bind_group_layout,
             area_bind_group,
+
+            spot_lights,
+            spot_slots: Slots::new(LightKind::Spot),
+            directional_lights,
+            directional_slots: Slots::new(LightKind::Directional),
+
+            sun,
+            sun_buffer,
+            sun_bind_group_layout,
+            sun_bind_group,
             gpu,
         }
     }
@@ -148,14 +556,25 @@ impl LightPool {
         gpu: &Gpu,
         bind_group_layout: &wgpu::BindGroupLayout,
         lights: &ResizableBuffer<Light>,
+        [counts, cluster_lights]: [&wgpu::Buffer; 2],
     ) -> wgpu::BindGroup {
         gpu.device().create_bind_group(&wgpu::BindGroupDescriptor {
             label: Some("Point Light Pool Bind Group"),
             layout: bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: lights.as_tight_binding(),
-            }],
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: lights.as_tight_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: counts.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: cluster_lights.as_entire_binding(),
+                },
+            ],
         })
     }
 
@@ -174,21 +593,297 @@ impl LightPool {
         })
     }
 
-    pub fn add_point_light(&mut self, lights: &[Light]) {
-        self.point_lights.push(&self.gpu, lights);
-        self.point_bind_group = Self::create_point_bind_group(
+    fn create_sun_bind_group(
+        gpu: &Gpu,
+        bind_group_layout: &wgpu::BindGroupLayout,
+        sun: &wgpu::Buffer,
+        spot_lights: &ResizableBuffer<SpotLight>,
+        directional_lights: &ResizableBuffer<DirectionalLight>,
+    ) -> wgpu::BindGroup {
+        gpu.device().create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Sun Light Bind Group"),
+            layout: bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: sun.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: spot_lights.as_tight_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: directional_lights.as_tight_binding(),
+                },
+            ],
+        })
+    }
+
+    fn update_sun_bind_group(&mut self) {
+        self.sun_bind_group = Self::create_sun_bind_group(
             &self.gpu,
-            &self.point_bind_group_layout,
-            &self.point_lights,
+            &self.sun_bind_group_layout,
+            &self.sun_buffer,
+            &self.spot_lights,
+            &self.directional_lights,
         );
     }
 
-    pub fn add_area_light(&mut self, lights: &[AreaLight]) {
-        self.area_lights.push(&self.gpu, lights);
-        self.area_bind_group = Self::create_area_bind_group(
+    /// Adds enabled lights, filling the slots of removed ones first.
+    pub fn add_point_light(&mut self, lights: &[Light]) -> Result<Vec<LightId>> {
+        let lights: Vec<_> = lights.iter().map(|&light| Slots::enabled(light)).collect();
+        let reused = self.point_slots.free.len().min(lights.len());
+        let rest = &lights[reused..];
+        if !rest.is_empty() {
+            let initial_len = self.point_lights.len();
+            let new_len = initial_len + rest.len();
+            if !self.point_lights.fits(self.gpu.device(), rest.len()) {
+                bail!("LightPool can't grow to {new_len} point lights past the buffer size limits");
+            }
+            let grown = self.gpu.error_scope(|| {
+                self.point_lights.push(&self.gpu, rest);
+                self.point_bind_group = Self::create_point_bind_group(
+                    &self.gpu,
+                    &self.point_bind_group_layout,
+                    &self.point_lights,
+                    [&self.cluster_counts, &self.cluster_lights],
+                );
+                self.cluster_bind_group = Self::create_point_bind_group(
+                    &self.gpu,
+                    &self.cluster_bind_group_layout,
+                    &self.point_lights,
+                    [&self.cluster_counts, &self.cluster_lights],
+                );
+            });
+            if let Err(err) = grown {
+                self.point_lights.truncate(initial_len);
+                return Err(err).wrap_err_with(|| {
+                    format!("while growing LightPool to {new_len} point lights")
+                });
+            }
+        }
+        // Freed slots are only taken once growing can't fail anymore.
+        let mut ids = self
+            .point_slots
+            .reuse(&self.gpu, &mut self.point_lights, &lights[..reused]);
+        ids.extend(self.point_slots.append(rest));
+        Ok(ids)
+    }
+
+    pub fn sun(&self) -> DirectionalLight {
+        self.sun
+    }
+
+    /// Lights the scene in `ShadingPass` and orients the sun of `pass::sky::Sky`.
+    pub fn set_sun(&mut self, sun: DirectionalLight) {
+        self.sun = sun;
+        self.gpu
+            .queue()
+            .write_buffer(&self.sun_buffer, 0, bytemuck::bytes_of(&sun));
+    }
+
+    /// Adds enabled lights, filling the slots of removed ones first.
+    pub fn add_area_light(&mut self, lights: &[AreaLight]) -> Result<Vec<LightId>> {
+        let lights: Vec<_> = lights.iter().map(|&light| Slots::enabled(light)).collect();
+        let reused = self.area_slots.free.len().min(lights.len());
+        let rest = &lights[reused..];
+        if !rest.is_empty() {
+            let initial_len = self.area_lights.len();
+            let new_len = initial_len + rest.len();
+            if !self.area_lights.fits(self.gpu.device(), rest.len()) {
+                bail!("LightPool can't grow to {new_len} area lights past the buffer size limits");
+            }
+            let grown = self.gpu.error_scope(|| {
+                self.area_lights.push(&self.gpu, rest);
+                self.area_bind_group = Self::create_area_bind_group(
+                    &self.gpu,
+                    &self.area_bind_group_layout,
+                    &self.area_lights,
+                );
+            });
+            if let Err(err) = grown {
+                self.area_lights.truncate(initial_len);
+                return Err(err)
+                    .wrap_err_with(|| format!("while growing LightPool to {new_len} area lights"));
+            }
+        }
+        // Freed slots are only taken once growing can't fail anymore.
+        let mut ids = self
+            .area_slots
+            .reuse(&self.gpu, &mut self.area_lights, &lights[..reused]);
+        ids.extend(self.area_slots.append(rest));
+        Ok(ids)
+    }
+
+    /// Adds enabled lights, filling the slots of removed ones first.
+    pub fn add_spot_light(&mut self, lights: &[SpotLight]) -> Result<Vec<LightId>> {
+        let lights: Vec<_> = lights.iter().map(|&light| Slots::enabled(light)).collect();
+        let reused = self.spot_slots.free.len().min(lights.len());
+        let rest = &lights[reused..];
+        if !rest.is_empty() {
+            let initial_len = self.spot_lights.len();
+            let new_len = initial_len + rest.len();
+            if !self.spot_lights.fits(self.gpu.device(), rest.len()) {
+                bail!("LightPool can't grow to {new_len} spot lights past the buffer size limits");
+            }
+            let grown = self.gpu.error_scope(|| {
+                self.spot_lights.push(&self.gpu, rest);
+                self.update_sun_bind_group();
+            });
+            if let Err(err) = grown {
+                self.spot_lights.truncate(initial_len);
+                return Err(err)
+                    .wrap_err_with(|| format!("while growing LightPool to {new_len} spot lights"));
+            }
+        }
+        // Freed slots are only taken once growing can't fail anymore.
+        let mut ids = self
+            .spot_slots
+            .reuse(&self.gpu, &mut self.spot_lights, &lights[..reused]);
+        ids.extend(self.spot_slots.append(rest));
+        Ok(ids)
+    }
+
+    /// Adds enabled lights besides the sun, filling the slots of removed ones first.
+    ///
+    /// Only `ShadingPass` reads them, the sky and the path tracer follow the sun alone.
+    pub fn add_directional_light(&mut self, lights: &[DirectionalLight]) -> Result<Vec<LightId>> {
+        let lights: Vec<_> = lights.iter().map(|&light| Slots::enabled(light)).collect();
+        let reused = self.directional_slots.free.len().min(lights.len());
+        let rest = &lights[reused..];
+        if !rest.is_empty() {
+            let initial_len = self.directional_lights.len();
+            let new_len = initial_len + rest.len();
+            if !self.directional_lights.fits(self.gpu.device(), rest.len()) {
+                bail!(
+                    "LightPool can't grow to {new_len} directional lights past the buffer size limits"
+                );
+            }
+            let grown = self.gpu.error_scope(|| {
+                self.directional_lights.push(&self.gpu, rest);
+                self.update_sun_bind_group();
+            });
+            if let Err(err) = grown {
+                self.directional_lights.truncate(initial_len);
+                return Err(err).wrap_err_with(|| {
+                    format!("while growing LightPool to {new_len} directional lights")
+                });
+            }
+        }
+        // Freed slots are only taken once growing can't fail anymore.
+        let mut ids = self.directional_slots.reuse(
             &self.gpu,
-            &self.area_bind_group_layout,
-            &self.area_lights,
+            &mut self.directional_lights,
+            &lights[..reused],
         );
+        ids.extend(self.directional_slots.append(rest));
+        Ok(ids)
+    }
+
+    /// Lights of `kind` that weren't removed, in slot order, the sun isn't one of them.
+    pub fn ids(&self, kind: LightKind) -> Vec<LightId> {
+        match kind {
+            LightKind::Point => self.point_slots.ids(),
+            LightKind::Area => self.area_slots.ids(),
+            LightKind::Spot => self.spot_slots.ids(),
+            LightKind::Directional => self.directional_slots.ids(),
+        }
+    }
+
+    /// Returns `false` for ids of removed lights.
+    pub fn contains(&self, id: LightId) -> bool {
+        self.light(id).is_some()
+    }
+
+    /// The light as last added or updated, `None` once removed.
+    pub fn light(&self, id: LightId) -> Option<AnyLight> {
+        match id.kind {
+            LightKind::Point => self.point_slots.get(id).copied().map(AnyLight::Point),
+            LightKind::Area => self.area_slots.get(id).copied().map(AnyLight::Area),
+            LightKind::Spot => self.spot_slots.get(id).copied().map(AnyLight::Spot),
+            LightKind::Directional => self
+                .directional_slots
+                .get(id)
+                .copied()
+                .map(AnyLight::Directional),
+        }
+    }
+
+    /// Replaces the light behind `id`, which keeps being enabled or disabled.
+    pub fn update_light(&mut self, id: LightId, light: impl Into<AnyLight>) -> Result<()> {
+        let Some(flags) = self.flags(id) else {
+            bail!(
+                "Light {} of generation {} is stale",
+                id.index,
+                id.generation
+            );
+        };
+        let mut light = light.into();
+        *light.flags() = flags;
+        self.write(id, light)
+    }
+
+    /// Disables the light and frees its slot for the next add, the id goes stale.
+    pub fn remove_light(&mut self, id: LightId) -> Result<()> {
+        self.set_light_enabled(id, false)?;
+        match id.kind {
+            LightKind::Point => self.point_slots.remove(id),
+            LightKind::Area => self.area_slots.remove(id),
+            LightKind::Spot => self.spot_slots.remove(id),
+            LightKind::Directional => self.directional_slots.remove(id),
+        }
+        Ok(())
+    }
+
+    /// Disabled lights keep their slot and are skipped by the shaders.
+    pub fn set_light_enabled(&mut self, id: LightId, enabled: bool) -> Result<()> {
+        let Some(mut light) = self.light(id) else {
+            bail!(
+                "Light {} of generation {} is stale",
+                id.index,
+                id.generation
+            );
+        };
+        if enabled {
+            *light.flags() &= !LIGHT_DISABLED;
+        } else {
+            *light.flags() |= LIGHT_DISABLED;
+        }
+        self.write(id, light)
+    }
+
+    pub fn is_light_enabled(&self, id: LightId) -> bool {
+        self.flags(id)
+            .is_some_and(|flags| flags & LIGHT_DISABLED == 0)
+    }
+
+    fn flags(&self, id: LightId) -> Option<u32> {
+        let mut light = self.light(id)?;
+        Some(*light.flags())
+    }
+
+    fn write(&mut self, id: LightId, light: AnyLight) -> Result<()> {
+        let gpu = &self.gpu;
+        match (light, id.kind) {
+            (AnyLight::Point(light), LightKind::Point) => {
+                self.point_slots
+                    .write(gpu, &mut self.point_lights, id.index, light)
+            }
+            (AnyLight::Area(light), LightKind::Area) => {
+                self.area_slots
+                    .write(gpu, &mut self.area_lights, id.index, light)
+            }
+            (AnyLight::Spot(light), LightKind::Spot) => {
+                self.spot_slots
+                    .write(gpu, &mut self.spot_lights, id.index, light)
+            }
+            (AnyLight::Directional(light), LightKind::Directional) => {
+                let buffer = &mut self.directional_lights;
+                self.directional_slots.write(gpu, buffer, id.index, light)
+            }
+            (_, kind) => bail!("Light {} is a {kind:?} light", id.index),
+        }
+        Ok(())
     }
 }
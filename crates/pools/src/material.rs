@@ -1,4 +1,9 @@
-use std::sync::Arc;
+use std::{collections::HashMap, fmt, sync::Arc};
+
+use color_eyre::{
+    eyre::{bail, WrapErr},
+    Result,
+};
 
 use bytemuck::{Pod, Zeroable};
 use glam::Vec4;
@@ -8,7 +13,7 @@ use components::{
     Gpu, MaterialId, NonZeroSized, ResizableBuffer, ResizableBufferExt,
 };
 
-use super::texture::{TextureId, BLACK_TEXTURE, WHITE_TEXTURE};
+use super::texture::{ColorSpace, TextureId, TexturePool, BLACK_TEXTURE, WHITE_TEXTURE};
 
 #[repr(C)]
 #[derive(Copy, Clone, Pod, Zeroable)]
@@ -18,6 +23,16 @@ pub struct Material {
     pub normal: TextureId,
     pub metallic_roughness: TextureId,
     pub emissive: TextureId,
+    /// Multiplier of the emissive texture, `KHR_materials_emissive_strength`.
+    pub emissive_strength: f32,
+    /// Index of refraction of dielectrics, `KHR_materials_ior`.
+    pub ior: f32,
+    /// Share of the diffuse light passing through the surface, `KHR_materials_transmission`.
+    pub transmission: f32,
+    /// Strength of the clear coat layer on top, `KHR_materials_clearcoat`.
+    pub clearcoat: f32,
+    pub clearcoat_roughness: f32,
+    pub junk: [u32; 3],
 }
 
 impl Default for Material {
@@ -28,12 +43,21 @@ impl Default for Material {
             emissive: BLACK_TEXTURE,
             metallic_roughness: BLACK_TEXTURE,
             normal: WHITE_TEXTURE,
+            emissive_strength: 1.,
+            ior: 1.5,
+            transmission: 0.,
+            clearcoat: 0.,
+            clearcoat_roughness: 0.,
+            junk: [0; 3],
         }
     }
 }
 
 pub struct MaterialPool {
     pub(crate) buffer: ResizableBuffer<Material>,
+    materials: Vec<Material>,
+    /// Added materials by their bytes, for [`MaterialPool::find`].
+    lookup: HashMap<Vec<u8>, MaterialId>,
 
     pub bind_group_layout: bind_group_layout::BindGroupLayout,
     pub bind_group: wgpu::BindGroup,
@@ -41,15 +65,52 @@ pub struct MaterialPool {
     gpu: Arc<Gpu>,
 }
 
+impl Material {
+    /// Texture slots with their names and the color space they expect.
+    pub fn texture_slots(&self) -> [(&'static str, TextureId, ColorSpace); 4] {
+        [
+            ("albedo", self.albedo, ColorSpace::Srgb),
+            ("normal", self.normal, ColorSpace::Linear),
+            (
+                "metallic_roughness",
+                self.metallic_roughness,
+                ColorSpace::Linear,
+            ),
+            ("emissive", self.emissive, ColorSpace::Srgb),
+        ]
+    }
+}
+
+/// A material slot bound to a texture of the wrong color space.
+#[derive(Debug, Clone)]
+pub struct ColorSpaceMismatch {
+    pub material: MaterialId,
+    pub slot: &'static str,
+    pub texture: TextureId,
+    pub expected: ColorSpace,
+    pub found: ColorSpace,
+}
+
+impl fmt::Display for ColorSpaceMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Material {} binds {:?} texture {} as {}, which expects {:?}",
+            self.material.0,
+            self.found,
+            self.texture.id(),
+            self.slot,
+            self.expected,
+        )
+    }
+}
+
 impl MaterialPool {
     pub const LIGHT_MATERIAL: MaterialId = MaterialId::new(2);
     pub fn new(gpu: Arc<Gpu>) -> Self {
+        let materials = vec![Material::default(); 3];
         let buffer = gpu.device().create_resizable_buffer_init(
-            &[
-                Material::default(),
-                Material::default(),
-                Material::default(),
-            ],
+            &materials,
             wgpu::BufferUsages::STORAGE
                 | wgpu::BufferUsages::COPY_DST
                 | wgpu::BufferUsages::COPY_SRC,
@@ -76,6 +137,8 @@ impl MaterialPool {
 
         Self {
             buffer,
+            materials,
+            lookup: HashMap::new(),
             bind_group_layout,
             bind_group,
 
@@ -87,6 +150,10 @@ impl MaterialPool {
         self.buffer.len()
     }
 
+    pub fn buffer(&self) -> &ResizableBuffer<Material> {
+        &self.buffer
+    }
+
     pub fn create_bind_group(
         device: &wgpu::Device,
         layout: &wgpu::BindGroupLayout,
@@ -104,15 +171,67 @@ impl MaterialPool {
         bind_group
     }
 
-    pub fn add(&mut self, material: Material) -> MaterialId {
-        let was_resized = self.buffer.push(&self.gpu, &[material]);
-
-        if was_resized {
-            self.bind_group =
-                Self::create_bind_group(self.gpu.device(), &self.bind_group_layout, &self.buffer);
+    /// Fails without changing the pool when the buffer can't grow.
+    pub fn add(&mut self, material: Material) -> Result<MaterialId> {
+        let initial_len = self.buffer.len();
+        let new_len = initial_len + 1;
+        if !self.buffer.fits(self.gpu.device(), 1) {
+            bail!("MaterialPool can't grow to {new_len} entries past the buffer size limits");
+        }
+        let grown = self.gpu.error_scope(|| {
+            let was_resized = self.buffer.push(&self.gpu, &[material]);
+
+            if was_resized {
+                self.bind_group = Self::create_bind_group(
+                    self.gpu.device(),
+                    &self.bind_group_layout,
+                    &self.buffer,
+                );
+            }
+        });
+        if let Err(err) = grown {
+            // Ids are the buffer length, it has to follow `materials`.
+            self.buffer.truncate(initial_len);
+            return Err(err)
+                .wrap_err_with(|| format!("while growing MaterialPool to {new_len} entries"));
         }
 
-        log::info!("Added material with id: {}", self.buffer.len() as u32 - 1);
-        MaterialId(self.buffer.len() as u32 - 1)
+        let id = MaterialId(self.buffer.len() as u32 - 1);
+        self.materials.push(material);
+        self.lookup
+            .entry(bytemuck::bytes_of(&material).to_vec())
+            .or_insert(id);
+        log::info!("Added material with id: {}", id.0);
+        Ok(id)
+    }
+
+    pub fn get(&self, id: MaterialId) -> Option<&Material> {
+        self.materials.get(id.0 as usize)
+    }
+
+    /// An added material equal to `material`, to share it instead of adding a copy.
+    pub fn find(&self, material: &Material) -> Option<MaterialId> {
+        self.lookup.get(bytemuck::bytes_of(material)).copied()
+    }
+
+    /// Lists material slots whose textures hold the wrong color space, e.g. a normal map
+    /// loaded as sRGB. Both are fine with the white and black defaults.
+    pub fn validate_color_spaces(&self, textures: &TexturePool) -> Vec<ColorSpaceMismatch> {
+        let mut mismatches = vec![];
+        for (id, material) in self.materials.iter().enumerate() {
+            for (slot, texture, expected) in material.texture_slots() {
+                match textures.color_space(texture) {
+                    Some(found) if found != expected => mismatches.push(ColorSpaceMismatch {
+                        material: MaterialId::new(id as u32),
+                        slot,
+                        texture,
+                        expected,
+                        found,
+                    }),
+                    _ => {}
+                }
+            }
+        }
+        mismatches
     }
 }
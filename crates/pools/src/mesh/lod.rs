@@ -0,0 +1,142 @@
+use std::collections::{HashMap, HashSet};
+
+use bytemuck::{Pod, Zeroable};
+use glam::{IVec3, Vec3};
+
+use super::calculate_bounds;
+
+/// A coarser level of a mesh, drawn in its place once the error it shows on screen fits
+/// the budget of `emit_draws.wgsl`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, Pod, Zeroable)]
+pub struct MeshLod {
+    /// Relative to `MeshInfo::base_index`, levels follow the indices of the full mesh.
+    pub base_index: u32,
+    pub index_count: u32,
+    /// Farthest any vertex moved, in mesh space.
+    pub error: f32,
+    pub junk: u32,
+}
+
+/// Coarser levels `MeshPool` makes for the meshes it imports.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LodChain {
+    /// Zero turns generation off.
+    pub levels: u32,
+    /// Share of the triangles of the previous level every level aims for.
+    pub reduction: f32,
+    /// Meshes and levels with fewer triangles are left as they are.
+    pub min_triangles: u32,
+}
+
+impl Default for LodChain {
+    fn default() -> Self {
+        Self {
+            levels: 4,
+            reduction: 0.5,
+            min_triangles: 256,
+        }
+    }
+}
+
+impl LodChain {
+    /// Simplifies by vertex clustering. Vertices sharing a grid cell snap to the one
+    /// closest to their average and collapsed triangles are dropped. Cells grow until a
+    /// level keeps at most `reduction` of the triangles before it.
+    ///
+    /// Levels index the vertices of the mesh, so they only cost indices, which follow
+    /// the ones of the mesh.
+    pub fn build(&self, vertices: &[Vec3], indices: &[u32]) -> (Vec<MeshLod>, Vec<u32>) {
+        let mut lods = vec![];
+        let mut lod_indices = vec![];
+        let (min, max) = calculate_bounds(vertices);
+        let extent = (max - min).max_element();
+        if self.levels == 0 || !(extent > 0.) {
+            return (lods, lod_indices);
+        }
+
+        let mut previous = indices.len() / 3;
+        let mut error = 0f32;
+        while lods.len() < self.levels as usize && previous >= self.min_triangles as usize {
+            let target = (previous as f32 * self.reduction) as usize;
+            // A surface cut by cells of this size leaves about that many triangles, start
+            // a bit finer and grow from there.
+            let mut cell = extent * (2. / target.max(1) as f32).sqrt() / 2.;
+            let level = loop {
+                let (level, level_error) = cluster(vertices, indices, min, cell);
+                if level.len() / 3 <= target || cell > extent {
+                    error = error.max(level_error);
+                    break level;
+                }
+                cell *= std::f32::consts::SQRT_2;
+            };
+            if level.is_empty() || level.len() / 3 >= previous {
+                break;
+            }
+            previous = level.len() / 3;
+            lods.push(MeshLod {
+                base_index: (indices.len() + lod_indices.len()) as u32,
+                index_count: level.len() as u32,
+                error,
+                junk: 0,
+            });
+            lod_indices.extend(level);
+        }
+        (lods, lod_indices)
+    }
+}
+
+// Indices of the clustered mesh and the farthest a vertex moved.
+fn cluster(vertices: &[Vec3], indices: &[u32], origin: Vec3, cell: f32) -> (Vec<u32>, f32) {
+    let cells: Vec<IVec3> = vertices
+        .iter()
+        .map(|&pos| ((pos - origin) / cell).floor().as_ivec3())
+        .collect();
+    let mut averages: HashMap<IVec3, (Vec3, f32)> = HashMap::new();
+    for &i in indices {
+        let (sum, count) = averages.entry(cells[i as usize]).or_default();
+        *sum += vertices[i as usize];
+        *count += 1.;
+    }
+    let mut representatives: HashMap<IVec3, u32> = HashMap::with_capacity(averages.len());
+    for &i in indices {
+        let cell = cells[i as usize];
+        let (sum, count) = averages[&cell];
+        let average = sum / count;
+        let dist = |i: u32| vertices[i as usize].distance_squared(average);
+        representatives
+            .entry(cell)
+            .and_modify(|r| {
+                if dist(i) < dist(*r) {
+                    *r = i;
+                }
+            })
+            .or_insert(i);
+    }
+    let snap = |i: u32| representatives[&cells[i as usize]];
+
+    let error = indices
+        .iter()
+        .map(|&i| vertices[i as usize].distance(vertices[snap(i) as usize]))
+        .fold(0., f32::max);
+    let mut seen = HashSet::new();
+    let mut clustered = vec![];
+    for triangle in indices.chunks_exact(3) {
+        let [a, b, c] = [snap(triangle[0]), snap(triangle[1]), snap(triangle[2])];
+        if a == b || b == c || a == c {
+            continue;
+        }
+        // Several triangles can collapse into the same one, rotated keeps the winding.
+        let key = if a < b && a < c {
+            [a, b, c]
+        } else if b < c {
+            [b, c, a]
+        } else {
+            [c, a, b]
+        };
+        if seen.insert(key) {
+            clustered.extend(key);
+        }
+    }
+    (clustered, error)
+}
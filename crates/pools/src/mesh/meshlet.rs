@@ -0,0 +1,95 @@
+use bytemuck::{Pod, Zeroable};
+use glam::Vec3;
+
+use super::{calculate_bounding_sphere, calculate_bounds};
+
+/// A cluster of triangles culled on its own, see `shaders/meshlet_cull.wgsl`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, Pod, Zeroable)]
+pub struct Meshlet {
+    pub center: Vec3,
+    pub radius: f32,
+    /// Average facing of the triangles.
+    pub cone_axis: Vec3,
+    /// Sine of the widest angle between a triangle and the axis, one when they face too
+    /// many ways to ever be back-facing together.
+    pub cone_cutoff: f32,
+    /// Relative to `MeshInfo::base_index`.
+    pub base_index: u32,
+    pub index_count: u32,
+    pub junk: [u32; 2],
+}
+
+impl Meshlet {
+    pub const MAX_VERTICES: usize = 64;
+    pub const MAX_TRIANGLES: usize = 124;
+}
+
+/// Splits `indices` into meshlets of consecutive triangles, closing one once it would go
+/// over [`Meshlet::MAX_VERTICES`] or [`Meshlet::MAX_TRIANGLES`], like `meshopt_buildMeshletsScan`.
+///
+/// The triangles are left in place, so the BVH built over them stays valid. Its builder
+/// already sorts them spatially, which is what keeps the clusters tight.
+pub fn build_meshlets(vertices: &[Vec3], indices: &[u32]) -> Vec<Meshlet> {
+    let mut meshlets = vec![];
+    let mut unique = Vec::with_capacity(Meshlet::MAX_VERTICES);
+    let mut first = 0;
+    for (i, triangle) in indices.chunks_exact(3).enumerate() {
+        let new_vertices = triangle
+            .iter()
+            .enumerate()
+            .filter(|&(j, index)| !unique.contains(index) && !triangle[..j].contains(index))
+            .count();
+        let triangle_count = i - first;
+        if unique.len() + new_vertices > Meshlet::MAX_VERTICES
+            || triangle_count == Meshlet::MAX_TRIANGLES
+        {
+            meshlets.push(meshlet(vertices, indices, first..i));
+            unique.clear();
+            first = i;
+        }
+        for index in triangle {
+            if !unique.contains(index) {
+                unique.push(*index);
+            }
+        }
+    }
+    let triangle_count = indices.len() / 3;
+    if first < triangle_count {
+        meshlets.push(meshlet(vertices, indices, first..triangle_count));
+    }
+    meshlets
+}
+
+fn meshlet(vertices: &[Vec3], indices: &[u32], triangles: std::ops::Range<usize>) -> Meshlet {
+    let indices = &indices[triangles.start * 3..triangles.end * 3];
+    let positions: Vec<_> = indices.iter().map(|&i| vertices[i as usize]).collect();
+    let (min, max) = calculate_bounds(&positions);
+    let (center, radius) = calculate_bounding_sphere(&positions, min, max);
+
+    let normals: Vec<_> = positions
+        .chunks_exact(3)
+        .filter_map(|tri| (tri[1] - tri[0]).cross(tri[2] - tri[0]).try_normalize())
+        .collect();
+    let cone_axis = normals.iter().sum::<Vec3>().normalize_or_zero();
+    let min_dot = normals
+        .iter()
+        .map(|normal| normal.dot(cone_axis))
+        .fold(1., f32::min);
+    // Past about 84 degrees the cone hardly ever culls, like meshoptimizer gives up there.
+    let cone_cutoff = if normals.is_empty() || min_dot <= 0.1 {
+        1.
+    } else {
+        (1. - min_dot * min_dot).sqrt()
+    };
+
+    Meshlet {
+        center,
+        radius,
+        cone_axis,
+        cone_cutoff,
+        base_index: triangles.start as u32 * 3,
+        index_count: indices.len() as u32,
+        junk: [0; 2],
+    }
+}
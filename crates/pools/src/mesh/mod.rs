@@ -1,21 +1,35 @@
 mod boxx;
 mod cube;
+mod lod;
+mod meshlet;
 mod plane;
 mod sphere;
 
 use core::sync::atomic::{AtomicU32, Ordering};
-use std::sync::Arc;
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+    ops::Range,
+    sync::Arc,
+};
 
-use glam::{Vec2, Vec3, Vec4};
+use color_eyre::{
+    eyre::{bail, WrapErr},
+    Result,
+};
+use glam::{Mat3, Mat4, Vec2, Vec3, Vec4};
+use rayon::prelude::*;
 
 use components::bind_group_layout::{self, WrappedBindGroupLayout};
-use components::{BindGroupLayout, Gpu, Instance, MeshId, MeshInfo};
+use components::{BindGroupLayout, Gpu, Instance, MaterialId, MeshId, MeshInfo};
 use components::{NonZeroSized, ResizableBuffer, ResizableBufferExt};
 
 use bvh::{BvhBuilder, BvhNode, Tlas, TlasNode};
 
 pub use boxx::make_box_mesh;
 pub use cube::make_cube_mesh;
+pub use lod::{LodChain, MeshLod};
+pub use meshlet::{build_meshlets, Meshlet};
 pub use plane::make_plane_mesh;
 pub use sphere::make_uv_sphere;
 
@@ -26,6 +40,47 @@ pub fn calculate_bounds(positions: &[Vec3]) -> (Vec3, Vec3) {
     )
 }
 
+// Ritter's sphere, falls back to the one around the box center when that is smaller.
+fn calculate_bounding_sphere(vertices: &[Vec3], min: Vec3, max: Vec3) -> (Vec3, f32) {
+    let Some(&first) = vertices.first() else {
+        return (Vec3::ZERO, 0.);
+    };
+    let farthest = |from: Vec3| {
+        vertices
+            .iter()
+            .copied()
+            .max_by(|a, b| {
+                from.distance_squared(*a)
+                    .total_cmp(&from.distance_squared(*b))
+            })
+            .unwrap_or(from)
+    };
+    let a = farthest(first);
+    let b = farthest(a);
+    let mut center = (a + b) / 2.;
+    let mut radius = a.distance(b) / 2.;
+    for &pos in vertices {
+        let dist = center.distance(pos);
+        if dist > radius {
+            let grown = (radius + dist) / 2.;
+            center += (pos - center) * ((grown - radius) / dist);
+            radius = grown;
+        }
+    }
+
+    let box_center = (min + max) / 2.;
+    let box_radius = vertices
+        .iter()
+        .map(|pos| box_center.distance_squared(*pos))
+        .fold(0., f32::max)
+        .sqrt();
+    if box_radius < radius {
+        (box_center, box_radius)
+    } else {
+        (center, radius)
+    }
+}
+
 pub struct Mesh {
     pub vertices: Vec<Vec3>,
     pub normals: Vec<Vec3>,
@@ -46,6 +101,29 @@ impl Mesh {
     }
 }
 
+/// Ranges a mesh occupies in the pool buffers.
+#[derive(Debug, Clone)]
+struct MeshAllocation {
+    vertices: Range<u32>,
+    indices: Range<u32>,
+    bvh_nodes: Range<u32>,
+    triangle_materials: Range<u32>,
+    meshlets: Range<u32>,
+    lods: Range<u32>,
+    /// Content hash of meshes shared through [`MeshPool::add_many`].
+    hash: Option<u64>,
+    /// Number of times the id was handed out, [`MeshPool::remove`] frees the mesh at zero.
+    references: u32,
+    removed: bool,
+}
+
+/// State [`MeshPool::add_many`] returns to when adding fails.
+struct Checkpoint {
+    counters: [u32; 6],
+    buffers: [usize; 9],
+    references: Vec<u32>,
+}
+
 pub struct MeshRef<'a> {
     pub vertices: &'a [Vec3],
     pub normals: &'a [Vec3],
@@ -54,16 +132,43 @@ pub struct MeshRef<'a> {
     pub indices: Vec<u32>,
 }
 
+impl MeshRef<'_> {
+    fn content_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        for bytes in [
+            bytemuck::cast_slice::<_, u8>(self.vertices),
+            bytemuck::cast_slice(self.normals),
+            bytemuck::cast_slice(self.tangents),
+            bytemuck::cast_slice(self.tex_coords),
+            bytemuck::cast_slice(&self.indices),
+        ] {
+            bytes.len().hash(&mut hasher);
+            bytes.hash(&mut hasher);
+        }
+        hasher.finish()
+    }
+}
+
 pub struct MeshPool {
     vertex_offset: AtomicU32,
     base_index: AtomicU32,
     mesh_index: AtomicU32,
     bvh_index: AtomicU32,
+    meshlet_index: AtomicU32,
+    lod_index: AtomicU32,
+    /// Levels made for meshes added from then on.
+    pub lod_chain: LodChain,
 
     pub mesh_info_layout: bind_group_layout::BindGroupLayout,
     pub mesh_info_bind_group: wgpu::BindGroup,
     pub mesh_info_cpu: Vec<MeshInfo>,
     pub mesh_info: ResizableBuffer<MeshInfo>,
+    allocations: Vec<MeshAllocation>,
+    contents: HashMap<u64, MeshId>,
+    /// Vertices left behind by [`MeshPool::update_mesh`], reclaimed by defragmenting.
+    stale_vertices: u32,
+    /// Material of every triangle of meshes merged with [`MeshPool::merge_with_materials`].
+    pub triangle_materials: ResizableBuffer<u32>,
 
     pub vertices: ResizableBuffer<Vec3>,
     pub normals: ResizableBuffer<Vec3>,
@@ -71,6 +176,10 @@ pub struct MeshPool {
     pub tex_coords: ResizableBuffer<Vec2>,
     pub indices: ResizableBuffer<u32>,
     pub bvh_nodes: ResizableBuffer<BvhNode>,
+    /// Clusters of every mesh, ranges of them are given by `MeshInfo::meshlet_index`.
+    pub meshlets: ResizableBuffer<Meshlet>,
+    /// Coarser levels of every mesh, ranges of them are given by `MeshInfo::lod_index`.
+    pub lods: ResizableBuffer<MeshLod>,
 
     pub tlas: Tlas,
     pub tlas_nodes: ResizableBuffer<TlasNode>,
@@ -78,6 +187,11 @@ pub struct MeshPool {
     pub trace_bind_group_layout: BindGroupLayout,
     pub trace_bind_group: wgpu::BindGroup,
 
+    /// Mesh infos, indices and every vertex stream as storage buffers,
+    /// for passes that fetch and interpolate vertices themselves.
+    pub attributes_layout: BindGroupLayout,
+    pub attributes_bind_group: wgpu::BindGroup,
+
     gpu: Arc<Gpu>,
 }
 
@@ -87,25 +201,34 @@ impl MeshPool {
     pub const SPHERE_1_MESH: MeshId = MeshId::new(2);
     pub const SPHERE_10_MESH: MeshId = MeshId::new(3);
 
+    /// Share of freed vertices after which [`MeshPool::remove`] compacts the buffers.
+    pub const DEFRAGMENT_THRESHOLD: f32 = 0.5;
+
     pub fn new(gpu: Arc<Gpu>) -> Self {
         let vertices = gpu
             .device()
             .create_resizable_buffer(wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::STORAGE);
         let normals = gpu
             .device()
-            .create_resizable_buffer(wgpu::BufferUsages::VERTEX);
+            .create_resizable_buffer(wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::STORAGE);
         let tangents = gpu
             .device()
-            .create_resizable_buffer(wgpu::BufferUsages::VERTEX);
+            .create_resizable_buffer(wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::STORAGE);
         let tex_coords = gpu
             .device()
-            .create_resizable_buffer(wgpu::BufferUsages::VERTEX);
+            .create_resizable_buffer(wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::STORAGE);
         let indices = gpu
             .device()
             .create_resizable_buffer(wgpu::BufferUsages::INDEX | wgpu::BufferUsages::STORAGE);
         let bvh_nodes = gpu
             .device()
             .create_resizable_buffer(wgpu::BufferUsages::STORAGE);
+        let meshlets = gpu
+            .device()
+            .create_resizable_buffer(wgpu::BufferUsages::STORAGE);
+        let lods = gpu
+            .device()
+            .create_resizable_buffer(wgpu::BufferUsages::STORAGE);
         let tlas = Tlas::empty();
         let tlas_nodes = gpu
             .device()
@@ -114,24 +237,68 @@ impl MeshPool {
         let mesh_info = gpu
             .device()
             .create_resizable_buffer(wgpu::BufferUsages::STORAGE);
+        let triangle_materials = gpu
+            .device()
+            .create_resizable_buffer(wgpu::BufferUsages::STORAGE);
         let mesh_info_layout =
             gpu.device()
                 .create_bind_group_layout_wrap(&wgpu::BindGroupLayoutDescriptor {
                     label: Some("Mesh Info Bind Group Layout"),
-                    entries: &[wgpu::BindGroupLayoutEntry {
-                        binding: 0,
-                        visibility: wgpu::ShaderStages::COMPUTE
-                            | wgpu::ShaderStages::VERTEX_FRAGMENT,
-                        ty: wgpu::BindingType::Buffer {
-                            ty: wgpu::BufferBindingType::Storage { read_only: true },
-                            has_dynamic_offset: false,
-                            min_binding_size: Some(MeshInfo::NSIZE),
+                    entries: &[
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 0,
+                            visibility: wgpu::ShaderStages::COMPUTE
+                                | wgpu::ShaderStages::VERTEX_FRAGMENT,
+                            ty: wgpu::BindingType::Buffer {
+                                ty: wgpu::BufferBindingType::Storage { read_only: true },
+                                has_dynamic_offset: false,
+                                min_binding_size: Some(MeshInfo::NSIZE),
+                            },
+                            count: None,
+                        },
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 1,
+                            visibility: wgpu::ShaderStages::COMPUTE
+                                | wgpu::ShaderStages::VERTEX_FRAGMENT,
+                            ty: wgpu::BindingType::Buffer {
+                                ty: wgpu::BufferBindingType::Storage { read_only: true },
+                                has_dynamic_offset: false,
+                                min_binding_size: Some(u32::NSIZE),
+                            },
+                            count: None,
+                        },
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 2,
+                            visibility: wgpu::ShaderStages::COMPUTE
+                                | wgpu::ShaderStages::VERTEX_FRAGMENT,
+                            ty: wgpu::BindingType::Buffer {
+                                ty: wgpu::BufferBindingType::Storage { read_only: true },
+                                has_dynamic_offset: false,
+                                min_binding_size: Some(Meshlet::NSIZE),
+                            },
+                            count: None,
                         },
-                        count: None,
-                    }],
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 3,
+                            visibility: wgpu::ShaderStages::COMPUTE
+                                | wgpu::ShaderStages::VERTEX_FRAGMENT,
+                            ty: wgpu::BindingType::Buffer {
+                                ty: wgpu::BufferBindingType::Storage { read_only: true },
+                                has_dynamic_offset: false,
+                                min_binding_size: Some(MeshLod::NSIZE),
+                            },
+                            count: None,
+                        },
+                    ],
                 });
-        let mesh_info_bind_group =
-            Self::mesh_info_bind_group(gpu.device(), &mesh_info_layout, &mesh_info);
+        let mesh_info_bind_group = Self::mesh_info_bind_group(
+            gpu.device(),
+            &mesh_info_layout,
+            &mesh_info,
+            &triangle_materials,
+            &meshlets,
+            &lods,
+        );
 
         let trace_bind_group_layout =
             gpu.device()
@@ -201,6 +368,34 @@ impl MeshPool {
                     ],
                 });
 
+        let attributes_layout =
+            gpu.device()
+                .create_bind_group_layout_wrap(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some("Mesh Attributes BGL"),
+                    entries: &[
+                        storage_entry(0, MeshInfo::NSIZE),
+                        storage_entry(1, u32::NSIZE),
+                        storage_entry(2, f32::NSIZE),
+                        storage_entry(3, f32::NSIZE),
+                        storage_entry(4, Vec4::NSIZE),
+                        storage_entry(5, Vec2::NSIZE),
+                        storage_entry(6, u32::NSIZE),
+                    ],
+                });
+        let attributes_bind_group = Self::attributes_bind_group(
+            gpu.device(),
+            &attributes_layout,
+            [
+                &mesh_info,
+                &indices,
+                &vertices,
+                &normals,
+                &tangents,
+                &tex_coords,
+                &triangle_materials,
+            ],
+        );
+
         let trace_bind_group = {
             let instances = gpu
                 .device()
@@ -242,11 +437,18 @@ impl MeshPool {
             base_index: AtomicU32::new(0),
             mesh_index: AtomicU32::new(0),
             bvh_index: AtomicU32::new(0),
+            meshlet_index: AtomicU32::new(0),
+            lod_index: AtomicU32::new(0),
+            lod_chain: LodChain::default(),
 
             mesh_info_layout,
             mesh_info_bind_group,
             mesh_info_cpu: vec![],
             mesh_info,
+            allocations: vec![],
+            contents: HashMap::new(),
+            stale_vertices: 0,
+            triangle_materials,
 
             vertices,
             indices,
@@ -254,6 +456,8 @@ impl MeshPool {
             tangents,
             tex_coords,
             bvh_nodes,
+            meshlets,
+            lods,
 
             tlas,
             tlas_nodes,
@@ -261,17 +465,20 @@ impl MeshPool {
             trace_bind_group_layout,
             trace_bind_group,
 
+            attributes_layout,
+            attributes_bind_group,
+
             gpu,
         };
 
         let mut plane_mesh = make_plane_mesh(1., 1.);
-        this.add(plane_mesh.as_ref());
+        this.add_unchecked(plane_mesh.as_ref());
         let rot = glam::Mat3::from_rotation_x(-std::f32::consts::PI / 2.);
         plane_mesh.vertices.iter_mut().for_each(|v| *v = rot * *v);
         plane_mesh.normals.iter_mut().for_each(|v| *v = rot * *v);
-        this.add(plane_mesh.as_ref());
-        this.add(make_uv_sphere(1., 1).as_ref());
-        this.add(make_uv_sphere(1., 10).as_ref());
+        this.add_unchecked(plane_mesh.as_ref());
+        this.add_unchecked(make_uv_sphere(1., 1).as_ref());
+        this.add_unchecked(make_uv_sphere(1., 10).as_ref());
 
         this
     }
@@ -285,68 +492,932 @@ impl MeshPool {
         self.tlas_nodes.push(&self.gpu, &self.tlas.nodes);
     }
 
+    /// Rebuilds the dynamic part of the TLAS, keeping the static one.
+    ///
+    /// Returns `true` if the node buffer was reallocated.
+    pub fn update_dynamic_tlas(&mut self, instances: &[Instance]) -> bool {
+        if instances.is_empty() {
+            return false;
+        }
+        self.tlas.rebuild_dynamic(instances, &self.mesh_info_cpu);
+        if self.tlas_nodes.len() == self.tlas.nodes.len() {
+            self.tlas_nodes.write_slice(&self.gpu, 0, &self.tlas.nodes);
+            return false;
+        }
+        self.tlas_nodes.clear();
+        self.tlas_nodes.push(&self.gpu, &self.tlas.nodes)
+    }
+
+    /// Refits the TLAS to moved instances and patches the node buffer in place, see
+    /// [`Tlas::refit`]. Only valid while the instances the TLAS was built from stay the same.
+    pub fn refit_tlas(&mut self, instances: &[Instance]) {
+        self.tlas.refit(instances, &self.mesh_info_cpu);
+        if self.tlas_nodes.len() == self.tlas.nodes.len() {
+            self.tlas_nodes.write_slice(&self.gpu, 0, &self.tlas.nodes);
+        }
+    }
+
     pub fn mesh_info_bind_group(
         device: &wgpu::Device,
         layout: &wgpu::BindGroupLayout,
         mesh_info: &ResizableBuffer<MeshInfo>,
+        triangle_materials: &ResizableBuffer<u32>,
+        meshlets: &ResizableBuffer<Meshlet>,
+        lods: &ResizableBuffer<MeshLod>,
     ) -> wgpu::BindGroup {
         let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: Some("Mesh Info Bind Group"),
             layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: mesh_info.as_tight_binding(),
-            }],
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: mesh_info.as_tight_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: triangle_materials.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: meshlets.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: lods.as_entire_binding(),
+                },
+            ],
         });
 
         bind_group
     }
 
+    fn attributes_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        buffers: [&wgpu::Buffer; 7],
+    ) -> wgpu::BindGroup {
+        let entries = buffers
+            .iter()
+            .enumerate()
+            .map(|(i, buffer)| wgpu::BindGroupEntry {
+                binding: i as u32,
+                resource: buffer.as_entire_binding(),
+            })
+            .collect::<Vec<_>>();
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Mesh Attributes BG"),
+            layout,
+            entries: &entries,
+        })
+    }
+
     pub fn count(&self) -> u32 {
         self.mesh_index.load(Ordering::Relaxed)
     }
 
-    pub fn add(&mut self, mut mesh: MeshRef) -> MeshId {
-        let vertex_count = mesh.vertices.len() as u32;
-        let vertex_offset = self
-            .vertex_offset
-            .fetch_add(vertex_count, Ordering::Relaxed);
+    /// Overrides the bounds used for culling and the TLAS, e.g. for meshes deformed on the gpu.
+    /// Its meshlets no longer match the geometry, so the mesh is drawn whole from then on.
+    pub fn set_bounds(&mut self, id: MeshId, min: Vec3, max: Vec3) {
+        let info = &mut self.mesh_info_cpu[id.0 as usize];
+        info.min = min;
+        info.max = max;
+        info.center = (min + max) / 2.;
+        info.radius = (max - min).length() / 2.;
+        info.meshlet_count = 0;
+        self.mesh_info.write(&self.gpu, id.0 as usize, *info);
+    }
+
+    /// Replaces the geometry of a mesh, e.g. after a procedural edit, and rebuilds its BVH
+    /// and bounds. The id and the triangle materials stay, so the triangle count of merged
+    /// meshes can't change.
+    ///
+    /// The data is written in place when it fits and appended otherwise, the old ranges
+    /// count towards [`MeshPool::fragmentation`]. A mesh shared by [`MeshPool::add_many`]
+    /// changes for every holder and stops being shared with later imports. The TLAS only
+    /// sees the new bounds after the next [`MeshPool::generate_tlas`].
+    pub fn update_mesh(&mut self, id: MeshId, mesh: MeshRef) -> Result<()> {
+        let Some(allocation) = self.allocations.get(id.0 as usize) else {
+            bail!("Mesh {} is not in the MeshPool", id.0);
+        };
+        if allocation.removed {
+            bail!("Mesh {} is removed", id.0);
+        }
+        let vertex_count = mesh.vertices.len();
+        if [
+            mesh.normals.len(),
+            mesh.tangents.len(),
+            mesh.tex_coords.len(),
+        ]
+        .iter()
+        .any(|&len| len != vertex_count)
+        {
+            bail!("Mesh attributes don't match its {vertex_count} vertices");
+        }
+        let index_count = mesh.indices.len();
+        let info = &self.mesh_info_cpu[id.0 as usize];
+        if info.triangle_materials != MeshInfo::NO_TRIANGLE_MATERIALS
+            && index_count as u32 != info.index_count
+        {
+            bail!(
+                "Mesh {} has triangle materials for {} indices, got {index_count}",
+                id.0,
+                info.index_count
+            );
+        }
 
-        self.vertices.push(&self.gpu, mesh.vertices);
-        self.normals.push(&self.gpu, mesh.normals);
-        self.tangents.push(&self.gpu, mesh.tangents);
-        self.tex_coords.push(&self.gpu, mesh.tex_coords);
+        let gpu = self.gpu.clone();
+        gpu.error_scope(|| self.update_mesh_unchecked(id, mesh))
+            .wrap_err_with(|| {
+                format!(
+                    "while updating mesh {} with {vertex_count} vertices and {index_count} indices",
+                    id.0
+                )
+            })?;
+        if self.fragmentation() > Self::DEFRAGMENT_THRESHOLD {
+            self.defragment()?;
+        }
+        Ok(())
+    }
 
+    fn update_mesh_unchecked(&mut self, id: MeshId, mut mesh: MeshRef) {
         let bvh =
             BvhBuilder::new(mesh.vertices, bytemuck::cast_slice_mut(&mut mesh.indices)).build();
-        let bvh_index = self
-            .bvh_index
-            .fetch_add(bvh.nodes.len() as u32, Ordering::Relaxed);
-        self.bvh_nodes.push(&self.gpu, &bvh.nodes);
+        let meshlets = build_meshlets(mesh.vertices, &mesh.indices);
+        let (lods, lod_indices) = self.lod_chain.build(mesh.vertices, &mesh.indices);
+        let (min, max) = calculate_bounds(mesh.vertices);
+        let (center, radius) = calculate_bounding_sphere(mesh.vertices, min, max);
 
+        let old = self.allocations[id.0 as usize].clone();
+        let vertex_count = mesh.vertices.len() as u32;
         let index_count = mesh.indices.len() as u32;
-        let base_index = self.base_index.fetch_add(index_count, Ordering::Relaxed);
+        mesh.indices.extend(lod_indices);
+        let lod_index_count = mesh.indices.len() as u32;
+        let bvh_count = bvh.nodes.len() as u32;
+        let meshlet_count = meshlets.len() as u32;
+        let lod_count = lods.len() as u32;
+        let fits = vertex_count as usize <= old.vertices.len()
+            && lod_index_count as usize <= old.indices.len()
+            && bvh_count as usize <= old.bvh_nodes.len()
+            && meshlet_count as usize <= old.meshlets.len()
+            && lod_count as usize <= old.lods.len();
 
-        self.indices.push(&self.gpu, &mesh.indices);
-        let mesh_index = self.mesh_index.fetch_add(1, Ordering::Relaxed);
+        let (vertex_offset, base_index, bvh_index, meshlet_index, lod_index) = if fits {
+            let (vertex_offset, base_index, bvh_index, meshlet_index, lod_index) = (
+                old.vertices.start,
+                old.indices.start,
+                old.bvh_nodes.start,
+                old.meshlets.start,
+                old.lods.start,
+            );
+            self.vertices
+                .write_slice(&self.gpu, vertex_offset as usize, mesh.vertices);
+            self.normals
+                .write_slice(&self.gpu, vertex_offset as usize, mesh.normals);
+            self.tangents
+                .write_slice(&self.gpu, vertex_offset as usize, mesh.tangents);
+            self.tex_coords
+                .write_slice(&self.gpu, vertex_offset as usize, mesh.tex_coords);
+            self.indices
+                .write_slice(&self.gpu, base_index as usize, &mesh.indices);
+            self.bvh_nodes
+                .write_slice(&self.gpu, bvh_index as usize, &bvh.nodes);
+            self.meshlets
+                .write_slice(&self.gpu, meshlet_index as usize, &meshlets);
+            self.lods.write_slice(&self.gpu, lod_index as usize, &lods);
+            self.stale_vertices += old.vertices.len() as u32 - vertex_count;
+            (
+                vertex_offset,
+                base_index,
+                bvh_index,
+                meshlet_index,
+                lod_index,
+            )
+        } else {
+            let vertex_offset = self
+                .vertex_offset
+                .fetch_add(vertex_count, Ordering::Relaxed);
+            let base_index = self
+                .base_index
+                .fetch_add(lod_index_count, Ordering::Relaxed);
+            let bvh_index = self.bvh_index.fetch_add(bvh_count, Ordering::Relaxed);
+            let meshlet_index = self
+                .meshlet_index
+                .fetch_add(meshlet_count, Ordering::Relaxed);
+            let lod_index = self.lod_index.fetch_add(lod_count, Ordering::Relaxed);
+            self.vertices.push(&self.gpu, mesh.vertices);
+            self.normals.push(&self.gpu, mesh.normals);
+            self.tangents.push(&self.gpu, mesh.tangents);
+            self.tex_coords.push(&self.gpu, mesh.tex_coords);
+            self.indices.push(&self.gpu, &mesh.indices);
+            self.bvh_nodes.push(&self.gpu, &bvh.nodes);
+            if !meshlets.is_empty() {
+                self.meshlets.push(&self.gpu, &meshlets);
+            }
+            if !lods.is_empty() {
+                self.lods.push(&self.gpu, &lods);
+            }
+            self.stale_vertices += old.vertices.len() as u32;
+            (
+                vertex_offset,
+                base_index,
+                bvh_index,
+                meshlet_index,
+                lod_index,
+            )
+        };
 
-        let (min, max) = calculate_bounds(mesh.vertices);
+        let allocation = &mut self.allocations[id.0 as usize];
+        allocation.vertices = vertex_offset..vertex_offset + vertex_count;
+        allocation.indices = base_index..base_index + lod_index_count;
+        allocation.bvh_nodes = bvh_index..bvh_index + bvh_count;
+        allocation.meshlets = meshlet_index..meshlet_index + meshlet_count;
+        allocation.lods = lod_index..lod_index + lod_count;
+        if let Some(hash) = allocation.hash.take() {
+            self.contents.remove(&hash);
+        }
 
-        let mesh_info = MeshInfo {
+        let info = &mut self.mesh_info_cpu[id.0 as usize];
+        *info = MeshInfo {
             min,
             vertex_offset: vertex_offset as i32,
             max,
             base_index,
             index_count,
             bvh_index,
-            junk: [0; 2],
+            meshlet_index,
+            center,
+            radius,
+            meshlet_count,
+            lod_index,
+            lod_count,
+            ..*info
+        };
+        let info = *info;
+        self.mesh_info.write(&self.gpu, id.0 as usize, info);
+        if !fits {
+            self.update_bind_groups();
+        }
+        log::info!("Updated mesh with id: {}", id.0);
+    }
+
+    /// Fails without changing the pool, like [`MeshPool::add_many`].
+    pub fn add(&mut self, mesh: MeshRef) -> Result<MeshId> {
+        let vertex_count = mesh.vertices.len();
+        let index_count = mesh.indices.len();
+        self.add_rolled_back(vec![mesh], false)
+            .map(|ids| ids[0])
+            .wrap_err_with(|| {
+                format!(
+                    "while adding mesh with {vertex_count} vertices and {index_count} indices to MeshPool"
+                )
+            })
+    }
+
+    /// Adds all meshes with one upload per buffer, their BVHs are built in parallel.
+    ///
+    /// Meshes identical to one added earlier by `add_many`, or earlier in `meshes`, return
+    /// the existing id instead of a copy, so spawning the same model twice shares its
+    /// buffers. [`MeshPool::add`] always makes a copy, e.g. for meshes skinned in place.
+    ///
+    /// When the buffers can't grow the pool is left as it was and no id is handed out.
+    pub fn add_many(&mut self, meshes: Vec<MeshRef>) -> Result<Vec<MeshId>> {
+        let mesh_count = meshes.len();
+        let vertex_count: usize = meshes.iter().map(|mesh| mesh.vertices.len()).sum();
+        self.add_rolled_back(meshes, true).wrap_err_with(|| {
+            format!("while adding {mesh_count} meshes with {vertex_count} vertices to MeshPool")
+        })
+    }
+
+    fn add_rolled_back(&mut self, meshes: Vec<MeshRef>, deduplicate: bool) -> Result<Vec<MeshId>> {
+        let checkpoint = self.checkpoint();
+        let gpu = self.gpu.clone();
+        let added = gpu
+            .error_scope(|| self.add_many_unchecked(meshes, deduplicate))
+            .and_then(|ids| ids);
+        if added.is_err() {
+            self.rollback(checkpoint);
+        }
+        added
+    }
+
+    fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            counters: [
+                &self.vertex_offset,
+                &self.base_index,
+                &self.mesh_index,
+                &self.bvh_index,
+                &self.meshlet_index,
+                &self.lod_index,
+            ]
+            .map(|counter| counter.load(Ordering::Relaxed)),
+            buffers: [
+                self.vertices.len(),
+                self.normals.len(),
+                self.tangents.len(),
+                self.tex_coords.len(),
+                self.indices.len(),
+                self.bvh_nodes.len(),
+                self.meshlets.len(),
+                self.lods.len(),
+                self.mesh_info.len(),
+            ],
+            references: self
+                .allocations
+                .iter()
+                .map(|allocation| allocation.references)
+                .collect(),
+        }
+    }
+
+    // Undoes a failed `add_many_unchecked`, meshes past the checkpoint are forgotten.
+    fn rollback(&mut self, checkpoint: Checkpoint) {
+        let counters = [
+            &self.vertex_offset,
+            &self.base_index,
+            &self.mesh_index,
+            &self.bvh_index,
+            &self.meshlet_index,
+            &self.lod_index,
+        ];
+        for (counter, value) in counters.into_iter().zip(checkpoint.counters) {
+            counter.store(value, Ordering::Relaxed);
+        }
+        let [vertices, normals, tangents, tex_coords, indices, bvh_nodes, meshlets, lods, mesh_info] =
+            checkpoint.buffers;
+        self.vertices.truncate(vertices);
+        self.normals.truncate(normals);
+        self.tangents.truncate(tangents);
+        self.tex_coords.truncate(tex_coords);
+        self.indices.truncate(indices);
+        self.bvh_nodes.truncate(bvh_nodes);
+        self.meshlets.truncate(meshlets);
+        self.lods.truncate(lods);
+        self.mesh_info.truncate(mesh_info);
+
+        let mesh_count = checkpoint.references.len();
+        self.mesh_info_cpu.truncate(mesh_count);
+        self.allocations.truncate(mesh_count);
+        for (allocation, references) in self.allocations.iter_mut().zip(checkpoint.references) {
+            allocation.references = references;
+        }
+        self.contents.retain(|_, id| (id.0 as usize) < mesh_count);
+        self.update_bind_groups();
+    }
+
+    fn add_many_unchecked(
+        &mut self,
+        meshes: Vec<MeshRef>,
+        deduplicate: bool,
+    ) -> Result<Vec<MeshId>> {
+        if meshes.is_empty() {
+            return Ok(vec![]);
+        }
+        // Hashed before the BVH builder reorders the indices.
+        let hashes: Vec<_> = if deduplicate {
+            meshes
+                .par_iter()
+                .map(|mesh| Some(mesh.content_hash()))
+                .collect()
+        } else {
+            vec![None; meshes.len()]
+        };
+
+        let mut ids = vec![MeshId(0); meshes.len()];
+        let mut new_meshes = vec![];
+        let mut new_hashes = vec![];
+        let mut pending = HashMap::new();
+        for (i, (mesh, hash)) in meshes.into_iter().zip(hashes).enumerate() {
+            if let Some(hash) = hash {
+                if let Some(&id) = self.contents.get(&hash) {
+                    log::info!("Reused mesh with id: {}", id.0);
+                    ids[i] = id;
+                    continue;
+                }
+                if let Some(&first) = pending.get(&hash) {
+                    ids[i] = MeshId(first);
+                    continue;
+                }
+            }
+            // Final ids are handed out below, in the same order.
+            let pending_index = self.mesh_index.load(Ordering::Relaxed) + new_meshes.len() as u32;
+            if let Some(hash) = hash {
+                pending.insert(hash, pending_index);
+            }
+            ids[i] = MeshId(pending_index);
+            new_meshes.push(mesh);
+            new_hashes.push(hash);
+        }
+        let mut meshes = new_meshes;
+        if meshes.is_empty() {
+            self.add_references(&ids);
+            return Ok(ids);
+        }
+
+        let lod_chain = self.lod_chain;
+        let built: Vec<_> = meshes
+            .par_iter_mut()
+            .map(|mesh| {
+                let bvh =
+                    BvhBuilder::new(mesh.vertices, bytemuck::cast_slice_mut(&mut mesh.indices))
+                        .build();
+                let meshlets = build_meshlets(mesh.vertices, &mesh.indices);
+                let lods = lod_chain.build(mesh.vertices, &mesh.indices);
+                let (min, max) = calculate_bounds(mesh.vertices);
+                let sphere = calculate_bounding_sphere(mesh.vertices, min, max);
+                (bvh, meshlets, lods, (min, max), sphere)
+            })
+            .collect();
+
+        let mut vertices = vec![];
+        let mut normals = vec![];
+        let mut tangents = vec![];
+        let mut tex_coords = vec![];
+        let mut indices = vec![];
+        let mut bvh_nodes = vec![];
+        let mut meshlets = vec![];
+        let mut lods = vec![];
+        let mut mesh_infos = vec![];
+        for (
+            (mesh, hash),
+            (bvh, mesh_meshlets, (mesh_lods, lod_indices), (min, max), (center, radius)),
+        ) in meshes.iter().zip(new_hashes).zip(built)
+        {
+            let vertex_count = mesh.vertices.len() as u32;
+            let vertex_offset = self
+                .vertex_offset
+                .fetch_add(vertex_count, Ordering::Relaxed);
+            let bvh_count = bvh.nodes.len() as u32;
+            let bvh_index = self.bvh_index.fetch_add(bvh_count, Ordering::Relaxed);
+            let index_count = mesh.indices.len() as u32;
+            let lod_index_count = index_count + lod_indices.len() as u32;
+            let base_index = self
+                .base_index
+                .fetch_add(lod_index_count, Ordering::Relaxed);
+            let meshlet_count = mesh_meshlets.len() as u32;
+            let meshlet_index = self
+                .meshlet_index
+                .fetch_add(meshlet_count, Ordering::Relaxed);
+            let lod_count = mesh_lods.len() as u32;
+            let lod_index = self.lod_index.fetch_add(lod_count, Ordering::Relaxed);
+            let mesh_index = self.mesh_index.fetch_add(1, Ordering::Relaxed);
+
+            vertices.extend_from_slice(mesh.vertices);
+            normals.extend_from_slice(mesh.normals);
+            tangents.extend_from_slice(mesh.tangents);
+            tex_coords.extend_from_slice(mesh.tex_coords);
+            indices.extend_from_slice(&mesh.indices);
+            indices.extend(lod_indices);
+            bvh_nodes.extend(bvh.nodes);
+            meshlets.extend(mesh_meshlets);
+            lods.extend(mesh_lods);
+
+            mesh_infos.push(MeshInfo {
+                min,
+                vertex_offset: vertex_offset as i32,
+                max,
+                base_index,
+                index_count,
+                bvh_index,
+                triangle_materials: MeshInfo::NO_TRIANGLE_MATERIALS,
+                meshlet_index,
+                center,
+                radius,
+                meshlet_count,
+                lod_index,
+                lod_count,
+                junk: 0,
+            });
+            self.allocations.push(MeshAllocation {
+                vertices: vertex_offset..vertex_offset + vertex_count,
+                indices: base_index..base_index + lod_index_count,
+                bvh_nodes: bvh_index..bvh_index + bvh_count,
+                triangle_materials: 0..0,
+                meshlets: meshlet_index..meshlet_index + meshlet_count,
+                lods: lod_index..lod_index + lod_count,
+                hash,
+                references: 0,
+                removed: false,
+            });
+            if let Some(hash) = hash {
+                self.contents.insert(hash, MeshId(mesh_index));
+            }
+            log::info!("Added new mesh with id: {mesh_index}");
+        }
+
+        let device = self.gpu.device();
+        let fits = self.vertices.fits(device, vertices.len())
+            && self.normals.fits(device, normals.len())
+            && self.tangents.fits(device, tangents.len())
+            && self.tex_coords.fits(device, tex_coords.len())
+            && self.indices.fits(device, indices.len())
+            && self.bvh_nodes.fits(device, bvh_nodes.len())
+            && self.meshlets.fits(device, meshlets.len())
+            && self.lods.fits(device, lods.len())
+            && self.mesh_info.fits(device, mesh_infos.len());
+        if !fits {
+            bail!("MeshPool buffers can't grow past the buffer size limits");
+        }
+        self.vertices.push(&self.gpu, &vertices);
+        self.normals.push(&self.gpu, &normals);
+        self.tangents.push(&self.gpu, &tangents);
+        self.tex_coords.push(&self.gpu, &tex_coords);
+        self.indices.push(&self.gpu, &indices);
+        self.bvh_nodes.push(&self.gpu, &bvh_nodes);
+        if !meshlets.is_empty() {
+            self.meshlets.push(&self.gpu, &meshlets);
+        }
+        if !lods.is_empty() {
+            self.lods.push(&self.gpu, &lods);
+        }
+        self.mesh_info_cpu.extend_from_slice(&mesh_infos);
+        self.mesh_info.push(&self.gpu, &mesh_infos);
+        self.update_bind_groups();
+
+        self.add_references(&ids);
+        Ok(ids)
+    }
+
+    fn add_references(&mut self, ids: &[MeshId]) {
+        for id in ids {
+            self.allocations[id.0 as usize].references += 1;
+        }
+    }
+
+    fn update_bind_groups(&mut self) {
+        self.mesh_info_bind_group = Self::mesh_info_bind_group(
+            self.gpu.device(),
+            &self.mesh_info_layout,
+            &self.mesh_info,
+            &self.triangle_materials,
+            &self.meshlets,
+            &self.lods,
+        );
+        self.attributes_bind_group = Self::attributes_bind_group(
+            self.gpu.device(),
+            &self.attributes_layout,
+            [
+                &self.mesh_info,
+                &self.indices,
+                &self.vertices,
+                &self.normals,
+                &self.tangents,
+                &self.tex_coords,
+                &self.triangle_materials,
+            ],
+        );
+    }
+
+    fn vertex_range(&self, id: MeshId) -> Range<usize> {
+        let vertices = &self.allocations[id.0 as usize].vertices;
+        vertices.start as usize..vertices.end as usize
+    }
+
+    /// Frees the buffer ranges of the mesh. The id stays taken and the mesh draws nothing,
+    /// so instances of it should be removed or deactivated first.
+    ///
+    /// Ids shared by [`MeshPool::add_many`] are only freed once every holder removed them.
+    ///
+    /// Once more than [`MeshPool::DEFRAGMENT_THRESHOLD`] of the vertices are freed, the
+    /// pool is compacted with [`MeshPool::defragment`].
+    pub fn remove(&mut self, id: MeshId) -> Result<()> {
+        let Some(allocation) = self.allocations.get_mut(id.0 as usize) else {
+            bail!("Mesh {} is not in the MeshPool", id.0);
+        };
+        if allocation.removed {
+            bail!("Mesh {} is already removed", id.0);
+        }
+        allocation.references = allocation.references.saturating_sub(1);
+        if allocation.references > 0 {
+            return Ok(());
+        }
+        allocation.removed = true;
+        if let Some(hash) = allocation.hash {
+            self.contents.remove(&hash);
+        }
+
+        let info = MeshInfo {
+            triangle_materials: MeshInfo::NO_TRIANGLE_MATERIALS,
+            ..Default::default()
+        };
+        self.mesh_info_cpu[id.0 as usize] = info;
+        self.mesh_info.write(&self.gpu, id.0 as usize, info);
+        log::info!("Removed mesh with id: {}", id.0);
+
+        if self.fragmentation() > Self::DEFRAGMENT_THRESHOLD {
+            self.defragment()?;
+        }
+        Ok(())
+    }
+
+    /// Share of the vertex buffers held by removed meshes and old versions of updated ones.
+    pub fn fragmentation(&self) -> f32 {
+        let freed: u32 = self
+            .allocations
+            .iter()
+            .filter(|allocation| allocation.removed)
+            .map(|allocation| allocation.vertices.len() as u32)
+            .sum();
+        (freed + self.stale_vertices) as f32 / self.vertices.len().max(1) as f32
+    }
+
+    /// Moves the live meshes together on the gpu, so the ranges of removed meshes get reused.
+    ///
+    /// Every mesh buffer is reallocated, bind groups made from them have to be recreated
+    /// like after a [`MeshPool::add`] that grew them.
+    pub fn defragment(&mut self) -> Result<()> {
+        let gpu = self.gpu.clone();
+        gpu.error_scope(|| self.defragment_unchecked())
+            .wrap_err("while defragmenting MeshPool")
+    }
+
+    fn defragment_unchecked(&mut self) {
+        let mut moved = self.allocations.clone();
+        let mut ends = [0u32; 6];
+        for (allocation, info) in moved.iter_mut().zip(&mut self.mesh_info_cpu) {
+            let ranges = [
+                &mut allocation.vertices,
+                &mut allocation.indices,
+                &mut allocation.bvh_nodes,
+                &mut allocation.triangle_materials,
+                &mut allocation.meshlets,
+                &mut allocation.lods,
+            ];
+            for (range, end) in ranges.into_iter().zip(&mut ends) {
+                let len = if allocation.removed {
+                    0
+                } else {
+                    range.len() as u32
+                };
+                *range = *end..*end + len;
+                *end += len;
+            }
+            if !allocation.removed {
+                info.vertex_offset = allocation.vertices.start as i32;
+                info.base_index = allocation.indices.start;
+                info.bvh_index = allocation.bvh_nodes.start;
+                info.meshlet_index = allocation.meshlets.start;
+                info.lod_index = allocation.lods.start;
+                if info.triangle_materials != MeshInfo::NO_TRIANGLE_MATERIALS {
+                    info.triangle_materials = allocation.triangle_materials.start;
+                }
+            }
+        }
+
+        let device = self.gpu.device();
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Mesh Defragment Encoder"),
+        });
+        let vertices = |a: &MeshAllocation| a.vertices.clone();
+        compact(
+            device,
+            &mut encoder,
+            &mut self.vertices,
+            &self.allocations,
+            &moved,
+            vertices,
+        );
+        compact(
+            device,
+            &mut encoder,
+            &mut self.normals,
+            &self.allocations,
+            &moved,
+            vertices,
+        );
+        compact(
+            device,
+            &mut encoder,
+            &mut self.tangents,
+            &self.allocations,
+            &moved,
+            vertices,
+        );
+        compact(
+            device,
+            &mut encoder,
+            &mut self.tex_coords,
+            &self.allocations,
+            &moved,
+            vertices,
+        );
+        compact(
+            device,
+            &mut encoder,
+            &mut self.indices,
+            &self.allocations,
+            &moved,
+            |a| a.indices.clone(),
+        );
+        compact(
+            device,
+            &mut encoder,
+            &mut self.bvh_nodes,
+            &self.allocations,
+            &moved,
+            |a| a.bvh_nodes.clone(),
+        );
+        compact(
+            device,
+            &mut encoder,
+            &mut self.triangle_materials,
+            &self.allocations,
+            &moved,
+            |a| a.triangle_materials.clone(),
+        );
+        compact(
+            device,
+            &mut encoder,
+            &mut self.meshlets,
+            &self.allocations,
+            &moved,
+            |a| a.meshlets.clone(),
+        );
+        compact(
+            device,
+            &mut encoder,
+            &mut self.lods,
+            &self.allocations,
+            &moved,
+            |a| a.lods.clone(),
+        );
+        self.gpu.queue().submit(Some(encoder.finish()));
+
+        let [vertex_count, index_count, bvh_count, _, meshlet_count, lod_count] = ends;
+        self.stale_vertices = 0;
+        self.vertex_offset.store(vertex_count, Ordering::Relaxed);
+        self.base_index.store(index_count, Ordering::Relaxed);
+        self.bvh_index.store(bvh_count, Ordering::Relaxed);
+        self.meshlet_index.store(meshlet_count, Ordering::Relaxed);
+        self.lod_index.store(lod_count, Ordering::Relaxed);
+        self.allocations = moved;
+        self.mesh_info
+            .write_slice(&self.gpu, 0, &self.mesh_info_cpu);
+        self.update_bind_groups();
+        log::info!("Defragmented MeshPool down to {vertex_count} vertices");
+    }
+
+    /// Bakes static meshes placed with their transforms into a single mesh, so a group
+    /// of instances sharing a material turns into one instance and one draw.
+    ///
+    /// Reads the source meshes back from the gpu, meant for scene setup.
+    pub fn merge(&mut self, parts: &[(MeshId, Mat4)]) -> Result<MeshId> {
+        let mesh = self.merged_mesh(parts.iter().copied(), false)?;
+        self.add(mesh.as_ref())
+    }
+
+    /// Like [`MeshPool::merge`], but every part keeps its own material.
+    ///
+    /// The merged mesh shares no vertices between triangles, so the triangle of a vertex
+    /// is its index divided by three and `triangle_materials` is read per vertex as well
+    /// as per pixel. Instances of it ignore their own material.
+    pub fn merge_with_materials(&mut self, parts: &[(MeshId, Mat4, MaterialId)]) -> Result<MeshId> {
+        let mesh = self.merged_mesh(
+            parts.iter().map(|(id, transform, _)| (*id, *transform)),
+            true,
+        )?;
+        let materials = parts
+            .iter()
+            .flat_map(|(id, _, material)| {
+                let triangle_count = self.mesh_info_cpu[id.0 as usize].index_count / 3;
+                std::iter::repeat(material.0).take(triangle_count as usize)
+            })
+            .collect::<Vec<_>>();
+
+        let id = self.add(mesh.as_ref())?;
+        let offset = self.triangle_materials.len() as u32;
+        let gpu = self.gpu.clone();
+        gpu.error_scope(|| {
+            self.triangle_materials.push(&self.gpu, &materials);
+            self.allocations[id.0 as usize].triangle_materials =
+                offset..offset + materials.len() as u32;
+            let info = &mut self.mesh_info_cpu[id.0 as usize];
+            info.triangle_materials = offset;
+            let info = *info;
+            self.mesh_info.write(&self.gpu, id.0 as usize, info);
+            self.update_bind_groups();
+        })
+        .wrap_err_with(|| {
+            format!(
+                "while adding {} triangle materials to MeshPool",
+                materials.len()
+            )
+        })?;
+        Ok(id)
+    }
+
+    fn merged_mesh(
+        &self,
+        parts: impl Iterator<Item = (MeshId, Mat4)>,
+        unshared_vertices: bool,
+    ) -> Result<Mesh> {
+        let mut merged = Mesh {
+            vertices: vec![],
+            normals: vec![],
+            tangents: vec![],
+            tex_coords: vec![],
+            indices: vec![],
         };
-        self.mesh_info_cpu.push(mesh_info);
-        self.mesh_info.push(&self.gpu, &[mesh_info]);
-        self.mesh_info_bind_group =
-            Self::mesh_info_bind_group(self.gpu.device(), &self.mesh_info_layout, &self.mesh_info);
+        for (id, transform) in parts {
+            let Some(info) = self.mesh_info_cpu.get(id.0 as usize) else {
+                bail!("Mesh {} is not in the MeshPool", id.0);
+            };
+            let range = self.vertex_range(id);
+            let first = info.base_index as usize;
+            let mut indices = self
+                .indices
+                .read_range(&self.gpu, first..first + info.index_count as usize);
+            // Mirroring transforms flip the winding and the bitangent.
+            let mirrored = transform.determinant() < 0.;
+            if mirrored {
+                indices.chunks_exact_mut(3).for_each(|tri| tri.swap(1, 2));
+            }
+
+            let normal_matrix = Mat3::from_mat4(transform).inverse().transpose();
+            let tangent_matrix = Mat3::from_mat4(transform);
+            let vertices = self.vertices.read_range(&self.gpu, range.clone());
+            let normals = self.normals.read_range(&self.gpu, range.clone());
+            let tangents = self.tangents.read_range(&self.gpu, range.clone());
+            let tex_coords = self.tex_coords.read_range(&self.gpu, range);
+            let vertex = |i: usize| {
+                let tangent = tangent_matrix * tangents[i].truncate();
+                let sign = if mirrored {
+                    -tangents[i].w
+                } else {
+                    tangents[i].w
+                };
+                (
+                    transform.transform_point3(vertices[i]),
+                    (normal_matrix * normals[i]).normalize_or_zero(),
+                    tangent.normalize_or_zero().extend(sign),
+                    tex_coords[i],
+                )
+            };
+            let mut push = |(position, normal, tangent, uv)| {
+                merged.vertices.push(position);
+                merged.normals.push(normal);
+                merged.tangents.push(tangent);
+                merged.tex_coords.push(uv);
+            };
+
+            if unshared_vertices {
+                for &index in &indices {
+                    merged.indices.push(merged.vertices.len() as u32);
+                    push(vertex(index as usize));
+                }
+            } else {
+                let base = merged.vertices.len() as u32;
+                merged
+                    .indices
+                    .extend(indices.iter().map(|index| base + index));
+                (0..vertices.len()).for_each(|i| push(vertex(i)));
+            }
+        }
+        if merged.indices.is_empty() {
+            bail!("Nothing to merge");
+        }
+        Ok(merged)
+    }
+}
+
+// Copies the live ranges of `buffer` into a new buffer at their `moved` places.
+fn compact<T: bytemuck::Pod + NonZeroSized>(
+    device: &wgpu::Device,
+    encoder: &mut wgpu::CommandEncoder,
+    buffer: &mut ResizableBuffer<T>,
+    allocations: &[MeshAllocation],
+    moved: &[MeshAllocation],
+    range: impl Fn(&MeshAllocation) -> Range<u32>,
+) {
+    let len = moved.iter().map(|a| range(a).end).max().unwrap_or(0) as usize;
+    let mut compacted =
+        ResizableBuffer::with_capasity(device, (len + 1).next_power_of_two(), buffer.usages());
+    compacted.set_len(device, encoder, len);
+    for (old, new) in allocations.iter().zip(moved) {
+        let (old, new) = (range(old), range(new));
+        if new.is_empty() {
+            continue;
+        }
+        encoder.copy_buffer_to_buffer(
+            buffer,
+            (old.start as usize * T::SIZE) as u64,
+            &compacted,
+            (new.start as usize * T::SIZE) as u64,
+            (new.len() * T::SIZE) as u64,
+        );
+    }
+    *buffer = compacted;
+}
 
-        log::info!("Added new mesh with id: {mesh_index}");
-        MeshId(mesh_index)
+fn storage_entry(binding: u32, min_binding_size: wgpu::BufferSize) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::COMPUTE.union(wgpu::ShaderStages::FRAGMENT),
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only: true },
+            has_dynamic_offset: false,
+            min_binding_size: Some(min_binding_size),
+        },
+        count: None,
     }
 }
@@ -0,0 +1,244 @@
+use std::sync::Arc;
+
+use bytemuck::{Pod, Zeroable};
+use color_eyre::{eyre::WrapErr, Result};
+use glam::{Mat4, UVec4, Vec3, Vec4};
+
+use components::{
+    bind_group_layout::{self, WrappedBindGroupLayout},
+    Gpu, MeshId, NonZeroSized, ResizableBuffer, ResizableBufferExt,
+};
+
+use crate::{MeshPool, MeshRef};
+
+/// Rest pose of a skinned vertex with up to four joint influences.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default, Pod, Zeroable)]
+pub struct SkinnedVertex {
+    pub position: Vec3,
+    /// Index into the skinned meshes, filled in by `SkinnedMeshPool::add_mesh`.
+    pub skinned_mesh: u32,
+    pub normal: Vec3,
+    pub padding: u32,
+    pub tangent: Vec4,
+    pub joints: UVec4,
+    pub weights: Vec4,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default, Pod, Zeroable)]
+pub struct SkinnedMeshInfo {
+    /// First vertex in `SkinnedMeshPool::vertices`.
+    pub rest_offset: u32,
+    /// First matrix of the skin in `SkinnedMeshPool::joint_matrices`.
+    pub joint_offset: u32,
+    pub joint_count: u32,
+    /// Posed copy in the `MeshPool`, written at its vertex offset and refit by the skinning pass.
+    pub mesh_id: u32,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct SkinId(pub u32);
+
+/// Skinned meshes posed on the gpu every frame.
+///
+/// Every skinned mesh owns a copy in the `MeshPool`, so it is drawn, culled and shaded
+/// like any other mesh while the skinning pass overwrites its vertices from the rest pose
+/// kept here. Meshes sharing a skin share its joint matrices.
+pub struct SkinnedMeshPool {
+    pub vertices: ResizableBuffer<SkinnedVertex>,
+    pub meshes: ResizableBuffer<SkinnedMeshInfo>,
+    pub joint_matrices: ResizableBuffer<Mat4>,
+    /// Posed bounds of every skinned mesh gathered by the skinning pass, six order
+    /// preserving u32s each: the inverted min followed by the max, zero when empty.
+    pub bounds: ResizableBuffer<u32>,
+    // Joint offset and count per skin.
+    skins: Vec<(u32, u32)>,
+
+    pub bind_group_layout: bind_group_layout::BindGroupLayout,
+    pub bind_group: wgpu::BindGroup,
+    gpu: Arc<Gpu>,
+}
+
+impl SkinnedMeshPool {
+    const LAYOUT: wgpu::BindGroupLayoutDescriptor<'static> = wgpu::BindGroupLayoutDescriptor {
+        label: Some("Skinned Mesh Pool Bind Group Layout"),
+        entries: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: Some(SkinnedVertex::NSIZE),
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: Some(SkinnedMeshInfo::NSIZE),
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: Some(Mat4::NSIZE),
+                },
+                count: None,
+            },
+        ],
+    };
+
+    pub fn new(gpu: Arc<Gpu>) -> Self {
+        let vertices = gpu
+            .device()
+            .create_resizable_buffer(wgpu::BufferUsages::STORAGE);
+        let meshes = gpu
+            .device()
+            .create_resizable_buffer(wgpu::BufferUsages::STORAGE);
+        let joint_matrices = gpu
+            .device()
+            .create_resizable_buffer(wgpu::BufferUsages::STORAGE);
+        let bounds = gpu
+            .device()
+            .create_resizable_buffer(wgpu::BufferUsages::STORAGE);
+
+        let bind_group_layout = gpu.device().create_bind_group_layout_wrap(&Self::LAYOUT);
+        let bind_group = Self::create_bind_group(
+            gpu.device(),
+            &bind_group_layout,
+            [&vertices, &meshes, &joint_matrices],
+        );
+
+        Self {
+            vertices,
+            meshes,
+            joint_matrices,
+            bounds,
+            skins: vec![],
+            bind_group_layout,
+            bind_group,
+            gpu,
+        }
+    }
+
+    fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        buffers: [&wgpu::Buffer; 3],
+    ) -> wgpu::BindGroup {
+        let entries = buffers
+            .iter()
+            .enumerate()
+            .map(|(i, buffer)| wgpu::BindGroupEntry {
+                binding: i as u32,
+                resource: buffer.as_entire_binding(),
+            })
+            .collect::<Vec<_>>();
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Skinned Mesh Pool Bind Group"),
+            layout,
+            entries: &entries,
+        })
+    }
+
+    fn update_bind_group(&mut self) {
+        self.bind_group = Self::create_bind_group(
+            self.gpu.device(),
+            &self.bind_group_layout,
+            [&self.vertices, &self.meshes, &self.joint_matrices],
+        );
+    }
+
+    /// Allocates `joint_count` joint matrices, set to identity until the first pose.
+    pub fn add_skin(&mut self, joint_count: u32) -> SkinId {
+        let offset = self.joint_matrices.len() as u32;
+        let joint_count = joint_count.max(1);
+        if self
+            .joint_matrices
+            .push(&self.gpu, &vec![Mat4::IDENTITY; joint_count as usize])
+        {
+            self.update_bind_group();
+        }
+        self.skins.push((offset, joint_count));
+        SkinId(self.skins.len() as u32 - 1)
+    }
+
+    /// Adds a posed copy of `mesh` to `mesh_pool`, deformed by `skin` from then on.
+    ///
+    /// `joints` index into the joint matrices of the skin.
+    pub fn add_mesh(
+        &mut self,
+        mesh_pool: &mut MeshPool,
+        skin: SkinId,
+        mesh: MeshRef,
+        joints: &[UVec4],
+        weights: &[Vec4],
+    ) -> Result<MeshId> {
+        let (joint_offset, joint_count) = self.skins[skin.0 as usize];
+        let skinned_mesh = self.meshes.len() as u32;
+        let vertices: Vec<_> = (0..mesh.vertices.len())
+            .map(|i| SkinnedVertex {
+                position: mesh.vertices[i],
+                skinned_mesh,
+                normal: mesh.normals.get(i).copied().unwrap_or(Vec3::Y),
+                padding: 0,
+                tangent: mesh.tangents.get(i).copied().unwrap_or(Vec4::Y),
+                joints: joints.get(i).copied().unwrap_or_default(),
+                weights: weights.get(i).copied().unwrap_or(Vec4::X),
+            })
+            .collect();
+        let (min, max) = crate::calculate_bounds(mesh.vertices);
+
+        let mesh_id = mesh_pool.add(mesh)?;
+        // Poses reach past the rest bounds. The skinning pass refits the gpu copy every
+        // frame, the dilated ones are kept on the cpu and until the first pose.
+        let margin = (max - min) * 0.5;
+        mesh_pool.set_bounds(mesh_id, min - margin, max + margin);
+
+        let info = SkinnedMeshInfo {
+            rest_offset: self.vertices.len() as u32,
+            joint_offset,
+            joint_count,
+            mesh_id: mesh_id.0,
+        };
+        let gpu = self.gpu.clone();
+        gpu.error_scope(|| {
+            self.bounds.push(&self.gpu, &[0; 6]);
+            let resized = self.vertices.push(&self.gpu, &vertices);
+            if self.meshes.push(&self.gpu, &[info]) || resized {
+                self.update_bind_group();
+            }
+        })
+        .wrap_err_with(|| format!("while adding skinned mesh with {} vertices", vertices.len()))?;
+        Ok(mesh_id)
+    }
+
+    /// Uploads the pose of `skin`, each matrix maps the rest pose into model space.
+    pub fn set_joint_matrices(&mut self, skin: SkinId, matrices: &[Mat4]) {
+        let (offset, count) = self.skins[skin.0 as usize];
+        let count = matrices.len().min(count as usize);
+        self.joint_matrices
+            .write_slice(&self.gpu, offset as usize, &matrices[..count]);
+    }
+
+    pub fn vertex_count(&self) -> u32 {
+        self.vertices.len() as u32
+    }
+
+    pub fn mesh_count(&self) -> u32 {
+        self.meshes.len() as u32
+    }
+
+    pub fn skin_count(&self) -> u32 {
+        self.skins.len() as u32
+    }
+}
@@ -1,10 +1,15 @@
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc};
+
+use color_eyre::{
+    eyre::{bail, WrapErr},
+    Result,
+};
 
 use wgpu::util::DeviceExt;
 
 use components::{
     bind_group_layout::{self, WrappedBindGroupLayout},
-    create_solid_color_texture, Gpu,
+    create_solid_color_texture, Blitter, Gpu,
 };
 
 pub const WHITE_TEXTURE: TextureId = TextureId(0);
@@ -16,8 +21,29 @@ mod ltc {
     include!("ltc_matrix.raw");
 }
 
+/// What the texels of a texture hold, checked against the material slots with
+/// [`MaterialPool::validate_color_spaces`](crate::MaterialPool::validate_color_spaces).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ColorSpace {
+    /// Colors, in an sRGB format so sampling decodes them, or linearized on import.
+    Srgb,
+    /// Data sampled as is, like normals, occlusion-roughness-metallic or lookup tables.
+    Linear,
+}
+
+impl ColorSpace {
+    /// Color space of 8-bit textures, which are only decoded when the format says so.
+    pub fn of_format(format: wgpu::TextureFormat) -> Self {
+        if format.is_srgb() {
+            Self::Srgb
+        } else {
+            Self::Linear
+        }
+    }
+}
+
 #[repr(C)]
-#[derive(Debug, Copy, Default, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+#[derive(Debug, Copy, Default, Clone, PartialEq, Eq, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct TextureId(u32);
 
 impl TextureId {
@@ -26,8 +52,17 @@ impl TextureId {
     }
 }
 
+/// Bindless array of texture views, slots of removed textures are reused by [`TexturePool::add`].
 pub struct TexturePool {
     pub views: Vec<wgpu::TextureView>,
+    /// `None` for the white and black defaults, which read the same in both.
+    color_spaces: Vec<Option<ColorSpace>>,
+    ref_counts: Vec<u32>,
+    free: Vec<u32>,
+    /// Textures added with [`TexturePool::add_with_hash`], by content.
+    contents: HashMap<(u64, ColorSpace), TextureId>,
+    /// Bound in freed slots, the array can't have holes.
+    placeholder: wgpu::Texture,
 
     sampler: wgpu::Sampler,
     ltc_sampler: wgpu::Sampler,
@@ -37,11 +72,21 @@ pub struct TexturePool {
     gpu: Arc<Gpu>,
 }
 
-const MAX_TEXTURES: u32 = 1 << 10;
+/// Length of the bindless array, layouts binding the views of the pool declare it.
+pub const MAX_TEXTURES: u32 = 1 << 10;
 
 impl TexturePool {
     pub fn new(gpu: Arc<Gpu>) -> Self {
         let views = default_textures(&gpu);
+        let color_spaces = vec![
+            None,
+            None,
+            Some(ColorSpace::Linear),
+            Some(ColorSpace::Linear),
+        ];
+        let ref_counts = vec![0; views.len()];
+        let placeholder =
+            create_solid_color_texture(gpu.device(), gpu.queue(), glam::Vec3::splat(1.));
 
         let bind_group_layout =
             gpu.device()
@@ -105,6 +150,11 @@ impl TexturePool {
 
         Self {
             views,
+            color_spaces,
+            ref_counts,
+            free: vec![],
+            contents: HashMap::new(),
+            placeholder,
 
             sampler,
             ltc_sampler,
@@ -114,10 +164,111 @@ impl TexturePool {
         }
     }
 
-    pub fn add(&mut self, view: wgpu::TextureView) -> TextureId {
+    /// Takes a freed slot if there is one. The texture starts with one reference,
+    /// the bind group picks it up on the next [`TexturePool::update_bind_group`].
+    pub fn add(&mut self, view: wgpu::TextureView, color_space: ColorSpace) -> Result<TextureId> {
+        if let Some(slot) = self.free.pop() {
+            self.views[slot as usize] = view;
+            self.color_spaces[slot as usize] = Some(color_space);
+            self.ref_counts[slot as usize] = 1;
+            return Ok(TextureId(slot));
+        }
+        if self.views.len() as u32 >= MAX_TEXTURES {
+            bail!("TexturePool is full, it holds at most {MAX_TEXTURES} textures");
+        }
         self.views.push(view);
+        self.color_spaces.push(Some(color_space));
+        self.ref_counts.push(1);
+
+        Ok(TextureId(self.views.len() as u32 - 1))
+    }
 
-        TextureId(self.views.len() as u32 - 1)
+    /// Fills the mips of `texture` below the first with `blitter`, then adds a view of it
+    /// like [`TexturePool::add`]. Textures with a single mip are added as they are.
+    pub fn add_mipmapped(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        blitter: &Blitter,
+        texture: &wgpu::Texture,
+        color_space: ColorSpace,
+    ) -> Result<TextureId> {
+        if texture.mip_level_count() > 1 {
+            let format = texture.format();
+            if !Blitter::can_generate_mipmaps(format, self.gpu.device().features()) {
+                bail!("Can't generate mipmaps of {format:?} textures");
+            }
+            blitter.generate_mipmaps(encoder, self.gpu.device(), texture);
+        }
+        self.add(texture.create_view(&Default::default()), color_space)
+    }
+
+    /// Like [`TexturePool::add`], but [`TexturePool::reuse`] finds the texture by `hash`,
+    /// a hash of its pixels, so identical textures of other documents can share it.
+    pub fn add_with_hash(
+        &mut self,
+        view: wgpu::TextureView,
+        color_space: ColorSpace,
+        hash: u64,
+    ) -> Result<TextureId> {
+        let id = self.add(view, color_space)?;
+        self.contents.insert((hash, color_space), id);
+        Ok(id)
+    }
+
+    /// Adds a reference to the texture with the same content hash, if there is one.
+    pub fn reuse(&mut self, hash: u64, color_space: ColorSpace) -> Option<TextureId> {
+        let id = *self.contents.get(&(hash, color_space))?;
+        self.ref_counts[id.0 as usize] += 1;
+        Some(id)
+    }
+
+    /// Adds a reference to a texture, so it survives one more [`TexturePool::remove`].
+    pub fn retain(&mut self, id: TextureId) -> Result<()> {
+        self.check_removable(id)?;
+        self.ref_counts[id.0 as usize] += 1;
+        Ok(())
+    }
+
+    /// Drops a reference and frees the slot with the last one. Materials still pointing
+    /// at a freed slot sample white until it is reused.
+    pub fn remove(&mut self, id: TextureId) -> Result<()> {
+        self.check_removable(id)?;
+        let count = &mut self.ref_counts[id.0 as usize];
+        *count -= 1;
+        if *count == 0 {
+            self.views[id.0 as usize] = self.placeholder.create_view(&Default::default());
+            self.color_spaces[id.0 as usize] = None;
+            self.contents.retain(|_, content| *content != id);
+            self.free.push(id.0);
+            log::info!("Freed texture slot {}", id.0);
+        }
+        Ok(())
+    }
+
+    /// `None` for freed slots and the defaults that fit either color space.
+    pub fn color_space(&self, id: TextureId) -> Option<ColorSpace> {
+        self.color_spaces.get(id.0 as usize).copied().flatten()
+    }
+
+    /// Repeating sampler of material textures, binding 1 of the bind group.
+    pub fn sampler(&self) -> &wgpu::Sampler {
+        &self.sampler
+    }
+
+    /// Textures in use, the default ones included.
+    pub fn count(&self) -> u32 {
+        (self.views.len() - self.free.len()) as u32
+    }
+
+    fn check_removable(&self, id: TextureId) -> Result<()> {
+        match self.ref_counts.get(id.0 as usize) {
+            None => bail!("Texture {} is not in the TexturePool", id.0),
+            Some(_) if id.0 <= LTC2_TEXTURE.0 => {
+                bail!("Texture {} is a default texture and can't be removed", id.0)
+            }
+            Some(0) => bail!("Texture {} was already removed", id.0),
+            Some(_) => Ok(()),
+        }
     }
 
     fn create_bind_group(
@@ -149,14 +300,25 @@ impl TexturePool {
         })
     }
 
-    pub fn update_bind_group(&mut self) {
-        self.bind_group = Self::create_bind_group(
-            &self.gpu,
-            &self.bind_group_layout,
-            &self.views,
-            &self.sampler,
-            &self.ltc_sampler,
-        )
+    pub fn update_bind_group(&mut self) -> Result<()> {
+        self.bind_group = self
+            .gpu
+            .error_scope(|| {
+                Self::create_bind_group(
+                    &self.gpu,
+                    &self.bind_group_layout,
+                    &self.views,
+                    &self.sampler,
+                    &self.ltc_sampler,
+                )
+            })
+            .wrap_err_with(|| {
+                format!(
+                    "while updating TexturePool bind group with {} textures",
+                    self.views.len()
+                )
+            })?;
+        Ok(())
     }
 }
 
@@ -1,5 +1,5 @@
 #import "shared.wgsl"
-#import "utils/math.wgsl"
+#import <voidin/math.wgsl>
 
 @group(0) @binding(0) var<uniform> un: Globals;
 @group(1) @binding(0)
@@ -0,0 +1,109 @@
+// G-buffer channels drawn in place of the post processed image, see `pass::postprocess::DebugView`.
+#import "shared.wgsl"
+#import <voidin/camera.wgsl>
+#import <voidin/random.wgsl>
+#import "utils/gbuffer.wgsl"
+
+// Mirrors the order of `DebugView`.
+const VIEW_ALBEDO = 1u;
+const VIEW_NORMALS = 2u;
+const VIEW_ROUGHNESS_METALLIC = 3u;
+const VIEW_DEPTH = 4u;
+const VIEW_MOTION_VECTORS = 5u;
+const VIEW_INSTANCE_IDS = 6u;
+const VIEW_OVERDRAW = 7u;
+
+@group(0) @binding(0) var<uniform> global: Globals;
+@group(0) @binding(1) var<uniform> camera: Camera;
+
+@group(1) @binding(0) var t_gbuffer: texture_2d<u32>;
+@group(1) @binding(1) var t_depth: texture_depth_2d;
+@group(1) @binding(2) var t_sampler: sampler;
+@group(1) @binding(4) var t_instance_ids: texture_2d<u32>;
+@group(1) @binding(5) var t_overdraw: texture_2d<f32>;
+
+@group(2) @binding(0) var texture_array: binding_array<texture_2d<f32>>;
+
+@group(3) @binding(0) var<storage, read> materials: array<Material>;
+
+struct DebugViewParams {
+    view: u32,
+}
+@group(4) @binding(0) var<uniform> params: DebugViewParams;
+
+struct VertexOutput {
+  @builtin(position) pos: vec4<f32>,
+  @location(0) uv: vec2<f32>,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_idx: u32) -> VertexOutput {
+    var out: VertexOutput;
+    out.uv = vec2<f32>(vec2((vertex_idx << 1u) & 2u, vertex_idx & 2u));
+    out.pos = vec4(2.0 * out.uv.x - 1.0, 1. - out.uv.y * 2., 0.0, 1.0);
+    return out;
+}
+
+// Blue through green to red over `t` in [0, 1].
+fn heatmap(t: f32) -> vec3<f32> {
+    let x = clamp(t, 0., 1.);
+    return clamp(vec3(2. * x - 0.5, 1.5 - abs(4. * x - 2.), 1.5 - 2. * x), vec3(0.), vec3(1.));
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let dims = textureDimensions(t_gbuffer);
+    let pix = vec2<u32>(in.uv * vec2<f32>(dims));
+
+    if params.view == VIEW_OVERDRAW {
+        // Saturates at 8 layers.
+        let count = textureLoad(t_overdraw, pix, 0).r;
+        return vec4(select(heatmap(count / 8.), vec3(0.), count == 0.), 1.);
+    }
+    if params.view == VIEW_INSTANCE_IDS {
+        let id = textureLoad(t_instance_ids, pix, 0).r;
+        let hash = pcg(id);
+        let color = vec3<f32>(vec3(hash, hash >> 8u, hash >> 16u) & vec3(0xffu)) / 255.;
+        return vec4(select(color, vec3(0.), id == 0u), 1.);
+    }
+
+    // Reversed depth is cleared to 0, nothing was rasterized there.
+    let depth = textureLoad(t_depth, pix, 0);
+    if depth == 0. {
+        return vec4(0., 0., 0., 1.);
+    }
+    let gbuffer = unpack_gbuffer(textureLoad(t_gbuffer, pix, 0).xy);
+    let material = materials[gbuffer.material_id];
+
+    var color = vec3(0.);
+    switch params.view {
+        case VIEW_ALBEDO: {
+            color = textureSampleLevel(texture_array[material.albedo], t_sampler, gbuffer.uv, 0.).rgb;
+        }
+        case VIEW_NORMALS: {
+            color = gbuffer.normal * 0.5 + 0.5;
+        }
+        case VIEW_ROUGHNESS_METALLIC: {
+            // R: occlusion, G: roughness, B: metallic, packed at import.
+            let orm = textureSampleLevel(texture_array[material.metallic_roughness], t_sampler, gbuffer.uv, 0.);
+            color = vec3(orm.g, orm.b, 0.);
+        }
+        case VIEW_DEPTH: {
+            // Infinite reversed projection, view distance is znear / depth.
+            let distance = camera.znear / depth;
+            color = vec3(1. - clamp(log2(1. + distance) / 10., 0., 1.));
+        }
+        case VIEW_MOTION_VECTORS: {
+            // Camera motion only, same as `reproject.wgsl` feeds to TAA.
+            let pos_ws = world_position_from_depth(in.uv, depth, camera.clip_to_world);
+            let prev_clip = camera.prev_world_to_clip * vec4(pos_ws, 1.);
+            let curr_ndc = ndc_from_uv_raw_depth(in.uv, depth).xy + camera.jitter;
+            let prev_ndc = prev_clip.xy / prev_clip.w + camera.prev_jitter;
+            // Pixels moved, full color at 16.
+            let motion = (curr_ndc - prev_ndc) * 0.5 * vec2<f32>(dims) / 16.;
+            color = vec3(clamp(0.5 + 0.5 * motion, vec2(0.), vec2(1.)), 0.5);
+        }
+        default: {}
+    }
+    return vec4(color, 1.);
+}
@@ -1,40 +1,181 @@
 #import "shared.wgsl"
-#import "utils/math.wgsl"
+#import <voidin/math.wgsl>
 
 @group(0) @binding(0)
 var<uniform> camera: Camera;
 @group(1) @binding(0)
 var<storage, read> meshes: array<MeshInfo>;
+@group(1) @binding(3)
+var<storage, read> lods: array<MeshLod>;
 @group(2) @binding(0)
 var<storage, read_write> instances: array<Instance>;
 @group(3) @binding(0)
 var<storage, read_write> cmd_buffer: array<DrawIndexedIndirect>;
+@group(4) @binding(0)
+var<storage, read> occluded: array<u32>;
+// Farthest depth of the previous frame, built by `pass::hiz::HiZ`.
+@group(5) @binding(0)
+var t_hiz: texture_2d<f32>;
 
-fn is_visible(mesh: MeshInfo, transform: mat4x4<f32>, scale: vec3<f32>) -> bool {
-    var center = (mesh.max + mesh.min) / 2.;
-    center = (camera.view * transform * vec4(center, 1.0)).xyz;
+struct ImposterSettings {
+    distance: f32,
+}
+
+// Instances swapped to imposters, drawn indirectly by `pass::imposter::ImposterBillboards`.
+struct ImposterDraws {
+    vertex_count: u32,
+    instance_count: atomic<u32>,
+    first_vertex: u32,
+    first_instance: u32,
+    instances: array<u32>,
+}
+
+@group(6) @binding(0)
+var<uniform> imposter_settings: ImposterSettings;
+@group(6) @binding(1)
+var<storage, read> mesh_imposters: array<u32>;
+@group(6) @binding(2)
+var<storage, read_write> imposter_draws: ImposterDraws;
+
+const NO_IMPOSTER = 0xffffffffu;
+
+// Mirrors `pools::MeshLod`.
+struct MeshLod {
+    base_index: u32,
+    index_count: u32,
+    error: f32,
+    junk: u32,
+}
+
+struct LodSettings {
+    // Zero draws every mesh whole.
+    pixel_error: f32,
+    viewport_height: f32,
+    junk: vec2<f32>,
+}
 
-    let abs_scale = abs(scale);
-    let max_scale = max(max(abs_scale.x, abs_scale.y), abs_scale.z);
-    let radius = max(distance(mesh.min, center), distance(mesh.max, center)) * max_scale;
+@group(7) @binding(0)
+var<uniform> lod_settings: LodSettings;
 
-    if center.z * camera.frustum.y - abs(center.x) * camera.frustum.x < -radius {
+fn is_occluded(index: u32) -> bool {
+    let word = index / 32u;
+    if word >= arrayLength(&occluded) {
         return false;
     }
-    if center.z * camera.frustum.w - abs(center.y) * camera.frustum.z < -radius {
-        return false;
+    return ((occluded[word] >> (index % 32u)) & 1u) != 0u;
+}
+
+fn max_scale(transform: mat4x4<f32>) -> f32 {
+    return max(length(transform[0].xyz), max(length(transform[1].xyz), length(transform[2].xyz)));
+}
+
+fn world_sphere(mesh: MeshInfo, transform: mat4x4<f32>) -> BoundingSphere {
+    let center = (transform * vec4(mesh.center, 1.0)).xyz;
+    return BoundingSphere(center, mesh.radius * max_scale(transform));
+}
+
+// Coarsest level whose error, projected from the closest point of the bounding sphere,
+// stays within the pixel budget.
+fn select_lod(mesh: MeshInfo, transform: mat4x4<f32>, cmd: ptr<function, DrawIndexedIndirect>) {
+    if lod_settings.pixel_error <= 0. {
+        return;
+    }
+    let sphere = world_sphere(mesh, transform);
+    let dist = max(distance(camera.position.xyz, sphere.center) - sphere.radius, camera.znear);
+    let pixels_per_unit = 0.5 * lod_settings.viewport_height * camera.proj[1][1] / dist;
+    let scale = max_scale(transform);
+    for (var i = 0u; i < mesh.lod_count; i++) {
+        let lod = lods[mesh.lod_index + i];
+        if lod.error * scale * pixels_per_unit > lod_settings.pixel_error {
+            break;
+        }
+        (*cmd).vertex_count = lod.index_count;
+        (*cmd).base_index = mesh.base_index + lod.base_index;
     }
+}
 
-    if center.z + radius > camera.znear && center.z - radius > camera.zfar {
-        return false;
+fn is_visible(mesh: MeshInfo, transform: mat4x4<f32>) -> bool {
+    // The sphere settles most instances, the far plane of the infinite projection is skipped.
+    let sphere = world_sphere(mesh, transform);
+    var inside = true;
+    for (var i = 0u; i < 5u; i++) {
+        let plane = camera.frustum_planes[i];
+        let dist = dot(plane.xyz, sphere.center) + plane.w;
+        if dist < -sphere.radius {
+            return false;
+        }
+        inside = inside && dist >= sphere.radius;
+    }
+    if inside {
+        return true;
+    }
+
+    // Straddles a plane, the transformed box is tighter for elongated meshes.
+    let center = (transform * vec4((mesh.max + mesh.min) / 2., 1.0)).xyz;
+    let half_size = (mesh.max - mesh.min) / 2.;
+    // Half size of the world space box enclosing the transformed one.
+    let extent = abs(transform[0].xyz) * half_size.x
+        + abs(transform[1].xyz) * half_size.y
+        + abs(transform[2].xyz) * half_size.z;
+
+    for (var i = 0u; i < 6u; i++) {
+        let plane = camera.frustum_planes[i];
+        if dot(plane.xyz, center) + plane.w + dot(abs(plane.xyz), extent) < 0. {
+            return false;
+        }
     }
 
     return true;
 }
 
-@compute
-@workgroup_size(64, 1, 1)
-fn emit_draws(@builtin(global_invocation_id) global_id: vec3<u32>) {
+// Tests the box against the previous frame's depth, projected with the camera it was
+// rendered from. Depth is reversed, so the box is hidden when its closest corner is
+// smaller than the farthest depth of every texel it covers.
+fn is_hiz_occluded(mesh: MeshInfo, transform: mat4x4<f32>) -> bool {
+    let world_to_clip = camera.prev_world_to_clip * transform;
+    var uv_min = vec2(1.);
+    var uv_max = vec2(0.);
+    var closest = 0.;
+    for (var i = 0u; i < 8u; i++) {
+        let pick_max = vec3((i & 1u) != 0u, (i & 2u) != 0u, (i & 4u) != 0u);
+        let corner = select(mesh.min, mesh.max, pick_max);
+        let clip = world_to_clip * vec4(corner, 1.0);
+        // Crosses the near plane, the projected box is unbounded.
+        if clip.w <= camera.znear {
+            return false;
+        }
+        let ndc = clip.xyz / clip.w;
+        let uv = ndc.xy * vec2(0.5, -0.5) + 0.5;
+        uv_min = min(uv_min, uv);
+        uv_max = max(uv_max, uv);
+        closest = max(closest, ndc.z);
+    }
+    uv_min = saturate(uv_min);
+    uv_max = saturate(uv_max);
+
+    // Lowest level where the box covers at most 2x2 texels.
+    let base_dims = vec2<f32>(textureDimensions(t_hiz, 0));
+    let size = (uv_max - uv_min) * base_dims;
+    let level_count = i32(textureNumLevels(t_hiz));
+    let level = clamp(i32(ceil(log2(max(max(size.x, size.y), 1.)))), 0, level_count - 1);
+    // Odd mip sizes fold the leftover texels into the last one, so clamping stays conservative.
+    let dims = vec2<i32>(textureDimensions(t_hiz, level));
+    let shift = vec2(u32(level));
+    let lo = min(vec2<i32>(uv_min * base_dims) >> shift, dims - 1);
+    let hi = min(vec2<i32>(uv_max * base_dims) >> shift, dims - 1);
+
+    let farthest = min(
+        min(textureLoad(t_hiz, lo, level).r, textureLoad(t_hiz, vec2(hi.x, lo.y), level).r),
+        min(textureLoad(t_hiz, vec2(lo.x, hi.y), level).r, textureLoad(t_hiz, hi, level).r),
+    );
+    return closest < farthest;
+}
+
+fn has_imposter(mesh_id: u32) -> bool {
+    return mesh_id < arrayLength(&mesh_imposters) && mesh_imposters[mesh_id] != NO_IMPOSTER;
+}
+
+fn emit(global_id: vec3<u32>, hiz_culling: bool) {
     let index = global_id.x;
     let len = arrayLength(&instances);
     if index >= len {
@@ -45,13 +186,26 @@ fn emit_draws(@builtin(global_invocation_id) global_id: vec3<u32>) {
     let transform = instance.transform;
     let mesh_info = meshes[instance.mesh_id];
 
-    let scale = extract_scale(transform);
-
     var instance_count = 1u;
-    if !is_visible(mesh_info, transform, scale) {
+    let culled = (instance.flags & INSTANCE_INACTIVE) != 0u || is_occluded(index);
+    if culled || !is_visible(mesh_info, transform) {
+        instance_count = 0u;
+    } else if hiz_culling && is_hiz_occluded(mesh_info, transform) {
         instance_count = 0u;
     }
 
+    if instance_count != 0u && has_imposter(instance.mesh_id) {
+        // Measured in bounding radii, so the swap happens at the same screen size.
+        let sphere = world_sphere(mesh_info, transform);
+        if distance(camera.position.xyz, sphere.center) > imposter_settings.distance * sphere.radius {
+            instance_count = 0u;
+            let slot = atomicAdd(&imposter_draws.instance_count, 1u);
+            if slot < arrayLength(&imposter_draws.instances) {
+                imposter_draws.instances[slot] = index;
+            }
+        }
+    }
+
     var cmd: DrawIndexedIndirect;
 
     cmd.vertex_count = mesh_info.index_count;
@@ -59,6 +213,21 @@ fn emit_draws(@builtin(global_invocation_id) global_id: vec3<u32>) {
     cmd.base_index = mesh_info.base_index;
     cmd.vertex_offset = mesh_info.vertex_offset;
     cmd.base_instance = global_id.x;
+    if instance_count != 0u {
+        select_lod(mesh_info, transform, &cmd);
+    }
 
     cmd_buffer[global_id.x] = cmd;
 }
+
+@compute
+@workgroup_size(64, 1, 1)
+fn emit_draws(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    emit(global_id, false);
+}
+
+@compute
+@workgroup_size(64, 1, 1)
+fn emit_draws_hiz(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    emit(global_id, true);
+}
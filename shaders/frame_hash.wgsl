@@ -0,0 +1,39 @@
+// [xor of texel hashes, wrapping sum of texel hashes]
+@group(0) @binding(0) var<storage, read_write> hash: array<atomic<u32>>;
+@group(1) @binding(0) var t_frame: texture_2d<f32>;
+
+var<workgroup> group_xor: atomic<u32>;
+var<workgroup> group_sum: atomic<u32>;
+
+fn pcg(v: u32) -> u32 {
+    let state = v * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+// Both reductions are order independent, the position is mixed in so equal texels
+// in different places don't cancel out.
+@compute
+@workgroup_size(8, 8, 1)
+fn cs_main(
+    @builtin(global_invocation_id) global_id: vec3<u32>,
+    @builtin(local_invocation_index) local_index: u32,
+) {
+    let dims = textureDimensions(t_frame);
+    if all(global_id.xy < dims) {
+        let texel = bitcast<vec4<u32>>(textureLoad(t_frame, global_id.xy, 0));
+        var h = pcg(global_id.y * dims.x + global_id.x);
+        h = pcg(h ^ texel.x);
+        h = pcg(h ^ texel.y);
+        h = pcg(h ^ texel.z);
+        h = pcg(h ^ texel.w);
+        atomicXor(&group_xor, h);
+        atomicAdd(&group_sum, pcg(h));
+    }
+    workgroupBarrier();
+
+    if local_index == 0u {
+        atomicXor(&hash[0], atomicLoad(&group_xor));
+        atomicAdd(&hash[1], atomicLoad(&group_sum));
+    }
+}
@@ -0,0 +1,53 @@
+// Builds the closest-depth pyramid read by `utils/hiz.wgsl`,
+// or the farthest-depth one used by occlusion culling in `emit_draws.wgsl`.
+// Depth is reversed, so the closest surface of a cell is its max.
+
+@group(0) @binding(0) var t_out: texture_storage_2d<r32float, write>;
+
+@group(1) @binding(0) var t_src: texture_2d<f32>;
+@group(1) @binding(1) var t_depth: texture_depth_2d;
+
+@compute
+@workgroup_size(8, 8, 1)
+fn copy_depth(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    if any(global_id.xy >= textureDimensions(t_out)) {
+        return;
+    }
+    let pix = vec2<i32>(global_id.xy);
+    textureStore(t_out, pix, vec4(textureLoad(t_depth, pix, 0)));
+}
+
+fn reduce_depth(global_id: vec3<u32>, farthest: bool) {
+    let dims = vec2<i32>(textureDimensions(t_out));
+    let pix = vec2<i32>(global_id.xy);
+    if any(pix >= dims) {
+        return;
+    }
+
+    // Odd source sizes fold the leftover row and column into the last texel.
+    let src_dims = vec2<i32>(textureDimensions(t_src));
+    let odd = ((src_dims & vec2(1)) == vec2(1)) & (pix == dims - 1);
+    let extent = select(vec2(1), vec2(2), odd);
+
+    var depth = select(0.0, 1.0, farthest);
+    for (var y = 0; y <= extent.y; y += 1) {
+        for (var x = 0; x <= extent.x; x += 1) {
+            let src = min(pix * 2 + vec2(x, y), src_dims - 1);
+            let texel = textureLoad(t_src, src, 0).r;
+            depth = select(max(depth, texel), min(depth, texel), farthest);
+        }
+    }
+    textureStore(t_out, pix, vec4(depth));
+}
+
+@compute
+@workgroup_size(8, 8, 1)
+fn downsample(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    reduce_depth(global_id, false);
+}
+
+@compute
+@workgroup_size(8, 8, 1)
+fn downsample_farthest(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    reduce_depth(global_id, true);
+}
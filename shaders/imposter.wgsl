@@ -0,0 +1,85 @@
+#import "shared.wgsl"
+#import <voidin/math.wgsl>
+#import "utils/gbuffer.wgsl"
+#import "utils/imposter.wgsl"
+
+struct ImposterDraws {
+    vertex_count: u32,
+    instance_count: u32,
+    first_vertex: u32,
+    first_instance: u32,
+    instances: array<u32>,
+}
+
+@group(0) @binding(0) var<uniform> camera: Camera;
+@group(1) @binding(0) var texture_array: binding_array<texture_2d<f32>>;
+@group(1) @binding(1) var tex_sampler: sampler;
+@group(1) @binding(2) var tex_int_sampler: sampler;
+@group(2) @binding(0) var<storage, read_write> instances: array<Instance>;
+@group(3) @binding(0) var<storage, read> mesh_imposters: array<u32>;
+@group(3) @binding(1) var<storage, read> imposters: array<ImposterInfo>;
+@group(3) @binding(2) var<storage, read> draws: ImposterDraws;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) @interpolate(flat) instance_index: u32,
+}
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) vertex_index: u32,
+    @builtin(instance_index) slot: u32,
+) -> VertexOutput {
+    var out: VertexOutput;
+    if slot >= arrayLength(&draws.instances) {
+        return out;
+    }
+
+    let instance_index = draws.instances[slot];
+    let instance = instances[instance_index];
+    let info = imposters[mesh_imposters[instance.mesh_id]];
+
+    // The quad faces the camera of the closest baked view, so the cell maps onto it exactly.
+    let eye = (instance.inv_transform * vec4(camera.position.xyz, 1.0)).xyz;
+    let cell = imposter_frame(normalize(eye - info.center), info.frames);
+    let basis = imposter_basis(imposter_frame_dir(cell, info.frames));
+
+    var corners = array<vec2<f32>, 6>(
+        vec2(-1., -1.), vec2(1., -1.), vec2(-1., 1.),
+        vec2(-1., 1.), vec2(1., -1.), vec2(1., 1.),
+    );
+    let corner = corners[vertex_index];
+    let local_pos = info.center + (basis[0] * corner.x + basis[1] * corner.y) * info.radius;
+    let world_pos = instance.transform * vec4(local_pos, 1.0);
+
+    out.clip_position = camera.proj * camera.view * world_pos;
+    out.uv = imposter_atlas_uv(cell, info.frames, corner);
+    out.instance_index = instance_index;
+    return out;
+}
+
+struct FragmentOutput {
+    @location(0) gbuffer: vec2<u32>,
+    @location(1) history_reject: u32,
+    @location(2) instance_id: u32,
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> FragmentOutput {
+    let instance = instances[in.instance_index];
+    let info = imposters[mesh_imposters[instance.mesh_id]];
+
+    let albedo = textureSampleBias(texture_array[info.albedo], tex_sampler, in.uv, camera.mip_bias);
+    if albedo.a < 0.5 {
+        discard;
+    }
+    let normal_tex = textureSampleBias(texture_array[info.normal], tex_sampler, in.uv, camera.mip_bias);
+    let normal = normalize(mat4_to_mat3(instance.transform) * (normal_tex.rgb * 2.0 - 1.0));
+
+    return FragmentOutput(
+        pack_gbuffer(normal, in.uv, info.material_id),
+        instance.flags & INSTANCE_CHANGED,
+        in.instance_index + 1u,
+    );
+}
@@ -0,0 +1,81 @@
+#import "shared.wgsl"
+
+struct ImposterBake {
+    material_id: u32,
+}
+
+@group(0) @binding(0) var<uniform> bake: ImposterBake;
+// A view per atlas cell, see `Imposters::cell_view`.
+@group(0) @binding(2) var<uniform> views: array<Camera, MAX_VIEWS>;
+@group(1) @binding(0) var texture_array: binding_array<texture_2d<f32>>;
+@group(1) @binding(1) var tex_sampler: sampler;
+@group(1) @binding(2) var tex_int_sampler: sampler;
+@group(2) @binding(0) var<storage, read> materials: array<Material>;
+
+struct VertexInput {
+    // One instance per atlas cell, a batch of `MAX_VIEWS` cells per draw.
+    @builtin(instance_index) frame: u32,
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) tangent: vec4<f32>,
+    @location(3) tex_coords: vec2<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) normal: vec3<f32>,
+    @location(1) tangent: vec3<f32>,
+    @location(2) bitangent: vec3<f32>,
+    @location(3) uv: vec2<f32>,
+}
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    let camera = views[in.frame % MAX_VIEWS];
+
+    var out: VertexOutput;
+    out.clip_position = camera.proj * camera.view * vec4(in.position, 1.0);
+    out.normal = in.normal;
+    out.tangent = in.tangent.xyz;
+    out.bitangent = cross(in.normal, in.tangent.xyz) * in.tangent.w;
+    out.uv = in.tex_coords;
+    return out;
+}
+
+struct FragmentOutput {
+    @location(0) albedo: vec4<f32>,
+    @location(1) normal: vec4<f32>,
+    @location(2) orm: vec4<f32>,
+    @location(3) emissive: vec4<f32>,
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> FragmentOutput {
+    let material = materials[bake.material_id];
+    let albedo = textureSample(texture_array[material.albedo], tex_sampler, in.uv);
+    if material.base_color.w < 0.5 || albedo.a < 0.5 {
+        discard;
+    }
+
+    var normal = normalize(in.normal);
+    if material.normal != 0u {
+        let tbn = mat3x3(normalize(in.tangent), normalize(in.bitangent), normal);
+        let normal_tex = textureSample(texture_array[material.normal], tex_sampler, in.uv);
+        normal = normalize(tbn * (normal_tex.rgb * 2.0 - 1.0));
+    }
+
+    var orm = textureSample(texture_array[material.metallic_roughness], tex_sampler, in.uv);
+    // The imposter material always has an occlusion map, so the missing one is baked as 1.
+    if material.metallic_roughness == BLACK_TEXTURE {
+        orm.x = 1.;
+    }
+    let emissive = textureSample(texture_array[material.emissive], tex_sampler, in.uv);
+
+    var out: FragmentOutput;
+    out.albedo = vec4(albedo.rgb * material.base_color.rgb, 1.);
+    // Object space, rotated by the instance transform when drawn.
+    out.normal = vec4(normal * 0.5 + 0.5, 1.);
+    out.orm = vec4(orm.xyz, 1.);
+    out.emissive = vec4(emissive.rgb, 1.);
+    return out;
+}
@@ -0,0 +1,76 @@
+#import "shared.wgsl"
+
+// Mirrors `InstanceMotion` of the `InstancePool`, `vec4`s hold the `Vec3A`s.
+struct InstanceMotion {
+    prev_rotation: vec4<f32>,
+    rotation: vec4<f32>,
+    prev_translation: vec4<f32>,
+    translation: vec4<f32>,
+    prev_scale: vec4<f32>,
+    scale: vec4<f32>,
+    index: u32,
+    junk: array<u32, 3>,
+}
+
+@group(0) @binding(0) var<uniform> un: Globals;
+@group(1) @binding(0)
+var<storage, read_write> instances: array<Instance>;
+@group(2) @binding(0)
+var<storage, read> motions: array<InstanceMotion>;
+
+fn quat_to_mat3(q: vec4<f32>) -> mat3x3<f32> {
+    let x2 = q.x + q.x;
+    let y2 = q.y + q.y;
+    let z2 = q.z + q.z;
+    let xx = q.x * x2;
+    let xy = q.x * y2;
+    let xz = q.x * z2;
+    let yy = q.y * y2;
+    let yz = q.y * z2;
+    let zz = q.z * z2;
+    let wx = q.w * x2;
+    let wy = q.w * y2;
+    let wz = q.w * z2;
+    return mat3x3(
+        vec3(1.0 - (yy + zz), xy + wz, xz - wy),
+        vec3(xy - wz, 1.0 - (xx + zz), yz + wx),
+        vec3(xz + wy, yz - wx, 1.0 - (xx + yy)),
+    );
+}
+
+@compute
+@workgroup_size(64, 1, 1)
+fn interpolate(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    if global_id.x >= arrayLength(&motions) {
+        return;
+    }
+    let motion = motions[global_id.x];
+    let t = un.alpha;
+
+    // Steps are short, normalized lerp is as good as slerp there.
+    let rotation = quat_to_mat3(normalize(mix(motion.prev_rotation, motion.rotation, t)));
+    let translation = mix(motion.prev_translation.xyz, motion.translation.xyz, t);
+    let scale = mix(motion.prev_scale.xyz, motion.scale.xyz, t);
+
+    let inv_scale = 1.0 / scale;
+    let inv_rotation = transpose(rotation);
+    let inv_linear = mat3x3(
+        inv_rotation[0] * inv_scale,
+        inv_rotation[1] * inv_scale,
+        inv_rotation[2] * inv_scale,
+    );
+
+    let instance = &instances[motion.index];
+    (*instance).transform = mat4x4(
+        vec4(rotation[0] * scale.x, 0.0),
+        vec4(rotation[1] * scale.y, 0.0),
+        vec4(rotation[2] * scale.z, 0.0),
+        vec4(translation, 1.0),
+    );
+    (*instance).inv_transform = mat4x4(
+        vec4(inv_linear[0], 0.0),
+        vec4(inv_linear[1], 0.0),
+        vec4(inv_linear[2], 0.0),
+        vec4(-(inv_linear * translation), 1.0),
+    );
+}
@@ -0,0 +1,66 @@
+#import "shared.wgsl"
+#import <voidin/camera.wgsl>
+#import "utils/clusters.wgsl"
+
+@group(0) @binding(0) var<uniform> global: Globals;
+@group(0) @binding(1) var<uniform> camera: Camera;
+
+@group(1) @binding(0) var<storage, read> point_lights: array<Light>;
+@group(1) @binding(1) var<storage, read_write> cluster_counts: array<u32>;
+@group(1) @binding(2) var<storage, read_write> cluster_lights: array<u32>;
+
+// View space point seen through `uv` at view `depth`, jitter included.
+fn view_point(uv: vec2<f32>, depth: f32) -> vec3<f32> {
+    let cs = uv_to_cs(uv);
+    let xy = (cs + vec2(camera.proj[2][0], camera.proj[2][1])) / vec2(camera.proj[0][0], camera.proj[1][1]);
+    return vec3(xy * depth, -depth);
+}
+
+// One invocation per cluster, testing every light against the bounds of its froxel.
+@compute @workgroup_size(64, 1, 1)
+fn cull(@builtin(global_invocation_id) id: vec3<u32>) {
+    let cluster = id.x;
+    if cluster >= CLUSTER_GRID.x * CLUSTER_GRID.y * CLUSTER_GRID.z {
+        return;
+    }
+    let tile = vec2(cluster % CLUSTER_GRID.x, (cluster / CLUSTER_GRID.x) % CLUSTER_GRID.y);
+    let slice = cluster / (CLUSTER_GRID.x * CLUSTER_GRID.y);
+
+    let near = cluster_slice_depth(slice);
+    var far = 1e30;
+    if slice + 1u < CLUSTER_GRID.z {
+        far = cluster_slice_depth(slice + 1u);
+    }
+    let uv_min = vec2<f32>(tile) / vec2<f32>(CLUSTER_GRID.xy);
+    let uv_max = vec2<f32>(tile + 1u) / vec2<f32>(CLUSTER_GRID.xy);
+    var aabb_min = vec3(1e30);
+    var aabb_max = vec3(-1e30);
+    for (var i = 0u; i < 8u; i += 1u) {
+        let uv = select(uv_min, uv_max, vec2((i & 1u) != 0u, (i & 2u) != 0u));
+        let p = view_point(uv, select(near, far, (i & 4u) != 0u));
+        aabb_min = min(aabb_min, p);
+        aabb_max = max(aabb_max, p);
+    }
+
+    var count = 0u;
+    let light_count = arrayLength(&point_lights);
+    for (var i = 0u; i < light_count; i += 1u) {
+        let light = point_lights[i];
+        if light.radius <= 0. || (light.flags & LIGHT_DISABLED) != 0u {
+            continue;
+        }
+        let center = (camera.view * vec4(light.position, 1.)).xyz;
+        let closest = clamp(center, aabb_min, aabb_max);
+        let offset = center - closest;
+        if dot(offset, offset) > light.radius * light.radius {
+            continue;
+        }
+        // Lights past the capacity are dropped, the grid has to be fine enough.
+        if count == MAX_LIGHTS_PER_CLUSTER {
+            break;
+        }
+        cluster_lights[cluster * MAX_LIGHTS_PER_CLUSTER + count] = i;
+        count += 1u;
+    }
+    cluster_counts[cluster] = count;
+}
@@ -0,0 +1,90 @@
+// Second culling level. Instances that survived `emit_draws.wgsl` get a draw per meshlet
+// that is inside the frustum and not facing away from the camera.
+#import "shared.wgsl"
+#import <voidin/math.wgsl>
+
+// Mirrors `pools::Meshlet`.
+struct Meshlet {
+    center: vec3<f32>,
+    radius: f32,
+    cone_axis: vec3<f32>,
+    cone_cutoff: f32,
+    base_index: u32,
+    index_count: u32,
+    junk: array<u32, 2>,
+}
+
+@group(0) @binding(0) var<uniform> camera: Camera;
+@group(1) @binding(0) var<storage, read> meshes: array<MeshInfo>;
+@group(1) @binding(2) var<storage, read> meshlets: array<Meshlet>;
+@group(2) @binding(0) var<storage, read_write> instances: array<Instance>;
+
+// One draw per instance, written by `emit_draws`.
+@group(3) @binding(0) var<storage, read> instance_draws: array<DrawIndexedIndirect>;
+@group(3) @binding(1) var<storage, read_write> draws: array<DrawIndexedIndirect>;
+@group(3) @binding(2) var<storage, read_write> draw_count: atomic<u32>;
+
+fn emit(instance_index: u32, base_index: u32, index_count: u32, vertex_offset: i32) {
+    let slot = atomicAdd(&draw_count, 1u);
+    if slot < arrayLength(&draws) {
+        draws[slot] = DrawIndexedIndirect(index_count, 1u, base_index, vertex_offset, instance_index);
+    }
+}
+
+// The cone test is meshoptimizer's `meshopt_computeMeshletBounds` one, done in world space.
+fn is_visible(meshlet: Meshlet, instance: Instance, scale: f32, mirrored: bool) -> bool {
+    let center = (instance.transform * vec4(meshlet.center, 1.0)).xyz;
+    let radius = meshlet.radius * scale;
+    for (var i = 0u; i < 5u; i++) {
+        let plane = camera.frustum_planes[i];
+        if dot(plane.xyz, center) + plane.w < -radius {
+            return false;
+        }
+    }
+
+    // Mirroring flips the winding the cone was built from.
+    if mirrored || meshlet.cone_cutoff >= 1. {
+        return true;
+    }
+    let axis = normalize(transpose(mat4_to_mat3(instance.inv_transform)) * meshlet.cone_axis);
+    let to_center = center - camera.position.xyz;
+    return dot(to_center, axis) < meshlet.cone_cutoff * length(to_center) + radius;
+}
+
+// One workgroup per instance, spread over y when there are more than fit in x.
+@compute
+@workgroup_size(64, 1, 1)
+fn cull(
+    @builtin(workgroup_id) workgroup: vec3<u32>,
+    @builtin(num_workgroups) workgroups: vec3<u32>,
+    @builtin(local_invocation_index) local_index: u32,
+) {
+    let index = workgroup.y * workgroups.x + workgroup.x;
+    if index >= arrayLength(&instance_draws) {
+        return;
+    }
+    let draw = instance_draws[index];
+    if draw.instance_count == 0u {
+        return;
+    }
+
+    let instance = instances[index];
+    let mesh = meshes[instance.mesh_id];
+    // Meshlets only cover the full mesh, coarser levels picked by `emit_draws` go as they are.
+    if mesh.meshlet_count == 0u || draw.base_index != mesh.base_index {
+        if local_index == 0u {
+            emit(index, draw.base_index, draw.vertex_count, mesh.vertex_offset);
+        }
+        return;
+    }
+
+    let transform = mat4_to_mat3(instance.transform);
+    let scale = max(length(transform[0]), max(length(transform[1]), length(transform[2])));
+    let mirrored = determinant(transform) < 0.;
+    for (var i = local_index; i < mesh.meshlet_count; i += 64u) {
+        let meshlet = meshlets[mesh.meshlet_index + i];
+        if is_visible(meshlet, instance, scale, mirrored) {
+            emit(index, mesh.base_index + meshlet.base_index, meshlet.index_count, mesh.vertex_offset);
+        }
+    }
+}
@@ -0,0 +1,336 @@
+#import "shared.wgsl"
+#import <voidin/math.wgsl>
+#import <voidin/brdf.wgsl>
+#import <voidin/random.wgsl>
+#import <voidin/bvh.wgsl>
+#import "utils/triangle_materials.wgsl"
+#import "utils/ies.wgsl"
+
+struct PathTraceUniform {
+    clip_to_world: mat4x4<f32>,
+    position: vec4<f32>,
+    // Samples already in the history, 0 discards it.
+    samples: u32,
+    max_samples: u32,
+    max_bounces: u32,
+    junk: u32,
+}
+
+@group(0) @binding(0) var t_history: texture_2d<f32>;
+@group(0) @binding(1) var<uniform> params: PathTraceUniform;
+
+@group(1) @binding(0) var texture_array: binding_array<texture_2d<f32>>;
+@group(1) @binding(1) var tex_sampler: sampler;
+
+@group(2) @binding(0) var<storage, read> materials: array<Material>;
+
+@group(3) @binding(0) var<storage, read> point_lights: array<Light>;
+
+@group(4) @binding(0) var<storage, read> tlas_nodes: array<TlasNode>;
+@group(4) @binding(1) var<storage, read> instances: array<Instance>;
+@group(4) @binding(2) var<storage, read> meshes: array<MeshInfo>;
+@group(4) @binding(3) var<storage, read> bvh_nodes: array<BvhNode>;
+@group(4) @binding(4) var<storage, read> vertices: array<f32>;
+@group(4) @binding(5) var<storage, read> indices: array<u32>;
+
+@group(5) @binding(3) var<storage, read> normals: array<f32>;
+@group(5) @binding(5) var<storage, read> tex_coords: array<vec2<f32>>;
+@group(5) @binding(6) var<storage, read> triangle_materials: array<u32>;
+
+@group(6) @binding(0) var<uniform> sun: DirectionalLight;
+
+@group(7) @binding(0) var<storage, read> area_lights: array<AreaLight>;
+
+// Moves ray origins off the surface they start on.
+const RAY_OFFSET: f32 = 0.001;
+
+// Shadow rays per area light go from one up to this many, by the solid angle it covers.
+const MAX_AREA_SAMPLES: u32 = 4u;
+const AREA_SAMPLES_PER_SR: f32 = 4.;
+
+var<private> rng_state: u32;
+// Fixed per pixel across samples, unlike `rng_state`.
+var<private> pixel_hash: u32;
+
+fn rand() -> f32 {
+    rng_state = pcg(rng_state);
+    return f32(rng_state >> 8u) / 16777216.;
+}
+
+fn rand2() -> vec2<f32> {
+    return vec2(rand(), rand());
+}
+
+// Point `index` of the R2 sequence (Roberts 2018), in fixed point so long runs keep their
+// precision. Shifted by `shift`, so pixels decorrelate while each one stays stratified over
+// the samples it accumulates.
+fn sequence(index: u32, shift: u32) -> vec2<f32> {
+    let x = vec2(index * 3242174889u + pcg(shift), index * 2447445413u + pcg(shift ^ 0x68bc21ebu));
+    return vec2<f32>(x >> vec2(8u)) / 16777216.;
+}
+
+// Columns are a tangent, a bitangent and `n` (Duff et al. 2017).
+fn tangent_frame(n: vec3<f32>) -> mat3x3<f32> {
+    let s = select(-1., 1., n.z >= 0.);
+    let a = -1. / (s + n.z);
+    let b = n.x * n.y * a;
+    return mat3x3(
+        vec3(1. + s * n.x * n.x * a, s * b, -s * n.x),
+        vec3(b, s + n.y * n.y * a, -n.y),
+        n,
+    );
+}
+
+fn sqr(x: f32) -> f32 {
+    return x * x;
+}
+
+// Same falloff as the shading pass, so both light the scene alike.
+fn attenuation(max_intensity: f32, falloff: f32, dist: f32, radius: f32) -> f32 {
+    var s = dist / radius;
+    if s >= 1.0 {
+        return 0.;
+    }
+    let s2 = sqr(s);
+    return max_intensity * sqr(1. - s2) / (1. + falloff * s2);
+}
+
+struct Surface {
+    position: vec3<f32>,
+    // Faces the incoming ray.
+    geometric_normal: vec3<f32>,
+    normal: vec3<f32>,
+    material_id: u32,
+    albedo: vec3<f32>,
+    emissive: vec3<f32>,
+    roughness: f32,
+    metallic: f32,
+}
+
+fn surface(ray: Ray, res: TraceResult) -> Surface {
+    let instance = instances[res.instance];
+    let mesh = meshes[instance.mesh_id];
+    let position = ray.eye + ray.dir * res.dist;
+
+    // Barycentrics of the object space hit.
+    let p = (instance.inv_transform * vec4(position, 1.)).xyz;
+    let e1 = res.v1 - res.v0;
+    let e2 = res.v2 - res.v0;
+    let ep = p - res.v0;
+    let d11 = dot(e1, e1);
+    let d12 = dot(e1, e2);
+    let d22 = dot(e2, e2);
+    let inv_det = 1. / max(d11 * d22 - d12 * d12, 1e-20);
+    let b1 = (d22 * dot(ep, e1) - d12 * dot(ep, e2)) * inv_det;
+    let b2 = (d11 * dot(ep, e2) - d12 * dot(ep, e1)) * inv_det;
+    let bary = vec3(1. - b1 - b2, b1, b2);
+
+    var normal = vec3(0.);
+    var uv = vec2(0.);
+    for (var k = 0u; k < 3u; k += 1u) {
+        let i = u32(mesh.vertex_offset) + indices[mesh.base_index + 3u * res.triangle + k];
+        normal += bary[k] * vec3(normals[3u * i + 0u], normals[3u * i + 1u], normals[3u * i + 2u]);
+        uv += bary[k] * tex_coords[i];
+    }
+
+    // Normals go to world space with the inverse transpose.
+    var geometric_normal = normalize((vec4(cross(e1, e2), 0.) * instance.inv_transform).xyz);
+    if dot(geometric_normal, ray.dir) > 0. {
+        geometric_normal = -geometric_normal;
+    }
+    normal = normalize((vec4(normal, 0.) * instance.inv_transform).xyz);
+    if dot(normal, geometric_normal) < 0. {
+        normal = -normal;
+    }
+
+    let material_id = vertex_material(instance, 3u * res.triangle);
+    let material = materials[material_id];
+    let albedo = textureSampleLevel(texture_array[material.albedo], tex_sampler, uv, 0.).rgb;
+    let emissive = textureSampleLevel(texture_array[material.emissive], tex_sampler, uv, 0.).rgb * material.emissive_strength;
+    // R: occlusion, G: roughness, B: metallic, packed at import.
+    let orm = textureSampleLevel(texture_array[material.metallic_roughness], tex_sampler, uv, 0.);
+
+    return Surface(position, geometric_normal, normal, material_id, albedo, emissive, orm.y, orm.z);
+}
+
+// Lights are not occluded by light geometry, point lights sit inside their spheres.
+fn visible(eye: vec3<f32>, dir: vec3<f32>, dist: f32) -> bool {
+    let res = traverse_tlas(ray_new(eye, dir));
+    if !res.hit || res.dist >= dist {
+        return true;
+    }
+    return vertex_material(instances[res.instance], 3u * res.triangle) == LIGHT_MATERIAL;
+}
+
+fn eval_brdf(input: BrdfInput, n: vec3<f32>, v: vec3<f32>, l: vec3<f32>) -> vec3<f32> {
+    let h = normalize(v + l);
+    let n_dot_v = max(dot(n, v), EPS);
+    let n_dot_l = max(dot(n, l), EPS);
+    let n_dot_h = max(dot(n, h), 0.);
+    let v_dot_h = max(dot(v, h), 0.);
+    return brdf_specular(input, n_dot_v, n_dot_l, n_dot_h, v_dot_h) + brdf_diffuse(input, v_dot_h);
+}
+
+// Point lights and the sun, one point light picked at random.
+fn direct_light(hit: Surface, input: BrdfInput, v: vec3<f32>) -> vec3<f32> {
+    let eye = hit.position + hit.geometric_normal * RAY_OFFSET;
+    var color = vec3(0.);
+
+    let light_count = arrayLength(&point_lights);
+    if light_count > 0u {
+        let light = point_lights[min(u32(rand() * f32(light_count)), light_count - 1u)];
+        let light_vec = light.position - hit.position;
+        let dist = length(light_vec);
+        let l = light_vec / dist;
+        let n_dot_l = dot(hit.normal, l);
+        let enabled = (light.flags & LIGHT_DISABLED) == 0u;
+        if enabled && dist < light.radius && n_dot_l > 0. && visible(eye, l, dist) {
+            let profile = textureSampleLevel(texture_array[light.ies], tex_sampler, ies_uv(-l, vec3(0., -1., 0.)), 0.).r;
+            let atten = attenuation(1., 1., dist, light.radius) * profile;
+            color += light.color * atten * eval_brdf(input, hit.normal, v, l) * n_dot_l * f32(light_count);
+        }
+    }
+
+    let n_dot_l = dot(hit.normal, sun.direction);
+    if sun.intensity > 0. && n_dot_l > 0. && visible(eye, sun.direction, MAX_DIST) {
+        color += sun.color * sun.intensity * eval_brdf(input, hit.normal, v, sun.direction) * n_dot_l;
+    }
+    return color;
+}
+
+// Every area light through shadow rays to points spread over it by `sequence`, so soft
+// shadows converge with the accumulation. Both faces emit, like in the shading pass.
+fn area_light(hit: Surface, input: BrdfInput, v: vec3<f32>, bounce: u32) -> vec3<f32> {
+    let eye = hit.position + hit.geometric_normal * RAY_OFFSET;
+    var color = vec3(0.);
+    for (var i = 0u; i < arrayLength(&area_lights); i += 1u) {
+        let light = area_lights[i];
+        let normal = cross(light.points[1] - light.points[0], light.points[3] - light.points[0]);
+        let size = length(normal);
+        if (light.flags & LIGHT_DISABLED) != 0u || light.intensity <= 0. || size <= EPS {
+            continue;
+        }
+
+        let center_vec = mix(light.points[0], light.points[2], 0.5) - hit.position;
+        let center_dist2 = max(dot(center_vec, center_vec), EPS);
+        let solid_angle = abs(dot(normal, center_vec)) * inverseSqrt(center_dist2) / center_dist2;
+        let count = clamp(u32(ceil(solid_angle * AREA_SAMPLES_PER_SR)), 1u, MAX_AREA_SAMPLES);
+        let shift = pcg(pixel_hash ^ pcg(i ^ pcg(bounce)));
+
+        var sum = vec3(0.);
+        for (var k = 0u; k < count; k += 1u) {
+            let uv = sequence(params.samples * MAX_AREA_SAMPLES + k, shift);
+            let pos = mix(mix(light.points[0], light.points[1], uv.x), mix(light.points[3], light.points[2], uv.x), uv.y);
+            let light_vec = pos - hit.position;
+            let dist2 = max(dot(light_vec, light_vec), EPS);
+            let dist = sqrt(dist2);
+            let l = light_vec / dist;
+            let n_dot_l = dot(hit.normal, l);
+            if n_dot_l > 0. && visible(eye, l, dist) {
+                let cos_light = abs(dot(normal, l)) / size;
+                sum += eval_brdf(input, hit.normal, v, l) * n_dot_l * cos_light * size / dist2;
+            }
+        }
+        color += light.color * light.intensity * sum / f32(count);
+    }
+    return color;
+}
+
+fn radiance(primary: Ray) -> vec3<f32> {
+    var ray = primary;
+    var throughput = vec3(1.);
+    var color = vec3(0.);
+    for (var bounce = 0u; bounce <= params.max_bounces; bounce += 1u) {
+        let res = traverse_tlas(ray);
+        if !res.hit {
+            break;
+        }
+        let hit = surface(ray, res);
+
+        // Light geometry is only shown when seen directly, the light it gives off comes from
+        // `direct_light` and `area_light`, so it isn't counted twice.
+        if hit.material_id == LIGHT_MATERIAL {
+            if bounce == 0u {
+                color += throughput * (hit.albedo + hit.emissive);
+            }
+            break;
+        }
+        color += throughput * hit.emissive;
+
+        let v = -ray.dir;
+        let input = BrdfInput(hit.albedo, hit.roughness, hit.metallic);
+        color += throughput * (direct_light(hit, input, v) + area_light(hit, input, v, bounce));
+
+        // One sample of the diffuse and specular lobes, weighted by their mixed pdf.
+        let alpha = brdf_alpha(hit.roughness);
+        let frame = tangent_frame(hit.normal);
+        var l: vec3<f32>;
+        if rand() < 0.5 {
+            l = frame * sample_cosine(rand2());
+        } else {
+            l = reflect(-v, frame * sample_ggx(rand2(), alpha));
+        }
+        let n_dot_l = dot(hit.normal, l);
+        if n_dot_l <= 0. || dot(hit.geometric_normal, l) <= 0. {
+            break;
+        }
+        let h = normalize(v + l);
+        let n_dot_h = max(dot(hit.normal, h), 0.);
+        let v_dot_h = max(dot(v, h), EPS);
+        let pdf = 0.5 * n_dot_l / PI + 0.5 * d_ggx(n_dot_h, alpha) * n_dot_h / (4. * v_dot_h);
+        throughput *= eval_brdf(input, hit.normal, v, l) * n_dot_l / max(pdf, EPS);
+
+        if bounce >= 2u {
+            let survive = clamp(max_element(throughput), 0.05, 0.95);
+            if rand() > survive {
+                break;
+            }
+            throughput /= survive;
+        }
+        ray = ray_new(hit.position + hit.geometric_normal * RAY_OFFSET, l);
+    }
+    return color;
+}
+
+struct VertexOutput {
+  @builtin(position) pos: vec4<f32>,
+  @location(0) uv: vec2<f32>,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_idx: u32) -> VertexOutput {
+    var out: VertexOutput;
+    out.uv = vec2<f32>(vec2((vertex_idx << 1u) & 2u, vertex_idx & 2u));
+    out.pos = vec4(2.0 * out.uv.x - 1.0, 1. - out.uv.y * 2., 0.0, 1.0);
+    return out;
+}
+
+struct FragmentOutput {
+    @location(0) accumulation: vec4<f32>,
+    @location(1) color: vec4<f32>,
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> FragmentOutput {
+    let pix = vec2<u32>(in.pos.xy);
+    let history = textureLoad(t_history, pix, 0);
+    if params.samples >= params.max_samples {
+        return FragmentOutput(history, history);
+    }
+
+    pixel_hash = pcg(pix.x + pcg(pix.y));
+    rng_state = pcg(pix.x + pcg(pix.y + pcg(params.samples)));
+    let dims = vec2<f32>(textureDimensions(t_history));
+    let uv = (floor(in.pos.xy) + rand2()) / dims;
+    let near_point = params.clip_to_world * vec4(uv.x * 2. - 1., 1. - uv.y * 2., 1., 1.);
+    let eye = params.position.xyz;
+    var estimate = radiance(ray_new(eye, normalize(near_point.xyz / near_point.w - eye)));
+    // Drops the odd NaN before it spreads over the whole accumulation.
+    estimate = select(estimate, vec3(0.), estimate != estimate);
+
+    var color = estimate;
+    if params.samples > 0u {
+        color = mix(history.rgb, estimate, 1. / f32(params.samples + 1u));
+    }
+    return FragmentOutput(vec4(color, 1.), vec4(color, 1.));
+}
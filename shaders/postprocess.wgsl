@@ -1,10 +1,16 @@
 #import "shared.wgsl"
-#import "utils/color.wgsl"
+#import <voidin/color.wgsl>
+#import <voidin/tonemap.wgsl>
 
 @group(0) @binding(0) var<uniform> un: Globals;
 @group(1) @binding(0) var src_texture : texture_2d<f32>;
 @group(2) @binding(0) var src_sampler : sampler;
 
+struct PostProcessParams {
+    white_balance: mat3x3<f32>,
+}
+@group(3) @binding(0) var<uniform> params: PostProcessParams;
+
 struct VertexOutput {
   @builtin(position) pos: vec4<f32>,
   @location(0) uv: vec2<f32>,
@@ -18,35 +24,6 @@ fn vs_main(@builtin(vertex_index) vertex_idx: u32) -> VertexOutput {
     return out;
 }
 
-fn tonemap_curve(v: f32) -> f32 {
-    let c = v + v * v + 0.5 * v * v * v;
-    return c / (1.0 + c);
-}
-
-fn tonemap_curve_vec(col: vec3<f32>) -> vec3<f32> {
-    return vec3(tonemap_curve(col.r), tonemap_curve(col.g), tonemap_curve(col.b));
-}
-
-fn neutral_tonemap(col: vec3<f32>) -> vec3<f32> {
-    let ycbcr = rgb_to_ycbcr(col);
-
-    let chroma = length(ycbcr.yz) * 2.4;
-    let bt = tonemap_curve(chroma);
-
-    var desat = max((bt - 0.7) * 0.8, 0.0);
-    desat *= desat;
-
-    let desat_col = mix(col, ycbcr.xxx, desat);
-
-    let tm_luma = tonemap_curve(ycbcr.x);
-    let tm0 = col * max(0.0, tm_luma / max(1e-5, calculate_luma(col)));
-    let final_mult = 0.97;
-    let tm1 = tonemap_curve_vec(desat_col);
-
-    let res = mix(tm0, tm1, vec3(bt * bt));
-    return res * final_mult;
-}
-
 fn sharpen_remap(l: f32) -> f32 {
     return sqrt(l);
 }
@@ -92,6 +69,7 @@ fn fs_main(
 
     col *= max(0.0, sharpened_luma / max(1e-5, calculate_luma(col.rgb)));
 
+    col = max(vec3(0.), params.white_balance * col);
     col = neutral_tonemap(col);
 
     return vec4(col, 1.);
@@ -1,12 +1,13 @@
 #import "shared.wgsl"
-#import "utils/uv.wgsl"
+#import <voidin/camera.wgsl>
+#import "utils/gbuffer.wgsl"
 
 @group(0) @binding(0) var<uniform> camera: Camera;
 
-@group(1) @binding(0) var t_normal_uv: texture_2d<u32>;
-@group(1) @binding(1) var t_material: texture_2d<u32>;
-@group(1) @binding(2) var t_depth: texture_depth_2d;
-@group(1) @binding(3) var t_sampler: sampler;
+@group(1) @binding(0) var t_gbuffer: texture_2d<u32>;
+@group(1) @binding(1) var t_depth: texture_depth_2d;
+@group(1) @binding(2) var t_sampler: sampler;
+@group(1) @binding(3) var t_history_reject: texture_2d<u32>;
 
 @group(2) @binding(0) var t_motion: texture_storage_2d<rgba16float, write>;
 
@@ -35,5 +36,7 @@ fn cs_main(@builtin(global_invocation_id) global_id: vec3<u32>) {
 
     let inv_dims = 1.0 / vec2<f32>(dims);
     let limits = all(prev_position_ndc.xy == clamp(prev_position_ndc.xy, -1. + inv_dims, 1. - inv_dims));
-    textureStore(t_motion, pix, vec4(velocity, f32(limits), 1.));
+    // Swapped mesh or material, whatever history holds here is a different surface.
+    let rejected = textureLoad(t_history_reject, pix, 0).x != 0u;
+    textureStore(t_motion, pix, vec4(velocity, f32(limits && !rejected), 1.));
 }
@@ -0,0 +1,269 @@
+// ReSTIR DI, Bitterli et al. 2020 "Spatiotemporal reservoir resampling for real-time ray
+// tracing with dynamic direct lighting". Lights are unshadowed and use the light model
+// of `shading.wgsl`, so turning it on changes the noise and not the look.
+#import "shared.wgsl"
+#import <voidin/math.wgsl>
+#import <voidin/camera.wgsl>
+#import <voidin/color.wgsl>
+#import <voidin/random.wgsl>
+#import "utils/gbuffer.wgsl"
+#import "utils/ies.wgsl"
+
+struct RestirSettings {
+    candidates: u32,
+    spatial_samples: u32,
+    spatial_radius: f32,
+    // Temporal history is capped at this many times `candidates`, 0 turns it off.
+    max_history: f32,
+}
+
+struct Reservoir {
+    light: u32,
+    w_sum: f32,
+    m: f32,
+    // Unbiased contribution weight of the sample.
+    w: f32,
+    // Point on an area light.
+    uv: vec2<f32>,
+    // View depth of the pixel, rejects disoccluded history.
+    depth: f32,
+    junk: u32,
+}
+
+@group(0) @binding(0) var<uniform> global: Globals;
+@group(0) @binding(1) var<uniform> camera: Camera;
+
+@group(1) @binding(0) var t_gbuffer: texture_2d<u32>;
+@group(1) @binding(1) var t_depth: texture_depth_2d;
+
+@group(2) @binding(0) var texture_array: binding_array<texture_2d<f32>>;
+@group(2) @binding(1) var tex_sampler: sampler;
+
+@group(3) @binding(0) var<storage, read> materials: array<Material>;
+
+@group(4) @binding(0) var<storage, read> point_lights: array<Light>;
+@group(5) @binding(0) var<storage, read> area_lights: array<AreaLight>;
+
+// Spatial reuse writes `reservoirs`, they are the history of the next frame.
+@group(6) @binding(0) var<storage, read_write> reservoirs: array<Reservoir>;
+@group(6) @binding(1) var<storage, read_write> temporal: array<Reservoir>;
+@group(6) @binding(2) var t_lighting: texture_storage_2d<rgba16float, write>;
+@group(6) @binding(3) var<uniform> settings: RestirSettings;
+
+const NO_LIGHT: u32 = 0xffffffffu;
+
+var<private> rng_state: u32;
+
+fn rand() -> f32 {
+    rng_state = pcg(rng_state);
+    return f32(rng_state >> 8u) / 16777216.;
+}
+
+fn sqr(x: f32) -> f32 {
+    return x * x;
+}
+
+fn attenuation(max_intensity: f32, falloff: f32, dist: f32, radius: f32) -> f32 {
+    var s = dist / radius;
+    if s >= 1.0 {
+        return 0.;
+    }
+    let s2 = sqr(s);
+    return max_intensity * sqr(1. - s2) / (1. + falloff * s2);
+}
+
+struct Surface {
+    position: vec3<f32>,
+    normal: vec3<f32>,
+    // Towards the camera.
+    view: vec3<f32>,
+    depth: f32,
+    diffuse: vec3<f32>,
+    specular: f32,
+    lit: bool,
+}
+
+fn load_surface(pix: vec2<u32>, dims: vec2<u32>) -> Surface {
+    var surface: Surface;
+    let depth = textureLoad(t_depth, pix, 0);
+    let gbuffer = unpack_gbuffer(textureLoad(t_gbuffer, pix, 0).xy);
+    // Sky and lights take no light.
+    surface.lit = depth > 0. && gbuffer.material_id != LIGHT_MATERIAL;
+
+    let uv = (vec2<f32>(pix) + 0.5) / vec2<f32>(dims);
+    surface.position = world_position_from_depth(uv, depth, camera.clip_to_world);
+    surface.normal = gbuffer.normal;
+    surface.view = normalize(camera.position.xyz - surface.position);
+    surface.depth = -(camera.view * vec4(surface.position, 1.)).z;
+
+    let material = materials[gbuffer.material_id];
+    let albedo = textureSampleLevel(texture_array[material.albedo], tex_sampler, gbuffer.uv, 0.);
+    let orm = textureSampleLevel(texture_array[material.metallic_roughness], tex_sampler, gbuffer.uv, 0.);
+    surface.diffuse = albedo.rgb * (1. - material.transmission * (1. - orm.z));
+    surface.specular = mix(sqr((material.ior - 1.) / (material.ior + 1.)), 1., orm.z);
+    return surface;
+}
+
+fn light_count() -> u32 {
+    return arrayLength(&point_lights) + arrayLength(&area_lights);
+}
+
+fn reflected(surface: Surface, dir: vec3<f32>) -> vec3<f32> {
+    let shade = max(0., dot(surface.normal, dir));
+    let half_dir = normalize(dir + surface.view);
+    let spec = surface.specular * pow(max(0., dot(surface.normal, half_dir)), 16.) * step(0., shade);
+    return surface.diffuse * shade + spec;
+}
+
+// Light reaching the camera from `light`, at `uv` for area lights.
+fn light_sample(light: u32, uv: vec2<f32>, surface: Surface) -> vec3<f32> {
+    let point_count = arrayLength(&point_lights);
+    if light < point_count {
+        let point = point_lights[light];
+        let light_vec = point.position - surface.position;
+        let dist = length(light_vec);
+        if dist >= point.radius || (point.flags & LIGHT_DISABLED) != 0u {
+            return vec3(0.);
+        }
+        let dir = light_vec / dist;
+        let profile = textureSampleLevel(texture_array[point.ies], tex_sampler, ies_uv(-dir, vec3(0., -1., 0.)), 0.).r;
+        return point.color * attenuation(1., 1., dist, point.radius) * profile * reflected(surface, dir);
+    }
+
+    let area = area_lights[light - point_count];
+    if (area.flags & LIGHT_DISABLED) != 0u {
+        return vec3(0.);
+    }
+    let pos = mix(mix(area.points[0], area.points[1], uv.x), mix(area.points[3], area.points[2], uv.x), uv.y);
+    let normal = cross(area.points[1] - area.points[0], area.points[3] - area.points[0]);
+    let size = length(normal);
+    let light_vec = pos - surface.position;
+    let dist2 = max(dot(light_vec, light_vec), EPS);
+    let dir = light_vec * inverseSqrt(dist2);
+    // Both faces emit, like the LTC integration does.
+    let cos_light = abs(dot(normal, dir)) / max(size, EPS);
+    return area.color * area.intensity * reflected(surface, dir) * cos_light * size / (PI * dist2);
+}
+
+fn target_pdf(light: u32, uv: vec2<f32>, surface: Surface) -> f32 {
+    return calculate_luma(light_sample(light, uv, surface));
+}
+
+fn reservoir_new(depth: f32) -> Reservoir {
+    return Reservoir(NO_LIGHT, 0., 0., 0., vec2(0.), depth, 0u);
+}
+
+fn update(r: ptr<function, Reservoir>, light: u32, uv: vec2<f32>, weight: f32, m: f32) {
+    (*r).w_sum += weight;
+    (*r).m += m;
+    if weight > 0. && rand() * (*r).w_sum < weight {
+        (*r).light = light;
+        (*r).uv = uv;
+    }
+}
+
+// Folds in another pixel's reservoir, its sample weighted by how much this pixel wants it.
+fn merge(r: ptr<function, Reservoir>, other: Reservoir, surface: Surface) {
+    if other.light >= light_count() {
+        return;
+    }
+    let weight = target_pdf(other.light, other.uv, surface) * other.w * other.m;
+    update(r, other.light, other.uv, weight, other.m);
+}
+
+fn finalize(r: ptr<function, Reservoir>, surface: Surface) {
+    (*r).w = 0.;
+    if (*r).light == NO_LIGHT || (*r).m == 0. {
+        return;
+    }
+    let p_hat = target_pdf((*r).light, (*r).uv, surface);
+    if p_hat > 0. {
+        (*r).w = (*r).w_sum / ((*r).m * p_hat);
+    }
+}
+
+// Candidates picked uniformly over all lights, then the reprojected history.
+@compute @workgroup_size(8, 8, 1)
+fn initial(@builtin(global_invocation_id) id: vec3<u32>) {
+    let dims = textureDimensions(t_gbuffer);
+    if any(id.xy >= dims) {
+        return;
+    }
+    let idx = id.y * dims.x + id.x;
+    rng_state = pcg(idx + pcg(global.frame));
+
+    let surface = load_surface(id.xy, dims);
+    var r = reservoir_new(surface.depth);
+    if !surface.lit {
+        temporal[idx] = r;
+        return;
+    }
+
+    let count = light_count();
+    if count > 0u {
+        for (var i = 0u; i < settings.candidates; i += 1u) {
+            let light = min(u32(rand() * f32(count)), count - 1u);
+            let uv = vec2(rand(), rand());
+            update(&r, light, uv, target_pdf(light, uv, surface) * f32(count), 1.);
+        }
+    }
+    finalize(&r, surface);
+
+    if settings.max_history > 0. {
+        let prev_clip = camera.prev_world_to_clip * vec4(surface.position, 1.);
+        let prev_uv = cs_to_uv(prev_clip.xy / prev_clip.w);
+        if prev_clip.w > 0. && all(prev_uv >= vec2(0.)) && all(prev_uv < vec2(1.)) {
+            let prev_pix = vec2<u32>(prev_uv * vec2<f32>(dims));
+            var prev = reservoirs[prev_pix.y * dims.x + prev_pix.x];
+            if abs(prev.depth - prev_clip.w) < 0.1 * prev_clip.w {
+                prev.m = min(prev.m, settings.max_history * f32(settings.candidates));
+                merge(&r, prev, surface);
+                finalize(&r, surface);
+            }
+        }
+    }
+    temporal[idx] = r;
+}
+
+// Reuses the reservoirs of nearby pixels on a similar surface and shades the result.
+@compute @workgroup_size(8, 8, 1)
+fn spatial(@builtin(global_invocation_id) id: vec3<u32>) {
+    let dims = textureDimensions(t_gbuffer);
+    if any(id.xy >= dims) {
+        return;
+    }
+    let idx = id.y * dims.x + id.x;
+    rng_state = pcg(idx + pcg(global.frame + 0x9e3779b9u));
+
+    let surface = load_surface(id.xy, dims);
+    let center = temporal[idx];
+    var r = reservoir_new(surface.depth);
+    if !surface.lit {
+        reservoirs[idx] = r;
+        textureStore(t_lighting, id.xy, vec4(0.));
+        return;
+    }
+
+    merge(&r, center, surface);
+    for (var i = 0u; i < settings.spatial_samples; i += 1u) {
+        let offset = (vec2(rand(), rand()) * 2. - 1.) * settings.spatial_radius;
+        let pix = vec2<i32>(id.xy) + vec2<i32>(offset);
+        if any(pix < vec2(0)) || any(pix >= vec2<i32>(dims)) {
+            continue;
+        }
+        let neighbour = temporal[u32(pix.y) * dims.x + u32(pix.x)];
+        let normal = unpack_gbuffer(textureLoad(t_gbuffer, pix, 0).xy).normal;
+        if abs(neighbour.depth - surface.depth) > 0.1 * surface.depth || dot(normal, surface.normal) < 0.9 {
+            continue;
+        }
+        merge(&r, neighbour, surface);
+    }
+    finalize(&r, surface);
+    reservoirs[idx] = r;
+
+    var lighting = vec3(0.);
+    if r.light != NO_LIGHT {
+        lighting = light_sample(r.light, r.uv, surface) * r.w;
+    }
+    textureStore(t_lighting, id.xy, vec4(max(lighting, vec3(0.)), 1.));
+}
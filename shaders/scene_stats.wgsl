@@ -0,0 +1,66 @@
+#import "shared.wgsl"
+
+const MATERIAL_SLOTS = 256u;
+
+// [material pixels | material triangles | instance pixels | instance triangles]
+@group(0) @binding(0) var<storage, read_write> counters: array<atomic<u32>>;
+
+@group(1) @binding(0) var t_gbuffer: texture_2d<u32>;
+@group(1) @binding(1) var t_depth: texture_depth_2d;
+
+@group(2) @binding(0) var<storage, read_write> instances: array<Instance>;
+@group(3) @binding(0) var<storage, read_write> cmd_buffer: array<DrawIndexedIndirect>;
+
+@group(4) @binding(0) var t_ids: texture_2d<u32>;
+
+fn instance_pixels_offset() -> u32 {
+    return 2u * MATERIAL_SLOTS;
+}
+
+fn instance_triangles_offset() -> u32 {
+    return 2u * MATERIAL_SLOTS + arrayLength(&instances);
+}
+
+@compute
+@workgroup_size(8, 8, 1)
+fn count_pixels(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let pix = global_id.xy;
+    if any(pix >= textureDimensions(t_gbuffer)) {
+        return;
+    }
+    // Reversed depth is cleared to 0, nothing was rasterized there.
+    if textureLoad(t_depth, pix, 0) == 0.0 {
+        return;
+    }
+    let material_id = textureLoad(t_gbuffer, pix, 0).x >> 24u;
+    atomicAdd(&counters[material_id], 1u);
+}
+
+@compute
+@workgroup_size(64, 1, 1)
+fn count_triangles(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let index = global_id.x;
+    if index >= arrayLength(&instances) {
+        return;
+    }
+    let cmd = cmd_buffer[index];
+    let triangles = cmd.instance_count * cmd.vertex_count / 3u;
+    let material_id = instances[index].material_id & 0xffu;
+    atomicAdd(&counters[MATERIAL_SLOTS + material_id], triangles);
+    atomicStore(&counters[instance_triangles_offset() + index], triangles);
+}
+
+@compute
+@workgroup_size(8, 8, 1)
+fn count_instance_pixels(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let pix = global_id.xy;
+    if any(pix >= textureDimensions(t_ids)) {
+        return;
+    }
+    // Visibility buffer stores `instance + 1`, 0 is the clear value.
+    let instance = textureLoad(t_ids, pix, 0).x;
+    if instance == 0u || instance > arrayLength(&instances) {
+        return;
+    }
+    atomicAdd(&counters[instance_pixels_offset() + instance - 1u], 1u);
+}
@@ -1,15 +1,17 @@
 #import "shared.wgsl"
-#import "utils/encoding.wgsl"
+#import <voidin/packing.wgsl>
 #import "utils/ltc.wgsl"
-#import "utils/uv.wgsl"
+#import <voidin/camera.wgsl>
+#import "utils/gbuffer.wgsl"
+#import "utils/clusters.wgsl"
+#import "utils/ies.wgsl"
 
 @group(0) @binding(0) var<uniform> global: Globals;
 @group(0) @binding(1) var<uniform> camera: Camera;
 
-@group(1) @binding(0) var t_normal_uv: texture_2d<u32>;
-@group(1) @binding(1) var t_material: texture_2d<u32>;
-@group(1) @binding(2) var t_depth: texture_depth_2d;
-@group(1) @binding(3) var t_sampler: sampler;
+@group(1) @binding(0) var t_gbuffer: texture_2d<u32>;
+@group(1) @binding(1) var t_depth: texture_depth_2d;
+@group(1) @binding(2) var t_sampler: sampler;
 
 @group(2) @binding(0) var texture_array: binding_array<texture_2d<f32>>;
 @group(2) @binding(1) var tex_sampler: sampler;
@@ -18,7 +20,12 @@
 @group(3) @binding(0) var<storage, read> materials: array<Material>;
 
 @group(4) @binding(0) var<storage, read> point_lights: array<Light>;
+@group(4) @binding(1) var<storage, read> cluster_counts: array<u32>;
+@group(4) @binding(2) var<storage, read> cluster_lights: array<u32>;
 @group(5) @binding(0) var<storage, read> area_lights: array<AreaLight>;
+@group(7) @binding(0) var<uniform> sun: DirectionalLight;
+@group(7) @binding(1) var<storage, read> spot_lights: array<SpotLight>;
+@group(7) @binding(2) var<storage, read> directional_lights: array<DirectionalLight>;
 
 struct VertexOutput {
   @builtin(position) pos: vec4<f32>,
@@ -46,62 +53,125 @@ fn attenuation(max_intensity: f32, falloff: f32, dist: f32, radius: f32) -> f32
     return max_intensity * sqr(1. - s2) / (1. + falloff * s2);
 }
 
+// Light reflected towards `rd` from a light in direction `light_dir`, per unit of radiance.
+fn directional(light_dir: vec3<f32>, nor: vec3<f32>, rd: vec3<f32>, diffuse: vec3<f32>, specular: f32, coat: f32, coat_exponent: f32) -> vec3<f32> {
+    let shade = max(0., dot(nor, light_dir));
+    let half_dir = normalize(light_dir + rd);
+    let spec = specular * pow(max(0., dot(nor, half_dir)), 16.) * step(0., shade);
+    let coat_spec = coat * pow(max(0., dot(nor, half_dir)), coat_exponent) * step(0., shade);
+    return (diffuse * shade + spec) * (1. - coat) + coat_spec;
+}
+
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    let tex_dims = vec2f(textureDimensions(t_normal_uv));
+    return shade(in, true);
+}
+
+// Leaves point and area lights to `pass::restir::RestirDi`, spot and directional ones stay.
+@fragment
+fn fs_ambient(in: VertexOutput) -> @location(0) vec4<f32> {
+    return shade(in, false);
+}
+
+fn shade(in: VertexOutput, local_lights: bool) -> vec4<f32> {
+    let tex_dims = vec2f(textureDimensions(t_gbuffer));
     let load_uv = vec2<u32>(in.uv * tex_dims);
 
     let depth = textureLoad(t_depth, load_uv, 0);
-    let norm_uv_tex = textureLoad(t_normal_uv, load_uv, 0);
-    let material_id = textureLoad(t_material, load_uv, 0).r;
+    let gbuffer = unpack_gbuffer(textureLoad(t_gbuffer, load_uv, 0).xy);
+    let material_id = gbuffer.material_id;
 
     let material = materials[material_id];
-    let uv = unpack2x16float(norm_uv_tex.y);
-    let albedo = textureSample(texture_array[material.albedo], t_sampler, uv);
-    let emissive = textureSample(texture_array[material.emissive], t_sampler, uv).rgb;
-    let metallic_roughness = textureSample(texture_array[material.metallic_roughness], t_sampler, uv);
+    let uv = gbuffer.uv;
+    let albedo = textureSampleBias(texture_array[material.albedo], t_sampler, uv, camera.mip_bias);
+    let emissive = textureSampleBias(texture_array[material.emissive], t_sampler, uv, camera.mip_bias).rgb * material.emissive_strength;
+    // R: occlusion, G: roughness, B: metallic, packed at import.
+    let orm = textureSampleBias(texture_array[material.metallic_roughness], t_sampler, uv, camera.mip_bias);
+    let occlusion = select(orm.x, 1., material.metallic_roughness == BLACK_TEXTURE);
+
+    // Dielectrics reflect by their index of refraction, metals fully.
+    let specular = mix(sqr((material.ior - 1.) / (material.ior + 1.)), 1., orm.z);
+    // Transmitted light isn't traced, it only takes away from the diffuse lobe.
+    let diffuse = albedo.rgb * (1. - material.transmission * (1. - orm.z));
+    // The coat reflects like a dielectric with ior 1.5 and dims the layers below.
+    let coat = material.clearcoat * 0.04;
+    let coat_exponent = 2. / max(pow(material.clearcoat_roughness, 4.), 1e-3) - 2.;
 
     let pos = world_position_from_depth(in.uv, depth, camera.clip_to_world);
-    let nor = decode_octahedral_32(norm_uv_tex.x);
+    let nor = gbuffer.normal;
     let rd = normalize(camera.position.xyz - pos);
 
     var color = vec3(0.);
 
-    color = albedo.rgb * 0.01 + emissive;
+    color = diffuse * 0.01 * occlusion + emissive;
     if material_id == LIGHT_MATERIAL {
         color = albedo.rgb + emissive;
     }
 
-    let light_count = arrayLength(&point_lights);
+    // Only the point lights binned into the cluster of this pixel.
+    let cluster = cluster_index(in.uv, -(camera.view * vec4(pos, 1.)).z);
+    let light_count = select(0u, cluster_counts[cluster], local_lights);
     for (var i = 0u; i < light_count; i += 1u) {
         if material_id == LIGHT_MATERIAL { break; }
 
-        let light = point_lights[i];
+        let light = point_lights[cluster_lights[cluster * MAX_LIGHTS_PER_CLUSTER + i]];
 
         let light_vec = light.position - pos;
         let dist = length(light_vec);
         if dist - light.radius > 0. { continue; }
 
-        let atten = attenuation(1., 1., dist, light.radius);
-
         let light_dir = normalize(light_vec);
+        let profile = textureSampleLevel(texture_array[light.ies], tex_sampler, ies_uv(-light_dir, vec3(0., -1., 0.)), 0.).r;
+        let atten = attenuation(1., 1., dist, light.radius) * profile;
+
         let shade = max(0., dot(nor, light_dir));
-        let diff = light.color * albedo.rgb * shade * atten;
+        let diff = light.color * diffuse * shade * atten;
 
         let refl = reflect(-light_dir, rd);
         let covr = max(0., dot(-rd, nor));
-        let spec = light.color * metallic_roughness.z * pow(covr, 16.) * atten;
+        let spec = light.color * specular * pow(covr, 16.) * atten;
+
+        let half_dir = normalize(light_dir + rd);
+        let coat_spec = light.color * coat * pow(max(0., dot(nor, half_dir)), coat_exponent) * atten * step(0., shade);
+
+        color += (diff + spec) * (1. - coat) + coat_spec;
+    }
+
+    for (var i = 0u; i < arrayLength(&spot_lights); i += 1u) {
+        if material_id == LIGHT_MATERIAL { break; }
+
+        let light = spot_lights[i];
+        if (light.flags & LIGHT_DISABLED) != 0u { continue; }
+        let light_vec = light.position - pos;
+        let dist = length(light_vec);
+        if dist >= light.radius { continue; }
+
+        let light_dir = light_vec / dist;
+        let cone = smoothstep(light.cos_outer, light.cos_inner, dot(-light_dir, light.direction));
+        let profile = textureSampleLevel(texture_array[light.ies], tex_sampler, ies_uv(-light_dir, light.direction), 0.).r;
+        let atten = attenuation(1., 1., dist, light.radius) * cone * profile;
+        color += light.color * atten * directional(light_dir, nor, rd, diffuse, specular, coat, coat_exponent);
+    }
+
+    if material_id != LIGHT_MATERIAL && sun.intensity > 0. {
+        color += sun.color * sun.intensity * directional(sun.direction, nor, rd, diffuse, specular, coat, coat_exponent);
+    }
+    for (var i = 0u; i < arrayLength(&directional_lights); i += 1u) {
+        if material_id == LIGHT_MATERIAL { break; }
 
-        color += diff + spec;
+        let light = directional_lights[i];
+        if (light.flags & LIGHT_DISABLED) != 0u || light.intensity <= 0. { continue; }
+        color += light.color * light.intensity * directional(light.direction, nor, rd, diffuse, specular, coat, coat_exponent);
     }
 
-    let ltc = ltc_matrix(nor, rd, saturate(metallic_roughness.x));
-    let area_light_count = arrayLength(&area_lights);
+    let ltc = ltc_matrix(nor, rd, saturate(orm.y));
+    let area_light_count = select(0u, arrayLength(&area_lights), local_lights);
     for (var i = 0u; i < area_light_count; i += 1u) {
         if material_id == LIGHT_MATERIAL { break; }
         let light_radius = 25.;
 
         let light = area_lights[i];
+        if (light.flags & LIGHT_DISABLED) != 0u { continue; }
         let center = mix(light.points[0], light.points[2], 0.5);
         let light_vec = center - pos;
         let dist = length(light_vec);
@@ -110,7 +180,7 @@ fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
         let spec = get_area_light_specular(nor, rd, pos, ltc, light.points, false, vec3(1.));
 
         let atten = attenuation(light.intensity, 500., distance(center, pos), light_radius);
-        color += light.color * light.intensity * (spec * atten + albedo.rgb * diff) ;
+        color += light.color * light.intensity * (spec * atten + diffuse * diff) * (1. - coat);
     }
 
     color = max(color, vec3(0.));
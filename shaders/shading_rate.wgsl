@@ -0,0 +1,141 @@
+// Decoupled shading rate. Screen tiles whose luminance and normals barely vary are shaded
+// once per 2x2 quad, the stencil mask keeps the shading pass away from the other three
+// pixels, and `reconstruct` fills them in from the shaded pixels around.
+#import <voidin/math.wgsl>
+#import <voidin/color.wgsl>
+#import "utils/gbuffer.wgsl"
+
+struct ShadingRateSettings {
+    luma_threshold: f32,
+    normal_threshold: f32,
+}
+
+@group(0) @binding(0) var t_gbuffer: texture_2d<u32>;
+@group(0) @binding(1) var t_depth: texture_depth_2d;
+
+@group(1) @binding(0) var<storage, read_write> rates: array<u32>;
+@group(1) @binding(1) var<uniform> settings: ShadingRateSettings;
+
+// The previous frame for `classify`, the one just shaded for `reconstruct`.
+@group(2) @binding(0) var t_color: texture_2d<f32>;
+@group(3) @binding(0) var t_output: texture_storage_2d<rgba16float, write>;
+
+// Matches the workgroup size of `classify` and `ShadingRate::TILE_SIZE`.
+const TILE_SIZE = 8u;
+const RATE_FULL = 1u;
+const RATE_COARSE = 2u;
+
+fn tile_rate(pix: vec2<u32>, dims: vec2<u32>) -> u32 {
+    let tiles_x = (dims.x + TILE_SIZE - 1u) / TILE_SIZE;
+    let tile = pix / TILE_SIZE;
+    return rates[tile.y * tiles_x + tile.x];
+}
+
+// The top left pixel of every quad is always shaded, so are the sky and the pixels whose
+// quad starts on the sky.
+fn skips_shading(pix: vec2<u32>, dims: vec2<u32>) -> bool {
+    if all((pix & vec2(1u)) == vec2(0u)) || tile_rate(pix, dims) != RATE_COARSE {
+        return false;
+    }
+    return textureLoad(t_depth, pix, 0) > 0. && textureLoad(t_depth, pix & vec2(~1u), 0) > 0.;
+}
+
+// Luminance and count of the pixels covering geometry, then the normal sum.
+var<workgroup> stats: array<vec4<f32>, 64>;
+var<workgroup> normals: array<vec3<f32>, 64>;
+
+// One workgroup per tile. Luminance comes from the previous frame, it lags a frame
+// behind the camera, which tiles of this size hardly notice.
+@compute @workgroup_size(8, 8, 1)
+fn classify(
+    @builtin(global_invocation_id) id: vec3<u32>,
+    @builtin(local_invocation_index) local_index: u32,
+    @builtin(workgroup_id) tile: vec3<u32>,
+    @builtin(num_workgroups) tiles: vec3<u32>,
+) {
+    let dims = textureDimensions(t_gbuffer);
+    var stat = vec4(0.);
+    var normal = vec3(0.);
+    if all(id.xy < dims) && textureLoad(t_depth, id.xy, 0) > 0. {
+        let luma = calculate_luma(textureLoad(t_color, id.xy, 0).rgb);
+        stat = vec4(luma, luma * luma, 1., 0.);
+        normal = unpack_gbuffer(textureLoad(t_gbuffer, id.xy, 0).xy).normal;
+    }
+    stats[local_index] = stat;
+    normals[local_index] = normal;
+    for (var stride = 32u; stride > 0u; stride >>= 1u) {
+        workgroupBarrier();
+        if local_index < stride {
+            stats[local_index] += stats[local_index + stride];
+            normals[local_index] += normals[local_index + stride];
+        }
+    }
+    if local_index != 0u {
+        return;
+    }
+
+    var rate = RATE_FULL;
+    let count = stats[0].z;
+    if count > 0. {
+        let mean = stats[0].x / count;
+        let deviation = sqrt(max(stats[0].y / count - mean * mean, 0.));
+        // Zero when all normals agree, growing as they spread.
+        let normal_spread = 1. - length(normals[0]) / count;
+        if deviation <= settings.luma_threshold * max(mean, 1e-3) && normal_spread <= settings.normal_threshold {
+            rate = RATE_COARSE;
+        }
+    }
+    rates[tile.y * tiles.x + tile.x] = rate;
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_idx: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(vec2((vertex_idx << 1u) & 2u, vertex_idx & 2u));
+    return vec4(2.0 * uv.x - 1.0, 1. - uv.y * 2., 0.0, 1.0);
+}
+
+// Marks the pixels left to `reconstruct` in the stencil buffer.
+@fragment
+fn fs_mask(@builtin(position) pos: vec4<f32>) {
+    if !skips_shading(vec2<u32>(pos.xy), textureDimensions(t_gbuffer)) {
+        discard;
+    }
+}
+
+// Fills skipped pixels from the shaded quad corners around them, weighted by how close
+// they are and how alike their surface is. Everything else is copied as is.
+@compute @workgroup_size(8, 8, 1)
+fn reconstruct(@builtin(global_invocation_id) id: vec3<u32>) {
+    let dims = textureDimensions(t_gbuffer);
+    if any(id.xy >= dims) {
+        return;
+    }
+    var color = textureLoad(t_color, id.xy, 0);
+    if skips_shading(id.xy, dims) {
+        let base = id.xy & vec2(~1u);
+        let t = vec2<f32>(id.xy & vec2(1u)) * 0.5;
+        let depth = textureLoad(t_depth, id.xy, 0);
+        let normal = unpack_gbuffer(textureLoad(t_gbuffer, id.xy, 0).xy).normal;
+
+        var sum = vec4(0.);
+        var weight_sum = 0.;
+        for (var i = 0u; i < 4u; i += 1u) {
+            let corner = vec2(i & 1u, i >> 1u);
+            let anchor = base + corner * 2u;
+            let bilinear = mix(1. - t, t, vec2<f32>(corner));
+            let anchor_depth = textureLoad(t_depth, min(anchor, dims - 1u), 0);
+            if any(anchor >= dims) || bilinear.x * bilinear.y == 0. || anchor_depth <= 0. {
+                continue;
+            }
+            let anchor_normal = unpack_gbuffer(textureLoad(t_gbuffer, anchor, 0).xy).normal;
+            // Reversed depth goes as one over the distance, so this is relative in view space too.
+            let depth_weight = max(0., 1. - abs(anchor_depth - depth) / (0.1 * depth));
+            let normal_weight = pow(max(0., dot(normal, anchor_normal)), 8.);
+            let weight = bilinear.x * bilinear.y * depth_weight * normal_weight;
+            sum += textureLoad(t_color, anchor, 0) * weight;
+            weight_sum += weight;
+        }
+        color = select(textureLoad(t_color, base, 0), sum / weight_sum, weight_sum > EPS);
+    }
+    textureStore(t_output, id.xy, color);
+}
@@ -2,37 +2,85 @@ const LIGHT_MATERIAL = 2u;
 const WHITE_TEXTURE = 0u;
 const BLACK_TEXTURE = 1u;
 
+const INSTANCE_CHANGED = 1u;
+const INSTANCE_DYNAMIC = 2u;
+const INSTANCE_INACTIVE = 4u;
+
+const NO_TRIANGLE_MATERIALS = 0xffffffffu;
+
+// `flags` of every light in `LightPool`, set by `LightPool::set_light_enabled`.
+const LIGHT_DISABLED = 1u;
+
 struct Globals {
     resolution: vec2<f32>,
     frame: u32,
     time: f32,
 	dt: f32,
 	custom: f32,
+	alpha: f32,
+	user: array<vec4<f32>, 4>,
+}
+
+struct Audio {
+    bands: array<vec4<f32>, 4>,
+    level: f32,
+    beat: f32,
+    beat_count: u32,
 }
 
+// Views of `CameraUniformBinding`, view 0 is the main camera. Passes rendering from any of
+// them declare `@binding(2) var<uniform> views: array<Camera, MAX_VIEWS>;` in the group
+// holding the array, `CameraUniformBinding` or `GlobalsBindGroup`, and
+// `var<push_constant> view_index: u32;`. The imposter bake fills its own array the same way
+// and indexes it with `instance_index % MAX_VIEWS`, which works without push constants.
+const MAX_VIEWS = 8u;
+
 struct Camera {
 	position: vec4<f32>,
 	proj: mat4x4<f32>,
 	view: mat4x4<f32>,
 	clip_to_world: mat4x4<f32>,
 	prev_world_to_clip: mat4x4<f32>,
-	frustum: vec4<f32>,
+	frustum_planes: array<vec4<f32>, 6>,
 	zfar: f32, znear: f32,
 	jitter: vec2<f32>,
 	prev_jitter: vec2<f32>,
-	padding: vec2<f32>,
+	mip_bias: f32,
+	padding: f32,
 }
 
 struct Light {
 	position: vec3<f32>,
 	radius: f32,
-	color: vec3<f32>
+	color: vec3<f32>,
+	flags: u32,
+	// Texture of an IES profile, see `utils/ies.wgsl`.
+	ies: u32,
+}
+
+struct DirectionalLight {
+	direction: vec3<f32>,
+	intensity: f32,
+	color: vec3<f32>,
+	flags: u32,
+}
+
+struct SpotLight {
+	position: vec3<f32>,
+	radius: f32,
+	direction: vec3<f32>,
+	cos_inner: f32,
+	color: vec3<f32>,
+	cos_outer: f32,
+	flags: u32,
+	ies: u32,
 }
 
 struct AreaLight {
 	color: vec3<f32>,
 	intensity: f32,
 	points: array<vec3<f32>, 4>,
+	flags: u32,
 }
 
 struct BoundingSphere {
@@ -47,7 +95,14 @@ struct MeshInfo {
 	base_index: u32,
     vertex_offset: i32,
 	bvh_index: u32,
-	junk: vec2<f32>,
+	triangle_materials: u32,
+	meshlet_index: u32,
+	center: vec3<f32>,
+	radius: f32,
+	meshlet_count: u32,
+	lod_index: u32,
+	lod_count: u32,
+	junk: u32,
 }
 
 struct Instance {
@@ -55,7 +110,8 @@ struct Instance {
     inv_transform: mat4x4<f32>,
 	mesh_id: u32,
 	material_id: u32,
-	padding: vec2<f32>,
+	flags: u32,
+	padding: f32,
 }
 
 struct Material {
@@ -64,6 +120,12 @@ struct Material {
 	normal: u32,
 	metallic_roughness: u32,
 	emissive: u32,
+	emissive_strength: f32,
+	ior: f32,
+	transmission: f32,
+	clearcoat: f32,
+	clearcoat_roughness: f32,
+	junk: array<u32, 3>,
 }
 
 struct DrawIndexedIndirect {
@@ -0,0 +1,113 @@
+#import "shared.wgsl"
+
+struct SkinnedVertex {
+    position: vec3<f32>,
+    skinned_mesh: u32,
+    normal: vec3<f32>,
+    padding: u32,
+    tangent: vec4<f32>,
+    joints: vec4<u32>,
+    weights: vec4<f32>,
+}
+
+struct SkinnedMeshInfo {
+    rest_offset: u32,
+    joint_offset: u32,
+    joint_count: u32,
+    mesh_id: u32,
+}
+
+@group(0) @binding(0) var<storage, read> rest_vertices: array<SkinnedVertex>;
+@group(0) @binding(1) var<storage, read> skinned_meshes: array<SkinnedMeshInfo>;
+@group(0) @binding(2) var<storage, read> joint_matrices: array<mat4x4<f32>>;
+
+// `MeshPool` attributes of the posed copies.
+@group(1) @binding(0) var<storage, read_write> vertices: array<f32>;
+@group(1) @binding(1) var<storage, read_write> normals: array<f32>;
+@group(1) @binding(2) var<storage, read_write> tangents: array<vec4<f32>>;
+@group(1) @binding(3) var<storage, read_write> meshes: array<MeshInfo>;
+// Six per skinned mesh, cleared to zero before the pass.
+@group(1) @binding(4) var<storage, read_write> bounds: array<atomic<u32>>;
+
+// Maps floats to u32s with the same order, so bounds grow with atomicMax.
+fn order_f32(x: f32) -> u32 {
+    let bits = bitcast<u32>(x);
+    return select(bits | 0x80000000u, ~bits, (bits & 0x80000000u) != 0u);
+}
+
+fn unorder_f32(x: u32) -> f32 {
+    return bitcast<f32>(select(~x, x & 0x7fffffffu, (x & 0x80000000u) != 0u));
+}
+
+fn joint_matrix(mesh: SkinnedMeshInfo, joint: u32) -> mat4x4<f32> {
+    return joint_matrices[mesh.joint_offset + min(joint, mesh.joint_count - 1u)];
+}
+
+@compute
+@workgroup_size(64, 1, 1)
+fn cs_main(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    if global_id.x >= arrayLength(&rest_vertices) {
+        return;
+    }
+    let vertex = rest_vertices[global_id.x];
+    let mesh = skinned_meshes[vertex.skinned_mesh];
+
+    // Weights are not guaranteed to be normalized by exporters.
+    let weights = vertex.weights / max(dot(vertex.weights, vec4(1.0)), 1e-6);
+    let skin = joint_matrix(mesh, vertex.joints.x) * weights.x
+        + joint_matrix(mesh, vertex.joints.y) * weights.y
+        + joint_matrix(mesh, vertex.joints.z) * weights.z
+        + joint_matrix(mesh, vertex.joints.w) * weights.w;
+    let skin3 = mat3x3(skin[0].xyz, skin[1].xyz, skin[2].xyz);
+
+    let position = (skin * vec4(vertex.position, 1.0)).xyz;
+    let normal = normalize(skin3 * vertex.normal);
+    let tangent = vec4(normalize(skin3 * vertex.tangent.xyz), vertex.tangent.w);
+
+    // Read from the mesh info, `MeshPool::defragment` moves the posed copy.
+    let idx = u32(meshes[mesh.mesh_id].vertex_offset) + global_id.x - mesh.rest_offset;
+    vertices[idx * 3u + 0u] = position.x;
+    vertices[idx * 3u + 1u] = position.y;
+    vertices[idx * 3u + 2u] = position.z;
+    normals[idx * 3u + 0u] = normal.x;
+    normals[idx * 3u + 1u] = normal.y;
+    normals[idx * 3u + 2u] = normal.z;
+    tangents[idx] = tangent;
+
+    // The min is inverted, so zero is the empty bounds for both.
+    let base = vertex.skinned_mesh * 6u;
+    atomicMax(&bounds[base + 0u], ~order_f32(position.x));
+    atomicMax(&bounds[base + 1u], ~order_f32(position.y));
+    atomicMax(&bounds[base + 2u], ~order_f32(position.z));
+    atomicMax(&bounds[base + 3u], order_f32(position.x));
+    atomicMax(&bounds[base + 4u], order_f32(position.y));
+    atomicMax(&bounds[base + 5u], order_f32(position.z));
+}
+
+// Refits the culling bounds of the posed copies to the vertices written by `cs_main`.
+@compute
+@workgroup_size(64, 1, 1)
+fn cs_bounds(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    if global_id.x >= arrayLength(&skinned_meshes) {
+        return;
+    }
+    let base = global_id.x * 6u;
+    let max_bits = vec3(atomicLoad(&bounds[base + 3u]), atomicLoad(&bounds[base + 4u]), atomicLoad(&bounds[base + 5u]));
+    if all(max_bits == vec3(0u)) {
+        return;
+    }
+    let lo = vec3(
+        unorder_f32(~atomicLoad(&bounds[base + 0u])),
+        unorder_f32(~atomicLoad(&bounds[base + 1u])),
+        unorder_f32(~atomicLoad(&bounds[base + 2u])),
+    );
+    let hi = vec3(unorder_f32(max_bits.x), unorder_f32(max_bits.y), unorder_f32(max_bits.z));
+
+    let mesh_id = skinned_meshes[global_id.x].mesh_id;
+    var info = meshes[mesh_id];
+    info.min = lo;
+    info.max = hi;
+    info.center = (lo + hi) / 2.;
+    info.radius = length(hi - lo) / 2.;
+    meshes[mesh_id] = info;
+}
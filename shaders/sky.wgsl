@@ -0,0 +1,116 @@
+#import "shared.wgsl"
+#import <voidin/camera.wgsl>
+#import <voidin/math.wgsl>
+
+struct SkySettings {
+    turbidity: f32,
+    exposure: f32,
+    cubemap: u32,
+    padding: u32,
+}
+
+@group(0) @binding(0) var<uniform> camera: Camera;
+@group(1) @binding(0) var<uniform> settings: SkySettings;
+@group(1) @binding(1) var t_cubemap: texture_cube<f32>;
+@group(1) @binding(2) var t_sampler: sampler;
+@group(2) @binding(0) var<uniform> sun: DirectionalLight;
+
+// Angular radius of the sun, a bit larger than the real one to stay visible after TAA.
+const SUN_ANGULAR_RADIUS: f32 = 0.01;
+
+struct VertexOutput {
+  @builtin(position) pos: vec4<f32>,
+  @location(0) uv: vec2<f32>,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_idx: u32) -> VertexOutput {
+    var out: VertexOutput;
+    out.uv = vec2<f32>(vec2((vertex_idx << 1u) & 2u, vertex_idx & 2u));
+    // Sits on the cleared far plane of the reversed depth.
+    out.pos = vec4(2.0 * out.uv.x - 1.0, 1. - out.uv.y * 2., 0.0, 1.0);
+    return out;
+}
+
+fn perez(theta: f32, gamma: f32, a: f32, b: f32, c: f32, d: f32, e: f32) -> f32 {
+    let cos_gamma = cos(gamma);
+    return (1. + a * exp(b / max(cos(theta), 0.01))) * (1. + c * exp(d * gamma) + e * cos_gamma * cos_gamma);
+}
+
+// A Practical Analytic Model for Daylight, Preetham et al. 1999.
+fn preetham(dir: vec3<f32>, sun_dir: vec3<f32>, t: f32) -> vec3<f32> {
+    let theta = acos(clamp(dir.y, 0., 1.));
+    let theta_s = acos(clamp(sun_dir.y, 0., 1.));
+    let gamma = acos(clamp(dot(dir, sun_dir), -1., 1.));
+
+    let chi = (4. / 9. - t / 120.) * (PI - 2. * theta_s);
+    let zenith_y = (4.0453 * t - 4.9710) * tan(chi) - 0.2155 * t + 2.4192;
+
+    let ts = vec3(theta_s * theta_s * theta_s, theta_s * theta_s, theta_s);
+    let t_vec = vec3(t * t, t, 1.);
+    let zenith_x = dot(t_vec, vec3(
+        dot(vec4(ts, 0.), vec4(0.00166, -0.00375, 0.00209, 0.)),
+        dot(vec4(ts, 1.), vec4(-0.02903, 0.06377, -0.03202, 0.00394)),
+        dot(vec4(ts, 1.), vec4(0.11693, -0.21196, 0.06052, 0.25886)),
+    ));
+    let zenith_yy = dot(t_vec, vec3(
+        dot(vec4(ts, 0.), vec4(0.00275, -0.00610, 0.00317, 0.)),
+        dot(vec4(ts, 1.), vec4(-0.04214, 0.08970, -0.04153, 0.00516)),
+        dot(vec4(ts, 1.), vec4(0.15346, -0.26756, 0.06670, 0.26688)),
+    ));
+
+    let lum_a = 0.1787 * t - 1.4630;
+    let lum_b = -0.3554 * t + 0.4275;
+    let lum_c = -0.0227 * t + 5.3251;
+    let lum_d = 0.1206 * t - 2.5771;
+    let lum_e = -0.0670 * t + 0.3703;
+
+    let x_a = -0.0193 * t - 0.2592;
+    let x_b = -0.0665 * t + 0.0008;
+    let x_c = -0.0004 * t + 0.2125;
+    let x_d = -0.0641 * t - 0.8989;
+    let x_e = -0.0033 * t + 0.0452;
+
+    let y_a = -0.0167 * t - 0.2608;
+    let y_b = -0.0950 * t + 0.0092;
+    let y_c = -0.0079 * t + 0.2102;
+    let y_d = -0.0441 * t - 1.6537;
+    let y_e = -0.0109 * t + 0.0529;
+
+    let lum = zenith_y * perez(theta, gamma, lum_a, lum_b, lum_c, lum_d, lum_e)
+        / perez(0., theta_s, lum_a, lum_b, lum_c, lum_d, lum_e);
+    let x = zenith_x * perez(theta, gamma, x_a, x_b, x_c, x_d, x_e)
+        / perez(0., theta_s, x_a, x_b, x_c, x_d, x_e);
+    let y = zenith_yy * perez(theta, gamma, y_a, y_b, y_c, y_d, y_e)
+        / perez(0., theta_s, y_a, y_b, y_c, y_d, y_e);
+
+    let xyz = vec3(x / y * lum, lum, (1. - x - y) / y * lum);
+    let xyz_to_rgb = mat3x3(
+        3.2406, -0.9689, 0.0557,
+        -1.5372, 1.8758, -0.2040,
+        -0.4986, 0.0415, 1.0570,
+    );
+    return max(xyz_to_rgb * xyz, vec3(0.));
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let far = world_position_from_depth(in.uv, 1., camera.clip_to_world);
+    let dir = normalize(far - camera.position.xyz);
+
+    if settings.cubemap != 0u {
+        return vec4(textureSampleLevel(t_cubemap, t_sampler, dir, 0.).rgb, 1.);
+    }
+
+    let sun_dir = normalize(sun.direction);
+    var color = preetham(dir, sun_dir, settings.turbidity) * settings.exposure;
+
+    let sun_disk = smoothstep(cos(SUN_ANGULAR_RADIUS * 1.2), cos(SUN_ANGULAR_RADIUS), dot(dir, sun_dir));
+    color += sun.color * sun.intensity * sun_disk;
+
+    // The model is only defined above the horizon, fade to a dim ground below it.
+    let ground = preetham(vec3(0., 0., 1.), sun_dir, settings.turbidity) * settings.exposure * 0.1;
+    color = mix(ground, color, smoothstep(-0.05, 0.02, dir.y));
+
+    return vec4(color, 1.);
+}
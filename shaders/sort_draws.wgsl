@@ -0,0 +1,23 @@
+// Copies the draws written by `emit_draws.wgsl` in material order, so instances sharing
+// a material and a mesh are drawn back to back.
+#import "shared.wgsl"
+
+@group(0) @binding(0) var<storage, read> draws: array<DrawIndexedIndirect>;
+// Instance indices sorted by material, then by mesh.
+@group(0) @binding(1) var<storage, read> order: array<u32>;
+@group(0) @binding(2) var<storage, read_write> sorted: array<DrawIndexedIndirect>;
+
+@compute
+@workgroup_size(64, 1, 1)
+fn sort(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let i = global_id.x;
+    if i >= arrayLength(&order) || i >= arrayLength(&sorted) {
+        return;
+    }
+    let index = order[i];
+    if index < arrayLength(&draws) {
+        sorted[i] = draws[index];
+    } else {
+        sorted[i] = DrawIndexedIndirect(0u, 0u, 0u, 0, 0u);
+    }
+}
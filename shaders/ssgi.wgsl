@@ -0,0 +1,98 @@
+#import "shared.wgsl"
+#import <voidin/math.wgsl>
+#import <voidin/camera.wgsl>
+#import <voidin/random.wgsl>
+#import "utils/gbuffer.wgsl"
+#import "utils/hiz.wgsl"
+
+@group(0) @binding(0) var<uniform> global: Globals;
+@group(0) @binding(1) var<uniform> camera: Camera;
+
+@group(1) @binding(0) var t_gbuffer: texture_2d<u32>;
+@group(1) @binding(1) var t_depth: texture_depth_2d;
+
+@group(2) @binding(0) var t_hiz: texture_2d<f32>;
+
+struct SsgiSettings {
+    intensity: f32,
+    radius: f32,
+    thickness: f32,
+    ray_count: u32,
+}
+
+@group(3) @binding(0) var t_radiance: texture_2d<f32>;
+@group(3) @binding(1) var t_history: texture_2d<f32>;
+@group(3) @binding(2) var t_out: texture_storage_2d<rgba16float, write>;
+@group(3) @binding(3) var t_sampler: sampler;
+@group(3) @binding(4) var<uniform> settings: SsgiSettings;
+
+const MAX_STEPS = 48u;
+// Frames averaged at most, lower reacts faster to lighting changes.
+const HISTORY_LENGTH = 16.0;
+
+fn random_unit_vector(r: vec2<f32>) -> vec3<f32> {
+    let z = 1.0 - 2.0 * r.y;
+    let a = TAU * r.x;
+    let s = sqrt(max(0.0, 1.0 - z * z));
+    return vec3(s * cos(a), s * sin(a), z);
+}
+
+fn prev_frame_uv(pos: vec3<f32>) -> vec2<f32> {
+    let clip = camera.prev_world_to_clip * vec4(pos, 1.0);
+    return cs_to_uv(clip.xy / clip.w);
+}
+
+fn in_screen(uv: vec2<f32>) -> bool {
+    return all(uv >= vec2(0.)) && all(uv <= vec2(1.));
+}
+
+@compute
+@workgroup_size(8, 8, 1)
+fn cs_main(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let dims = textureDimensions(t_out);
+    if any(global_id.xy >= dims) {
+        return;
+    }
+    let pix = vec2<i32>(global_id.xy);
+    let uv = get_uv_comp(global_id, dims);
+
+    let depth = textureLoad(t_depth, pix, 0);
+    if depth == 0.0 {
+        textureStore(t_out, pix, vec4(0.));
+        return;
+    }
+
+    let pos = world_position_from_depth(uv, depth, camera.clip_to_world);
+    let nor = unpack_gbuffer(textureLoad(t_gbuffer, pix, 0).xy).normal;
+    // Keeps rays from hitting the surface they start on.
+    let origin = pos + nor * 0.02;
+
+    var irradiance = vec3(0.);
+    for (var i = 0u; i < settings.ray_count; i += 1u) {
+        let seed = f32(global.frame * settings.ray_count + i);
+        let rnd = hash33(vec3(vec2<f32>(pix), seed));
+        let dir = normalize(nor + random_unit_vector(rnd.xy));
+
+        let hit = hiz_trace(origin, dir, settings.radius, settings.thickness, MAX_STEPS);
+        if !hit.hit {
+            continue;
+        }
+        // Radiance is last frame's image, reproject the hit into it.
+        let hit_pos = world_position_from_depth(hit.uv, hit.depth, camera.clip_to_world);
+        let hit_uv = prev_frame_uv(hit_pos);
+        if in_screen(hit_uv) {
+            irradiance += textureSampleLevel(t_radiance, t_sampler, hit_uv, 0.).rgb;
+        }
+    }
+    irradiance /= f32(max(settings.ray_count, 1u));
+
+    // Alpha counts the frames accumulated so far.
+    let history_uv = prev_frame_uv(pos);
+    var history = vec4(0.);
+    if in_screen(history_uv) {
+        history = textureSampleLevel(t_history, t_sampler, history_uv, 0.);
+    }
+    let count = min(history.a + 1.0, HISTORY_LENGTH);
+    let result = mix(history.rgb, irradiance, 1.0 / count);
+    textureStore(t_out, pix, vec4(result, count));
+}
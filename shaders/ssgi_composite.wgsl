@@ -0,0 +1,51 @@
+#import "shared.wgsl"
+#import "utils/gbuffer.wgsl"
+
+@group(0) @binding(0) var<uniform> global: Globals;
+@group(0) @binding(1) var<uniform> camera: Camera;
+
+@group(1) @binding(0) var t_gbuffer: texture_2d<u32>;
+@group(1) @binding(1) var t_depth: texture_depth_2d;
+@group(1) @binding(2) var t_sampler: sampler;
+
+@group(2) @binding(0) var texture_array: binding_array<texture_2d<f32>>;
+
+@group(3) @binding(0) var<storage, read> materials: array<Material>;
+
+struct SsgiSettings {
+    intensity: f32,
+    radius: f32,
+    thickness: f32,
+    ray_count: u32,
+}
+
+@group(4) @binding(0) var t_irradiance: texture_2d<f32>;
+@group(4) @binding(1) var<uniform> settings: SsgiSettings;
+
+struct VertexOutput {
+  @builtin(position) pos: vec4<f32>,
+  @location(0) uv: vec2<f32>,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_idx: u32) -> VertexOutput {
+    var out: VertexOutput;
+    out.uv = vec2<f32>(vec2((vertex_idx << 1u) & 2u, vertex_idx & 2u));
+    out.pos = vec4(2.0 * out.uv.x - 1.0, 1. - out.uv.y * 2., 0.0, 1.0);
+    return out;
+}
+
+// Added on top of the shaded image, the ambient term shading leaves out.
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let load_uv = vec2<u32>(in.pos.xy);
+
+    let depth = textureLoad(t_depth, load_uv, 0);
+    let gbuffer = unpack_gbuffer(textureLoad(t_gbuffer, load_uv, 0).xy);
+    let material = materials[gbuffer.material_id];
+    let albedo = textureSampleBias(texture_array[material.albedo], t_sampler, gbuffer.uv, camera.mip_bias).rgb;
+    let irradiance = textureLoad(t_irradiance, load_uv, 0).rgb;
+
+    let lit = depth > 0.0 && gbuffer.material_id != LIGHT_MATERIAL;
+    return vec4(select(vec3(0.), albedo * irradiance * settings.intensity, lit), 1.0);
+}
@@ -1,5 +1,6 @@
-#import "utils/uv.wgsl"
-#import "utils/color.wgsl"
+#import <voidin/camera.wgsl>
+#import <voidin/math.wgsl>
+#import <voidin/color.wgsl>
 
 @group(0) @binding(0) var t_sampler: sampler;
 @group(1) @binding(0) var t_input: texture_2d<f32>;
@@ -8,6 +9,14 @@
 
 @group(4) @binding(0) var t_output: texture_storage_2d<rgba16float, write>;
 
+struct TaaSettings {
+    blend: f32,
+    clamp_gamma: f32,
+    sharpness: f32,
+    anti_flicker: f32,
+}
+@group(5) @binding(0) var<uniform> settings: TaaSettings;
+
 fn mitchell_netravali(x: f32) -> f32 {
     let B = 1.0 / 3.0;
     let C = 1.0 / 3.0;
@@ -40,6 +49,53 @@ fn fetch_center_filtered(pix: vec2<i32>) -> vec3<f32> {
 }
 
 
+// Catmull-Rom filtered history in 5 bilinear taps, the corner taps are dropped.
+fn sample_history_catmull_rom(uv: vec2<f32>, dims: vec2<f32>) -> vec3<f32> {
+    let sample_pos = uv * dims;
+    let tex_pos1 = floor(sample_pos - 0.5) + 0.5;
+    let f = sample_pos - tex_pos1;
+
+    let w0 = f * (-0.5 + f * (1.0 - 0.5 * f));
+    let w1 = 1.0 + f * f * (-2.5 + 1.5 * f);
+    let w2 = f * (0.5 + f * (2.0 - 1.5 * f));
+    let w3 = f * f * (-0.5 + 0.5 * f);
+    let w12 = w1 + w2;
+
+    let tex_pos0 = (tex_pos1 - 1.0) / dims;
+    let tex_pos3 = (tex_pos1 + 2.0) / dims;
+    let tex_pos12 = (tex_pos1 + w2 / w12) / dims;
+
+    var res = vec3(0.);
+    var wsum = 0.;
+    var w = w12.x * w0.y;
+    res += textureSampleLevel(t_history, t_sampler, vec2(tex_pos12.x, tex_pos0.y), 0.).rgb * w;
+    wsum += w;
+    w = w0.x * w12.y;
+    res += textureSampleLevel(t_history, t_sampler, vec2(tex_pos0.x, tex_pos12.y), 0.).rgb * w;
+    wsum += w;
+    w = w12.x * w12.y;
+    res += textureSampleLevel(t_history, t_sampler, tex_pos12, 0.).rgb * w;
+    wsum += w;
+    w = w3.x * w12.y;
+    res += textureSampleLevel(t_history, t_sampler, vec2(tex_pos3.x, tex_pos12.y), 0.).rgb * w;
+    wsum += w;
+    w = w12.x * w3.y;
+    res += textureSampleLevel(t_history, t_sampler, vec2(tex_pos12.x, tex_pos3.y), 0.).rgb * w;
+    wsum += w;
+
+    return max(res / wsum, vec3(0.));
+}
+
+// Moves `history` towards the box center until it is inside,
+// keeps its hue where a per channel clamp would not.
+fn clip_aabb(aabb_min: vec3<f32>, aabb_max: vec3<f32>, history: vec3<f32>) -> vec3<f32> {
+    let center = 0.5 * (aabb_max + aabb_min);
+    let extents = 0.5 * (aabb_max - aabb_min) + 1e-5;
+    let offset = history - center;
+    let t = saturate(min_element(extents / max(abs(offset), vec3(1e-7))));
+    return center + offset * t;
+}
+
 @compute
 @workgroup_size(8, 8, 1)
 fn cs_main(@builtin(global_invocation_id) global_id: vec3<u32>) {
@@ -50,10 +106,9 @@ fn cs_main(@builtin(global_invocation_id) global_id: vec3<u32>) {
     let velocity = textureLoad(t_motion, pix, 0);
     let history_uv = uv - velocity.xy * 0.5 * vec2(1., -1.);
 
-    var history = textureSampleLevel(t_history, t_sampler, history_uv, 0.).rgb;
-    history = rgb_to_ycbcr(history);
-    var center = textureLoad(t_input, pix, 0).rgb;
-    center = rgb_to_ycbcr(center);
+    var history = sample_history_catmull_rom(history_uv, vec2<f32>(dims));
+    history = rgb_to_ycocg(history);
+    let raw_center = rgb_to_ycocg(textureLoad(t_input, pix, 0).rgb);
 
     var vsum = vec3(0.);
     var vsum2 = vec3(0.);
@@ -62,7 +117,7 @@ fn cs_main(@builtin(global_invocation_id) global_id: vec3<u32>) {
     for (var y = -k; y <= k; y += 1) {
         for (var x = -k; x <= k; x += 1) {
             var neigh = textureLoad(t_input, pix + vec2(x, y), 0).rgb;
-            neigh = rgb_to_ycbcr(neigh);
+            neigh = rgb_to_ycocg(neigh);
 
             let w = exp(-3.0 * f32(x * x + y * y) / f32((k + 1) * (k + 1)));
             vsum += neigh * w;
@@ -84,21 +139,32 @@ fn cs_main(@builtin(global_invocation_id) global_id: vec3<u32>) {
     box_size *= mix(0.5, 1.0, smoothstep(-0.1, 0.3, local_contrast));
     box_size *= mix(0.5, 1.0, clamp(1.0 - texel_center_dist, 0.0, 1.0));
 
-    center = fetch_center_filtered(pix);
-    center = rgb_to_ycbcr(center);
+    var center = fetch_center_filtered(pix);
+    center = rgb_to_ycocg(center);
 
-    let n_deviations = 1.5;
-    let nmin = mix(center, ex, box_size * box_size) - dev * box_size * n_deviations;
-    let nmax = mix(center, ex, box_size * box_size) + dev * box_size * n_deviations;
+    let box_center = mix(center, ex, box_size * box_size);
+    let nmin = box_center - dev * box_size * settings.clamp_gamma;
+    let nmax = box_center + dev * box_size * settings.clamp_gamma;
 
-    let clamped_history = clamp(history, nmin, nmax);
-    var blend_factor = mix(1.0, 1.0 / 12.0, velocity.z);
+    let clipped_history = clip_aabb(nmin, nmax, history);
+    var blend_factor = mix(1.0, settings.blend, velocity.z);
 
     let clamp_dist = (min(abs(history.x - nmin.x), abs(history.x - nmax.x))) / max(max(history.x, ex.x), 1e-5);
     blend_factor *= mix(0.2, 1.0, smoothstep(0.0, 2.0, clamp_dist));
 
-    var result = mix(clamped_history, center, blend_factor);
-    result = ycbcr_to_rgb(result);
+    // Small luma changes are sub-pixel detail flickering in and out, lean on history there.
+    let luma_diff = abs(center.x - clipped_history.x) / max(max(center.x, clipped_history.x), 0.2);
+    let unbiased = 1.0 - luma_diff;
+    blend_factor *= 1.0 - 0.5 * settings.anti_flicker * unbiased * unbiased;
+
+    // Weighting by inverse luma keeps bright outliers from dominating the average.
+    let center_weight = blend_factor / (1.0 + center.x);
+    let history_weight = (1.0 - blend_factor) / (1.0 + clipped_history.x);
+    var result = (center * center_weight + clipped_history * history_weight) / (center_weight + history_weight);
+
+    // Scaled by the blend factor so the sharpening does not pile up in the history.
+    result.x += settings.sharpness * blend_factor * (raw_center.x - ex.x);
+    result = max(ycocg_to_rgb(result), vec3(0.));
 
     textureStore(t_output, global_id.xy, vec4(result, 1.));
 }
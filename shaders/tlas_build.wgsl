@@ -0,0 +1,205 @@
+#import "shared.wgsl"
+
+struct BuildParams {
+    count: u32,
+    padded: u32,
+    // Block and compare distance of the bitonic sort stage.
+    k: u32,
+    j: u32,
+}
+
+struct Aabb {
+    min: vec4<f32>,
+    max: vec4<f32>,
+}
+
+// Same as in `<voidin/bvh.wgsl>`, which can't be imported without its bindings.
+struct TlasNode {
+    min: vec3<f32>,
+    left_right: u32,
+    max: vec3<f32>,
+    instance_idx: u32,
+}
+
+@group(0) @binding(0) var<uniform> params: BuildParams;
+@group(0) @binding(1) var<storage, read> instances: array<Instance>;
+@group(0) @binding(2) var<storage, read> meshes: array<MeshInfo>;
+@group(0) @binding(3) var<storage, read_write> aabbs: array<Aabb>;
+@group(0) @binding(4) var<storage, read_write> keys: array<vec2<u32>>;
+@group(0) @binding(5) var<storage, read_write> scene: Aabb;
+@group(0) @binding(6) var<storage, read_write> nodes: array<TlasNode>;
+
+const BIG: f32 = 1e30;
+
+@compute @workgroup_size(64)
+fn instance_bounds(@builtin(global_invocation_id) id: vec3<u32>) {
+    let i = id.x;
+    if i >= params.count {
+        return;
+    }
+    let instance = instances[i];
+    let mesh = meshes[instance.mesh_id];
+    let center = (mesh.min + mesh.max) * 0.5;
+    let extent = (mesh.max - mesh.min) * 0.5;
+    let m = instance.transform;
+    let world_center = (m * vec4(center, 1.0)).xyz;
+    let world_extent = abs(m[0].xyz) * extent.x + abs(m[1].xyz) * extent.y + abs(m[2].xyz) * extent.z;
+    aabbs[i] = Aabb(vec4(world_center - world_extent, 0.0), vec4(world_center + world_extent, 0.0));
+}
+
+var<workgroup> lows: array<vec3<f32>, 256>;
+var<workgroup> highs: array<vec3<f32>, 256>;
+
+// Bounds of the instance centers, a single workgroup.
+@compute @workgroup_size(256)
+fn scene_bounds(@builtin(local_invocation_index) local: u32) {
+    var lo = vec3(BIG);
+    var hi = vec3(-BIG);
+    for (var i = local; i < params.count; i += 256u) {
+        let centroid = (aabbs[i].min.xyz + aabbs[i].max.xyz) * 0.5;
+        lo = min(lo, centroid);
+        hi = max(hi, centroid);
+    }
+    lows[local] = lo;
+    highs[local] = hi;
+    workgroupBarrier();
+
+    for (var stride = 128u; stride > 0u; stride >>= 1u) {
+        if local < stride {
+            lows[local] = min(lows[local], lows[local + stride]);
+            highs[local] = max(highs[local], highs[local + stride]);
+        }
+        workgroupBarrier();
+    }
+    if local == 0u {
+        scene = Aabb(vec4(lows[0], 0.0), vec4(highs[0], 0.0));
+    }
+}
+
+fn expand_bits(v: u32) -> u32 {
+    var x = v & 0x3ffu;
+    x = (x | (x << 16u)) & 0x030000ffu;
+    x = (x | (x << 8u)) & 0x0300f00fu;
+    x = (x | (x << 4u)) & 0x030c30c3u;
+    x = (x | (x << 2u)) & 0x09249249u;
+    return x;
+}
+
+// 30 bit morton codes of the centers next to the instance index, the padding sorts last.
+@compute @workgroup_size(64)
+fn morton(@builtin(global_invocation_id) id: vec3<u32>) {
+    let i = id.x;
+    if i >= params.padded {
+        return;
+    }
+    if i >= params.count {
+        keys[i] = vec2(0xffffffffu);
+        return;
+    }
+    let centroid = (aabbs[i].min.xyz + aabbs[i].max.xyz) * 0.5;
+    let extent = max(scene.max.xyz - scene.min.xyz, vec3(1e-6));
+    let p = clamp((centroid - scene.min.xyz) / extent, vec3(0.0), vec3(1.0));
+    let q = vec3<u32>(p * 1023.0);
+    let code = (expand_bits(q.x) << 2u) | (expand_bits(q.y) << 1u) | expand_bits(q.z);
+    keys[i] = vec2(code, i);
+}
+
+fn key_less(a: vec2<u32>, b: vec2<u32>) -> bool {
+    return a.x < b.x || (a.x == b.x && a.y < b.y);
+}
+
+// One compare and swap stage of the bitonic sort.
+@compute @workgroup_size(64)
+fn bitonic(@builtin(global_invocation_id) id: vec3<u32>) {
+    let i = id.x;
+    let l = i ^ params.j;
+    if i >= params.padded || l <= i {
+        return;
+    }
+    let a = keys[i];
+    let b = keys[l];
+    let ascending = (i & params.k) == 0u;
+    if key_less(b, a) == ascending {
+        keys[i] = b;
+        keys[l] = a;
+    }
+}
+
+// Leaves go after the `count - 1` internal nodes, in sorted order.
+@compute @workgroup_size(64)
+fn leaves(@builtin(global_invocation_id) id: vec3<u32>) {
+    let i = id.x;
+    if i >= params.count {
+        return;
+    }
+    let instance = keys[i].y;
+    let aabb = aabbs[instance];
+    nodes[params.count - 1u + i] = TlasNode(aabb.min.xyz, 0u, aabb.max.xyz, instance);
+}
+
+// Length of the common prefix of two sorted keys, equal codes fall back to the index.
+fn delta(i: i32, j: i32) -> i32 {
+    if j < 0 || j >= i32(params.count) {
+        return -1;
+    }
+    let a = keys[i];
+    let b = keys[j];
+    if a.x == b.x {
+        return 32 + i32(countLeadingZeros(a.y ^ b.y));
+    }
+    return i32(countLeadingZeros(a.x ^ b.x));
+}
+
+// Internal node `i` of Karras' "Maximizing Parallelism in the Construction of BVHs".
+@compute @workgroup_size(64)
+fn internal(@builtin(global_invocation_id) id: vec3<u32>) {
+    let n = i32(params.count);
+    let i = i32(id.x);
+    if i >= n - 1 {
+        return;
+    }
+
+    // Direction and other end of the range of keys covered by the node.
+    let d = select(-1, 1, delta(i, i + 1) > delta(i, i - 1));
+    let delta_min = delta(i, i - d);
+    var l_max = 2;
+    while delta(i, i + l_max * d) > delta_min {
+        l_max *= 2;
+    }
+    var l = 0;
+    for (var t = l_max / 2; t > 0; t /= 2) {
+        if delta(i, i + (l + t) * d) > delta_min {
+            l += t;
+        }
+    }
+    let j = i + l * d;
+
+    // Where the highest differing bit of the range flips.
+    let delta_node = delta(i, j);
+    var s = 0;
+    var t = l;
+    loop {
+        t = (t + 1) / 2;
+        if delta(i, i + (s + t) * d) > delta_node {
+            s += t;
+        }
+        if t <= 1 {
+            break;
+        }
+    }
+    let split = i + s * d + min(d, 0);
+
+    let first = min(i, j);
+    let last = max(i, j);
+    let left = select(u32(split), u32(n - 1 + split), first == split);
+    let right = select(u32(split + 1), u32(n + split), last == split + 1);
+
+    var lo = vec3(BIG);
+    var hi = vec3(-BIG);
+    for (var k = first; k <= last; k++) {
+        let aabb = aabbs[keys[k].y];
+        lo = min(lo, aabb.min.xyz);
+        hi = max(hi, aabb.max.xyz);
+    }
+    nodes[i] = TlasNode(lo, left | (right << 16u), hi, 0xffffffffu);
+}
@@ -0,0 +1,35 @@
+// Froxel grid of `pools::LightPool`, filled by `shaders/light_cull.wgsl`.
+//
+// Tiles split the screen evenly, slices split view depth exponentially between
+// `CLUSTER_NEAR` and `CLUSTER_FAR`. The first slice covers everything closer, the last one
+// everything further. Sizes match `LightPool::CLUSTER_GRID` and `MAX_LIGHTS_PER_CLUSTER`.
+
+const CLUSTER_GRID: vec3<u32> = vec3(16u, 9u, 24u);
+const MAX_LIGHTS_PER_CLUSTER: u32 = 128u;
+const CLUSTER_NEAR: f32 = 0.1;
+const CLUSTER_FAR: f32 = 1000.;
+
+// View depth where `slice` starts.
+fn cluster_slice_depth(slice: u32) -> f32 {
+    if slice == 0u {
+        return 0.;
+    }
+    let t = f32(slice - 1u) / f32(CLUSTER_GRID.z - 2u);
+    return CLUSTER_NEAR * pow(CLUSTER_FAR / CLUSTER_NEAR, t);
+}
+
+fn cluster_slice(depth: f32) -> u32 {
+    if depth < CLUSTER_NEAR {
+        return 0u;
+    }
+    if depth >= CLUSTER_FAR {
+        return CLUSTER_GRID.z - 1u;
+    }
+    let t = log(depth / CLUSTER_NEAR) / log(CLUSTER_FAR / CLUSTER_NEAR);
+    return min(u32(t * f32(CLUSTER_GRID.z - 2u)) + 1u, CLUSTER_GRID.z - 1u);
+}
+
+fn cluster_index(uv: vec2<f32>, depth: f32) -> u32 {
+    let tile = min(vec2<u32>(uv * vec2<f32>(CLUSTER_GRID.xy)), CLUSTER_GRID.xy - 1u);
+    return (cluster_slice(depth) * CLUSTER_GRID.y + tile.y) * CLUSTER_GRID.x + tile.x;
+}
@@ -0,0 +1,57 @@
+// Layout of the packed Rg32Uint target of the G-buffer, `GBuffer` in `app/gbuffer.rs` lists
+// the other targets next to it:
+//
+//   x: bits  0..12  octahedral normal, u
+//      bits 12..24  octahedral normal, v
+//      bits 24..32  material id
+//   y: texture uv, pack2x16float
+//
+// Albedo, roughness, metallic and the rest are fetched through the material id and uv
+// during shading, so they take no space here. Position comes from depth.
+
+const GBUFFER_NORMAL_BITS = 12u;
+
+struct GBufferSample {
+    normal: vec3<f32>,
+    uv: vec2<f32>,
+    material_id: u32,
+}
+
+fn encode_octahedral_24(normal: vec3<f32>) -> u32 {
+    var nor = normal / (abs(normal.x) + abs(normal.y) + abs(normal.z));
+    if nor.z < 0.0 {
+        let xy = (1.0 - abs(nor.yx)) * sign(nor.xy);
+        nor = vec3(xy, nor.z);
+    }
+    let v = nor.xy * 0.5 + 0.5;
+
+    let mu = (1u << GBUFFER_NORMAL_BITS) - 1u;
+    let d = vec2<u32>(floor(v * f32(mu) + 0.5));
+    return (d.y << GBUFFER_NORMAL_BITS) | d.x;
+}
+
+fn decode_octahedral_24(data: u32) -> vec3<f32> {
+    let mu = (1u << GBUFFER_NORMAL_BITS) - 1u;
+    let d = vec2<u32>(data, data >> GBUFFER_NORMAL_BITS) & vec2(mu);
+    var v = vec2<f32>(d) / f32(mu);
+
+    v = v * 2.0 - 1.0;
+    var nor = vec3(v, 1.0 - abs(v.x) - abs(v.y));
+    let t = max(-nor.z, 0.0);
+    if nor.x > 0.0 { nor.x += -t; } else { nor.x += t; }
+    if nor.y > 0.0 { nor.y += -t; } else { nor.y += t; }
+    return normalize(nor);
+}
+
+fn pack_gbuffer(normal: vec3<f32>, uv: vec2<f32>, material_id: u32) -> vec2<u32> {
+    let x = encode_octahedral_24(normal) | ((material_id & 0xffu) << 24u);
+    return vec2(x, pack2x16float(uv));
+}
+
+fn unpack_gbuffer(data: vec2<u32>) -> GBufferSample {
+    return GBufferSample(
+        decode_octahedral_24(data.x & 0xffffffu),
+        unpack2x16float(data.y),
+        data.x >> 24u,
+    );
+}
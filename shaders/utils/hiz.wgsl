@@ -0,0 +1,103 @@
+// Hi-Z accelerated screen space ray marching, the pyramid is built by `pass::hiz::HiZ`.
+//
+// The including shader declares `camera: Camera` and `t_hiz: texture_2d<f32>`
+// bound to `HiZ::bind_group`, and imports `<voidin/math.wgsl>`.
+// Depth is reversed, a bigger value is closer to the camera.
+
+struct HizHit {
+    hit: bool,
+    uv: vec2<f32>,
+    depth: f32,
+}
+
+// Screen space position, uv in xy and raw depth in z.
+// Both change linearly along a world space segment, so the ray stays a line.
+fn hiz_project(pos: vec3<f32>) -> vec3<f32> {
+    let clip = camera.proj * camera.view * vec4(pos, 1.0);
+    let ndc = clip.xyz / clip.w;
+    return vec3(ndc.xy * vec2(0.5, -0.5) + 0.5, ndc.z);
+}
+
+// Assumes the infinite reversed projection of `Camera`.
+fn hiz_linear_depth(raw_depth: f32) -> f32 {
+    return camera.znear / max(raw_depth, EPS);
+}
+
+// Ray parameter at which `start + delta * t` leaves the cell it is in at `t`.
+fn hiz_cell_exit(start: vec3<f32>, delta: vec3<f32>, t: f32, cell_count: vec2<f32>) -> f32 {
+    let pos = (start.xy + delta.xy * t) * cell_count;
+    let boundary = floor(pos) + step(vec2(0.), delta.xy);
+    let dist = (boundary / cell_count - start.xy) / delta.xy;
+    // Axis aligned rays never cross the other axis.
+    let dist_x = select(MAX_DIST, dist.x, abs(delta.x) > EPS);
+    let dist_y = select(MAX_DIST, dist.y, abs(delta.y) > EPS);
+    // Nudge past the boundary so the next lookup lands in the neighbour.
+    return min(dist_x, dist_y) + 1e-4;
+}
+
+// Marches the world space ray `origin + dir * t` for `t < max_dist` against the depth buffer.
+// Surfaces are assumed `thickness` units deep, rays passing further behind them miss.
+fn hiz_trace(origin: vec3<f32>, dir: vec3<f32>, max_dist: f32, thickness: f32, max_steps: u32) -> HizHit {
+    let miss = HizHit(false, vec2(0.), 0.);
+
+    // Rays heading towards the camera are clipped at the near plane.
+    let view_origin = (camera.view * vec4(origin, 1.0)).xyz;
+    let view_dir = mat4_to_mat3(camera.view) * dir;
+    var dist = max_dist;
+    if view_dir.z > EPS {
+        dist = min(dist, 0.99 * (-camera.znear - view_origin.z) / view_dir.z);
+    }
+    if dist <= 0. {
+        return miss;
+    }
+
+    let start = hiz_project(origin);
+    let delta = hiz_project(origin + dir * dist) - start;
+
+    let level_count = i32(textureNumLevels(t_hiz));
+    let base_count = vec2<f32>(textureDimensions(t_hiz, 0));
+    var level = 0;
+    var t = hiz_cell_exit(start, delta, 0., base_count);
+    for (var i = 0u; i < max_steps; i += 1u) {
+        if level < 0 || t >= 1.0 {
+            break;
+        }
+        let pos = start + delta * t;
+        if any(pos.xy < vec2(0.)) || any(pos.xy >= vec2(1.)) {
+            return miss;
+        }
+
+        let cell_count = vec2<f32>(textureDimensions(t_hiz, level));
+        let cell = vec2<i32>(pos.xy * cell_count);
+        let closest = textureLoad(t_hiz, cell, level).r;
+        let t_exit = hiz_cell_exit(start, delta, t, cell_count);
+
+        if pos.z > closest {
+            // In front of everything in the cell, unless it dives behind the closest depth.
+            var t_plane = MAX_DIST;
+            if delta.z < 0. {
+                t_plane = (closest - start.z) / delta.z;
+            }
+            if t_plane < t_exit {
+                t = max(t, t_plane);
+                level -= 1;
+            } else {
+                t = t_exit;
+                level = min(level + 1, level_count - 1);
+            }
+        } else if level > 0 {
+            level -= 1;
+        } else if hiz_linear_depth(pos.z) - hiz_linear_depth(closest) < thickness {
+            level -= 1;
+        } else {
+            // Passes behind a thin surface.
+            t = t_exit;
+        }
+    }
+
+    if level >= 0 {
+        return miss;
+    }
+    let pos = start + delta * t;
+    return HizHit(true, pos.xy, pos.z);
+}
@@ -0,0 +1,22 @@
+// Uvs into an IES profile baked by `app::IesProfile`, sampled from the bindless textures
+// at `Light::ies` or `SpotLight::ies`. Lights without a profile point at the white texture.
+//
+// u runs around the axis, starting from `cross(axis, +Z)`, or `cross(axis, +X)` for axes
+// along Z. v runs from the axis, the nadir of the luminaire, to the opposite pole.
+#import <voidin/math.wgsl>
+
+const IES_SIZE: vec2<u32> = vec2(128u, 64u);
+
+// `dir` points from the light towards the shaded point.
+fn ies_uv(dir: vec3<f32>, axis: vec3<f32>) -> vec2<f32> {
+    let reference = select(vec3(0., 0., 1.), vec3(1., 0., 0.), abs(axis.z) > 0.99);
+    let tangent = normalize(cross(axis, reference));
+    let bitangent = cross(axis, tangent);
+    let vertical = acos(clamp(dot(dir, axis), -1., 1.));
+    let horizontal = atan2(dot(dir, bitangent), dot(dir, tangent));
+    let u = fract(horizontal / (2. * PI));
+    // The poles don't wrap, keep the filter inside the first and last rows.
+    let half_texel = 0.5 / f32(IES_SIZE.y);
+    let v = clamp(vertical / PI, half_texel, 1. - half_texel);
+    return vec2(u, v);
+}
@@ -0,0 +1,56 @@
+// Octahedral imposter atlases, baked and drawn by `app::imposters::Imposters`.
+//
+// The atlas is a grid of `frames` x `frames` orthographic views of the mesh bounding
+// sphere. Each cell looks at the mesh from the direction the cell center maps to
+// through the octahedral encoding over the full sphere. `Imposters::cell_view` builds the
+// bake views from the same mapping on the CPU.
+
+struct ImposterInfo {
+    center: vec3<f32>,
+    radius: f32,
+    albedo: u32,
+    normal: u32,
+    material_id: u32,
+    frames: u32,
+}
+
+fn imposter_dir_to_uv(dir: vec3<f32>) -> vec2<f32> {
+    var nor = dir / (abs(dir.x) + abs(dir.y) + abs(dir.z));
+    if nor.z < 0.0 {
+        let xy = (1.0 - abs(nor.yx)) * select(vec2(-1.), vec2(1.), nor.xy >= vec2(0.));
+        nor = vec3(xy, nor.z);
+    }
+    return nor.xy * 0.5 + 0.5;
+}
+
+fn imposter_uv_to_dir(uv: vec2<f32>) -> vec3<f32> {
+    let v = uv * 2.0 - 1.0;
+    var nor = vec3(v, 1.0 - abs(v.x) - abs(v.y));
+    let t = max(-nor.z, 0.0);
+    nor.x += select(t, -t, nor.x >= 0.0);
+    nor.y += select(t, -t, nor.y >= 0.0);
+    return normalize(nor);
+}
+
+// Cell of the atlas whose view is closest to `dir`, pointing from the mesh to the viewer.
+fn imposter_frame(dir: vec3<f32>, frames: u32) -> vec2<u32> {
+    let cell = vec2<u32>(imposter_dir_to_uv(dir) * f32(frames));
+    return min(cell, vec2(frames - 1u));
+}
+
+fn imposter_frame_dir(cell: vec2<u32>, frames: u32) -> vec3<f32> {
+    return imposter_uv_to_dir((vec2<f32>(cell) + 0.5) / f32(frames));
+}
+
+// Right, up and view direction of the camera a cell was baked with.
+fn imposter_basis(dir: vec3<f32>) -> mat3x3<f32> {
+    let up_hint = select(vec3(0., 1., 0.), vec3(0., 0., 1.), abs(dir.y) > 0.999);
+    let right = normalize(cross(up_hint, dir));
+    let up = cross(dir, right);
+    return mat3x3(right, up, dir);
+}
+
+// Atlas uv of a point on the cell's view plane, `local` spans -1..1 over the bounding sphere.
+fn imposter_atlas_uv(cell: vec2<u32>, frames: u32, local: vec2<f32>) -> vec2<f32> {
+    return (vec2<f32>(cell) + local * vec2(0.5, -0.5) + 0.5) / f32(frames);
+}
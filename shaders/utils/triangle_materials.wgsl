@@ -0,0 +1,12 @@
+// Per-triangle materials of meshes merged by `pools::MeshPool::merge_with_materials`.
+//
+// The including shader declares `meshes: array<MeshInfo>` and `triangle_materials: array<u32>`.
+// Merged meshes share no vertices, so `local_vertex / 3` is the triangle the vertex belongs to.
+
+fn vertex_material(instance: Instance, local_vertex: u32) -> u32 {
+    let mesh = meshes[instance.mesh_id];
+    if mesh.triangle_materials == NO_TRIANGLE_MATERIALS {
+        return instance.material_id;
+    }
+    return triangle_materials[mesh.triangle_materials + local_vertex / 3u];
+}
@@ -0,0 +1,66 @@
+#import "shared.wgsl"
+#import "utils/triangle_materials.wgsl"
+
+@group(0) @binding(0) var<uniform> camera: Camera;
+@group(1) @binding(0) var texture_array: binding_array<texture_2d<f32>>;
+@group(1) @binding(1) var tex_sampler: sampler;
+@group(1) @binding(2) var tex_int_sampler: sampler;
+
+@group(2) @binding(0) var<storage, read_write> instances: array<Instance>;
+@group(3) @binding(0) var<storage, read> materials: array<Material>;
+@group(4) @binding(0) var<storage, read> meshes: array<MeshInfo>;
+@group(4) @binding(1) var<storage, read> triangle_materials: array<u32>;
+
+// Only positions and uvs are read, the rest of the attributes are fetched
+// by `vis_buffer_resolve.wgsl` for the visible triangle of every pixel.
+struct VertexInput {
+	@builtin(instance_index) instance_index: u32,
+	@builtin(vertex_index) vertex_index: u32,
+    @location(0) position: vec3<f32>,
+    @location(3) tex_coords: vec2<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) @interpolate(flat) instance_index: u32,
+    @location(2) @interpolate(flat) material_id: u32,
+}
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    let instance = instances[in.instance_index];
+    let world_pos = instance.transform * vec4(in.position, 1.0);
+
+    var out: VertexOutput;
+    out.clip_position = camera.proj * camera.view * world_pos;
+    out.uv = in.tex_coords;
+    out.instance_index = in.instance_index;
+    let local_vertex = in.vertex_index - u32(meshes[instance.mesh_id].vertex_offset);
+    out.material_id = vertex_material(instance, local_vertex);
+    return out;
+}
+
+struct FragmentOutput {
+    // x: instance index + 1, zero is left for the background
+    // y: triangle index within the mesh
+    @location(0) ids: vec2<u32>,
+    @location(1) history_reject: u32,
+    // Same as `ids.x`, written to the G-buffer for picking.
+    @location(2) instance_id: u32,
+}
+
+@fragment
+fn fs_main(in: VertexOutput, @builtin(primitive_index) triangle: u32) -> FragmentOutput {
+    let instance = instances[in.instance_index];
+    let material = materials[in.material_id];
+    let albedo_tex = textureSampleBias(texture_array[material.albedo], tex_sampler, in.uv, camera.mip_bias);
+    if material.base_color.w < 0.5 || albedo_tex.a < 0.5 {
+     	 discard;
+    }
+    return FragmentOutput(
+        vec2(in.instance_index + 1u, triangle),
+        instance.flags & INSTANCE_CHANGED,
+        in.instance_index + 1u,
+    );
+}
@@ -0,0 +1,143 @@
+#import "shared.wgsl"
+#import <voidin/math.wgsl>
+#import <voidin/camera.wgsl>
+#import "utils/gbuffer.wgsl"
+#import "utils/triangle_materials.wgsl"
+
+@group(0) @binding(0) var<uniform> camera: Camera;
+
+@group(1) @binding(0) var t_ids: texture_2d<u32>;
+@group(1) @binding(1) var t_gbuffer: texture_storage_2d<rg32uint, write>;
+
+@group(2) @binding(0) var<storage, read_write> instances: array<Instance>;
+
+@group(3) @binding(0) var<storage, read> meshes: array<MeshInfo>;
+@group(3) @binding(1) var<storage, read> indices: array<u32>;
+@group(3) @binding(2) var<storage, read> vertices: array<f32>;
+@group(3) @binding(3) var<storage, read> normals: array<f32>;
+@group(3) @binding(4) var<storage, read> tangents: array<vec4<f32>>;
+@group(3) @binding(5) var<storage, read> tex_coords: array<vec2<f32>>;
+@group(3) @binding(6) var<storage, read> triangle_materials: array<u32>;
+
+@group(4) @binding(0) var<storage, read> materials: array<Material>;
+
+@group(5) @binding(0) var texture_array: binding_array<texture_2d<f32>>;
+@group(5) @binding(1) var tex_sampler: sampler;
+
+struct Barycentrics {
+    lambda: vec3<f32>,
+    ddx: vec3<f32>,
+    ddy: vec3<f32>,
+}
+
+// Perspective correct barycentrics of `ndc` and their screen space derivatives,
+// computed analytically from the clip space triangle.
+fn barycentrics(p0: vec4<f32>, p1: vec4<f32>, p2: vec4<f32>, ndc: vec2<f32>, dims: vec2<f32>) -> Barycentrics {
+    let inv_w = 1.0 / vec3(p0.w, p1.w, p2.w);
+    let ndc0 = p0.xy * inv_w.x;
+    let ndc1 = p1.xy * inv_w.y;
+    let ndc2 = p2.xy * inv_w.z;
+
+    let inv_det = 1.0 / determinant(mat2x2(ndc2 - ndc1, ndc0 - ndc1));
+    var ddx = vec3(ndc1.y - ndc2.y, ndc2.y - ndc0.y, ndc0.y - ndc1.y) * inv_det * inv_w;
+    var ddy = vec3(ndc2.x - ndc1.x, ndc0.x - ndc2.x, ndc1.x - ndc0.x) * inv_det * inv_w;
+    var ddx_sum = dot(ddx, vec3(1.));
+    var ddy_sum = dot(ddy, vec3(1.));
+
+    let delta = ndc - ndc0;
+    let interp_inv_w = inv_w.x + delta.x * ddx_sum + delta.y * ddy_sum;
+    let interp_w = 1.0 / interp_inv_w;
+    let lambda = interp_w * (vec3(inv_w.x, 0., 0.) + delta.x * ddx + delta.y * ddy);
+
+    // One pixel in ndc, y points down in screen space.
+    let px = 2.0 / dims.x;
+    let py = -2.0 / dims.y;
+    ddx *= px;
+    ddy *= py;
+    ddx_sum *= px;
+    ddy_sum *= py;
+
+    let interp_w_ddx = 1.0 / (interp_inv_w + ddx_sum);
+    let interp_w_ddy = 1.0 / (interp_inv_w + ddy_sum);
+    ddx = interp_w_ddx * (lambda * interp_inv_w + ddx) - lambda;
+    ddy = interp_w_ddy * (lambda * interp_inv_w + ddy) - lambda;
+
+    return Barycentrics(lambda, ddx, ddy);
+}
+
+fn fetch_position(i: u32) -> vec3<f32> {
+    return vec3(vertices[3u * i + 0u], vertices[3u * i + 1u], vertices[3u * i + 2u]);
+}
+
+fn fetch_normal(i: u32) -> vec3<f32> {
+    return vec3(normals[3u * i + 0u], normals[3u * i + 1u], normals[3u * i + 2u]);
+}
+
+fn interpolate2(b: vec3<f32>, a0: vec2<f32>, a1: vec2<f32>, a2: vec2<f32>) -> vec2<f32> {
+    return a0 * b.x + a1 * b.y + a2 * b.z;
+}
+
+fn interpolate3(b: vec3<f32>, a0: vec3<f32>, a1: vec3<f32>, a2: vec3<f32>) -> vec3<f32> {
+    return a0 * b.x + a1 * b.y + a2 * b.z;
+}
+
+fn interpolate4(b: vec3<f32>, a0: vec4<f32>, a1: vec4<f32>, a2: vec4<f32>) -> vec4<f32> {
+    return a0 * b.x + a1 * b.y + a2 * b.z;
+}
+
+@compute
+@workgroup_size(8, 8, 1)
+fn cs_main(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let dims = textureDimensions(t_ids);
+    if any(global_id.xy >= dims) {
+        return;
+    }
+    let pix = vec2<i32>(global_id.xy);
+
+    let ids = textureLoad(t_ids, pix, 0).xy;
+    if ids.x == 0u {
+        textureStore(t_gbuffer, pix, vec4(0u));
+        return;
+    }
+
+    let instance = instances[ids.x - 1u];
+    let mesh = meshes[instance.mesh_id];
+    let base = mesh.base_index + 3u * ids.y;
+    let i0 = u32(mesh.vertex_offset) + indices[base + 0u];
+    let i1 = u32(mesh.vertex_offset) + indices[base + 1u];
+    let i2 = u32(mesh.vertex_offset) + indices[base + 2u];
+
+    let world_to_clip = camera.proj * camera.view * instance.transform;
+    let p0 = world_to_clip * vec4(fetch_position(i0), 1.0);
+    let p1 = world_to_clip * vec4(fetch_position(i1), 1.0);
+    let p2 = world_to_clip * vec4(fetch_position(i2), 1.0);
+
+    let ndc = uv_to_cs(get_uv_comp(global_id, dims));
+    let bary = barycentrics(p0, p1, p2, ndc, vec2<f32>(dims));
+
+    let uv0 = tex_coords[i0];
+    let uv1 = tex_coords[i1];
+    let uv2 = tex_coords[i2];
+    let uv = interpolate2(bary.lambda, uv0, uv1, uv2);
+    // Scaling the gradients by 2^bias shifts the selected mip by the bias.
+    let lod_scale = exp2(camera.mip_bias);
+    let uv_ddx = interpolate2(bary.ddx, uv0, uv1, uv2) * lod_scale;
+    let uv_ddy = interpolate2(bary.ddy, uv0, uv1, uv2) * lod_scale;
+
+    let transform = mat4_to_mat3(instance.transform);
+    var normal = normalize(transform * interpolate3(bary.lambda, fetch_normal(i0), fetch_normal(i1), fetch_normal(i2)));
+
+    let material_id = vertex_material(instance, indices[base]);
+    let material = materials[material_id];
+    if material.normal != 0u {
+        let tangent = interpolate4(bary.lambda, tangents[i0], tangents[i1], tangents[i2]);
+        let t = normalize(transform * tangent.xyz);
+        let b = cross(normal, t) * tangent.w;
+        let tbn = mat3x3(t, normalize(b), normal);
+
+        let normal_tex = textureSampleGrad(texture_array[material.normal], tex_sampler, uv, uv_ddx, uv_ddy);
+        normal = normalize(tbn * (normal_tex.rgb * 2.0 - 1.0));
+    }
+
+    textureStore(t_gbuffer, pix, vec4(pack_gbuffer(normal, uv, material_id), 0u, 0u));
+}
@@ -1,6 +1,8 @@
 #import "shared.wgsl"
-#import "utils/math.wgsl"
-#import "utils/encoding.wgsl"
+#import <voidin/math.wgsl>
+#import <voidin/packing.wgsl>
+#import "utils/gbuffer.wgsl"
+#import "utils/triangle_materials.wgsl"
 
 @group(0) @binding(0) var<uniform> camera: Camera;
 @group(1) @binding(0) var texture_array: binding_array<texture_2d<f32>>;
@@ -10,9 +12,12 @@
 // FIXME: add more bind groups for only read storage
 @group(2) @binding(0) var<storage, read_write> instances: array<Instance>;
 @group(3) @binding(0) var<storage, read> materials: array<Material>;
+@group(4) @binding(0) var<storage, read> meshes: array<MeshInfo>;
+@group(4) @binding(1) var<storage, read> triangle_materials: array<u32>;
 
 struct VertexInput {
 	@builtin(instance_index) instance_index: u32,
+	@builtin(vertex_index) vertex_index: u32,
     @location(0) position: vec3<f32>,
     @location(1) normal: vec3<f32>,
     @location(2) tangent: vec4<f32>,
@@ -26,6 +31,8 @@ struct VertexOutput {
     @location(3) bitangent: vec3<f32>,
     @location(4) uv: vec2<f32>,
     @location(5) @interpolate(flat) material_id: u32,
+    @location(6) @interpolate(flat) history_reject: u32,
+    @location(7) @interpolate(flat) instance_id: u32,
 }
 
 @vertex
@@ -45,14 +52,19 @@ fn vs_main(in: VertexInput) -> VertexOutput {
     out.bitangent = cross(out.normal, out.tangent) * in.tangent.w;
 
     out.uv = in.tex_coords;
-    out.material_id = instance.material_id;
+    let local_vertex = in.vertex_index - u32(meshes[instance.mesh_id].vertex_offset);
+    out.material_id = vertex_material(instance, local_vertex);
+    out.history_reject = instance.flags & INSTANCE_CHANGED;
+    out.instance_id = in.instance_index + 1u;
 
     return out;
 }
 
 struct FragmentOutput {
-    @location(0) normal_uv: vec2<u32>,
-    @location(1) @interpolate(flat) material: u32,
+    @location(0) gbuffer: vec2<u32>,
+    @location(1) history_reject: u32,
+    // Instance index + 1, zero is left for the background.
+    @location(2) instance_id: u32,
 }
 
 fn get_tbn(normal: vec3<f32>, tangent: vec3<f32>, bitangent: vec3<f32>) -> mat3x3<f32> {
@@ -63,12 +75,33 @@ fn get_tbn(normal: vec3<f32>, tangent: vec3<f32>, bitangent: vec3<f32>) -> mat3x
     );
 }
 
+// Depth pre-pass, only the alpha test has to match `fs_main`.
+@fragment
+fn fs_depth(in: VertexOutput) {
+    let material = materials[in.material_id];
+    let albedo_tex = textureSampleBias(texture_array[material.albedo], tex_sampler, in.uv, camera.mip_bias);
+    if material.base_color.w < 0.5 || albedo_tex.a < 0.5 {
+     	 discard;
+    }
+}
+
+// Counts every fragment passing the alpha test, depth isn't tested. See `GBuffer::overdraw`.
+@fragment
+fn fs_overdraw(in: VertexOutput) -> @location(0) vec4<f32> {
+    let material = materials[in.material_id];
+    let albedo_tex = textureSampleBias(texture_array[material.albedo], tex_sampler, in.uv, camera.mip_bias);
+    if material.base_color.w < 0.5 || albedo_tex.a < 0.5 {
+     	 discard;
+    }
+    return vec4(1.);
+}
+
 @fragment
 fn fs_main(in: VertexOutput) -> FragmentOutput {
     let uv = in.uv;
     let material = materials[in.material_id];
-    let albedo_tex = textureSample(texture_array[material.albedo], tex_sampler, uv);
-    let normal_tex = textureSample(texture_array[material.normal], tex_sampler, uv);
+    let albedo_tex = textureSampleBias(texture_array[material.albedo], tex_sampler, uv, camera.mip_bias);
+    let normal_tex = textureSampleBias(texture_array[material.normal], tex_sampler, uv, camera.mip_bias);
 
     if material.base_color.w < 0.5 || albedo_tex.a < 0.5 {
      	 discard;
@@ -82,10 +115,5 @@ fn fs_main(in: VertexOutput) -> FragmentOutput {
         normal = normalize(tbn * (normal_tex.rgb * 2.0 - 1.0));
     }
 
-    let packed_norm = encode_octahedral_32(normal);
-
-    return FragmentOutput(
-        vec2(packed_norm, pack2x16float(in.uv)),
-        in.material_id
-    );
+    return FragmentOutput(pack_gbuffer(normal, in.uv, in.material_id), in.history_reject, in.instance_id);
 }
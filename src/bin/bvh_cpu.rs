@@ -36,7 +36,7 @@ impl Example for Demo {
             usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC,
             mapped_at_creation: false,
         });
-        let mut rng = rand::thread_rng();
+        let mut rng = app.world.get_mut::<SceneRng>()?;
         let n = 64;
         let mut vertices = Vec::with_capacity(n * 3);
         for _ in 0..n {
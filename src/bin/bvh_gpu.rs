@@ -1,18 +1,69 @@
 use std::time::Duration;
 
-use app::MeshInfo;
-use bvh::{BvhNode, Tlas, TlasNode};
+use bvh::{FrameDiff, ReferenceTracer, SceneRef};
 use color_eyre::Result;
 use voidin::*;
 
+// Keep in sync with `bvh_trace.wgsl`.
+const REFERENCE_SIZE: u32 = 96;
+
 #[allow(dead_code)]
 struct Demo {
     pipeline: RenderHandle,
 
-    tlas: Tlas,
-    tlas_nodes: ResizableBuffer<TlasNode>,
+    tlas_build: pass::tlas_build::TlasBuild,
+    gpu_build: bool,
+    // Bunnies circling the dragon, with their transform at rest.
+    orbiting: Vec<(InstanceId, Mat4)>,
+
+    reference_pipeline: ComputeHandle,
+    reference: ResizableBuffer<Vec4>,
+    reference_bind_group: wgpu::BindGroup,
+    compare: bool,
+    diff: Option<FrameDiff>,
+}
+
+impl Demo {
+    /// Traces the same low resolution frame on the gpu and with `bvh::ReferenceTracer`.
+    fn compare_with_reference(&self, ctx: &RenderContext) -> FrameDiff {
+        let arena = ctx.world.unwrap::<PipelineArena>();
+        let camera = ctx.world.unwrap::<CameraUniformBinding>();
+        let scene = ctx.world.unwrap::<SceneBindings>();
+        let mut encoder = ctx.gpu.device().create_command_encoder(&Default::default());
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Reference Trace Pass"),
+            });
+            pass.set_pipeline(arena.get_pipeline(self.reference_pipeline));
+            pass.set_bind_group(0, camera.binding(), &[]);
+            pass.set_bind_group(1, scene.bind_group(), &[]);
+            pass.set_bind_group(2, &self.reference_bind_group, &[]);
+            let groups = (REFERENCE_SIZE + 7) / 8;
+            pass.dispatch_workgroups(groups, groups, 1);
+        }
+        ctx.gpu.queue().submit(Some(encoder.finish()));
+        let gpu_frame = self.reference.read(ctx.gpu);
+
+        let meshes = ctx.world.unwrap::<MeshPool>();
+        let instances = ctx.world.unwrap::<InstancePool>();
+        let (vertices, indices) = (meshes.vertices.read(ctx.gpu), meshes.indices.read(ctx.gpu));
+        let bvh_nodes = meshes.bvh_nodes.read(ctx.gpu);
+        let scene = SceneRef {
+            tlas_nodes: &meshes.tlas.nodes,
+            instances: &instances.instances_data,
+            meshes: &meshes.mesh_info_cpu,
+            bvh_nodes: &bvh_nodes,
+            vertices: &vertices,
+            indices: &indices,
+        };
+        let clip_to_world = ctx.world.unwrap::<CameraUniform>().clip_to_world;
+        let cpu_frame =
+            ReferenceTracer::new(REFERENCE_SIZE, REFERENCE_SIZE).render(&scene, clip_to_world);
 
-    geometry_bind_group: wgpu::BindGroup,
+        let diff = FrameDiff::new(&cpu_frame, &gpu_frame);
+        log::info!("Gpu trace against cpu reference: {diff:?}");
+        diff
+    }
 }
 
 impl Example for Demo {
@@ -21,73 +72,6 @@ impl Example for Demo {
     }
 
     fn init(app: &mut App) -> Result<Self> {
-        let geometry_bgl =
-            app.device()
-                .create_bind_group_layout_wrap(&wgpu::BindGroupLayoutDescriptor {
-                    label: Some("Trace BGL"),
-                    entries: &[
-                        wgpu::BindGroupLayoutEntry {
-                            binding: 0,
-                            visibility: wgpu::ShaderStages::FRAGMENT,
-                            ty: wgpu::BindingType::Buffer {
-                                ty: wgpu::BufferBindingType::Storage { read_only: true },
-                                has_dynamic_offset: false,
-                                min_binding_size: Some(TlasNode::NSIZE),
-                            },
-                            count: None,
-                        },
-                        wgpu::BindGroupLayoutEntry {
-                            binding: 1,
-                            visibility: wgpu::ShaderStages::FRAGMENT,
-                            ty: wgpu::BindingType::Buffer {
-                                ty: wgpu::BufferBindingType::Storage { read_only: true },
-                                has_dynamic_offset: false,
-                                min_binding_size: Some(Instance::NSIZE),
-                            },
-                            count: None,
-                        },
-                        wgpu::BindGroupLayoutEntry {
-                            binding: 2,
-                            visibility: wgpu::ShaderStages::FRAGMENT,
-                            ty: wgpu::BindingType::Buffer {
-                                ty: wgpu::BufferBindingType::Storage { read_only: true },
-                                has_dynamic_offset: false,
-                                min_binding_size: Some(MeshInfo::NSIZE),
-                            },
-                            count: None,
-                        },
-                        wgpu::BindGroupLayoutEntry {
-                            binding: 3,
-                            visibility: wgpu::ShaderStages::FRAGMENT,
-                            ty: wgpu::BindingType::Buffer {
-                                ty: wgpu::BufferBindingType::Storage { read_only: true },
-                                has_dynamic_offset: false,
-                                min_binding_size: Some(BvhNode::NSIZE),
-                            },
-                            count: None,
-                        },
-                        wgpu::BindGroupLayoutEntry {
-                            binding: 4,
-                            visibility: wgpu::ShaderStages::FRAGMENT,
-                            ty: wgpu::BindingType::Buffer {
-                                ty: wgpu::BufferBindingType::Storage { read_only: true },
-                                has_dynamic_offset: false,
-                                min_binding_size: Some(f32::NSIZE),
-                            },
-                            count: None,
-                        },
-                        wgpu::BindGroupLayoutEntry {
-                            binding: 5,
-                            visibility: wgpu::ShaderStages::FRAGMENT,
-                            ty: wgpu::BindingType::Buffer {
-                                ty: wgpu::BufferBindingType::Storage { read_only: true },
-                                has_dynamic_offset: false,
-                                min_binding_size: Some(u32::NSIZE),
-                            },
-                            count: None,
-                        },
-                    ],
-                });
         let pipeline = {
             let camera_binding = app.world.get::<CameraUniformBinding>()?;
             app.get_pipeline_arena_mut()
@@ -96,7 +80,7 @@ impl Example for Demo {
                     pipeline::RenderPipelineDescriptor {
                         layout: vec![
                             camera_binding.bind_group_layout.clone(),
-                            geometry_bgl.clone(),
+                            app.world.get::<SceneBindings>()?.layout.clone(),
                         ],
                         depth_stencil: None,
                         ..Default::default()
@@ -104,6 +88,51 @@ impl Example for Demo {
                 )?
         };
 
+        let reference_bgl =
+            app.device()
+                .create_bind_group_layout_wrap(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some("Reference Trace BGL"),
+                    entries: &[wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: false },
+                            has_dynamic_offset: false,
+                            min_binding_size: Some(Vec4::NSIZE),
+                        },
+                        count: None,
+                    }],
+                });
+        let reference_pipeline = {
+            let camera_binding = app.world.get::<CameraUniformBinding>()?;
+            app.get_pipeline_arena_mut()
+                .process_compute_pipeline_from_path(
+                    "src/bin/bvh_trace.wgsl",
+                    pipeline::ComputePipelineDescriptor {
+                        label: Some("Reference Trace Pipeline".into()),
+                        layout: vec![
+                            camera_binding.bind_group_layout.clone(),
+                            app.world.get::<SceneBindings>()?.layout.clone(),
+                            reference_bgl.clone(),
+                        ],
+                        entry_point: "cs_reference".into(),
+                        ..Default::default()
+                    },
+                )?
+        };
+        let reference = app.device().create_resizable_buffer_init(
+            &vec![Vec4::ZERO; (REFERENCE_SIZE * REFERENCE_SIZE) as usize],
+            wgpu::BufferUsages::STORAGE,
+        );
+        let reference_bind_group = app.device().create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Reference Trace Bind Group"),
+            layout: &reference_bgl,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: reference.as_entire_binding(),
+            }],
+        });
+
         let mut instances = vec![];
         let dragon_mesh = models::ObjModel::import(app, "assets/dragon.obj")?;
         for (mesh, material) in dragon_mesh {
@@ -115,70 +144,78 @@ impl Example for Demo {
                 material,
             ));
         }
+        let static_count = instances.len();
         let bnuuy_mesh = models::ObjModel::import(app, "assets/bunny.obj")?;
         for [x, y] in [[8., 8.], [-8., 8.], [8., -8.], [-8., -8.]] {
             for (mesh, material) in &bnuuy_mesh {
-                instances.push(Instance::new(
-                    Mat4::from_translation(vec3(x, y, 0.)) * Mat4::from_scale(Vec3::splat(3.)),
-                    *mesh,
-                    *material,
-                ));
+                instances.push(
+                    Instance::new(
+                        Mat4::from_translation(vec3(x, y, 0.)) * Mat4::from_scale(Vec3::splat(3.)),
+                        *mesh,
+                        *material,
+                    )
+                    .dynamic(),
+                );
             }
         }
 
-        app.get_instance_pool_mut().add(&instances);
-        let mut tlas = Tlas::empty();
-        tlas.build(&instances, &app.get_mesh_pool().mesh_info_cpu);
-
-        let tlas_nodes = app
-            .device()
-            .create_resizable_buffer_init(&tlas.nodes, wgpu::BufferUsages::STORAGE);
-
-        let geometry_bind_group = app.device().create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("Geometry Bind Group"),
-            layout: &geometry_bgl,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: tlas_nodes.as_entire_binding(),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: app.get_instance_pool().instances.as_entire_binding(),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 2,
-                    resource: app.get_mesh_pool().mesh_info.as_entire_binding(),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 3,
-                    resource: app.get_mesh_pool().bvh_nodes.as_entire_binding(),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 4,
-                    resource: app.get_mesh_pool().vertices.as_entire_binding(),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 5,
-                    resource: app.get_mesh_pool().indices.as_entire_binding(),
-                },
-            ],
-        });
+        let ids = app.get_instance_pool_mut().add(&instances)?;
+        let orbiting = ids[static_count..]
+            .iter()
+            .zip(&instances[static_count..])
+            .map(|(&id, instance)| (id, instance.transform))
+            .collect();
+        let tlas_build = pass::tlas_build::TlasBuild::new(&app.world)?;
 
         Ok(Self {
             pipeline,
-            tlas,
-            tlas_nodes,
-            geometry_bind_group,
+            tlas_build,
+            gpu_build: false,
+            orbiting,
+
+            reference_pipeline,
+            reference,
+            reference_bind_group,
+            compare: false,
+            diff: None,
         })
     }
 
-    fn update(&mut self, _ctx: UpdateContext) {}
+    fn update(&mut self, ctx: UpdateContext) {
+        let rotation = Mat4::from_rotation_y(ctx.app_state.total_time as f32 * 0.5);
+        let mut instances = ctx.world.unwrap_mut::<InstancePool>();
+        for &(id, transform) in &self.orbiting {
+            instances.set_transform(id, rotation * transform);
+        }
+    }
 
     fn resize(&mut self, _gpu: &Gpu, _width: u32, _height: u32) {}
 
     fn render(&mut self, mut ctx: RenderContext) {
+        // The app rebuilt the dynamic part of its TLAS after `update` moved the bunnies.
+        // The gpu build overwrites the uploaded nodes, the cpu reference keeps tracing
+        // the cpu tree either way.
+        if self.gpu_build {
+            let meshes = ctx.world.unwrap::<MeshPool>();
+            let instance_count = ctx.world.unwrap::<InstancePool>().count() as u32;
+            match pass::tlas_build::TlasBuild::node_count(instance_count) {
+                Ok(count) if count <= meshes.tlas_nodes.len() => self.tlas_build.record(
+                    ctx.world,
+                    &mut ctx.encoder,
+                    pass::tlas_build::TlasBuildResources {
+                        nodes: &meshes.tlas_nodes,
+                    },
+                ),
+                _ => self.gpu_build = false,
+            }
+        }
+
+        if std::mem::take(&mut self.compare) {
+            self.diff = Some(self.compare_with_reference(&ctx));
+        }
+
         let camera = ctx.world.unwrap::<CameraUniformBinding>();
+        let scene = ctx.world.unwrap::<SceneBindings>();
         let arena = ctx.world.unwrap::<PipelineArena>();
         let mut pass = ctx.encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
             label: None,
@@ -199,17 +236,28 @@ impl Example for Demo {
         });
 
         pass.set_pipeline(arena.get_pipeline(self.pipeline));
-        pass.set_bind_group(0, &camera.binding, &[]);
-        pass.set_bind_group(1, &self.geometry_bind_group, &[]);
+        pass.set_bind_group(0, camera.binding(), &[]);
+        pass.set_bind_group(1, scene.bind_group(), &[]);
         pass.draw(0..3, 0..1);
         drop(pass);
 
+        let compare = &mut self.compare;
+        let gpu_build = &mut self.gpu_build;
+        let diff = &self.diff;
         ctx.ui(|egui_ctx| {
             egui::Window::new("debug").show(egui_ctx, |ui| {
                 ui.label(format!(
                     "Fps: {:.04?}",
                     Duration::from_secs_f64(ctx.app_state.dt)
                 ));
+                ui.checkbox(gpu_build, "Build TLAS on the gpu");
+                *compare = ui.button("Compare with cpu reference").clicked();
+                if let Some(diff) = diff {
+                    ui.label(format!("Hit mismatches: {}", diff.hit_mismatches));
+                    ui.label(format!("Max color error: {:.5}", diff.max_color_error));
+                    ui.label(format!("Rms color error: {:.5}", diff.rms_color_error));
+                    ui.label(format!("Max distance error: {:.5}", diff.max_dist_error));
+                }
             });
         });
     }
@@ -1,10 +1,10 @@
 #import "shared.wgsl"
-#import "utils/math.wgsl"
+#import <voidin/math.wgsl>
 
 var<private> BDEPTH: f32 = 0.;
 var<private> TDEPTH: f32 = -1.;
 
-const STACK_LEN: u32 = 24u;
+const STACK_LEN: u32 = 32u;
 struct Stack {
     arr: array<u32, STACK_LEN>,
 	head: u32,
@@ -246,3 +246,30 @@ fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
     color = pow(color, vec3(1.4545));
     return vec4(color, 1.0);
 }
+
+// Keep in sync with `bvh_gpu.rs`.
+const REFERENCE_SIZE = 96u;
+
+@group(2) @binding(0) var<storage, read_write> reference: array<vec4<f32>>;
+
+// Low resolution frame without the traversal heatmap, compared against `bvh::ReferenceTracer`.
+@compute
+@workgroup_size(8, 8, 1)
+fn cs_reference(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    if any(global_id.xy >= vec2(REFERENCE_SIZE)) {
+        return;
+    }
+    let pix = vec2(f32(global_id.x) + 0.5, f32(REFERENCE_SIZE - global_id.y) - 0.5);
+    let uv = pix / f32(REFERENCE_SIZE) * 2. - 1.;
+
+    let view_pos = cam.clip_to_world * vec4(uv, 1., 1.);
+    let view_dir = cam.clip_to_world * vec4(uv, 0., 1.);
+    let ray = ray_new(view_pos.xyz / view_pos.w, normalize(view_dir.xyz));
+
+    var color = vec4(vec3(0.13), MAX_DIST);
+    let res = traverse_tlas(ray);
+    if res.hit {
+        color = vec4(triangle_normal(res.v0, res.v1, res.v2) * 0.5 + 0.5, res.dist);
+    }
+    reference[global_id.y * REFERENCE_SIZE + global_id.x] = color;
+}
@@ -1,6 +1,6 @@
 use std::time::Duration;
 
-use app::GlobalsBindGroup;
+use app::{AudioBinding, GlobalsBindGroup};
 use color_eyre::Result;
 use voidin::*;
 
@@ -16,12 +16,17 @@ impl Example for Triangle {
     fn init(app: &mut App) -> Result<Self> {
         let camera = app.world.get::<CameraUniformBinding>()?;
         let globals = app.world.get::<GlobalsBindGroup>()?;
+        let audio = app.world.get::<AudioBinding>()?;
         let pipeline = app
             .get_pipeline_arena_mut()
             .process_render_pipeline_from_path(
                 "src/bin/fractal.wgsl",
                 pipeline::RenderPipelineDescriptor {
-                    layout: vec![globals.layout.clone(), camera.bind_group_layout.clone()],
+                    layout: vec![
+                        globals.layout.clone(),
+                        camera.bind_group_layout.clone(),
+                        audio.layout.clone(),
+                    ],
                     vertex: VertexState {
                         entry_point: "vs_main_trig".into(),
                         ..Default::default()
@@ -41,6 +46,7 @@ impl Example for Triangle {
         let arena = ctx.world.unwrap::<PipelineArena>();
         let globals = ctx.world.unwrap::<GlobalsBindGroup>();
         let camera = ctx.world.unwrap::<CameraUniformBinding>();
+        let audio = ctx.world.unwrap::<AudioBinding>();
         let mut pass = ctx.encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
             label: None,
             color_attachments: &[Some(wgpu::RenderPassColorAttachment {
@@ -60,8 +66,9 @@ impl Example for Triangle {
         });
 
         pass.set_pipeline(arena.get_pipeline(self.pipeline));
-        pass.set_bind_group(0, &globals.binding, &[]);
-        pass.set_bind_group(1, &camera.binding, &[]);
+        pass.set_bind_group(0, globals.binding(), &[]);
+        pass.set_bind_group(1, camera.binding(), &[]);
+        pass.set_bind_group(2, &audio.binding, &[]);
         pass.draw(0..3, 0..1);
         drop(pass);
 
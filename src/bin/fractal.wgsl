@@ -20,6 +20,7 @@ fn nmod3(a: vec3<f32>, b: f32) -> vec3<f32> {
 
 @group(0) @binding(0) var<uniform> global: Globals;
 @group(0) @binding(1) var<uniform> camera: Camera;
+@group(2) @binding(0) var<uniform> audio: Audio;
 
 struct VertexOutput {
   @builtin(position) pos: vec4<f32>,
@@ -87,6 +88,7 @@ fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
         // let nor = get_nor(pos);
         // color = nor * 0.5 + 0.5;
         color = vec3(res.z / 200.);
+        color *= 1. + audio.bands[0].x * 0.5 + audio.beat * 0.5;
     }
 
     return vec4(color, 1.);
@@ -0,0 +1,303 @@
+use std::{fmt::Write as _, time::Duration};
+
+use app::GlobalsBindGroup;
+use color_eyre::Result;
+use voidin::*;
+
+// Keep in sync with `furnace.wgsl`.
+const GRID: usize = 8;
+const ANGLE_STEPS: usize = 16;
+const CELLS: usize = GRID * GRID * ANGLE_STEPS;
+
+const REPORT_PATH: &str = "furnace.csv";
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct Settings {
+    base_color: [f32; 3],
+    sample_count: u32,
+    tolerance: f32,
+    highlight: u32,
+    padding: [u32; 2],
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            base_color: [1.; 3],
+            sample_count: 64,
+            tolerance: 1e-3,
+            highlight: 1,
+            padding: [0; 2],
+        }
+    }
+}
+
+/// White furnace test of the reference BRDF in `<voidin/brdf.wgsl>`.
+///
+/// The image shows a roughness by metallic sweep of spheres under a uniform white
+/// environment, press F3 to save it. The numeric check integrates the same BRDF over
+/// view angles with a fixed sample set and writes the directional albedo to `furnace.csv`.
+struct Furnace {
+    pipeline: RenderHandle,
+    albedo_pipeline: ComputeHandle,
+    settings: Settings,
+    settings_buffer: wgpu::Buffer,
+    settings_bind_group: wgpu::BindGroup,
+    albedo: ResizableBuffer<[f32; 4]>,
+    albedo_bind_group: wgpu::BindGroup,
+    report: Option<Report>,
+    rerun: bool,
+}
+
+struct Report {
+    max_albedo: f32,
+    min_albedo: f32,
+    failures: usize,
+}
+
+impl Furnace {
+    fn measure(&mut self, gpu: &Gpu, arena: &PipelineArena, globals: &GlobalsBindGroup) {
+        let mut encoder = gpu.device().create_command_encoder(&Default::default());
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Furnace Albedo Pass"),
+            });
+            pass.set_pipeline(arena.get_pipeline(self.albedo_pipeline));
+            pass.set_bind_group(0, globals.binding(), &[]);
+            pass.set_bind_group(1, &self.settings_bind_group, &[]);
+            pass.set_bind_group(2, &self.albedo_bind_group, &[]);
+            pass.dispatch_workgroups((CELLS as u32 + 63) / 64, 1, 1);
+        }
+        gpu.queue().submit(Some(encoder.finish()));
+        let albedo = self.albedo.read(gpu);
+
+        let limit = 1. + self.settings.tolerance;
+        let mut csv = String::from("roughness,metallic,n_dot_v,r,g,b\n");
+        let mut report = Report {
+            max_albedo: 0.,
+            min_albedo: f32::INFINITY,
+            failures: 0,
+        };
+        println!("Directional albedo, min..max over view angles");
+        println!("{:>9} {:>9} {:>17}", "roughness", "metallic", "albedo");
+        for (i, cell) in albedo.chunks_exact(ANGLE_STEPS).enumerate() {
+            let roughness = (i % GRID) as f32 / (GRID - 1) as f32;
+            let metallic = (i / GRID) as f32 / (GRID - 1) as f32;
+            let (mut min, mut max) = (f32::INFINITY, 0f32);
+            for &[r, g, b, n_dot_v] in cell {
+                let _ = writeln!(csv, "{roughness},{metallic},{n_dot_v},{r},{g},{b}");
+                min = min.min(r.min(g).min(b));
+                max = max.max(r.max(g).max(b));
+            }
+            let gain = max > limit;
+            report.failures += gain as usize;
+            report.min_albedo = report.min_albedo.min(min);
+            report.max_albedo = report.max_albedo.max(max);
+            println!(
+                "{roughness:>9.3} {metallic:>9.3} {min:>8.4}..{max:<8.4}{}",
+                if gain { " ENERGY GAIN" } else { "" }
+            );
+        }
+
+        match std::fs::write(REPORT_PATH, csv) {
+            Ok(()) => log::info!("Furnace report written to {REPORT_PATH}"),
+            Err(err) => log::error!("Failed to write {REPORT_PATH}: {err}"),
+        }
+        if report.failures > 0 {
+            log::warn!(
+                "{} of {} cells reflect more than {limit} of the incoming energy",
+                report.failures,
+                GRID * GRID
+            );
+        }
+        self.report = Some(report);
+    }
+}
+
+impl Example for Furnace {
+    fn name() -> &'static str {
+        "White Furnace"
+    }
+
+    fn init(app: &mut App) -> Result<Self> {
+        let globals = app.world.get::<GlobalsBindGroup>()?;
+        let device = app.device();
+
+        let settings_layout =
+            device.create_bind_group_layout_wrap(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Furnace Settings BGL"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+            });
+        let albedo_layout =
+            device.create_bind_group_layout_wrap(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Furnace Albedo BGL"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+            });
+
+        let settings = Settings::default();
+        let settings_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Furnace Settings"),
+            size: std::mem::size_of::<Settings>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        app.gpu
+            .queue()
+            .write_buffer(&settings_buffer, 0, bytemuck::bytes_of(&settings));
+        let settings_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Furnace Settings Bind Group"),
+            layout: &settings_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: settings_buffer.as_entire_binding(),
+            }],
+        });
+
+        let albedo =
+            device.create_resizable_buffer_init(&[[0f32; 4]; CELLS], wgpu::BufferUsages::STORAGE);
+        let albedo_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Furnace Albedo Bind Group"),
+            layout: &albedo_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: albedo.as_entire_binding(),
+            }],
+        });
+
+        let path = "src/bin/furnace.wgsl";
+        let mut arena = app.get_pipeline_arena_mut();
+        let pipeline = arena.process_render_pipeline_from_path(
+            path,
+            pipeline::RenderPipelineDescriptor {
+                layout: vec![globals.layout.clone(), settings_layout.clone()],
+                vertex: VertexState {
+                    entry_point: "vs_main_trig".into(),
+                    ..Default::default()
+                },
+                depth_stencil: None,
+                ..Default::default()
+            },
+        )?;
+        let albedo_pipeline = arena.process_compute_pipeline_from_path(
+            path,
+            pipeline::ComputePipelineDescriptor {
+                label: Some("Furnace Albedo Pipeline".into()),
+                layout: vec![globals.layout.clone(), settings_layout, albedo_layout],
+                ..Default::default()
+            },
+        )?;
+
+        let mut furnace = Self {
+            pipeline,
+            albedo_pipeline,
+            settings,
+            settings_buffer,
+            settings_bind_group,
+            albedo,
+            albedo_bind_group,
+            report: None,
+            rerun: false,
+        };
+        furnace.measure(&app.gpu, &arena, &globals);
+        Ok(furnace)
+    }
+
+    fn update(&mut self, _ctx: UpdateContext) {}
+
+    fn resize(&mut self, _gpu: &Gpu, _width: u32, _height: u32) {}
+
+    fn render(&mut self, mut ctx: RenderContext) {
+        let arena = ctx.world.unwrap::<PipelineArena>();
+        let globals = ctx.world.unwrap::<GlobalsBindGroup>();
+        ctx.gpu
+            .queue()
+            .write_buffer(&self.settings_buffer, 0, bytemuck::bytes_of(&self.settings));
+        if std::mem::take(&mut self.rerun) {
+            self.measure(ctx.gpu, &arena, &globals);
+        }
+
+        let mut pass = ctx.encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Furnace Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: ctx.view_target.main_view(),
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::WHITE),
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+        pass.set_pipeline(arena.get_pipeline(self.pipeline));
+        pass.set_bind_group(0, globals.binding(), &[]);
+        pass.set_bind_group(1, &self.settings_bind_group, &[]);
+        pass.draw(0..3, 0..1);
+        drop(pass);
+
+        let settings = &mut self.settings;
+        let report = &self.report;
+        let rerun = &mut self.rerun;
+        ctx.ui(|egui_ctx| {
+            egui::Window::new("Furnace").show(egui_ctx, |ui| {
+                ui.label(format!(
+                    "Fps: {:.04?}",
+                    Duration::from_secs_f64(ctx.app_state.dt)
+                ));
+                ui.label("Roughness grows to the right, metallic downwards");
+                ui.horizontal(|ui| {
+                    ui.label("Base color");
+                    ui.color_edit_button_rgb(&mut settings.base_color);
+                });
+                ui.add(egui::Slider::new(&mut settings.sample_count, 1..=1024).text("Samples"));
+                ui.add(
+                    egui::Slider::new(&mut settings.tolerance, 0.0..=0.1)
+                        .logarithmic(true)
+                        .text("Tolerance"),
+                );
+                let mut highlight = settings.highlight != 0;
+                ui.checkbox(&mut highlight, "Highlight energy gain");
+                settings.highlight = highlight as u32;
+
+                ui.separator();
+                if let Some(report) = report {
+                    ui.label(format!(
+                        "Albedo range: {:.4}..{:.4}",
+                        report.min_albedo, report.max_albedo
+                    ));
+                    ui.label(format!(
+                        "Cells gaining energy: {} / {}",
+                        report.failures,
+                        GRID * GRID
+                    ));
+                }
+                *rerun = ui.button("Run numeric check").clicked();
+                ui.label(format!(
+                    "Table in stdout and {REPORT_PATH}, F3 saves the image"
+                ));
+            });
+        });
+    }
+}
+
+fn main() -> Result<()> {
+    run_default::<Furnace>()
+}
@@ -0,0 +1,83 @@
+#import "shared.wgsl"
+#import <voidin/math.wgsl>
+#import <voidin/random.wgsl>
+#import <voidin/brdf.wgsl>
+
+// Keep in sync with `furnace.rs`.
+const GRID = 8u;
+const ANGLE_STEPS = 16u;
+
+struct Settings {
+    base_color: vec3<f32>,
+    sample_count: u32,
+    tolerance: f32,
+    highlight: u32,
+}
+
+@group(0) @binding(0) var<uniform> global: Globals;
+@group(1) @binding(0) var<uniform> settings: Settings;
+@group(2) @binding(0) var<storage, read_write> albedo: array<vec4<f32>>;
+
+fn cell_input(cell: vec2<u32>) -> BrdfInput {
+    let t = vec2<f32>(cell) / f32(GRID - 1u);
+    return BrdfInput(settings.base_color, t.x, t.y);
+}
+
+struct VertexOutput {
+  @builtin(position) pos: vec4<f32>,
+  @location(0) uv: vec2<f32>,
+};
+
+@vertex
+fn vs_main_trig(@builtin(vertex_index) vertex_idx: u32) -> VertexOutput {
+    let uv = vec2<f32>(vec2((vertex_idx << 1u) & 2u, vertex_idx & 2u));
+    let out = VertexOutput(vec4(2.0 * uv - 1.0, 0.0, 1.0), uv);
+    return out;
+}
+
+// Roughness grows to the right, metallic downwards. Every sphere is lit by a uniform
+// white environment of radiance 1, so an energy conserving white material vanishes
+// into the background, dark rims show lost energy and values above 1 created energy.
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let size = min(global.resolution.x, global.resolution.y);
+    let offset = (global.resolution - size) * 0.5;
+    let grid_uv = (in.pos.xy - offset) / size * f32(GRID);
+    if any(grid_uv < vec2(0.)) || any(grid_uv >= vec2(f32(GRID))) {
+        return vec4(1.);
+    }
+
+    let cell = vec2<u32>(grid_uv);
+    let p = (fract(grid_uv) * 2. - 1.) / 0.9;
+    let r2 = dot(p, p);
+    if r2 >= 1. {
+        return vec4(1.);
+    }
+    // Orthographic view along -z, the sphere normal gives n_dot_v directly.
+    let n_dot_v = max(sqrt(1. - r2), 1e-3);
+
+    let seed = in.pos.xy + f32(global.frame % 1024u);
+    let jitter = vec2(hash21(seed), hash21(seed.yx + 17.));
+    let color = directional_albedo(cell_input(cell), n_dot_v, settings.sample_count, jitter);
+
+    if settings.highlight != 0u && any(color > vec3(1. + settings.tolerance)) {
+        return vec4(1., 0., 0., 1.);
+    }
+    return vec4(color, 1.);
+}
+
+// One invocation per (roughness, metallic, view angle) cell with a fixed sample set,
+// the results are compared against 1 on the cpu.
+@compute
+@workgroup_size(64, 1, 1)
+fn cs_main(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let index = global_id.x;
+    if index >= GRID * GRID * ANGLE_STEPS {
+        return;
+    }
+    let angle = index % ANGLE_STEPS;
+    let cell = vec2(index / ANGLE_STEPS % GRID, index / (ANGLE_STEPS * GRID));
+    let n_dot_v = (f32(angle) + 0.5) / f32(ANGLE_STEPS);
+    let color = directional_albedo(cell_input(cell), n_dot_v, settings.sample_count * 16u, vec2(0.));
+    albedo[index] = vec4(color, n_dot_v);
+}
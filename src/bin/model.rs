@@ -9,15 +9,30 @@ struct Model {
     visibility_pass: pass::visibility::Visibility,
 
     shading_pass: pass::shading::ShadingPass,
+    shading_rate_pass: pass::shading_rate::ShadingRate,
+    sky_pass: pass::sky::Sky,
+
+    hiz_pass: pass::hiz::HiZ,
+    ssgi_pass: pass::ssgi::Ssgi,
+
+    stats_pass: pass::stats::SceneStats,
+    editor: Editor,
+    light_bounds: bool,
 
     postprocess_pass: pass::postprocess::PostProcess,
+    picking_neutral: bool,
 
     update_pass: pass::compute_update::ComputeUpdate,
+    interpolation_pass: pass::interpolate::InstanceInterpolation,
 
     taa_pass: pass::taa::Taa,
 
-    moving_instances: ResizableBuffer<InstanceId>,
+    // Slots of the instances animated by `ComputeUpdate`.
+    moving_instances: ResizableBuffer<u32>,
     moving_instances_bind_group: wgpu::BindGroup,
+
+    // The orbiting point light, then the area lights and a spot light.
+    lights: Vec<LightId>,
 }
 
 impl Example for Model {
@@ -26,23 +41,38 @@ impl Example for Model {
     }
 
     fn init(app: &mut App) -> Result<Self> {
-        let visibility_pass = pass::visibility::Visibility::new(&app.world)?;
+        let mut visibility_pass = pass::visibility::Visibility::new(&app.world)?;
+        // Sponza foliage is alpha-tested.
+        visibility_pass.set_depth_prepass(true);
 
         let shading_pass =
-            pass::shading::ShadingPass::new("shaders/shading.wgsl", &app.world, &app.gbuffer)?;
+            pass::shading::ShadingPass::new(Self::shading_shader(), &app.world, &app.gbuffer)?;
+        let sky_pass = pass::sky::Sky::new(&app.world)?;
+
+        let (width, height) = (app.surface_config.width, app.surface_config.height);
+        let hiz_pass = pass::hiz::HiZ::new(&app.world, &app.gbuffer, width, height)?;
+        let shading_rate_pass =
+            pass::shading_rate::ShadingRate::new(&app.world, &app.gbuffer, width, height)?;
+        let ssgi_pass = pass::ssgi::Ssgi::new(&app.world, &app.gbuffer, &hiz_pass, width, height)?;
+
+        let stats_pass = pass::stats::SceneStats::new(&app.world, &app.gbuffer)?;
 
         let postprocess_pass =
-            pass::postprocess::PostProcess::new(&app.world, "shaders/postprocess.wgsl")?;
+            pass::postprocess::PostProcess::new(&app.world, Self::postprocess_shader())?;
 
         let update_pass =
             pass::compute_update::ComputeUpdate::new(&app.world, "shaders/compute_update.wgsl")?;
 
-        let taa_pass = pass::taa::Taa::new(
-            &app.world,
-            &app.gbuffer,
-            app.surface_config.width,
-            app.surface_config.height,
-        )?;
+        let interpolation_pass = pass::interpolate::InstanceInterpolation::new(&app.world)?;
+        // Poses the skinned meshes once per frame, ahead of every built-in pass.
+        let skinning_pass = pass::skinning::Skinning::new(&app.world)?;
+        app.world
+            .get_mut::<PrePasses>()?
+            .add("Skinning", 0, move |world, encoder| {
+                skinning_pass.record(world, encoder, ())
+            });
+
+        let taa_pass = pass::taa::Taa::new(&app.world, &app.gbuffer, width, height)?;
         let moving_instances = app
             .device()
             .create_resizable_buffer(wgpu::BufferUsages::STORAGE);
@@ -52,12 +82,23 @@ impl Example for Model {
         Ok(Self {
             visibility_pass,
             shading_pass,
+            shading_rate_pass,
+            sky_pass,
+            hiz_pass,
+            ssgi_pass,
+            stats_pass,
+            editor: Editor::new(),
+            light_bounds: false,
             postprocess_pass,
+            picking_neutral: false,
             update_pass,
+            interpolation_pass,
             taa_pass,
 
             moving_instances,
             moving_instances_bind_group,
+
+            lights: vec![],
         })
     }
 
@@ -65,31 +106,39 @@ impl Example for Model {
         use std::f32::consts::PI;
         let mut instances = vec![];
 
-        app.world
+        self.lights = app
+            .world
             .get_mut::<LightPool>()?
-            .add_point_light(&[Light::new(vec3(0., 0.5, 0.), 10., vec3(1., 1., 1.))]);
+            .add_point_light(&[Light::new(vec3(0., 0.5, 0.), 10., vec3(1., 1., 1.))])?;
 
-        app.add_area_light(
+        let front = app.add_area_light(
             vec3(1., 1., 1.),
             7.,
             (5., 8.).into(),
             Mat4::from_translation(vec3(0., 10., 15.)) * Mat4::from_rotation_x(-PI / 4.),
         )?;
-        app.add_area_light(
+        let back = app.add_area_light(
             vec3(1., 1., 1.),
             7.,
             (5., 8.).into(),
             Mat4::from_translation(vec3(0., 10., -25.)) * Mat4::from_rotation_x(-3. * PI / 4.),
         )?;
+        self.lights.extend([front, back]);
+        self.lights.extend(
+            app.world
+                .get_mut::<LightPool>()?
+                .add_spot_light(&[SpotLight::new(
+                    vec3(0., 6., 0.),
+                    Vec3::NEG_Y,
+                    15.,
+                    vec3(1., 0.8, 0.6),
+                    PI / 8.,
+                    PI / 5.,
+                )])?,
+        );
 
-        let gltf_scene = GltfDocument::import(
-            app,
-            "assets/glTF-Sample-Models/2.0/Sponza/glTF/Sponza.gltf",
-            // "assets/glTF-Sample-Models/2.0/AntiqueCamera/glTF/AntiqueCamera.gltf",
-            // "assets/glTF-Sample-Models/2.0/Buggy/glTF-Binary/Buggy.glb",
-            // "assets/glTF-Sample-Models/2.0/FlightHelmet/glTF/FlightHelmet.gltf",
-            // "assets/glTF-Sample-Models/2.0/DamagedHelmet/glTF-Binary/DamagedHelmet.glb",
-        )?;
+        let samples = SampleAssets::from_env();
+        let gltf_scene = GltfDocument::import(app, samples.fetch(SampleModel::Sponza)?)?;
 
         instances.extend(gltf_scene.get_scene_instances(
             Mat4::from_rotation_y(PI / 2.)
@@ -97,31 +146,50 @@ impl Example for Model {
                 * Mat4::from_scale(Vec3::splat(3.)),
         ));
 
-        let helmet = GltfDocument::import(
-            app,
-            "assets/glTF-Sample-Models/2.0/DamagedHelmet/glTF-Binary/DamagedHelmet.glb",
-        )?;
+        let helmet = GltfDocument::import(app, samples.fetch(SampleModel::DamagedHelmet)?)?;
         instances.extend(helmet.get_scene_instances(
             Mat4::from_translation(vec3(0., 0., 9.)) * Mat4::from_scale(Vec3::splat(3.)),
         ));
 
+        // Dynamic so the editor can move them around.
         let gltf_ferris = GltfDocument::import(app, "assets/ferris3d_v1.0.glb")?;
-        instances.extend(gltf_ferris.get_scene_instances(
-            Mat4::from_translation(vec3(-3., -5.0, -4.)) * Mat4::from_scale(Vec3::splat(3.)),
-        ));
-        instances.extend(gltf_ferris.get_scene_instances(
-            Mat4::from_translation(vec3(2., -5.0, -2.)) * Mat4::from_scale(Vec3::splat(3.)),
-        ));
+        instances.extend(
+            gltf_ferris
+                .get_scene_instances(
+                    Mat4::from_translation(vec3(-3., -5.0, -4.))
+                        * Mat4::from_scale(Vec3::splat(3.)),
+                )
+                .into_iter()
+                .map(Instance::dynamic),
+        );
+        instances.extend(
+            gltf_ferris
+                .get_scene_instances(
+                    Mat4::from_translation(vec3(2., -5.0, -2.)) * Mat4::from_scale(Vec3::splat(3.)),
+                )
+                .into_iter()
+                .map(Instance::dynamic),
+        );
         gltf_ferris.get_scene_instances(
             Mat4::from_translation(vec3(2., -5.0, -2.)) * Mat4::from_scale(Vec3::splat(3.)),
         );
-        app.world.get_mut::<InstancePool>()?.add(&instances);
+
+        // Optional, only shown when it is already on disk.
+        if let Ok(cesium_man) = samples.offline(true).fetch(SampleModel::CesiumMan) {
+            let cesium_man = GltfDocument::import(app, cesium_man)?;
+            let id = cesium_man.instantiate_animated(
+                app,
+                Mat4::from_translation(vec3(0., -5., 4.)) * Mat4::from_scale(Vec3::splat(3.)),
+            )?;
+            app.world.get_mut::<AnimationPlayer>()?.play(id, Some(0));
+        }
+        app.world.get_mut::<InstancePool>()?.add(&instances)?;
 
         let sphere_mesh = make_uv_sphere(1.0, 10);
-        let sphere_mesh_id = app.get_mesh_pool_mut().add(sphere_mesh.as_ref());
+        let sphere_mesh_id = app.get_mesh_pool_mut().add(sphere_mesh.as_ref())?;
 
         let mut moving_instances = vec![];
-        let mut rng = rand::thread_rng();
+        let mut rng = app.world.get::<SceneRng>()?.fork(0);
         let num = 10;
         for i in 0..num {
             let r = 3.5;
@@ -142,7 +210,13 @@ impl Example for Model {
             ));
         }
 
-        let moving_instances_id = app.world.get_mut::<InstancePool>()?.add(&moving_instances);
+        let moving_instances_id: Vec<_> = app
+            .world
+            .get_mut::<InstancePool>()?
+            .add(&moving_instances)?
+            .iter()
+            .map(InstanceId::id)
+            .collect();
         self.moving_instances.push(&app.gpu, &moving_instances_id);
         self.moving_instances_bind_group = self
             .moving_instances
@@ -162,9 +236,21 @@ impl Example for Model {
         };
         self.update_pass
             .record(ctx.world, &mut ctx.encoder, resources);
+
+        if let Some(&point_light) = self.lights.first() {
+            let t = ctx.app_state.total_time as f32 * 0.5;
+            let light = Light::new(vec3(3. * t.cos(), 0.5, 3. * t.sin()), 10., Vec3::ONE);
+            let mut lights = ctx.world.unwrap_mut::<LightPool>();
+            if let Err(err) = lights.update_light(point_light, light) {
+                log::warn!("Failed to move the point light: {err}");
+            }
+        }
     }
 
     fn resize(&mut self, gpu: &Gpu, width: u32, height: u32) {
+        self.hiz_pass.resize(gpu.device(), width, height);
+        self.shading_rate_pass.resize(gpu.device(), width, height);
+        self.ssgi_pass.resize(gpu.device(), width, height);
         self.taa_pass.resize(gpu.device(), width, height);
     }
 
@@ -183,6 +269,7 @@ impl Example for Model {
     ) {
         let encoder = &mut ctx.encoder;
 
+        self.interpolation_pass.record(world, encoder, ());
         self.visibility_pass.record(
             world,
             encoder,
@@ -193,12 +280,65 @@ impl Example for Model {
             },
         );
 
-        self.shading_pass.record(
+        self.hiz_pass
+            .record(world, encoder, pass::hiz::HiZResource { gbuffer });
+
+        self.stats_pass.record(
+            world,
+            encoder,
+            pass::stats::SceneStatsResource {
+                gbuffer,
+                draw_cmd_buffer,
+                draw_cmd_bind_group,
+                ids: self.visibility_pass.vis_buffer_ids().as_deref(),
+            },
+        );
+
+        {
+            let mut lighting = encoder.scope("Lighting");
+            self.shading_rate_pass.record(
+                world,
+                &mut lighting,
+                pass::shading_rate::ShadingRateResource {
+                    gbuffer,
+                    view_target,
+                },
+            );
+            self.shading_pass.record(
+                world,
+                &mut lighting,
+                pass::shading::ShadingResource {
+                    gbuffer,
+                    view_target,
+                    shading_rate: Some(&self.shading_rate_pass),
+                },
+            );
+            self.shading_rate_pass.reconstruct(
+                world,
+                &mut lighting,
+                pass::shading_rate::ShadingRateResource {
+                    gbuffer,
+                    view_target,
+                },
+            );
+            self.sky_pass.record(
+                world,
+                &mut lighting,
+                pass::sky::SkyResource {
+                    gbuffer,
+                    view_target,
+                },
+            );
+        }
+
+        self.ssgi_pass.record(
             world,
             encoder,
-            pass::shading::ShadingResource {
+            pass::ssgi::SsgiResource {
                 gbuffer,
+                hiz: &self.hiz_pass,
                 view_target,
+                width_height: (width, height),
             },
         );
 
@@ -215,17 +355,250 @@ impl Example for Model {
         self.postprocess_pass.record(
             world,
             encoder,
-            pass::postprocess::PostProcessResource { view_target },
+            pass::postprocess::PostProcessResource {
+                view_target,
+                gbuffer,
+            },
         );
 
+        let mut white_balance = self.postprocess_pass.white_balance();
+        let mut depth_prepass = self.visibility_pass.depth_prepass();
+        let mut vis_buffer = self.visibility_pass.vis_buffer();
+        let mut hiz_culling = self.visibility_pass.hiz_culling();
+        let mut meshlet_culling = self.visibility_pass.meshlet_culling();
+        let mut lod_settings = self.visibility_pass.lod_settings();
+        let mut draw_sorting = self.visibility_pass.draw_sorting();
+        let mut occlusion = world.unwrap_mut::<SoftwareOcclusion>();
+        let mut software_occlusion = occlusion.enabled();
+        let mut ssgi_enabled = self.ssgi_pass.enabled();
+        let mut ssgi = self.ssgi_pass.settings();
+        let mut taa = self.taa_pass.settings();
+        let mut shading_rate_enabled = self.shading_rate_pass.enabled();
+        let mut shading_rate = self.shading_rate_pass.settings();
+        let mut sky = self.sky_pass.settings();
+        let mut lights = world.unwrap_mut::<LightPool>();
+        let mut sun = lights.sun();
+        let mut lights_enabled: Vec<_> = self
+            .lights
+            .iter()
+            .map(|&id| lights.is_light_enabled(id))
+            .collect();
+        let sun_angles = (
+            sun.direction.y.asin().to_degrees(),
+            sun.direction.z.atan2(sun.direction.x).to_degrees(),
+        );
+        let (mut sun_elevation, mut sun_azimuth) = sun_angles;
+        let mut stats_enabled = self.stats_pass.enabled();
+        let stats = self.stats_pass.report();
+        let picking_neutral = &mut self.picking_neutral;
+        let editor = &mut self.editor;
+        let light_bounds = &mut self.light_bounds;
+        let mut timeline = world.unwrap_mut::<Timeline>();
+        let mut texture_lod = world.unwrap_mut::<TextureLod>();
+        let mut mip_bias = texture_lod.bias();
+        let mut imposters = world.unwrap_mut::<Imposters>();
+        let mut imposter_distance = imposters.distance();
+        let live_params = world.unwrap::<LiveParams>();
+        let mut asset_browser = world.unwrap_mut::<AssetBrowser>();
+        let mut debug_view = world.unwrap_mut::<pass::postprocess::DebugView>();
         ctx.ui(|egui_ctx| {
+            egui::Window::new("Assets")
+                .default_open(false)
+                .show(egui_ctx, |ui| asset_browser.ui(ui));
             egui::Window::new("debug").show(egui_ctx, |ui| {
                 ui.label(format!(
                     "Fps: {:.04?}",
                     Duration::from_secs_f64(ctx.app_state.dt)
                 ));
+                ui.checkbox(&mut depth_prepass, "Depth Pre-Pass");
+                ui.checkbox(&mut vis_buffer, "Visibility Buffer");
+                ui.checkbox(&mut hiz_culling, "Hi-Z Occlusion Culling");
+                ui.checkbox(&mut meshlet_culling, "Meshlet Culling");
+                ui.checkbox(&mut draw_sorting, "Sort Draws by Material");
+                ui.checkbox(&mut editor.enabled, "Scene Editor");
+                ui.checkbox(light_bounds, "Light Bounds");
+                ui.add(
+                    egui::Slider::new(&mut lod_settings.pixel_error, 0.0..=8.0)
+                        .text("LOD Pixel Error"),
+                );
+                ui.add(egui::Slider::new(&mut mip_bias, -2.0..=2.0).text("Mip Bias"));
+                egui::ComboBox::from_label("Debug View (F5)")
+                    .selected_text(debug_view.name())
+                    .show_ui(ui, |ui| {
+                        for view in pass::postprocess::DebugView::ALL {
+                            ui.selectable_value(&mut *debug_view, view, view.name());
+                        }
+                    });
+                if !imposters.is_empty() {
+                    ui.add(
+                        egui::Slider::new(&mut imposter_distance, 1.0..=500.0)
+                            .text("Imposter Distance (radii)"),
+                    );
+                }
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut software_occlusion, "Software Occlusion");
+                    ui.label(format!("{} occluded", occlusion.occluded_count()));
+                });
+                ui.collapsing("SSGI", |ui| {
+                    ui.checkbox(&mut ssgi_enabled, "Enabled");
+                    ui.add(egui::Slider::new(&mut ssgi.intensity, 0.0..=4.0).text("Intensity"));
+                    ui.add(egui::Slider::new(&mut ssgi.radius, 0.1..=20.0).text("Radius"));
+                    ui.add(egui::Slider::new(&mut ssgi.thickness, 0.01..=2.0).text("Thickness"));
+                    ui.add(egui::Slider::new(&mut ssgi.ray_count, 1..=8).text("Rays"));
+                });
+                ui.collapsing("Shading Rate", |ui| {
+                    ui.checkbox(&mut shading_rate_enabled, "Enabled");
+                    ui.add(
+                        egui::Slider::new(&mut shading_rate.luma_threshold, 0.0..=0.5)
+                            .text("Luma Threshold"),
+                    );
+                    ui.add(
+                        egui::Slider::new(&mut shading_rate.normal_threshold, 0.0..=0.1)
+                            .text("Normal Threshold"),
+                    );
+                });
+                ui.collapsing("Scene Stats", |ui| {
+                    ui.checkbox(&mut stats_enabled, "Enabled");
+                    let top = |values: &[u32]| {
+                        let mut ids: Vec<_> =
+                            (0..values.len()).filter(|&i| values[i] > 0).collect();
+                        ids.sort_by_key(|&i| std::cmp::Reverse(values[i]));
+                        ids.truncate(8);
+                        ids
+                    };
+                    ui.label("Materials by pixels");
+                    for i in top(&stats.material_pixels) {
+                        ui.label(format!(
+                            "#{i}: {} px, {} tris",
+                            stats.material_pixels[i], stats.material_triangles[i]
+                        ));
+                    }
+                    ui.label("Instances by triangles");
+                    for i in top(&stats.instance_triangles) {
+                        match stats.instance_pixels.get(i).filter(|&&px| px > 0) {
+                            Some(px) => ui.label(format!(
+                                "#{i}: {} tris, {px} px",
+                                stats.instance_triangles[i]
+                            )),
+                            None => ui.label(format!("#{i}: {} tris", stats.instance_triangles[i])),
+                        };
+                    }
+                });
+                ui.collapsing("Lights", |ui| {
+                    for (i, (id, enabled)) in
+                        self.lights.iter().zip(&mut lights_enabled).enumerate()
+                    {
+                        ui.checkbox(enabled, format!("#{i}: {:?} Light", id.kind()));
+                    }
+                });
+                ui.collapsing("Sky", |ui| {
+                    ui.add(
+                        egui::Slider::new(&mut sun_elevation, -10.0..=90.0).text("Sun Elevation"),
+                    );
+                    ui.add(egui::Slider::new(&mut sun_azimuth, -180.0..=180.0).text("Sun Azimuth"));
+                    ui.add(egui::Slider::new(&mut sun.intensity, 0.0..=10.0).text("Sun Intensity"));
+                    ui.add(egui::Slider::new(&mut sky.turbidity, 2.0..=10.0).text("Turbidity"));
+                    ui.add(egui::Slider::new(&mut sky.exposure, 0.0..=0.5).text("Exposure"));
+                });
+                ui.collapsing("TAA", |ui| {
+                    ui.add(egui::Slider::new(&mut taa.blend, 0.01..=1.0).text("Blend"));
+                    ui.add(egui::Slider::new(&mut taa.clamp_gamma, 0.5..=4.0).text("Clamp Gamma"));
+                    ui.add(egui::Slider::new(&mut taa.sharpness, 0.0..=1.0).text("Sharpness"));
+                    ui.add(
+                        egui::Slider::new(&mut taa.anti_flicker, 0.0..=1.0).text("Anti-Flicker"),
+                    );
+                    if ui.button("Reset").clicked() {
+                        taa = Default::default();
+                    }
+                });
+                ui.collapsing("White Balance", |ui| {
+                    ui.add(
+                        egui::Slider::new(&mut white_balance.temperature, -1.0..=1.0)
+                            .text("Temperature"),
+                    );
+                    ui.add(egui::Slider::new(&mut white_balance.tint, -1.0..=1.0).text("Tint"));
+                    ui.horizontal(|ui| {
+                        ui.toggle_value(picking_neutral, "Pick Neutral");
+                        if ui.button("Reset").clicked() {
+                            white_balance = Default::default();
+                        }
+                    });
+                    if *picking_neutral {
+                        ui.label("Click on a pixel that should be gray");
+                    }
+                });
+                ui.collapsing("Timeline", |ui| {
+                    timeline.ui(ui, &ctx.app_state.camera, &live_params);
+                });
             });
+            editor.ui(egui_ctx, &ctx.app_state.camera, world);
         });
+        drop(stats);
+        texture_lod.set_bias(mip_bias);
+        if imposter_distance != imposters.distance() {
+            imposters.set_distance(imposter_distance);
+        }
+        self.stats_pass.set_enabled(stats_enabled);
+        self.visibility_pass.set_depth_prepass(depth_prepass);
+        occlusion.set_enabled(software_occlusion);
+        if vis_buffer != self.visibility_pass.vis_buffer() {
+            self.visibility_pass.set_vis_buffer(vis_buffer);
+        }
+        if hiz_culling != self.visibility_pass.hiz_culling() {
+            self.visibility_pass.set_hiz_culling(hiz_culling);
+        }
+        if meshlet_culling != self.visibility_pass.meshlet_culling() {
+            self.visibility_pass.set_meshlet_culling(meshlet_culling);
+        }
+        if draw_sorting != self.visibility_pass.draw_sorting() {
+            self.visibility_pass.set_draw_sorting(draw_sorting);
+        }
+        if lod_settings != self.visibility_pass.lod_settings() {
+            self.visibility_pass.set_lod_settings(lod_settings);
+        }
+        if (sun_elevation, sun_azimuth) != sun_angles {
+            let (elevation, azimuth) = (sun_elevation.to_radians(), sun_azimuth.to_radians());
+            sun.direction = vec3(
+                elevation.cos() * azimuth.cos(),
+                elevation.sin(),
+                elevation.cos() * azimuth.sin(),
+            );
+        }
+        if sun != lights.sun() {
+            lights.set_sun(sun);
+        }
+        for (&id, enabled) in self.lights.iter().zip(lights_enabled) {
+            if enabled != lights.is_light_enabled(id) {
+                lights.set_light_enabled(id, enabled).ok();
+            }
+        }
+        if self.light_bounds {
+            let mut debug_draw = world.unwrap_mut::<DebugDraw>();
+            for &id in &self.lights {
+                if let Some(AnyLight::Point(light)) = lights.light(id) {
+                    debug_draw.sphere(light.position, light.radius, light.color);
+                }
+            }
+        }
+        if sky != self.sky_pass.settings() {
+            self.sky_pass.set_settings(world.queue(), sky);
+        }
+        if taa != self.taa_pass.settings() {
+            self.taa_pass.set_settings(world.queue(), taa);
+        }
+        self.shading_rate_pass.set_enabled(shading_rate_enabled);
+        if shading_rate != self.shading_rate_pass.settings() {
+            self.shading_rate_pass
+                .set_settings(world.queue(), shading_rate);
+        }
+        self.ssgi_pass.set_enabled(ssgi_enabled);
+        if ssgi != self.ssgi_pass.settings() {
+            self.ssgi_pass.set_settings(world.queue(), ssgi);
+        }
+        if white_balance != self.postprocess_pass.white_balance() {
+            self.postprocess_pass
+                .set_white_balance(world.queue(), white_balance);
+        }
     }
 }
 
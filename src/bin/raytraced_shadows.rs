@@ -13,13 +13,14 @@ impl Example for Shadows {
         "Raytraced Shadows"
     }
 
+    fn shading_shader() -> &'static str {
+        "src/bin/raytraced_shadows.wgsl"
+    }
+
     fn init(app: &mut App) -> Result<Self> {
         let visibility_pass = pass::visibility::Visibility::new(&app.world)?;
-        let shading_pass = pass::shading::ShadingPass::new(
-            "src/bin/raytraced_shadows.wgsl",
-            &app.world,
-            &app.gbuffer,
-        )?;
+        let shading_pass =
+            pass::shading::ShadingPass::new(Self::shading_shader(), &app.world, &app.gbuffer)?;
 
         Ok(Self {
             visibility_pass,
@@ -30,7 +31,7 @@ impl Example for Shadows {
     fn setup_scene(&mut self, app: &mut App) -> Result<()> {
         app.world
             .get_mut::<LightPool>()?
-            .add_point_light(&[Light::new(vec3(-3., 8.5, 10.), 100., vec3(1., 1., 1.))]);
+            .add_point_light(&[Light::new(vec3(-3., 8.5, 10.), 100., vec3(1., 1., 1.))])?;
         let mut instances = vec![];
 
         instances.push(Instance::new(
@@ -64,7 +65,7 @@ impl Example for Shadows {
             Mat4::from_translation(vec3(-3., 1.0, -4.)) * Mat4::from_scale(Vec3::splat(3.)),
         ));
 
-        app.get_instance_pool_mut().add(&instances);
+        app.get_instance_pool_mut().add(&instances)?;
 
         Ok(())
     }
@@ -102,6 +103,7 @@ impl Example for Shadows {
             pass::shading::ShadingResource {
                 gbuffer,
                 view_target,
+                shading_rate: None,
             },
         );
 
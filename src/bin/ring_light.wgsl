@@ -1,16 +1,16 @@
 #import "shared.wgsl"
-#import "utils/encoding.wgsl"
+#import <voidin/packing.wgsl>
 #import "utils/ltc.wgsl"
-#import "utils/uv.wgsl"
-#import "utils/bvh.wgsl"
+#import <voidin/camera.wgsl>
+#import <voidin/bvh.wgsl>
+#import "utils/gbuffer.wgsl"
 
 @group(0) @binding(0) var<uniform> global: Globals;
 @group(0) @binding(1) var<uniform> camera: Camera;
 
-@group(1) @binding(0) var t_normal_uv: texture_2d<u32>;
-@group(1) @binding(1) var t_material: texture_2d<u32>;
-@group(1) @binding(2) var t_depth: texture_depth_2d;
-@group(1) @binding(3) var t_sampler: sampler;
+@group(1) @binding(0) var t_gbuffer: texture_2d<u32>;
+@group(1) @binding(1) var t_depth: texture_depth_2d;
+@group(1) @binding(2) var t_sampler: sampler;
 
 @group(2) @binding(0) var texture_array: binding_array<texture_2d<f32>>;
 @group(2) @binding(1) var tex_sampler: sampler;
@@ -348,21 +348,21 @@ fn attenuation(max_intensity: f32, falloff: f32, dist: f32, radius: f32) -> f32
 
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    let tex_dims = vec2f(textureDimensions(t_normal_uv));
+    let tex_dims = vec2f(textureDimensions(t_gbuffer));
     let load_uv = vec2<u32>(in.uv * tex_dims);
 
     var depth = textureLoad(t_depth, load_uv, 0);
-    let norm_uv_tex = textureLoad(t_normal_uv, load_uv, 0);
-    var material_id = textureLoad(t_material, load_uv, 0).r;
+    let gbuffer = unpack_gbuffer(textureLoad(t_gbuffer, load_uv, 0).xy);
+    var material_id = gbuffer.material_id;
 
     let material = materials[material_id];
-    let uv = unpack2x16float(norm_uv_tex.y);
+    let uv = gbuffer.uv;
     let albedo = textureSample(texture_array[material.albedo], t_sampler, uv);
     let emissive = textureSample(texture_array[material.emissive], t_sampler, uv).rgb;
     let metallic_roughness = textureSample(texture_array[material.metallic_roughness], t_sampler, uv);
 
     let pos = world_position_from_depth(in.uv, depth, camera.clip_to_world);
-    let nor = decode_octahedral_32(norm_uv_tex.x);
+    let nor = gbuffer.normal;
     let rd = normalize(camera.position.xyz - pos);
 
     let width = 6.;
@@ -0,0 +1,196 @@
+use std::time::Duration;
+
+use color_eyre::{eyre::eyre, Result};
+use voidin::*;
+
+/// Shows one of the built-in test scenes, picked by the first argument, like
+/// `cargo run --bin test_scene -- furnace`. The path tracer can stand in for the raster
+/// passes to compare against, and ReSTIR can take over the point and area lights.
+struct Demo {
+    scene: TestScene,
+    visibility_pass: pass::visibility::Visibility,
+    shading_pass: pass::shading::ShadingPass,
+    ambient_shading_pass: pass::shading::ShadingPass,
+    restir_pass: pass::restir::RestirDi,
+    restir: bool,
+    path_trace_pass: pass::pathtrace::PathTrace,
+    path_traced: bool,
+}
+
+fn scene_from_args() -> Result<TestScene> {
+    let Some(arg) = std::env::args().nth(1) else {
+        return Ok(TestScene::CornellBox);
+    };
+    TestScene::ALL
+        .into_iter()
+        .find(|scene| scene.name().replace(' ', "").eq_ignore_ascii_case(&arg))
+        .ok_or_else(|| {
+            let names: Vec<_> = TestScene::ALL
+                .map(|scene| scene.name().replace(' ', ""))
+                .into();
+            eyre!("Unknown test scene {arg}, pick one of {names:?}")
+        })
+}
+
+impl Example for Demo {
+    fn name() -> &'static str {
+        "Test Scene"
+    }
+
+    fn init(app: &mut App) -> Result<Self> {
+        let (width, height) = (app.surface_config.width, app.surface_config.height);
+        Ok(Self {
+            scene: scene_from_args()?,
+            visibility_pass: pass::visibility::Visibility::new(&app.world)?,
+            shading_pass: pass::shading::ShadingPass::new(
+                Self::shading_shader(),
+                &app.world,
+                &app.gbuffer,
+            )?,
+            ambient_shading_pass: pass::shading::ShadingPass::without_local_lights(
+                Self::shading_shader(),
+                &app.world,
+                &app.gbuffer,
+            )?,
+            restir_pass: pass::restir::RestirDi::new(&app.world, &app.gbuffer, width, height)?,
+            restir: false,
+            path_trace_pass: pass::pathtrace::PathTrace::new(&app.world, width, height)?,
+            path_traced: false,
+        })
+    }
+
+    fn setup_scene(&mut self, app: &mut App) -> Result<()> {
+        app.load_test_scene(self.scene)
+    }
+
+    fn update(&mut self, _ctx: UpdateContext) {}
+
+    fn resize(&mut self, gpu: &Gpu, width: u32, height: u32) {
+        self.path_trace_pass.resize(gpu.device(), width, height);
+        self.restir_pass.resize(gpu.device(), width, height);
+    }
+
+    fn render(
+        &mut self,
+        mut ctx @ RenderContext {
+            world,
+            gbuffer,
+            view_target,
+            draw_cmd_bind_group,
+            draw_cmd_buffer,
+            ..
+        }: RenderContext,
+    ) {
+        let encoder = &mut ctx.encoder;
+
+        if self.path_traced {
+            self.path_trace_pass.record(
+                world,
+                encoder,
+                pass::pathtrace::PathTraceResource {
+                    view_target,
+                    camera: &ctx.app_state.camera,
+                },
+            );
+        } else {
+            self.visibility_pass.record(
+                world,
+                encoder,
+                pass::visibility::VisibilityResource {
+                    gbuffer,
+                    draw_cmd_buffer,
+                    draw_cmd_bind_group,
+                },
+            );
+
+            if self.restir {
+                self.ambient_shading_pass.record(
+                    world,
+                    encoder,
+                    pass::shading::ShadingResource {
+                        gbuffer,
+                        view_target,
+                        shading_rate: None,
+                    },
+                );
+                self.restir_pass.record(
+                    world,
+                    encoder,
+                    pass::restir::RestirResource {
+                        gbuffer,
+                        view_target,
+                    },
+                );
+            } else {
+                self.shading_pass.record(
+                    world,
+                    encoder,
+                    pass::shading::ShadingResource {
+                        gbuffer,
+                        view_target,
+                        shading_rate: None,
+                    },
+                );
+            }
+        }
+
+        let scene = self.scene;
+        let mut path_traced = self.path_traced;
+        let mut settings = self.path_trace_pass.settings();
+        let samples = self.path_trace_pass.samples();
+        let mut restir = self.restir;
+        let mut restir_settings = self.restir_pass.settings();
+        ctx.ui(|egui_ctx| {
+            egui::Window::new("debug").show(egui_ctx, |ui| {
+                ui.label(scene.name());
+                ui.label(format!(
+                    "Fps: {:.04?}",
+                    Duration::from_secs_f64(ctx.app_state.dt)
+                ));
+                ui.checkbox(&mut path_traced, "Path Traced");
+                if path_traced {
+                    ui.add(egui::Slider::new(&mut settings.max_bounces, 0..=16).text("Bounces"));
+                    ui.add(
+                        egui::Slider::new(&mut settings.max_samples, 1..=65536)
+                            .logarithmic(true)
+                            .text("Max Samples"),
+                    );
+                    ui.label(format!("Samples: {samples}"));
+                } else {
+                    ui.checkbox(&mut restir, "ReSTIR");
+                    if restir {
+                        ui.add(
+                            egui::Slider::new(&mut restir_settings.candidates, 1..=64)
+                                .text("Candidates"),
+                        );
+                        ui.add(
+                            egui::Slider::new(&mut restir_settings.spatial_samples, 0..=16)
+                                .text("Spatial Samples"),
+                        );
+                        ui.add(
+                            egui::Slider::new(&mut restir_settings.max_history, 0.0..=50.0)
+                                .text("Max History"),
+                        );
+                    }
+                }
+            });
+        });
+        if path_traced && !self.path_traced {
+            self.path_trace_pass.reset();
+        }
+        self.path_traced = path_traced;
+        self.path_trace_pass.set_settings(settings);
+        self.restir = restir;
+        if restir_settings != self.restir_pass.settings() {
+            self.restir_pass
+                .set_settings(world.queue(), restir_settings);
+        }
+    }
+}
+
+fn main() -> Result<()> {
+    let window = WindowBuilder::new();
+
+    let camera = Camera::new(vec3(0., 5., 18.), 0., 0.);
+    run::<Demo>(window, camera)
+}